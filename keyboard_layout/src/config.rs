@@ -1,10 +1,17 @@
 use crate::keyboard::KeyboardYAML;
-use crate::neo_layout_generator::BaseLayoutYAML;
+use crate::keycap_group::KeycapGroupError;
+use crate::layout::ModifierLocation;
+use crate::neo_layout_generator::{BaseLayoutYAML, LayoutError};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::Deserialize;
+use serde_yaml::Value;
 use std::error::Error;
-use std::{fs::File, str::FromStr};
+use std::path::Path;
+use std::{fs, str::FromStr};
+
+/// Maximum depth of chained `extends`
+const MAX_EXTENDS_DEPTH: usize = 16;
 
 #[derive(Deserialize, Debug)]
 pub struct LayoutConfig {
@@ -12,10 +19,68 @@ pub struct LayoutConfig {
     pub base_layout: BaseLayoutYAML,
 }
 
+/// Merge `overlay` into `base`. Mappings are merged recursively, all other values
+/// (including lists) are replaced.
+fn merge_yaml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (k, v) in overlay {
+                match base.get_mut(&k) {
+                    Some(base_v) => merge_yaml(base_v, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Read a YAML file and resolve its `extends` entry (if any).
+///
+/// A file containing `extends: <path>` only needs to specify the entries that differ from
+/// the extended file. The path is relative to the directory of the extending file.
+fn read_yaml_with_extends(filename: &Path, depth: usize) -> Result<Value> {
+    if depth > MAX_EXTENDS_DEPTH {
+        return Err(anyhow!(
+            "Too many nested `extends` (circular reference?) in {:?}",
+            filename
+        ));
+    }
+
+    let mut value: Value = serde_yaml::from_str(&fs::read_to_string(filename)?)?;
+
+    let extends = match &mut value {
+        Value::Mapping(m) => m.remove("extends"),
+        _ => None,
+    };
+
+    match extends {
+        Some(Value::String(extends)) => {
+            let base_filename = filename
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(extends);
+            let mut base = read_yaml_with_extends(&base_filename, depth + 1)?;
+            merge_yaml(&mut base, value);
+            Ok(base)
+        }
+        Some(_) => Err(anyhow!("`extends` in {:?} needs to be a path", filename)),
+        None => Ok(value),
+    }
+}
+
+/// Read a layout config file and return it as YAML string with all `extends` resolved.
+pub fn resolved_yaml(filename: &str) -> Result<String> {
+    let value = read_yaml_with_extends(Path::new(filename), 0)?;
+    Ok(serde_yaml::to_string(&value)?)
+}
+
 impl LayoutConfig {
     pub fn from_yaml(filename: &str) -> Result<Self> {
-        let f = File::open(filename)?;
-        let cfg: LayoutConfig = serde_yaml::from_reader(f)?;
+        let value = read_yaml_with_extends(Path::new(filename), 0)?;
+        let cfg: LayoutConfig = serde_yaml::from_value(value)?;
         cfg.validate()?;
 
         Ok(cfg)
@@ -24,6 +89,66 @@ impl LayoutConfig {
     pub fn validate(&self) -> Result<()> {
         self.keyboard.validate()?;
         self.base_layout.validate()?;
+
+        let geometry = self.keyboard.geometry()?;
+        self.base_layout
+            .validate_keys(&self.keyboard.row_lengths(), geometry.keys())?;
+
+        for p in self.base_layout.unlocked_positions.iter() {
+            if geometry.key_at(*p).is_none() {
+                return Err(LayoutError::UnknownUnlockedPosition(*p).into());
+            }
+        }
+
+        // blocked keys can neither hold symbols nor act as modifiers
+        for (layers, key) in self.base_layout.keys.iter().flatten().zip(geometry.keys()) {
+            let symbols: String = layers.iter().filter_map(|l| l.chars().next()).collect();
+            if key.is_blocked && !symbols.is_empty() && !self.base_layout.drop_blocked_symbols {
+                return Err(LayoutError::BlockedKeySymbols(key.matrix_position, symbols).into());
+            }
+        }
+        for location in self
+            .base_layout
+            .modifiers
+            .iter()
+            .flat_map(|m| m.values())
+            .flat_map(|m| m.iter())
+        {
+            if let ModifierLocation::Position(p) = location {
+                if geometry
+                    .key_at(*p)
+                    .map_or(false, |i| geometry.key(i).is_blocked)
+                {
+                    return Err(LayoutError::BlockedModifier(*p).into());
+                }
+            }
+        }
+
+        // the symbols and keys of keycap groups need to be permutable symbols and non-fixed keys
+        // of the keyboard
+        let fixed_keys = self.base_layout.flat_fixed_keys(geometry.keys());
+        let keys = self.base_layout.keys.concat();
+        for group in self.base_layout.keycap_groups.iter() {
+            for c in group.symbols.chars() {
+                let is_permutable = keys.iter().zip(fixed_keys.iter()).any(|(layers, fixed)| {
+                    !fixed && layers.first().and_then(|l| l.chars().next()) == Some(c)
+                });
+                if !is_permutable {
+                    return Err(KeycapGroupError::UnknownSymbol(group.symbols.clone(), c).into());
+                }
+            }
+            for p in group.positions.iter() {
+                let is_permutable = geometry
+                    .keys()
+                    .iter()
+                    .zip(fixed_keys.iter())
+                    .any(|(key, fixed)| !fixed && key.matrix_position == *p);
+                if !is_permutable {
+                    return Err(KeycapGroupError::UnknownPosition(group.symbols.clone(), *p).into());
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -37,3 +162,86 @@ impl FromStr for LayoutConfig {
         Ok(cfg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::MatrixPosition;
+    use crate::neo_layout_generator::BaseLayoutError;
+
+    fn mismatch(fixture: &str) -> BaseLayoutError {
+        let err = LayoutConfig::from_yaml(&format!("tests/fixtures/{}.yml", fixture)).unwrap_err();
+        err.downcast_ref::<BaseLayoutError>()
+            .unwrap_or_else(|| panic!("Unexpected error: {}", err))
+            .clone()
+    }
+
+    #[test]
+    fn matching_base_layout() {
+        assert!(LayoutConfig::from_yaml("tests/fixtures/two_rows.yml").is_ok());
+    }
+
+    #[test]
+    fn row_number() {
+        assert_eq!(
+            mismatch("mismatch_row_number"),
+            BaseLayoutError::RowNumber {
+                attribute: "keys",
+                reference: "matrix_positions",
+                expected: 2,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn row_length() {
+        let err = mismatch("mismatch_row_length");
+        assert_eq!(
+            err,
+            BaseLayoutError::RowLength {
+                attribute: "keys",
+                reference: "matrix_positions",
+                row: 0,
+                expected: 3,
+                found: 4
+            }
+        );
+        assert!(err.to_string().contains("Row 0 of `keys` has 4 entries"));
+
+        assert_eq!(
+            mismatch("mismatch_fixed_keys"),
+            BaseLayoutError::RowLength {
+                attribute: "fixed_keys",
+                reference: "keys",
+                row: 1,
+                expected: 3,
+                found: 2
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_symbol() {
+        assert_eq!(
+            mismatch("mismatch_duplicate_symbol"),
+            BaseLayoutError::DuplicateSymbol {
+                layer: 1,
+                symbol: 'A',
+                first: MatrixPosition(0, 0),
+                second: MatrixPosition(1, 0)
+            }
+        );
+    }
+
+    #[test]
+    fn permutable_modifier() {
+        assert_eq!(
+            mismatch("mismatch_permutable_modifier"),
+            BaseLayoutError::PermutableModifier {
+                layer: 1,
+                position: MatrixPosition(2, 1)
+            }
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! This module provides the [`KeyGeometry`], a validated collection of the physical properties
+//! of all keys on a keyboard. It is the single source of truth for questions like
+//! "which finger presses this key?" or "which key is the mirror image of this one?".
+
+use crate::key::{Finger, Hand, Key, MatrixPosition, PhysicalPosition, Position};
+use crate::keyboard::{KeyIndex, KeyboardError};
+
+/// A validated list of [`Key`]s with typed accessors for their geometric properties.
+#[derive(Clone, Debug)]
+pub struct KeyGeometry {
+    keys: Vec<Key>,
+    mirrors: Vec<Option<KeyIndex>>,
+}
+
+impl KeyGeometry {
+    /// Generate a [`KeyGeometry`] from a list of keys.
+    ///
+    /// Fails if two keys share the same matrix position or the same 2D position.
+    pub fn new(keys: Vec<Key>) -> Result<Self, KeyboardError> {
+        if keys.len() > KeyIndex::MAX as usize + 1 {
+            return Err(KeyboardError::TooManyKeys(keys.len()));
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i]
+                .iter()
+                .any(|k| k.matrix_position == key.matrix_position)
+            {
+                return Err(KeyboardError::DuplicateMatrixPositions);
+            }
+            if keys[..i].iter().any(|k| k.position == key.position) {
+                return Err(KeyboardError::DuplicatePositions);
+            }
+        }
+
+        let mirrors = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                keys.iter()
+                    .enumerate()
+                    .find(|(j, k)| {
+                        *j != i && k.hand != key.hand && k.symmetry_index == key.symmetry_index
+                    })
+                    .map(|(j, _)| j as KeyIndex)
+            })
+            .collect();
+
+        Ok(Self { keys, mirrors })
+    }
+
+    /// All keys in the order of the keyboard configuration
+    #[inline(always)]
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Number of keys on the keyboard
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the keyboard has no keys at all
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Indices of all keys
+    #[inline(always)]
+    pub fn indices(&self) -> impl Iterator<Item = KeyIndex> {
+        (0..self.keys.len()).map(|i| i as KeyIndex)
+    }
+
+    /// The [`Key`] with given index
+    #[inline(always)]
+    pub fn key(&self, key: KeyIndex) -> &Key {
+        &self.keys[key as usize]
+    }
+
+    /// Hand of the finger used to press the key
+    #[inline(always)]
+    pub fn hand(&self, key: KeyIndex) -> Hand {
+        self.key(key).hand
+    }
+
+    /// Finger used to press the key
+    #[inline(always)]
+    pub fn finger(&self, key: KeyIndex) -> Finger {
+        self.key(key).finger
+    }
+
+    /// Row index of the key
+    #[inline(always)]
+    pub fn row(&self, key: KeyIndex) -> u8 {
+        self.key(key).matrix_position.1
+    }
+
+    /// Column index of the key
+    #[inline(always)]
+    pub fn column(&self, key: KeyIndex) -> u8 {
+        self.key(key).matrix_position.0
+    }
+
+    /// Row and column position of the key
+    #[inline(always)]
+    pub fn matrix_position(&self, key: KeyIndex) -> MatrixPosition {
+        self.key(key).matrix_position
+    }
+
+    /// 2D position of the key
+    #[inline(always)]
+    pub fn coord(&self, key: KeyIndex) -> Position {
+        self.key(key).position
+    }
+
+    /// Physical position of the key in millimeters
+    #[inline(always)]
+    pub fn physical_coord(&self, key: KeyIndex) -> PhysicalPosition {
+        self.key(key).physical_position
+    }
+
+    /// Whether the key is pressed by a thumb
+    #[inline(always)]
+    pub fn is_thumb(&self, key: KeyIndex) -> bool {
+        self.key(key).finger == Finger::Thumb
+    }
+
+    /// The symmetrical key on the other hand (if there is one)
+    #[inline(always)]
+    pub fn mirror(&self, key: KeyIndex) -> Option<KeyIndex> {
+        self.mirrors[key as usize]
+    }
+
+    /// Index of the key at given matrix position (if there is one)
+    pub fn key_at(&self, matrix_position: MatrixPosition) -> Option<KeyIndex> {
+        self.keys
+            .iter()
+            .position(|k| k.matrix_position == matrix_position)
+            .map(|i| i as KeyIndex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::KeyboardYAML;
+
+    const KEYBOARD: &str = "
+matrix_positions:
+  - [[0, 0], [1, 0], [2, 0], [3, 0]]
+  - [[1, 1], [2, 1]]
+positions:
+  - [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]
+  - [[1.5, 1.0], [2.5, 1.0]]
+hands:
+  - [Left, Left, Right, Right]
+  - [Left, Right]
+fingers:
+  - [Middle, Index, Index, Middle]
+  - [Thumb, Thumb]
+key_costs:
+  - [2.0, 1.0, 1.0, 2.0]
+  - [0.5, 0.5]
+symmetries:
+  - [1, 2, 2, 1]
+  - [3, 4]
+unbalancing_positions:
+  - [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0]]
+  - [[0.0, 0.0], [0.0, 0.0]]
+finger_resting_positions: {}
+plot_template: ''
+plot_template_short: ''
+";
+
+    fn geometry(yaml: &str) -> Result<KeyGeometry, KeyboardError> {
+        let k: KeyboardYAML = serde_yaml::from_str(yaml).unwrap();
+        k.geometry()
+    }
+
+    #[test]
+    fn accessors() {
+        let g = geometry(KEYBOARD).unwrap();
+
+        assert_eq!(g.len(), 6);
+        assert_eq!(g.hand(0), Hand::Left);
+        assert_eq!(g.hand(3), Hand::Right);
+        assert_eq!(g.finger(1), Finger::Index);
+        assert_eq!(g.row(4), 1);
+        assert_eq!(g.column(5), 2);
+        assert_eq!(g.coord(2), Position(2.0, 0.0));
+        assert!(g.is_thumb(4));
+        assert!(!g.is_thumb(0));
+        assert_eq!(g.key_at(MatrixPosition(2, 1)), Some(5));
+        assert_eq!(g.key_at(MatrixPosition(9, 9)), None);
+    }
+
+    #[test]
+    fn mirrors() {
+        let g = geometry(KEYBOARD).unwrap();
+
+        assert_eq!(g.mirror(0), Some(3));
+        assert_eq!(g.mirror(3), Some(0));
+        assert_eq!(g.mirror(1), Some(2));
+        // thumb keys have distinct symmetry indices
+        assert_eq!(g.mirror(4), None);
+    }
+
+    #[test]
+    fn missing_attribute() {
+        let yaml = KEYBOARD.replace("[0.5, 0.5]", "[0.5]");
+        assert!(matches!(
+            geometry(&yaml),
+            Err(KeyboardError::WrongRowLength {
+                attribute: "key_costs",
+                row: 1,
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn entries_moved_between_rows() {
+        // the total number of entries matches, but the keys of the rows do not
+        let yaml = KEYBOARD.replace(
+            "  - [1, 2, 2, 1]\n  - [3, 4]",
+            "  - [1, 2, 2]\n  - [1, 3, 4]",
+        );
+        assert!(matches!(
+            geometry(&yaml),
+            Err(KeyboardError::WrongRowLength {
+                attribute: "symmetries",
+                row: 0,
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn missing_row() {
+        let yaml = KEYBOARD.replace("  - [Thumb, Thumb]\n", "");
+        assert!(matches!(
+            geometry(&yaml),
+            Err(KeyboardError::WrongRowNumber {
+                attribute: "fingers",
+                expected: 2,
+                found: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn physical_positions() {
+        // derived from the matrix positions of a standard keyboard by default
+        let g = geometry(KEYBOARD).unwrap();
+        assert_eq!(g.physical_coord(1), PhysicalPosition(19.05, 0.0, 0.0));
+        assert_eq!(
+            g.physical_coord(5),
+            PhysicalPosition(2.5 * 19.05, 19.05, 0.0)
+        );
+
+        let yaml = format!(
+            "{}physical_positions:\n  - [[0, 0], [19, 0], [38, 0], [57, 0]]\n  - [[20, 19, 3], [40, 19]]\n",
+            KEYBOARD
+        );
+        let g = geometry(&yaml).unwrap();
+        assert_eq!(g.physical_coord(4), PhysicalPosition(20.0, 19.0, 3.0));
+        assert_eq!(g.physical_coord(5), PhysicalPosition(40.0, 19.0, 0.0));
+    }
+
+    #[test]
+    fn duplicate_matrix_positions() {
+        let yaml = KEYBOARD.replace("[[1, 1], [2, 1]]", "[[1, 1], [1, 1]]");
+        assert!(matches!(
+            geometry(&yaml),
+            Err(KeyboardError::DuplicateMatrixPositions)
+        ));
+    }
+}
@@ -2,6 +2,8 @@ use crate::key::Hand;
 use crate::keyboard::Keyboard;
 use crate::layout::{LayerModifierLocations, Layout};
 use crate::layout_generator::LayoutGenerator;
+use crate::mod_tap::ModTapYAML;
+use crate::multi_key_symbol::MultiKeySymbolYAML;
 use crate::neo_layout_generator::BaseLayoutYAML;
 
 use ahash::{AHashMap, AHashSet};
@@ -26,19 +28,17 @@ pub struct GroupedLayoutGenerator {
     permutable_key_map: AHashMap<char, (u8, u8)>,
     grouped_layers: u8,
     modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
+    multi_key_symbols: Vec<MultiKeySymbolYAML>,
+    mod_taps: Vec<ModTapYAML>,
+    named_symbols: AHashMap<char, String>,
     keyboard: Arc<Keyboard>,
 }
 
 impl GroupedLayoutGenerator {
     /// Generate a [`GroupedLayoutGenerator`] from a [`BaseLayoutYAML`] object
     pub fn from_object(base: BaseLayoutYAML, keyboard: Arc<Keyboard>) -> Self {
-        let base_layout_symbols: Vec<Vec<char>> = base
-            .keys
-            .iter()
-            .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
-            .collect();
-        let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
+        let base_layout_symbols = base.flat_symbols(keyboard.keys());
+        let fixed_keys = base.flat_fixed_keys(keyboard.keys());
 
         let mut permutable_key_map: AHashMap<char, (u8, u8)> = AHashMap::default();
         base_layout_symbols
@@ -66,6 +66,9 @@ impl GroupedLayoutGenerator {
             permutable_key_map,
             grouped_layers: base.grouped_layers,
             modifiers: base.modifiers,
+            multi_key_symbols: base.multi_key_symbols,
+            mod_taps: base.mod_taps,
+            named_symbols: base.named_symbols,
             keyboard,
         }
     }
@@ -145,12 +148,17 @@ impl GroupedLayoutGenerator {
             }
         }
 
+        let modifiers =
+            ModTapYAML::apply_all(&self.mod_taps, &self.modifiers, &key_chars, &self.keyboard)?;
+
         Layout::new(
             key_chars,
             self.fixed_keys.clone(),
             self.keyboard.clone(),
-            self.modifiers.clone(),
-        )
+            modifiers,
+        )?
+        .with_named_symbols(&self.named_symbols)
+        .with_multi_key_symbols(&self.multi_key_symbols)
     }
 }
 
@@ -183,4 +191,8 @@ impl LayoutGenerator for GroupedLayoutGenerator {
 
         self.generate_unchecked(layout_keys)
     }
+
+    fn permutable_keys(&self) -> Vec<char> {
+        self.permutable_key_map.keys().cloned().collect()
+    }
 }
@@ -1,12 +1,13 @@
 //! This module provides structs for representing physical properties of keys in a keyboard
 
 use ahash::AHashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
 use std::slice;
 
 /// Row and columnar location on the keyboard
-#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, Hash, Debug)]
 pub struct MatrixPosition(
     /// Index of column
     pub u8,
@@ -39,6 +40,74 @@ impl Default for Position {
     }
 }
 
+/// Distance between the centers of two adjacent keys of a standard keyboard in millimeters
+pub const KEY_PITCH: f64 = 19.05;
+
+/// Horizontal offsets of the rows of a standard (row-staggered) keyboard relative to the number
+/// row in multiples of [`KEY_PITCH`]. Rows beyond these have no offset.
+const ROW_STAGGER: [f64; 4] = [0.0, 0.5, 0.75, 1.25];
+
+/// 3D position of a key in millimeters. The depth (`2`) is only relevant for keyboards with a
+/// keywell and zero otherwise.
+///
+/// Configured as `[x, y]` or `[x, y, z]`.
+#[derive(Clone, Copy, Deserialize, PartialEq, Debug)]
+#[serde(try_from = "Vec<f64>")]
+pub struct PhysicalPosition(
+    /// Horizontal positioning
+    pub f64,
+    /// Vertical positioning
+    pub f64,
+    /// Depth
+    pub f64,
+);
+
+impl PhysicalPosition {
+    /// Approximate position of a key at the matrix position of a standard row-staggered keyboard
+    /// (with a pitch of [`KEY_PITCH`]).
+    pub fn from_matrix_position(matrix_position: &MatrixPosition) -> Self {
+        let MatrixPosition(column, row) = *matrix_position;
+        let stagger = ROW_STAGGER.get(row as usize).copied().unwrap_or(0.0);
+
+        PhysicalPosition(
+            (column as f64 + stagger) * KEY_PITCH,
+            row as f64 * KEY_PITCH,
+            0.0,
+        )
+    }
+
+    /// Euclidean distance in millimeters
+    #[inline(always)]
+    pub fn distance(&self, other: &Self) -> f64 {
+        let dx = self.0 - other.0;
+        let dy = self.1 - other.1;
+        let dz = self.2 - other.2;
+
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+}
+
+impl Default for PhysicalPosition {
+    fn default() -> Self {
+        PhysicalPosition(0.0, 0.0, 0.0)
+    }
+}
+
+impl TryFrom<Vec<f64>> for PhysicalPosition {
+    type Error = String;
+
+    fn try_from(coordinates: Vec<f64>) -> Result<Self, Self::Error> {
+        match coordinates[..] {
+            [x, y] => Ok(PhysicalPosition(x, y, 0.0)),
+            [x, y, z] => Ok(PhysicalPosition(x, y, z)),
+            _ => Err(format!(
+                "A physical position needs two or three coordinates, but {} are given",
+                coordinates.len()
+            )),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Debug)]
 #[repr(u8)]
 pub enum Finger {
@@ -302,6 +371,9 @@ pub struct Key {
     /// 2D position of the key
     pub position: Position,
 
+    /// Physical position of the key in millimeters
+    pub physical_position: PhysicalPosition,
+
     /// Symmetriy index: Two different keys with identical symmetry index are considered symmetrical
     pub symmetry_index: u8,
 
@@ -310,4 +382,31 @@ pub struct Key {
 
     /// How strongly does the hand need to move away from the home row (start position) horizontally and vertically
     pub unbalancing: Position,
+
+    /// Whether the key is missing on the keyboard (it can not hold any symbols)
+    pub is_blocked: bool,
+
+    /// Width of the key in multiples of a standard key (e.g. 1.5 for a tab key)
+    pub width: f64,
+
+    /// Hand and finger that may press the key instead of the usual ones (e.g. the other index
+    /// finger for a key in the middle of the keyboard)
+    pub alternate_finger: Option<(Hand, Finger)>,
+}
+
+impl Key {
+    /// Distance in millimeters between the physical positions of two keys. Wide keys can be hit
+    /// off-center, which shortens the horizontal distance by the part of their width that exceeds
+    /// a standard key.
+    #[inline(always)]
+    pub fn physical_distance(&self, other: &Self) -> f64 {
+        let p1 = self.physical_position;
+        let p2 = other.physical_position;
+        let slack = 0.5 * ((self.width - 1.0).max(0.0) + (other.width - 1.0).max(0.0)) * KEY_PITCH;
+        let dx = ((p1.0 - p2.0).abs() - slack).max(0.0);
+        let dy = p1.1 - p2.1;
+        let dz = p1.2 - p2.2;
+
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
 }
@@ -0,0 +1,233 @@
+//! The `key_cost_tuning` module derives key costs from measured typing data, e.g. the average
+//! latencies of key presses exported from a typing trainer.
+//!
+//! The latencies are scaled linearly such that the keys under the resting positions of the
+//! fingers ("home keys") have a given average cost. Keys without (enough) data keep their
+//! original costs.
+
+use crate::key::{Finger, MatrixPosition};
+use crate::keyboard::Keyboard;
+
+use ahash::AHashMap;
+use anyhow::{anyhow, Result};
+use std::fs;
+
+/// Mapping of key names (as used in the typing data) to matrix positions
+pub type KeyNames = AHashMap<String, MatrixPosition>;
+
+/// Read a [`KeyNames`] table from a YAML file.
+pub fn key_names_from_yaml(filename: &str) -> Result<KeyNames> {
+    Ok(serde_yaml::from_str(&fs::read_to_string(filename)?)?)
+}
+
+/// Measured latency of a key
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyLatency {
+    pub name: String,
+    /// Mean latency in milliseconds
+    pub mean_ms: f64,
+    /// Number of key presses the mean is based on
+    pub samples: usize,
+}
+
+/// Parse CSV data with the columns key name, mean latency (ms), and number of samples.
+///
+/// A header line, empty lines, and lines starting with `#` are skipped.
+pub fn read_latencies_csv(data: &str) -> Result<Vec<KeyLatency>> {
+    let mut latencies = Vec::new();
+
+    for (i, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != 3 {
+            return Err(anyhow!(
+                "Line {}: expected 3 columns (key, mean ms, samples), found {}",
+                i + 1,
+                fields.len()
+            ));
+        }
+
+        match (fields[1].parse::<f64>(), fields[2].parse::<usize>()) {
+            (Ok(mean_ms), Ok(samples)) => latencies.push(KeyLatency {
+                name: fields[0].trim_matches('"').to_string(),
+                mean_ms,
+                samples,
+            }),
+            // header
+            _ if latencies.is_empty() && i == 0 => continue,
+            _ => return Err(anyhow!("Line {}: invalid number in '{}'", i + 1, line)),
+        }
+    }
+
+    Ok(latencies)
+}
+
+/// Result of [`tune_key_costs`]
+#[derive(Clone, Debug)]
+pub struct TunedKeyCosts {
+    /// New costs of all keys (in the order of the keyboard's keys)
+    pub costs: Vec<f64>,
+    /// Keys that kept their original cost due to missing data
+    pub missing: Vec<MatrixPosition>,
+    /// Names in the data that do not correspond to a key of the keyboard
+    pub unknown: Vec<String>,
+    /// Average cost of the home keys the costs are normalized to
+    pub baseline: f64,
+}
+
+/// Convert latencies into key costs.
+///
+/// The latencies are scaled such that the home keys (keys at the fingers' resting positions,
+/// without thumbs) have an average cost of `baseline`. If no baseline is given, the average
+/// original cost of the home keys is used. Entries with less than `min_samples` samples are
+/// ignored.
+pub fn tune_key_costs(
+    keyboard: &Keyboard,
+    latencies: &[KeyLatency],
+    key_names: &KeyNames,
+    baseline: Option<f64>,
+    min_samples: usize,
+) -> Result<TunedKeyCosts> {
+    let mut unknown = Vec::new();
+    let mut measured: AHashMap<MatrixPosition, f64> = AHashMap::default();
+    for l in latencies.iter().filter(|l| l.samples >= min_samples) {
+        match key_names.get(&l.name) {
+            Some(pos) if keyboard.geometry().key_at(*pos).is_some() => {
+                measured.insert(*pos, l.mean_ms);
+            }
+            _ => {
+                log::warn!("Key '{}' is not part of the keyboard. Ignoring it.", l.name);
+                unknown.push(l.name.clone());
+            }
+        }
+    }
+
+    let home_keys: Vec<_> = keyboard
+        .keys()
+        .iter()
+        .filter(|k| {
+            k.finger != Finger::Thumb
+                && k.position == *keyboard.finger_resting_positions.get(&k.hand, &k.finger)
+        })
+        .collect();
+
+    let home_latencies: Vec<f64> = home_keys
+        .iter()
+        .filter_map(|k| measured.get(&k.matrix_position).cloned())
+        .collect();
+    if home_latencies.is_empty() {
+        return Err(anyhow!("No (sufficient) data for any of the home keys"));
+    }
+    let home_latency = home_latencies.iter().sum::<f64>() / home_latencies.len() as f64;
+
+    let baseline = baseline
+        .unwrap_or_else(|| home_keys.iter().map(|k| k.cost).sum::<f64>() / home_keys.len() as f64);
+    let scale = baseline / home_latency;
+
+    let mut missing = Vec::new();
+    let costs = keyboard
+        .keys()
+        .iter()
+        .map(|k| match measured.get(&k.matrix_position) {
+            Some(latency) => latency * scale,
+            None => {
+                log::warn!(
+                    "No (sufficient) data for key at {:?}. Keeping its original cost of {}.",
+                    k.matrix_position,
+                    k.cost
+                );
+                missing.push(k.matrix_position);
+                k.cost
+            }
+        })
+        .collect();
+
+    Ok(TunedKeyCosts {
+        costs,
+        missing,
+        unknown,
+        baseline,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::LayoutConfig;
+
+    fn tune(baseline: Option<f64>, min_samples: usize) -> (Keyboard, TunedKeyCosts) {
+        let layout_config = LayoutConfig::from_yaml("../config/keyboard/standard.yml").unwrap();
+        let keyboard = Keyboard::from_yaml_object(layout_config.keyboard);
+        let key_names = key_names_from_yaml("../config/keyboard/key_names/standard.yml").unwrap();
+        let latencies =
+            read_latencies_csv(&fs::read_to_string("tests/fixtures/key_latencies.csv").unwrap())
+                .unwrap();
+
+        let tuned =
+            tune_key_costs(&keyboard, &latencies, &key_names, baseline, min_samples).unwrap();
+        (keyboard, tuned)
+    }
+
+    fn cost_at(keyboard: &Keyboard, tuned: &TunedKeyCosts, column: u8, row: u8) -> f64 {
+        let idx = keyboard
+            .keys()
+            .iter()
+            .position(|k| k.matrix_position == MatrixPosition(column, row))
+            .unwrap();
+        tuned.costs[idx]
+    }
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn read_csv() {
+        let latencies =
+            read_latencies_csv("key,mean_ms,samples\nKeyA, 120.5, 30\n\n# comment\n").unwrap();
+        assert_eq!(
+            latencies,
+            vec![KeyLatency {
+                name: "KeyA".to_string(),
+                mean_ms: 120.5,
+                samples: 30
+            }]
+        );
+
+        assert!(read_latencies_csv("KeyA,120\n").is_err());
+        assert!(read_latencies_csv("KeyA,120,3\nKeyB,fast,3\n").is_err());
+    }
+
+    #[test]
+    fn home_keys_average_to_baseline() {
+        // the home keys in the fixture average to 150 ms
+        let (keyboard, tuned) = tune(Some(4.0), 1);
+        assert_approx_eq(cost_at(&keyboard, &tuned, 5, 2), 4.0); // KeyF (150 ms)
+        assert_approx_eq(cost_at(&keyboard, &tuned, 2, 2), 4.0 * 180.0 / 150.0); // KeyA
+        assert_approx_eq(cost_at(&keyboard, &tuned, 2, 1), 4.0 * 300.0 / 150.0); // KeyQ
+        assert_eq!(tuned.unknown, vec!["F13".to_string()]);
+
+        // without baseline, the original average cost of the home keys (3.5) is used
+        let (keyboard, tuned) = tune(None, 1);
+        assert_eq!(tuned.baseline, 3.5);
+        assert_approx_eq(cost_at(&keyboard, &tuned, 5, 2), 3.5);
+    }
+
+    #[test]
+    fn missing_keys_keep_original_costs() {
+        let (keyboard, tuned) = tune(Some(4.0), 1);
+        // KeyZ is not part of the data
+        assert_eq!(cost_at(&keyboard, &tuned, 2, 3), 19.0);
+        assert!(tuned.missing.contains(&MatrixPosition(2, 3)));
+
+        // KeyQ has only 5 samples
+        let (keyboard, tuned) = tune(Some(4.0), 10);
+        assert_eq!(cost_at(&keyboard, &tuned, 2, 1), 16.0);
+        assert!(tuned.missing.contains(&MatrixPosition(2, 1)));
+    }
+}
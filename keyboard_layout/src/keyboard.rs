@@ -1,21 +1,69 @@
 //! This module provides a struct representing a keyboard.
 
-use crate::key::{Finger, Hand, HandFingerMap, Key, MatrixPosition, Position};
+use crate::geometry::KeyGeometry;
+use crate::key::{Finger, Hand, HandFingerMap, Key, MatrixPosition, PhysicalPosition, Position};
+use crate::split_keyboard::{SplitHalvesYAML, SplitKeyboardError};
 
-use ahash::{AHashMap, AHashSet};
+use ahash::AHashMap;
 use anyhow::Result;
 use serde::Deserialize;
-use std::fs::File;
+use std::{convert::TryFrom, fmt, fs::File};
 use thiserror::Error;
 
+/// Label of blocked keys in plots
+const BLOCKED_KEY_LABEL: &str = "▓▓";
+
+/// Number of keys whose declared finger may deviate from the one derived from their position
+/// before a warning is issued
+const FINGER_DEVIATION_THRESHOLD: usize = 2;
+
 #[derive(Error, Debug)]
 pub enum KeyboardError {
-    #[error("Invalid keyboard: Not the same number of keys in each keyboard list.")]
-    WrongKeyNumber,
+    #[error(
+        "Invalid keyboard: `{attribute}` has {found} rows, but `matrix_positions` has {expected}."
+    )]
+    WrongRowNumber {
+        attribute: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Invalid keyboard: Row {row} of `{attribute}` has {found} entries, but the same row of `matrix_positions` has {expected}.")]
+    WrongRowLength {
+        attribute: &'static str,
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Invalid keyboard: {0} keys exceed the maximum number of keys.")]
+    TooManyKeys(usize),
     #[error("Invalid keyboard: Duplicate `matrix_positions`.")]
     DuplicateMatrixPositions,
     #[error("Invalid keyboard: Duplicate `positions`.")]
     DuplicatePositions,
+    #[error("Invalid finger override: There is no key at {0:?}.")]
+    UnknownOverridePosition(MatrixPosition),
+    #[error("Invalid keyboard: There is no key at the blocked position {0:?}.")]
+    UnknownBlockedPosition(MatrixPosition),
+    #[error("Invalid keyboard: There is no key at the mirror position {0:?}.")]
+    UnknownMirrorPosition(MatrixPosition),
+    #[error("Invalid keyboard: The key at {0:?} is given in more than one mirror pair.")]
+    DuplicateMirrorPosition(MatrixPosition),
+    #[error("Invalid keyboard: The key at {0:?} has no mirror partner in `mirror_pairs`.")]
+    MissingMirrorPartner(MatrixPosition),
+    #[error(
+        "Invalid keyboard: The capability of the {0:?} {1:?} is {2}, but needs to be positive."
+    )]
+    InvalidFingerCapability(Hand, Finger, f64),
+    #[error("Invalid keyboard: There is no key at the position {0:?} of a key width.")]
+    UnknownKeyWidthPosition(MatrixPosition),
+    #[error("Invalid keyboard: The width of the key at {0:?} is {1}, but needs to be positive.")]
+    InvalidKeyWidth(MatrixPosition, f64),
+    #[error("Invalid keyboard: There is no key at the position {0:?} of an alternate finger.")]
+    UnknownAlternateFingerPosition(MatrixPosition),
+    #[error("Invalid keyboard: `{0}` is missing.")]
+    MissingAttribute(&'static str),
+    #[error(transparent)]
+    SplitKeyboard(#[from] SplitKeyboardError),
 }
 
 /// The index of a [`Key`] in the `keys` vec of a [`Keyboard`]
@@ -25,19 +73,29 @@ pub type KeyIndex = u8;
 #[derive(Clone, Debug)]
 pub struct Keyboard {
     /// The keys of the keyboard
-    pub keys: Vec<Key>,
+    geometry: KeyGeometry,
     pub finger_resting_positions: HandFingerMap<Position>,
     plot_template: String,
     plot_template_short: String,
+    /// The mirror partner of each key (see [`KeyboardYAML::mirror_map`])
+    mirror_map: AHashMap<MatrixPosition, MatrixPosition>,
+    /// Relative strength of each finger (1.0 unless configured otherwise)
+    pub finger_capabilities: HandFingerMap<f64>,
 }
 
 /// A collection of all relevant properties for the keys on a keyboard (configuration).
 ///
-/// Corresponds to (parts of) a YAML configuration file.
+/// Corresponds to (parts of) a YAML configuration file. For split keyboards (`split: true`), most
+/// attributes are derived from the description of their `halves` (see [`SplitHalvesYAML`]).
 #[derive(Deserialize, Debug)]
+#[serde(try_from = "KeyboardConfigYAML")]
 pub struct KeyboardYAML {
     matrix_positions: Vec<Vec<MatrixPosition>>,
     positions: Vec<Vec<Position>>,
+    /// Physical positions of the keys in millimeters. Derived from the matrix positions of a
+    /// standard row-staggered keyboard if unset (see [`PhysicalPosition::from_matrix_position`]).
+    #[serde(default)]
+    physical_positions: Option<Vec<Vec<PhysicalPosition>>>,
     hands: Vec<Vec<Hand>>,
     fingers: Vec<Vec<Finger>>,
     key_costs: Vec<Vec<f64>>,
@@ -46,92 +104,459 @@ pub struct KeyboardYAML {
     finger_resting_positions: AHashMap<Hand, AHashMap<Finger, Position>>,
     plot_template: String,
     plot_template_short: String,
+    /// Matrix positions of keys whose finger intentionally deviates from the one derived from
+    /// their horizontal position (e.g. for an angle mod)
+    #[serde(default)]
+    accept_finger_overrides: Vec<MatrixPosition>,
+    /// Matrix positions of keys that are missing on this keyboard (e.g. the ISO keys on an ANSI
+    /// keyboard). They can not hold any symbols and are excluded from the optimization.
+    #[serde(default)]
+    blocked_positions: Vec<MatrixPosition>,
+    /// Pairs of keys that are swapped when the keyboard is mirrored (e.g. for typing with one
+    /// hand). Keys on the axis are paired with themselves.
+    #[serde(default)]
+    mirror_pairs: Vec<(MatrixPosition, MatrixPosition)>,
+    /// Relative strength of each finger (default 1.0), e.g. for down-weighting an injured finger.
+    /// Metrics that depend on the fingers' strength scale their costs with it.
+    #[serde(default)]
+    finger_capabilities: AHashMap<Hand, AHashMap<Finger, f64>>,
+    /// Widths of keys that are wider than a standard key (in multiples of a standard key)
+    #[serde(default)]
+    key_widths: AHashMap<MatrixPosition, f64>,
+    /// Keys that may also be pressed by another finger (e.g. the key between both index fingers)
+    #[serde(default)]
+    alternate_fingers: Vec<FingerOverride>,
+}
+
+/// A hand and finger pressing the key at a matrix position instead of the ones given in the
+/// keyboard configuration (e.g. for an angle mod), or in addition to them (for alternate fingers).
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub struct FingerOverride {
+    pub position: MatrixPosition,
+    pub hand: Hand,
+    pub finger: Finger,
+}
+
+/// The keyboard configuration as given in a YAML file, before the attributes of split keyboards
+/// are derived.
+#[derive(Deserialize, Debug)]
+struct KeyboardConfigYAML {
+    #[serde(default)]
+    split: bool,
+    halves: Option<SplitHalvesYAML>,
+    matrix_positions: Option<Vec<Vec<MatrixPosition>>>,
+    positions: Option<Vec<Vec<Position>>>,
+    physical_positions: Option<Vec<Vec<PhysicalPosition>>>,
+    hands: Option<Vec<Vec<Hand>>>,
+    fingers: Option<Vec<Vec<Finger>>>,
+    key_costs: Option<Vec<Vec<f64>>>,
+    symmetries: Option<Vec<Vec<u8>>>,
+    unbalancing_positions: Option<Vec<Vec<Position>>>,
+    finger_resting_positions: Option<AHashMap<Hand, AHashMap<Finger, Position>>>,
+    plot_template: Option<String>,
+    plot_template_short: Option<String>,
+    #[serde(default)]
+    accept_finger_overrides: Vec<MatrixPosition>,
+    #[serde(default)]
+    blocked_positions: Vec<MatrixPosition>,
+    #[serde(default)]
+    mirror_pairs: Vec<(MatrixPosition, MatrixPosition)>,
+    #[serde(default)]
+    finger_capabilities: AHashMap<Hand, AHashMap<Finger, f64>>,
+    #[serde(default)]
+    key_widths: AHashMap<MatrixPosition, f64>,
+    #[serde(default)]
+    alternate_fingers: Vec<FingerOverride>,
 }
 
-/// Takes a slice of some iterable and checks whether that iterable contains
-/// duplicates of any of its elements.
-fn contains_duplicates<T: PartialEq>(v: &[T]) -> bool {
-    // Cycle through all elements
-    v.iter().enumerate().any(|(first_idx, checked_pos)| {
-        // Get index of last element that is equal to `checked_pos`
-        let last_idx = v.iter().rposition(|pos| pos == checked_pos).unwrap();
-        // See if that last element is different from the first one,
-        // which would be a duplicate.
-        first_idx != last_idx
-    })
+/// Unwraps an attribute that is required for a (non-split) keyboard.
+fn required<T>(attribute: &'static str, value: Option<T>) -> Result<T, KeyboardError> {
+    value.ok_or(KeyboardError::MissingAttribute(attribute))
+}
+
+impl TryFrom<KeyboardConfigYAML> for KeyboardYAML {
+    type Error = KeyboardError;
+
+    fn try_from(k: KeyboardConfigYAML) -> Result<Self, Self::Error> {
+        if !k.split {
+            return Ok(Self {
+                matrix_positions: required("matrix_positions", k.matrix_positions)?,
+                positions: required("positions", k.positions)?,
+                physical_positions: k.physical_positions,
+                hands: required("hands", k.hands)?,
+                fingers: required("fingers", k.fingers)?,
+                key_costs: required("key_costs", k.key_costs)?,
+                symmetries: required("symmetries", k.symmetries)?,
+                unbalancing_positions: required("unbalancing_positions", k.unbalancing_positions)?,
+                finger_resting_positions: required(
+                    "finger_resting_positions",
+                    k.finger_resting_positions,
+                )?,
+                plot_template: required("plot_template", k.plot_template)?,
+                plot_template_short: required("plot_template_short", k.plot_template_short)?,
+                accept_finger_overrides: k.accept_finger_overrides,
+                blocked_positions: k.blocked_positions,
+                mirror_pairs: k.mirror_pairs,
+                finger_capabilities: k.finger_capabilities,
+                key_widths: k.key_widths,
+                alternate_fingers: k.alternate_fingers,
+            });
+        }
+
+        let halves = k.halves.ok_or(SplitKeyboardError::MissingHalves)?;
+        halves.validate()?;
+        for (attribute, given) in [
+            ("matrix_positions", k.matrix_positions.is_some()),
+            ("positions", k.positions.is_some()),
+            ("hands", k.hands.is_some()),
+            ("fingers", k.fingers.is_some()),
+            ("symmetries", k.symmetries.is_some()),
+        ] {
+            if given {
+                return Err(SplitKeyboardError::DerivedAttribute(attribute).into());
+            }
+        }
+
+        let keys = halves.keys();
+        Ok(Self {
+            matrix_positions: keys
+                .iter()
+                .map(|row| row.iter().map(|k| k.matrix_position).collect())
+                .collect(),
+            positions: keys
+                .iter()
+                .map(|row| row.iter().map(|k| k.position()).collect())
+                .collect(),
+            physical_positions: Some(k.physical_positions.unwrap_or_else(|| {
+                keys.iter()
+                    .map(|row| row.iter().map(|k| k.physical_position()).collect())
+                    .collect()
+            })),
+            hands: keys
+                .iter()
+                .map(|row| row.iter().map(|k| k.hand).collect())
+                .collect(),
+            fingers: keys
+                .iter()
+                .map(|row| row.iter().map(|k| k.finger).collect())
+                .collect(),
+            key_costs: required("key_costs", k.key_costs)?,
+            symmetries: keys
+                .iter()
+                .map(|row| row.iter().map(|k| k.symmetry_index).collect())
+                .collect(),
+            unbalancing_positions: k.unbalancing_positions.unwrap_or_else(|| {
+                keys.iter()
+                    .map(|row| vec![Position::default(); row.len()])
+                    .collect()
+            }),
+            finger_resting_positions: k
+                .finger_resting_positions
+                .unwrap_or_else(|| halves.finger_resting_positions()),
+            plot_template: k.plot_template.unwrap_or_else(|| halves.plot_template()),
+            plot_template_short: k
+                .plot_template_short
+                .unwrap_or_else(|| halves.plot_template_short()),
+            accept_finger_overrides: k.accept_finger_overrides,
+            blocked_positions: k.blocked_positions,
+            mirror_pairs: k.mirror_pairs,
+            finger_capabilities: k.finger_capabilities,
+            key_widths: k.key_widths,
+            alternate_fingers: k.alternate_fingers,
+        })
+    }
+}
+
+/// A key whose declared finger differs from the finger with the horizontally nearest resting
+/// position (of the same hand).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FingerDeviation {
+    pub matrix_position: MatrixPosition,
+    pub position: Position,
+    pub hand: Hand,
+    pub declared: Finger,
+    pub expected: Finger,
+}
+
+impl fmt::Display for FingerDeviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}, {}] at ({}, {}): {:?} {:?} instead of {:?}",
+            self.matrix_position.0,
+            self.matrix_position.1,
+            self.position.0,
+            self.position.1,
+            self.hand,
+            self.declared,
+            self.expected,
+        )
+    }
+}
+
+/// Makes sure that `rows` has as many rows as `matrix_positions` and that each row has the same
+/// length as the corresponding row of `matrix_positions`.
+fn check_rows<T>(
+    attribute: &'static str,
+    rows: &[Vec<T>],
+    row_lengths: &[usize],
+) -> Result<(), KeyboardError> {
+    if rows.len() != row_lengths.len() {
+        return Err(KeyboardError::WrongRowNumber {
+            attribute,
+            expected: row_lengths.len(),
+            found: rows.len(),
+        });
+    }
+    for (row, (r, expected)) in rows.iter().zip(row_lengths.iter()).enumerate() {
+        if r.len() != *expected {
+            return Err(KeyboardError::WrongRowLength {
+                attribute,
+                row,
+                expected: *expected,
+                found: r.len(),
+            });
+        }
+    }
+
+    Ok(())
 }
 
 impl KeyboardYAML {
     /// Checks the [`KeyboardYAML`] for common errors.
     pub fn validate(&self) -> Result<()> {
-        let flat_matrix_positions = self.matrix_positions.concat();
-        let flat_positions = self.positions.concat();
-
-        // Make sure that all settings that should have the same number of elements
-        // do in fact have the same number of elements.
-        let mut lengths = AHashSet::default();
-        lengths.insert(flat_matrix_positions.len());
-        lengths.insert(flat_positions.len());
-        lengths.insert(self.hands.concat().len());
-        lengths.insert(self.fingers.concat().len());
-        lengths.insert(self.key_costs.concat().len());
-        lengths.insert(self.symmetries.concat().len());
-        lengths.insert(self.unbalancing_positions.concat().len());
-        if lengths.len() > 1 {
-            return Err(KeyboardError::WrongKeyNumber.into());
+        self.geometry()?;
+        self.mirror_map()?;
+        self.finger_capabilities()?;
+
+        let deviations = self.finger_deviations();
+        if deviations.len() > FINGER_DEVIATION_THRESHOLD {
+            log::warn!(
+                "The fingers of {} keys deviate from the ones expected from their positions \
+                (add intentional deviations to `accept_finger_overrides`):\n  {}",
+                deviations.len(),
+                deviations
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n  ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Keys whose declared finger differs from the one whose resting position is horizontally
+    /// nearest (among the non-thumb fingers of the key's hand). Thumb keys and the keys listed
+    /// in `accept_finger_overrides` are not considered.
+    ///
+    /// This is a heuristic for detecting finger assignments that were not adapted to the
+    /// key positions (e.g. of a columnar keyboard).
+    pub fn finger_deviations(&self) -> Vec<FingerDeviation> {
+        self.hands
+            .iter()
+            .flatten()
+            .zip(self.fingers.iter().flatten())
+            .zip(self.matrix_positions.iter().flatten())
+            .zip(self.positions.iter().flatten())
+            .filter(|(((_, finger), matrix_position), _)| {
+                **finger != Finger::Thumb && !self.accept_finger_overrides.contains(matrix_position)
+            })
+            .filter_map(|(((hand, finger), matrix_position), position)| {
+                let expected = *self
+                    .finger_resting_positions
+                    .get(hand)?
+                    .iter()
+                    .filter(|(f, _)| **f != Finger::Thumb)
+                    .min_by(|(f1, p1), (f2, p2)| {
+                        let d1 = (p1.0 - position.0).abs();
+                        let d2 = (p2.0 - position.0).abs();
+                        d1.partial_cmp(&d2)
+                            .unwrap()
+                            .then((**f1 as u8).cmp(&(**f2 as u8)))
+                    })?
+                    .0;
+
+                (expected != *finger).then(|| FingerDeviation {
+                    matrix_position: *matrix_position,
+                    position: *position,
+                    hand: *hand,
+                    declared: *finger,
+                    expected,
+                })
+            })
+            .collect()
+    }
+
+    /// The mirror partner of each key as given by `mirror_pairs` (in both directions). If any
+    /// pairs are given, each key that is not blocked needs a partner.
+    pub fn mirror_map(&self) -> Result<AHashMap<MatrixPosition, MatrixPosition>, KeyboardError> {
+        let mut mirror_map = AHashMap::default();
+        if self.mirror_pairs.is_empty() {
+            return Ok(mirror_map);
         }
 
-        // Make sure there are no duplicates in `matrix_positions`.
-        if contains_duplicates(&flat_matrix_positions) {
-            return Err(KeyboardError::DuplicateMatrixPositions.into());
+        let positions: Vec<&MatrixPosition> = self.matrix_positions.iter().flatten().collect();
+        for (p1, p2) in self.mirror_pairs.iter() {
+            for p in [p1, p2] {
+                if !positions.contains(&p) {
+                    return Err(KeyboardError::UnknownMirrorPosition(*p));
+                }
+            }
+            if mirror_map.insert(*p1, *p2).is_some() {
+                return Err(KeyboardError::DuplicateMirrorPosition(*p1));
+            }
+            if p1 != p2 && mirror_map.insert(*p2, *p1).is_some() {
+                return Err(KeyboardError::DuplicateMirrorPosition(*p2));
+            }
         }
 
-        // Make sure there are no duplicates in `positions`.
-        if contains_duplicates(&flat_positions) {
-            return Err(KeyboardError::DuplicatePositions.into());
+        if let Some(p) = positions
+            .iter()
+            .find(|p| !mirror_map.contains_key(p) && !self.blocked_positions.contains(p))
+        {
+            return Err(KeyboardError::MissingMirrorPartner(**p));
         }
 
-        Ok(())
+        Ok(mirror_map)
     }
-}
 
-impl Keyboard {
-    /// Generate a [`Keyboard`] from a [`KeyboardYAML`] object
-    pub fn from_yaml_object(k: KeyboardYAML) -> Self {
-        let keys = k
+    /// The relative strength of each finger as given by `finger_capabilities` (1.0 for fingers
+    /// that are not given). Capabilities need to be positive.
+    pub fn finger_capabilities(&self) -> Result<HandFingerMap<f64>, KeyboardError> {
+        for (hand, finger) in HandFingerMap::<f64>::keys() {
+            let capability = self
+                .finger_capabilities
+                .get(&hand)
+                .and_then(|capabilities| capabilities.get(&finger));
+            if let Some(c) = capability.filter(|c| !(**c > 0.0 && c.is_finite())) {
+                return Err(KeyboardError::InvalidFingerCapability(hand, finger, *c));
+            }
+        }
+
+        Ok(HandFingerMap::with_hashmap(&self.finger_capabilities, 1.0))
+    }
+
+    /// Number of keys in each row of the configuration (in the order of the keys in [`Keyboard`]).
+    pub fn row_lengths(&self) -> Vec<usize> {
+        self.matrix_positions.iter().map(|row| row.len()).collect()
+    }
+
+    /// Generate the validated [`KeyGeometry`] described by the configuration.
+    ///
+    /// Every key needs to have every attribute, so each row of all per-key lists must have
+    /// exactly as many entries as the same row of `matrix_positions`.
+    pub fn geometry(&self) -> Result<KeyGeometry, KeyboardError> {
+        let row_lengths = self.row_lengths();
+        check_rows("positions", &self.positions, &row_lengths)?;
+        check_rows("hands", &self.hands, &row_lengths)?;
+        check_rows("fingers", &self.fingers, &row_lengths)?;
+        check_rows("key_costs", &self.key_costs, &row_lengths)?;
+        check_rows("symmetries", &self.symmetries, &row_lengths)?;
+        check_rows(
+            "unbalancing_positions",
+            &self.unbalancing_positions,
+            &row_lengths,
+        )?;
+        let physical_positions: Vec<PhysicalPosition> = match &self.physical_positions {
+            Some(positions) => {
+                check_rows("physical_positions", positions, &row_lengths)?;
+                positions.iter().flatten().cloned().collect()
+            }
+            None => self
+                .matrix_positions
+                .iter()
+                .flatten()
+                .map(PhysicalPosition::from_matrix_position)
+                .collect(),
+        };
+        for p in self.blocked_positions.iter() {
+            if !self.matrix_positions.iter().flatten().any(|mp| mp == p) {
+                return Err(KeyboardError::UnknownBlockedPosition(*p));
+            }
+        }
+        for (p, width) in self.key_widths.iter() {
+            if !self.matrix_positions.iter().flatten().any(|mp| mp == p) {
+                return Err(KeyboardError::UnknownKeyWidthPosition(*p));
+            }
+            if !(*width > 0.0 && width.is_finite()) {
+                return Err(KeyboardError::InvalidKeyWidth(*p, *width));
+            }
+        }
+        for a in self.alternate_fingers.iter() {
+            if !self
+                .matrix_positions
+                .iter()
+                .flatten()
+                .any(|mp| *mp == a.position)
+            {
+                return Err(KeyboardError::UnknownAlternateFingerPosition(a.position));
+            }
+        }
+
+        let keys = self
             .hands
-            .into_iter()
+            .iter()
             .flatten()
-            .zip(k.fingers.into_iter().flatten())
-            .zip(k.matrix_positions.into_iter().flatten())
-            .zip(k.positions.into_iter().flatten())
-            .zip(k.symmetries.into_iter().flatten())
-            .zip(k.key_costs.into_iter().flatten())
-            .zip(k.unbalancing_positions.into_iter().flatten())
+            .zip(self.fingers.iter().flatten())
+            .zip(self.matrix_positions.iter().flatten())
+            .zip(self.positions.iter().flatten())
+            .zip(self.symmetries.iter().flatten())
+            .zip(self.key_costs.iter().flatten())
+            .zip(self.unbalancing_positions.iter().flatten())
+            .zip(physical_positions.iter())
             .map(
                 |(
-                    (((((hand, finger), matrix_position), position), symmetry_index), cost),
-                    unbalancing,
+                    (
+                        (((((hand, finger), matrix_position), position), symmetry_index), cost),
+                        unbalancing,
+                    ),
+                    physical_position,
                 )| Key {
-                    hand,
-                    finger,
-                    matrix_position,
-                    position,
-                    symmetry_index,
-                    cost,
-                    unbalancing,
+                    hand: *hand,
+                    finger: *finger,
+                    matrix_position: *matrix_position,
+                    position: *position,
+                    physical_position: *physical_position,
+                    symmetry_index: *symmetry_index,
+                    cost: *cost,
+                    unbalancing: *unbalancing,
+                    is_blocked: self.blocked_positions.contains(matrix_position),
+                    width: self.key_widths.get(matrix_position).copied().unwrap_or(1.0),
+                    alternate_finger: self
+                        .alternate_fingers
+                        .iter()
+                        .find(|a| a.position == *matrix_position)
+                        .map(|a| (a.hand, a.finger)),
                 },
             )
             .collect();
 
+        KeyGeometry::new(keys)
+    }
+}
+
+impl Keyboard {
+    /// Generate a [`Keyboard`] from a [`KeyboardYAML`] object
+    ///
+    /// The object is expected to be validated (see [`KeyboardYAML::validate`]).
+    pub fn from_yaml_object(k: KeyboardYAML) -> Self {
+        let geometry = k.geometry().expect("Invalid keyboard configuration");
+        let mirror_map = k.mirror_map().expect("Invalid keyboard configuration");
+        let finger_capabilities = k
+            .finger_capabilities()
+            .expect("Invalid keyboard configuration");
+
         Keyboard {
-            keys,
+            geometry,
             finger_resting_positions: HandFingerMap::with_hashmap(
                 &k.finger_resting_positions,
                 Position::default(),
             ),
             plot_template: k.plot_template,
             plot_template_short: k.plot_template_short,
+            mirror_map,
+            finger_capabilities,
         }
     }
 
@@ -139,21 +564,73 @@ impl Keyboard {
     pub fn from_yaml_file(filename: &str) -> Result<Self> {
         let f = File::open(filename)?;
         let k: KeyboardYAML = serde_yaml::from_reader(f)?;
+        k.validate()?;
         Ok(Keyboard::from_yaml_object(k))
     }
 
     /// Generate a [`Keyboard`] from a YAML string
     pub fn from_yaml_str(data: &str) -> Result<Self> {
         let k: KeyboardYAML = serde_yaml::from_str(data)?;
+        k.validate()?;
         Ok(Keyboard::from_yaml_object(k))
     }
 
-    /// Plot a graphical representation of the keyboard with given key labels
+    /// Replace the hands and fingers of the keys at the overrides' matrix positions.
+    pub fn with_finger_overrides(
+        self,
+        overrides: &[FingerOverride],
+    ) -> Result<Self, KeyboardError> {
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+
+        let mut keys = self.geometry.keys().to_vec();
+        for o in overrides {
+            let key = keys
+                .iter_mut()
+                .find(|k| k.matrix_position == o.position)
+                .ok_or(KeyboardError::UnknownOverridePosition(o.position))?;
+            key.hand = o.hand;
+            key.finger = o.finger;
+        }
+        let geometry = KeyGeometry::new(keys)?;
+
+        Ok(Keyboard { geometry, ..self })
+    }
+
+    /// The mirror partner of each key (empty if the keyboard declares no `mirror_pairs`)
+    pub fn mirror_map(&self) -> &AHashMap<MatrixPosition, MatrixPosition> {
+        &self.mirror_map
+    }
+
+    /// The validated geometry of the keyboard's keys
+    pub fn geometry(&self) -> &KeyGeometry {
+        &self.geometry
+    }
+
+    /// The keys of the keyboard
+    pub fn keys(&self) -> &[Key] {
+        self.geometry.keys()
+    }
+
+    /// Plot a graphical representation of the keyboard with given key labels. Blocked keys are
+    /// shown as `▓▓`.
     pub fn plot(&self, key_labels: &[String]) -> String {
         let mut reg = handlebars::Handlebars::new();
         reg.register_escape_fn(handlebars::no_escape);
-        let labels: AHashMap<usize, String> = key_labels.iter().cloned().enumerate().collect();
-        reg.render_template(&self.plot_template, &labels).unwrap()
+        let mut labels: AHashMap<usize, String> = key_labels.iter().cloned().enumerate().collect();
+        let mut template = self.plot_template.clone();
+        self.keys()
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| key.is_blocked)
+            .for_each(|(i, _)| {
+                // the label is one character wider than the others and takes the following space
+                template =
+                    template.replacen(&format!("{{{{{}}}}} ", i), &format!("{{{{{}}}}}", i), 1);
+                labels.insert(i, BLOCKED_KEY_LABEL.to_string());
+            });
+        reg.render_template(&template, &labels).unwrap()
     }
 
     /// Plot a compact graphical representation of the keyboard with given key labels without borders (compatible with ArneBab's input strings)
@@ -168,12 +645,12 @@ impl Keyboard {
     pub fn estimated_finger_loads(&self, exclude_thumbs: bool) -> HandFingerMap<f64> {
         let mut intended_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
-        self.keys
-            .iter()
-            .filter(|k| !exclude_thumbs || k.finger != Finger::Thumb)
-            .for_each(|k| {
-                let il = intended_loads.get_mut(&k.hand, &k.finger);
-                *il += 1.0 / (1.0 + k.cost);
+        let g = &self.geometry;
+        g.indices()
+            .filter(|i| !g.key(*i).is_blocked && (!exclude_thumbs || !g.is_thumb(*i)))
+            .for_each(|i| {
+                let il = intended_loads.get_mut(&g.hand(i), &g.finger(i));
+                *il += 1.0 / (1.0 + g.key(i).cost);
             });
 
         let sum: f64 = intended_loads.iter().sum();
@@ -185,9 +662,10 @@ impl Keyboard {
     pub fn estimated_row_loads(&self) -> AHashMap<u8, f64> {
         let mut intended_loads: AHashMap<u8, f64> = AHashMap::default();
 
-        self.keys.iter().for_each(|k| {
-            let il = intended_loads.entry(k.matrix_position.1).or_insert(0.0);
-            *il += 1.0 / (1.0 + k.cost);
+        let g = &self.geometry;
+        g.indices().filter(|i| !g.key(*i).is_blocked).for_each(|i| {
+            let il = intended_loads.entry(g.row(i)).or_insert(0.0);
+            *il += 1.0 / (1.0 + g.key(i).cost);
         });
 
         let sum: f64 = intended_loads.values().sum();
@@ -196,3 +674,122 @@ impl Keyboard {
         intended_loads
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Columnar positions with the finger mapping of a row-staggered keyboard
+    const KEYBOARD: &str = "
+matrix_positions:
+  - [[0, 0], [1, 0], [2, 0], [3, 0]]
+  - [[0, 1], [1, 1], [2, 1], [3, 1]]
+  - [[0, 2]]
+positions:
+  - [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]]
+  - [[0.0, 1.0], [1.0, 1.0], [2.0, 1.0], [3.0, 1.0]]
+  - [[3.0, 2.0]]
+hands:
+  - [Left, Left, Left, Left]
+  - [Left, Left, Left, Left]
+  - [Left]
+fingers:
+  - [Pinky, Ring, Middle, Index]
+  - [Pinky, Pinky, Ring, Middle]
+  - [Thumb]
+key_costs:
+  - [1.0, 1.0, 1.0, 1.0]
+  - [1.0, 1.0, 1.0, 1.0]
+  - [1.0]
+symmetries:
+  - [1, 2, 3, 4]
+  - [5, 6, 7, 8]
+  - [9]
+unbalancing_positions:
+  - [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0]]
+  - [[0.0, 0.0], [0.0, 0.0], [0.0, 0.0], [0.0, 0.0]]
+  - [[0.0, 0.0]]
+finger_resting_positions:
+  Left:
+    Pinky: [0.0, 1.0]
+    Ring: [1.0, 1.0]
+    Middle: [2.0, 1.0]
+    Index: [3.0, 1.0]
+    Thumb: [3.0, 2.0]
+plot_template: ''
+plot_template_short: ''
+";
+
+    #[test]
+    fn deviating_fingers_are_listed() {
+        let k: KeyboardYAML = serde_yaml::from_str(KEYBOARD).unwrap();
+        let deviations = k.finger_deviations();
+
+        assert_eq!(
+            deviations
+                .iter()
+                .map(|d| (d.matrix_position, d.declared, d.expected))
+                .collect::<Vec<_>>(),
+            vec![
+                (MatrixPosition(1, 1), Finger::Pinky, Finger::Ring),
+                (MatrixPosition(2, 1), Finger::Ring, Finger::Middle),
+                (MatrixPosition(3, 1), Finger::Middle, Finger::Index),
+            ]
+        );
+        assert_eq!(
+            deviations[0].to_string(),
+            "[1, 1] at (1, 1): Left Pinky instead of Ring"
+        );
+    }
+
+    #[test]
+    fn accepted_overrides_are_ignored() {
+        let yaml = format!("{}accept_finger_overrides: [[1, 1], [3, 1]]\n", KEYBOARD);
+        let k: KeyboardYAML = serde_yaml::from_str(&yaml).unwrap();
+        let deviations = k.finger_deviations();
+
+        assert_eq!(deviations.len(), 1);
+        assert_eq!(deviations[0].matrix_position, MatrixPosition(2, 1));
+    }
+
+    #[test]
+    fn mirror_pairs() {
+        let pairs = "mirror_pairs: [[[0, 0], [3, 0]], [[1, 0], [2, 0]], [[0, 1], [3, 1]], \
+            [[1, 1], [2, 1]], [[0, 2], [0, 2]]]\n";
+        let k: KeyboardYAML = serde_yaml::from_str(&format!("{}{}", KEYBOARD, pairs)).unwrap();
+        let mirror_map = k.mirror_map().unwrap();
+        assert_eq!(mirror_map.len(), 9);
+        assert_eq!(mirror_map[&MatrixPosition(3, 0)], MatrixPosition(0, 0));
+        assert_eq!(mirror_map[&MatrixPosition(0, 2)], MatrixPosition(0, 2));
+
+        // the key on the axis has no partner
+        let missing = pairs.replace(", [[0, 2], [0, 2]]", "");
+        let k: KeyboardYAML = serde_yaml::from_str(&format!("{}{}", KEYBOARD, missing)).unwrap();
+        assert!(matches!(
+            k.mirror_map(),
+            Err(KeyboardError::MissingMirrorPartner(MatrixPosition(0, 2)))
+        ));
+        // unless it is blocked
+        let blocked = format!("{}{}blocked_positions: [[0, 2]]\n", KEYBOARD, missing);
+        let k: KeyboardYAML = serde_yaml::from_str(&blocked).unwrap();
+        assert!(k.mirror_map().is_ok());
+
+        let duplicate = pairs.replace("[[0, 2], [0, 2]]", "[[0, 2], [1, 0]]");
+        let k: KeyboardYAML = serde_yaml::from_str(&format!("{}{}", KEYBOARD, duplicate)).unwrap();
+        assert!(matches!(
+            k.mirror_map(),
+            Err(KeyboardError::DuplicateMirrorPosition(MatrixPosition(1, 0)))
+        ));
+    }
+
+    #[test]
+    fn blocked_positions_need_to_exist() {
+        let yaml = format!("{}blocked_positions: [[0, 2], [9, 9]]\n", KEYBOARD);
+        let k: KeyboardYAML = serde_yaml::from_str(&yaml).unwrap();
+
+        assert!(matches!(
+            k.geometry(),
+            Err(KeyboardError::UnknownBlockedPosition(MatrixPosition(9, 9)))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! This module provides keycap groups: sets of symbols that have to stay on a set of keys as a
+//! group, e.g. because they are printed on the keycaps. The symbols may be permuted among the
+//! keys of their group, but never move to other keys (and no other symbols may take their keys).
+//!
+//! In contrast to fixed keys (that pin a symbol to a single key), this allows optimizing the
+//! arrangement within the group without relegending any keycaps.
+
+use crate::key::MatrixPosition;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum KeycapGroupError {
+    #[error("Invalid keycap group '{0}': {1} symbols, but {2} positions")]
+    SizeMismatch(String, usize, usize),
+    #[error("Invalid keycap groups: Symbol '{0}' is part of more than one group")]
+    DuplicateSymbol(char),
+    #[error("Invalid keycap groups: Position {0:?} is part of more than one group")]
+    DuplicatePosition(MatrixPosition),
+    #[error(
+        "Invalid keycap group '{0}': Symbol '{1}' is not a permutable symbol of the base layout"
+    )]
+    UnknownSymbol(String, char),
+    #[error("Invalid keycap group '{0}': Position {1:?} is not a non-fixed key of the keyboard")]
+    UnknownPosition(String, MatrixPosition),
+}
+
+/// A keycap group as given in the layout configuration.
+///
+/// Corresponds to (parts of) a YAML configuration file.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct KeycapGroupYAML {
+    /// The symbols of the group
+    pub symbols: String,
+    /// The matrix positions of the keys the symbols have to stay on
+    pub positions: Vec<MatrixPosition>,
+}
+
+impl KeycapGroupYAML {
+    /// Checks a list of keycap groups for consistency (same number of symbols and positions,
+    /// no symbol or position in more than one group).
+    pub fn validate_all(groups: &[KeycapGroupYAML]) -> Result<(), KeycapGroupError> {
+        let mut symbols: Vec<char> = Vec::new();
+        let mut positions: Vec<MatrixPosition> = Vec::new();
+
+        for group in groups {
+            let n_symbols = group.symbols.chars().count();
+            if n_symbols != group.positions.len() {
+                return Err(KeycapGroupError::SizeMismatch(
+                    group.symbols.clone(),
+                    n_symbols,
+                    group.positions.len(),
+                ));
+            }
+
+            for c in group.symbols.chars() {
+                if symbols.contains(&c) {
+                    return Err(KeycapGroupError::DuplicateSymbol(c));
+                }
+                symbols.push(c);
+            }
+
+            for p in group.positions.iter() {
+                if positions.contains(p) {
+                    return Err(KeycapGroupError::DuplicatePosition(*p));
+                }
+                positions.push(*p);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A keycap group in terms of a layout string (as given to a layout generator).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeycapGroup {
+    /// The symbols of the group
+    pub symbols: Vec<char>,
+    /// The indices (in terms of chars) in the layout string the symbols have to stay on
+    pub indices: Vec<usize>,
+}
+
+impl KeycapGroup {
+    /// The group's symbols that are not placed on one of the group's indices of the layout
+    /// string (and are therefore placed on a key with another keycap).
+    pub fn misplaced_symbols(&self, layout_str: &str) -> Vec<char> {
+        let chars: Vec<char> = layout_str.chars().collect();
+        self.symbols
+            .iter()
+            .filter(|c| !self.indices.iter().any(|i| chars.get(*i) == Some(*c)))
+            .cloned()
+            .collect()
+    }
+}
+
+impl std::fmt::Display for KeycapGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbols.iter().collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(symbols: &str, positions: &[(u8, u8)]) -> KeycapGroupYAML {
+        KeycapGroupYAML {
+            symbols: symbols.to_string(),
+            positions: positions
+                .iter()
+                .map(|(c, r)| MatrixPosition(*c, *r))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn validation() {
+        let valid = vec![group(",.", &[(1, 1), (2, 1)]), group("-", &[(3, 1)])];
+        assert!(KeycapGroupYAML::validate_all(&valid).is_ok());
+
+        let wrong_size = vec![group(",.-", &[(1, 1), (2, 1)])];
+        assert!(matches!(
+            KeycapGroupYAML::validate_all(&wrong_size),
+            Err(KeycapGroupError::SizeMismatch(_, 3, 2))
+        ));
+
+        let duplicate_symbol = vec![group(",.", &[(1, 1), (2, 1)]), group(".", &[(3, 1)])];
+        assert!(matches!(
+            KeycapGroupYAML::validate_all(&duplicate_symbol),
+            Err(KeycapGroupError::DuplicateSymbol('.'))
+        ));
+
+        let duplicate_position = vec![group(",.", &[(1, 1), (2, 1)]), group("-", &[(2, 1)])];
+        assert!(matches!(
+            KeycapGroupYAML::validate_all(&duplicate_position),
+            Err(KeycapGroupError::DuplicatePosition(MatrixPosition(2, 1)))
+        ));
+    }
+
+    #[test]
+    fn misplaced_symbols() {
+        let group = KeycapGroup {
+            symbols: vec![',', '.'],
+            indices: vec![3, 4],
+        };
+        assert!(group.misplaced_symbols("abc.,").is_empty());
+        assert_eq!(group.misplaced_symbols("a,c.b"), vec![',']);
+    }
+}
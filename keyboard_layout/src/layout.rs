@@ -2,8 +2,12 @@
 //! its relation to the individual keys required to generate the layout's symbols.
 //! These provide the core objects that are evaluated in the `layout_evaluation` crate.
 
+use crate::geometry::KeyGeometry;
 use crate::key::{Hand, Key, MatrixPosition};
 use crate::keyboard::{KeyIndex, Keyboard};
+use crate::multi_key_symbol::{MultiKeySymbol, MultiKeySymbolError, MultiKeySymbolYAML};
+use crate::neo_layout_generator::LayoutError;
+use crate::script::isolate_rtl;
 
 use ahash::AHashMap;
 use anyhow::Result;
@@ -12,6 +16,7 @@ use core::slice;
 use serde::Deserialize;
 use smallmap::Map;
 use std::{fmt, sync::Arc};
+use thiserror::Error;
 
 /// The index of a [`LayerKey`] in the `layerkeys` vec of a [`Layout`]
 ///
@@ -20,6 +25,34 @@ use std::{fmt, sync::Arc};
 /// Therefore, this is not a [`usize`] or larger.
 pub type LayerKeyIndex = u16;
 
+/// Maximum number of modifiers for accessing a layer with one hand. The number of ngrams that
+/// are generated for a higher-layer symbol grows with the permutations of its modifiers.
+pub const MAX_MODIFIERS_PER_LAYER: usize = 4;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ModifierError {
+    #[error("Invalid modifiers of layer {0}: {2} modifiers are given for the {1:?} hand, but at most {} are supported", MAX_MODIFIERS_PER_LAYER)]
+    TooMany(usize, Hand, usize),
+    #[error(
+        "Invalid modifiers of layer {0}: Modifier {2:?} is given more than once for the {1:?} hand"
+    )]
+    Duplicate(usize, Hand, ModifierLocation),
+    #[error("Invalid modifiers: Modifiers are given for {0} layers, but at most {} are supported", u8::MAX - 1)]
+    TooManyLayers(usize),
+    #[error("Invalid modifiers of layer {0}: The hold modifier at {1:?} also generates the printable symbol {2:?}")]
+    PrintableSymbol(usize, MatrixPosition, char),
+    #[error("Invalid modifiers of layer {0}: The chord for the {1:?} hand consists of {2} keys, but needs at least two")]
+    ChordTooSmall(usize, Hand, usize),
+    #[error("Invalid modifiers of layer {0}: Modifier {1:?} is not part of the layout")]
+    NotFound(usize, ModifierLocation),
+}
+
+/// Whether a symbol is typed as content (letters, digits, whitespace, and ASCII punctuation).
+/// Symbols such as arrows (e.g. "⇧") that only name a modifier key are not printable.
+fn is_printable(c: char) -> bool {
+    c.is_alphanumeric() || c.is_whitespace() || c.is_ascii_punctuation()
+}
+
 /// Enum for specifying the location of a modifier relative to the keyboard.
 ///
 /// This can be a `MatrixPosition` provided by the keyboard or a symbol that a corresponding layout
@@ -41,6 +74,9 @@ pub enum LayerModifierType {
     Hold,
     OneShot,
     LongPress,
+    Lock,
+    Chord,
+    ModTap,
 }
 
 impl Default for LayerModifierType {
@@ -69,6 +105,24 @@ impl LayerModifierType {
     pub fn is_long_press(&self) -> bool {
         matches!(self, Self::LongPress)
     }
+
+    pub fn is_lock(&self) -> bool {
+        matches!(self, Self::Lock)
+    }
+
+    pub fn is_chord(&self) -> bool {
+        matches!(self, Self::Chord)
+    }
+
+    pub fn is_mod_tap(&self) -> bool {
+        matches!(self, Self::ModTap)
+    }
+
+    /// Whether the modifier is held while typing the symbols of its layer, either on its own, as
+    /// a member of a chord, or as the hold function of a mod-tap
+    pub fn is_held(&self) -> bool {
+        matches!(self, Self::Hold | Self::Chord | Self::ModTap)
+    }
 }
 
 /// Enum for configuring the way how the modifiers shall be used to access a layer.
@@ -80,6 +134,15 @@ pub enum LayerModifierLocations {
     Hold(Vec<ModifierLocation>),
     OneShot(Vec<ModifierLocation>),
     LongPress,
+    /// The modifiers are tapped (together) to lock the layer and tapped again to leave it.
+    Lock(Vec<ModifierLocation>),
+    /// The keys are pressed simultaneously (as a chord or combo) and held to access the layer.
+    /// In contrast to [`LayerModifierLocations::Hold`], the keys may generate symbols themselves
+    /// when tapped alone.
+    Chord(Vec<ModifierLocation>),
+    /// A key generating a symbol when tapped is held to access the layer (see
+    /// [`ModTapYAML`](crate::mod_tap::ModTapYAML)).
+    ModTap(ModifierLocation),
 }
 
 impl LayerModifierLocations {
@@ -88,6 +151,9 @@ impl LayerModifierLocations {
             Self::Hold(v) => v.iter(),
             Self::OneShot(v) => v.iter(),
             Self::LongPress => [].iter(),
+            Self::Lock(v) => v.iter(),
+            Self::Chord(v) => v.iter(),
+            Self::ModTap(location) => slice::from_ref(location).iter(),
         }
     }
     pub fn layer_modifier_type(&self) -> LayerModifierType {
@@ -95,7 +161,44 @@ impl LayerModifierLocations {
             Self::Hold(_) => LayerModifierType::Hold,
             Self::OneShot(_) => LayerModifierType::OneShot,
             Self::LongPress => LayerModifierType::LongPress,
+            Self::Lock(_) => LayerModifierType::Lock,
+            Self::Chord(_) => LayerModifierType::Chord,
+            Self::ModTap(_) => LayerModifierType::ModTap,
+        }
+    }
+
+    /// Checks the modifiers of all (higher) layers: There must be no more than
+    /// [`MAX_MODIFIERS_PER_LAYER`] modifiers for each layer and hand, no modifier may be given twice,
+    /// chords need to consist of at least two keys and the layer indices need to fit into a `u8`.
+    pub fn validate_all(modifiers: &[AHashMap<Hand, Self>]) -> Result<(), ModifierError> {
+        if modifiers.len() >= u8::MAX as usize {
+            return Err(ModifierError::TooManyLayers(modifiers.len()));
+        }
+
+        for (i, mods_per_hand) in modifiers.iter().enumerate() {
+            let layer = i + 1;
+            // sort by hand for deterministic error messages
+            let mut hands: Vec<&Hand> = mods_per_hand.keys().collect();
+            hands.sort_by_key(|hand| **hand == Hand::Right);
+            for hand in hands {
+                let locations = mods_per_hand[hand].iter().as_slice();
+                if locations.len() > MAX_MODIFIERS_PER_LAYER {
+                    return Err(ModifierError::TooMany(layer, *hand, locations.len()));
+                }
+                if let Self::Chord(_) = mods_per_hand[hand] {
+                    if locations.len() < 2 {
+                        return Err(ModifierError::ChordTooSmall(layer, *hand, locations.len()));
+                    }
+                }
+                for (j, location) in locations.iter().enumerate() {
+                    if locations[..j].contains(location) {
+                        return Err(ModifierError::Duplicate(layer, *hand, location.clone()));
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
 }
 
@@ -106,6 +209,10 @@ pub enum LayerModifiers {
     Hold(Vec<LayerKeyIndex>),
     OneShot(Vec<LayerKeyIndex>),
     LongPress,
+    /// Keys toggling the (lock) layer, see [`LayerModifierLocations::Lock`]
+    Lock(Vec<LayerKeyIndex>),
+    /// Keys pressed together as a chord, see [`LayerModifierLocations::Chord`]
+    Chord(Vec<LayerKeyIndex>),
 }
 
 impl LayerModifiers {
@@ -114,6 +221,8 @@ impl LayerModifiers {
             Self::Hold(v) => v,
             Self::OneShot(v) => v,
             Self::LongPress => &[],
+            Self::Lock(v) => v,
+            Self::Chord(v) => v,
         }
     }
 }
@@ -135,6 +244,8 @@ pub struct LayerKey {
     pub layer: u8,
     /// Key to press for the symbol
     pub key: Key,
+    /// Index of the key in the [`KeyGeometry`](crate::geometry::KeyGeometry) of the keyboard
+    pub key_index: KeyIndex,
     /// Symbol belonging to a layout
     pub symbol: char,
     /// Vec of modifiers required to activate the layer (in terms of a [`LayerKeyIndex`] for a layout)
@@ -143,14 +254,21 @@ pub struct LayerKey {
     pub is_fixed: bool,
     /// If the symbol itself is a modifier
     pub is_modifier: LayerModifierType,
+    /// Name of the symbol to display instead of the symbol itself (e.g. "⌫" for a private-use-area
+    /// character standing for backspace), see [`Layout::with_named_symbols`]
+    pub name: Option<String>,
 }
 
 impl fmt::Display for LayerKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match &self.name {
+            Some(name) => name.clone(),
+            None => isolate_rtl(&self.symbol.escape_debug().to_string()),
+        };
         if self.is_modifier.is_some() {
-            write!(f, "[{}]", self.symbol.escape_debug())
+            write!(f, "[{}]", symbol)
         } else {
-            write!(f, "{}", self.symbol.escape_debug())
+            write!(f, "{}", symbol)
         }
     }
 }
@@ -159,6 +277,7 @@ impl LayerKey {
     pub fn new(
         layer: u8,
         key: Key,
+        key_index: KeyIndex,
         symbol: char,
         modifiers: LayerModifiers,
         is_fixed: bool,
@@ -167,10 +286,12 @@ impl LayerKey {
         Self {
             layer,
             key,
+            key_index,
             symbol,
             modifiers,
             is_fixed,
             is_modifier,
+            name: None,
         }
     }
 }
@@ -190,13 +311,16 @@ pub struct Layout {
     pub layerkeys: Vec<LayerKey>,
     /// The underlying keyboard providing the keys
     pub keyboard: Arc<Keyboard>,
-    /// Vec of the [`KeyIndex`] corresponding to each [`LayerKey`] in `layerkeys`
-    layerkey_to_key_index: Vec<KeyIndex>,
     /// Vec for each [`Key`] of the [`Keyboard`] containing a Vec of all [`LayerKey`] that are
     /// generaten with that [`Key`]
     key_layers: Vec<Vec<LayerKeyIndex>>,
     /// Map for retrieving the [`LayerKey`] for the symbol it generates
     key_map: Map<char, LayerKeyIndex>,
+    /// Symbols that are generated by multiple keys (by symbol)
+    multi_key_symbols: AHashMap<char, MultiKeySymbol>,
+    /// Symbols that are generated by multiple [`LayerKey`]s (e.g. on different layers) with all of
+    /// them, the one of `key_map` first and the others by ascending cost
+    duplicate_symbols: AHashMap<char, Vec<LayerKeyIndex>>,
 }
 
 impl fmt::Display for Layout {
@@ -212,15 +336,29 @@ impl Layout {
         keyboard: Arc<Keyboard>,
         modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
     ) -> Result<Self> {
+        LayerModifierLocations::validate_all(&modifiers)?;
+
+        // blocked keys do not exist on the keyboard
+        if let Some((chars, key)) = key_chars
+            .iter()
+            .zip(keyboard.keys().iter())
+            .find(|(chars, key)| key.is_blocked && !chars.is_empty())
+        {
+            return Err(LayoutError::BlockedKeySymbols(
+                key.matrix_position,
+                chars.iter().collect(),
+            )
+            .into());
+        }
+
         // generate layer keys
         let mut layerkeys = Vec::new();
-        let mut layerkey_to_key_index = Vec::new();
         let mut char2layerkey_index: AHashMap<char, LayerKeyIndex> = AHashMap::default();
         let mut pos2layerkey_index: AHashMap<MatrixPosition, LayerKeyIndex> = AHashMap::default();
         let mut layerkey_index = 0;
         let key_layers: Vec<Vec<LayerKeyIndex>> = key_chars
             .iter()
-            .zip(keyboard.keys.iter())
+            .zip(keyboard.keys().iter())
             .zip(fixed_keys.iter())
             .enumerate()
             .map(|(key_index, ((layer_chars, key), fixed))| {
@@ -232,13 +370,12 @@ impl Layout {
                         layerkeys.push(LayerKey::new(
                             layer_id as u8,
                             key.clone(),
+                            key_index as KeyIndex,
                             *c,
                             LayerModifiers::default(),
                             *fixed,
                             LayerModifierType::None,
                         ));
-                        layerkey_to_key_index.push(key_index as KeyIndex);
-
                         pos2layerkey_index
                             .entry(key.matrix_position)
                             .or_insert(layerkey_index);
@@ -267,33 +404,33 @@ impl Layout {
             AHashMap::default();
         let mut char2mod_index: AHashMap<(LayerModifierType, char), LayerKeyIndex> =
             AHashMap::default();
-        for mods_per_hand in modifiers.iter() {
+        for (i, mods_per_hand) in modifiers.iter().enumerate() {
             let mut resolved_mods_per_hand = AHashMap::default();
-            for (hand, mods) in mods_per_hand.iter() {
+            // in a fixed order of the hands, so that identical layouts get identical indices
+            let mut mods_per_hand: Vec<_> = mods_per_hand.iter().collect();
+            mods_per_hand.sort_by_key(|(hand, _)| **hand as u8);
+            for (hand, mods) in mods_per_hand {
                 let mut resolved_mods_vec = Vec::new();
                 for mp in mods.iter() {
                     let layer_modifier_type = mods.layer_modifier_type();
                     match mp {
-                        ModifierLocation::Position(mp) => {
+                        ModifierLocation::Position(p) => {
                             let base_key_idx = *pos2layerkey_index
-                                .get(mp)
-                                .ok_or(format!("Modifier position '{:?}' not found", mp))
-                                .map_err(anyhow::Error::msg)?;
+                                .get(p)
+                                .ok_or_else(|| ModifierError::NotFound(i + 1, mp.clone()))?;
                             let mod_idx = *pos2mod_index
-                                .entry((layer_modifier_type, *mp))
+                                .entry((layer_modifier_type, *p))
                                 .or_insert_with(|| {
                                     let base_layerkey = &layerkeys[base_key_idx as usize];
                                     layerkeys.push(LayerKey::new(
                                         0,
                                         base_layerkey.key.clone(),
+                                        base_layerkey.key_index,
                                         base_layerkey.symbol,
                                         LayerModifiers::default(),
                                         base_layerkey.is_fixed,
                                         layer_modifier_type,
                                     ));
-                                    layerkey_to_key_index
-                                        .push(layerkey_to_key_index[base_key_idx as usize]);
-
                                     layerkey_index += 1;
                                     layerkey_index - 1
                                 });
@@ -302,8 +439,7 @@ impl Layout {
                         ModifierLocation::Symbol(c) => {
                             let base_key_idx = *char2layerkey_index
                                 .get(c)
-                                .ok_or(format!("Modifier char '{:?}' not found", c))
-                                .map_err(anyhow::Error::msg)?;
+                                .ok_or_else(|| ModifierError::NotFound(i + 1, mp.clone()))?;
                             let mod_idx = *char2mod_index
                                 .entry((layer_modifier_type, *c))
                                 .or_insert_with(|| {
@@ -311,14 +447,12 @@ impl Layout {
                                     layerkeys.push(LayerKey::new(
                                         base_layerkey.layer,
                                         base_layerkey.key.clone(),
+                                        base_layerkey.key_index,
                                         base_layerkey.symbol,
                                         base_layerkey.modifiers.clone(),
                                         base_layerkey.is_fixed,
                                         layer_modifier_type,
                                     ));
-                                    layerkey_to_key_index
-                                        .push(layerkey_to_key_index[base_key_idx as usize]);
-
                                     layerkey_index += 1;
                                     layerkey_index - 1
                                 });
@@ -327,12 +461,34 @@ impl Layout {
                         }
                     }
                 }
+                if let LayerModifierLocations::Hold(_) = mods {
+                    // a held key that also generates a symbol would be counted as both
+                    for mod_idx in resolved_mods_vec.iter() {
+                        let key_idx = layerkeys[*mod_idx as usize].key_index as usize;
+                        let printable = key_layers[key_idx]
+                            .iter()
+                            .map(|idx| &layerkeys[*idx as usize])
+                            .find(|lk| is_printable(lk.symbol));
+                        if let Some(lk) = printable {
+                            return Err(ModifierError::PrintableSymbol(
+                                i + 1,
+                                lk.key.matrix_position,
+                                lk.symbol,
+                            )
+                            .into());
+                        }
+                    }
+                }
                 let resolved_mods = match mods {
                     LayerModifierLocations::Hold(_) => LayerModifiers::Hold(resolved_mods_vec),
                     LayerModifierLocations::OneShot(_) => {
                         LayerModifiers::OneShot(resolved_mods_vec)
                     }
                     LayerModifierLocations::LongPress => LayerModifiers::LongPress,
+                    LayerModifierLocations::Lock(_) => LayerModifiers::Lock(resolved_mods_vec),
+                    LayerModifierLocations::Chord(_) => LayerModifiers::Chord(resolved_mods_vec),
+                    // the key of a mod-tap is held like any hold modifier
+                    LayerModifierLocations::ModTap(_) => LayerModifiers::Hold(resolved_mods_vec),
                 };
                 resolved_mods_per_hand.insert(*hand, resolved_mods);
             }
@@ -356,16 +512,63 @@ impl Layout {
         });
 
         let key_map = Self::gen_key_map(&layerkeys);
+        let duplicate_symbols = Self::gen_duplicate_symbols(&layerkeys, &key_map);
 
         Ok(Self {
             layerkeys,
             key_layers,
             keyboard,
-            layerkey_to_key_index,
             key_map,
+            multi_key_symbols: AHashMap::default(),
+            duplicate_symbols,
         })
     }
 
+    /// Cost of a [`LayerKey`]'s key plus the costs of the keys of its modifiers
+    fn cost_with_modifiers(layerkeys: &[LayerKey], layerkey: &LayerKey) -> f64 {
+        let modifier_cost: f64 = layerkey
+            .modifiers
+            .layerkey_indices()
+            .iter()
+            .map(|i| layerkeys[*i as usize].key.cost)
+            .sum();
+
+        layerkey.key.cost + modifier_cost
+    }
+
+    fn gen_duplicate_symbols(
+        layerkeys: &[LayerKey],
+        key_map: &Map<char, LayerKeyIndex>,
+    ) -> AHashMap<char, Vec<LayerKeyIndex>> {
+        let mut symbols: AHashMap<char, Vec<LayerKeyIndex>> = AHashMap::default();
+        layerkeys
+            .iter()
+            .enumerate()
+            .filter(|(_, lk)| lk.is_modifier.is_none())
+            .for_each(|(idx, lk)| {
+                symbols
+                    .entry(lk.symbol)
+                    .or_default()
+                    .push(idx as LayerKeyIndex)
+            });
+        symbols.retain(|_, indices| indices.len() > 1);
+
+        symbols.iter_mut().for_each(|(c, indices)| {
+            let cost = |idx: &LayerKeyIndex| {
+                Self::cost_with_modifiers(layerkeys, &layerkeys[*idx as usize])
+            };
+            let first = key_map[c];
+            indices.sort_by(|i1, i2| {
+                (*i1 != first)
+                    .cmp(&(*i2 != first))
+                    .then(cost(i1).partial_cmp(&cost(i2)).unwrap())
+                    .then(i1.cmp(i2))
+            });
+        });
+
+        symbols
+    }
+
     fn gen_key_map(layerkeys: &[LayerKey]) -> Map<char, LayerKeyIndex> {
         let mut m = Map::default();
         layerkeys
@@ -382,23 +585,9 @@ impl Layout {
                 let entry = m.entry(layerkey.symbol).or_insert(layerkey_index);
                 let entry_layerkey = &layerkeys[*entry as usize]; // is layerkey or existing one from map m
 
-                let entry_modifier_cost: f64 = entry_layerkey
-                    .modifiers
-                    .layerkey_indices()
-                    .iter()
-                    .map(|i| layerkeys[*i as usize].key.cost)
-                    .sum();
-
-                let new_modifier_cost: f64 = layerkey
-                    .modifiers
-                    .layerkey_indices()
-                    .iter()
-                    .map(|i| layerkeys[*i as usize].key.cost)
-                    .sum();
-
                 // NOTE: In contrast to ArneBab's version, here the layer costs are not multiplied by 3
-                let entry_cost = entry_layerkey.key.cost + entry_modifier_cost;
-                let new_cost = layerkey.key.cost + new_modifier_cost;
+                let entry_cost = Self::cost_with_modifiers(layerkeys, entry_layerkey);
+                let new_cost = Self::cost_with_modifiers(layerkeys, layerkey);
 
                 // if key already exists use the representation with lowest key cost
                 // if costs are identical, use lowest layer
@@ -413,6 +602,12 @@ impl Layout {
         m
     }
 
+    /// The validated geometry of the keyboard's keys
+    #[inline(always)]
+    pub fn geometry(&self) -> &KeyGeometry {
+        self.keyboard.geometry()
+    }
+
     /// Get a [`LayerKey`] for a given index
     #[inline(always)]
     pub fn get_layerkey(&self, layerkey_index: &LayerKeyIndex) -> &LayerKey {
@@ -434,7 +629,7 @@ impl Layout {
     /// Get the index of the "base" symbol (the one on the base layer, e.g. "A" -> "a") for a given [`LayerKeyIndex`]
     #[inline(always)]
     pub fn get_base_layerkey_index(&self, layerkey_index: &LayerKeyIndex) -> LayerKeyIndex {
-        let key_index: usize = self.layerkey_to_key_index[*layerkey_index as usize] as usize;
+        let key_index = self.get_layerkey(layerkey_index).key_index as usize;
         self.key_layers[key_index][0]
     }
 
@@ -453,11 +648,189 @@ impl Layout {
         (base, mods)
     }
 
-    /// If the layout has at least one layer configured as hold layer
+    /// If the layout has at least one layer configured as hold (or chord) layer
     pub fn has_hold_layers(&self) -> bool {
-        self.layerkeys
+        self.layerkeys.iter().any(|lk| {
+            std::matches!(
+                lk.modifiers,
+                LayerModifiers::Hold(_) | LayerModifiers::Chord(_)
+            )
+        })
+    }
+
+    /// Give display names to symbols (e.g. private-use-area characters standing for key actions
+    /// such as backspace or the arrow keys). The names are used in plots and in the output of
+    /// the evaluation instead of the symbols.
+    pub fn with_named_symbols(mut self, named_symbols: &AHashMap<char, String>) -> Self {
+        for lk in self.layerkeys.iter_mut() {
+            lk.name = named_symbols.get(&lk.symbol).cloned();
+        }
+
+        self
+    }
+
+    /// Declare symbols that are generated by multiple keys (on their base layer). The key used
+    /// for such a symbol is chosen by the ngram mapper depending on the symbol's policy.
+    pub fn with_multi_key_symbols(mut self, symbols: &[MultiKeySymbolYAML]) -> Result<Self> {
+        MultiKeySymbolYAML::validate_all(symbols)?;
+
+        for s in symbols {
+            let mut keys: Vec<(LayerKeyIndex, &LayerKey)> = self
+                .layerkeys
+                .iter()
+                .enumerate()
+                .filter(|(_, lk)| {
+                    lk.layer == 0 && lk.is_modifier.is_none() && lk.symbol == s.symbol
+                })
+                .map(|(idx, lk)| (idx as LayerKeyIndex, lk))
+                .collect();
+
+            if keys.len() < 2 {
+                return Err(MultiKeySymbolError::TooFewKeys(s.symbol, keys.len()).into());
+            }
+            if keys.iter().any(|(_, lk)| !lk.is_fixed) {
+                return Err(MultiKeySymbolError::NotFixed(s.symbol).into());
+            }
+
+            keys.sort_by(|(i1, lk1), (i2, lk2)| {
+                lk1.key
+                    .cost
+                    .partial_cmp(&lk2.key.cost)
+                    .unwrap()
+                    .then(i1.cmp(i2))
+            });
+            let keys = keys
+                .into_iter()
+                .map(|(idx, lk)| (idx, lk.key.hand))
+                .collect();
+
+            self.multi_key_symbols.insert(
+                s.symbol,
+                MultiKeySymbol {
+                    keys,
+                    policy: s.policy,
+                },
+            );
+        }
+
+        Ok(self)
+    }
+
+    /// The layout as typed on the mirrored keyboard (e.g. while holding a key that reflects the
+    /// keyboard for typing with one hand): The symbols of each key move to its partner in the
+    /// `axis_map` (see [`Keyboard::mirror_map`]), including the modifiers.
+    pub fn mirrored(&self, axis_map: &AHashMap<MatrixPosition, MatrixPosition>) -> Result<Self> {
+        let keys = self.keyboard.keys();
+        let key_indices: AHashMap<MatrixPosition, KeyIndex> = keys
             .iter()
-            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Hold(_)))
+            .enumerate()
+            .map(|(i, key)| (key.matrix_position, i as KeyIndex))
+            .collect();
+
+        // the key that the symbols of each key move to
+        let mut partners: Vec<KeyIndex> = Vec::with_capacity(keys.len());
+        let mut key_layers: Vec<Vec<LayerKeyIndex>> = vec![Vec::new(); keys.len()];
+        for (i, (key, layers)) in keys.iter().zip(self.key_layers.iter()).enumerate() {
+            let partner = match axis_map.get(&key.matrix_position) {
+                Some(p) => *key_indices
+                    .get(p)
+                    .ok_or(LayoutError::NoMirrorPartner(key.matrix_position))?,
+                None if layers.is_empty() => i as KeyIndex,
+                None => return Err(LayoutError::NoMirrorPartner(key.matrix_position).into()),
+            };
+            if !layers.is_empty() {
+                let partner_key = &keys[partner as usize];
+                if partner_key.is_blocked {
+                    return Err(LayoutError::BlockedKeySymbols(
+                        partner_key.matrix_position,
+                        layers
+                            .iter()
+                            .map(|idx| self.get_layerkey(idx).symbol)
+                            .collect(),
+                    )
+                    .into());
+                }
+                if !key_layers[partner as usize].is_empty() {
+                    return Err(
+                        LayoutError::DuplicateMirrorPartner(partner_key.matrix_position).into(),
+                    );
+                }
+                key_layers[partner as usize] = layers.clone();
+            }
+            partners.push(partner);
+        }
+
+        let mut layout = self.clone();
+        layout.key_layers = key_layers;
+        for lk in layout.layerkeys.iter_mut() {
+            lk.key_index = partners[lk.key_index as usize];
+            lk.key = keys[lk.key_index as usize].clone();
+        }
+        // the choice among symbols generated by multiple layerkeys depends on the key costs
+        layout.key_map = Self::gen_key_map(&layout.layerkeys);
+        layout.duplicate_symbols = Self::gen_duplicate_symbols(&layout.layerkeys, &layout.key_map);
+
+        // the keys of multi-key symbols are sorted by their (new) costs
+        let layerkeys = &layout.layerkeys;
+        for s in layout.multi_key_symbols.values_mut() {
+            s.keys.iter_mut().for_each(|(idx, hand)| {
+                *hand = layerkeys[*idx as usize].key.hand;
+            });
+            s.keys.sort_by(|(i1, _), (i2, _)| {
+                let cost1 = layerkeys[*i1 as usize].key.cost;
+                let cost2 = layerkeys[*i2 as usize].key.cost;
+                cost1.partial_cmp(&cost2).unwrap().then(i1.cmp(i2))
+            });
+        }
+
+        Ok(layout)
+    }
+
+    /// If the layout has at least one symbol that is generated by multiple keys
+    pub fn has_multi_key_symbols(&self) -> bool {
+        !self.multi_key_symbols.is_empty()
+    }
+
+    /// Get the [`MultiKeySymbol`] if the given [`LayerKeyIndex`] is the one a multi-key symbol
+    /// is mapped to by default (see [`Layout::get_layerkey_index_for_symbol`])
+    #[inline(always)]
+    pub fn get_multi_key_symbol(&self, layerkey_index: &LayerKeyIndex) -> Option<&MultiKeySymbol> {
+        let symbol = self.get_layerkey(layerkey_index).symbol;
+        self.multi_key_symbols
+            .get(&symbol)
+            .filter(|_| self.key_map.get(&symbol) == Some(layerkey_index))
+    }
+
+    /// Cost of generating the symbol of a [`LayerKey`]: the cost of its key plus the costs of the
+    /// keys of its modifiers
+    pub fn layerkey_cost(&self, layerkey_index: &LayerKeyIndex) -> f64 {
+        Self::cost_with_modifiers(&self.layerkeys, self.get_layerkey(layerkey_index))
+    }
+
+    /// If the layout generates at least one symbol with multiple [`LayerKey`]s (that is not
+    /// declared as multi-key symbol)
+    pub fn has_duplicate_symbols(&self) -> bool {
+        self.duplicate_symbols
+            .keys()
+            .any(|c| !self.multi_key_symbols.contains_key(c))
+    }
+
+    /// Get all [`LayerKeyIndex`]es generating the same symbol if the given one is the one the
+    /// symbol is mapped to by default (see [`Layout::get_layerkey_index_for_symbol`]) and the
+    /// symbol is generated by multiple [`LayerKey`]s. The default one comes first, the others
+    /// follow by ascending cost (see [`Layout::layerkey_cost`]). Multi-key symbols (see
+    /// [`Layout::get_multi_key_symbol`]) are excluded.
+    #[inline(always)]
+    pub fn get_duplicate_symbol(&self, layerkey_index: &LayerKeyIndex) -> Option<&[LayerKeyIndex]> {
+        let symbol = self.get_layerkey(layerkey_index).symbol;
+        if self.multi_key_symbols.contains_key(&symbol) {
+            return None;
+        }
+
+        self.duplicate_symbols
+            .get(&symbol)
+            .filter(|indices| indices.first() == Some(layerkey_index))
+            .map(|indices| indices.as_slice())
     }
 
     /// If the layout has at least one layer configured as one-shot layer
@@ -467,7 +840,34 @@ impl Layout {
             .any(|lk| std::matches!(lk.modifiers, LayerModifiers::OneShot(_)))
     }
 
-    /// Plot a graphical representation of a layer
+    /// If the layout has at least one layer configured as lock layer
+    pub fn has_lock_layers(&self) -> bool {
+        self.layerkeys
+            .iter()
+            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Lock(_)))
+    }
+
+    /// Get the keys toggling the layer of a given [`LayerKeyIndex`], if it belongs to a lock layer
+    #[inline(always)]
+    pub fn lock_toggles(&self, layerkey_index: &LayerKeyIndex) -> Option<&[LayerKeyIndex]> {
+        match &self.get_layerkey(layerkey_index).modifiers {
+            LayerModifiers::Lock(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Number of layers of the layout (the base layer and all layers reachable with modifiers
+    /// that contain at least one symbol)
+    pub fn n_layers(&self) -> usize {
+        self.layerkeys
+            .iter()
+            .map(|k| k.layer as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Plot a graphical representation of a layer. Symbols of keys that may be pressed with an
+    /// alternate finger are underlined.
     pub fn plot_layer(&self, layer: usize) -> String {
         let fmt_char = |c: char| -> char {
             match c {
@@ -493,10 +893,16 @@ impl Layout {
                     " ".to_string()
                 } else {
                     // if no symbol is in layers, show last layers value if it is fixed
-                    let mut s = fmt_char(k.symbol).to_string();
+                    let mut s = match &k.name {
+                        Some(name) => name.clone(),
+                        None => isolate_rtl(&fmt_char(k.symbol).to_string()),
+                    };
                     if !k.is_fixed {
                         s = s.yellow().bold().to_string();
                     }
+                    if k.key.alternate_finger.is_some() {
+                        s = s.underline().to_string();
+                    }
                     s
                 }
             })
@@ -531,4 +937,209 @@ impl Layout {
             .map(|k| k.symbol.to_string())
             .collect()
     }
+
+    /// Fraction of non-fixed keys that generate the same base-layer symbol in both layouts
+    /// (1.0 for identical layouts).
+    ///
+    /// The comparison is based on the keys of the keyboard, not on the layout strings. Hence,
+    /// differences in how the layouts were specified (e.g. whitespace or the generator used)
+    /// do not matter. Both layouts are expected to use the same keyboard.
+    pub fn similarity(&self, other: &Layout) -> f64 {
+        let base_symbol = |layout: &Layout, layers: &[LayerKeyIndex]| -> Option<char> {
+            layers
+                .first()
+                .map(|lk| layout.get_layerkey(lk))
+                .filter(|k| !k.is_fixed)
+                .map(|k| k.symbol)
+        };
+
+        let (n_keys, n_same) = self
+            .key_layers
+            .iter()
+            .zip(other.key_layers.iter())
+            .filter_map(
+                |(l1, l2)| match (base_symbol(self, l1), base_symbol(other, l2)) {
+                    (None, None) => None,
+                    (s1, s2) => Some(s1 == s2),
+                },
+            )
+            .fold((0, 0), |(n_keys, n_same), same| {
+                (n_keys + 1, n_same + same as usize)
+            });
+
+        if n_keys == 0 {
+            return 1.0;
+        }
+
+        n_same as f64 / n_keys as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::LayoutConfig, layout_generator::LayoutGenerator,
+        neo_layout_generator::NeoLayoutGenerator,
+    };
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+    const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+    fn generate(modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>) -> Result<Layout> {
+        let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+        layout_config.base_layout.modifiers = modifiers;
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard).generate(LAYOUT)
+    }
+
+    fn hold(positions: &[(u8, u8)]) -> LayerModifierLocations {
+        LayerModifierLocations::Hold(
+            positions
+                .iter()
+                .map(|(c, r)| ModifierLocation::Position(MatrixPosition(*c, *r)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn too_many_modifiers() {
+        let modifiers = vec![
+            [(Hand::Left, hold(&[(0, 3)]))].iter().cloned().collect(),
+            [
+                (Hand::Left, hold(&[(0, 3)])),
+                (
+                    Hand::Right,
+                    hold(&[(18, 3), (18, 2), (16, 4), (15, 4), (14, 4)]),
+                ),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+        ];
+
+        let err = generate(modifiers).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ModifierError>(),
+            Some(&ModifierError::TooMany(2, Hand::Right, 5))
+        );
+        assert!(err.to_string().contains("layer 2"), "{}", err);
+    }
+
+    #[test]
+    fn duplicate_modifiers() {
+        let modifiers = vec![[(Hand::Left, hold(&[(0, 3), (1, 3), (0, 3)]))]
+            .iter()
+            .cloned()
+            .collect()];
+
+        let err = generate(modifiers).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ModifierError>(),
+            Some(&ModifierError::Duplicate(
+                1,
+                Hand::Left,
+                ModifierLocation::Position(MatrixPosition(0, 3))
+            ))
+        );
+    }
+
+    #[test]
+    fn chord_needs_two_keys() {
+        let chord =
+            LayerModifierLocations::Chord(vec![ModifierLocation::Position(MatrixPosition(0, 3))]);
+        let modifiers = vec![[(Hand::Left, chord)].iter().cloned().collect()];
+
+        let err = generate(modifiers).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ModifierError>(),
+            Some(&ModifierError::ChordTooSmall(1, Hand::Left, 1))
+        );
+    }
+
+    #[test]
+    fn printable_hold_modifier() {
+        let layout_config =
+            LayoutConfig::from_yaml("tests/fixtures/printable_modifier.yml").unwrap();
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        let err = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+            .generate("ac")
+            .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<ModifierError>(),
+            Some(&ModifierError::PrintableSymbol(
+                1,
+                MatrixPosition(2, 0),
+                ' '
+            ))
+        );
+        assert!(err.to_string().contains("layer 1"), "{}", err);
+    }
+
+    #[test]
+    fn too_many_layers() {
+        let modifiers = vec![AHashMap::default(); u8::MAX as usize];
+
+        assert_eq!(
+            LayerModifierLocations::validate_all(&modifiers),
+            Err(ModifierError::TooManyLayers(u8::MAX as usize))
+        );
+    }
+
+    /// Random modifier configurations either result in a valid layout or in an error.
+    #[test]
+    fn random_modifiers_do_not_panic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let symbols: Vec<char> = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j ⇧€@".chars().collect();
+
+        for _ in 0..300 {
+            let mut modifiers: Vec<AHashMap<Hand, LayerModifierLocations>> = Vec::new();
+            for _ in 0..rng.gen_range(0..8) {
+                let mut mods_per_hand = AHashMap::default();
+                for hand in [Hand::Left, Hand::Right].iter() {
+                    if rng.gen_bool(0.2) {
+                        continue;
+                    }
+                    let mut locations = Vec::new();
+                    for _ in 0..rng.gen_range(0..7) {
+                        locations.push(match rng.gen_bool(0.7) {
+                            true => ModifierLocation::Position(MatrixPosition(
+                                rng.gen_range(0..22),
+                                rng.gen_range(0..7),
+                            )),
+                            false => {
+                                ModifierLocation::Symbol(symbols[rng.gen_range(0..symbols.len())])
+                            }
+                        });
+                    }
+                    let locations = match rng.gen_range(0..3) {
+                        0 => LayerModifierLocations::Hold(locations),
+                        1 => LayerModifierLocations::OneShot(locations),
+                        _ => LayerModifierLocations::LongPress,
+                    };
+                    mods_per_hand.insert(*hand, locations);
+                }
+                modifiers.push(mods_per_hand);
+            }
+            let too_many = modifiers
+                .iter()
+                .flat_map(|m| m.values())
+                .any(|l| l.iter().count() > MAX_MODIFIERS_PER_LAYER);
+
+            match generate(modifiers) {
+                Ok(layout) => {
+                    assert!(!too_many);
+                    for i in 0..layout.layerkeys.len() {
+                        let (_, mods) = layout.resolve_modifiers(&(i as LayerKeyIndex));
+                        assert!(mods.layerkey_indices().len() <= MAX_MODIFIERS_PER_LAYER);
+                    }
+                    layout.plot();
+                }
+                Err(e) => assert!(!e.to_string().is_empty()),
+            }
+        }
+    }
 }
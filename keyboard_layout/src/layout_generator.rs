@@ -3,11 +3,21 @@
 
 use core::fmt;
 
+use crate::keycap_group::KeycapGroup;
 use crate::layout::Layout;
 use anyhow::Result;
 
 pub trait LayoutGenerator: Send + Sync + LayoutGeneratorClone + fmt::Debug {
     fn generate(&self, layout_keys: &str) -> Result<Layout>;
+
+    /// The symbols that make up the layout strings (in arbitrary order).
+    fn permutable_keys(&self) -> Vec<char>;
+
+    /// Groups of symbols that have to stay on a set of positions of the layout string
+    /// (see [`crate::keycap_group`]). Generators that do not support them return no groups.
+    fn keycap_groups(&self) -> Vec<KeycapGroup> {
+        Vec::new()
+    }
 }
 
 impl Clone for Box<dyn LayoutGenerator> {
@@ -7,12 +7,19 @@
 //! and other associated properties.
 
 pub mod config;
+pub mod geometry;
 pub mod grouped_layout_generator;
 pub mod key;
+pub mod key_cost_tuning;
 pub mod keyboard;
+pub mod keycap_group;
 pub mod layout;
 pub mod layout_generator;
+pub mod mod_tap;
+pub mod multi_key_symbol;
 pub mod neo_layout_generator;
+pub mod script;
+pub mod split_keyboard;
 
 #[cfg(test)]
 mod tests {
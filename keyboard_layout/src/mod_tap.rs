@@ -0,0 +1,140 @@
+//! This module provides mod-taps (e.g. home-row modifiers): keys that generate their symbol when
+//! tapped and act as the modifier of a layer when held. For the layer, the mod-tap replaces the
+//! modifiers of its hand.
+
+use crate::key::Hand;
+use crate::keyboard::{KeyIndex, Keyboard};
+use crate::layout::{LayerModifierLocations, ModifierLocation};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ModTapError {
+    #[error("Invalid mod-tap {0:?}: Layer {1} has no modifiers (needs to be between 1 and {2})")]
+    UnknownLayer(ModifierLocation, u8, usize),
+    #[error("Invalid mod-taps: Key {0:?} is declared more than once")]
+    Duplicate(ModifierLocation),
+    #[error("Invalid mod-tap {0:?}: The key is not part of the layout")]
+    NotFound(ModifierLocation),
+}
+
+/// A mod-tap as given in the layout configuration.
+///
+/// Corresponds to (parts of) a YAML configuration file.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub struct ModTapYAML {
+    /// The key generating a symbol when tapped, given by its matrix position or its symbol (in
+    /// which case the mod-tap moves with the symbol during an optimization)
+    pub tap: ModifierLocation,
+    /// The layer (by its index, `1` being the shift layer) that is accessed when holding the key
+    pub hold: u8,
+}
+
+impl ModTapYAML {
+    /// Checks that each mod-tap accesses a layer with modifiers and that no key is declared more
+    /// than once.
+    pub fn validate_all(mod_taps: &[ModTapYAML], n_layers: usize) -> Result<(), ModTapError> {
+        let mut seen: Vec<&ModifierLocation> = Vec::new();
+        for mod_tap in mod_taps {
+            if mod_tap.hold == 0 || mod_tap.hold as usize > n_layers {
+                return Err(ModTapError::UnknownLayer(
+                    mod_tap.tap.clone(),
+                    mod_tap.hold,
+                    n_layers,
+                ));
+            }
+            if seen.contains(&&mod_tap.tap) {
+                return Err(ModTapError::Duplicate(mod_tap.tap.clone()));
+            }
+            seen.push(&mod_tap.tap);
+        }
+
+        Ok(())
+    }
+
+    /// The hand of the key, given the symbols of each key of the layout (in the order of the
+    /// keyboard's keys). Symbols are looked up on the lowest layer first.
+    fn hand(&self, key_chars: &[Vec<char>], keyboard: &Keyboard) -> Option<Hand> {
+        let key_idx = match &self.tap {
+            ModifierLocation::Position(p) => keyboard.geometry().key_at(*p),
+            ModifierLocation::Symbol(c) => {
+                let n_layers = key_chars.iter().map(|chars| chars.len()).max()?;
+                (0..n_layers).find_map(|layer| {
+                    key_chars
+                        .iter()
+                        .position(|chars| chars.get(layer) == Some(c))
+                        .map(|i| i as KeyIndex)
+                })
+            }
+        }?;
+
+        Some(keyboard.geometry().hand(key_idx))
+    }
+
+    /// The modifiers of the layers with the mod-taps applied: Each mod-tap replaces the
+    /// modifiers of its hand for the layer it accesses. If multiple mod-taps of the same hand
+    /// access a layer, the first one is used.
+    pub fn apply_all(
+        mod_taps: &[ModTapYAML],
+        modifiers: &[AHashMap<Hand, LayerModifierLocations>],
+        key_chars: &[Vec<char>],
+        keyboard: &Keyboard,
+    ) -> Result<Vec<AHashMap<Hand, LayerModifierLocations>>, ModTapError> {
+        let n_layers = modifiers.len();
+        let mut modifiers = modifiers.to_vec();
+        let mut applied: Vec<(u8, Hand)> = Vec::new();
+        for mod_tap in mod_taps {
+            let hand = mod_tap
+                .hand(key_chars, keyboard)
+                .ok_or_else(|| ModTapError::NotFound(mod_tap.tap.clone()))?;
+            if applied.contains(&(mod_tap.hold, hand)) {
+                continue;
+            }
+            applied.push((mod_tap.hold, hand));
+
+            let layer_modifiers = (mod_tap.hold as usize)
+                .checked_sub(1)
+                .and_then(|i| modifiers.get_mut(i))
+                .ok_or_else(|| {
+                    ModTapError::UnknownLayer(mod_tap.tap.clone(), mod_tap.hold, n_layers)
+                })?;
+            layer_modifiers.insert(hand, LayerModifierLocations::ModTap(mod_tap.tap.clone()));
+        }
+
+        Ok(modifiers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::MatrixPosition;
+
+    fn mod_tap(tap: ModifierLocation, hold: u8) -> ModTapYAML {
+        ModTapYAML { tap, hold }
+    }
+
+    #[test]
+    fn validate() {
+        let a = ModifierLocation::Symbol('a');
+        let pos = ModifierLocation::Position(MatrixPosition(5, 2));
+
+        assert!(
+            ModTapYAML::validate_all(&[mod_tap(a.clone(), 1), mod_tap(pos.clone(), 3)], 3).is_ok()
+        );
+        assert_eq!(
+            ModTapYAML::validate_all(&[mod_tap(a.clone(), 4)], 3),
+            Err(ModTapError::UnknownLayer(a.clone(), 4, 3))
+        );
+        assert_eq!(
+            ModTapYAML::validate_all(&[mod_tap(a.clone(), 0)], 3),
+            Err(ModTapError::UnknownLayer(a.clone(), 0, 3))
+        );
+        assert_eq!(
+            ModTapYAML::validate_all(&[mod_tap(pos.clone(), 1), mod_tap(pos.clone(), 2)], 3),
+            Err(ModTapError::Duplicate(pos))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! This module provides multi-key symbols: symbols that are generated by several (fixed) keys,
+//! e.g. the space on split keyboards with a space key for each thumb. Which of the keys is used
+//! for typing the symbol depends on a policy and on the preceding key in an ngram.
+
+use crate::key::Hand;
+use crate::layout::LayerKeyIndex;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MultiKeySymbolError {
+    #[error("Invalid multi-key symbol '{0}': It needs to be on the base layer of at least two keys, but is on {1}")]
+    TooFewKeys(char, usize),
+    #[error("Invalid multi-key symbol '{0}': All keys generating it need to be fixed")]
+    NotFixed(char),
+    #[error("Invalid multi-key symbols: Symbol '{0}' is declared more than once")]
+    Duplicate(char),
+}
+
+/// Policy for choosing one of the keys generating a multi-key symbol.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MultiKeyPolicy {
+    /// Use a key of the other hand than the preceding key. Without a preceding key (e.g. for
+    /// unigrams or at the start of an ngram), the weight is split equally among all keys.
+    Alternate,
+    /// Always use a key of the left hand.
+    FixedLeft,
+    /// Always use a key of the right hand.
+    FixedRight,
+    /// Split the weight equally among all keys.
+    SplitWeight,
+}
+
+/// A multi-key symbol as given in the layout configuration.
+///
+/// Corresponds to (parts of) a YAML configuration file.
+#[derive(Clone, Deserialize, Debug)]
+pub struct MultiKeySymbolYAML {
+    /// The symbol (needs to be on the base layer of all keys that generate it)
+    pub symbol: char,
+    /// How to choose the key generating the symbol
+    pub policy: MultiKeyPolicy,
+}
+
+impl MultiKeySymbolYAML {
+    /// Checks that no symbol is declared more than once.
+    pub fn validate_all(symbols: &[MultiKeySymbolYAML]) -> Result<(), MultiKeySymbolError> {
+        let mut seen: Vec<char> = Vec::new();
+        for s in symbols {
+            if seen.contains(&s.symbol) {
+                return Err(MultiKeySymbolError::Duplicate(s.symbol));
+            }
+            seen.push(s.symbol);
+        }
+
+        Ok(())
+    }
+}
+
+/// A multi-key symbol in terms of the [`LayerKeyIndex`]es of a layout.
+#[derive(Clone, Debug)]
+pub struct MultiKeySymbol {
+    /// The keys generating the symbol along with their hands (sorted by key cost, ascending)
+    pub keys: Vec<(LayerKeyIndex, Hand)>,
+    pub policy: MultiKeyPolicy,
+}
+
+impl MultiKeySymbol {
+    /// The first key of the given hand (or the first key overall if there is none).
+    fn key_of_hand(&self, hand: Hand) -> LayerKeyIndex {
+        self.keys
+            .iter()
+            .find(|(_, h)| *h == hand)
+            .unwrap_or(&self.keys[0])
+            .0
+    }
+
+    /// All keys, each with an equal fraction of the weight.
+    fn split(&self) -> Vec<(LayerKeyIndex, f64)> {
+        let fraction = 1.0 / self.keys.len() as f64;
+        self.keys.iter().map(|(k, _)| (*k, fraction)).collect()
+    }
+
+    /// Choose the key(s) for typing the symbol after a key of hand `preceding` (if any). Returns
+    /// the keys with the fraction of the weight assigned to each.
+    pub fn choose(&self, preceding: Option<Hand>) -> Vec<(LayerKeyIndex, f64)> {
+        match (self.policy, preceding) {
+            (MultiKeyPolicy::Alternate, Some(hand)) => {
+                vec![(self.key_of_hand(hand.other()), 1.0)]
+            }
+            (MultiKeyPolicy::Alternate, None) => self.split(),
+            (MultiKeyPolicy::FixedLeft, _) => vec![(self.key_of_hand(Hand::Left), 1.0)],
+            (MultiKeyPolicy::FixedRight, _) => vec![(self.key_of_hand(Hand::Right), 1.0)],
+            (MultiKeyPolicy::SplitWeight, _) => self.split(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(policy: MultiKeyPolicy) -> MultiKeySymbol {
+        MultiKeySymbol {
+            keys: vec![(7, Hand::Left), (9, Hand::Right)],
+            policy,
+        }
+    }
+
+    #[test]
+    fn choose_keys() {
+        let alternate = symbol(MultiKeyPolicy::Alternate);
+        assert_eq!(alternate.choose(Some(Hand::Left)), vec![(9, 1.0)]);
+        assert_eq!(alternate.choose(Some(Hand::Right)), vec![(7, 1.0)]);
+        assert_eq!(alternate.choose(None), vec![(7, 0.5), (9, 0.5)]);
+
+        let fixed_left = symbol(MultiKeyPolicy::FixedLeft);
+        assert_eq!(fixed_left.choose(Some(Hand::Left)), vec![(7, 1.0)]);
+        assert_eq!(fixed_left.choose(None), vec![(7, 1.0)]);
+
+        let fixed_right = symbol(MultiKeyPolicy::FixedRight);
+        assert_eq!(fixed_right.choose(Some(Hand::Right)), vec![(9, 1.0)]);
+
+        let split = symbol(MultiKeyPolicy::SplitWeight);
+        assert_eq!(split.choose(Some(Hand::Left)), vec![(7, 0.5), (9, 0.5)]);
+    }
+}
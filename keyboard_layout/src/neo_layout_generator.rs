@@ -1,7 +1,10 @@
-use crate::key::Hand;
+use crate::key::{Hand, Key, MatrixPosition};
 use crate::keyboard::Keyboard;
-use crate::layout::{LayerModifierLocations, Layout};
+use crate::keycap_group::{KeycapGroup, KeycapGroupYAML};
+use crate::layout::{LayerModifierLocations, Layout, ModifierLocation};
 use crate::layout_generator::LayoutGenerator;
+use crate::mod_tap::ModTapYAML;
+use crate::multi_key_symbol::MultiKeySymbolYAML;
 
 use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
@@ -17,10 +20,88 @@ pub enum LayoutError {
     MissingChars(String),
     #[error("Invalid keyboard layout: Unsupported characters in provided layout (not in first level of `base_layout` and `fixed_keys` with value `false`): '{0}'")]
     UnsupportedChars(String),
+    #[error("Invalid base layout: Unlocked position {0:?} is not a key of the keyboard")]
+    UnknownUnlockedPosition(MatrixPosition),
+    #[error("Invalid base layout: Unlocked position {0:?} is a modifier")]
+    UnlockedModifier(MatrixPosition),
+    #[error("Invalid base layout: Symbols '{1}' are on the blocked key {0:?} (set `drop_blocked_symbols` to drop them)")]
+    BlockedKeySymbols(MatrixPosition, String),
+    #[error("Invalid base layout: Modifier position {0:?} is blocked")]
+    BlockedModifier(MatrixPosition),
     #[error(
-        "Invalid base layout: Not the same number of `keys` ({0}) as entries in `fixed_keys` ({1})"
+        "Invalid keyboard layout: Symbols '{0}' are on keys that are blocked on this keyboard"
     )]
-    WrongKeyNumber(usize, usize),
+    BlockedSymbols(String),
+    #[error("Invalid mirror: The key at {0:?} has no mirror partner")]
+    NoMirrorPartner(MatrixPosition),
+    #[error("Invalid mirror: Multiple keys are mirrored to {0:?}")]
+    DuplicateMirrorPartner(MatrixPosition),
+}
+
+/// Mismatches within a base layout or between a base layout and its keyboard. The variants hold
+/// the location of the mismatch, so that frontends can point to it.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BaseLayoutError {
+    #[error(
+        "Invalid base layout: `{attribute}` has {found} rows, but `{reference}` has {expected}"
+    )]
+    RowNumber {
+        attribute: &'static str,
+        reference: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Invalid base layout: Row {row} of `{attribute}` has {found} entries, but the same row of `{reference}` has {expected}")]
+    RowLength {
+        attribute: &'static str,
+        reference: &'static str,
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Invalid base layout: Symbol '{symbol}' is on the permutable keys {first:?} and {second:?} in layer {layer}")]
+    DuplicateSymbol {
+        layer: usize,
+        symbol: char,
+        first: MatrixPosition,
+        second: MatrixPosition,
+    },
+    #[error("Invalid base layout: The key at {position:?} is a modifier of layer {layer}, but not fixed")]
+    PermutableModifier {
+        layer: usize,
+        position: MatrixPosition,
+    },
+}
+
+/// Makes sure that `rows` has as many rows as `reference_rows` and that each row has the
+/// expected length.
+fn check_rows<T>(
+    attribute: &'static str,
+    rows: &[Vec<T>],
+    reference: &'static str,
+    reference_rows: &[usize],
+) -> Result<(), BaseLayoutError> {
+    if rows.len() != reference_rows.len() {
+        return Err(BaseLayoutError::RowNumber {
+            attribute,
+            reference,
+            expected: reference_rows.len(),
+            found: rows.len(),
+        });
+    }
+    for (row, (r, expected)) in rows.iter().zip(reference_rows.iter()).enumerate() {
+        if r.len() != *expected {
+            return Err(BaseLayoutError::RowLength {
+                attribute,
+                reference,
+                row,
+                expected: *expected,
+                found: r.len(),
+            });
+        }
+    }
+
+    Ok(())
 }
 
 /// A collection of data (configuration) regarding the Neo layout (and its family)
@@ -34,22 +115,168 @@ pub struct BaseLayoutYAML {
     pub fixed_layers: Vec<u8>,
     pub modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
     pub grouped_layers: u8,
+    /// Symbols that have to stay on a set of keys as a group (e.g. printed on the keycaps)
+    #[serde(default)]
+    pub keycap_groups: Vec<KeycapGroupYAML>,
+    /// Symbols that are generated by multiple (fixed) keys, e.g. space on split keyboards
+    #[serde(default)]
+    pub multi_key_symbols: Vec<MultiKeySymbolYAML>,
+    /// Matrix positions of fixed keys that are permutable nevertheless (e.g. thumb keys holding
+    /// whitespace that may take letters as well)
+    #[serde(default)]
+    pub unlocked_positions: Vec<MatrixPosition>,
+    /// Whether the symbols on blocked keys (see `blocked_positions` of the keyboard) are dropped
+    /// instead of being an error
+    #[serde(default)]
+    pub drop_blocked_symbols: bool,
+    /// Keys that generate their symbol when tapped and access a layer when held (e.g. home-row
+    /// modifiers)
+    #[serde(default)]
+    pub mod_taps: Vec<ModTapYAML>,
+    /// Names to display instead of symbols that are not readable on their own (e.g. "⌫" for a
+    /// private-use-area character standing for backspace in the ngrams)
+    #[serde(default)]
+    pub named_symbols: AHashMap<char, String>,
 }
 
 impl BaseLayoutYAML {
     /// Checks the [`KeyboardYAML`] for common errors.
     pub fn validate(&self) -> Result<()> {
-        let flat_keys = self.keys.concat();
-        let flat_fixed_keys = self.fixed_keys.concat();
-
         // Make sure that all settings that should have the same number of elements
         // do in fact have the same number of elements.
-        if flat_keys.len() != flat_fixed_keys.len() {
-            return Err(LayoutError::WrongKeyNumber(flat_keys.len(), flat_fixed_keys.len()).into());
+        let row_lengths: Vec<usize> = self.keys.iter().map(|row| row.len()).collect();
+        check_rows("fixed_keys", &self.fixed_keys, "keys", &row_lengths)?;
+
+        LayerModifierLocations::validate_all(&self.modifiers)?;
+        KeycapGroupYAML::validate_all(&self.keycap_groups)?;
+        MultiKeySymbolYAML::validate_all(&self.multi_key_symbols)?;
+        ModTapYAML::validate_all(&self.mod_taps, self.modifiers.len())?;
+
+        // the keys of modifiers given by their position must not be permuted
+        for p in self.unlocked_positions.iter() {
+            let is_modifier = self.modifiers.iter().flat_map(|m| m.values()).any(|m| {
+                m.iter()
+                    .any(|location| *location == ModifierLocation::Position(*p))
+            });
+            if is_modifier {
+                return Err(LayoutError::UnlockedModifier(*p).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the base layout against the keys of its keyboard (with given number of keys per
+    /// row): Each row needs to have as many entries as the keyboard's row, symbols may not occur
+    /// on multiple permutable keys within a layer, and keys of modifiers (given by their position)
+    /// need to be fixed.
+    pub fn validate_keys(
+        &self,
+        row_lengths: &[usize],
+        keys: &[Key],
+    ) -> Result<(), BaseLayoutError> {
+        check_rows("keys", &self.keys, "matrix_positions", row_lengths)?;
+
+        let fixed_keys = self.flat_fixed_keys(keys);
+        let mut seen: AHashMap<(usize, char), MatrixPosition> = AHashMap::default();
+        for ((layers, key), _) in self
+            .keys
+            .iter()
+            .flatten()
+            .zip(keys.iter())
+            .zip(fixed_keys.iter())
+            .filter(|(_, fixed)| !**fixed)
+        {
+            for (layer, symbol) in layers
+                .iter()
+                .enumerate()
+                .filter_map(|(layer, l)| Some((layer, l.chars().next()?)))
+            {
+                if let Some(first) = seen.insert((layer, symbol), key.matrix_position) {
+                    return Err(BaseLayoutError::DuplicateSymbol {
+                        layer,
+                        symbol,
+                        first,
+                        second: key.matrix_position,
+                    });
+                }
+            }
+        }
+
+        for (i, mods_per_hand) in self.modifiers.iter().enumerate() {
+            // sort by hand for deterministic error messages
+            let mut hands: Vec<&Hand> = mods_per_hand.keys().collect();
+            hands.sort_by_key(|hand| **hand == Hand::Right);
+            for hand in hands {
+                let mods = &mods_per_hand[hand];
+                // the keys of chords and mod-taps generate symbols themselves
+                if mods.layer_modifier_type().is_chord() || mods.layer_modifier_type().is_mod_tap()
+                {
+                    continue;
+                }
+                for location in mods.iter() {
+                    if let ModifierLocation::Position(p) = location {
+                        let is_fixed = keys
+                            .iter()
+                            .zip(fixed_keys.iter())
+                            .find(|(key, _)| key.matrix_position == *p)
+                            .map_or(true, |(_, fixed)| *fixed);
+                        if !is_fixed {
+                            return Err(BaseLayoutError::PermutableModifier {
+                                layer: i + 1,
+                                position: *p,
+                            });
+                        }
+                    }
+                }
+            }
         }
 
         Ok(())
     }
+
+    /// Whether each key (in the order of the keyboard's keys) is fixed, considering the
+    /// `unlocked_positions`. Blocked keys are always fixed.
+    pub fn flat_fixed_keys(&self, keys: &[Key]) -> Vec<bool> {
+        self.fixed_keys
+            .iter()
+            .flatten()
+            .zip(keys.iter())
+            .map(|(fixed, key)| {
+                key.is_blocked
+                    || (*fixed && !self.unlocked_positions.contains(&key.matrix_position))
+            })
+            .collect()
+    }
+
+    /// The symbols of each key's layers (in the order of the keyboard's keys). Blocked keys have
+    /// no symbols (the ones given for them are dropped).
+    pub fn flat_symbols(&self, keys: &[Key]) -> Vec<Vec<char>> {
+        self.keys
+            .iter()
+            .flatten()
+            .zip(keys.iter())
+            .map(|(layers, key)| match key.is_blocked {
+                true => Vec::new(),
+                false => layers.iter().filter_map(|l| l.chars().next()).collect(),
+            })
+            .collect()
+    }
+
+    /// First-layer symbols of blocked keys that would be permutable if the keys were not blocked.
+    pub fn blocked_symbols(&self, keys: &[Key]) -> Vec<char> {
+        self.keys
+            .iter()
+            .flatten()
+            .zip(self.fixed_keys.iter().flatten())
+            .zip(keys.iter())
+            .filter(|((_, fixed), key)| {
+                key.is_blocked
+                    && (!**fixed || self.unlocked_positions.contains(&key.matrix_position))
+            })
+            .filter_map(|((layers, _), _)| layers.first().and_then(|l| l.chars().next()))
+            .collect()
+    }
 }
 /// Provides functionalities for generating Neo layout variants from given string representations
 /// of their base layer.
@@ -60,19 +287,22 @@ pub struct NeoLayoutGenerator {
     permutable_key_map: AHashMap<char, u8>,
     fixed_layers: Vec<u8>,
     modifiers: Vec<AHashMap<Hand, LayerModifierLocations>>,
+    keycap_groups: Vec<KeycapGroup>,
+    multi_key_symbols: Vec<MultiKeySymbolYAML>,
+    mod_taps: Vec<ModTapYAML>,
+    named_symbols: AHashMap<char, String>,
+    /// Symbols of blocked keys that are not part of the layouts
+    blocked_symbols: AHashSet<char>,
+    drop_blocked_symbols: bool,
     keyboard: Arc<Keyboard>,
 }
 
 impl NeoLayoutGenerator {
     /// Generate a [`NeoLayoutGenerator`] from a [`BaseLayoutYAML`] object
     pub fn from_object(base: BaseLayoutYAML, keyboard: Arc<Keyboard>) -> Self {
-        let base_layout_symbols: Vec<Vec<char>> = base
-            .keys
-            .iter()
-            .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
-            .collect();
-        let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
+        let base_layout_symbols = base.flat_symbols(keyboard.keys());
+        let fixed_keys = base.flat_fixed_keys(keyboard.keys());
+        let blocked_symbols = base.blocked_symbols(keyboard.keys()).into_iter().collect();
 
         let mut permutable_key_map: AHashMap<char, u8> = AHashMap::default();
         base_layout_symbols
@@ -86,12 +316,39 @@ impl NeoLayoutGenerator {
                 }
             });
 
+        // positions of the keycap groups in terms of the layout string (non-fixed keys only)
+        let layout_string_positions: Vec<_> = keyboard
+            .keys()
+            .iter()
+            .zip(fixed_keys.iter())
+            .filter(|(_, fixed)| !**fixed)
+            .map(|(key, _)| key.matrix_position)
+            .collect();
+        let keycap_groups = base
+            .keycap_groups
+            .iter()
+            .map(|group| KeycapGroup {
+                symbols: group.symbols.chars().collect(),
+                indices: group
+                    .positions
+                    .iter()
+                    .filter_map(|p| layout_string_positions.iter().position(|lp| lp == p))
+                    .collect(),
+            })
+            .collect();
+
         NeoLayoutGenerator {
             base_layout_symbols,
             fixed_keys,
             permutable_key_map,
             fixed_layers: base.fixed_layers,
             modifiers: base.modifiers,
+            keycap_groups,
+            multi_key_symbols: base.multi_key_symbols,
+            mod_taps: base.mod_taps,
+            named_symbols: base.named_symbols,
+            blocked_symbols,
+            drop_blocked_symbols: base.drop_blocked_symbols,
             keyboard,
         }
     }
@@ -151,23 +408,38 @@ impl NeoLayoutGenerator {
             }
         }
 
+        let modifiers =
+            ModTapYAML::apply_all(&self.mod_taps, &self.modifiers, &key_chars, &self.keyboard)?;
+
         Layout::new(
             key_chars,
             self.fixed_keys.clone(),
             self.keyboard.clone(),
-            self.modifiers.clone(),
-        )
-    }
-
-    /// Get the list of permutable symbols
-    pub fn permutable_keys(&self) -> Vec<char> {
-        self.permutable_key_map.keys().cloned().collect()
+            modifiers,
+        )?
+        .with_named_symbols(&self.named_symbols)
+        .with_multi_key_symbols(&self.multi_key_symbols)
     }
 }
 
 impl LayoutGenerator for NeoLayoutGenerator {
     /// Generate a Neo variant [`Layout`] from a given string representation of its base layer (only non-fixed keys)
     fn generate(&self, layout_keys: &str) -> Result<Layout> {
+        let blocked: String = layout_keys
+            .chars()
+            .filter(|c| self.blocked_symbols.contains(c))
+            .collect();
+        let layout_keys = if blocked.is_empty() {
+            layout_keys.to_string()
+        } else if self.drop_blocked_symbols {
+            layout_keys
+                .chars()
+                .filter(|c| !self.blocked_symbols.contains(c))
+                .collect()
+        } else {
+            return Err(LayoutError::BlockedSymbols(blocked).into());
+        };
+        let layout_keys = layout_keys.as_str();
         let chars: Vec<char> = layout_keys.chars().collect();
 
         let char_set: AHashSet<char> = AHashSet::from_iter(chars.clone());
@@ -207,4 +479,12 @@ impl LayoutGenerator for NeoLayoutGenerator {
 
         self.generate_unchecked(layout_keys)
     }
+
+    fn permutable_keys(&self) -> Vec<char> {
+        self.permutable_key_map.keys().cloned().collect()
+    }
+
+    fn keycap_groups(&self) -> Vec<KeycapGroup> {
+        self.keycap_groups.clone()
+    }
 }
@@ -0,0 +1,109 @@
+//! The `script` module provides helpers for dealing with properties of writing systems
+//! (scripts) that need special treatment, e.g. right-to-left scripts like Hebrew and Arabic
+//! or combining marks.
+
+/// First strong isolate (U+2068)
+const FSI: char = '\u{2068}';
+/// Pop directional isolate (U+2069)
+const PDI: char = '\u{2069}';
+
+/// Whether the character belongs to a right-to-left script (Hebrew, Arabic, Syriac, Thaana, ...).
+pub fn is_rtl(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}'
+    )
+}
+
+/// Whether the character is a combining mark that is optional in writing, i.e. vowel points
+/// and cantillation marks of Hebrew (niqqud), Arabic (harakat), Syriac and Thaana.
+///
+/// Texts in these scripts are usually written without these marks. If they are present in a
+/// corpus, they are mostly due to religious or educational texts.
+pub fn is_optional_mark(c: char) -> bool {
+    matches!(c,
+        // Hebrew
+        '\u{0591}'..='\u{05BD}'
+        | '\u{05BF}'
+        | '\u{05C1}'..='\u{05C2}'
+        | '\u{05C4}'..='\u{05C5}'
+        | '\u{05C7}'
+        | '\u{FB1E}'
+        // Arabic
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0670}'
+        | '\u{06D6}'..='\u{06DC}'
+        | '\u{06DF}'..='\u{06E4}'
+        | '\u{06E7}'..='\u{06E8}'
+        | '\u{06EA}'..='\u{06ED}'
+        | '\u{08D3}'..='\u{08E1}'
+        | '\u{08E3}'..='\u{08FF}'
+        // Syriac
+        | '\u{0730}'..='\u{074A}'
+        // Thaana
+        | '\u{07A6}'..='\u{07B0}'
+    )
+}
+
+/// Whether the character is a combining mark (of any script).
+pub fn is_combining_mark(c: char) -> bool {
+    is_optional_mark(c)
+        || matches!(c,
+            '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+        )
+}
+
+/// Wrap a (printable) label in directional isolates if it contains right-to-left characters.
+///
+/// This prevents the terminal's bidirectional algorithm from reordering the surrounding text,
+/// e.g. the borders of a plotted layout. The isolates are zero-width, so alignment is kept.
+pub fn isolate_rtl(s: &str) -> String {
+    if s.chars().any(is_rtl) {
+        format!("{}{}{}", FSI, s, PDI)
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtl_characters() {
+        assert!(is_rtl('ש'));
+        assert!(is_rtl('ك'));
+        assert!(!is_rtl('a'));
+        assert!(!is_rtl('.'));
+    }
+
+    #[test]
+    fn optional_marks() {
+        // hebrew patah and dagesh
+        assert!(is_optional_mark('\u{05B7}'));
+        assert!(is_optional_mark('\u{05BC}'));
+        // hebrew maqaf is punctuation, not a mark
+        assert!(!is_optional_mark('\u{05BE}'));
+        // arabic fatha
+        assert!(is_optional_mark('\u{064E}'));
+        // latin combining acute is a mark, but not an optional one
+        assert!(!is_optional_mark('\u{0301}'));
+        assert!(is_combining_mark('\u{0301}'));
+        assert!(!is_combining_mark('e'));
+    }
+
+    #[test]
+    fn isolates() {
+        assert_eq!(isolate_rtl("a"), "a");
+        assert_eq!(isolate_rtl("ש"), "\u{2068}ש\u{2069}");
+    }
+}
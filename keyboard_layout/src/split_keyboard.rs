@@ -0,0 +1,487 @@
+//! This module provides the configuration of split keyboards whose halves are mirror images of
+//! each other (e.g. the Corne aka. crkbd). Their key matrices, columnar stagger, and thumb
+//! clusters are declared per half instead of per key (see [`SplitHalvesYAML`]).
+
+use crate::key::{Finger, Hand, MatrixPosition, PhysicalPosition, Position, KEY_PITCH};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Distance between the positions (see [`Position`]) of adjacent keys of a split keyboard, as
+/// in the bundled keyboard configurations
+const POSITION_UNIT: f64 = 50.0;
+
+/// Matrix column of the innermost column of the left half (as on the standard keyboard)
+const LEFT_INNER_COLUMN: u8 = 6;
+
+/// Matrix column of the innermost column of the right half (as on the standard keyboard)
+const RIGHT_INNER_COLUMN: u8 = 13;
+
+/// Matrix row of the home row (as on the standard keyboard)
+const HOME_ROW: u8 = 2;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SplitKeyboardError {
+    #[error("Invalid split keyboard: `{0}` is derived from `halves` and must not be given")]
+    DerivedAttribute(&'static str),
+    #[error("Invalid split keyboard: `halves` is required with `split: true`")]
+    MissingHalves,
+    #[error("Invalid split keyboard: {0} columns per half are given, but at most {} are supported", LEFT_INNER_COLUMN + 1)]
+    TooManyColumns(u8),
+    #[error("Invalid split keyboard: {0} thumb keys per half are given, but at most {} are supported", LEFT_INNER_COLUMN + 2)]
+    TooManyThumbKeys(u8),
+    #[error("Invalid split keyboard: The home row {0} needs to be below the number of rows ({1}) and at most {}", HOME_ROW)]
+    InvalidHomeRow(u8, u8),
+    #[error("Invalid split keyboard: `{attribute}` has {found} entries, but there are {expected} columns per half")]
+    WrongColumnNumber {
+        attribute: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// The layout of each half of a split keyboard: A matrix of columns with a vertical offset
+/// each (columnar stagger) and a row of thumb keys below its inner columns. The configuration
+/// describes the left half from its outer to its inner column, the right half mirrors it.
+///
+/// The keys are ordered by rows (from the left to the right), followed by the thumb keys of the
+/// left and then the right half. Their matrix positions are aligned with the standard keyboard:
+/// The inner columns are `6` and `13` and the home row is `2`. The thumb keys are in the row
+/// below the matrix and end one column beyond the inner column of their half.
+#[derive(Deserialize, Clone, Debug)]
+pub struct SplitHalvesYAML {
+    /// Number of rows of the key matrix of each half (without the thumb keys)
+    pub rows: u8,
+    /// Number of columns of the key matrix of each half
+    pub columns: u8,
+    /// Index of the home row (counted from the top)
+    #[serde(default = "default_home_row")]
+    pub home_row: u8,
+    /// Finger for each column
+    pub column_fingers: Vec<Finger>,
+    /// Vertical offset of each column (in key heights, downwards). No offsets if empty.
+    #[serde(default)]
+    pub column_stagger: Vec<f64>,
+    /// Number of thumb keys of each half
+    #[serde(default)]
+    pub thumb_keys: u8,
+    /// Horizontal gap between the halves (in key widths)
+    #[serde(default = "default_gap")]
+    pub gap: u8,
+}
+
+fn default_home_row() -> u8 {
+    1
+}
+
+fn default_gap() -> u8 {
+    2
+}
+
+/// A key of a split keyboard derived from its [`SplitHalvesYAML`]
+#[derive(Clone, Debug)]
+pub struct SplitKey {
+    pub matrix_position: MatrixPosition,
+    /// Position in key widths and heights
+    pub coordinates: (f64, f64),
+    pub hand: Hand,
+    pub finger: Finger,
+    pub symmetry_index: u8,
+}
+
+impl SplitKey {
+    pub fn position(&self) -> Position {
+        Position(
+            self.coordinates.0 * POSITION_UNIT,
+            self.coordinates.1 * POSITION_UNIT,
+        )
+    }
+
+    pub fn physical_position(&self) -> PhysicalPosition {
+        PhysicalPosition(
+            self.coordinates.0 * KEY_PITCH,
+            self.coordinates.1 * KEY_PITCH,
+            0.0,
+        )
+    }
+}
+
+impl SplitHalvesYAML {
+    /// Checks the configuration for errors.
+    pub fn validate(&self) -> Result<(), SplitKeyboardError> {
+        if self.columns > LEFT_INNER_COLUMN + 1 {
+            return Err(SplitKeyboardError::TooManyColumns(self.columns));
+        }
+        if self.thumb_keys > LEFT_INNER_COLUMN + 2 {
+            return Err(SplitKeyboardError::TooManyThumbKeys(self.thumb_keys));
+        }
+        if self.home_row >= self.rows || self.home_row > HOME_ROW {
+            return Err(SplitKeyboardError::InvalidHomeRow(self.home_row, self.rows));
+        }
+        if self.column_fingers.len() != self.columns as usize {
+            return Err(SplitKeyboardError::WrongColumnNumber {
+                attribute: "column_fingers",
+                expected: self.columns as usize,
+                found: self.column_fingers.len(),
+            });
+        }
+        if !self.column_stagger.is_empty() && self.column_stagger.len() != self.columns as usize {
+            return Err(SplitKeyboardError::WrongColumnNumber {
+                attribute: "column_stagger",
+                expected: self.columns as usize,
+                found: self.column_stagger.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Vertical offset of a column of the left half (from its outer column)
+    fn stagger(&self, column: u8) -> f64 {
+        self.column_stagger
+            .get(column as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// The keys of the keyboard by rows (the thumb keys being the last row).
+    ///
+    /// The configuration is expected to be validated (see [`SplitHalvesYAML::validate`]).
+    pub fn keys(&self) -> Vec<Vec<SplitKey>> {
+        let cols = self.columns;
+        let thumbs = self.thumb_keys;
+        let right_start = (cols + self.gap) as f64;
+        let first_row = HOME_ROW - self.home_row;
+
+        let mut rows: Vec<Vec<SplitKey>> = (0..self.rows)
+            .map(|r| {
+                let left = (0..cols).map(|c| SplitKey {
+                    matrix_position: MatrixPosition(
+                        LEFT_INNER_COLUMN + 1 - cols + c,
+                        first_row + r,
+                    ),
+                    coordinates: (c as f64, r as f64 + self.stagger(c)),
+                    hand: Hand::Left,
+                    finger: self.column_fingers[c as usize],
+                    symmetry_index: r * cols + c + 1,
+                });
+                // mirrored, from the inner to the outer column
+                let right = (0..cols).map(|c| {
+                    let mirrored = cols - 1 - c;
+                    SplitKey {
+                        matrix_position: MatrixPosition(RIGHT_INNER_COLUMN + c, first_row + r),
+                        coordinates: (right_start + c as f64, r as f64 + self.stagger(mirrored)),
+                        hand: Hand::Right,
+                        finger: self.column_fingers[mirrored as usize],
+                        symmetry_index: r * cols + mirrored + 1,
+                    }
+                });
+
+                left.chain(right).collect()
+            })
+            .collect();
+
+        if thumbs > 0 {
+            // below the inner columns, shifted inwards by half a key
+            let thumb_row = first_row + self.rows;
+            let y = self.rows as f64 + self.stagger(cols - 1);
+            let left = (0..thumbs).map(|i| SplitKey {
+                matrix_position: MatrixPosition(LEFT_INNER_COLUMN + 2 - thumbs + i, thumb_row),
+                coordinates: ((cols - thumbs + i) as f64 + 0.5, y),
+                hand: Hand::Left,
+                finger: Finger::Thumb,
+                symmetry_index: self.rows * cols + i + 1,
+            });
+            let right = (0..thumbs).map(|i| SplitKey {
+                matrix_position: MatrixPosition(RIGHT_INNER_COLUMN - 1 + i, thumb_row),
+                coordinates: (right_start + i as f64 - 0.5, y),
+                hand: Hand::Right,
+                finger: Finger::Thumb,
+                symmetry_index: self.rows * cols + thumbs - i,
+            });
+            rows.push(left.chain(right).collect());
+        }
+
+        rows
+    }
+
+    /// Resting positions of the fingers: The home row key of the finger's column nearest to the
+    /// middle finger and the middle thumb key.
+    pub fn finger_resting_positions(&self) -> AHashMap<Hand, AHashMap<Finger, Position>> {
+        let rows = self.keys();
+        let home_row = &rows[self.home_row as usize];
+        let cols = self.columns as usize;
+        let middle = self
+            .column_fingers
+            .iter()
+            .position(|f| *f == Finger::Middle)
+            .unwrap_or(cols / 2);
+
+        let mut resting_positions = AHashMap::default();
+        for (hand, keys) in [
+            (Hand::Left, &home_row[..cols]),
+            (Hand::Right, &home_row[cols..]),
+        ] {
+            let mut positions: AHashMap<Finger, (usize, Position)> = AHashMap::default();
+            for (i, key) in keys.iter().enumerate() {
+                let column = match hand {
+                    Hand::Left => i,
+                    Hand::Right => cols - 1 - i,
+                };
+                let distance = (column as isize - middle as isize).unsigned_abs();
+                let entry = positions
+                    .entry(key.finger)
+                    .or_insert((distance, key.position()));
+                if distance < entry.0 {
+                    *entry = (distance, key.position());
+                }
+            }
+            let mut positions: AHashMap<Finger, Position> =
+                positions.into_iter().map(|(f, (_, p))| (f, p)).collect();
+
+            if self.thumb_keys > 0 {
+                let thumbs = &rows[self.rows as usize];
+                let t = self.thumb_keys as usize;
+                let thumb = match hand {
+                    Hand::Left => &thumbs[t / 2],
+                    Hand::Right => &thumbs[t + (t - 1) / 2],
+                };
+                positions.insert(Finger::Thumb, thumb.position());
+            }
+            resting_positions.insert(hand, positions);
+        }
+
+        resting_positions
+    }
+
+    /// A plot template with a border around each key and the halves separated by the gap.
+    pub fn plot_template(&self) -> String {
+        let cols = self.columns as usize;
+        let gap = " ".repeat(4 * self.gap as usize);
+        let border = |n: usize, left: &str, inner: &str, right: &str| {
+            format!("{}{}{}", left, vec!["───"; n].join(inner), right)
+        };
+        let cells = |indices: std::ops::Range<usize>| {
+            format!(
+                "│{}",
+                indices
+                    .map(|i| format!(" {{{{{}}}}} │", i))
+                    .collect::<String>()
+            )
+        };
+
+        let mut lines = Vec::new();
+        let mut index = 0;
+        for r in 0..self.rows as usize {
+            let (left, inner, right) = match r {
+                0 => ("┌", "┬", "┐"),
+                _ => ("├", "┼", "┤"),
+            };
+            let b = border(cols, left, inner, right);
+            lines.push(format!("{}{}{}", b, gap, b));
+            lines.push(format!(
+                "{}{}{}",
+                cells(index..index + cols),
+                gap,
+                cells(index + cols..index + 2 * cols)
+            ));
+            index += 2 * cols;
+        }
+        let b = border(cols, "└", "┴", "┘");
+        lines.push(format!("{}{}{}", b, gap, b));
+
+        let thumbs = self.thumb_keys as usize;
+        if thumbs > 0 {
+            let indent = " ".repeat(4 * (cols - thumbs.min(cols)));
+            let thumb_gap = " ".repeat(4 * self.gap as usize);
+            for (left, inner, right, is_cells) in [
+                ("┌", "┬", "┐", false),
+                ("", "", "", true),
+                ("└", "┴", "┘", false),
+            ] {
+                let (l, r) = match is_cells {
+                    true => (
+                        cells(index..index + thumbs),
+                        cells(index + thumbs..index + 2 * thumbs),
+                    ),
+                    false => (
+                        border(thumbs, left, inner, right),
+                        border(thumbs, left, inner, right),
+                    ),
+                };
+                lines.push(format!("{}{}{}{}", indent, l, thumb_gap, r));
+            }
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// A compact plot template with the halves separated by the gap.
+    pub fn plot_template_short(&self) -> String {
+        let cols = self.columns as usize;
+        let gap = " ".repeat(self.gap.max(1) as usize);
+        let labels = |indices: std::ops::Range<usize>| {
+            indices
+                .map(|i| format!("{{{{{}}}}}", i))
+                .collect::<String>()
+        };
+
+        let mut lines = Vec::new();
+        let mut index = 0;
+        for _ in 0..self.rows {
+            lines.push(format!(
+                "{}{}{}",
+                labels(index..index + cols),
+                gap,
+                labels(index + cols..index + 2 * cols)
+            ));
+            index += 2 * cols;
+        }
+
+        let thumbs = self.thumb_keys as usize;
+        if thumbs > 0 {
+            lines.push(format!(
+                "{}{}{}{}",
+                " ".repeat(cols - thumbs.min(cols)),
+                labels(index..index + thumbs),
+                gap,
+                labels(index + thumbs..index + 2 * thumbs)
+            ));
+        }
+
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LayoutConfig;
+    use crate::keyboard::Keyboard;
+    use std::str::FromStr;
+
+    const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+    const HALVES: &str = "
+rows: 2
+columns: 3
+home_row: 1
+column_fingers: [Ring, Middle, Index]
+column_stagger: [0.2, 0.0, 0.1]
+thumb_keys: 2
+gap: 1
+";
+
+    fn halves() -> SplitHalvesYAML {
+        let halves: SplitHalvesYAML = serde_yaml::from_str(HALVES).unwrap();
+        halves.validate().unwrap();
+        halves
+    }
+
+    #[test]
+    fn keys_are_mirrored() {
+        let keys: Vec<SplitKey> = halves().keys().into_iter().flatten().collect();
+        let matrix_positions: Vec<(u8, u8)> = keys
+            .iter()
+            .map(|k| (k.matrix_position.0, k.matrix_position.1))
+            .collect();
+        assert_eq!(
+            matrix_positions,
+            vec![
+                (4, 1),
+                (5, 1),
+                (6, 1),
+                (13, 1),
+                (14, 1),
+                (15, 1),
+                (4, 2),
+                (5, 2),
+                (6, 2),
+                (13, 2),
+                (14, 2),
+                (15, 2),
+                (6, 3),
+                (7, 3),
+                (12, 3),
+                (13, 3),
+            ]
+        );
+
+        // the outer column of the left half mirrors the one of the right half
+        assert_eq!(keys[0].finger, Finger::Ring);
+        assert_eq!(keys[5].finger, Finger::Ring);
+        assert_eq!(keys[0].symmetry_index, keys[5].symmetry_index);
+        assert_eq!(keys[0].coordinates, (0.0, 0.2));
+        assert_eq!(keys[5].coordinates, (6.0, 0.2));
+        // the inner thumb keys mirror each other
+        assert_eq!(keys[13].symmetry_index, keys[14].symmetry_index);
+        assert_eq!(keys[13].coordinates, (2.5, 2.1));
+        assert_eq!(keys[14].coordinates, (3.5, 2.1));
+    }
+
+    #[test]
+    fn plots_show_gap() {
+        let halves = halves();
+        let labels: AHashMap<usize, String> = (0..16).map(|i| (i, format!("{:x}", i))).collect();
+        let render = |template: &str| {
+            let mut reg = handlebars::Handlebars::new();
+            reg.register_escape_fn(handlebars::no_escape);
+            reg.render_template(template, &labels).unwrap()
+        };
+
+        let plot = render(&halves.plot_template());
+        assert!(plot.contains("│ 0 │ 1 │ 2 │    │ 3 │ 4 │ 5 │"));
+        assert!(plot.contains("    │ c │ d │    │ e │ f │"));
+
+        let plot = render(&halves.plot_template_short());
+        assert_eq!(plot, "012 345\n678 9ab\n cd ef\n");
+    }
+
+    #[test]
+    fn invalid_halves() {
+        let halves: SplitHalvesYAML =
+            serde_yaml::from_str(&HALVES.replace("[Ring, Middle, Index]", "[Middle, Index]"))
+                .unwrap();
+        assert_eq!(
+            halves.validate(),
+            Err(SplitKeyboardError::WrongColumnNumber {
+                attribute: "column_fingers",
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn crkbd() {
+        let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+        let keyboard = Keyboard::from_yaml_object(layout_config.keyboard);
+        let geometry = keyboard.geometry();
+
+        assert_eq!(geometry.len(), 42);
+        let space = geometry.key_at(MatrixPosition(13, 4)).unwrap();
+        assert!(geometry.is_thumb(space));
+        assert_eq!(geometry.hand(space), Hand::Right);
+        assert_eq!(
+            geometry.mirror(space),
+            geometry.key_at(MatrixPosition(6, 4))
+        );
+    }
+
+    #[test]
+    fn base_layout_needs_a_symbol_for_each_key() {
+        let yaml = crate::config::resolved_yaml(LAYOUT_CONFIG).unwrap();
+        let mut value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        value["keyboard"]["halves"]["thumb_keys"] = 2.into();
+        value["keyboard"]["key_costs"][3] = serde_yaml::from_str("[3, 6, 6, 3]").unwrap();
+        value["keyboard"]["unbalancing_positions"] = serde_yaml::Value::Null;
+
+        let err = LayoutConfig::from_str(&serde_yaml::to_string(&value).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid base layout: Row 3 of `keys` has 6 entries, but the same row of \
+            `matrix_positions` has 4"
+        );
+    }
+}
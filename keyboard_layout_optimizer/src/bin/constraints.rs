@@ -0,0 +1,185 @@
+use clap::{Parser, Subcommand};
+use std::process;
+
+use keyboard_layout_optimizer::common;
+use layout_evaluation::atomic_file;
+use layout_optimization_common::constraints::{ConstraintSet, Pin};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout constraints")]
+struct Options {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report every constraint that is violated by the given layouts
+    Check(Box<CheckOptions>),
+    /// Write the constraints of the layout config, the commandline and a constraints file to a
+    /// single constraints file
+    Export(ExportOptions),
+}
+
+#[derive(Parser, Debug)]
+struct CheckOptions {
+    /// Layout keys from left to right, top to bottom
+    #[clap(required = true)]
+    layout_str: Vec<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    #[clap(flatten)]
+    constraint_options: common::ConstraintOptions,
+
+    #[clap(flatten)]
+    hand_balance_options: common::HandBalanceOptions,
+}
+
+#[derive(Parser, Debug)]
+struct ExportOptions {
+    /// Pin these symbols to the keys they occupy in the "fix-from" layout
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Layout from which the keys of the pinned symbols are taken
+    #[clap(long, default_value = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")]
+    fix_from: String,
+
+    /// Filename of layout configuration file to use
+    #[clap(short, long, default_value = "config/keyboard/standard.yml")]
+    layout_config: String,
+
+    /// Interpred given layout string using the "grouped" logic
+    #[clap(long)]
+    grouped_layout_generator: bool,
+
+    #[clap(flatten)]
+    constraint_options: common::ConstraintOptions,
+
+    #[clap(flatten)]
+    hand_balance_options: common::HandBalanceOptions,
+
+    /// Write the constraints to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+}
+
+fn exit_with_error<E: std::fmt::Display>(e: E) -> ! {
+    log::error!("{}", e);
+    process::exit(1);
+}
+
+fn check(options: &CheckOptions) {
+    let constraints = common::init_constraints(
+        &options.constraint_options,
+        &options.general_parameters.layout_config,
+        options.general_parameters.grouped_layout_generator,
+        options.hand_balance_options.parameters(),
+    )
+    .unwrap_or_else(|e| exit_with_error(e));
+
+    // the ngrams are only required for checking the hand balance
+    let (layout_generator, evaluator) = if constraints.hand_balance.is_some() {
+        let (layout_generator, evaluator) = common::init(&options.general_parameters);
+        (layout_generator, Some(evaluator))
+    } else {
+        let layout_generator = common::init_layout_generator(
+            &options.general_parameters.layout_config,
+            options.general_parameters.grouped_layout_generator,
+        );
+        (layout_generator, None)
+    };
+
+    let mut violated = false;
+    for layout_str in options.layout_str.iter() {
+        let layout_str: String = layout_str
+            .chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect();
+        let layout = layout_generator
+            .generate(&layout_str)
+            .unwrap_or_else(|e| exit_with_error(format!("Layout {}: {:?}", layout_str, e)));
+        let violations = constraints
+            .check(&layout_str, &layout, evaluator.as_ref())
+            .unwrap_or_else(|e| exit_with_error(format!("Layout {}: {}", layout_str, e)));
+
+        if violations.is_empty() {
+            println!("{}: satisfies all constraints", layout_str);
+        } else {
+            violated = true;
+            println!("{}: {} violated constraints", layout_str, violations.len());
+            violations.iter().for_each(|v| println!("  {}", v));
+        }
+    }
+
+    if violated {
+        process::exit(1);
+    }
+}
+
+fn export(options: &ExportOptions) {
+    let mut constraints = common::init_constraints(
+        &options.constraint_options,
+        &options.layout_config,
+        options.grouped_layout_generator,
+        options.hand_balance_options.parameters(),
+    )
+    .unwrap_or_else(|e| exit_with_error(e));
+
+    if let Some(fix) = &options.fix {
+        let layout_generator =
+            common::init_layout_generator(&options.layout_config, options.grouped_layout_generator);
+        let layout = layout_generator
+            .generate(&options.fix_from)
+            .unwrap_or_else(|e| exit_with_error(format!("{:?}", e)));
+        let pins = fix
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match layout.get_layerkey_for_symbol(&c) {
+                Some(key) => Pin {
+                    symbol: c,
+                    position: key.key.matrix_position,
+                },
+                None => exit_with_error(format!(
+                    "Symbol '{}' to fix is not part of the layout '{}'",
+                    c, options.fix_from
+                )),
+            })
+            .collect();
+        let pinned = ConstraintSet {
+            pins,
+            ..Default::default()
+        };
+        constraints = constraints
+            .merge(&pinned)
+            .unwrap_or_else(|e| exit_with_error(e));
+    }
+
+    let yaml = constraints.to_yaml().unwrap_or_else(|e| exit_with_error(e));
+    match &options.output {
+        Some(filename) => {
+            atomic_file::write_atomic(filename, yaml).unwrap_or_else(|e| {
+                exit_with_error(format!("Could not write to {}: {}", filename, e))
+            });
+            log::info!("Wrote constraints to {}", filename);
+        }
+        None => print!("{}", yaml),
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    env_logger::init();
+    let options = Options::parse();
+
+    match &options.command {
+        Command::Check(options) => check(options),
+        Command::Export(options) => export(options),
+    }
+}
@@ -1,13 +1,25 @@
 use keyboard_layout::layout::Layout;
-use keyboard_layout_optimizer::common;
-use layout_evaluation::{cache::Cache, results::EvaluationResult};
+use keyboard_layout_optimizer::{bundle, common, tui};
+use layout_evaluation::{
+    cache::Cache,
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    key_summary::KeySummary,
+    memory::MemoryReport,
+    ngram_mapper::{common::ShiftPolicy, MappingStatistics},
+    results::EvaluationResult,
+    robustness::RobustnessResult,
+};
 
+use ahash::AHashMap;
 use clap::Parser;
+use colored::Colorize;
 use rayon::prelude::*;
 use serde::Serialize;
 use std::{
     fs::File,
     io::{BufRead, BufReader},
+    path::Path,
 };
 
 #[derive(Serialize)]
@@ -26,6 +38,46 @@ impl From<EvaluationResult> for LayoutEvaluation {
     }
 }
 
+/// The output of `--json`: the evaluations of the layouts along with the requested reports.
+#[derive(Serialize)]
+struct JsonOutput {
+    results: Vec<LayoutEvaluation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_summaries: Option<Vec<KeySummary>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_reports: Option<Vec<MemoryReport>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mapping_statistics: Option<Vec<Option<MappingStatistics>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    robustness: Option<Vec<RobustnessResult>>,
+}
+
+impl JsonOutput {
+    fn new(results: Vec<LayoutEvaluation>) -> Self {
+        Self {
+            results,
+            key_summaries: None,
+            memory_reports: None,
+            mapping_statistics: None,
+            robustness: None,
+        }
+    }
+
+    /// A single JSON document: the list of results if no report was requested, otherwise an
+    /// object containing the results and the reports.
+    fn to_json(&self) -> String {
+        if self.key_summaries.is_none()
+            && self.memory_reports.is_none()
+            && self.mapping_statistics.is_none()
+            && self.robustness.is_none()
+        {
+            serde_json::to_string(&self.results).unwrap()
+        } else {
+            serde_json::to_string(self).unwrap()
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout evaluation")]
 struct Options {
@@ -44,7 +96,9 @@ struct Options {
     #[clap(flatten)]
     general_parameters: common::Options,
 
-    /// If to only output the results as JSON to stdout
+    /// If to only output the results as JSON to stdout (along with the reports of
+    /// `--summary-keys`, `--memory-report`, `--mapping-stats`, and `--robustness` in a single
+    /// object if any of them is requested)
     #[clap(long)]
     json: bool,
 
@@ -55,6 +109,276 @@ struct Options {
     /// Sort results by total costs
     #[clap(long)]
     sort: bool,
+
+    /// Evaluate each layout additionally on this many bootstrap resamples of the ngram data
+    /// and report the spread of the costs (each resample is a full evaluation). The number is
+    /// optional and has to be given as `--robustness=N`
+    #[clap(
+        long,
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "20"
+    )]
+    robustness: Option<usize>,
+
+    /// Seed for the resampling of the robustness analysis
+    #[clap(long, default_value = "0")]
+    robustness_seed: u64,
+
+    /// Number of ngram occurrences (per ngram type) the corpus is assumed to consist of in the
+    /// robustness analysis. Larger corpora lead to smaller spreads of the costs
+    #[clap(long, default_value = "1000000")]
+    robustness_sample_size: f64,
+
+    /// Evaluate the layouts under all weight profiles and print a comparison
+    #[clap(long, conflicts_with = "profile")]
+    all_profiles: bool,
+
+    /// List the symbols contributing most to the weight of ngrams that the layouts can not
+    /// generate (per ngram type)
+    #[clap(long)]
+    verbose_missing: bool,
+
+    /// Report estimates of the memory used by the ngram data, the mapped ngrams, the result
+    /// cache, and the metrics
+    #[clap(long)]
+    memory_report: bool,
+
+    /// Report how much of the weight of the unigrams, bigrams, and trigrams is mapped to the
+    /// layouts, excluded (line breaks), or generated (modifiers split off higher-layer symbols)
+    #[clap(long)]
+    mapping_stats: bool,
+
+    /// Write the unigrams, bigrams, and trigrams mapped to the layout (after splitting off
+    /// modifiers) to TSV files in this directory (requires a single layout)
+    #[clap(long)]
+    dump_mapped_ngrams: Option<String>,
+
+    /// List the trigrams that this trigram of the ngram data is mapped to for each layout, i.e.
+    /// after splitting off modifiers, along with their weights (may be given multiple times)
+    #[clap(long)]
+    explain_trigram: Vec<String>,
+
+    /// Distribute the costs of all metrics onto the keys and list this many keys with the
+    /// highest costs (along with the metrics contributing most to them). The number is optional
+    /// and has to be given as `--summary-keys=N`
+    #[clap(
+        long,
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "3"
+    )]
+    summary_keys: Option<usize>,
+
+    /// Pack the layout, the configuration files, the fingerprint of the ngram data, and the
+    /// result into a reproducibility bundle with this filename (requires a single layout). The
+    /// bundle is compressed if the filename ends with `.zst` (e.g. `bundle.tar.zst`)
+    #[clap(long)]
+    bundle: Option<String>,
+
+    /// Store a copy of the ngram data in the bundle (not only their fingerprint)
+    #[clap(long, requires = "bundle")]
+    bundle_with_ngrams: bool,
+
+    /// Replay the evaluation of a reproducibility bundle and check that the total cost matches
+    #[clap(long, conflicts_with = "bundle")]
+    replay: Option<String>,
+
+    /// Maximal (absolute) difference of the total costs for a replay to reproduce the bundle
+    #[clap(long, default_value = "1e-6")]
+    replay_tolerance: f64,
+
+    /// Browse the evaluation of a single layout in an interactive terminal viewer (falls back
+    /// to the normal output if not run in a terminal)
+    #[clap(long, conflicts_with_all = &["json", "only-total-costs", "all-profiles"])]
+    tui: bool,
+
+    /// Map the ngrams consisting of symbols that are placed identically in all layouts with the
+    /// same base layer only once for all of them (speeds up evaluating many variants of the
+    /// higher layers)
+    #[clap(long)]
+    share_mapping: bool,
+
+    /// Evaluate the layouts with each way of modeling the modifiers of the shift layer (as
+    /// configured in the layout, held, one-shot, and caps word) and print a comparison
+    #[clap(long, conflicts_with_all = &["all-profiles", "tui"])]
+    compare_shift_policies: bool,
+
+    /// Evaluate the mirrored layouts, i.e. with the symbols of each key moved to its partner in
+    /// the `mirror_pairs` of the keyboard config (e.g. for typing with one hand)
+    #[clap(long, conflicts_with = "mirror-weight")]
+    mirror: bool,
+
+    /// Evaluate the layouts together with their mirrored variants, which get this share of the
+    /// ngrams' weight (between 0 and 1)
+    #[clap(long)]
+    mirror_weight: Option<f64>,
+
+    /// Check the layouts against the constraints (pins, keycap groups, hand balance) of this
+    /// file and the layout config
+    #[clap(flatten)]
+    constraint_options: common::ConstraintOptions,
+}
+
+/// Print a table comparing the weighted metric costs of the results (one column per profile).
+/// Profiles in `approximate` are marked.
+fn print_profiles_table(results: &[EvaluationResult], approximate: &[&str]) {
+    let columns: Vec<String> = results
+        .iter()
+        .map(|r| {
+            let name = r.profile().unwrap_or("default");
+            if approximate.contains(&name) {
+                format!("{}*", name)
+            } else {
+                name.to_string()
+            }
+        })
+        .collect();
+    let widths: Vec<usize> = columns.iter().map(|c| c.chars().count().max(8)).collect();
+
+    let mut header = format!("{:<45}", "");
+    columns
+        .iter()
+        .zip(&widths)
+        .for_each(|(c, w)| header.push_str(&format!(" {:>w$}", c, w = w)));
+    println!("{}", header.bold());
+
+    for (i, metric_results) in results[0].iter().enumerate() {
+        for (j, metric_cost) in metric_results.metric_costs.iter().enumerate() {
+            let mut line = format!(
+                "{:<45}",
+                format!(
+                    "{} ({:?})",
+                    metric_cost.core.name, metric_results.metric_type
+                )
+            );
+            results.iter().zip(&widths).for_each(|(r, w)| {
+                let cost = r
+                    .iter()
+                    .nth(i)
+                    .and_then(|mr| mr.metric_costs.get(j))
+                    .map(|mc| mc.weighted_cost)
+                    .unwrap_or(0.0);
+                line.push_str(&format!(" {:>w$.2}", cost, w = w));
+            });
+            println!("{}", line);
+        }
+    }
+
+    let mut totals = format!("{:<45}", "Total cost");
+    results
+        .iter()
+        .zip(&widths)
+        .for_each(|(r, w)| totals.push_str(&format!(" {:>w$.2}", r.total_cost(), w = w)));
+    println!("{}", totals.green().bold());
+
+    if !approximate.is_empty() {
+        println!(
+            "* Metrics aggregating bigram metrics use the default bigram weights. \
+            Use `--profile` for exact values."
+        );
+    }
+}
+
+/// Number of symbols that are listed in the report of missing symbols
+const N_MISSING_SYMBOLS: usize = 20;
+
+/// Print the symbols with the highest weight of ngrams that could not be mapped by the layout
+/// (per metric type) along with their share of the total ngram weight.
+fn print_missing_symbols(result: &EvaluationResult) {
+    for metric_results in result.iter() {
+        let missing = metric_results.top_missing_symbols(N_MISSING_SYMBOLS);
+        if missing.is_empty() {
+            continue;
+        }
+
+        let total_weight = metric_results.found_weight + metric_results.not_found_weight;
+        println!(
+            "{}",
+            format!("Missing symbols ({:?}):", metric_results.metric_type).bold()
+        );
+        for (c, weight) in missing {
+            println!(
+                "  {:<8} {:>14.2} {:>8.4}%",
+                format!("{:?}", c),
+                weight,
+                100.0 * weight / total_weight
+            );
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ShiftPolicyEvaluation {
+    shift_policy: String,
+    #[serde(flatten)]
+    evaluation: LayoutEvaluation,
+}
+
+/// Number of metrics that are listed in the comparison of shift policies
+const N_DIFFERING_METRICS: usize = 5;
+
+/// Print the total costs under each shift policy and the metrics whose costs differ most
+/// between them.
+fn print_shift_policies_table(results: &[(ShiftPolicy, EvaluationResult)]) {
+    let widths: Vec<usize> = results
+        .iter()
+        .map(|(policy, _)| policy.to_string().len().max(8))
+        .collect();
+
+    let mut header = format!("{:<45}", "");
+    results.iter().zip(&widths).for_each(|((policy, _), w)| {
+        header.push_str(&format!(" {:>w$}", policy.to_string(), w = w))
+    });
+    println!("{}", header.bold());
+
+    let mut totals = format!("{:<45}", "Total cost");
+    results
+        .iter()
+        .zip(&widths)
+        .for_each(|((_, r), w)| totals.push_str(&format!(" {:>w$.2}", r.total_cost(), w = w)));
+    println!("{}", totals.green().bold());
+
+    // (name, costs per policy, spread)
+    let mut metrics: Vec<(String, Vec<f64>, f64)> = Vec::new();
+    for (i, metric_results) in results[0].1.iter().enumerate() {
+        for (j, metric_cost) in metric_results.metric_costs.iter().enumerate() {
+            let costs: Vec<f64> = results
+                .iter()
+                .map(|(_, r)| {
+                    r.iter()
+                        .nth(i)
+                        .and_then(|mr| mr.metric_costs.get(j))
+                        .map(|mc| mc.weighted_cost)
+                        .unwrap_or(0.0)
+                })
+                .collect();
+            let max = costs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let min = costs.iter().cloned().fold(f64::INFINITY, f64::min);
+            let name = format!(
+                "{} ({:?})",
+                metric_cost.core.name, metric_results.metric_type
+            );
+            metrics.push((name, costs, max - min));
+        }
+    }
+    metrics.sort_by(|(_, _, s1), (_, _, s2)| s2.partial_cmp(s1).unwrap());
+
+    println!("Metrics differing most:");
+    for (name, costs, _) in metrics
+        .iter()
+        .filter(|(_, _, spread)| *spread > 0.0)
+        .take(N_DIFFERING_METRICS)
+    {
+        let mut line = format!("{:<45}", name);
+        costs
+            .iter()
+            .zip(&widths)
+            .for_each(|(cost, w)| line.push_str(&format!(" {:>w$.2}", cost, w = w)));
+        println!("{}", line);
+    }
 }
 
 fn main() {
@@ -65,6 +389,27 @@ fn main() {
         env_logger::init();
     }
 
+    // replay a bundle (does not evaluate any other layouts)
+    if let Some(filename) = &options.replay {
+        match bundle::replay(
+            filename,
+            &options.general_parameters,
+            options.replay_tolerance,
+        ) {
+            Ok(report) => {
+                println!("{}", report);
+                if !report.is_reproduced() {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                log::error!("Could not replay bundle {}: {:?}", filename, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let (layout_generator, evaluator) = common::init(&options.general_parameters);
 
     // collect layout strings to a vec
@@ -72,7 +417,8 @@ fn main() {
     if let Some(filename) = &options.from_file {
         match File::open(filename) {
             Ok(file) => {
-                layout_strings.append(&mut BufReader::new(file).lines().flatten().collect());
+                layout_strings
+                    .append(&mut BufReader::new(file).lines().map_while(Result::ok).collect());
             }
             Err(e) => {
                 log::error!("Error reading layouts file {}: {:?}", filename, e);
@@ -81,10 +427,30 @@ fn main() {
         }
     }
 
+    if let Some(mirror_weight) = options.mirror_weight {
+        if !(0.0..=1.0).contains(&mirror_weight) {
+            log::error!("The mirror weight needs to be between 0 and 1");
+            std::process::exit(1);
+        }
+    }
+
     let result_cache: Cache<EvaluationResult> = Cache::new();
+    let keycap_groups = layout_generator.keycap_groups();
+    let constraints = options.constraint_options.constraints.as_ref().map(|_| {
+        common::init_constraints(
+            &options.constraint_options,
+            &options.general_parameters.layout_config,
+            options.general_parameters.grouped_layout_generator,
+            None,
+        )
+        .unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1);
+        })
+    });
 
-    // evaluate layouts
-    let mut results: Vec<(String, Layout, EvaluationResult)> = layout_strings
+    // generate layouts
+    let layouts: Vec<(String, Layout)> = layout_strings
         .par_iter()
         .map(|layout_str| {
             let layout_str: String = layout_str
@@ -98,8 +464,82 @@ fn main() {
                     panic!("{:?}", e);
                 }
             };
+            // check the mirror pairs early (they are needed for the combined evaluation as well)
+            let mirrored = if options.mirror || options.mirror_weight.is_some() {
+                match layout.mirrored(layout.keyboard.mirror_map()) {
+                    Ok(mirrored) => Some(mirrored),
+                    Err(e) => {
+                        log::error!("Layout {}: Can not mirror the layout: {}", layout_str, e);
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                None
+            };
+            for group in keycap_groups.iter() {
+                let misplaced = group.misplaced_symbols(&layout_str);
+                if !misplaced.is_empty() {
+                    log::warn!(
+                        "Layout {}: Symbols {} are not on the keys of keycap group '{}'",
+                        layout_str,
+                        misplaced.iter().collect::<String>(),
+                        group
+                    );
+                }
+            }
+            if let Some(constraints) = &constraints {
+                match constraints.check(&layout_str, &layout, Some(&evaluator)) {
+                    Ok(violations) => violations.iter().for_each(|v| {
+                        log::warn!("Layout {}: Violated constraint: {}", layout_str, v)
+                    }),
+                    Err(e) => {
+                        log::error!("Layout {}: Could not check constraints: {}", layout_str, e)
+                    }
+                }
+            }
+            match mirrored {
+                Some(mirrored) if options.mirror => (layout_str, mirrored),
+                _ => (layout_str, layout),
+            }
+        })
+        .collect();
+
+    // share the mapped ngrams between layouts with the same base layer
+    let mut shared_evaluators: AHashMap<String, Evaluator> = AHashMap::default();
+    if options.share_mapping {
+        let mut groups: AHashMap<String, Vec<&Layout>> = AHashMap::default();
+        layouts
+            .iter()
+            .for_each(|(_, layout)| groups.entry(layout.as_text()).or_default().push(layout));
+        groups.retain(|_, group| group.len() > 1);
+
+        log::info!(
+            "Sharing the mapped ngrams within {} groups of layouts with the same base layer",
+            groups.len()
+        );
+        let groups: Vec<(String, Vec<&Layout>)> = groups.into_iter().collect();
+        shared_evaluators = groups
+            .par_iter()
+            .map(|(base, group)| (base.clone(), evaluator.with_shared_mapping(group)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect();
+    }
+
+    // evaluate layouts
+    let mut results: Vec<(String, Layout, EvaluationResult)> = layouts
+        .into_par_iter()
+        .map(|(layout_str, layout)| {
+            let evaluator = shared_evaluators
+                .get(&layout.as_text())
+                .unwrap_or(&evaluator);
             let evaluation_result =
-                result_cache.get_or_insert_with(&layout_str, || evaluator.evaluate_layout(&layout));
+                result_cache.get_or_insert_with(&layout_str, || match options.mirror_weight {
+                    Some(mirror_weight) => evaluator
+                        .evaluate_mirrored(&layout, mirror_weight)
+                        .unwrap_or_else(|e| panic!("{:?}", e)),
+                    None => evaluator.evaluate_layout(&layout),
+                });
             (layout_str, layout, evaluation_result)
         })
         .collect();
@@ -111,13 +551,128 @@ fn main() {
         });
     }
 
-    // print results
+    // reweight results for all profiles (reusing the metric costs)
+    if options.all_profiles {
+        let eval_params =
+            EvaluationParameters::from_yaml(&options.general_parameters.eval_parameters)
+                .unwrap_or_else(|e| panic!("Could not read evaluation yaml file: {:?}", e));
+
+        let profile_results: Vec<(String, Vec<EvaluationResult>)> = results
+            .iter()
+            .map(|(layout_str, _, res)| {
+                let mut all = vec![res.clone()];
+                all.extend(
+                    eval_params
+                        .profiles
+                        .iter()
+                        .map(|(name, profile)| evaluator.reweight(res, name, profile)),
+                );
+                (layout_str.clone(), all)
+            })
+            .collect();
+
+        if options.json {
+            let profile_results: Vec<Vec<LayoutEvaluation>> = profile_results
+                .into_iter()
+                .map(|(_, all)| all.into_iter().map(|r| r.into()).collect())
+                .collect();
+            println!("{}", serde_json::to_string(&profile_results).unwrap());
+        } else {
+            let approximate: Vec<&str> = eval_params
+                .profiles
+                .iter()
+                .filter(|(_, profile)| !evaluator.reweighting_is_exact(profile))
+                .map(|(name, _)| name.as_str())
+                .collect();
+            for (layout_str, all) in profile_results.iter() {
+                println!("\n{}", layout_str);
+                print_profiles_table(all, &approximate);
+            }
+        }
+
+        return;
+    }
+
+    // evaluate with all shift policies
+    if options.compare_shift_policies {
+        let policies: Vec<ShiftPolicy> = std::iter::once(ShiftPolicy::Layout)
+            .chain(ShiftPolicy::OVERRIDES.iter().cloned())
+            .collect();
+        let evaluators: Vec<(ShiftPolicy, Evaluator)> = policies
+            .iter()
+            .map(|policy| {
+                let evaluator = evaluator.with_shift_policy(*policy).unwrap_or_else(|| {
+                    log::error!("The ngram mapper does not support shift policies");
+                    std::process::exit(1);
+                });
+                (*policy, evaluator)
+            })
+            .collect();
+
+        let policy_results: Vec<(String, Vec<(ShiftPolicy, EvaluationResult)>)> = results
+            .par_iter()
+            .map(|(layout_str, layout, _)| {
+                let all = evaluators
+                    .iter()
+                    .map(|(policy, evaluator)| (*policy, evaluator.evaluate_layout(layout)))
+                    .collect();
+                (layout_str.clone(), all)
+            })
+            .collect();
+
+        if options.json {
+            let policy_results: Vec<Vec<ShiftPolicyEvaluation>> = policy_results
+                .into_iter()
+                .map(|(_, all)| {
+                    all.into_iter()
+                        .map(|(policy, r)| ShiftPolicyEvaluation {
+                            shift_policy: policy.to_string(),
+                            evaluation: r.into(),
+                        })
+                        .collect()
+                })
+                .collect();
+            println!("{}", serde_json::to_string(&policy_results).unwrap());
+        } else {
+            for (layout_str, all) in policy_results.iter() {
+                println!("\n{}", layout_str);
+                print_shift_policies_table(all);
+            }
+        }
+
+        return;
+    }
+
+    // interactive viewer
+    if options.tui {
+        if results.len() != 1 {
+            log::error!("The terminal viewer can only show a single layout");
+            std::process::exit(1);
+        }
+        if tui::is_terminal() {
+            let (_, layout, evaluation_result) = &results[0];
+            let mut viewer =
+                tui::Viewer::new(layout, evaluation_result, evaluator.key_summary(layout));
+            if let Err(e) = tui::run(&mut viewer) {
+                log::error!("Error in the terminal viewer: {:?}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        log::warn!("Not running in a terminal, printing the evaluation instead");
+    }
+
+    // print results (as JSON after all reports have been added)
+    let mut json_output = None;
     if options.json {
-        let results: Vec<LayoutEvaluation> =
-            results.into_iter().map(|(_, _, res)| res.into()).collect();
-        println!("{}", serde_json::to_string(&results).unwrap());
+        json_output = Some(JsonOutput::new(
+            results
+                .iter()
+                .map(|(_, _, res)| res.clone().into())
+                .collect(),
+        ));
     } else {
-        for (layout_str, layout, evaluation_result) in results {
+        for (layout_str, layout, evaluation_result) in results.iter() {
             if !options.only_total_costs {
                 println!("Layout (layer 1):\n{}", layout.plot_layer(0));
                 println!("Layout string (layer 1):\n{}\n", layout);
@@ -125,6 +680,195 @@ fn main() {
             } else {
                 println!("{} {:4.2}", layout_str, evaluation_result.total_cost());
             }
+            if options.verbose_missing {
+                print_missing_symbols(evaluation_result);
+            }
+        }
+    }
+
+    // reproducibility bundle
+    if let Some(filename) = &options.bundle {
+        if results.len() != 1 {
+            log::error!("A bundle can only be created for a single layout");
+            std::process::exit(1);
         }
+        let (layout_str, _, evaluation_result) = &results[0];
+        match bundle::create(
+            filename,
+            &options.general_parameters,
+            layout_str,
+            evaluation_result,
+            options.bundle_with_ngrams,
+        ) {
+            Ok(manifest) => log::info!(
+                "Wrote bundle {} (evaluation fingerprint {})",
+                filename,
+                manifest.fingerprints.evaluation
+            ),
+            Err(e) => {
+                log::error!("Could not write bundle {}: {:?}", filename, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // mapped ngrams
+    if let Some(dir) = &options.dump_mapped_ngrams {
+        if results.len() != 1 {
+            log::error!("Mapped ngrams can only be written for a single layout");
+            std::process::exit(1);
+        }
+        let (_, layout, _) = &results[0];
+        match evaluator.dump_mapped_ngrams(layout, Path::new(dir)) {
+            Ok(()) => log::info!("Wrote mapped ngrams to {}", dir),
+            Err(e) => {
+                log::error!("{:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // generated trigrams
+    for trigram_str in options.explain_trigram.iter() {
+        let trigram = match trigram_str.chars().collect::<Vec<char>>()[..] {
+            [c1, c2, c3] => (c1, c2, c3),
+            _ => {
+                log::error!("'{}' is not a trigram", trigram_str);
+                std::process::exit(1);
+            }
+        };
+        for (_, layout, _) in results.iter() {
+            match evaluator.explain_trigram(trigram, layout) {
+                Some(expansion) => println!("\n{}\n{}", layout, expansion),
+                None => log::warn!("Trigram '{}' is not part of the ngram data", trigram_str),
+            }
+        }
+    }
+
+    // costs per key
+    if let Some(n_keys) = options.summary_keys {
+        let key_summaries: Vec<KeySummary> = results
+            .par_iter()
+            .map(|(_, layout, _)| evaluator.key_summary(layout).top(n_keys))
+            .collect();
+
+        if let Some(json_output) = json_output.as_mut() {
+            json_output.key_summaries = Some(key_summaries);
+        } else {
+            for s in key_summaries.iter() {
+                println!("\n{}\n{}", s.layout, s);
+            }
+        }
+    }
+
+    // memory usage
+    if options.memory_report {
+        let memory_reports: Vec<MemoryReport> = results
+            .iter()
+            .map(|(_, layout, _)| {
+                let mut report = evaluator.memory_report(layout);
+                report.add(
+                    "Result cache",
+                    result_cache.len(),
+                    result_cache.estimated_bytes(),
+                );
+                report
+            })
+            .collect();
+
+        if let Some(json_output) = json_output.as_mut() {
+            json_output.memory_reports = Some(memory_reports);
+        } else {
+            for ((layout_str, _, _), report) in results.iter().zip(memory_reports.iter()) {
+                println!("\nMemory usage (estimated) for {}:\n{}", layout_str, report);
+            }
+        }
+    }
+
+    // mapping statistics
+    if options.mapping_stats {
+        let mapping_statistics: Vec<Option<MappingStatistics>> = results
+            .par_iter()
+            .map(|(_, layout, _)| evaluator.mapping_statistics(layout))
+            .collect();
+
+        if let Some(json_output) = json_output.as_mut() {
+            json_output.mapping_statistics = Some(mapping_statistics);
+        } else {
+            for ((layout_str, _, _), stats) in results.iter().zip(mapping_statistics.iter()) {
+                match stats {
+                    Some(stats) => println!("\nNgram mapping of {}:\n{}", layout_str, stats),
+                    None => log::warn!("No mapping statistics available for {}", layout_str),
+                }
+            }
+        }
+    }
+
+    // robustness analysis
+    if let Some(n_resamples) = options.robustness {
+        let robustness_results: Vec<RobustnessResult> = results
+            .par_iter()
+            .map(|(_, layout, _)| {
+                evaluator.evaluate_robustness(
+                    layout,
+                    n_resamples,
+                    options.robustness_sample_size,
+                    options.robustness_seed,
+                )
+            })
+            .collect();
+
+        if let Some(json_output) = json_output.as_mut() {
+            json_output.robustness = Some(robustness_results);
+        } else {
+            for r in robustness_results.iter() {
+                println!("\n{}\n{}", r.layout, r);
+            }
+
+            for (i, r1) in robustness_results.iter().enumerate() {
+                for r2 in robustness_results.iter().skip(i + 1) {
+                    println!(
+                        "{} beats {} in {:.1}% of resamples",
+                        r1.layout,
+                        r2.layout,
+                        100.0 * r1.fraction_better_than(r2)
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(json_output) = json_output {
+        println!("{}", json_output.to_json());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn robustness_does_not_consume_layouts() {
+        let options = Options::try_parse_from(["evaluate", "--robustness", "xvlcw"]).unwrap();
+        assert_eq!(options.robustness, Some(20));
+        assert_eq!(options.layout_str, vec!["xvlcw"]);
+
+        let options = Options::try_parse_from(["evaluate", "--robustness=5", "xvlcw"]).unwrap();
+        assert_eq!(options.robustness, Some(5));
+        assert_eq!(options.layout_str, vec!["xvlcw"]);
+
+        let options = Options::try_parse_from(["evaluate", "xvlcw"]).unwrap();
+        assert_eq!(options.robustness, None);
+    }
+
+    #[test]
+    fn summary_keys_do_not_consume_layouts() {
+        let options = Options::try_parse_from(["evaluate", "--summary-keys", "xvlcw"]).unwrap();
+        assert_eq!(options.summary_keys, Some(3));
+        assert_eq!(options.layout_str, vec!["xvlcw"]);
+
+        let options = Options::try_parse_from(["evaluate", "--summary-keys=5", "xvlcw"]).unwrap();
+        assert_eq!(options.summary_keys, Some(5));
+        assert_eq!(options.layout_str, vec!["xvlcw"]);
     }
 }
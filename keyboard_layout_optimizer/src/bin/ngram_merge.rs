@@ -2,7 +2,10 @@ use ahash::AHashMap;
 use clap::Parser;
 use std::{hash::Hash, path::Path, str::FromStr};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use layout_evaluation::{
+    atomic_file,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
 
 #[derive(Debug)]
 struct WeightedComponent(f64, String);
@@ -101,6 +104,7 @@ fn main() {
 
     log::info!("Writing result to {}...", options.out);
     let out = Path::new(&options.out);
+    atomic_file::clean_orphaned_temp_files(out).expect("Could not clean up output directory.");
     Unigrams {
         grams: res_unigrams,
     }
@@ -0,0 +1,90 @@
+use clap::Parser;
+use std::path::Path;
+
+use layout_evaluation::{
+    ngram_stats::{NgramsStatistics, DEFAULT_COVERAGE_LEVELS},
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Ngram statistics")]
+/// Print statistics of the ngram frequencies in a directory: total weight, number of distinct
+/// ngrams, entropy, the most frequent ngrams, and how many ngrams cover given shares of the weight
+struct Options {
+    /// Directory containing the ngram frequency files (1-grams.txt, 2-grams.txt, 3-grams.txt,
+    /// and optionally 4-grams.txt)
+    ngrams: String,
+
+    /// Number of most frequent ngrams to list per ngram order
+    #[clap(long, default_value = "10")]
+    top: usize,
+
+    /// Comma-separated shares of the total weight (in percent) for which the number of covering
+    /// ngrams is reported
+    #[clap(long)]
+    coverage: Option<String>,
+
+    /// Print the statistics as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+fn parse_levels(s: &str) -> Result<Vec<f64>, String> {
+    s.split(',')
+        .map(|l| {
+            let percent: f64 = l
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid coverage level '{}'", l))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("Coverage level {} is not within [0, 100]", percent));
+            }
+            Ok(percent / 100.0)
+        })
+        .collect()
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    env_logger::init();
+
+    let levels = match &options.coverage {
+        Some(s) => parse_levels(s).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => DEFAULT_COVERAGE_LEVELS.to_vec(),
+    };
+
+    let d = Path::new(&options.ngrams);
+    let p = d.join("1-grams.txt");
+    let unigrams = Unigrams::from_file(p.to_str().unwrap())
+        .unwrap_or_else(|_| panic!("Could not read 1-gramme file from '{:?}'.", &p));
+    let p = d.join("2-grams.txt");
+    let bigrams = Bigrams::from_file(p.to_str().unwrap())
+        .unwrap_or_else(|_| panic!("Could not read 2-gramme file from '{:?}'.", &p));
+    let p = d.join("3-grams.txt");
+    let trigrams = Trigrams::from_file(p.to_str().unwrap())
+        .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
+    let p = d.join("4-grams.txt");
+    let quadgrams = p.exists().then(|| {
+        Quadgrams::from_file(p.to_str().unwrap())
+            .unwrap_or_else(|_| panic!("Could not read 4-gramme file from '{:?}'.", &p))
+    });
+
+    let stats = NgramsStatistics::new(
+        &unigrams,
+        &bigrams,
+        &trigrams,
+        quadgrams.as_ref(),
+        options.top,
+        &levels,
+    );
+
+    if options.json {
+        println!("{}", serde_json::to_string(&stats).unwrap());
+    } else {
+        print!("{}", stats);
+    }
+}
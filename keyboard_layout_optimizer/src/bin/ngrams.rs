@@ -1,38 +1,333 @@
 use clap::Parser;
-use std::{fs, path::Path};
+use std::{fs::File, io::BufReader, path::Path};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use layout_evaluation::{
+    atomic_file, corpus,
+    graphemes::GraphemeClusters,
+    json_corpus,
+    ngrams::{Bigrams, ChunkOverlap, Quadgrams, TextOptions, TextWeights, Trigrams, Unigrams},
+    normalization::TextNormalization,
+    results::MetricType,
+    token_filter::TokenFilter,
+};
 
 #[derive(Parser, Debug)]
-#[clap(name = "Ngram frequency generator")]
-/// Generate ngram-frequency files from a given text file.
+#[clap(name = "Ngram frequency generator", allow_missing_positional = true)]
+/// Generate ngram-frequency files from a given text file or a directory of text files.
 struct Options {
     /// Read text from this file
-    filename: String,
+    #[clap(required_unless_present_any = &["input-dir", "from-json", "append-to"])]
+    filename: Option<String>,
 
     /// Name for resulting ngram frequencies (a directory at that path will be generated)
-    out: String,
+    #[clap(required_unless_present = "append-to")]
+    out: Option<String>,
+
+    /// Add the counts of the text to the ngram frequencies in this directory (e.g. generated
+    /// from previous texts with this binary) instead of starting from scratch. The result is
+    /// written back to the directory unless another output directory is given.
+    #[clap(long, conflicts_with = "from-json")]
+    append_to: Option<String>,
+
+    /// Also generate quadgram frequencies (only used by quadgram metrics)
+    #[clap(long)]
+    quadgrams: bool,
+
+    /// Read text from all files in this directory (recursively) instead of a single file
+    #[clap(long, conflicts_with = "filename")]
+    input_dir: Option<String>,
+
+    /// Convert the ngram maps of a JSON corpus (with "characters", "bigrams", and "trigrams"
+    /// maps, as used by e.g. Oxeylyzer or genkey) to ngram frequency files instead of counting
+    /// ngrams in texts
+    #[clap(long, conflicts_with_all = &["filename", "input-dir"])]
+    from_json: Option<String>,
+
+    /// Only read files from the input directory whose names match one of these comma-separated
+    /// glob patterns (e.g. "*.txt,*.md")
+    #[clap(long)]
+    include: Option<String>,
+
+    /// Apply Unicode NFC normalization to the text, e.g. combine "a" followed by a combining
+    /// diaeresis into "ä"
+    #[clap(long)]
+    nfc: bool,
+
+    /// Replace uppercase letters by their lowercase forms (the total weight stays the same)
+    #[clap(long)]
+    fold_case: bool,
+
+    /// Only count ngrams within words, i.e. restart bigrams, trigrams, and quadgrams at each
+    /// whitespace or punctuation character (unigrams are counted as usual)
+    #[clap(long)]
+    word_internal: bool,
+
+    /// Characters separating words for `--word-internal` (replaces the default whitespace and
+    /// punctuation characters)
+    #[clap(long, requires = "word-internal")]
+    word_separators: Option<String>,
+
+    /// Treatment of grapheme clusters consisting of multiple characters (e.g. emoji sequences
+    /// or Devanagari syllables): "chars" counts their characters separately, "first-char" uses
+    /// the first character of each cluster, and "skip" drops ngrams containing such clusters
+    /// (their weight is logged)
+    #[clap(long, default_value = "chars")]
+    grapheme_clusters: GraphemeClusters,
+
+    /// Remove tokens that are not typed character by character (URLs, long hexadecimal strings,
+    /// and base64-like runs) before counting
+    #[clap(long)]
+    exclude_tokens: bool,
+
+    /// Remove tokens matching this regular expression before counting (may be given multiple
+    /// times, replaces the default patterns of `--exclude-tokens`)
+    #[clap(long)]
+    exclude_pattern: Vec<String>,
 }
 
 fn main() {
     dotenv::dotenv().ok();
-    let options = Options::parse();
+    let mut options = Options::parse();
     env_logger::init();
 
-    let text = fs::read_to_string(&options.filename)
-        .unwrap_or_else(|_| panic!("Could not read corpus file from {}.", options.filename));
+    // a single positional argument is taken as output directory
+    if options.append_to.is_some() && options.filename.is_none() && options.input_dir.is_none() {
+        options.filename = options.out.take();
+    }
+    if options.filename.is_none() && options.input_dir.is_none() && options.from_json.is_none() {
+        eprintln!("Either a text file or --input-dir is required");
+        std::process::exit(1);
+    }
+
+    if options.include.is_some() && options.input_dir.is_none() {
+        eprintln!("--include can only be used with --input-dir");
+        std::process::exit(1);
+    }
+
+    if let Some(json) = &options.from_json {
+        convert_json_corpus(json, options.out.as_ref().unwrap());
+        return;
+    }
+
+    let text_options = TextOptions {
+        normalization: if options.nfc {
+            TextNormalization::Nfc
+        } else {
+            TextNormalization::None
+        },
+        fold_case: options.fold_case,
+        word_internal_only: options.word_internal,
+        word_separators: options.word_separators.clone(),
+        grapheme_clusters: options.grapheme_clusters,
+    };
+    let token_filter = if !options.exclude_pattern.is_empty() {
+        Some(
+            TokenFilter::new(&options.exclude_pattern).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }),
+        )
+    } else if options.exclude_tokens {
+        Some(TokenFilter::default())
+    } else {
+        None
+    };
+    let mut ngrams = CollectedNgrams::new(options.quadgrams, token_filter);
+
+    match &options.input_dir {
+        Some(dir) => {
+            let patterns = options
+                .include
+                .as_deref()
+                .map(corpus::parse_patterns)
+                .unwrap_or_default();
+            let files = corpus::text_files(&[dir.into()], &patterns)
+                .unwrap_or_else(|e| panic!("Could not list corpus files in {}: {}", dir, e));
+            let n_read = corpus::for_each_file(&files, |reader| {
+                // ngrams do not span file boundaries
+                let mut overlaps =
+                    [1, 2, 3, 4].map(|n| ChunkOverlap::with_options(n, &text_options));
+                corpus::for_each_chunk(reader, |chunk| {
+                    ngrams.add_chunk(chunk, &mut overlaps, &text_options)
+                })
+            })
+            .unwrap_or_else(|e| panic!("Could not read corpus files in {}: {}", dir, e));
+            log::info!("Read {} of {} corpus files in {}", n_read, files.len(), dir);
+        }
+        None => {
+            let filename = options.filename.as_ref().unwrap();
+            // the file is streamed, so that large corpora do not need to fit into memory
+            let file = File::open(filename)
+                .unwrap_or_else(|_| panic!("Could not read corpus file from {}.", filename));
+            let mut overlaps = [1, 2, 3, 4].map(|n| ChunkOverlap::with_options(n, &text_options));
+            corpus::for_each_chunk(BufReader::new(file), |chunk| {
+                ngrams.add_chunk(chunk, &mut overlaps, &text_options)
+            })
+            .unwrap_or_else(|e| panic!("Could not read corpus file from {}: {}", filename, e));
+        }
+    };
+
+    if ngrams.token_filter.is_some() {
+        log::info!(
+            "Excluded tokens with a total of {} characters (unigram weight)",
+            ngrams.excluded_weight
+        );
+    }
 
-    let d = Path::new(&options.out);
+    if options.fold_case {
+        log::info!(
+            "Case-folded weight: unigrams {}, bigrams {}, trigrams {}, quadgrams {}",
+            ngrams.weights[0].folded,
+            ngrams.weights[1].folded,
+            ngrams.weights[2].folded,
+            ngrams.weights[3].folded,
+        );
+    }
+
+    if options.grapheme_clusters == GraphemeClusters::Skip {
+        log::info!(
+            "Skipped weight (grapheme clusters): unigrams {}, bigrams {}, trigrams {}, quadgrams {}",
+            ngrams.weights[0].skipped,
+            ngrams.weights[1].skipped,
+            ngrams.weights[2].skipped,
+            ngrams.weights[3].skipped,
+        );
+    }
+
+    if let Some(dir) = &options.append_to {
+        ngrams.merge_existing(Path::new(dir));
+    }
+
+    let d = Path::new(options.out.as_ref().or(options.append_to.as_ref()).unwrap());
+    atomic_file::clean_orphaned_temp_files(d).expect("Could not clean up output directory.");
 
-    let unigrams = Unigrams::from_text(&text).expect("Could not generate unigrams from text.");
     let p = d.join("1-grams.txt");
-    unigrams.save_frequencies(p).unwrap();
+    ngrams.unigrams.save_frequencies(p).unwrap();
 
-    let bigrams = Bigrams::from_text(&text).expect("Could not generate bigrams from text.");
     let p = d.join("2-grams.txt");
-    bigrams.save_frequencies(p).unwrap();
+    ngrams.bigrams.save_frequencies(p).unwrap();
 
-    let trigrams = Trigrams::from_text(&text).expect("Could not generate trigrams from text.");
     let p = d.join("3-grams.txt");
-    trigrams.save_frequencies(p).unwrap();
+    ngrams.trigrams.save_frequencies(p).unwrap();
+
+    if let Some(quadgrams) = ngrams.quadgrams {
+        let p = d.join("4-grams.txt");
+        quadgrams.save_frequencies(p).unwrap();
+    }
+}
+
+/// Write the ngrams of a JSON corpus as ngram frequency files into the directory `out`.
+fn convert_json_corpus(json: &str, out: &str) {
+    let (unigrams, bigrams, trigrams, missing) =
+        json_corpus::read_corpus(json).unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        });
+
+    let d = Path::new(out);
+    atomic_file::clean_orphaned_temp_files(d).expect("Could not clean up output directory.");
+
+    if !missing.contains(&MetricType::Unigram) {
+        unigrams.save_frequencies(d.join("1-grams.txt")).unwrap();
+    }
+    if !missing.contains(&MetricType::Bigram) {
+        bigrams.save_frequencies(d.join("2-grams.txt")).unwrap();
+    }
+    if !missing.contains(&MetricType::Trigram) {
+        trigrams.save_frequencies(d.join("3-grams.txt")).unwrap();
+    }
+}
+
+/// Ngrams collected from (possibly multiple) texts
+struct CollectedNgrams {
+    unigrams: Unigrams,
+    bigrams: Bigrams,
+    trigrams: Trigrams,
+    quadgrams: Option<Quadgrams>,
+    /// Weights of the (uni-, bi-, tri-, quad-) grams that contain case-folded letters or have
+    /// been skipped due to grapheme clusters
+    weights: [TextWeights; 4],
+    /// Filter for tokens that are removed before counting
+    token_filter: Option<TokenFilter>,
+    /// Number of characters removed by the token filter
+    excluded_weight: usize,
+}
+
+impl CollectedNgrams {
+    fn new(with_quadgrams: bool, token_filter: Option<TokenFilter>) -> Self {
+        Self {
+            unigrams: Unigrams::from_text("").unwrap(),
+            bigrams: Bigrams::from_text("").unwrap(),
+            trigrams: Trigrams::from_text("").unwrap(),
+            quadgrams: with_quadgrams.then(Quadgrams::default),
+            weights: [TextWeights::default(); 4],
+            token_filter,
+            excluded_weight: 0,
+        }
+    }
+
+    /// Add the counts of the ngram frequency files in a directory. Missing files are skipped
+    /// with a warning.
+    fn merge_existing(&mut self, dir: &Path) {
+        let path_if_exists = |filename: &str| {
+            let p = dir.join(filename);
+            if p.exists() {
+                Some(p.to_str().unwrap().to_string())
+            } else {
+                log::warn!("Ngram file '{:?}' does not exist, nothing to append to", p);
+                None
+            }
+        };
+        let read_error = |p: &str| -> ! { panic!("Could not read ngram file from '{}'.", p) };
+
+        if let Some(p) = path_if_exists("1-grams.txt") {
+            self.unigrams
+                .merge(Unigrams::from_file(&p).unwrap_or_else(|_| read_error(&p)));
+        }
+        if let Some(p) = path_if_exists("2-grams.txt") {
+            self.bigrams
+                .merge(Bigrams::from_file(&p).unwrap_or_else(|_| read_error(&p)));
+        }
+        if let Some(p) = path_if_exists("3-grams.txt") {
+            self.trigrams
+                .merge(Trigrams::from_file(&p).unwrap_or_else(|_| read_error(&p)));
+        }
+        if let Some(quadgrams) = self.quadgrams.as_mut() {
+            if let Some(p) = path_if_exists("4-grams.txt") {
+                quadgrams.merge(Quadgrams::from_file(&p).unwrap_or_else(|_| read_error(&p)));
+            }
+        }
+    }
+
+    /// Add a chunk of a text that is streamed chunk-wise. The overlaps (one per ngram order)
+    /// carry the end of the preceding chunks.
+    fn add_chunk(&mut self, chunk: &str, overlaps: &mut [ChunkOverlap; 4], options: &TextOptions) {
+        let chunk = match &self.token_filter {
+            Some(filter) => {
+                let (filtered, n_removed) = filter.apply(chunk);
+                self.excluded_weight += n_removed;
+                filtered
+            }
+            None => chunk.into(),
+        };
+        // normalize only once for all ngram orders
+        let chunk = options.normalization.apply(&chunk);
+        let options = &TextOptions {
+            normalization: TextNormalization::None,
+            ..options.clone()
+        };
+
+        self.weights[0] += self
+            .unigrams
+            .add_text_with_options(&overlaps[0].join(&chunk), options);
+        self.weights[1] += self
+            .bigrams
+            .add_text_with_options(&overlaps[1].join(&chunk), options);
+        self.weights[2] += self
+            .trigrams
+            .add_text_with_options(&overlaps[2].join(&chunk), options);
+        if let Some(quadgrams) = self.quadgrams.as_mut() {
+            self.weights[3] += quadgrams.add_text_with_options(&overlaps[3].join(&chunk), options);
+        }
+    }
 }
@@ -51,6 +51,18 @@ struct Options {
     /// Publishing options
     #[clap(flatten)]
     publishing_options: common::PublishingOptions,
+
+    /// Blacklist options
+    #[clap(flatten)]
+    blacklist_options: common::BlacklistOptions,
+
+    /// Hand balance options
+    #[clap(flatten)]
+    hand_balance_options: common::HandBalanceOptions,
+
+    /// Constraint options
+    #[clap(flatten)]
+    constraint_options: common::ConstraintOptions,
 }
 
 fn main() {
@@ -89,6 +101,17 @@ fn main() {
     });
 
     let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+    let blacklist = common::init_blacklist(&options.blacklist_options, layout_generator.as_ref());
+    let constraints = common::init_constraints(
+        &options.constraint_options,
+        &options.evaluation_parameters.layout_config,
+        options.evaluation_parameters.grouped_layout_generator,
+        options.hand_balance_options.parameters(),
+    )
+    .unwrap_or_else(|e| {
+        log::error!("{}", e);
+        process::exit(1);
+    });
 
     let mut optimization_params = optimization::Parameters::from_yaml(
         &options.optimization_parameters,
@@ -111,10 +134,12 @@ fn main() {
             &optimization_params,
             &evaluator,
             &fix_from,
-            &layout_generator,
+            layout_generator.as_ref(),
             &options.fix.clone().unwrap_or_default(),
             start_layout.is_some(),
             !options.no_cache_results,
+            blacklist.clone(),
+            Some(&constraints),
         );
         let evaluation_result = evaluator.evaluate_layout(&layout);
         let cost = evaluation_result.total_cost();
@@ -126,6 +151,24 @@ fn main() {
             final_results.highlighted_fmt(Some(&layout_str), 10)
         );
 
+        if let Some(blacklist) = &blacklist {
+            println!(
+                "{}\n",
+                common::format_blacklist_similarity(blacklist, &layout)
+            );
+            if blacklist.forbids(&layout) {
+                log::error!(
+                    "No layout allowed by the blacklist found. Discarding '{}'.",
+                    layout_str
+                );
+                if options.run_forever {
+                    continue;
+                } else {
+                    break;
+                }
+            }
+        }
+
         // Log solution to file.
         if let Some(filename) = &options.append_solutions_to {
             common::append_to_file(&layout_str, filename);
@@ -133,10 +176,14 @@ fn main() {
 
         // Publish to webservice.
         let o = &options.publishing_options;
-        if o.publish_as.is_some() && cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+        if let Some(publish_as) = o
+            .publish_as
+            .as_ref()
+            .filter(|_| cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY))
+        {
             common::publish_to_webservice(
                 &layout_str,
-                o.publish_as.as_ref().unwrap(),
+                publish_as,
                 &o.publish_to,
                 &o.publish_layout_config,
             );
@@ -1,6 +1,9 @@
 use keyboard_layout_optimizer::common;
-use layout_evaluation::cache::Cache;
-use layout_optimization_sa::optimization;
+use layout_evaluation::{atomic_file, cache::Cache};
+use layout_optimization_sa::{
+    acceptance::{AcceptanceStats, AutoInitTemperature},
+    optimization,
+};
 
 use clap::Parser;
 use colored::Colorize;
@@ -46,6 +49,20 @@ struct Options {
     #[clap(short, long)]
     greedy: bool,
 
+    /// Determine the initial temperature from a burn-in, such that worse neighbors of the
+    /// starting layout are accepted at this rate (below 0.5; will be overwritten by --init-temp
+    /// and --greedy)
+    #[clap(long)]
+    auto_init_temp: Option<f64>,
+
+    /// Collect acceptance statistics over intervals of this many iterations
+    #[clap(long)]
+    stats_interval: Option<u64>,
+
+    /// Append acceptance statistics to this CSV file
+    #[clap(long)]
+    stats_to: Option<String>,
+
     /// If used, log every single iteration instead of every 100th.
     #[clap(long)]
     log_everything: bool,
@@ -61,6 +78,18 @@ struct Options {
     /// Publishing options
     #[clap(flatten)]
     publishing_options: common::PublishingOptions,
+
+    /// Blacklist options
+    #[clap(flatten)]
+    blacklist_options: common::BlacklistOptions,
+
+    /// Hand balance options
+    #[clap(flatten)]
+    hand_balance_options: common::HandBalanceOptions,
+
+    /// Constraint options
+    #[clap(flatten)]
+    constraint_options: common::ConstraintOptions,
 }
 
 /// An iterator for layouts to feed into the optimizer.
@@ -146,6 +175,17 @@ fn main() {
         .collect();
 
     let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+    let blacklist = common::init_blacklist(&options.blacklist_options, layout_generator.as_ref());
+    let constraints = common::init_constraints(
+        &options.constraint_options,
+        &options.evaluation_parameters.layout_config,
+        options.evaluation_parameters.grouped_layout_generator,
+        options.hand_balance_options.parameters(),
+    )
+    .unwrap_or_else(|e| {
+        log::error!("{}", e);
+        process::exit(1);
+    });
 
     let mut optimization_params = optimization::Parameters::from_yaml(
         &options.optimization_parameters,
@@ -156,13 +196,39 @@ fn main() {
             &options.optimization_parameters
         )
     });
+    if let Some(target_acceptance) = options.auto_init_temp {
+        let samples = optimization_params
+            .auto_init_temperature
+            .as_ref()
+            .map(|auto| auto.samples)
+            .unwrap_or(100);
+        optimization_params.auto_init_temperature = Some(AutoInitTemperature {
+            target_acceptance,
+            samples,
+        });
+    }
     if options.greedy {
         optimization_params.init_temp = Some(f64::MIN_POSITIVE);
+        optimization_params.auto_init_temperature = None;
     } else if options.init_temp.is_some() {
         optimization_params.init_temp = options.init_temp;
+        optimization_params.auto_init_temperature = None;
     }
     optimization_params.correct_init_temp();
 
+    if options.stats_interval.is_some() {
+        optimization_params.stats_interval = options.stats_interval;
+    }
+    if options.stats_to.is_some() {
+        optimization_params.stats_file = options.stats_to.clone();
+    }
+    if let Some(filename) = &optimization_params.stats_file {
+        if !std::path::Path::new(filename).exists() {
+            atomic_file::append_durable(filename, format!("{}\n", AcceptanceStats::CSV_HEADER))
+                .unwrap_or_else(|e| panic!("Could not write to {}: {}", filename, e));
+        }
+    }
+
     let mut layouts: Vec<String> = start_layouts.to_vec();
     if layouts.is_empty() {
         layouts = vec![fix_from];
@@ -199,11 +265,13 @@ fn main() {
                 &optimization_params,
                 &fix_from,
                 &options.fix.clone().unwrap_or_default(),
-                &layout_generator,
+                layout_generator.as_ref(),
                 start_from_layout,
                 &evaluator,
                 options.log_everything,
                 cache.clone(),
+                blacklist.clone(),
+                Some(&constraints),
                 None,
             );
             let evaluation_result = evaluator.evaluate_layout(&layout);
@@ -222,6 +290,21 @@ fn main() {
                 final_results.highlighted_fmt(Some(&layout_str), 10),
             );
 
+            if let Some(blacklist) = &blacklist {
+                println!(
+                    "{}\n",
+                    common::format_blacklist_similarity(blacklist, &layout)
+                );
+                if blacklist.forbids(&layout) {
+                    log::error!(
+                        "{} No layout allowed by the blacklist found. Discarding '{}'.",
+                        format!("{}:", process_id).yellow().bold(),
+                        layout_str,
+                    );
+                    return;
+                }
+            }
+
             // Log solution to file.
             if let Some(filename) = &options.append_solutions_to {
                 common::append_to_file(&layout_str, filename);
@@ -229,10 +312,14 @@ fn main() {
 
             // Publish to webservice.
             let o = &options.publishing_options;
-            if o.publish_as.is_some() && cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+            if let Some(publish_as) = o
+                .publish_as
+                .as_ref()
+                .filter(|_| cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY))
+            {
                 common::publish_to_webservice(
                     &layout_str,
-                    o.publish_as.as_ref().unwrap(),
+                    publish_as,
                     &o.publish_to,
                     &o.publish_layout_config,
                 );
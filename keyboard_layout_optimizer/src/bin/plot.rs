@@ -41,13 +41,12 @@ fn main() {
             panic!("{:?}", e);
         }
     };
-    let max_layer = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0);
-    for layer in 0..max_layer + 1 {
+    for layer in 0..layout.n_layers() {
         println!(
             "Layout '{}' (layer {}):\n{}",
             layout_str,
             layer + 1,
-            layout.plot_layer(layer as usize)
+            layout.plot_layer(layer)
         );
     }
     println!("Layout compact: \n{}", layout.plot_compact());
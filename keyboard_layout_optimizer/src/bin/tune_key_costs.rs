@@ -0,0 +1,110 @@
+use keyboard_layout::{
+    config::LayoutConfig,
+    key_cost_tuning::{key_names_from_yaml, read_latencies_csv, tune_key_costs},
+    keyboard::Keyboard,
+};
+use layout_evaluation::atomic_file;
+
+use anyhow::Result;
+use clap::Parser;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Key cost tuning")]
+/// Derive key costs from measured key latencies (e.g. exported from a typing trainer) and
+/// write them as a layout config overlay that extends the original layout config
+struct Options {
+    /// CSV file with the columns key name, mean latency (ms), and number of samples
+    latencies: String,
+
+    /// Filename of the resulting layout config overlay
+    out: String,
+
+    /// Layout config whose keyboard shall be tuned
+    #[clap(long, default_value = "config/keyboard/standard.yml")]
+    layout_config: String,
+
+    /// Mapping of the key names used in the CSV file to matrix positions
+    #[clap(long, default_value = "config/keyboard/key_names/standard.yml")]
+    key_names: String,
+
+    /// Average cost of the home keys (defaults to their average original cost)
+    #[clap(long)]
+    baseline: Option<f64>,
+
+    /// Ignore keys with less samples than this
+    #[clap(long, default_value = "1")]
+    min_samples: usize,
+}
+
+/// Path of `base` as seen from the directory of `out` (absolute if `base` is not below it).
+fn extends_path(base: &str, out: &str) -> PathBuf {
+    let base = fs::canonicalize(base).unwrap_or_else(|_| PathBuf::from(base));
+    let out_dir = Path::new(out)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    match fs::canonicalize(out_dir) {
+        Ok(out_dir) => match base.strip_prefix(&out_dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => base,
+        },
+        Err(_) => base,
+    }
+}
+
+fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    env_logger::init();
+
+    let layout_config = LayoutConfig::from_yaml(&options.layout_config)?;
+    let row_lengths = layout_config.keyboard.row_lengths();
+    let keyboard = Keyboard::from_yaml_object(layout_config.keyboard);
+
+    let key_names = key_names_from_yaml(&options.key_names)?;
+    let latencies = read_latencies_csv(&fs::read_to_string(&options.latencies)?)?;
+
+    let tuned = tune_key_costs(
+        &keyboard,
+        &latencies,
+        &key_names,
+        options.baseline,
+        options.min_samples,
+    )?;
+
+    let mut costs = tuned.costs.iter();
+    let rows: Vec<String> = row_lengths
+        .iter()
+        .map(|n| {
+            let row: Vec<String> = costs
+                .by_ref()
+                .take(*n)
+                .map(|c| format!("{:.2}", c))
+                .collect();
+            format!("    - [{}]", row.join(", "))
+        })
+        .collect();
+
+    let overlay = format!(
+        "# Key costs derived from '{}' (home key baseline: {:.2}).\n\
+         # {} keys kept their original costs due to missing data.\n\
+         extends: {}\n\
+         \n\
+         keyboard:\n  key_costs:\n{}\n",
+        options.latencies,
+        tuned.baseline,
+        tuned.missing.len(),
+        extends_path(&options.layout_config, &options.out).display(),
+        rows.join("\n"),
+    );
+
+    atomic_file::write_atomic(&options.out, overlay)?;
+    log::info!("Wrote tuned key costs to {}", options.out);
+
+    Ok(())
+}
@@ -0,0 +1,500 @@
+//! The `bundle` module packs everything that is required for reproducing the evaluation of a
+//! layout into a single file (a "reproducibility bundle") and replays evaluations from such
+//! bundles.
+//!
+//! A bundle is a tar archive (compressed with zstd if the filename ends with `.zst`) containing
+//! the following files:
+//! - `manifest.json`: The bundle's format version, the crate version, the layout, the options
+//!   of the evaluation, the fingerprints of all inputs, and the resulting total cost
+//! - `layout_config.yml`: The layout config (with all `extends` resolved)
+//! - `evaluation_parameters.yml`: The evaluation parameters
+//! - `ngrams/{1,2,3}-grams.txt`: The ngram data exactly as passed to the evaluation, i.e. after
+//!   all preprocessing (optional, by default only their fingerprint is stored)
+//! - `result.json`: The evaluation result
+
+use crate::common;
+
+use keyboard_layout::config::{self, LayoutConfig};
+use layout_evaluation::{
+    atomic_file,
+    config::EvaluationParameters,
+    fingerprint,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    results::{EvaluationResult, MetricType},
+};
+
+use ahash::AHashMap;
+use anyhow::{anyhow, bail, Result};
+use colored::Colorize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+/// Version of the bundle format. Bundles with a higher version can not be replayed.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const LAYOUT_CONFIG_FILE: &str = "layout_config.yml";
+const EVALUATION_PARAMETERS_FILE: &str = "evaluation_parameters.yml";
+const RESULT_FILE: &str = "result.json";
+const UNIGRAMS_FILE: &str = "ngrams/1-grams.txt";
+const BIGRAMS_FILE: &str = "ngrams/2-grams.txt";
+const TRIGRAMS_FILE: &str = "ngrams/3-grams.txt";
+
+/// Magic number at the start of zstd-compressed files
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// Compression level of compressed bundles (zstd's default)
+const ZSTD_LEVEL: i32 = 3;
+
+/// Fingerprints of the inputs of the bundled evaluation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprints {
+    pub layout_config: String,
+    pub evaluation_parameters: String,
+    pub ngrams: String,
+    /// Combination of the above
+    pub evaluation: String,
+}
+
+/// Description of the contents of a bundle.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    /// Version of the crate that created the bundle
+    pub crate_version: String,
+    pub layout: String,
+    pub grouped_layout_generator: bool,
+    pub profile: Option<String>,
+    pub split_modifiers: bool,
+    /// Types of metrics that were skipped due to missing ngram data
+    pub skipped_metric_types: Vec<MetricType>,
+    /// Where the ngram data was read from (for information only)
+    pub ngram_source: String,
+    /// Whether the ngram data is part of the bundle
+    pub ngrams_included: bool,
+    pub fingerprints: Fingerprints,
+    pub total_cost: f64,
+}
+
+/// A fingerprint that differs between the bundle and the replay.
+#[derive(Clone, Debug)]
+pub struct FingerprintMismatch {
+    pub input: String,
+    pub bundled: String,
+    pub replayed: String,
+}
+
+/// The outcome of replaying a bundle.
+#[derive(Clone, Debug)]
+pub struct ReplayReport {
+    pub manifest: Manifest,
+    pub total_cost: f64,
+    pub tolerance: f64,
+    pub fingerprint_mismatches: Vec<FingerprintMismatch>,
+    /// Whether the bundle was created with a different crate version
+    pub crate_version_differs: bool,
+}
+
+impl ReplayReport {
+    /// Whether the replayed total cost equals the bundled one (within the tolerance).
+    pub fn cost_matches(&self) -> bool {
+        (self.total_cost - self.manifest.total_cost).abs() <= self.tolerance
+    }
+
+    /// Whether the replay reproduced the bundled evaluation.
+    pub fn is_reproduced(&self) -> bool {
+        self.cost_matches() && self.fingerprint_mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Layout: {}", self.manifest.layout)?;
+        if self.crate_version_differs {
+            writeln!(
+                f,
+                "{}",
+                format!(
+                    "Bundle was created with version {} (this is version {})",
+                    self.manifest.crate_version,
+                    env!("CARGO_PKG_VERSION")
+                )
+                .yellow()
+            )?;
+        }
+        for m in self.fingerprint_mismatches.iter() {
+            writeln!(
+                f,
+                "{}",
+                format!(
+                    "Fingerprint mismatch of {}: bundled {}, replayed {}",
+                    m.input, m.bundled, m.replayed
+                )
+                .red()
+            )?;
+        }
+        writeln!(
+            f,
+            "Total cost: bundled {:.6}, replayed {:.6} (difference {:.2e}, tolerance {:.2e})",
+            self.manifest.total_cost,
+            self.total_cost,
+            (self.total_cost - self.manifest.total_cost).abs(),
+            self.tolerance
+        )?;
+        if self.is_reproduced() {
+            write!(f, "{}", "Reproduced".green().bold())
+        } else {
+            write!(f, "{}", "NOT reproduced".red().bold())
+        }
+    }
+}
+
+/// Create a bundle of the evaluation of `layout_str` with the given options. The ngram data is
+/// read (and preprocessed) again according to the options. Evaluations on multiple keyboards
+/// can not be bundled.
+pub fn create<P: AsRef<Path>>(
+    filename: P,
+    options: &common::Options,
+    layout_str: &str,
+    result: &EvaluationResult,
+    include_ngrams: bool,
+) -> Result<Manifest> {
+    if !options.keyboards.is_empty() {
+        bail!("Evaluations on multiple keyboards can not be bundled");
+    }
+    let layout_config = config::resolved_yaml(&options.layout_config)?;
+    let evaluation_parameters = fs::read_to_string(&options.eval_parameters)?;
+    let eval_params = EvaluationParameters::from_str_with_profile(
+        &evaluation_parameters,
+        options.profile.as_deref(),
+    )?;
+
+    let (unigrams, bigrams, trigrams, skipped_metric_types) =
+        common::init_ngrams(options, &eval_params.ngrams);
+
+    let fingerprints = fingerprints(
+        &layout_config,
+        &evaluation_parameters,
+        &unigrams,
+        &bigrams,
+        &trigrams,
+    )?;
+
+    let ngram_source = match (&options.text, &options.corpus) {
+        (Some(_), _) => "text".to_string(),
+        (None, Some(corpus)) => format!("corpus file {}", corpus),
+        (None, None) => match &options.reweigh {
+            Some(weights) => format!(
+                "ngram files {} reweighed with {}",
+                common::ngrams_dirs_str(&options.ngrams),
+                weights.iter().join(",")
+            ),
+            None => format!("ngram files {}", common::ngrams_dirs_str(&options.ngrams)),
+        },
+    };
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        layout: layout_str.to_string(),
+        grouped_layout_generator: options.grouped_layout_generator,
+        profile: options.profile.clone(),
+        split_modifiers: eval_params.ngram_mapper.split_modifiers.splits_any_layer()
+            && !options.no_split_modifiers,
+        skipped_metric_types,
+        ngram_source,
+        ngrams_included: include_ngrams,
+        fingerprints,
+        total_cost: result.total_cost(),
+    };
+
+    let manifest_json = serde_json::to_string_pretty(&manifest)?;
+    let result_json = serde_json::to_string_pretty(result)?;
+    let mut entries: Vec<(&str, Vec<u8>)> = vec![
+        (MANIFEST_FILE, manifest_json.into_bytes()),
+        (LAYOUT_CONFIG_FILE, layout_config.into_bytes()),
+        (
+            EVALUATION_PARAMETERS_FILE,
+            evaluation_parameters.into_bytes(),
+        ),
+        (RESULT_FILE, result_json.into_bytes()),
+    ];
+    if include_ngrams {
+        let skipped = &manifest.skipped_metric_types;
+        if !skipped.contains(&MetricType::Unigram) {
+            entries.push((UNIGRAMS_FILE, unigrams.frequencies_str().into_bytes()));
+        }
+        if !skipped.contains(&MetricType::Bigram) {
+            entries.push((BIGRAMS_FILE, bigrams.frequencies_str().into_bytes()));
+        }
+        if !skipped.contains(&MetricType::Trigram) {
+            entries.push((TRIGRAMS_FILE, trigrams.frequencies_str().into_bytes()));
+        }
+    }
+
+    let mut data = write_tar(&entries)?;
+    if filename
+        .as_ref()
+        .extension()
+        .map_or(false, |ext| ext == "zst")
+    {
+        data = zstd::encode_all(data.as_slice(), ZSTD_LEVEL)?;
+    }
+    atomic_file::write_atomic(filename, data)?;
+
+    Ok(manifest)
+}
+
+/// Replay the evaluation of a bundle. If the bundle does not contain the ngram data, it is read
+/// according to the given options.
+pub fn replay<P: AsRef<Path>>(
+    filename: P,
+    options: &common::Options,
+    tolerance: f64,
+) -> Result<ReplayReport> {
+    let mut data = fs::read(filename)?;
+    if data.starts_with(&ZSTD_MAGIC) {
+        data = zstd::decode_all(data.as_slice())?;
+    }
+    let files: AHashMap<String, Vec<u8>> = read_tar(&data)?.into_iter().collect();
+    let file = |name: &str| -> Result<String> {
+        let content = files
+            .get(name)
+            .ok_or_else(|| anyhow!("Bundle does not contain '{}'", name))?;
+        Ok(String::from_utf8(content.clone())?)
+    };
+
+    let manifest: Manifest = serde_json::from_str(&file(MANIFEST_FILE)?)?;
+    if manifest.format_version > FORMAT_VERSION {
+        bail!(
+            "Bundle format version {} is not supported (supported up to version {})",
+            manifest.format_version,
+            FORMAT_VERSION
+        );
+    }
+
+    let layout_config_yaml = file(LAYOUT_CONFIG_FILE)?;
+    let layout_config: LayoutConfig = serde_yaml::from_str(&layout_config_yaml)?;
+    layout_config.validate()?;
+    let evaluation_parameters = file(EVALUATION_PARAMETERS_FILE)?;
+    let eval_params = EvaluationParameters::from_str_with_profile(
+        &evaluation_parameters,
+        manifest.profile.as_deref(),
+    )?;
+
+    let (unigrams, bigrams, trigrams, skipped_metric_types) = if manifest.ngrams_included {
+        let unigrams = match files.get(UNIGRAMS_FILE) {
+            Some(_) => Unigrams::from_frequencies_str(&file(UNIGRAMS_FILE)?)?,
+            None => Unigrams::from_frequencies_str("")?,
+        };
+        let bigrams = match files.get(BIGRAMS_FILE) {
+            Some(_) => Bigrams::from_frequencies_str(&file(BIGRAMS_FILE)?)?,
+            None => Bigrams::from_frequencies_str("")?,
+        };
+        let trigrams = match files.get(TRIGRAMS_FILE) {
+            Some(_) => Trigrams::from_frequencies_str(&file(TRIGRAMS_FILE)?)?,
+            None => Trigrams::from_frequencies_str("")?,
+        };
+        (
+            unigrams,
+            bigrams,
+            trigrams,
+            manifest.skipped_metric_types.clone(),
+        )
+    } else {
+        log::info!(
+            "Bundle does not contain ngram data (bundled from {}), reading it according to the options",
+            manifest.ngram_source
+        );
+        common::init_ngrams(options, &eval_params.ngrams)
+    };
+
+    let replayed = fingerprints(
+        &layout_config_yaml,
+        &evaluation_parameters,
+        &unigrams,
+        &bigrams,
+        &trigrams,
+    )?;
+    let bundled = &manifest.fingerprints;
+    let fingerprint_mismatches: Vec<FingerprintMismatch> = [
+        (
+            "layout config",
+            &bundled.layout_config,
+            &replayed.layout_config,
+        ),
+        (
+            "evaluation parameters",
+            &bundled.evaluation_parameters,
+            &replayed.evaluation_parameters,
+        ),
+        ("ngrams", &bundled.ngrams, &replayed.ngrams),
+    ]
+    .iter()
+    .filter(|(_, b, r)| b != r)
+    .map(|(input, b, r)| FingerprintMismatch {
+        input: input.to_string(),
+        bundled: b.to_string(),
+        replayed: r.to_string(),
+    })
+    .collect();
+
+    let mut ngram_mapper_config = eval_params.ngram_mapper.clone();
+    if !manifest.split_modifiers {
+        ngram_mapper_config.split_modifiers.enabled = false;
+        ngram_mapper_config.split_modifiers.layers.clear();
+    }
+    let evaluator = common::build_evaluator(
+        &eval_params.metrics,
+        ngram_mapper_config,
+        (unigrams, bigrams, trigrams),
+        &skipped_metric_types,
+        manifest.profile.clone(),
+    );
+    let layout_generator = common::layout_generator_from_config(
+        layout_config,
+        manifest.grouped_layout_generator,
+        &eval_params.override_fingers,
+    );
+    let layout = layout_generator.generate(&manifest.layout)?;
+    let total_cost = evaluator.evaluate_layout(&layout).total_cost();
+
+    Ok(ReplayReport {
+        crate_version_differs: manifest.crate_version != env!("CARGO_PKG_VERSION"),
+        manifest,
+        total_cost,
+        tolerance,
+        fingerprint_mismatches,
+    })
+}
+
+fn fingerprints(
+    layout_config: &str,
+    evaluation_parameters: &str,
+    unigrams: &Unigrams,
+    bigrams: &Bigrams,
+    trigrams: &Trigrams,
+) -> Result<Fingerprints> {
+    let layout_config = fingerprint::fingerprint_yaml(layout_config)?;
+    let evaluation_parameters = fingerprint::fingerprint_yaml(evaluation_parameters)?;
+    let ngrams = fingerprint::fingerprint_ngrams(unigrams, bigrams, trigrams);
+    let evaluation = fingerprint::combine(&layout_config, &evaluation_parameters, &ngrams);
+
+    Ok(Fingerprints {
+        layout_config,
+        evaluation_parameters,
+        ngrams,
+        evaluation,
+    })
+}
+
+/// Pack files into a tar archive. The file metadata is fixed for reproducible bundles.
+fn write_tar(entries: &[(&str, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, content) in entries {
+        let mut header = tar::Header::new_ustar();
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_size(content.len() as u64);
+        builder.append_data(&mut header, name, content.as_slice())?;
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Read the regular files of a tar archive.
+fn read_tar(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = tar::Archive::new(Cursor::new(data));
+    let mut files = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // skip everything but regular files (e.g. directories)
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()?
+            .to_str()
+            .ok_or_else(|| anyhow!("Invalid file name in bundle"))?
+            .to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push((name, content));
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::Parser;
+
+    #[test]
+    fn tar_roundtrip() {
+        let entries = vec![
+            ("a.txt", b"hello".to_vec()),
+            ("dir/b.txt", vec![7u8; 1000]),
+            ("empty", Vec::new()),
+        ];
+        let archive = write_tar(&entries).unwrap();
+        // no timestamps or other varying metadata
+        assert_eq!(archive, write_tar(&entries).unwrap());
+
+        let files = read_tar(&archive).unwrap();
+        let expected: Vec<(String, Vec<u8>)> = entries
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn replay_reproduces_bundle() {
+        let options = common::Options::parse_from([
+            "evaluate",
+            "--text",
+            "Der schnelle braune Fuchs springt über den faulen Hund.",
+            "--layout-config",
+            "../config/keyboard/standard.yml",
+            "--eval-parameters",
+            "../config/evaluation/default.yml",
+        ]);
+        let layout_str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+        let (layout_generator, evaluator) = common::init(&options);
+        let result = evaluator.evaluate_layout(&layout_generator.generate(layout_str).unwrap());
+
+        for (include_ngrams, extension) in [(true, "tar"), (false, "tar"), (true, "tar.zst")] {
+            let filename = std::env::temp_dir().join(format!(
+                "bundle_{}_{}.{}",
+                include_ngrams,
+                std::process::id(),
+                extension
+            ));
+            let manifest =
+                create(&filename, &options, layout_str, &result, include_ngrams).unwrap();
+            assert_eq!(manifest.total_cost, result.total_cost());
+            let compressed = fs::read(&filename).unwrap().starts_with(&ZSTD_MAGIC);
+            assert_eq!(compressed, extension.ends_with(".zst"));
+
+            // without bundled ngrams, they are read according to the options
+            let report = replay(&filename, &options, 1e-6).unwrap();
+            assert!(report.is_reproduced(), "{}", report);
+
+            // different ngrams are detected
+            let other = common::Options::parse_from([
+                "evaluate",
+                "--text",
+                "The quick brown fox jumps over the lazy dog.",
+            ]);
+            let report = replay(&filename, &other, 1e-6).unwrap();
+            assert_eq!(report.is_reproduced(), include_ngrams, "{}", report);
+            fs::remove_file(&filename).unwrap();
+        }
+    }
+}
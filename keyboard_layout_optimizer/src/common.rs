@@ -1,43 +1,85 @@
 use keyboard_layout::{
-    config::LayoutConfig, grouped_layout_generator::GroupedLayoutGenerator, keyboard::Keyboard,
-    layout_generator::LayoutGenerator, neo_layout_generator::NeoLayoutGenerator,
+    config::LayoutConfig,
+    grouped_layout_generator::GroupedLayoutGenerator,
+    keyboard::{FingerOverride, Keyboard},
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
+    atomic_file,
     config::EvaluationParameters,
-    evaluation::Evaluator,
-    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    evaluation::{Evaluator, KeyboardVariant, MetricParameters},
+    ngram_mapper::on_demand_ngram_mapper::{NgramMapperConfig, OnDemandNgramMapper},
+    ngrams::{
+        read_ngrams_dir_with_cache, read_weighted_ngrams_dirs, Bigrams, NgramsConfig, TaggedNgrams,
+        Trigrams, Unigrams, WeightedNgramsDir,
+    },
+    results::MetricType,
+};
+use layout_optimization_common::{
+    blacklist::Blacklist,
+    constraints::ConstraintSet,
+    hand_balance::{HandBalanceMode, HandBalanceParameters},
 };
 
 use ahash::AHashMap;
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
+use colored::Colorize;
 use itertools::Itertools;
-use std::{
-    fs::{self, OpenOptions},
-    io::prelude::*,
-    path::Path,
-    sync::Arc,
-};
+use serde::Deserialize;
+use std::{fs, path::Path, str::FromStr, sync::Arc};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout evaluation")]
 pub struct Options {
-    /// Path to ngram files
+    /// Path to ngram files. May be given multiple times with weights in the form `path:weight`
+    /// for blending several corpora (each normalized to the same total weight before weighting)
     #[clap(
         short,
         long,
         default_value = "ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4"
     )]
-    pub ngrams: String,
+    pub ngrams: Vec<WeightedNgramsDir>,
+
+    /// Comma-separated weights (one per ngram directory) replacing those given with `--ngrams`,
+    /// e.g. "0.7,0.3". The corpora are read separately and blended afterwards
+    #[clap(long, use_value_delimiter = true, require_value_delimiter = true)]
+    pub reweigh: Option<Vec<f64>>,
+
+    /// Path to ngram files used for the ngrams that do not occur in the other ngram sources
+    /// (e.g. a generic corpus complementing a personal typing log given with `--ngrams`)
+    #[clap(long)]
+    pub fallback_ngrams: Option<String>,
+
+    /// Factor for the weights of the ngrams taken from `--fallback-ngrams`
+    #[clap(long, default_value = "1.0", requires = "fallback-ngrams")]
+    pub fallback_scale: f64,
 
     /// Filename of evaluation configuration file to use
     #[clap(short, long, default_value = "config/evaluation/default.yml")]
     pub eval_parameters: String,
 
+    /// Name of the weight profile (from the evaluation configuration file) to use
+    #[clap(long)]
+    pub profile: Option<String>,
+
     /// Filename of layout configuration file to use
     #[clap(short, long, default_value = "config/keyboard/standard.yml")]
     pub layout_config: String,
 
+    /// Further layout configuration files (keyboards) to evaluate the layouts on, with optional
+    /// weights in the form `path:weight`. May be given multiple times. The results are combined
+    /// with those on the keyboard of `--layout-config`
+    #[clap(long)]
+    pub keyboards: Vec<WeightedLayoutConfig>,
+
+    /// Weight of the keyboard of `--layout-config` when combining the results of multiple
+    /// keyboards (see `--keyboards`)
+    #[clap(long, default_value = "1.0")]
+    pub layout_config_weight: f64,
+
     /// Filename of corpus file to use instead of ngram files
     #[clap(short, long)]
     pub corpus: Option<String>,
@@ -62,11 +104,49 @@ pub struct Options {
     #[clap(long)]
     pub no_increase_common_ngrams: bool,
 
+    /// Always parse the ngram files instead of using (and writing) their binary cache files
+    #[clap(long)]
+    pub no_ngram_cache: bool,
+
     /// Interpred given layout string using the "grouped" logic
     #[clap(long)]
     pub grouped_layout_generator: bool,
 }
 
+/// A layout config along with the weight of its keyboard when evaluating layouts on multiple
+/// keyboards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedLayoutConfig {
+    /// Filename of the layout configuration file
+    pub path: String,
+    /// Share of the keyboard in the combined result
+    pub weight: f64,
+}
+
+impl FromStr for WeightedLayoutConfig {
+    type Err = anyhow::Error;
+
+    /// Parse a filename with an optional weight in the form `path:weight` (default weight 1.0).
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some((path, weight)) = s.rsplit_once(':') {
+            if let Ok(weight) = weight.parse::<f64>() {
+                if !weight.is_finite() || weight < 0.0 {
+                    bail!("Invalid weight {} for layout config '{}'", weight, path);
+                }
+                return Ok(Self {
+                    path: path.to_string(),
+                    weight,
+                });
+            }
+        }
+
+        Ok(Self {
+            path: s.to_string(),
+            weight: 1.0,
+        })
+    }
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout publication")]
 pub struct PublishingOptions {
@@ -88,11 +168,176 @@ pub struct PublishingOptions {
     pub publish_to: String,
 }
 
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout blacklist")]
+pub struct BlacklistOptions {
+    /// Never output layouts similar to the ones in this file (one layout string per line)
+    #[clap(long)]
+    pub blacklist: Option<String>,
+
+    /// Never output layouts similar to the ones published at this webservice url
+    /// (e.g. "https://keyboard-layout-optimizer.fly.dev/api")
+    #[clap(long)]
+    pub blacklist_url: Option<String>,
+
+    /// Fetch the blacklisted layouts for this layout config from the webservice
+    #[clap(long, default_value = "standard")]
+    pub blacklist_layout_config: String,
+
+    /// Layouts whose fraction of keys shared with a blacklisted layout exceeds this value
+    /// are rejected
+    #[clap(long, default_value = "0.9")]
+    pub blacklist_threshold: f64,
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout hand balance")]
+pub struct HandBalanceOptions {
+    /// Constrain candidate layouts to a split of the (non-thumb) unigram load whose left hand
+    /// fraction deviates from the target by at most this value (e.g. 0.05)
+    #[clap(long)]
+    pub hand_balance_margin: Option<f64>,
+
+    /// Target fraction of the load on the left hand
+    #[clap(long, default_value = "0.5", requires = "hand-balance-margin")]
+    pub hand_balance_target: f64,
+
+    /// Reject candidates outside of the margin instead of repairing them
+    #[clap(long, requires = "hand-balance-margin")]
+    pub hand_balance_reject: bool,
+}
+
+impl HandBalanceOptions {
+    /// The parameters of the hand balance constraint, if any.
+    pub fn parameters(&self) -> Option<HandBalanceParameters> {
+        self.hand_balance_margin
+            .map(|margin| HandBalanceParameters {
+                target: self.hand_balance_target,
+                margin,
+                mode: if self.hand_balance_reject {
+                    HandBalanceMode::Reject
+                } else {
+                    HandBalanceMode::Repair
+                },
+            })
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout constraints")]
+pub struct ConstraintOptions {
+    /// Read constraints (pins, keycap groups, hand balance) from this file in addition to the
+    /// ones of the layout config and the commandline
+    #[clap(long)]
+    pub constraints: Option<String>,
+}
+
+/// Collects the constraints from the layout config (keycap groups), the commandline (hand
+/// balance), and the constraints file (if any). Conflicting constraints are an error.
+pub fn init_constraints(
+    options: &ConstraintOptions,
+    layout_config: &str,
+    grouped_layout_generator: bool,
+    hand_balance: Option<HandBalanceParameters>,
+) -> Result<ConstraintSet> {
+    let mut constraints = ConstraintSet {
+        hand_balance,
+        ..Default::default()
+    };
+    // the grouped layout generator ignores the keycap groups of the layout config
+    if !grouped_layout_generator {
+        constraints.keycap_groups = LayoutConfig::from_yaml(layout_config)?
+            .base_layout
+            .keycap_groups;
+    }
+
+    if let Some(filename) = &options.constraints {
+        let from_file = ConstraintSet::from_yaml(filename)
+            .map_err(|e| anyhow!("Could not load constraints file {}: {}", filename, e))?;
+        constraints = constraints.merge(&from_file).map_err(|e| {
+            anyhow!(
+                "Constraints file {} conflicts with the layout config or the commandline: {}",
+                filename,
+                e
+            )
+        })?;
+        log::info!(
+            "Loaded {} pins, {} regions, {} keycap groups, {} symbol groups, and {} hand balance constraint from {}",
+            from_file.pins.len(),
+            from_file.regions.len(),
+            from_file.keycap_groups.len(),
+            from_file.symbol_groups().len(),
+            if from_file.hand_balance.is_some() {
+                "a"
+            } else {
+                "no"
+            },
+            filename
+        );
+    }
+
+    Ok(constraints)
+}
+
 pub fn init(options: &Options) -> (Box<dyn LayoutGenerator>, Evaluator) {
-    (
-        init_layout_generator(&options.layout_config, options.grouped_layout_generator),
-        init_evaluator(options),
-    )
+    let mut evaluator = init_evaluator(options);
+    let layout_config = LayoutConfig::from_yaml(&options.layout_config).unwrap_or_else(|e| {
+        panic!(
+            "Could not load config file {}: {}",
+            options.layout_config, e
+        )
+    });
+
+    let layout_generator = layout_generator_from_config(
+        layout_config,
+        options.grouped_layout_generator,
+        &init_finger_overrides(options),
+    );
+
+    if !options.keyboards.is_empty() {
+        evaluator = evaluator
+            .with_keyboards(
+                &keyboard_name(&options.layout_config),
+                options.layout_config_weight,
+                layout_generator.as_ref(),
+                init_keyboard_variants(options),
+            )
+            .unwrap_or_else(|e| panic!("Invalid keyboards: {}", e));
+    }
+
+    (layout_generator, evaluator)
+}
+
+/// Name of the keyboard of a layout config (the filename without extension).
+fn keyboard_name(layout_config: &str) -> String {
+    Path::new(layout_config)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| layout_config.to_string())
+}
+
+/// Builds the layout generators of the further keyboards given with `--keyboards`. The finger
+/// overrides only apply to the keyboard of `--layout-config`.
+pub fn init_keyboard_variants(options: &Options) -> Vec<KeyboardVariant> {
+    options
+        .keyboards
+        .iter()
+        .map(|keyboard| {
+            log::info!(
+                "Evaluating on keyboard {} (weight {})",
+                keyboard.path,
+                keyboard.weight
+            );
+            KeyboardVariant {
+                name: keyboard_name(&keyboard.path),
+                weight: keyboard.weight,
+                layout_generator: init_layout_generator(
+                    &keyboard.path,
+                    options.grouped_layout_generator,
+                ),
+            }
+        })
+        .collect()
 }
 
 pub fn init_layout_generator(
@@ -102,7 +347,36 @@ pub fn init_layout_generator(
     let layout_config = LayoutConfig::from_yaml(layout_config)
         .unwrap_or_else(|e| panic!("Could not load config file {}: {}", layout_config, e));
 
-    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    layout_generator_from_config(layout_config, grouped_layout_generator, &[])
+}
+
+/// Reads the finger overrides of the evaluation configuration (see
+/// [`EvaluationParameters::override_fingers`]).
+pub fn init_finger_overrides(options: &Options) -> Vec<FingerOverride> {
+    EvaluationParameters::from_yaml(&options.eval_parameters)
+        .map(|eval_params| eval_params.override_fingers)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not read evaluation yaml file {}: {:?}",
+                options.eval_parameters, e
+            )
+        })
+}
+
+/// Builds the layout generator from an already loaded layout config. The finger overrides are
+/// applied to the keyboard.
+pub fn layout_generator_from_config(
+    layout_config: LayoutConfig,
+    grouped_layout_generator: bool,
+    finger_overrides: &[FingerOverride],
+) -> Box<dyn LayoutGenerator> {
+    let keyboard = Keyboard::from_yaml_object(layout_config.keyboard)
+        .with_finger_overrides(finger_overrides)
+        .unwrap_or_else(|e| panic!("Could not apply the finger overrides: {}", e));
+    if !finger_overrides.is_empty() {
+        log::info!("Applied {} finger overrides", finger_overrides.len());
+    }
+    let keyboard = Arc::new(keyboard);
     log::info!("A-priori estimations from key_costs:");
     log::info!(
         "Finger loads (thumbs set to 0.00): {}",
@@ -121,6 +395,9 @@ pub fn init_layout_generator(
     log::info!("Row loads: {}", message);
 
     if grouped_layout_generator {
+        if !layout_config.base_layout.keycap_groups.is_empty() {
+            log::warn!("Keycap groups are ignored by the grouped layout generator");
+        }
         Box::new(GroupedLayoutGenerator::from_object(
             layout_config.base_layout,
             keyboard,
@@ -134,14 +411,45 @@ pub fn init_layout_generator(
 }
 
 pub fn init_evaluator(options: &Options) -> Evaluator {
-    let eval_params =
-        EvaluationParameters::from_yaml(&options.eval_parameters).unwrap_or_else(|e| {
-            panic!(
-                "Could not read evaluation yaml file {}: {:?}",
-                options.eval_parameters, e
-            )
-        });
+    let eval_params = EvaluationParameters::from_yaml_with_profile(
+        &options.eval_parameters,
+        options.profile.as_deref(),
+    )
+    .unwrap_or_else(|e| {
+        panic!(
+            "Could not read evaluation yaml file {}: {:?}",
+            options.eval_parameters, e
+        )
+    });
+    if let Some(profile) = &options.profile {
+        log::info!("Using weight profile '{}'", profile);
+    }
+
+    let (unigrams, bigrams, trigrams, missing_metric_types) =
+        init_ngrams(options, &eval_params.ngrams);
+
+    let mut ngram_mapper_config = eval_params.ngram_mapper.clone();
+    if options.no_split_modifiers {
+        ngram_mapper_config.split_modifiers.enabled = false;
+        ngram_mapper_config.split_modifiers.layers.clear();
+    }
+
+    build_evaluator(
+        &eval_params.metrics,
+        ngram_mapper_config,
+        (unigrams, bigrams, trigrams),
+        &missing_metric_types,
+        options.profile.clone(),
+    )
+}
 
+/// Reads the ngram data specified in the options (text, corpus file, or ngram files) and applies
+/// all preprocessing steps. Returns the ngrams along with the types of metrics that need to be
+/// skipped due to missing ngram files.
+pub fn init_ngrams(
+    options: &Options,
+    ngrams_config: &NgramsConfig,
+) -> (Unigrams, Bigrams, Trigrams, Vec<MetricType>) {
     let text = options.text.as_ref().cloned().or_else(|| {
         options.corpus.as_ref().map(|corpus_file| {
             fs::read_to_string(corpus_file)
@@ -149,44 +457,80 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
         })
     });
 
-    let mut ngram_mapper_config = eval_params.ngram_mapper.clone();
-    if options.no_split_modifiers {
-        ngram_mapper_config.split_modifiers.enabled = false;
-    }
-
-    let mut ngrams_config = eval_params.ngrams.clone();
+    let mut ngrams_config = ngrams_config.clone();
     if options.no_increase_common_ngrams {
         ngrams_config.increase_common_ngrams.enabled = false;
     }
 
-    let (mut unigrams, mut bigrams, mut trigrams) = match text {
+    let (mut unigrams, mut bigrams, mut trigrams, mut missing_metric_types) = match text {
         Some(txt) => {
+            let txt = ngrams_config.combining_marks.apply_to_text(&txt);
             let unigrams =
                 Unigrams::from_text(&txt).expect("Could not generate unigrams from text.");
             let bigrams = Bigrams::from_text(&txt).expect("Could not generate bigrams from text.");
             let trigrams =
                 Trigrams::from_text(&txt).expect("Could not generate trigrams from text.");
 
-            (unigrams, bigrams, trigrams)
+            (unigrams, bigrams, trigrams, Vec::new())
+        }
+        None if options.reweigh.is_some() => {
+            let dirs: Vec<String> = options.ngrams.iter().map(|d| d.dir.clone()).collect();
+            let tagged = TaggedNgrams::read(&dirs, !options.no_ngram_cache).unwrap_or_else(|e| {
+                panic!(
+                    "Could not read ngram files from '{}': {}",
+                    dirs.join(", "),
+                    e
+                )
+            });
+            let (unigrams, bigrams, trigrams) = tagged
+                .blend(options.reweigh.as_ref().unwrap())
+                .unwrap_or_else(|e| panic!("Could not blend ngrams: {}", e));
+
+            let (unigrams, bigrams, trigrams) = ngrams_config
+                .combining_marks
+                .apply(&unigrams, &bigrams, &trigrams);
+            (
+                unigrams,
+                bigrams,
+                trigrams,
+                tagged.missing_metric_types().to_vec(),
+            )
         }
         None => {
-            let p = Path::new(&options.ngrams).join("1-grams.txt");
-            log::info!("Reading unigram file: '{:?}'", p);
-            let unigrams = Unigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 1-gramme file from '{:?}'.", &p));
-            let p = Path::new(&options.ngrams).join("2-grams.txt");
-            log::info!("Reading bigram file: '{:?}'", p);
-            let bigrams = Bigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 2-gramme file from '{:?}'.", &p));
-            let p = Path::new(&options.ngrams).join("3-grams.txt");
-            log::info!("Reading trigram file: '{:?}'", p);
-            let trigrams = Trigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
-
-            (unigrams, bigrams, trigrams)
+            let (unigrams, bigrams, trigrams, missing_metric_types) =
+                read_weighted_ngrams_dirs(&options.ngrams, !options.no_ngram_cache).unwrap_or_else(
+                    |e| {
+                        panic!(
+                            "Could not read ngram files from '{}': {}",
+                            ngrams_dirs_str(&options.ngrams),
+                            e
+                        )
+                    },
+                );
+
+            let (unigrams, bigrams, trigrams) = ngrams_config
+                .combining_marks
+                .apply(&unigrams, &bigrams, &trigrams);
+            (unigrams, bigrams, trigrams, missing_metric_types)
         }
     };
 
+    if let Some(dir) = &options.fallback_ngrams {
+        let (fallback_unigrams, fallback_bigrams, fallback_trigrams, fallback_missing) =
+            read_ngrams_dir_with_cache(dir, !options.no_ngram_cache).unwrap_or_else(|e| {
+                panic!("Could not read fallback ngram files from '{}': {}", dir, e)
+            });
+        let (fallback_unigrams, fallback_bigrams, fallback_trigrams) = ngrams_config
+            .combining_marks
+            .apply(&fallback_unigrams, &fallback_bigrams, &fallback_trigrams);
+
+        let scale = options.fallback_scale;
+        unigrams = Unigrams::merge_with_fallback(&unigrams, &fallback_unigrams, scale);
+        bigrams = Bigrams::merge_with_fallback(&bigrams, &fallback_bigrams, scale);
+        trigrams = Trigrams::merge_with_fallback(&trigrams, &fallback_trigrams, scale);
+        missing_metric_types.retain(|metric_type| fallback_missing.contains(metric_type));
+    }
+
     if let Some(exclude_chars) = &options.exclude_chars {
         for exclude_char in exclude_chars.chars() {
             unigrams = unigrams.exclude_char(&exclude_char);
@@ -201,26 +545,63 @@ pub fn init_evaluator(options: &Options) -> Evaluator {
         trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
     }
 
+    if ngrams_config.prune.enabled() {
+        let (pruned_unigrams, pruned_1) = unigrams.prune(&ngrams_config.prune);
+        let (pruned_bigrams, pruned_2) = bigrams.prune(&ngrams_config.prune);
+        let (pruned_trigrams, pruned_3) = trigrams.prune(&ngrams_config.prune);
+        for (name, pruned) in [
+            ("unigrams", pruned_1),
+            ("bigrams", pruned_2),
+            ("trigrams", pruned_3),
+        ] {
+            log::info!(
+                "Pruned {} {} with a weight of {:.2} ({:.4}% of the total weight)",
+                pruned.count,
+                name,
+                pruned.weight,
+                100.0 * pruned.fraction(),
+            );
+        }
+        unigrams = pruned_unigrams;
+        bigrams = pruned_bigrams;
+        trigrams = pruned_trigrams;
+    }
+
     if let Some(tops) = options.tops {
         unigrams = unigrams.tops(tops);
         bigrams = bigrams.tops(tops);
         trigrams = trigrams.tops(tops);
     }
 
+    (unigrams, bigrams, trigrams, missing_metric_types)
+}
+
+/// The ngram directories (with weights) as a comma-separated string
+pub fn ngrams_dirs_str(dirs: &[WeightedNgramsDir]) -> String {
+    dirs.iter().map(|d| d.to_string()).join(", ")
+}
+
+/// Builds the evaluator with the default metrics from (preprocessed) ngram data.
+pub fn build_evaluator(
+    metrics: &MetricParameters,
+    ngram_mapper_config: NgramMapperConfig,
+    ngrams: (Unigrams, Bigrams, Trigrams),
+    missing_metric_types: &[MetricType],
+    profile: Option<String>,
+) -> Evaluator {
+    let (unigrams, bigrams, trigrams) = ngrams;
     let ngram_provider =
         OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
 
-    Evaluator::default(Box::new(ngram_provider)).default_metrics(&eval_params.metrics)
+    Evaluator::default(Box::new(ngram_provider))
+        .default_metrics(metrics)
+        .skip_metric_types(missing_metric_types)
+        .with_profile(profile)
 }
 
 /// Appends a layout-string to a file.
 pub fn append_to_file(layout_str: &str, filename: &str) {
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(filename)
-        .unwrap();
-    if let Err(e) = writeln!(file, "{}", layout_str) {
+    if let Err(e) = atomic_file::append_durable(filename, format!("{}\n", layout_str)) {
         log::error!("Couldn't write to file: {}", e);
     } else {
         log::info!("Appended layout '{}' to '{}'", layout_str, filename);
@@ -251,3 +632,87 @@ pub fn publish_to_webservice(
         log::error!("Could not publish result to webservice");
     }
 }
+
+#[derive(Deserialize)]
+struct PublishedLayout {
+    layout: String,
+}
+
+/// Fetches the layout strings published to a webservice for the given layout config.
+pub fn fetch_published_layouts(url: &str, layout_config: &str) -> Result<Vec<String>> {
+    let client = reqwest::blocking::Client::new();
+    let published: Vec<PublishedLayout> = client
+        .get(url)
+        .query(&[("layout_config", layout_config)])
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    Ok(published.into_iter().map(|p| p.layout).collect())
+}
+
+/// Builds the blacklist from a file and/or a webservice, if any of them is given.
+pub fn init_blacklist(
+    options: &BlacklistOptions,
+    layout_generator: &dyn LayoutGenerator,
+) -> Option<Arc<Blacklist>> {
+    if options.blacklist.is_none() && options.blacklist_url.is_none() {
+        return None;
+    }
+
+    let mut blacklist = Blacklist::new(options.blacklist_threshold);
+
+    if let Some(filename) = &options.blacklist {
+        let content = fs::read_to_string(filename)
+            .unwrap_or_else(|e| panic!("Could not read blacklist file {}: {}", filename, e));
+        content
+            .lines()
+            .map(|l| l.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+            .filter(|l| !l.is_empty())
+            .for_each(|layout_str| {
+                let layout = layout_generator.generate(&layout_str).unwrap_or_else(|e| {
+                    panic!("Invalid layout in blacklist file {}: {:?}", filename, e)
+                });
+                blacklist.add(layout);
+            });
+    }
+
+    if let Some(url) = &options.blacklist_url {
+        let layouts = fetch_published_layouts(url, &options.blacklist_layout_config)
+            .unwrap_or_else(|e| panic!("Could not fetch blacklisted layouts from {}: {}", url, e));
+        layouts
+            .iter()
+            .for_each(|layout_str| match layout_generator.generate(layout_str) {
+                Ok(layout) => blacklist.add(layout),
+                Err(e) => log::warn!("Skipping published layout '{}': {:?}", layout_str, e),
+            });
+    }
+
+    log::info!(
+        "Blacklisted {} layouts (similarity threshold: {})",
+        blacklist.len(),
+        blacklist.threshold()
+    );
+
+    Some(Arc::new(blacklist))
+}
+
+/// Formats the maximum similarity of a layout to the blacklisted layouts.
+pub fn format_blacklist_similarity(blacklist: &Blacklist, layout: &Layout) -> String {
+    match blacklist.max_similarity(layout) {
+        Some((similarity, closest)) => {
+            let msg = format!(
+                "Maximum similarity to blacklist: {:.3} (threshold: {}, closest: {})",
+                similarity,
+                blacklist.threshold(),
+                closest,
+            );
+            if similarity > blacklist.threshold() {
+                format!("{} {}", msg, "FORBIDDEN".red().bold())
+            } else {
+                msg.green().to_string()
+            }
+        }
+        None => "Blacklist is empty".to_string(),
+    }
+}
@@ -1 +1,3 @@
+pub mod bundle;
 pub mod common;
+pub mod tui;
@@ -0,0 +1,607 @@
+//! The `tui` module provides an interactive terminal viewer for the evaluation of a single
+//! layout (`evaluate --tui`). It consists of three panes:
+//! - the base layer of the layout with a movable cursor and the costs attributed to the key
+//!   under the cursor,
+//! - the list of metrics (optionally sorted by their contribution to the total cost),
+//! - the details of the selected metric (e.g. its worst ngrams).
+//!
+//! The viewer is built on the structured results only ([`EvaluationResult`] for the metrics and
+//! [`KeySummary`] for the keys). The terminal is drawn with `ratatui` on its `crossterm` backend.
+
+use keyboard_layout::{key::MatrixPosition, layout::Layout};
+use layout_evaluation::{
+    key_summary::{KeyCost, KeySummary},
+    results::{EvaluationResult, MetricType},
+};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use ratatui::{
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        tty::IsTty,
+    },
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+};
+use std::io;
+
+/// Width of a key in the layout pane
+const KEY_WIDTH: usize = 4;
+/// Number of metric contributions listed for the key under the cursor
+const N_KEY_METRICS: usize = 5;
+
+/// A key press relevant to the viewer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Tab,
+    Enter,
+    Escape,
+    /// Ctrl+C
+    Interrupt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pane {
+    Layout,
+    Metrics,
+}
+
+/// Order of the metric list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricOrder {
+    /// As in the evaluation results
+    Evaluation,
+    /// By weighted cost (descending)
+    Contribution,
+}
+
+#[derive(Clone, Debug)]
+struct MetricEntry {
+    name: String,
+    metric_type: MetricType,
+    weighted_cost: f64,
+    message: Option<String>,
+}
+
+/// State of the viewer (independent of the terminal).
+#[derive(Clone, Debug)]
+pub struct Viewer {
+    layout: String,
+    total_cost: f64,
+    /// Base layer symbols by row and column (`None` for positions without a key)
+    grid: Vec<Vec<Option<char>>>,
+    /// Positions of the keys generating each symbol (of any layer)
+    symbol_positions: AHashMap<char, MatrixPosition>,
+    key_costs: AHashMap<MatrixPosition, KeyCost>,
+    /// Row and column of the cursor
+    cursor: (usize, usize),
+    metrics: Vec<MetricEntry>,
+    order: MetricOrder,
+    /// Index of the selected metric in the current order
+    selected: usize,
+    focus: Pane,
+    /// Whether the next character is the symbol to search for
+    searching: bool,
+    status: String,
+}
+
+impl Viewer {
+    pub fn new(layout: &Layout, result: &EvaluationResult, summary: KeySummary) -> Self {
+        let base_keys: Vec<(MatrixPosition, char)> = layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.layer == 0)
+            .map(|k| (k.key.matrix_position, k.symbol))
+            .collect();
+        let n_rows = base_keys.iter().map(|(p, _)| p.1 as usize + 1).max();
+        let n_cols = base_keys.iter().map(|(p, _)| p.0 as usize + 1).max();
+        let mut grid = vec![vec![None; n_cols.unwrap_or(0)]; n_rows.unwrap_or(0)];
+        base_keys
+            .iter()
+            .for_each(|(p, c)| grid[p.1 as usize][p.0 as usize] = Some(*c));
+
+        let mut symbol_positions = AHashMap::default();
+        layout.layerkeys.iter().for_each(|k| {
+            symbol_positions
+                .entry(k.symbol)
+                .or_insert(k.key.matrix_position);
+        });
+
+        let metrics = result
+            .iter()
+            .flat_map(|mr| {
+                mr.metric_costs.iter().map(move |mc| MetricEntry {
+                    name: mc.core.name.clone(),
+                    metric_type: mr.metric_type.clone(),
+                    weighted_cost: mc.weighted_cost,
+                    message: mc.core.message.clone(),
+                })
+            })
+            .collect();
+
+        // start on the most costly key
+        let cursor = summary
+            .keys
+            .first()
+            .map(|k| (k.position.1 as usize, k.position.0 as usize))
+            .or_else(|| base_keys.first().map(|(p, _)| (p.1 as usize, p.0 as usize)))
+            .unwrap_or((0, 0));
+
+        Self {
+            layout: result.layout().to_string(),
+            total_cost: result.total_cost(),
+            grid,
+            symbol_positions,
+            key_costs: summary.keys.into_iter().map(|k| (k.position, k)).collect(),
+            cursor,
+            metrics,
+            order: MetricOrder::Evaluation,
+            selected: 0,
+            focus: Pane::Layout,
+            searching: false,
+            status: String::new(),
+        }
+    }
+
+    /// The symbol of the base layer under the cursor.
+    pub fn cursor_symbol(&self) -> Option<char> {
+        self.grid
+            .get(self.cursor.0)
+            .and_then(|row| row.get(self.cursor.1))
+            .cloned()
+            .flatten()
+    }
+
+    /// Name of the selected metric.
+    pub fn selected_metric(&self) -> Option<&str> {
+        self.ordered_metrics()
+            .get(self.selected)
+            .map(|m| m.name.as_str())
+    }
+
+    /// The metrics in the current order.
+    fn ordered_metrics(&self) -> Vec<&MetricEntry> {
+        let mut metrics: Vec<&MetricEntry> = self.metrics.iter().collect();
+        if self.order == MetricOrder::Contribution {
+            metrics.sort_by(|m1, m2| m2.weighted_cost.partial_cmp(&m1.weighted_cost).unwrap());
+        }
+        metrics
+    }
+
+    /// Process a key press. Returns `false` if the viewer shall be closed.
+    pub fn handle(&mut self, input: Input) -> bool {
+        if self.searching {
+            self.searching = false;
+            if let Input::Char(c) = input {
+                self.search(c);
+            } else {
+                self.status.clear();
+            }
+            return true;
+        }
+
+        self.status.clear();
+        match (input, self.focus) {
+            (Input::Char('q'), _) | (Input::Interrupt, _) => return false,
+            (Input::Char('/'), _) => {
+                self.searching = true;
+                self.status = "Search for symbol: ".to_string();
+            }
+            (Input::Char('s'), _) => self.toggle_order(),
+            (Input::Tab, Pane::Layout) => self.focus = Pane::Metrics,
+            (Input::Tab, Pane::Metrics) => self.focus = Pane::Layout,
+            (Input::Up, Pane::Layout) | (Input::Char('k'), Pane::Layout) => self.move_row(-1),
+            (Input::Down, Pane::Layout) | (Input::Char('j'), Pane::Layout) => self.move_row(1),
+            (Input::Left, Pane::Layout) | (Input::Char('h'), Pane::Layout) => self.move_col(-1),
+            (Input::Right, Pane::Layout) | (Input::Char('l'), Pane::Layout) => self.move_col(1),
+            (Input::Up, Pane::Metrics) | (Input::Char('k'), Pane::Metrics) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            (Input::Down, Pane::Metrics) | (Input::Char('j'), Pane::Metrics) => {
+                self.selected = (self.selected + 1).min(self.metrics.len().saturating_sub(1));
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    /// Sort the metric list by contribution (or back), keeping the selected metric.
+    fn toggle_order(&mut self) {
+        let selected = self.selected_metric().map(|s| s.to_string());
+        self.order = match self.order {
+            MetricOrder::Evaluation => MetricOrder::Contribution,
+            MetricOrder::Contribution => MetricOrder::Evaluation,
+        };
+        self.selected = self
+            .ordered_metrics()
+            .iter()
+            .position(|m| Some(&m.name) == selected.as_ref())
+            .unwrap_or(0);
+    }
+
+    /// Move the cursor to the key generating the symbol.
+    fn search(&mut self, c: char) {
+        match self.symbol_positions.get(&c) {
+            Some(p) => {
+                self.cursor = (p.1 as usize, p.0 as usize);
+                self.focus = Pane::Layout;
+            }
+            None => self.status = format!("Symbol '{}' not found", c.escape_debug()),
+        }
+    }
+
+    /// Move the cursor to the next key in the row in the given direction.
+    fn move_col(&mut self, step: isize) {
+        let (row, mut col) = self.cursor;
+        while let Some(next) = offset(col, step, self.grid[row].len()) {
+            col = next;
+            if self.grid[row][col].is_some() {
+                self.cursor = (row, col);
+                return;
+            }
+        }
+    }
+
+    /// Move the cursor to the closest key of the next row (containing keys) in the given
+    /// direction.
+    fn move_row(&mut self, step: isize) {
+        let (mut row, col) = self.cursor;
+        while let Some(next) = offset(row, step, self.grid.len()) {
+            row = next;
+            let closest = self.grid[row]
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.is_some())
+                .min_by_key(|(i, _)| (*i as isize - col as isize).abs());
+            if let Some((i, _)) = closest {
+                self.cursor = (row, i);
+                return;
+            }
+        }
+    }
+
+    /// Render the panes into lines of at most `width` characters.
+    pub fn render(&self, width: usize, height: usize) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::raw(truncate(
+            &format!(
+                "{} | total cost {:.2} | [Tab] pane [s] sort [/] search [q] quit",
+                self.layout, self.total_cost
+            ),
+            width,
+        ))];
+
+        // layout pane
+        lines.push(header("Layout", self.focus == Pane::Layout, width));
+        let max_keys = width / KEY_WIDTH;
+        for (i, row) in self.grid.iter().enumerate() {
+            let mut cells: Vec<Span> = row
+                .iter()
+                .enumerate()
+                .take(max_keys)
+                .map(|(j, c)| {
+                    let cell = format!(
+                        "{:^w$}",
+                        c.map(display_symbol).unwrap_or_default(),
+                        w = KEY_WIDTH
+                    );
+                    if (i, j) == self.cursor {
+                        Span::styled(cell, Style::new().add_modifier(Modifier::REVERSED))
+                    } else {
+                        Span::raw(cell)
+                    }
+                })
+                .collect();
+            // trailing blanks of the row
+            while cells.last().map_or(false, |c| {
+                c.style == Style::new() && c.content.trim().is_empty()
+            }) {
+                cells.pop();
+            }
+            lines.push(Line::from(cells));
+        }
+        self.key_lines()
+            .iter()
+            .for_each(|l| lines.push(Line::raw(truncate(l, width))));
+
+        // metric list and details share the remaining lines
+        let remaining = height.saturating_sub(lines.len() + 3);
+        let n_listed = (remaining / 2).max(1);
+        let metrics = self.ordered_metrics();
+        lines.push(header(
+            match self.order {
+                MetricOrder::Evaluation => "Metrics",
+                MetricOrder::Contribution => "Metrics (by contribution)",
+            },
+            self.focus == Pane::Metrics,
+            width,
+        ));
+        let first = (self.selected + 1).saturating_sub(n_listed);
+        for (i, m) in metrics.iter().enumerate().skip(first).take(n_listed) {
+            let line = truncate(
+                &format!(
+                    "{:>8.2} {:>5.1}% {:<35} {:?}",
+                    m.weighted_cost,
+                    percentage(m.weighted_cost, self.total_cost),
+                    m.name,
+                    m.metric_type
+                ),
+                width,
+            );
+            if i == self.selected {
+                lines.push(Line::styled(
+                    line,
+                    Style::new().add_modifier(Modifier::REVERSED),
+                ));
+            } else {
+                lines.push(Line::raw(line));
+            }
+        }
+
+        // details of the selected metric
+        if let Some(m) = metrics.get(self.selected) {
+            lines.push(header(
+                &format!("{} ({:?})", m.name, m.metric_type),
+                false,
+                width,
+            ));
+            let details = m.message.as_deref().map(detail_lines).unwrap_or_default();
+            details
+                .iter()
+                .take(height.saturating_sub(lines.len() + 1))
+                .for_each(|l| lines.push(Line::raw(truncate(l, width))));
+        }
+
+        lines.truncate(height.saturating_sub(1));
+        lines.push(Line::raw(truncate(&self.status, width)));
+
+        lines
+    }
+
+    /// Lines describing the costs of the key under the cursor.
+    fn key_lines(&self) -> Vec<String> {
+        let symbol = match self.cursor_symbol() {
+            Some(c) => display_symbol(c),
+            None => return Vec::new(),
+        };
+        let position = MatrixPosition(self.cursor.1 as u8, self.cursor.0 as u8);
+
+        match self.key_costs.get(&position) {
+            Some(key_cost) => {
+                let mut lines = vec![format!(
+                    "Key '{}' [{}, {}]: cost {:.2} ({:.1}% of total)",
+                    symbol,
+                    position.0,
+                    position.1,
+                    key_cost.cost,
+                    percentage(key_cost.cost, self.total_cost)
+                )];
+                lines.extend(
+                    key_cost
+                        .metrics
+                        .iter()
+                        .take(N_KEY_METRICS)
+                        .map(|m| format!("  {:>8.2} {}", m.cost, m.name)),
+                );
+                lines
+            }
+            None => vec![format!(
+                "Key '{}' [{}, {}]: no cost",
+                symbol, position.0, position.1
+            )],
+        }
+    }
+}
+
+/// The index `i` moved by `step`, if it is within `0..len`.
+fn offset(i: usize, step: isize, len: usize) -> Option<usize> {
+    let next = i as isize + step;
+    (next >= 0 && (next as usize) < len).then(|| next as usize)
+}
+
+fn percentage(cost: f64, total_cost: f64) -> f64 {
+    if total_cost != 0.0 {
+        100.0 * cost / total_cost
+    } else {
+        0.0
+    }
+}
+
+/// A printable representation of a symbol.
+fn display_symbol(c: char) -> String {
+    match c {
+        ' ' => "␣".to_string(),
+        '\n' => "⏎".to_string(),
+        '\t' => "⇥".to_string(),
+        c if c.is_control() => c.escape_debug().to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn header(title: &str, focused: bool, width: usize) -> Line<'static> {
+    let title = truncate(&format!("── {} ", title), width);
+    let line = format!(
+        "{}{}",
+        title,
+        "─".repeat(width.saturating_sub(title.chars().count()))
+    );
+    if focused {
+        Line::styled(line, Style::new().add_modifier(Modifier::BOLD))
+    } else {
+        Line::raw(line)
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+/// Split a metric's message into lines, listing each item of enumerations (e.g. the worst
+/// ngrams) on a line of its own.
+fn detail_lines(message: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for part in message.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once(": ") {
+            Some((label, items)) if items.contains(", ") => {
+                lines.push(format!("{}:", label));
+                lines.extend(items.split(", ").map(|item| format!("  {}", item)));
+            }
+            _ => lines.push(part.to_string()),
+        }
+    }
+    lines
+}
+
+/// Whether both stdin and stdout are terminals (otherwise the viewer can not be used).
+pub fn is_terminal() -> bool {
+    io::stdin().is_tty() && io::stdout().is_tty()
+}
+
+/// Show the viewer until it is closed.
+pub fn run(viewer: &mut Viewer) -> Result<()> {
+    // raw mode and alternate screen, restored on panics as well
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<()> {
+        loop {
+            terminal.draw(|frame| {
+                let area = frame.area();
+                let lines = viewer.render(area.width as usize, area.height as usize);
+                frame.render_widget(Paragraph::new(lines), area);
+            })?;
+
+            if let Event::Key(key) = event::read()? {
+                match input(key) {
+                    Some(input) if !viewer.handle(input) => return Ok(()),
+                    _ => {}
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+
+    result
+}
+
+/// The viewer's input for a key event (`None` for releases and keys without a function).
+fn input(key: KeyEvent) -> Option<Input> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Input::Interrupt)
+        }
+        KeyCode::Char(c) => Some(Input::Char(c)),
+        KeyCode::Up => Some(Input::Up),
+        KeyCode::Down => Some(Input::Down),
+        KeyCode::Left => Some(Input::Left),
+        KeyCode::Right => Some(Input::Right),
+        KeyCode::Tab => Some(Input::Tab),
+        KeyCode::Enter => Some(Input::Enter),
+        KeyCode::Esc => Some(Input::Escape),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::common;
+
+    use clap::Parser;
+
+    fn viewer() -> Viewer {
+        let options = common::Options::parse_from([
+            "evaluate",
+            "--text",
+            "Der schnelle braune Fuchs springt über den faulen Hund.",
+            "--layout-config",
+            "../config/keyboard/standard.yml",
+            "--eval-parameters",
+            "../config/evaluation/default.yml",
+        ]);
+        let (layout_generator, evaluator) = common::init(&options);
+        let layout = layout_generator
+            .generate("xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+            .unwrap();
+        let result = evaluator.evaluate_layout(&layout);
+
+        Viewer::new(&layout, &result, evaluator.key_summary(&layout))
+    }
+
+    #[test]
+    fn navigate_and_search() {
+        let mut viewer = viewer();
+
+        // search for a symbol of a higher layer
+        assert!(viewer.handle(Input::Char('/')));
+        assert!(viewer.handle(Input::Char('E')));
+        assert_eq!(viewer.cursor_symbol(), Some('e'));
+        viewer.handle(Input::Char('l'));
+        assert_eq!(viewer.cursor_symbol(), Some('o'));
+        viewer.handle(Input::Up);
+        assert_eq!(viewer.cursor_symbol(), Some('w'));
+
+        viewer.handle(Input::Char('/'));
+        viewer.handle(Input::Char('☃'));
+        assert_eq!(viewer.cursor_symbol(), Some('w'));
+        assert!(viewer
+            .render(80, 24)
+            .last()
+            .unwrap()
+            .to_string()
+            .contains("not found"));
+
+        // sorting by contribution keeps the selection, the most costly metric comes first
+        viewer.handle(Input::Tab);
+        viewer.handle(Input::Down);
+        let selected = viewer.selected_metric().unwrap().to_string();
+        viewer.handle(Input::Char('s'));
+        assert_eq!(viewer.selected_metric(), Some(selected.as_str()));
+        let costs: Vec<f64> = viewer
+            .ordered_metrics()
+            .iter()
+            .map(|m| m.weighted_cost)
+            .collect();
+        assert!(costs.windows(2).all(|w| w[0] >= w[1]));
+
+        assert!(!viewer.handle(Input::Char('q')));
+    }
+
+    #[test]
+    fn render_fits_terminal() {
+        let mut viewer = viewer();
+        viewer.handle(Input::Tab);
+        viewer.handle(Input::Char('s'));
+
+        for (width, height) in [(80, 24), (40, 10), (200, 60), (1, 1)] {
+            let lines = viewer.render(width, height);
+            assert!(lines.len() <= height);
+            for line in lines.iter() {
+                assert!(line.width() <= width, "{:?}", line.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn worst_ngrams_are_listed() {
+        assert_eq!(
+            detail_lines("Worst: ab (1.00%), cd (0.50%);  Worst non-fixed: ab (1.00%)"),
+            vec![
+                "Worst:",
+                "  ab (1.00%)",
+                "  cd (0.50%)",
+                "Worst non-fixed: ab (1.00%)"
+            ]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use keyboard_layout::{
-    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
     neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
@@ -24,7 +24,7 @@ pub struct NGramConfig {
     pub trigrams: String,
 }
 
-pub fn evaluate_bench(c: &mut Criterion) {
+fn evaluator(max_generated_per_trigram: Option<usize>) -> (Evaluator, Layout) {
     let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG)
         .unwrap_or_else(|e| panic!("Could not load config file '/keyboard/standard.yml': {}", e));
 
@@ -32,9 +32,14 @@ pub fn evaluate_bench(c: &mut Criterion) {
 
     let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
 
-    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap_or_else(|_| {
-        panic!("Could not read evaluation yaml file '/evaluation/default.yml'")
-    });
+    let mut eval_params =
+        EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap_or_else(|_| {
+            panic!("Could not read evaluation yaml file '/evaluation/default.yml'")
+        });
+    eval_params
+        .ngram_mapper
+        .split_modifiers
+        .max_generated_per_trigram = max_generated_per_trigram;
 
     let p = Path::new(NGRAMS).join("1-grams.txt");
     log::info!("Reading unigram file: '{:?}'", p);
@@ -54,6 +59,10 @@ pub fn evaluate_bench(c: &mut Criterion) {
     let ngram_mapper_config = eval_params.ngram_mapper.clone();
     let ngrams_config = eval_params.ngrams.clone();
 
+    (unigrams, bigrams, trigrams) = ngrams_config
+        .combining_marks
+        .apply(&unigrams, &bigrams, &trigrams);
+
     if ngrams_config.increase_common_ngrams.enabled {
         unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
         bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
@@ -73,10 +82,24 @@ pub fn evaluate_bench(c: &mut Criterion) {
             panic!("{:?}", e);
         }
     };
+
+    (evaluator, layout)
+}
+
+pub fn evaluate_bench(c: &mut Criterion) {
+    let (evaluator, layout) = evaluator(None);
     c.bench_function("evaluate", |b| {
         b.iter(|| evaluator.evaluate_layout(&layout));
     });
 }
 
-criterion_group!(benches, evaluate_bench);
+/// Evaluation with a limited number of trigrams generated from those with higher-layer symbols
+pub fn evaluate_capped_bench(c: &mut Criterion) {
+    let (evaluator, layout) = evaluator(Some(8));
+    c.bench_function("evaluate_capped", |b| {
+        b.iter(|| evaluator.evaluate_layout(&layout));
+    });
+}
+
+criterion_group!(benches, evaluate_bench, evaluate_capped_bench);
 criterion_main!(benches);
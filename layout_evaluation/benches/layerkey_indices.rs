@@ -0,0 +1,80 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters, ngram_mapper::trigram_mapper::OnDemandTrigramMapper,
+    ngrams::Trigrams,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::{path::Path, sync::Arc};
+
+const NGRAMS: &str = "../ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4";
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+
+/// Mapping of the corpus' trigrams with and without splitting off modifiers
+pub fn layerkey_indices_bench(c: &mut Criterion) {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG)
+        .unwrap_or_else(|e| panic!("Could not load config file '/keyboard/standard.yml': {}", e));
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let layout = layout_generator
+        .generate("jduaxphlmwqßctieobnrsgfvüäöyz,.k")
+        .unwrap();
+
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap_or_else(|_| {
+        panic!("Could not read evaluation yaml file '/evaluation/default.yml'")
+    });
+
+    let p = Path::new(NGRAMS).join("3-grams.txt");
+    let trigrams = Trigrams::from_file(p.to_str().unwrap())
+        .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", p));
+
+    let config = eval_params.ngram_mapper;
+    for split_modifiers in [true, false] {
+        let mut split_config = config.split_modifiers.clone();
+        split_config.enabled = split_modifiers;
+        let mapper = OnDemandTrigramMapper::new(
+            split_config,
+            config.shift_policy,
+            config.increase_common_trigrams.clone(),
+            config.deterministic_order,
+        );
+
+        let name = if split_modifiers {
+            "layerkey_indices"
+        } else {
+            "layerkey_indices_no_split"
+        };
+        c.bench_function(name, |b| {
+            b.iter(|| mapper.layerkey_indices(&trigrams, &layout, config.exclude_line_breaks));
+        });
+    }
+
+    // heavy usage of the shift layer
+    let mut uppercase_trigrams = trigrams.clone();
+    uppercase_trigrams.grams.clear();
+    trigrams.grams.iter().for_each(|((c1, c2, c3), w)| {
+        let upper = |c: &char| c.to_uppercase().next().unwrap_or(*c);
+        *uppercase_trigrams
+            .grams
+            .entry((upper(c1), upper(c2), upper(c3)))
+            .or_insert(0.0) += w;
+    });
+    let mapper = OnDemandTrigramMapper::new(
+        config.split_modifiers.clone(),
+        config.shift_policy,
+        config.increase_common_trigrams.clone(),
+        config.deterministic_order,
+    );
+    c.bench_function("layerkey_indices_uppercase", |b| {
+        b.iter(|| {
+            mapper.layerkey_indices(&uppercase_trigrams, &layout, config.exclude_line_breaks)
+        });
+    });
+}
+
+criterion_group!(benches, layerkey_indices_bench);
+criterion_main!(benches);
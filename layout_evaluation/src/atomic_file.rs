@@ -0,0 +1,207 @@
+//! The `atomic_file` module provides helpers for writing files atomically and durably.
+//!
+//! Data is written to a temporary file in the target's directory. Only once all data has been
+//! written and synced to disk, the temporary file is renamed over the target. An interrupted
+//! write therefore never leaves a truncated target file behind; at worst an orphaned temporary
+//! file remains, which can be removed with [`clean_orphaned_temp_files`].
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Marker contained in the names of all temporary files.
+const TEMP_MARKER: &str = ".atomic-tmp.";
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn temp_path(target: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    name.push(format!(
+        "{}{}-{}",
+        TEMP_MARKER,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    target.with_file_name(name)
+}
+
+/// A writer that replaces its target file atomically once [`AtomicFile::commit`] is called.
+///
+/// If the writer is dropped without committing (e.g. due to an error or a panic), the
+/// temporary file is removed and the target file stays untouched.
+pub struct AtomicFile {
+    target: PathBuf,
+    temp: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl AtomicFile {
+    /// Start writing a replacement for the file at `target`.
+    pub fn create<T: AsRef<Path>>(target: T) -> io::Result<Self> {
+        let target = target.as_ref().to_path_buf();
+        let temp = temp_path(&target);
+        let file = File::create(&temp)?;
+
+        Ok(Self {
+            target,
+            temp,
+            writer: Some(BufWriter::new(file)),
+        })
+    }
+
+    /// Sync all written data to disk and move it into place.
+    pub fn commit(mut self) -> io::Result<()> {
+        let writer = self.writer.take().unwrap();
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&self.temp, &self.target)?;
+        sync_parent_dir(&self.target);
+
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Make the rename durable by syncing the directory (not supported on all platforms).
+fn sync_parent_dir(path: &Path) {
+    if let Some(dir) = path.parent() {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        if let Ok(d) = File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+}
+
+/// Atomically replace the file at `target` with `data`.
+pub fn write_atomic<T: AsRef<Path>, D: AsRef<[u8]>>(target: T, data: D) -> io::Result<()> {
+    let mut f = AtomicFile::create(target)?;
+    f.write_all(data.as_ref())?;
+    f.commit()
+}
+
+/// Durably append `data` to the file at `target` (creating it if necessary).
+///
+/// The data is written with a single call in append mode, so concurrent appends of
+/// whole lines do not interleave.
+pub fn append_durable<T: AsRef<Path>, D: AsRef<[u8]>>(target: T, data: D) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(target)?;
+    file.write_all(data.as_ref())?;
+    file.sync_data()
+}
+
+/// Remove temporary files left behind by interrupted writes in `dir`.
+///
+/// Returns the number of removed files.
+pub fn clean_orphaned_temp_files<T: AsRef<Path>>(dir: T) -> io::Result<usize> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+
+    let mut n_removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_temp = name
+            .to_str()
+            .map(|n| n.starts_with('.') && n.contains(TEMP_MARKER))
+            .unwrap_or(false);
+        if is_temp && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+            log::warn!("Removed orphaned temporary file {:?}", entry.path());
+            n_removed += 1;
+        }
+    }
+
+    Ok(n_removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("atomic_file_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn commit_replaces_target() {
+        let dir = test_dir("commit");
+        let target = dir.join("out.txt");
+        fs::write(&target, "old").unwrap();
+
+        write_atomic(&target, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_write_keeps_old_file() {
+        let dir = test_dir("interrupt");
+        let target = dir.join("out.txt");
+        fs::write(&target, "old content").unwrap();
+
+        {
+            let mut f = AtomicFile::create(&target).unwrap();
+            f.write_all(b"partial").unwrap();
+            // dropped without commit
+        }
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old content");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn orphaned_temp_files_are_removed() {
+        let dir = test_dir("orphans");
+        let target = dir.join("out.txt");
+        fs::write(&target, "old").unwrap();
+
+        // simulate a crash: the temporary file survives
+        let f = AtomicFile::create(&target).unwrap();
+        std::mem::forget(f);
+
+        assert_eq!(clean_orphaned_temp_files(&dir).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use crate::memory::hashmap_bytes;
+
 use ahash::AHashMap;
 use colored::Colorize;
 use parking_lot::Mutex;
@@ -36,6 +38,23 @@ impl<T: Clone> Cache<T> {
             res
         })
     }
+
+    /// Number of cached results
+    pub fn len(&self) -> usize {
+        self.cache.lock().len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate number of bytes allocated for the cache (including the keys, but excluding
+    /// heap data held by the cached values)
+    pub fn estimated_bytes(&self) -> usize {
+        let cache = self.cache.lock();
+        hashmap_bytes(&cache) + cache.keys().map(|k| k.capacity()).sum::<usize>()
+    }
 }
 
 impl<T: Clone + fmt::Display + PartialOrd> Cache<T> {
@@ -3,15 +3,33 @@ use crate::{
     ngrams::NgramsConfig,
 };
 
-use anyhow::Result;
+use ahash::AHashMap;
+use anyhow::{anyhow, Result};
+use keyboard_layout::keyboard::FingerOverride;
 use serde::Deserialize;
-use std::{fs::File, str::FromStr};
+use serde_yaml::Value;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    str::FromStr,
+};
+
+/// A named set of metric weights overriding the default weights (keys are the metrics'
+/// names in the `metrics` section of the configuration).
+pub type WeightProfile = AHashMap<String, f64>;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct EvaluationParameters {
     pub metrics: MetricParameters,
     pub ngrams: NgramsConfig,
     pub ngram_mapper: NgramMapperConfig,
+    /// Named weight profiles that can be selected instead of the default weights.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, WeightProfile>,
+    /// Hands and fingers of keys differing from the keyboard configuration (e.g. for an angle
+    /// mod). They are applied when constructing the keyboard.
+    #[serde(default)]
+    pub override_fingers: Vec<FingerOverride>,
 }
 
 impl EvaluationParameters {
@@ -21,6 +39,52 @@ impl EvaluationParameters {
 
         Ok(k)
     }
+
+    /// Read the parameters from a YAML file and apply the weights of the given profile.
+    pub fn from_yaml_with_profile(filename: &str, profile: Option<&str>) -> Result<Self> {
+        let data = fs::read_to_string(filename)?;
+
+        Self::from_str_with_profile(&data, profile)
+    }
+
+    /// Read the parameters from a YAML string and apply the weights of the given profile.
+    ///
+    /// The profile's weights are applied on the YAML level, such that metrics that are composed
+    /// of other metrics (e.g. "Irregularity") see the overridden weights as well.
+    pub fn from_str_with_profile(data: &str, profile: Option<&str>) -> Result<Self> {
+        let profile = match profile {
+            Some(profile) => profile,
+            None => return Ok(serde_yaml::from_str(data)?),
+        };
+
+        let mut value: Value = serde_yaml::from_str(data)?;
+        let weights: WeightProfile = match value.get("profiles").and_then(|p| p.get(profile)) {
+            Some(w) => serde_yaml::from_value(w.clone())?,
+            None => return Err(anyhow!("Unknown weight profile '{}'", profile)),
+        };
+
+        let metrics = value
+            .get_mut("metrics")
+            .ok_or_else(|| anyhow!("Missing 'metrics' section"))?;
+        for (metric, weight) in weights {
+            match metrics.get_mut(metric.as_str()) {
+                Some(params) if params.is_mapping() => {
+                    params["weight"] = Value::from(weight);
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "Weight profile '{}' refers to unknown metric '{}'",
+                        profile,
+                        metric
+                    ))
+                }
+            }
+        }
+
+        // deserializing from the string representation supports `null` keys in the metrics'
+        // parameters (unlike deserializing from the `Value` directly)
+        Ok(serde_yaml::from_str(&serde_yaml::to_string(&value)?)?)
+    }
 }
 
 impl FromStr for EvaluationParameters {
@@ -31,3 +95,38 @@ impl FromStr for EvaluationParameters {
         Ok(cfg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = "profiles:
+  heavy_keys:
+    key_costs: 123.0
+  broken:
+    no_such_metric: 1.0
+";
+
+    fn config() -> String {
+        fs::read_to_string("../config/evaluation/default.yml")
+            .unwrap()
+            .replace("profiles: {}", PROFILES)
+    }
+
+    #[test]
+    fn profile_overrides_weights() {
+        let params = EvaluationParameters::from_str_with_profile(&config(), None).unwrap();
+        assert_eq!(params.profiles.len(), 2);
+        assert_ne!(params.metrics.key_costs.unwrap().weight, 123.0);
+
+        let params =
+            EvaluationParameters::from_str_with_profile(&config(), Some("heavy_keys")).unwrap();
+        assert_eq!(params.metrics.key_costs.unwrap().weight, 123.0);
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        assert!(EvaluationParameters::from_str_with_profile(&config(), Some("unknown")).is_err());
+        assert!(EvaluationParameters::from_str_with_profile(&config(), Some("broken")).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! The `corpus` module provides functionality for reading text corpora that consist of many
+//! files (e.g. a directory tree of text files) from which ngrams are collected.
+//!
+//! The files are read one after another and each of them is streamed chunk-wise, so that
+//! neither the whole corpus nor a whole file ever needs to be held in memory at once.
+
+use anyhow::{bail, Result};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// Chunks of text without whitespace are passed on once they exceed this size (in bytes).
+const MAX_CHUNK_SIZE: usize = 1 << 20;
+
+/// Whether a file name matches a glob pattern. The pattern may contain `*` (any sequence of
+/// characters) and `?` (any single character).
+pub fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    // position in the pattern after the last `*` and the position in the name it was matched to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut n, mut p) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            n += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // let the last `*` consume one more character
+            star = Some((star_p, star_n + 1));
+            p = star_p;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Parse a comma-separated list of glob patterns (e.g. `*.txt,*.md`).
+pub fn parse_patterns(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string())
+        .collect()
+}
+
+/// Collect all files below `dir` (recursively) whose names match one of the patterns.
+fn collect_dir(dir: &Path, patterns: &[String], files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    // a fixed order makes the results independent of the file system
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        // symlinked directories are not followed to avoid cycles
+        if entry.file_type()?.is_dir() {
+            collect_dir(&path, patterns, files)?;
+        } else if path.is_file() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if patterns.is_empty() || patterns.iter().any(|p| matches_pattern(&name, p)) {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand a list of files and directories to the list of files they contain.
+///
+/// Directories are walked recursively and only files whose names match one of the patterns are
+/// included (all files if there are no patterns). Files that are given explicitly are always
+/// included.
+pub fn text_files(paths: &[PathBuf], patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect_dir(path, patterns, &mut files)?;
+        } else if path.is_file() {
+            files.push(path.clone());
+        } else {
+            bail!("Corpus path '{}' does not exist", path.display());
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether the text read from `reader` is valid UTF-8. Only one read is held in memory at once.
+fn is_utf8<R: BufRead>(mut reader: R) -> std::io::Result<bool> {
+    // bytes of a character that is split across reads
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(pending.is_empty());
+        }
+        pending.extend_from_slice(buf);
+        let n_read = buf.len();
+        reader.consume(n_read);
+
+        match std::str::from_utf8(&pending) {
+            Ok(_) => pending.clear(),
+            Err(e) if e.error_len().is_none() => {
+                pending.drain(..e.valid_up_to());
+            }
+            Err(_) => return Ok(false),
+        }
+    }
+}
+
+/// Read the files one after another and call `f` with a reader of each of them, e.g. for
+/// streaming its text chunk-wise with [`for_each_chunk`].
+///
+/// Files that are not valid UTF-8 are skipped with a warning. They are detected in a first pass
+/// over each file, before `f` is called. Returns the number of files that were read.
+pub fn for_each_file<F>(files: &[PathBuf], mut f: F) -> Result<usize>
+where
+    F: FnMut(BufReader<File>) -> Result<()>,
+{
+    let mut n_read = 0;
+    for file in files {
+        if !is_utf8(BufReader::new(File::open(file)?))? {
+            log::warn!("Skipping '{}': not valid UTF-8", file.display());
+            continue;
+        }
+        f(BufReader::new(File::open(file)?))?;
+        n_read += 1;
+    }
+
+    Ok(n_read)
+}
+
+/// Read UTF-8 text from `reader` and call `f` with consecutive chunks of it, so that the whole
+/// text never needs to be held in memory.
+///
+/// Characters whose bytes are split across reads are completed by the following read. Chunks
+/// end before a whitespace character wherever possible, so that no character is separated from
+/// its combining marks (and normalizing the chunks separately equals normalizing the whole text).
+pub fn for_each_chunk<R: BufRead, F: FnMut(&str)>(mut reader: R, mut f: F) -> Result<()> {
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        pending.extend_from_slice(buf);
+        let n_read = buf.len();
+        reader.consume(n_read);
+
+        let valid_len = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => bail!("Text is not valid UTF-8: {}", e),
+        };
+        let valid = std::str::from_utf8(&pending[..valid_len]).unwrap();
+        let chunk_len = match valid.rfind(char::is_whitespace) {
+            Some(pos) if pos > 0 => pos,
+            _ if valid_len > MAX_CHUNK_SIZE => valid_len,
+            _ => continue,
+        };
+        f(&valid[..chunk_len]);
+        pending.drain(..chunk_len);
+    }
+
+    match std::str::from_utf8(&pending) {
+        Ok(text) if !text.is_empty() => f(text),
+        Ok(_) => {}
+        Err(e) => bail!("Text is not valid UTF-8: {}", e),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(matches_pattern("corpus.txt", "*.txt"));
+        assert!(matches_pattern("a.b.md", "*.md"));
+        assert!(matches_pattern("file1.txt", "file?.txt"));
+        assert!(matches_pattern("anything", "*"));
+        assert!(matches_pattern("abc", "a*b*c"));
+        assert!(!matches_pattern("corpus.txt", "*.md"));
+        assert!(!matches_pattern("file10.txt", "file?.txt"));
+        assert!(!matches_pattern("txt", "*.txt"));
+    }
+
+    #[test]
+    fn comma_separated_patterns() {
+        assert_eq!(parse_patterns("*.txt, *.md,"), vec!["*.txt", "*.md"]);
+        assert!(parse_patterns("").is_empty());
+    }
+}
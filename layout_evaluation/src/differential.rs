@@ -0,0 +1,106 @@
+//! The `differential` module provides the comparison of evaluation results for testing
+//! implementations of the evaluation pipeline against each other (usually against
+//! [`crate::evaluation::evaluate_layout`]).
+//!
+//! The results need to agree in their total cost and in the weighted cost of each metric within
+//! a tolerance. The layouts and corpora to compare them on are generated by the tests (see
+//! `tests/differential.rs`).
+
+use crate::results::{EvaluationResult, MetricType};
+
+use std::fmt;
+
+/// A metric (or the total cost) whose costs differ between two implementations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Disagreement {
+    /// Type of the metric (`None` for the total cost)
+    pub metric_type: Option<MetricType>,
+    /// Name of the metric
+    pub metric: String,
+    /// Weighted cost computed by the reference implementation (NaN if the metric is missing)
+    pub expected: f64,
+    /// Weighted cost computed by the tested implementation (NaN if the metric is missing)
+    pub actual: f64,
+}
+
+impl fmt::Display for Disagreement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.metric_type {
+            Some(metric_type) => write!(f, "{:?} metric '{}'", metric_type, self.metric)?,
+            None => write!(f, "{}", self.metric)?,
+        }
+        write!(f, ": expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+/// Whether two costs agree within the tolerance (relative to the larger one, but at least
+/// absolute).
+fn agree(expected: f64, actual: f64, tolerance: f64) -> bool {
+    if expected == actual {
+        return true;
+    }
+    let scale = expected.abs().max(actual.abs()).max(1.0);
+    (expected - actual).abs() <= tolerance * scale
+}
+
+/// Compare the total costs and the weighted costs of all metrics of two evaluation results.
+pub fn compare_results(
+    expected: &EvaluationResult,
+    actual: &EvaluationResult,
+    tolerance: f64,
+) -> Vec<Disagreement> {
+    let costs = |result: &EvaluationResult| -> Vec<((MetricType, String), f64)> {
+        result
+            .iter()
+            .flat_map(|results| {
+                results.metric_costs.iter().map(move |mc| {
+                    (
+                        (results.metric_type.clone(), mc.core.name.clone()),
+                        mc.weighted_cost,
+                    )
+                })
+            })
+            .collect()
+    };
+    let expected_costs = costs(expected);
+    let actual_costs = costs(actual);
+    let cost_of = |costs: &[((MetricType, String), f64)], metric: &(MetricType, String)| {
+        costs
+            .iter()
+            .find(|(m, _)| m == metric)
+            .map(|(_, c)| *c)
+            .unwrap_or(f64::NAN)
+    };
+
+    let mut disagreements = Vec::new();
+    if !agree(expected.total_cost(), actual.total_cost(), tolerance) {
+        disagreements.push(Disagreement {
+            metric_type: None,
+            metric: "Total cost".to_string(),
+            expected: expected.total_cost(),
+            actual: actual.total_cost(),
+        });
+    }
+
+    // metrics of the expected result first, then those only present in the actual one
+    let mut metrics: Vec<&(MetricType, String)> = expected_costs.iter().map(|(m, _)| m).collect();
+    actual_costs.iter().map(|(m, _)| m).for_each(|m| {
+        if !metrics.contains(&m) {
+            metrics.push(m);
+        }
+    });
+    for metric in metrics {
+        let e = cost_of(&expected_costs, metric);
+        let a = cost_of(&actual_costs, metric);
+        if !agree(e, a, tolerance) {
+            disagreements.push(Disagreement {
+                metric_type: Some(metric.0.clone()),
+                metric: metric.1.clone(),
+                expected: e,
+                actual: a,
+            });
+        }
+    }
+
+    disagreements
+}
@@ -2,22 +2,39 @@
 //! layouts with respect to a list of metrics and ngram data.
 //!
 //! It can hold multiple metrics operating on the layout itself, unigrams, bigrams,
-//! or trigrams. These are required to implement the corresponding trait from the `metrics` module.
+//! trigrams, or quadgrams. These are required to implement the corresponding trait from the `metrics` module.
+//! Bigram metrics may alternatively operate on skipgrams (bigrams of the first and third symbol of each trigram).
 //!
 //! The ngram mapper is responsible for mapping char-based ngrams (as read from input data)
-//! to singles, pairs, and triplets of [`LayerKey`]s that can then be analysed by the individual metrics.
+//! to singles, pairs, triplets, and quadruplets of [`LayerKey`]s that can then be analysed by the individual metrics.
 
 use crate::results::{
-    EvaluationResult, MetricResult, MetricResults, MetricType, NormalizationType,
+    EvaluationResult, KeyboardResult, MetricResult, MetricResults, MetricType, NormalizationType,
 };
 use crate::{
-    metrics::{bigram_metrics::*, layout_metrics::*, trigram_metrics::*, unigram_metrics::*},
-    ngram_mapper::NgramMapper,
+    config::WeightProfile,
+    memory::MemoryReport,
+    metrics::{
+        bigram_metrics::*, layout_metrics::*, quadgram_metrics::*, trigram_metrics::*,
+        unigram_metrics::*,
+    },
+    ngram_mapper::{
+        common::{MissingSymbols, ShiftPolicy},
+        shared_ngram_mapper::stable_symbols,
+        LayerKeyQuadgram, MappedBigrams, MappedQuadgrams, MappedTrigrams, MappedUnigrams,
+        MappingStatistics, NgramMapper, TrigramExpansion,
+    },
 };
 
-use keyboard_layout::layout::{LayerKey, Layout};
+use keyboard_layout::{
+    layout::{LayerKey, Layout},
+    layout_generator::LayoutGenerator,
+};
 
+use ahash::AHashMap;
+use anyhow::{bail, Result};
 use serde::Deserialize;
+use std::path::Path;
 
 /// A wrapper around individuals metric's parameters (`T`) specifying
 /// additional generic attributes. This mostly facilitates configuration of
@@ -30,6 +47,10 @@ pub struct WeightedParams<T> {
     pub weight: f64,
     /// The normalization strategy to use.
     pub normalization: NormalizationType,
+    /// Whether the (bigram) metric is evaluated on skipgrams (bigrams of the first and third
+    /// symbol of each trigram) instead of bigrams.
+    #[serde(default)]
+    pub skipgrams: bool,
     /// The metric's individual parameters.
     pub params: T,
 }
@@ -41,25 +62,34 @@ pub struct MetricParameters {
     pub shortcut_keys: Option<WeightedParams<shortcut_keys::Parameters>>,
     pub similar_letters: Option<WeightedParams<similar_letters::Parameters>>,
     pub similar_letter_groups: Option<WeightedParams<similar_letter_groups::Parameters>>,
+    pub rank_penalty: Option<WeightedParams<rank_penalty::Parameters>>,
+    pub required_symbols: Option<WeightedParams<required_symbols::Parameters>>,
+    pub word_start_hand: Option<WeightedParams<word_start_hand::Parameters>>,
 
     pub finger_balance: Option<WeightedParams<finger_balance::Parameters>>,
     pub hand_disbalance: Option<WeightedParams<hand_disbalance::Parameters>>,
     pub row_loads: Option<WeightedParams<row_loads::Parameters>>,
+    pub home_row_anchor: Option<WeightedParams<home_row_anchor::Parameters>>,
     pub key_costs: Option<WeightedParams<key_costs::Parameters>>,
     pub modifier_usage: Option<WeightedParams<modifier_usage::Parameters>>,
 
     pub symmetric_handswitches: Option<WeightedParams<symmetric_handswitches::Parameters>>,
     pub finger_repeats: Option<WeightedParams<finger_repeats::Parameters>>,
+    pub finger_travel_distance: Option<WeightedParams<finger_travel_distance::Parameters>>,
+    pub lateral_stretch_bigrams: Option<WeightedParams<lateral_stretch_bigrams::Parameters>>,
     pub manual_bigram_penalty: Option<WeightedParams<manual_bigram_penalty::Parameters>>,
     pub movement_pattern: Option<WeightedParams<movement_pattern::Parameters>>,
     pub no_handswitch_after_unbalancing_key:
         Option<WeightedParams<no_handswitch_after_unbalancing_key::Parameters>>,
+    pub same_hand_mod_tap: Option<WeightedParams<same_hand_mod_tap::Parameters>>,
 
     pub irregularity: Option<WeightedParams<irregularity::Parameters>>,
     pub no_handswitch_in_trigram: Option<WeightedParams<no_handswitch_in_trigram::Parameters>>,
     pub secondary_bigrams: Option<WeightedParams<secondary_bigrams::Parameters>>,
     pub trigram_finger_repeats: Option<WeightedParams<trigram_finger_repeats::Parameters>>,
     pub trigram_rolls: Option<WeightedParams<trigram_rolls::Parameters>>,
+    pub pivots: Option<WeightedParams<pivots::Parameters>>,
+    pub thumb_alternation: Option<WeightedParams<thumb_alternation::Parameters>>,
 
     pub kla_home_key_words: Option<WeightedParams<kla_home_key_words::Parameters>>,
     pub kla_same_finger_words: Option<WeightedParams<kla_same_finger_words::Parameters>>,
@@ -82,15 +112,72 @@ pub struct MetricParameters {
     pub oxey_bad_redirects: Option<WeightedParams<oxey_bad_redirects::Parameters>>,
 }
 
+/// Everything an evaluation depends on: the ngram data (held by the ngram mapper) and the
+/// configured metrics along with their weights and normalizations.
+///
+/// This is only an alias for [`Evaluator`], so the context includes the mapping caches of the
+/// ngram mapper (see
+/// [`NgramMapperConfig::mapping_cache_size`](crate::ngram_mapper::on_demand_ngram_mapper::NgramMapperConfig::mapping_cache_size)).
+/// They only avoid repeated mappings and do not change the results.
+pub type EvaluationContext = Evaluator;
+
+/// Evaluate all metrics of the context for a layout.
+///
+/// This is the single entry point of the evaluation pipeline. The result only depends on the
+/// context and the layout: Neither of them is modified, no global state is involved, nothing is
+/// logged, and repeated evaluations yield identical results. Other implementations of the
+/// pipeline can therefore be tested against it (see [`crate::differential`]).
+pub fn evaluate_layout(context: &EvaluationContext, layout: &Layout) -> EvaluationResult {
+    let mapped_unigrams = context.map_unigrams_if_required(layout);
+    let mapped_bigrams = context.map_bigrams_if_required(layout);
+    let mapped_trigrams = context.map_trigrams_if_required(layout);
+    let mapped_quadgrams = context.map_quadgrams_if_required(layout);
+    let mapped_skipgrams = context.map_skipgrams_if_required(layout);
+
+    context.evaluate_mapped_ngrams(
+        layout,
+        mapped_unigrams.as_ref(),
+        mapped_bigrams.as_ref(),
+        mapped_trigrams.as_ref(),
+        mapped_quadgrams.as_ref(),
+        mapped_skipgrams.as_ref(),
+    )
+}
+
+/// A further keyboard that layouts are evaluated on (see [`Evaluator::with_keyboards`]).
+#[derive(Clone, Debug)]
+pub struct KeyboardVariant {
+    /// Name of the keyboard (e.g. its layout config)
+    pub name: String,
+    /// Share of the keyboard in the combined result
+    pub weight: f64,
+    /// Generates the layouts on this keyboard from the layout strings
+    pub layout_generator: Box<dyn LayoutGenerator>,
+}
+
 /// The [`Evaluator`] object is responsible for evaluating multiple metrics with respect to given ngram data.
 /// The metrics are handled as dynamically dispatched trait objects for the metric traits in the `metrics` module.
 #[derive(Clone, Debug)]
 pub struct Evaluator {
-    layout_metrics: Vec<(f64, NormalizationType, Box<dyn LayoutMetric>)>,
-    unigram_metrics: Vec<(f64, NormalizationType, Box<dyn UnigramMetric>)>,
-    bigram_metrics: Vec<(f64, NormalizationType, Box<dyn BigramMetric>)>,
-    trigram_metrics: Vec<(f64, NormalizationType, Box<dyn TrigramMetric>)>,
+    pub(crate) layout_metrics: Vec<(f64, NormalizationType, Box<dyn LayoutMetric>)>,
+    pub(crate) unigram_metrics: Vec<(f64, NormalizationType, Box<dyn UnigramMetric>)>,
+    pub(crate) bigram_metrics: Vec<(f64, NormalizationType, Box<dyn BigramMetric>)>,
+    pub(crate) trigram_metrics: Vec<(f64, NormalizationType, Box<dyn TrigramMetric>)>,
+    pub(crate) quadgram_metrics: Vec<(f64, NormalizationType, Box<dyn QuadgramMetric>)>,
+    pub(crate) skipgram_metrics: Vec<(f64, NormalizationType, Box<dyn BigramMetric>)>,
     ngram_mapper: Box<dyn NgramMapper>,
+    /// Configuration keys of the "default" metrics (by metric name)
+    metric_keys: AHashMap<String, &'static str>,
+    /// Whether any metric aggregates the bigram metrics internally
+    has_composite_metrics: bool,
+    /// Name of the weight profile the metrics' weights were taken from
+    profile: Option<String>,
+    /// Types of metrics that are skipped due to missing ngram data
+    skipped_metric_types: Vec<MetricType>,
+    /// Name and weight of the keyboard of the evaluated layouts (if further keyboards are given)
+    keyboard: Option<(String, f64)>,
+    /// Further keyboards the layouts are evaluated on
+    keyboard_variants: Vec<KeyboardVariant>,
 }
 
 impl Evaluator {
@@ -101,35 +188,264 @@ impl Evaluator {
             unigram_metrics: Vec::new(),
             bigram_metrics: Vec::new(),
             trigram_metrics: Vec::new(),
+            quadgram_metrics: Vec::new(),
+            skipgram_metrics: Vec::new(),
             ngram_mapper,
+            metric_keys: AHashMap::default(),
+            has_composite_metrics: false,
+            profile: None,
+            skipped_metric_types: Vec::new(),
+            keyboard: None,
+            keyboard_variants: Vec::new(),
+        }
+    }
+
+    /// Evaluate layouts on further keyboards as well. The layouts passed to
+    /// [`Evaluator::evaluate_layout`] belong to the keyboard `name` with the given `weight` and
+    /// are generated by `layout_generator`. Their layout strings are realized on each of the
+    /// `variants` with its layout generator and the results are combined according to the
+    /// keyboards' weights (see [`EvaluationResult::combined`]).
+    ///
+    /// Fails if a variant's layout strings do not consist of the same symbols as those of
+    /// `layout_generator`.
+    pub fn with_keyboards(
+        mut self,
+        name: &str,
+        weight: f64,
+        layout_generator: &dyn LayoutGenerator,
+        variants: Vec<KeyboardVariant>,
+    ) -> Result<Self> {
+        let mut symbols = layout_generator.permutable_keys();
+        symbols.sort_unstable();
+        for variant in variants.iter() {
+            let mut variant_symbols = variant.layout_generator.permutable_keys();
+            variant_symbols.sort_unstable();
+            if variant_symbols != symbols {
+                let unsupported: String = symbols
+                    .iter()
+                    .filter(|c| !variant_symbols.contains(c))
+                    .collect();
+                let additional: String = variant_symbols
+                    .iter()
+                    .filter(|c| !symbols.contains(c))
+                    .collect();
+                bail!(
+                    "Layouts of keyboard '{}' can not be realized on keyboard '{}' (unsupported symbols: '{}', additional symbols: '{}')",
+                    name,
+                    variant.name,
+                    unsupported,
+                    additional
+                );
+            }
+        }
+
+        self.keyboard = Some((name.to_string(), weight));
+        self.keyboard_variants = variants;
+        Ok(self)
+    }
+
+    /// Record the name of the weight profile that was used for configuring the metrics.
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Name of the weight profile that was used for configuring the metrics.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Remove all metrics of the given types (e.g. because the ngram data they operate on is
+    /// missing). The evaluation results state the skipped types.
+    pub fn skip_metric_types(mut self, metric_types: &[MetricType]) -> Self {
+        let mut metric_types = metric_types.to_vec();
+        // skipgrams are derived from the trigrams
+        if metric_types.contains(&MetricType::Trigram)
+            && !metric_types.contains(&MetricType::Skipgram)
+            && !self.skipgram_metrics.is_empty()
+        {
+            metric_types.push(MetricType::Skipgram);
+        }
+
+        for metric_type in metric_types.iter() {
+            let names: Vec<String> = match metric_type {
+                MetricType::Layout => self
+                    .layout_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+                MetricType::Unigram => self
+                    .unigram_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+                MetricType::Bigram => self
+                    .bigram_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+                MetricType::Trigram => self
+                    .trigram_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+                MetricType::Quadgram => self
+                    .quadgram_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+                MetricType::Skipgram => self
+                    .skipgram_metrics
+                    .drain(..)
+                    .map(|(_, _, m)| m.name().to_string())
+                    .collect(),
+            };
+            if !names.is_empty() {
+                log::warn!(
+                    "Skipping {:?} metrics due to missing ngram data: {}",
+                    metric_type,
+                    names.join(", ")
+                );
+            }
+
+            if !self.skipped_metric_types.contains(metric_type) {
+                self.skipped_metric_types.push(metric_type.clone());
+            }
         }
+
+        self
+    }
+
+    /// Types of metrics that are skipped due to missing ngram data.
+    pub fn skipped_metric_types(&self) -> &[MetricType] {
+        &self.skipped_metric_types
+    }
+
+    /// Whether [`Evaluator::reweight`] yields the same result for the given profile as a full
+    /// evaluation would. This is not the case if the profile changes the weight of a bigram
+    /// metric that is aggregated by another metric.
+    pub fn reweighting_is_exact(&self, profile: &WeightProfile) -> bool {
+        !self.has_composite_metrics
+            || self.bigram_metrics.iter().all(|(_, _, metric)| {
+                self.metric_keys
+                    .get(metric.name())
+                    .map_or(true, |key| !profile.contains_key(*key))
+            })
+    }
+
+    /// Apply the weights of another profile to an evaluation result of this evaluator
+    /// without evaluating the metrics again.
+    ///
+    /// *Note:* Metrics that are composed of bigram metrics ("Irregularity" and "Secondary Bigrams")
+    /// keep using the bigram metrics' weights of this evaluator internally. See
+    /// [`Evaluator::reweighting_is_exact`].
+    pub fn reweight(
+        &self,
+        result: &EvaluationResult,
+        profile_name: &str,
+        profile: &WeightProfile,
+    ) -> EvaluationResult {
+        result.reweighted(Some(profile_name.to_string()), |metric_result| {
+            self.metric_keys
+                .get(&metric_result.name)
+                .and_then(|key| profile.get(*key))
+                .copied()
+                .unwrap_or(metric_result.weight)
+        })
     }
 
     /// Add all "default" metrics to the evaluator.
     pub fn default_metrics(mut self, params: &MetricParameters) -> Self {
         macro_rules! add_metric {
+            (bigram_metric, $metric_name:ident, $metric_struct:ident) => {
+                if let Some(p) = &params.$metric_name {
+                    if p.enabled {
+                        let metric = Box::new($metric_name::$metric_struct::new(&p.params));
+                        self.metric_keys
+                            .insert(metric.name().to_string(), stringify!($metric_name));
+                        if p.skipgrams {
+                            self.skipgram_metric(metric, p.weight, p.normalization.clone());
+                        } else {
+                            self.bigram_metric(metric, p.weight, p.normalization.clone());
+                        }
+                    }
+                }
+            };
             ($metric_type:ident, $metric_name:ident, $metric_struct:ident) => {
                 if let Some(p) = &params.$metric_name {
                     if p.enabled {
-                        self.$metric_type(
-                            Box::new($metric_name::$metric_struct::new(&p.params)),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
+                        if p.skipgrams {
+                            log::warn!(
+                                "Metric '{}' is no bigram metric and can not be evaluated on skipgrams",
+                                stringify!($metric_name)
+                            );
+                        }
+                        let metric = Box::new($metric_name::$metric_struct::new(&p.params));
+                        self.metric_keys
+                            .insert(metric.name().to_string(), stringify!($metric_name));
+                        self.$metric_type(metric, p.weight, p.normalization.clone());
                     }
                 }
             };
             ($metric_type:ident, $metric_name:ident, $metric_struct:ident, "add_bigram_metrics") => {
                 if let Some(p) = &params.$metric_name {
                     if p.enabled {
-                        self.$metric_type(
+                        let metric = Box::new($metric_name::$metric_struct::new(
+                            self.bigram_metrics.clone(),
+                            &p.params,
+                        ));
+                        self.metric_keys
+                            .insert(metric.name().to_string(), stringify!($metric_name));
+                        self.has_composite_metrics = true;
+                        self.$metric_type(metric, p.weight, p.normalization.clone());
+                    }
+                }
+            };
+            ($metric_type:ident, $metric_name:ident, $metric_struct:ident, "add_word_starts") => {
+                if let Some(p) = &params.$metric_name {
+                    if p.enabled {
+                        let metric = self.ngram_mapper.word_starts().map(|(word_starts, unigrams)| {
                             Box::new($metric_name::$metric_struct::new(
-                                self.bigram_metrics.clone(),
                                 &p.params,
-                            )),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
+                                word_starts,
+                                unigrams,
+                            ))
+                        });
+                        match metric {
+                            Some(metric) => {
+                                self.metric_keys
+                                    .insert(metric.name().to_string(), stringify!($metric_name));
+                                self.$metric_type(metric, p.weight, p.normalization.clone());
+                            }
+                            None => log::warn!(
+                                "Metric '{}' is skipped as the ngram mapper does not provide word-initial symbols",
+                                stringify!($metric_name)
+                            ),
+                        }
+                    }
+                }
+            };
+            ($metric_type:ident, $metric_name:ident, $metric_struct:ident, "add_redirect_metrics") => {
+                if let Some(p) = &params.$metric_name {
+                    if p.enabled {
+                        let redirects = params
+                            .oxey_redirects
+                            .as_ref()
+                            .filter(|r| r.enabled)
+                            .map(|r| oxey_redirects::OxeyRedirects::new(&r.params));
+                        let bad_redirects = params
+                            .oxey_bad_redirects
+                            .as_ref()
+                            .filter(|r| r.enabled)
+                            .map(|r| oxey_bad_redirects::OxeyBadRedirects::new(&r.params));
+                        let metric = Box::new($metric_name::$metric_struct::new(
+                            &p.params,
+                            redirects,
+                            bad_redirects,
+                        ));
+                        self.metric_keys
+                            .insert(metric.name().to_string(), stringify!($metric_name));
+                        self.$metric_type(metric, p.weight, p.normalization.clone());
                     }
                 }
             };
@@ -139,16 +455,31 @@ impl Evaluator {
         add_metric!(layout_metric, shortcut_keys, ShortcutKeys);
         add_metric!(layout_metric, similar_letters, SimilarLetters);
         add_metric!(layout_metric, similar_letter_groups, SimilarLetterGroups);
+        add_metric!(layout_metric, rank_penalty, RankPenalty);
+        add_metric!(layout_metric, required_symbols, RequiredSymbols);
+        add_metric!(
+            layout_metric,
+            word_start_hand,
+            WordStartHand,
+            "add_word_starts"
+        );
 
         // unigram metrics
         add_metric!(unigram_metric, finger_balance, FingerBalance);
         add_metric!(unigram_metric, hand_disbalance, HandDisbalance);
         add_metric!(unigram_metric, row_loads, RowLoads);
+        add_metric!(unigram_metric, home_row_anchor, HomeRowAnchor);
         add_metric!(unigram_metric, modifier_usage, ModifierUsage);
         add_metric!(unigram_metric, key_costs, KeyCost);
 
         // bigram metrics
         add_metric!(bigram_metric, finger_repeats, FingerRepeats);
+        add_metric!(bigram_metric, finger_travel_distance, FingerTravelDistance);
+        add_metric!(
+            bigram_metric,
+            lateral_stretch_bigrams,
+            LateralStretchBigrams
+        );
         add_metric!(bigram_metric, manual_bigram_penalty, ManualBigramPenalty);
         add_metric!(bigram_metric, movement_pattern, MovementPattern);
         add_metric!(
@@ -157,6 +488,7 @@ impl Evaluator {
             NoHandSwitchAfterUnbalancingKey
         );
         add_metric!(bigram_metric, symmetric_handswitches, SymmetricHandswitches);
+        add_metric!(bigram_metric, same_hand_mod_tap, SameHandModTap);
 
         // trigram_metrics
         add_metric!(
@@ -166,6 +498,8 @@ impl Evaluator {
         );
         add_metric!(trigram_metric, trigram_finger_repeats, TrigramFingerRepeats);
         add_metric!(trigram_metric, trigram_rolls, TrigramRolls);
+        add_metric!(trigram_metric, pivots, Pivots, "add_redirect_metrics");
+        add_metric!(trigram_metric, thumb_alternation, ThumbAlternation);
         add_metric!(
             trigram_metric,
             irregularity,
@@ -233,6 +567,18 @@ impl Evaluator {
         self.bigram_metrics.push((weight, normalization, metric));
     }
 
+    /// Add a bigram metric that operates on the skipgrams (bigrams of the first and third symbol
+    /// of each trigram) instead of the bigrams. The skipgrams are only mapped if there is at
+    /// least one such metric.
+    pub fn skipgram_metric(
+        &mut self,
+        metric: Box<dyn BigramMetric>,
+        weight: f64,
+        normalization: NormalizationType,
+    ) {
+        self.skipgram_metrics.push((weight, normalization, metric));
+    }
+
     /// Add a metric that operates on the trigram data ("trigram metric").
     pub fn trigram_metric(
         &mut self,
@@ -243,17 +589,56 @@ impl Evaluator {
         self.trigram_metrics.push((weight, normalization, metric));
     }
 
+    /// Add a metric that operates on the quadgram data ("quadgram metric"). The quadgrams are
+    /// only mapped if there is at least one such metric.
+    pub fn quadgram_metric(
+        &mut self,
+        metric: Box<dyn QuadgramMetric>,
+        weight: f64,
+        normalization: NormalizationType,
+    ) {
+        self.quadgram_metrics.push((weight, normalization, metric));
+    }
+
     /// Evaluate all layout metrics for a layout.
-    fn evaluate_layout_metrics(&self, layout: &Layout) -> Vec<MetricResult> {
+    fn evaluate_layout_metrics(
+        &self,
+        layout: &Layout,
+        unigrams: Option<&MappedUnigrams>,
+    ) -> Vec<MetricResult> {
         if self.layout_metrics.is_empty() {
             return Vec::new();
         }
 
+        // weights of the unmapped unigram symbols relative to the total unigram weight
+        let missing_symbols: Option<MissingSymbols> = unigrams.map(|unigrams| {
+            let total_weight = unigrams.weight_found + unigrams.weight_not_found;
+            unigrams
+                .missing_symbols
+                .iter()
+                .map(|(c, w)| {
+                    (
+                        *c,
+                        if total_weight > 0.0 {
+                            w / total_weight
+                        } else {
+                            0.0
+                        },
+                    )
+                })
+                .collect()
+        });
+
         let metric_costs: Vec<MetricResult> = self
             .layout_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(layout);
+                let (cost, message) = match &missing_symbols {
+                    Some(missing_symbols) if metric.requires_missing_symbols() => {
+                        metric.total_cost_with_missing_symbols(layout, missing_symbols)
+                    }
+                    _ => metric.total_cost(layout),
+                };
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
@@ -271,18 +656,25 @@ impl Evaluator {
     fn evaluate_unigram_metrics(
         &self,
         layout: &Layout,
-        keys: &[(&LayerKey, f64)],
+        unigrams: &MappedUnigrams,
     ) -> Vec<MetricResult> {
         if self.unigram_metrics.is_empty() {
             return Vec::new();
         }
 
+        let keys = &unigrams.grams;
         let total_weight = keys.iter().map(|(_, w)| w).sum();
         let metric_costs: Vec<MetricResult> = self
             .unigram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let (cost, message) = unigrams
+                    .modifier_presses
+                    .as_ref()
+                    .and_then(|presses| {
+                        metric.total_cost_with_modifier_presses(keys, presses, total_weight, layout)
+                    })
+                    .unwrap_or_else(|| metric.total_cost(keys, Some(total_weight), layout));
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
@@ -296,19 +688,19 @@ impl Evaluator {
         metric_costs
     }
 
-    /// Evaluate all bigram metrics for a layout.
+    /// Evaluate the given bigram metrics (the bigram or the skipgram metrics) for a layout.
     fn evaluate_bigram_metrics(
         &self,
+        metrics: &[(f64, NormalizationType, Box<dyn BigramMetric>)],
         layout: &Layout,
         keys: &[((&LayerKey, &LayerKey), f64)],
     ) -> Vec<MetricResult> {
-        if self.bigram_metrics.is_empty() {
+        if metrics.is_empty() {
             return Vec::new();
         }
 
         let total_weight = keys.iter().map(|(_, w)| w).sum();
-        let metric_costs: Vec<MetricResult> = self
-            .bigram_metrics
+        let metric_costs: Vec<MetricResult> = metrics
             .iter()
             .map(|(weight, normalization, metric)| {
                 let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
@@ -329,16 +721,56 @@ impl Evaluator {
     fn evaluate_trigram_metrics(
         &self,
         layout: &Layout,
-        keys: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        trigrams: &MappedTrigrams,
     ) -> Vec<MetricResult> {
         if self.trigram_metrics.is_empty() {
             return Vec::new();
         }
 
-        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let keys = &trigrams.grams;
+        let total_weight: f64 = keys.iter().map(|(_, w)| w).sum();
         let metric_costs: Vec<MetricResult> = self
             .trigram_metrics
             .iter()
+            .map(|(weight, normalization, metric)| {
+                let total_weight = match metric.includes_filtered_weight() {
+                    true => total_weight + trigrams.weight_filtered,
+                    false => total_weight,
+                };
+                let (cost, message) = trigrams
+                    .secondary_bigrams
+                    .as_ref()
+                    .and_then(|bigrams| {
+                        metric.total_cost_with_secondary_bigrams(bigrams, total_weight, layout)
+                    })
+                    .unwrap_or_else(|| metric.total_cost(keys, Some(total_weight), layout));
+                MetricResult {
+                    name: metric.name().to_string(),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    message,
+                }
+            })
+            .collect();
+
+        metric_costs
+    }
+
+    /// Evaluate all quadgram metrics for a layout.
+    fn evaluate_quadgram_metrics(
+        &self,
+        layout: &Layout,
+        keys: &[(LayerKeyQuadgram, f64)],
+    ) -> Vec<MetricResult> {
+        if self.quadgram_metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let metric_costs: Vec<MetricResult> = self
+            .quadgram_metrics
+            .iter()
             .map(|(weight, normalization, metric)| {
                 let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
                 MetricResult {
@@ -354,13 +786,277 @@ impl Evaluator {
         metric_costs
     }
 
-    /// Evaluate all metrics for a layout.
+    /// Evaluate all metrics for a layout (see [`evaluate_layout`]). If further keyboards are
+    /// given (see [`Evaluator::with_keyboards`]), the layout is evaluated on each of them and the
+    /// combined result is returned. Keyboards the layout string can not be realized on are
+    /// skipped (with a warning).
     pub fn evaluate_layout(&self, layout: &Layout) -> EvaluationResult {
+        let (name, weight) = match &self.keyboard {
+            Some(keyboard) if !self.keyboard_variants.is_empty() => keyboard,
+            _ => return evaluate_layout(self, layout),
+        };
+
+        let layout_str = layout.as_text();
+        let mut keyboard_results = vec![KeyboardResult {
+            keyboard: name.clone(),
+            weight: *weight,
+            result: evaluate_layout(self, layout),
+        }];
+        self.keyboard_variants.iter().for_each(|variant| {
+            let variant_layout = match variant.layout_generator.generate(&layout_str) {
+                Ok(variant_layout) => variant_layout,
+                Err(e) => {
+                    log::warn!(
+                        "Skipping keyboard '{}': Layout '{}' can not be realized on it: {}",
+                        variant.name,
+                        layout_str,
+                        e
+                    );
+                    return;
+                }
+            };
+            keyboard_results.push(KeyboardResult {
+                keyboard: variant.name.clone(),
+                weight: variant.weight,
+                result: evaluate_layout(self, &variant_layout),
+            });
+        });
+
+        EvaluationResult::combined(layout_str, keyboard_results).with_profile(self.profile.clone())
+    }
+
+    /// Evaluate a layout together with its mirrored variant (see [`Layout::mirrored`]), e.g. for
+    /// typing with one hand part of the time. The ngrams' weight is split between both variants:
+    /// the mirrored one gets the share `mirror_weight` of it. The results are combined like those
+    /// of multiple keyboards (see [`EvaluationResult::combined`]).
+    pub fn evaluate_mirrored(
+        &self,
+        layout: &Layout,
+        mirror_weight: f64,
+    ) -> Result<EvaluationResult> {
+        let mirrored = layout.mirrored(layout.keyboard.mirror_map())?;
+        let keyboard_results = vec![
+            KeyboardResult {
+                keyboard: "normal".to_string(),
+                weight: 1.0 - mirror_weight,
+                result: self.evaluate_layout(layout),
+            },
+            KeyboardResult {
+                keyboard: "mirrored".to_string(),
+                weight: mirror_weight,
+                result: self.evaluate_layout(&mirrored),
+            },
+        ];
+
+        Ok(
+            EvaluationResult::combined(layout.as_text(), keyboard_results)
+                .with_profile(self.profile.clone()),
+        )
+    }
+
+    /// Map the unigrams to the layout independently of the active metrics (e.g. for estimating
+    /// the load of its keys).
+    pub fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
+        self.ngram_mapper.map_unigrams(layout)
+    }
+
+    /// Statistics about how much of the ngrams' weight is mapped to the layout (see
+    /// [`MappingStatistics`]). `None` if the ngram mapper does not provide them.
+    pub fn mapping_statistics(&self, layout: &Layout) -> Option<MappingStatistics> {
+        self.ngram_mapper.mapping_statistics(layout)
+    }
+
+    /// Write the unigrams, bigrams, and trigrams mapped to the layout to TSV files in the given
+    /// directory (see [`NgramMapper::dump_mapped_ngrams`]).
+    pub fn dump_mapped_ngrams(&self, layout: &Layout, dir: &Path) -> Result<()> {
+        self.ngram_mapper.dump_mapped_ngrams(layout, dir)
+    }
+
+    /// The trigrams that a char-based trigram is mapped to (see [`NgramMapper::explain_trigram`]).
+    pub fn explain_trigram<'s>(
+        &self,
+        trigram: (char, char, char),
+        layout: &'s Layout,
+    ) -> Option<TrigramExpansion<'s>> {
+        self.ngram_mapper.explain_trigram(trigram, layout)
+    }
+
+    /// An evaluator for a batch of layouts that differ only in some of their symbols (e.g. in
+    /// their higher layers). Ngrams consisting of symbols that all layouts generate identically
+    /// are mapped only once. The evaluator yields the same results as this one for the given
+    /// layouts, but not necessarily for others.
+    ///
+    /// Falls back to a plain clone if the ngram mapper does not support sharing or the layouts
+    /// differ in the kinds of their layers.
+    pub fn with_shared_mapping(&self, layouts: &[&Layout]) -> Self {
+        let first = match layouts.first() {
+            Some(l) => l,
+            None => return self.clone(),
+        };
+
+        let layer_kinds = |l: &Layout| {
+            (
+                l.has_hold_layers(),
+                l.has_one_shot_layers(),
+                l.has_multi_key_symbols(),
+            )
+        };
+        if layouts.iter().any(|l| layer_kinds(l) != layer_kinds(first)) {
+            return self.clone();
+        }
+
+        match self
+            .ngram_mapper
+            .share_mapping(first, &stable_symbols(layouts))
+        {
+            Some(ngram_mapper) => Self {
+                ngram_mapper,
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// An evaluator that models the modifiers of the shift layer according to the given policy
+    /// (see [`ShiftPolicy`]). `None` if the ngram mapper does not support shift policies.
+    pub fn with_shift_policy(&self, policy: ShiftPolicy) -> Option<Self> {
+        self.ngram_mapper
+            .with_shift_policy(policy)
+            .map(|ngram_mapper| Self {
+                ngram_mapper,
+                ..self.clone()
+            })
+    }
+
+    /// Estimate the memory used by the data of the ngram mapper, the ngrams mapped to the given
+    /// layout (as allocated during each evaluation), and the metrics.
+    pub fn memory_report(&self, layout: &Layout) -> MemoryReport {
+        let mut report = MemoryReport::default();
+
+        self.ngram_mapper.memory_report(&mut report);
+
+        if let Some(mapped) = self.map_unigrams_if_required(layout) {
+            report.add(
+                "Mapped unigrams",
+                mapped.grams.len(),
+                mapped.estimated_bytes(),
+            );
+        }
+        if let Some(mapped) = self.map_bigrams_if_required(layout) {
+            report.add(
+                "Mapped bigrams",
+                mapped.grams.len(),
+                mapped.estimated_bytes(),
+            );
+        }
+        if let Some(mapped) = self.map_trigrams_if_required(layout) {
+            report.add(
+                "Mapped trigrams",
+                mapped.grams.len(),
+                mapped.estimated_bytes(),
+            );
+        }
+        if let Some(mapped) = self.map_quadgrams_if_required(layout) {
+            report.add(
+                "Mapped quadgrams",
+                mapped.grams.len(),
+                mapped.estimated_bytes(),
+            );
+        }
+        if let Some(mapped) = self.map_skipgrams_if_required(layout) {
+            report.add(
+                "Mapped skipgrams",
+                mapped.grams.len(),
+                mapped.estimated_bytes(),
+            );
+        }
+
+        let mut add_metric = |metric_type: &str, name: &str, bytes: usize| {
+            report.add_bytes(&format!("{} metric: {}", metric_type, name), bytes)
+        };
+        self.layout_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Layout", m.name(), m.estimated_bytes()));
+        self.unigram_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Unigram", m.name(), m.estimated_bytes()));
+        self.bigram_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Bigram", m.name(), m.estimated_bytes()));
+        self.trigram_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Trigram", m.name(), m.estimated_bytes()));
+        self.quadgram_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Quadgram", m.name(), m.estimated_bytes()));
+        self.skipgram_metrics
+            .iter()
+            .for_each(|(_, _, m)| add_metric("Skipgram", m.name(), m.estimated_bytes()));
+
+        report
+    }
+
+    /// Map unigrams to the layout if any unigram metric (or any layout metric depending on the
+    /// missing symbols) is active.
+    pub(crate) fn map_unigrams_if_required<'s>(
+        &self,
+        layout: &'s Layout,
+    ) -> Option<MappedUnigrams<'s>> {
+        let required = !self.unigram_metrics.is_empty()
+            || self
+                .layout_metrics
+                .iter()
+                .any(|(_, _, m)| m.requires_missing_symbols());
+        required.then(|| self.ngram_mapper.map_unigrams(layout))
+    }
+
+    /// Map bigrams to the layout if any bigram metric is active.
+    pub(crate) fn map_bigrams_if_required<'s>(
+        &self,
+        layout: &'s Layout,
+    ) -> Option<MappedBigrams<'s>> {
+        (!self.bigram_metrics.is_empty()).then(|| self.ngram_mapper.map_bigrams(layout))
+    }
+
+    /// Map trigrams to the layout if any trigram metric is active.
+    pub(crate) fn map_trigrams_if_required<'s>(
+        &self,
+        layout: &'s Layout,
+    ) -> Option<MappedTrigrams<'s>> {
+        (!self.trigram_metrics.is_empty()).then(|| self.ngram_mapper.map_trigrams(layout))
+    }
+
+    /// Map quadgrams to the layout if any quadgram metric is active.
+    pub(crate) fn map_quadgrams_if_required<'s>(
+        &self,
+        layout: &'s Layout,
+    ) -> Option<MappedQuadgrams<'s>> {
+        (!self.quadgram_metrics.is_empty()).then(|| self.ngram_mapper.map_quadgrams(layout))
+    }
+
+    /// Map skipgrams to the layout if any skipgram metric is active.
+    pub(crate) fn map_skipgrams_if_required<'s>(
+        &self,
+        layout: &'s Layout,
+    ) -> Option<MappedBigrams<'s>> {
+        (!self.skipgram_metrics.is_empty()).then(|| self.ngram_mapper.map_skipgrams(layout))
+    }
+
+    /// Evaluate all metrics for a layout with given (already mapped) ngrams.
+    pub(crate) fn evaluate_mapped_ngrams(
+        &self,
+        layout: &Layout,
+        mapped_unigrams: Option<&MappedUnigrams>,
+        mapped_bigrams: Option<&MappedBigrams>,
+        mapped_trigrams: Option<&MappedTrigrams>,
+        mapped_quadgrams: Option<&MappedQuadgrams>,
+        mapped_skipgrams: Option<&MappedBigrams>,
+    ) -> EvaluationResult {
         let mut results: Vec<MetricResults> = Vec::new();
 
         // Layout metrics
         if !self.layout_metrics.is_empty() {
-            let metric_costs = self.evaluate_layout_metrics(layout);
+            let metric_costs = self.evaluate_layout_metrics(layout, mapped_unigrams);
             let mut layout_costs = MetricResults::new(MetricType::Layout, 1.0, 0.0);
             metric_costs
                 .into_iter()
@@ -369,14 +1065,15 @@ impl Evaluator {
         }
 
         // Unigram metrics
-        if !self.unigram_metrics.is_empty() {
-            let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
-            let metric_costs = self.evaluate_unigram_metrics(layout, &mapped_unigrams.grams);
+        if let Some(mapped_unigrams) = mapped_unigrams.filter(|_| !self.unigram_metrics.is_empty())
+        {
+            let metric_costs = self.evaluate_unigram_metrics(layout, mapped_unigrams);
             let mut unigram_costs = MetricResults::new(
                 MetricType::Unigram,
                 mapped_unigrams.weight_found,
                 mapped_unigrams.weight_not_found,
-            );
+            )
+            .with_missing_symbols(mapped_unigrams.missing_symbols.clone());
             metric_costs
                 .into_iter()
                 .for_each(|mc| unigram_costs.add_result(mc));
@@ -385,14 +1082,15 @@ impl Evaluator {
         }
 
         // Bigram metrics
-        if !self.bigram_metrics.is_empty() {
-            let mapped_bigrams = self.ngram_mapper.map_bigrams(layout);
-            let metric_costs = self.evaluate_bigram_metrics(layout, &mapped_bigrams.grams);
+        if let Some(mapped_bigrams) = mapped_bigrams {
+            let metric_costs =
+                self.evaluate_bigram_metrics(&self.bigram_metrics, layout, &mapped_bigrams.grams);
             let mut bigram_costs = MetricResults::new(
                 MetricType::Bigram,
                 mapped_bigrams.weight_found,
                 mapped_bigrams.weight_not_found,
-            );
+            )
+            .with_missing_symbols(mapped_bigrams.missing_symbols.clone());
             metric_costs
                 .into_iter()
                 .for_each(|mc| bigram_costs.add_result(mc));
@@ -401,14 +1099,14 @@ impl Evaluator {
         }
 
         // Trigram metrics
-        if !self.trigram_metrics.is_empty() {
-            let mapped_trigrams = self.ngram_mapper.map_trigrams(layout);
-            let metric_costs = self.evaluate_trigram_metrics(layout, &mapped_trigrams.grams);
+        if let Some(mapped_trigrams) = mapped_trigrams {
+            let metric_costs = self.evaluate_trigram_metrics(layout, mapped_trigrams);
             let mut trigram_costs = MetricResults::new(
                 MetricType::Trigram,
                 mapped_trigrams.weight_found,
                 mapped_trigrams.weight_not_found,
-            );
+            )
+            .with_missing_symbols(mapped_trigrams.missing_symbols.clone());
             metric_costs
                 .into_iter()
                 .for_each(|mc| trigram_costs.add_result(mc));
@@ -416,6 +1114,44 @@ impl Evaluator {
             results.push(trigram_costs);
         }
 
+        // Quadgram metrics
+        if let Some(mapped_quadgrams) = mapped_quadgrams {
+            let metric_costs = self.evaluate_quadgram_metrics(layout, &mapped_quadgrams.grams);
+            let mut quadgram_costs = MetricResults::new(
+                MetricType::Quadgram,
+                mapped_quadgrams.weight_found,
+                mapped_quadgrams.weight_not_found,
+            )
+            .with_missing_symbols(mapped_quadgrams.missing_symbols.clone());
+            metric_costs
+                .into_iter()
+                .for_each(|mc| quadgram_costs.add_result(mc));
+
+            results.push(quadgram_costs);
+        }
+
+        // Skipgram metrics
+        if let Some(mapped_skipgrams) = mapped_skipgrams {
+            let metric_costs = self.evaluate_bigram_metrics(
+                &self.skipgram_metrics,
+                layout,
+                &mapped_skipgrams.grams,
+            );
+            let mut skipgram_costs = MetricResults::new(
+                MetricType::Skipgram,
+                mapped_skipgrams.weight_found,
+                mapped_skipgrams.weight_not_found,
+            )
+            .with_missing_symbols(mapped_skipgrams.missing_symbols.clone());
+            metric_costs
+                .into_iter()
+                .for_each(|mc| skipgram_costs.add_result(mc));
+
+            results.push(skipgram_costs);
+        }
+
         EvaluationResult::new(layout.as_text(), results)
+            .with_profile(self.profile.clone())
+            .with_skipped_metric_types(self.skipped_metric_types.clone())
     }
 }
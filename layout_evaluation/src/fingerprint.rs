@@ -0,0 +1,180 @@
+//! The `fingerprint` module computes fingerprints of the inputs of an evaluation (layout config,
+//! evaluation parameters, and ngram data). Evaluation results are only comparable if their
+//! fingerprints are equal, e.g. results computed in the browser and on a server.
+//!
+//! The fingerprints only depend on the content (not on the formatting of YAML files or on the
+//! order of ngrams in hashmaps) and are identical on all platforms, including wasm.
+
+use crate::ngrams::{Bigrams, Trigrams, Unigrams};
+
+use keyboard_layout::layout::{LayerModifiers, Layout};
+
+use anyhow::Result;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Stable 64-bit FNV-1a hasher (in contrast to the hashers of the standard library, the result
+/// is guaranteed to be the same across platforms and versions).
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // prefix with the length to keep consecutive writes unambiguous
+        for b in (bytes.len() as u64)
+            .to_le_bytes()
+            .iter()
+            .chain(bytes.iter())
+        {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Fingerprint of a YAML document (layout config or evaluation parameters). Comments and
+/// formatting do not matter.
+pub fn fingerprint_yaml(yaml: &str) -> Result<String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml)?;
+    let mut hasher = Fnv1a::new();
+    hasher.write(serde_yaml::to_string(&value)?.as_bytes());
+    Ok(hasher.finish())
+}
+
+fn write_weighted<K: Ord>(
+    hasher: &mut Fnv1a,
+    mut grams: Vec<(K, f64)>,
+    key_str: impl Fn(&K) -> String,
+) {
+    grams.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    hasher.write(&(grams.len() as u64).to_le_bytes());
+    grams.iter().for_each(|(k, w)| {
+        hasher.write(key_str(k).as_bytes());
+        hasher.write(&w.to_bits().to_le_bytes());
+    });
+}
+
+/// Fingerprint of the ngram data (as passed to the ngram mapper).
+pub fn fingerprint_ngrams(unigrams: &Unigrams, bigrams: &Bigrams, trigrams: &Trigrams) -> String {
+    let mut hasher = Fnv1a::new();
+    write_weighted(
+        &mut hasher,
+        unigrams.grams.iter().map(|(k, w)| (*k, *w)).collect(),
+        |c| c.to_string(),
+    );
+    write_weighted(
+        &mut hasher,
+        bigrams.grams.iter().map(|(k, w)| (*k, *w)).collect(),
+        |(c1, c2)| format!("{}{}", c1, c2),
+    );
+    write_weighted(
+        &mut hasher,
+        trigrams.grams.iter().map(|(k, w)| (*k, *w)).collect(),
+        |(c1, c2, c3)| format!("{}{}{}", c1, c2, c3),
+    );
+    hasher.finish()
+}
+
+/// Combine the fingerprints of the layout config, the evaluation parameters, and the ngram data
+/// into the fingerprint of an evaluation.
+pub fn combine(layout_config: &str, evaluation_parameters: &str, ngrams: &str) -> String {
+    let mut hasher = Fnv1a::new();
+    hasher.write(layout_config.as_bytes());
+    hasher.write(evaluation_parameters.as_bytes());
+    hasher.write(ngrams.as_bytes());
+    hasher.finish()
+}
+
+/// Fingerprint of a list of strings (e.g. identifiers and fingerprints of several documents).
+pub fn fingerprint_parts(parts: &[&str]) -> String {
+    let mut hasher = Fnv1a::new();
+    hasher.write(&(parts.len() as u64).to_le_bytes());
+    parts.iter().for_each(|part| hasher.write(part.as_bytes()));
+    hasher.finish()
+}
+
+/// Stable hash of a layout's key ordering: the symbol, layer, key (matrix position, hand, and
+/// finger), and modifiers of each of its [`LayerKey`](keyboard_layout::layout::LayerKey)s. Layouts
+/// generated from the same layout config have the same hash if (and only if, barring collisions)
+/// they place the same symbols on the same keys. The same layout string on keyboards with other
+/// finger assignments results in different hashes.
+pub fn layout_hash(layout: &Layout) -> u64 {
+    let mut hasher = Fnv1a::new();
+    layout.layerkeys.iter().for_each(|lk| {
+        let (modifier_type, modifiers) = match &lk.modifiers {
+            LayerModifiers::Hold(v) => (0, v.as_slice()),
+            LayerModifiers::OneShot(v) => (1, v.as_slice()),
+            LayerModifiers::LongPress => (2, [].as_slice()),
+            LayerModifiers::Lock(v) => (3, v.as_slice()),
+            LayerModifiers::Chord(v) => (4, v.as_slice()),
+        };
+        hasher.write(&(lk.symbol as u32).to_le_bytes());
+        hasher.write(&[
+            lk.layer,
+            layout.geometry().matrix_position(lk.key_index).0,
+            layout.geometry().matrix_position(lk.key_index).1,
+            layout.geometry().hand(lk.key_index) as u8,
+            layout.geometry().finger(lk.key_index) as u8,
+            lk.is_modifier as u8,
+            modifier_type,
+        ]);
+        let modifier_bytes: Vec<u8> = modifiers.iter().flat_map(|m| m.to_le_bytes()).collect();
+        hasher.write(&modifier_bytes);
+    });
+
+    hasher.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_formatting_does_not_matter() {
+        let a = fingerprint_yaml("a: 1\nb: [x, y]\n").unwrap();
+        let b = fingerprint_yaml("# comment\na:   1\nb:\n  - x\n  - y\n").unwrap();
+        let c = fingerprint_yaml("a: 2\nb: [x, y]\n").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn ngram_fingerprints_are_stable() {
+        let fp = |text: &str| {
+            fingerprint_ngrams(
+                &Unigrams::from_text(text).unwrap(),
+                &Bigrams::from_text(text).unwrap(),
+                &Trigrams::from_text(text).unwrap(),
+            )
+        };
+
+        assert_eq!(fp("hello world"), fp("hello world"));
+        assert_ne!(fp("hello world"), fp("hello word"));
+        // the value must not change between platforms or versions
+        assert_eq!(fp("abc"), "16b6acfd84593c91");
+    }
+
+    #[test]
+    fn parts_are_unambiguous() {
+        assert_eq!(
+            fingerprint_parts(&["ab", "c"]),
+            fingerprint_parts(&["ab", "c"])
+        );
+        assert_ne!(
+            fingerprint_parts(&["ab", "c"]),
+            fingerprint_parts(&["a", "bc"])
+        );
+        assert_ne!(
+            fingerprint_parts(&["ab", "c"]),
+            fingerprint_parts(&["c", "ab"])
+        );
+    }
+}
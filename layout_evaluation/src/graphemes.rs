@@ -0,0 +1,78 @@
+//! The `graphemes` module splits texts into extended grapheme clusters (user-perceived
+//! characters) according to [UAX #29](https://www.unicode.org/reports/tr29/). Emoji sequences
+//! (e.g. "👍🏽" or families joined by zero-width joiners) and e.g. Devanagari syllables consist
+//! of multiple `char`s, but only form one cluster.
+
+use anyhow::bail;
+use serde::Deserialize;
+use std::str::FromStr;
+use unicode_segmentation::{Graphemes, UnicodeSegmentation};
+
+/// How grapheme clusters consisting of multiple characters are treated when collecting ngrams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum GraphemeClusters {
+    /// Collect ngrams of characters, i.e. the characters of a cluster are separate elements of
+    /// the ngrams.
+    Chars,
+    /// Use the first character of each cluster as element of the ngrams (e.g. "👍" for "👍🏽").
+    FirstChar,
+    /// Drop clusters consisting of multiple characters. Ngrams containing them are not collected,
+    /// their weight is reported as skipped instead.
+    Skip,
+}
+
+impl Default for GraphemeClusters {
+    fn default() -> Self {
+        Self::Chars
+    }
+}
+
+impl FromStr for GraphemeClusters {
+    type Err = anyhow::Error;
+
+    /// Parse the mode from its kebab-case name ("chars", "first-char", or "skip").
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "chars" => Ok(Self::Chars),
+            "first-char" => Ok(Self::FirstChar),
+            "skip" => Ok(Self::Skip),
+            _ => bail!(
+                "Invalid grapheme cluster mode '{}' (expected 'chars', 'first-char', or 'skip')",
+                s
+            ),
+        }
+    }
+}
+
+/// Split a text into its extended grapheme clusters.
+pub fn clusters(text: &str) -> Graphemes<'_> {
+    text.graphemes(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        clusters(text).collect()
+    }
+
+    #[test]
+    fn splits_combining_sequences() {
+        assert_eq!(split("abc"), vec!["a", "b", "c"]);
+        assert_eq!(split("a\u{0308}b"), vec!["a\u{0308}", "b"]);
+        assert_eq!(split("\r\n\n"), vec!["\r\n", "\n"]);
+        // Hangul syllables from conjoining jamo
+        assert_eq!(
+            split("\u{1100}\u{1161}\u{11A8}가"),
+            vec!["\u{1100}\u{1161}\u{11A8}", "가"]
+        );
+        // Devanagari: consonants joined by a virama form a conjunct
+        assert_eq!(split("नमस्ते"), vec!["न", "म", "स्ते"]);
+    }
+
+    #[test]
+    fn splits_regional_indicators_in_pairs() {
+        assert_eq!(split("🇩🇪🇫🇷🇮"), vec!["🇩🇪", "🇫🇷", "🇮"]);
+    }
+}
@@ -0,0 +1,171 @@
+//! The `json_corpus` module reads ngram frequencies from JSON corpora as distributed for other
+//! layout analyzers (e.g. Oxeylyzer or genkey). Such a corpus is a single JSON object with a map
+//! per ngram order:
+//!
+//! ```json
+//! {
+//!   "characters": { "e": 1523, "n": 949, ... },
+//!   "bigrams": { "en": 356, "er": 332, ... },
+//!   "trigrams": { "en ": 201, "er ": 130, ... },
+//!   "skipgrams": { ... }
+//! }
+//! ```
+//!
+//! The keys are the ngrams' symbols (JSON escapes like `"\u00e4"` are resolved), the values
+//! their weights (absolute or relative). Further maps (e.g. `skipgrams`) are ignored, as
+//! skipgrams are derived from the trigrams (see
+//! [`NgramMapperConfig::skipgram_decay`](crate::ngram_mapper::on_demand_ngram_mapper::NgramMapperConfig::skipgram_decay)).
+
+use crate::ngram_mapper::common::NgramMap;
+use crate::ngrams::{Bigrams, Trigrams, Unigrams};
+use crate::results::MetricType;
+
+use ahash::AHashMap;
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{Map, Value};
+use std::{convert::TryInto, fs, hash::Hash, path::Path};
+
+/// Name of the map containing the unigrams
+pub const UNIGRAMS_KEY: &str = "characters";
+/// Name of the map containing the bigrams
+pub const BIGRAMS_KEY: &str = "bigrams";
+/// Name of the map containing the trigrams
+pub const TRIGRAMS_KEY: &str = "trigrams";
+
+/// Whether an ngram file is a JSON corpus, judging by its extension (`.json`) or its first
+/// non-whitespace character (`{`).
+pub fn is_json<T: AsRef<Path>>(filename: T, data: &str) -> bool {
+    has_json_extension(filename)
+        || data
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with('{')
+}
+
+/// Whether a file has the extension `.json`.
+pub fn has_json_extension<T: AsRef<Path>>(filename: T) -> bool {
+    filename
+        .as_ref()
+        .extension()
+        .map_or(false, |e| e.eq_ignore_ascii_case("json"))
+}
+
+/// Parse a JSON corpus into its top-level maps.
+pub fn parse(data: &str) -> Result<Map<String, Value>> {
+    let corpus: Value =
+        serde_json::from_str(data.trim_start_matches('\u{feff}')).context("Invalid JSON corpus")?;
+    match corpus {
+        Value::Object(maps) => Ok(maps),
+        _ => bail!("Invalid JSON corpus: expected an object with ngram maps at the top level"),
+    }
+}
+
+/// Read the ngrams of order `N` from the map `name` of a parsed JSON corpus. Returns `None` if
+/// the corpus does not contain the map.
+pub fn grams<K: Eq + Hash, const N: usize>(
+    corpus: &Map<String, Value>,
+    name: &str,
+    key: impl Fn([char; N]) -> K,
+) -> Result<Option<AHashMap<K, f64>>> {
+    let map = match corpus.get(name) {
+        Some(Value::Object(map)) => map,
+        Some(_) => bail!("Invalid JSON corpus: '{}' is not a map", name),
+        None => return Ok(None),
+    };
+
+    let mut grams = AHashMap::with_capacity(map.len());
+    for (symbols, weight) in map.iter() {
+        let chars: Vec<char> = symbols.chars().collect();
+        let chars: [char; N] = chars.as_slice().try_into().map_err(|_| {
+            anyhow!(
+                "Invalid key {:?} in the '{}' map of the JSON corpus: expected {} character(s), found {}",
+                symbols,
+                name,
+                N,
+                chars.len()
+            )
+        })?;
+        let weight = weight
+            .as_f64()
+            .filter(|w| w.is_finite() && *w >= 0.0)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid weight {} of {:?} in the '{}' map of the JSON corpus",
+                    weight,
+                    symbols,
+                    name
+                )
+            })?;
+        grams.insert_or_add_weight(key(chars), weight);
+    }
+
+    Ok(Some(grams))
+}
+
+/// Read the ngrams of order `N` from the map `name` of a JSON corpus, failing if the map is
+/// missing.
+pub fn required_grams<K: Eq + Hash, const N: usize>(
+    data: &str,
+    name: &str,
+    key: impl Fn([char; N]) -> K,
+) -> Result<AHashMap<K, f64>> {
+    let corpus = parse(data)?;
+    grams(&corpus, name, key)?.ok_or_else(|| {
+        anyhow!(
+            "The JSON corpus contains no '{}' map (found: {})",
+            name,
+            corpus.keys().cloned().collect::<Vec<_>>().join(", ")
+        )
+    })
+}
+
+/// Read unigrams, bigrams, and trigrams from a JSON corpus file.
+///
+/// As with ngram directories (see [`crate::ngrams::read_ngrams_dir`]), missing maps result in
+/// empty ngrams and the types of the metrics that can not be evaluated without them are
+/// returned. At least one of the maps has to exist.
+pub fn read_corpus<T: AsRef<Path>>(
+    filename: T,
+) -> Result<(Unigrams, Bigrams, Trigrams, Vec<MetricType>)> {
+    let filename = filename.as_ref();
+    log::info!("Reading JSON corpus: '{:?}'", filename);
+    let data = fs::read_to_string(filename)
+        .with_context(|| format!("Could not read JSON corpus '{:?}'", filename))?;
+    let corpus = parse(&data).with_context(|| format!("Could not parse '{:?}'", filename))?;
+
+    let mut missing = Vec::new();
+    let mut warn_missing = |name: &str, metric_type: MetricType| {
+        log::warn!(
+            "JSON corpus '{:?}' contains no '{}' map, skipping all {:?} metrics",
+            filename,
+            name,
+            metric_type
+        );
+        missing.push(metric_type);
+    };
+
+    let unigrams = grams(&corpus, UNIGRAMS_KEY, |[c]| c)?.unwrap_or_else(|| {
+        warn_missing(UNIGRAMS_KEY, MetricType::Unigram);
+        AHashMap::default()
+    });
+    let bigrams = grams(&corpus, BIGRAMS_KEY, |[c1, c2]| (c1, c2))?.unwrap_or_else(|| {
+        warn_missing(BIGRAMS_KEY, MetricType::Bigram);
+        AHashMap::default()
+    });
+    let trigrams =
+        grams(&corpus, TRIGRAMS_KEY, |[c1, c2, c3]| (c1, c2, c3))?.unwrap_or_else(|| {
+            warn_missing(TRIGRAMS_KEY, MetricType::Trigram);
+            AHashMap::default()
+        });
+
+    if missing.len() == 3 {
+        bail!("No ngram maps found in JSON corpus '{:?}'", filename);
+    }
+
+    Ok((
+        Unigrams { grams: unigrams },
+        Bigrams { grams: bigrams },
+        Trigrams { grams: trigrams },
+        missing,
+    ))
+}
@@ -0,0 +1,372 @@
+//! The `key_summary` module combines the costs of all metrics into costs of the physical keys
+//! of a layout. This answers the question which keys contribute most to a layout's total cost
+//! (and are therefore worth moving).
+//!
+//! Each metric's weighted cost is distributed onto keys according to the following rules:
+//! - Ngram metrics providing costs for individual ngrams: The cost of each ngram is split
+//!   equally among the keys of the ngram (a key occurring twice receives two shares).
+//!   Modifiers that are part of the mapped ngrams count as keys of their own.
+//! - Ngram metrics without (or with vanishing) individual costs: The cost is distributed in
+//!   proportion to the keys' shares of the ngram weight.
+//! - Layout metrics: The cost is distributed equally among the non-fixed keys.
+//!
+//! The individual costs of a metric are scaled such that they add up to the metric's weighted
+//! cost. Hence, the costs of all keys add up to the layout's total cost.
+
+use crate::evaluation::Evaluator;
+use crate::results::{EvaluationResult, MetricType};
+
+use keyboard_layout::{
+    key::MatrixPosition,
+    layout::{LayerKey, Layout},
+};
+
+use ahash::AHashMap;
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt;
+
+/// Number of contributing metrics to list for each key
+const N_LISTED_METRICS: usize = 3;
+
+/// Costs attributed to keys (before scaling to a metric's cost).
+#[derive(Clone, Debug, Default)]
+struct Attribution {
+    costs: AHashMap<MatrixPosition, f64>,
+    total: f64,
+}
+
+impl Attribution {
+    /// Split `cost` equally among `keys`.
+    fn add(&mut self, keys: &[&LayerKey], cost: f64) {
+        let share = cost / keys.len() as f64;
+        keys.iter().for_each(|k| {
+            *self.costs.entry(k.key.matrix_position).or_insert(0.0) += share;
+        });
+        self.total += cost;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.total.abs() < 1e-12
+    }
+}
+
+/// A metric's contribution to a key's cost.
+#[derive(Clone, Debug, Serialize)]
+pub struct MetricContribution {
+    pub name: String,
+    pub metric_type: MetricType,
+    pub cost: f64,
+}
+
+/// The combined cost of a physical key.
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyCost {
+    /// Symbol of the key's base layer
+    pub symbol: char,
+    pub position: MatrixPosition,
+    pub cost: f64,
+    /// Contributions of the metrics, sorted by cost (descending)
+    pub metrics: Vec<MetricContribution>,
+}
+
+/// The costs of a layout's keys (sorted by cost, descending).
+#[derive(Clone, Debug, Serialize)]
+pub struct KeySummary {
+    pub layout: String,
+    pub total_cost: f64,
+    pub keys: Vec<KeyCost>,
+    /// Combined cost of the keys that were dropped from `keys` (see [`KeySummary::top`])
+    pub other_keys_cost: f64,
+}
+
+impl KeySummary {
+    /// Keep only the `n_keys` keys with the highest costs.
+    pub fn top(mut self, n_keys: usize) -> Self {
+        if self.keys.len() > n_keys {
+            self.other_keys_cost += self.keys.drain(n_keys..).map(|k| k.cost).sum::<f64>();
+        }
+        self
+    }
+
+    /// Sum of the costs of all keys (including the dropped ones).
+    pub fn attributed_cost(&self) -> f64 {
+        self.keys.iter().map(|k| k.cost).sum::<f64>() + self.other_keys_cost
+    }
+}
+
+impl fmt::Display for KeySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", "Most costly keys:".bold())?;
+        for key in self.keys.iter() {
+            let metrics: Vec<String> = key
+                .metrics
+                .iter()
+                .take(N_LISTED_METRICS)
+                .map(|m| format!("{} {:.2}", m.name, m.cost))
+                .collect();
+            writeln!(
+                f,
+                "  {} {:<4} {:>5.1}% | {}",
+                format!("{:>7.2}", key.cost).green(),
+                format!("'{}'", key.symbol.escape_debug()).bold(),
+                100.0 * key.cost / self.total_cost,
+                metrics.join(", "),
+            )?;
+        }
+        if !self.keys.is_empty() && self.other_keys_cost != 0.0 {
+            writeln!(
+                f,
+                "  {} {:<4} {:>5.1}%",
+                format!("{:>7.2}", self.other_keys_cost).green(),
+                "rest",
+                100.0 * self.other_keys_cost / self.total_cost,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the costs of all keys and metrics.
+struct KeyCosts<'a> {
+    layout: &'a Layout,
+    keys: AHashMap<MatrixPosition, Vec<MetricContribution>>,
+}
+
+impl<'a> KeyCosts<'a> {
+    /// Distribute `weighted_cost` according to `attribution` (or the first non-empty fallback).
+    fn distribute(
+        &mut self,
+        name: &str,
+        metric_type: &MetricType,
+        weighted_cost: f64,
+        attributions: &[&Attribution],
+    ) {
+        if weighted_cost == 0.0 {
+            return;
+        }
+
+        let attribution = match attributions.iter().find(|a| !a.is_empty()) {
+            Some(a) => a,
+            None => return,
+        };
+        let factor = weighted_cost / attribution.total;
+        attribution.costs.iter().for_each(|(pos, c)| {
+            self.keys.entry(*pos).or_default().push(MetricContribution {
+                name: name.to_string(),
+                metric_type: metric_type.clone(),
+                cost: factor * c,
+            })
+        });
+    }
+
+    fn into_summary(self, total_cost: f64) -> KeySummary {
+        let symbols: AHashMap<MatrixPosition, char> = self
+            .layout
+            .layerkeys
+            .iter()
+            .filter(|lk| lk.layer == 0)
+            .map(|lk| (lk.key.matrix_position, lk.symbol))
+            .collect();
+
+        let mut keys: Vec<KeyCost> = self
+            .keys
+            .into_iter()
+            .map(|(position, mut metrics)| {
+                metrics.sort_by(|m1, m2| m2.cost.partial_cmp(&m1.cost).unwrap());
+                KeyCost {
+                    symbol: symbols.get(&position).cloned().unwrap_or(' '),
+                    position,
+                    cost: metrics.iter().map(|m| m.cost).sum(),
+                    metrics,
+                }
+            })
+            .collect();
+        keys.sort_by(|k1, k2| {
+            k2.cost
+                .partial_cmp(&k1.cost)
+                .unwrap()
+                .then((k1.position.1, k1.position.0).cmp(&(k2.position.1, k2.position.0)))
+        });
+
+        KeySummary {
+            layout: self.layout.as_text(),
+            total_cost,
+            keys,
+            other_keys_cost: 0.0,
+        }
+    }
+}
+
+/// The weighted costs of the metrics of the given type (in the order of the evaluator's metrics).
+fn weighted_costs(result: &EvaluationResult, metric_type: MetricType) -> Vec<f64> {
+    result
+        .iter()
+        .find(|mr| mr.metric_type == metric_type)
+        .map(|mr| mr.metric_costs.iter().map(|mc| mc.weighted_cost).collect())
+        .unwrap_or_default()
+}
+
+impl Evaluator {
+    /// Evaluate a layout and distribute the metrics' costs onto the layout's keys.
+    pub fn key_summary(&self, layout: &Layout) -> KeySummary {
+        let unigrams = self.map_unigrams_if_required(layout);
+        let bigrams = self.map_bigrams_if_required(layout);
+        let trigrams = self.map_trigrams_if_required(layout);
+        let quadgrams = self.map_quadgrams_if_required(layout);
+        let skipgrams = self.map_skipgrams_if_required(layout);
+        let result = self.evaluate_mapped_ngrams(
+            layout,
+            unigrams.as_ref(),
+            bigrams.as_ref(),
+            trigrams.as_ref(),
+            quadgrams.as_ref(),
+            skipgrams.as_ref(),
+        );
+
+        let mut key_costs = KeyCosts {
+            layout,
+            keys: AHashMap::default(),
+        };
+
+        // Layout metrics
+        let mut non_fixed = Attribution::default();
+        layout
+            .layerkeys
+            .iter()
+            .filter(|lk| lk.layer == 0 && !lk.is_fixed)
+            .for_each(|lk| non_fixed.add(&[lk], 1.0));
+        self.layout_metrics
+            .iter()
+            .zip(weighted_costs(&result, MetricType::Layout))
+            .for_each(|((_, _, metric), cost)| {
+                key_costs.distribute(metric.name(), &MetricType::Layout, cost, &[&non_fixed])
+            });
+
+        // Unigram metrics
+        if let Some(unigrams) = unigrams {
+            let grams = &unigrams.grams;
+            let total_weight: f64 = grams.iter().map(|(_, w)| w).sum();
+            let mut usage = Attribution::default();
+            grams.iter().for_each(|(k, w)| usage.add(&[k], *w));
+
+            self.unigram_metrics
+                .iter()
+                .zip(weighted_costs(&result, MetricType::Unigram))
+                .for_each(|((_, _, metric), cost)| {
+                    let mut individual = Attribution::default();
+                    grams.iter().for_each(|(k, w)| {
+                        if let Some(c) = metric.individual_cost(k, *w, total_weight, layout) {
+                            individual.add(&[k], c);
+                        }
+                    });
+                    key_costs.distribute(
+                        metric.name(),
+                        &MetricType::Unigram,
+                        cost,
+                        &[&individual, &usage, &non_fixed],
+                    );
+                });
+        }
+
+        // Bigram and skipgram metrics
+        for (mapped, metrics, metric_type) in [
+            (bigrams, &self.bigram_metrics, MetricType::Bigram),
+            (skipgrams, &self.skipgram_metrics, MetricType::Skipgram),
+        ] {
+            let grams = match &mapped {
+                Some(mapped) => &mapped.grams,
+                None => continue,
+            };
+            let total_weight: f64 = grams.iter().map(|(_, w)| w).sum();
+            let mut usage = Attribution::default();
+            grams
+                .iter()
+                .for_each(|((k1, k2), w)| usage.add(&[k1, k2], *w));
+
+            metrics
+                .iter()
+                .zip(weighted_costs(&result, metric_type.clone()))
+                .for_each(|((_, _, metric), cost)| {
+                    let mut individual = Attribution::default();
+                    grams.iter().for_each(|((k1, k2), w)| {
+                        if let Some(c) = metric.individual_cost(k1, k2, *w, total_weight, layout) {
+                            individual.add(&[k1, k2], c);
+                        }
+                    });
+                    key_costs.distribute(
+                        metric.name(),
+                        &metric_type,
+                        cost,
+                        &[&individual, &usage, &non_fixed],
+                    );
+                });
+        }
+
+        // Trigram metrics
+        if let Some(trigrams) = trigrams {
+            let grams = &trigrams.grams;
+            let total_weight: f64 = grams.iter().map(|(_, w)| w).sum();
+            let mut usage = Attribution::default();
+            grams
+                .iter()
+                .for_each(|((k1, k2, k3), w)| usage.add(&[k1, k2, k3], *w));
+
+            self.trigram_metrics
+                .iter()
+                .zip(weighted_costs(&result, MetricType::Trigram))
+                .for_each(|((_, _, metric), cost)| {
+                    let total_weight = match metric.includes_filtered_weight() {
+                        true => total_weight + trigrams.weight_filtered,
+                        false => total_weight,
+                    };
+                    let mut individual = Attribution::default();
+                    grams.iter().for_each(|((k1, k2, k3), w)| {
+                        if let Some(c) =
+                            metric.individual_cost(k1, k2, k3, *w, total_weight, layout)
+                        {
+                            individual.add(&[k1, k2, k3], c);
+                        }
+                    });
+                    key_costs.distribute(
+                        metric.name(),
+                        &MetricType::Trigram,
+                        cost,
+                        &[&individual, &usage, &non_fixed],
+                    );
+                });
+        }
+
+        // Quadgram metrics
+        if let Some(quadgrams) = quadgrams {
+            let grams = &quadgrams.grams;
+            let total_weight: f64 = grams.iter().map(|(_, w)| w).sum();
+            let mut usage = Attribution::default();
+            grams
+                .iter()
+                .for_each(|((k1, k2, k3, k4), w)| usage.add(&[k1, k2, k3, k4], *w));
+
+            self.quadgram_metrics
+                .iter()
+                .zip(weighted_costs(&result, MetricType::Quadgram))
+                .for_each(|((_, _, metric), cost)| {
+                    let mut individual = Attribution::default();
+                    grams.iter().for_each(|((k1, k2, k3, k4), w)| {
+                        if let Some(c) =
+                            metric.individual_cost(k1, k2, k3, k4, *w, total_weight, layout)
+                        {
+                            individual.add(&[k1, k2, k3, k4], c);
+                        }
+                    });
+                    key_costs.distribute(
+                        metric.name(),
+                        &MetricType::Quadgram,
+                        cost,
+                        &[&individual, &usage, &non_fixed],
+                    );
+                });
+        }
+
+        key_costs.into_summary(result.total_cost())
+    }
+}
@@ -1,10 +1,23 @@
+pub mod atomic_file;
 pub mod cache;
 pub mod config;
+pub mod corpus;
+pub mod differential;
 pub mod evaluation;
+pub mod fingerprint;
+pub mod graphemes;
+pub mod json_corpus;
+pub mod key_summary;
+pub mod memory;
 pub mod metrics;
+pub mod ngram_cache;
 pub mod ngram_mapper;
+pub mod ngram_stats;
 pub mod ngrams;
+pub mod normalization;
 pub mod results;
+pub mod robustness;
+pub mod token_filter;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,119 @@
+//! The `memory` module provides rough estimates of the memory used by the major data structures
+//! of an evaluation (ngram data, mapped ngrams, caches, and metrics).
+//!
+//! The estimates are computed from the lengths and capacities of the collections (instead of
+//! hooking into the allocator), so they work on stable Rust and do not slow down evaluations
+//! that do not request a report. Heap data referenced by the entries (e.g. strings) is only
+//! included where noted.
+
+use ahash::{AHashMap, AHashSet};
+use serde::Serialize;
+use std::{fmt, mem};
+
+/// Approximate number of bytes allocated by a hashmap (one control byte per bucket).
+pub fn hashmap_bytes<K, V>(map: &AHashMap<K, V>) -> usize {
+    map.capacity() * (mem::size_of::<(K, V)>() + 1)
+}
+
+/// Approximate number of bytes allocated by a hashset (one control byte per bucket).
+pub fn hashset_bytes<T>(set: &AHashSet<T>) -> usize {
+    set.capacity() * (mem::size_of::<T>() + 1)
+}
+
+/// Number of bytes allocated by a vec.
+pub fn vec_bytes<T>(v: &[T]) -> usize {
+    mem::size_of_val(v)
+}
+
+/// Size estimate of a single data structure.
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryItem {
+    /// Name of the data structure
+    pub name: String,
+    /// Number of entries (if it is a collection)
+    pub entries: Option<usize>,
+    /// Approximate number of bytes
+    pub bytes: usize,
+}
+
+/// A list of size estimates of the major data structures of an evaluation.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MemoryReport {
+    pub items: Vec<MemoryItem>,
+}
+
+impl MemoryReport {
+    /// Add the estimate of a collection.
+    pub fn add(&mut self, name: &str, entries: usize, bytes: usize) {
+        self.items.push(MemoryItem {
+            name: name.to_string(),
+            entries: Some(entries),
+            bytes,
+        });
+    }
+
+    /// Add the estimate of a data structure that is not a collection.
+    pub fn add_bytes(&mut self, name: &str, bytes: usize) {
+        self.items.push(MemoryItem {
+            name: name.to_string(),
+            entries: None,
+            bytes,
+        });
+    }
+
+    /// Sum of all estimates
+    pub fn total_bytes(&self) -> usize {
+        self.items.iter().map(|item| item.bytes).sum()
+    }
+}
+
+/// Format a number of bytes with a binary unit prefix.
+fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in self.items.iter() {
+            let entries = item.entries.map(|e| e.to_string()).unwrap_or_default();
+            writeln!(
+                f,
+                "  {:<55} {:>10} {:>12}",
+                item.name,
+                entries,
+                format_bytes(item.bytes)
+            )?;
+        }
+        write!(
+            f,
+            "  {:<55} {:>10} {:>12}",
+            "Total",
+            "",
+            format_bytes(self.total_bytes())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_are_formatted_with_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
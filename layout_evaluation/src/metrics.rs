@@ -1,6 +1,7 @@
-//! The `metrics` module provides traits for layout, unigram, bigram, and trigram metrics.
+//! The `metrics` module provides traits for layout, unigram, bigram, trigram, and quadgram metrics.
 
 pub mod bigram_metrics;
 pub mod layout_metrics;
+pub mod quadgram_metrics;
 pub mod trigram_metrics;
 pub mod unigram_metrics;
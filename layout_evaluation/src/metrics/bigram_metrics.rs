@@ -1,27 +1,67 @@
 //! The `metrics` module provides a trait for bigram metrics.
-use keyboard_layout::layout::{LayerKey, Layout};
+use keyboard_layout::{
+    key::{Finger, Hand, Key},
+    layout::{LayerKey, Layout},
+};
 
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
 use std::{env, fmt};
 
 pub mod finger_repeats;
+pub mod finger_travel_distance;
 pub mod kla_distance;
 pub mod kla_finger_usage;
 pub mod kla_same_finger;
 pub mod kla_same_hand;
+pub mod lateral_stretch_bigrams;
 pub mod manual_bigram_penalty;
 pub mod movement_pattern;
 pub mod no_handswitch_after_unbalancing_key;
 pub mod oxey_lsbs;
 pub mod oxey_sfbs;
+pub mod same_hand_mod_tap;
 pub mod symmetric_handswitches;
 
+/// The lowest cost of a bigram among the possible assignments of fingers to its keys. Keys with an
+/// [`alternate_finger`](Key::alternate_finger) may also be pressed with that finger, which adds
+/// `penalty` to the cost for each key pressed with its alternate finger.
+#[inline(always)]
+pub(crate) fn cheapest_finger_assignment<F>(k1: &Key, k2: &Key, penalty: f64, cost: F) -> f64
+where
+    F: Fn((Hand, Finger), (Hand, Finger)) -> f64,
+{
+    let primary = cost((k1.hand, k1.finger), (k2.hand, k2.finger));
+    if k1.alternate_finger.is_none() && k2.alternate_finger.is_none() {
+        return primary;
+    }
+
+    let alternates = |k: &Key| k.alternate_finger.map(|hf| (hf, penalty));
+    let fingers1 = Some(((k1.hand, k1.finger), 0.0))
+        .into_iter()
+        .chain(alternates(k1));
+    fingers1
+        .flat_map(|(hf1, p1)| {
+            Some(((k2.hand, k2.finger), 0.0))
+                .into_iter()
+                .chain(alternates(k2))
+                .map(move |(hf2, p2)| (hf1, hf2, p1 + p2))
+        })
+        .skip(1)
+        .map(|(hf1, hf2, p)| cost(hf1, hf2) + p)
+        .fold(primary, f64::min)
+}
+
 /// BigramMetric is a trait for metrics that iterates over weighted bigrams.
 pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
     /// Return the name of the metric.
     fn name(&self) -> &str;
 
+    /// Approximate number of bytes of the data held by the metric (e.g. lookup tables).
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
     /// Compute the cost of one bigram (if that is possible, otherwise, return `None`).
     #[inline(always)]
     fn individual_cost(
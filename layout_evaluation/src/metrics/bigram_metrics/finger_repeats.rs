@@ -1,14 +1,16 @@
 //! The bigram metric [`FingerRepeats`] incurrs a cost for bigram that uses the same finger
 //! for different keys (thumb excluded). If the finger is the index, the cost may be multiplied
-//! with a configurable factor (usually lessening the cost).
+//! with a configurable factor (usually lessening the cost). The cost is divided by the finger's
+//! capability given by the keyboard. Keys that may be pressed with an alternate finger are
+//! assigned to the finger resulting in the lower cost (plus a penalty for using the alternate).
 //!
 //! *Note:* In contrast to ArneBab's version of the metric, thumbs are excluded.
 
-use super::BigramMetric;
+use super::{cheapest_finger_assignment, BigramMetric};
 
 use ahash::AHashMap;
 use keyboard_layout::{
-    key::{Finger, FingerMap, Hand},
+    key::{Finger, FingerMap, Hand, Key},
     layout::{LayerKey, Layout},
 };
 
@@ -16,11 +18,16 @@ use serde::Deserialize;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {
+    /// Factors per finger (default 1.0) in addition to the keyboard's finger capabilities
+    #[serde(default)]
     pub finger_factors: AHashMap<Finger, f64>,
     pub stretch_factor: f64,
     pub curl_factor: f64,
     pub lateral_factor: f64,
     pub same_key_offset: f64,
+    /// Cost to add for each key that is pressed with its alternate finger
+    #[serde(default)]
+    pub alternate_finger_penalty: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -30,6 +37,7 @@ pub struct FingerRepeats {
     curl_factor: f64,
     lateral_factor: f64,
     same_key_offset: f64,
+    alternate_finger_penalty: f64,
 }
 
 impl FingerRepeats {
@@ -40,43 +48,36 @@ impl FingerRepeats {
             curl_factor: params.curl_factor,
             lateral_factor: params.lateral_factor,
             same_key_offset: params.same_key_offset,
+            alternate_finger_penalty: params.alternate_finger_penalty,
         }
     }
-}
-
-impl BigramMetric for FingerRepeats {
-    fn name(&self) -> &str {
-        "Finger Repeats"
-    }
 
+    /// The cost of a bigram whose keys are pressed with the given hands and fingers
     #[inline(always)]
-    fn individual_cost(
+    fn repeat_cost(
         &self,
-        k1: &LayerKey,
-        k2: &LayerKey,
-        weight: f64,
-        _total_weight: f64,
-        _layout: &Layout,
-    ) -> Option<f64> {
-        if (k1 == k2 && k1.is_modifier.is_some())
-            || k1.key.hand != k2.key.hand
-            || k1.key.finger != k2.key.finger
-        {
-            return Some(0.0);
+        key1: &Key,
+        key2: &Key,
+        (hand1, finger1): (Hand, Finger),
+        (hand2, finger2): (Hand, Finger),
+        layout: &Layout,
+    ) -> f64 {
+        if hand1 != hand2 || finger1 != finger2 {
+            return 0.0;
         }
 
-        let pos1 = k1.key.matrix_position;
-        let pos2 = k2.key.matrix_position;
-        let is_thumb: bool = k1.key.finger == Finger::Thumb;
+        let pos1 = key1.matrix_position;
+        let pos2 = key2.matrix_position;
+        let is_thumb: bool = finger1 == Finger::Thumb;
 
         let upwards: bool = pos2.1 < pos1.1;
         let downwards: bool = pos2.1 > pos1.1;
-        let inwards: bool = if k1.key.hand == Hand::Left {
+        let inwards: bool = if hand1 == Hand::Left {
             pos1.0 < pos2.0
         } else {
             pos1.0 > pos2.0
         };
-        let outwards: bool = if k1.key.hand == Hand::Left {
+        let outwards: bool = if hand1 == Hand::Left {
             pos1.0 > pos2.0
         } else {
             pos1.0 < pos2.0
@@ -101,12 +102,40 @@ impl BigramMetric for FingerRepeats {
             1.0
         };
 
-        let finger_factor = self.finger_factors.get(&k1.key.finger);
+        let finger_factor = self.finger_factors.get(&finger1)
+            / layout.keyboard.finger_capabilities.get(&hand1, &finger1);
 
-        let cost = finger_factor
+        finger_factor
             * (self.same_key_offset
                 + direction_factor * dist_in_line
-                + self.lateral_factor * dist_lateral);
+                + self.lateral_factor * dist_lateral)
+    }
+}
+
+impl BigramMetric for FingerRepeats {
+    fn name(&self) -> &str {
+        "Finger Repeats"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if k1 == k2 && k1.is_modifier.is_some() {
+            return Some(0.0);
+        }
+
+        let cost = cheapest_finger_assignment(
+            &k1.key,
+            &k2.key,
+            self.alternate_finger_penalty,
+            |hf1, hf2| self.repeat_cost(&k1.key, &k2.key, hf1, hf2, layout),
+        );
 
         Some(weight * cost)
     }
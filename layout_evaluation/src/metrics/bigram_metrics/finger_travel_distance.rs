@@ -0,0 +1,68 @@
+//! The bigram metric [`FingerTravelDistance`] sums the distances (in millimeters) a finger
+//! travels between the two keys of same-finger bigrams, based on the physical positions of the
+//! keys (see [`PhysicalPosition`](keyboard_layout::key::PhysicalPosition)). Wide keys shorten the
+//! distance, as they can be hit off-center.
+//!
+//! The distances are multiplied with a per-finger speed factor (e.g. higher for slow fingers).
+
+use super::BigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Finger, FingerMap},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Factors to multiply a finger's distances with (default: 1.0)
+    #[serde(default)]
+    pub finger_speed_factors: AHashMap<Finger, f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FingerTravelDistance {
+    finger_speed_factors: FingerMap<f64>,
+}
+
+impl FingerTravelDistance {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            finger_speed_factors: FingerMap::with_hashmap(&params.finger_speed_factors, 1.0),
+        }
+    }
+}
+
+impl BigramMetric for FingerTravelDistance {
+    fn name(&self) -> &str {
+        "Finger Travel Distance"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if layout.geometry().hand(k1.key_index) != layout.geometry().hand(k2.key_index)
+            || layout.geometry().finger(k1.key_index) != layout.geometry().finger(k2.key_index)
+        {
+            return Some(0.0);
+        }
+
+        let distance = k1.key.physical_distance(&k2.key);
+
+        Some(
+            weight
+                * distance
+                * self
+                    .finger_speed_factors
+                    .get(&layout.geometry().finger(k1.key_index)),
+        )
+    }
+}
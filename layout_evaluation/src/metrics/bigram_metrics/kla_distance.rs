@@ -4,6 +4,7 @@ use super::BigramMetric;
 
 use ahash::AHashMap;
 use keyboard_layout::{
+    geometry::KeyGeometry,
     key::{Finger, Hand, HandFingerMap, HandMap, Position},
     layout::{LayerKey, Layout},
 };
@@ -61,12 +62,16 @@ impl<'a> FingerStates<'a> {
     }
 
     #[inline(always)]
-    fn register_key(&mut self, k: &'a LayerKey) {
-        self.0.set(&k.key.hand, &k.key.finger, KeyUsage::Used(k));
+    fn register_key(&mut self, geometry: &KeyGeometry, k: &'a LayerKey) {
+        self.0.set(
+            &geometry.hand(k.key_index),
+            &geometry.finger(k.key_index),
+            KeyUsage::Used(k),
+        );
     }
 
     #[inline(always)]
-    pub fn iter(&self) -> slice::Iter<'_, KeyUsage> {
+    pub fn iter(&self) -> slice::Iter<'_, KeyUsage<'a>> {
         self.0.iter()
     }
 }
@@ -90,19 +95,19 @@ impl BigramMetric for KLADistance {
         bigrams.iter().for_each(|((prev_key, curr_key), weight)| {
             // collect used fingers and keys for previous symbol
             let mut prev_used_keys = finger_resting_positions;
-            prev_used_keys.register_key(prev_key);
+            prev_used_keys.register_key(layout.geometry(), prev_key);
             if !self.ignore_modifiers {
                 prev_key.modifiers.layerkey_indices().iter().for_each(|k| {
-                    prev_used_keys.register_key(layout.get_layerkey(k));
+                    prev_used_keys.register_key(layout.geometry(), layout.get_layerkey(k));
                 });
             }
 
             // collect used fingers and keys for currend symbol
             let mut curr_used_keys = finger_resting_positions;
-            curr_used_keys.register_key(curr_key);
+            curr_used_keys.register_key(layout.geometry(), curr_key);
             if !self.ignore_modifiers {
                 curr_key.modifiers.layerkey_indices().iter().for_each(|k| {
-                    curr_used_keys.register_key(layout.get_layerkey(k));
+                    curr_used_keys.register_key(layout.geometry(), layout.get_layerkey(k));
                 });
             }
 
@@ -116,29 +121,42 @@ impl BigramMetric for KLADistance {
 
                         // move previously idle finger to key press it
                         (KeyUsage::Idle(prev_pos), KeyUsage::Used(curr_key)) => {
-                            let dist = prev_pos.distance(&curr_key.key.position)
+                            let dist = prev_pos
+                                .distance(&layout.geometry().coord(curr_key.key_index))
                                 + self.keydown_distance
                                 + self.keyup_distance;
-                            *finger_values.get_mut(&curr_key.key.hand, &curr_key.key.finger) +=
-                                dist * weight;
+                            *finger_values.get_mut(
+                                &layout.geometry().hand(curr_key.key_index),
+                                &layout.geometry().finger(curr_key.key_index),
+                            ) += dist * weight;
                         }
 
                         // return finger from previous key press to home row
                         (KeyUsage::Used(prev_key), KeyUsage::Idle(curr_pos)) => {
-                            let dist = prev_key.key.position.distance(curr_pos);
-                            *finger_values.get_mut(&prev_key.key.hand, &prev_key.key.finger) +=
-                                dist * weight;
+                            let dist = layout
+                                .geometry()
+                                .coord(prev_key.key_index)
+                                .distance(curr_pos);
+                            *finger_values.get_mut(
+                                &layout.geometry().hand(prev_key.key_index),
+                                &layout.geometry().finger(prev_key.key_index),
+                            ) += dist * weight;
                         }
 
                         // move finger from previous keypress to key and press it (same finger activation)
                         (KeyUsage::Used(prev_key), KeyUsage::Used(curr_key)) => {
                             // if both keys are identical and are mods it is a hold -> no cost
                             if !(prev_key == curr_key && curr_key.is_modifier.is_some()) {
-                                let dist = curr_key.key.position.distance(&prev_key.key.position)
+                                let dist = layout
+                                    .geometry()
+                                    .coord(curr_key.key_index)
+                                    .distance(&layout.geometry().coord(prev_key.key_index))
                                     + self.keydown_distance
                                     + self.keyup_distance;
-                                *finger_values.get_mut(&curr_key.key.hand, &curr_key.key.finger) +=
-                                    dist * weight;
+                                *finger_values.get_mut(
+                                    &layout.geometry().hand(curr_key.key_index),
+                                    &layout.geometry().finger(curr_key.key_index),
+                                ) += dist * weight;
                             }
                         }
                     };
@@ -46,7 +46,10 @@ impl BigramMetric for KLAFingerUsage {
         let mut finger_values: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
         bigrams.iter().for_each(|((prev_key, curr_key), weight)| {
-            *finger_values.get_mut(&curr_key.key.hand, &curr_key.key.finger) += *weight;
+            *finger_values.get_mut(
+                &layout.geometry().hand(curr_key.key_index),
+                &layout.geometry().finger(curr_key.key_index),
+            ) += *weight;
 
             if !self.ignore_modifiers {
                 let prev_mods: AHashSet<LayerKeyIndex> = prev_key
@@ -66,8 +69,12 @@ impl BigramMetric for KLAFingerUsage {
                     .difference(&prev_mods)
                     .map(|k| layout.get_layerkey(k));
 
-                pressed_mods
-                    .for_each(|k| *finger_values.get_mut(&k.key.hand, &k.key.finger) += *weight);
+                pressed_mods.for_each(|k| {
+                    *finger_values.get_mut(
+                        &layout.geometry().hand(k.key_index),
+                        &layout.geometry().finger(k.key_index),
+                    ) += *weight
+                });
             }
         });
 
@@ -49,27 +49,47 @@ impl BigramMetric for KLASameFinger {
             // collect used fingers and keys for previous symbol
             let mut prev_keys_per_finger: HandFingerMap<Option<&LayerKey>> =
                 HandFingerMap::with_default(None);
-            prev_keys_per_finger.set(&prev_key.key.hand, &prev_key.key.finger, Some(prev_key));
+            prev_keys_per_finger.set(
+                &layout.geometry().hand(prev_key.key_index),
+                &layout.geometry().finger(prev_key.key_index),
+                Some(prev_key),
+            );
             if !self.ignore_modifiers {
                 prev_key
                     .modifiers
                     .layerkey_indices()
                     .iter()
                     .map(|k| layout.get_layerkey(k))
-                    .for_each(|k| prev_keys_per_finger.set(&k.key.hand, &k.key.finger, Some(k)));
+                    .for_each(|k| {
+                        prev_keys_per_finger.set(
+                            &layout.geometry().hand(k.key_index),
+                            &layout.geometry().finger(k.key_index),
+                            Some(k),
+                        )
+                    });
             }
 
             // collect used fingers and keys for current symbol
             let mut curr_keys_per_finger: HandFingerMap<Option<&LayerKey>> =
                 HandFingerMap::with_default(None);
-            curr_keys_per_finger.set(&curr_key.key.hand, &curr_key.key.finger, Some(curr_key));
+            curr_keys_per_finger.set(
+                &layout.geometry().hand(curr_key.key_index),
+                &layout.geometry().finger(curr_key.key_index),
+                Some(curr_key),
+            );
             if !self.ignore_modifiers {
                 curr_key
                     .modifiers
                     .layerkey_indices()
                     .iter()
                     .map(|k| layout.get_layerkey(k))
-                    .for_each(|k| curr_keys_per_finger.set(&k.key.hand, &k.key.finger, Some(k)));
+                    .for_each(|k| {
+                        curr_keys_per_finger.set(
+                            &layout.geometry().hand(k.key_index),
+                            &layout.geometry().finger(k.key_index),
+                            Some(k),
+                        )
+                    });
             }
 
             // check for same finger activations
@@ -85,9 +105,10 @@ impl BigramMetric for KLASameFinger {
                         // if both keys are identical and are mods it is a hold -> no cost
                         if !(prev_used_key == curr_used_key && curr_used_key.is_modifier.is_some())
                         {
-                            *finger_values
-                                .get_mut(&curr_used_key.key.hand, &curr_used_key.key.finger) +=
-                                *weight;
+                            *finger_values.get_mut(
+                                &layout.geometry().hand(curr_used_key.key_index),
+                                &layout.geometry().finger(curr_used_key.key_index),
+                            ) += *weight;
                         }
                     }
                 });
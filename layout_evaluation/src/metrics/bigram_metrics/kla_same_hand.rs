@@ -2,7 +2,7 @@ use super::BigramMetric;
 
 use ahash::AHashMap;
 use keyboard_layout::{
-    key::{Finger, Hand, HandMap},
+    key::{Hand, HandMap},
     layout::{LayerKey, Layout},
 };
 
@@ -47,8 +47,8 @@ impl BigramMetric for KLASameHand {
 
         bigrams.iter().for_each(|((prev_key, curr_key), weight)| {
             let mut prev_hands_used: HandMap<bool> = HandMap::with_default(false);
-            if !(self.ignore_thumbs && prev_key.key.finger == Finger::Thumb) {
-                prev_hands_used.set(&prev_key.key.hand, true);
+            if !(self.ignore_thumbs && layout.geometry().is_thumb(prev_key.key_index)) {
+                prev_hands_used.set(&layout.geometry().hand(prev_key.key_index), true);
             }
             if !self.ignore_modifiers {
                 prev_key
@@ -56,12 +56,12 @@ impl BigramMetric for KLASameHand {
                     .layerkey_indices()
                     .iter()
                     .map(|k| layout.get_layerkey(k))
-                    .for_each(|k| prev_hands_used.set(&k.key.hand, true));
+                    .for_each(|k| prev_hands_used.set(&layout.geometry().hand(k.key_index), true));
             }
 
             let mut curr_hands_used: HandMap<bool> = HandMap::with_default(false);
-            if !(self.ignore_thumbs && curr_key.key.finger == Finger::Thumb) {
-                curr_hands_used.set(&curr_key.key.hand, true);
+            if !(self.ignore_thumbs && layout.geometry().is_thumb(curr_key.key_index)) {
+                curr_hands_used.set(&layout.geometry().hand(curr_key.key_index), true);
             }
             if !self.ignore_modifiers {
                 curr_key
@@ -69,7 +69,7 @@ impl BigramMetric for KLASameHand {
                     .layerkey_indices()
                     .iter()
                     .map(|k| layout.get_layerkey(k))
-                    .for_each(|k| curr_hands_used.set(&k.key.hand, true));
+                    .for_each(|k| curr_hands_used.set(&layout.geometry().hand(k.key_index), true));
             }
 
             // check consecutive hands
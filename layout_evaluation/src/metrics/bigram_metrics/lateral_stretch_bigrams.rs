@@ -0,0 +1,104 @@
+//! The bigram metric [`LateralStretchBigrams`] penalizes bigrams of the same hand whose keys are
+//! pressed with adjacent fingers, but lie two or more columns apart (e.g. the index finger on the
+//! center column followed by the middle finger). The cost of each such bigram is its weight
+//! times the weight of the finger pair and the column distance to the power of a configurable
+//! exponent.
+
+use super::{cheapest_finger_assignment, BigramMetric};
+
+use keyboard_layout::{
+    key::{Finger, Hand, Key},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Deserialize, Debug)]
+pub struct FingerPairWeight {
+    /// Adjacent fingers (in either order, on both hands)
+    pub fingers: (Finger, Finger),
+    pub weight: f64,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Weights of the pairs of adjacent fingers (unlisted pairs are not penalized)
+    pub finger_pair_weights: Vec<FingerPairWeight>,
+    /// The column distance of the keys is raised to this power
+    pub distance_exponent: f64,
+    /// Cost to add for each key that is pressed with its alternate finger
+    #[serde(default)]
+    pub alternate_finger_penalty: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LateralStretchBigrams {
+    /// Weight for each pair of fingers (indexed by the fingers' numbers)
+    finger_pair_weights: [[f64; 5]; 5],
+    distance_exponent: f64,
+    alternate_finger_penalty: f64,
+}
+
+impl LateralStretchBigrams {
+    pub fn new(params: &Parameters) -> Self {
+        let mut finger_pair_weights = [[0.0; 5]; 5];
+        params.finger_pair_weights.iter().for_each(|fpw| {
+            let (f1, f2) = (fpw.fingers.0 as usize, fpw.fingers.1 as usize);
+            finger_pair_weights[f1][f2] = fpw.weight;
+            finger_pair_weights[f2][f1] = fpw.weight;
+        });
+
+        Self {
+            finger_pair_weights,
+            distance_exponent: params.distance_exponent,
+            alternate_finger_penalty: params.alternate_finger_penalty,
+        }
+    }
+
+    /// The cost of a bigram whose keys are pressed with the given hands and fingers
+    #[inline(always)]
+    fn stretch_cost(
+        &self,
+        key1: &Key,
+        key2: &Key,
+        (h1, f1): (Hand, Finger),
+        (h2, f2): (Hand, Finger),
+    ) -> f64 {
+        if h1 != h2 || f1.distance(&f2) != 1 {
+            return 0.0;
+        }
+
+        let distance = key1.matrix_position.0.abs_diff(key2.matrix_position.0);
+        if distance < 2 {
+            return 0.0;
+        }
+
+        self.finger_pair_weights[f1 as usize][f2 as usize]
+            * (distance as f64).powf(self.distance_exponent)
+    }
+}
+
+impl BigramMetric for LateralStretchBigrams {
+    fn name(&self) -> &str {
+        "Lateral Stretch Bigrams"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        let cost = cheapest_finger_assignment(
+            &k1.key,
+            &k2.key,
+            self.alternate_finger_penalty,
+            |hf1, hf2| self.stretch_cost(&k1.key, &k2.key, hf1, hf2),
+        );
+
+        Some(weight * cost)
+    }
+}
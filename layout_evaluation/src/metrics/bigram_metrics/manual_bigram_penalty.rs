@@ -3,6 +3,8 @@
 
 use super::BigramMetric;
 
+use crate::memory::hashmap_bytes;
+
 use keyboard_layout::layout::{LayerKey, Layout};
 
 use ahash::AHashMap;
@@ -45,6 +47,10 @@ impl BigramMetric for ManualBigramPenalty {
         "Manual Bigram Penalty"
     }
 
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self) + hashmap_bytes(&self.matrix_positions)
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -52,12 +58,12 @@ impl BigramMetric for ManualBigramPenalty {
         k2: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        let x1 = k1.key.matrix_position.0;
-        let y1 = k1.key.matrix_position.1;
-        let x2 = k2.key.matrix_position.0;
-        let y2 = k2.key.matrix_position.1;
+        let x1 = layout.geometry().column(k1.key_index);
+        let y1 = layout.geometry().row(k1.key_index);
+        let x2 = layout.geometry().column(k2.key_index);
+        let y2 = layout.geometry().row(k2.key_index);
 
         if let Some(val) = self.matrix_positions.get(&((x1, y1), (x2, y2))) {
             return Some(weight * *val);
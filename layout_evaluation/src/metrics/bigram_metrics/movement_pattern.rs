@@ -1,12 +1,14 @@
 //! The bigram metric [`MovementPattern`] puts cost on each bigram that is mapped to
 //! (almost) neighboring fingers. Which finger combinations come with which costs is
-//! configurable.
+//! configurable. The cost is divided by the capability (given by the keyboard) of the weaker
+//! of both fingers. Keys that may be pressed with an alternate finger are assigned to the finger
+//! resulting in the lower cost (plus a penalty for using the alternate).
 
-use super::BigramMetric;
+use super::{cheapest_finger_assignment, BigramMetric};
 
 use ahash::AHashMap;
 use keyboard_layout::{
-    key::{Finger, Hand, HandFingerMap},
+    key::{Finger, Hand, HandFingerMap, Key},
     layout::{LayerKey, Layout},
 };
 
@@ -28,6 +30,9 @@ pub struct Parameters {
     same_row_offset: f64,
     unbalancing_factor: f64,
     lateral_stretch_factor: f64,
+    /// Cost to add for each key that is pressed with its alternate finger
+    #[serde(default)]
+    alternate_finger_penalty: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -38,6 +43,7 @@ pub struct MovementPattern {
     same_row_offset: f64,
     unbalancing_factor: f64,
     lateral_stretch_factor: f64,
+    alternate_finger_penalty: f64,
 }
 
 impl MovementPattern {
@@ -58,35 +64,26 @@ impl MovementPattern {
             same_row_offset: params.same_row_offset,
             unbalancing_factor: params.unbalancing_factor,
             lateral_stretch_factor: params.lateral_stretch_factor,
+            alternate_finger_penalty: params.alternate_finger_penalty,
         }
     }
-}
-
-impl BigramMetric for MovementPattern {
-    fn name(&self) -> &str {
-        "Movement Pattern"
-    }
 
+    /// The cost of a bigram whose keys are pressed with the given hands and fingers
     #[inline(always)]
-    fn individual_cost(
+    fn movement_cost(
         &self,
-        k1: &LayerKey,
-        k2: &LayerKey,
-        weight: f64,
-        _total_weight: f64,
-        _layout: &Layout,
-    ) -> Option<f64> {
-        let f1 = k1.key.finger;
-        let f2 = k2.key.finger;
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
-
+        key1: &Key,
+        key2: &Key,
+        (h1, f1): (Hand, Finger),
+        (h2, f2): (Hand, Finger),
+        layout: &Layout,
+    ) -> f64 {
         if f1 == Finger::Thumb || f2 == Finger::Thumb || h1 != h2 || f1 == f2 {
-            return Some(0.0);
+            return 0.0;
         }
 
-        let pos1 = k1.key.matrix_position;
-        let pos2 = k2.key.matrix_position;
+        let pos1 = key1.matrix_position;
+        let pos2 = key2.matrix_position;
 
         let upwards: bool = pos2.1 < pos1.1;
         let downwards: bool = pos2.1 > pos1.1;
@@ -107,20 +104,45 @@ impl BigramMetric for MovementPattern {
 
         let unbalancing_factor = 1.0
             + (self.unbalancing_factor
-                * ((k1.key.unbalancing.0 - k2.key.unbalancing.0).abs()
-                    + (k1.key.unbalancing.1 - k2.key.unbalancing.1).abs()));
+                * ((key1.unbalancing.0 - key2.unbalancing.0).abs()
+                    + (key1.unbalancing.1 - key2.unbalancing.1).abs()));
 
         let lateral_stretch_factor = 1.0
-            + (f1.distance(&f2))
-                .abs_diff(k1.key.matrix_position.0.abs_diff(k2.key.matrix_position.0))
-                as f64
+            + (f1.distance(&f2)).abs_diff(pos1.0.abs_diff(pos2.0)) as f64
                 * self.lateral_stretch_factor;
 
-        let cost = (self.same_row_offset + num_rows * num_rows)
+        let capabilities = &layout.keyboard.finger_capabilities;
+        let capability = capabilities.get(&h1, &f1).min(*capabilities.get(&h2, &f2));
+
+        (self.same_row_offset + num_rows * num_rows)
             * finger_switch_factor
             * direction_factor
             * unbalancing_factor
-            * lateral_stretch_factor;
+            * lateral_stretch_factor
+            / capability
+    }
+}
+
+impl BigramMetric for MovementPattern {
+    fn name(&self) -> &str {
+        "Movement Pattern"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        let cost = cheapest_finger_assignment(
+            &k1.key,
+            &k2.key,
+            self.alternate_finger_penalty,
+            |hf1, hf2| self.movement_cost(&k1.key, &k2.key, hf1, hf2, layout),
+        );
 
         Some(weight * cost)
     }
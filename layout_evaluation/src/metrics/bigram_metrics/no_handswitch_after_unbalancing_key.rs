@@ -5,10 +5,7 @@
 
 use super::BigramMetric;
 
-use keyboard_layout::{
-    key::Finger,
-    layout::{LayerKey, Layout},
-};
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
 
@@ -36,11 +33,11 @@ impl BigramMetric for NoHandSwitchAfterUnbalancingKey {
         k2: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        if k1.key.hand != k2.key.hand  // or handswitch occurred -> no cost
-            || k1.key.finger == Finger::Thumb  // or one finger was a thumb -> no cost
-            || k2.key.finger == Finger::Thumb
+        if layout.geometry().hand(k1.key_index) != layout.geometry().hand(k2.key_index)  // or handswitch occurred -> no cost
+            || layout.geometry().is_thumb(k1.key_index)  // or one finger was a thumb -> no cost
+            || layout.geometry().is_thumb(k2.key_index)
         // or other finger was a thumb -> no cost
         {
             return Some(0.0);
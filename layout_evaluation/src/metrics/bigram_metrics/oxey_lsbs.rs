@@ -44,7 +44,7 @@ impl BigramMetric for OxeyLsbs {
         k2: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
         if self.exclude_modifiers && (k1.is_modifier.is_some() || k2.is_modifier.is_some()) {
             return Some(0.0);
@@ -59,21 +59,26 @@ impl BigramMetric for OxeyLsbs {
         if k1 == k2 {
             return Some(0.0);
         }
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h2 = layout.geometry().hand(k2.key_index);
 
         if h1 != h2 {
             return Some(0.0);
         }
 
-        let f1 = k1.key.finger;
-        let f2 = k2.key.finger;
+        let f1 = layout.geometry().finger(k1.key_index);
+        let f2 = layout.geometry().finger(k2.key_index);
 
         if self.exclude_thumbs && (f1 == Finger::Thumb || f2 == Finger::Thumb) {
             return Some(0.0);
         }
 
-        if f1.distance(&f2) == 1 && k1.key.matrix_position.0.abs_diff(k2.key.matrix_position.0) > 1
+        if f1.distance(&f2) == 1
+            && layout
+                .geometry()
+                .column(k1.key_index)
+                .abs_diff(layout.geometry().column(k2.key_index))
+                > 1
         {
             Some(weight)
         } else {
@@ -0,0 +1,47 @@
+//! The bigram metric [`SameHandModTap`] counts bigrams in which a mod-tap (e.g. a home-row
+//! modifier) is held and the next key is pressed with the same hand. Such rolls are prone to
+//! misfires (the mod-tap being registered as a tap or the other way around) and require the hand
+//! to hold one key while pressing another.
+
+use super::BigramMetric;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {}
+
+#[derive(Clone, Debug)]
+pub struct SameHandModTap {}
+
+impl SameHandModTap {
+    pub fn new(_params: &Parameters) -> Self {
+        Self {}
+    }
+}
+
+impl BigramMetric for SameHandModTap {
+    fn name(&self) -> &str {
+        "Same-Hand Mod-Tap"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if k1.is_modifier.is_mod_tap()
+            && layout.geometry().hand(k1.key_index) == layout.geometry().hand(k2.key_index)
+            && k1 != k2
+        {
+            Some(weight)
+        } else {
+            Some(0.0)
+        }
+    }
+}
@@ -6,10 +6,7 @@
 
 use super::BigramMetric;
 
-use keyboard_layout::{
-    key::Finger,
-    layout::{LayerKey, Layout},
-};
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
 
@@ -37,12 +34,12 @@ impl BigramMetric for SymmetricHandswitches {
         k2: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        if k1.key.hand != k2.key.hand
+        if layout.geometry().hand(k1.key_index) != layout.geometry().hand(k2.key_index)
             && k1.key.symmetry_index == k2.key.symmetry_index
-            && k1.key.finger != Finger::Thumb
-            && k2.key.finger != Finger::Thumb
+            && !layout.geometry().is_thumb(k1.key_index)
+            && !layout.geometry().is_thumb(k2.key_index)
         {
             Some(-weight)
         } else {
@@ -1,20 +1,46 @@
 //! The `metrics` module provides a trait for layout metrics.
+use crate::ngram_mapper::common::MissingSymbols;
+
 use keyboard_layout::layout::Layout;
 
 use std::fmt;
 
 pub mod kla_home_key_words;
 pub mod kla_same_finger_words;
+pub mod rank_penalty;
+pub mod required_symbols;
 pub mod shortcut_keys;
 pub mod similar_letter_groups;
 pub mod similar_letters;
+pub mod word_start_hand;
 
 /// LayoutMetric is a trait for metrics that depends only on the layout.
 pub trait LayoutMetric: Send + Sync + LayoutMetricClone + fmt::Debug {
     /// Return the name of the metric
     fn name(&self) -> &str;
+
+    /// Approximate number of bytes of the data held by the metric (e.g. lookup tables)
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
     /// Compute the total cost for the metric
     fn total_cost(&self, layout: &Layout) -> (f64, Option<String>);
+
+    /// Whether the metric depends on the unigram symbols that could not be mapped to the layout
+    fn requires_missing_symbols(&self) -> bool {
+        false
+    }
+
+    /// Compute the total cost for the metric given the weights of the unigram symbols that could
+    /// not be mapped to the layout (relative to the total unigram weight)
+    fn total_cost_with_missing_symbols(
+        &self,
+        layout: &Layout,
+        _missing_symbols: &MissingSymbols,
+    ) -> (f64, Option<String>) {
+        self.total_cost(layout)
+    }
 }
 
 // in order to implement clone for Box<dyn LayoutMetric>, the following trick is necessary
@@ -1,5 +1,7 @@
 use super::LayoutMetric;
 
+use crate::memory::{hashset_bytes, vec_bytes};
+
 use std::iter::FromIterator;
 
 use ahash::{AHashMap, AHashSet};
@@ -15,9 +17,13 @@ pub struct Parameters {
     pub home_row_positions: Vec<MatrixPosition>,
 }
 
+/// A word along with its set of chars, number of unique chars, and weight
+type WordEntry = (String, (AHashSet<char>, usize, f64));
+
 #[derive(Clone, Debug)]
 pub struct KLAHomeKeyWords {
-    words: AHashMap<String, (AHashSet<char>, usize, f64)>, // set of chars, number of unique chars, weight
+    // sorted by word, so that the weights are summed in a deterministic order
+    words: Vec<WordEntry>,
     home_row_positions: AHashSet<MatrixPosition>,
 }
 
@@ -50,6 +56,9 @@ impl KLAHomeKeyWords {
                 entry.2 += r.weight;
             }
         });
+        let mut words: Vec<_> = words.into_iter().collect();
+        words.sort_unstable_by(|(w1, _), (w2, _)| w1.cmp(w2));
+
         Self {
             words,
             home_row_positions: AHashSet::from_iter(params.home_row_positions.iter().cloned()),
@@ -62,6 +71,18 @@ impl LayoutMetric for KLAHomeKeyWords {
         "Home Key Words"
     }
 
+    fn estimated_bytes(&self) -> usize {
+        let entries: usize = self
+            .words
+            .iter()
+            .map(|(word, (chars, _, _))| word.capacity() + hashset_bytes(chars))
+            .sum();
+        std::mem::size_of_val(self)
+            + vec_bytes(&self.words)
+            + entries
+            + hashset_bytes(&self.home_row_positions)
+    }
+
     fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
         let mut found_weight = 0.0;
         let mut found_words = 0;
@@ -70,7 +91,11 @@ impl LayoutMetric for KLAHomeKeyWords {
             .layerkeys
             .iter()
             .filter_map(|k| {
-                if k.layer == 0 && self.home_row_positions.contains(&k.key.matrix_position) {
+                if k.layer == 0
+                    && self
+                        .home_row_positions
+                        .contains(&layout.geometry().matrix_position(k.key_index))
+                {
                     Some(k.symbol)
                 } else {
                     None
@@ -97,7 +122,11 @@ impl LayoutMetric for KLAHomeKeyWords {
             found_words, total_words
         );
 
-        let cost = -(found_weight / total_weight);
+        let cost = if total_weight > 0.0 {
+            -(found_weight / total_weight)
+        } else {
+            0.0
+        };
 
         (cost, Some(message))
     }
@@ -2,6 +2,8 @@ use std::iter::FromIterator;
 
 use super::LayoutMetric;
 
+use crate::memory::vec_bytes;
+
 use ahash::{AHashMap, AHashSet};
 use keyboard_layout::layout::Layout;
 
@@ -16,7 +18,8 @@ pub struct Parameters {
 
 #[derive(Clone, Debug)]
 pub struct KLASameFingerWords {
-    words: AHashMap<String, (usize, f64)>, // (chars, number of unique chars, weight)
+    // sorted by word, so that the weights are summed in a deterministic order
+    words: Vec<(String, (usize, f64))>, // (chars, number of unique chars, weight)
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,6 +49,9 @@ impl KLASameFingerWords {
                 entry.1 += r.weight;
             }
         });
+        let mut words: Vec<_> = words.into_iter().collect();
+        words.sort_unstable_by(|(w1, _), (w2, _)| w1.cmp(w2));
+
         Self { words }
     }
 }
@@ -55,6 +61,11 @@ impl LayoutMetric for KLASameFingerWords {
         "Same Finger Words"
     }
 
+    fn estimated_bytes(&self) -> usize {
+        let words: usize = self.words.iter().map(|(word, _)| word.capacity()).sum();
+        std::mem::size_of_val(self) + vec_bytes(&self.words) + words
+    }
+
     fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
         let mut found_char_weight = 0.0;
         let mut found_words = 0;
@@ -73,8 +84,10 @@ impl LayoutMetric for KLASameFingerWords {
                 .all(|(k1, k2)| {
                     if let (Some(k1), Some(k2)) = (k1, k2) {
                         k1.key == k2.key
-                            || k1.key.hand != k2.key.hand
-                            || k1.key.finger != k2.key.finger
+                            || layout.geometry().hand(k1.key_index)
+                                != layout.geometry().hand(k2.key_index)
+                            || layout.geometry().finger(k1.key_index)
+                                != layout.geometry().finger(k2.key_index)
                     } else {
                         false
                     }
@@ -97,7 +110,11 @@ impl LayoutMetric for KLASameFingerWords {
             found_words, total_words
         );
 
-        let cost = -(found_char_weight / total_weight);
+        let cost = if total_weight > 0.0 {
+            -(found_char_weight / total_weight)
+        } else {
+            0.0
+        };
 
         (cost, Some(message))
     }
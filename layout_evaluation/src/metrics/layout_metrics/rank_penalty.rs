@@ -0,0 +1,128 @@
+//! The layout metric [`RankPenalty`] compares the layout to the greedy assignment of symbols
+//! to keys: The most frequent symbol on the cheapest key, the second most frequent symbol on
+//! the second cheapest key, and so on. Each symbol placed on a key that is more expensive than
+//! the key of its frequency rank costs its relative frequency times the difference of the key
+//! costs. Symbols on cheaper keys do not compensate for that.
+//!
+//! Only non-fixed keys are considered. The frequency of a key includes the symbols of all of
+//! its layers. The metric intentionally ignores all bigram effects and is mostly meant as a
+//! sanity check and for listing the keys that are furthest from their "ideal" position.
+
+use super::LayoutMetric;
+
+use crate::memory::hashmap_bytes;
+use crate::ngrams::Unigrams;
+
+use ahash::AHashMap;
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// File containing the unigram frequencies to rank the symbols by
+    pub unigrams_filename: String,
+    /// Number of keys with the largest penalties to list in the message
+    pub n_listed: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RankPenalty {
+    unigrams: AHashMap<char, f64>,
+    n_listed: usize,
+}
+
+impl RankPenalty {
+    pub fn new(params: &Parameters) -> Self {
+        let unigrams = Unigrams::from_file(&params.unigrams_filename).unwrap_or_else(|_| {
+            panic!("Could not read unigrams file {}", params.unigrams_filename)
+        });
+
+        Self {
+            unigrams: unigrams.grams,
+            n_listed: params.n_listed,
+        }
+    }
+}
+
+impl LayoutMetric for RankPenalty {
+    fn name(&self) -> &str {
+        "Rank Penalty"
+    }
+
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self) + hashmap_bytes(&self.unigrams)
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        // weights of the non-fixed keys (referenced by their base-layer LayerKey)
+        let mut key_weights: AHashMap<LayerKeyIndex, f64> = layout
+            .layerkeys
+            .iter()
+            .enumerate()
+            .filter(|(_, lk)| lk.layer == 0 && !lk.is_fixed)
+            .map(|(i, _)| (i as LayerKeyIndex, 0.0))
+            .collect();
+
+        layout.layerkeys.iter().enumerate().for_each(|(i, lk)| {
+            let base = layout.get_base_layerkey_index(&(i as LayerKeyIndex));
+            if let Some(w) = key_weights.get_mut(&base) {
+                *w += self.unigrams.get(&lk.symbol).cloned().unwrap_or(0.0);
+            }
+        });
+
+        let mut ranked: Vec<(LayerKeyIndex, f64)> = key_weights.into_iter().collect();
+        ranked.sort_by(|(i1, w1), (i2, w2)| w2.partial_cmp(w1).unwrap().then(i1.cmp(i2)));
+
+        // summed in the order of the ranking (instead of the map's) to be deterministic
+        let total_weight: f64 = ranked.iter().map(|(_, w)| w).sum();
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
+        let mut ideal_costs: Vec<f64> = ranked
+            .iter()
+            .map(|(idx, _)| layout.get_layerkey(idx).key.cost)
+            .collect();
+        ideal_costs.sort_by(|c1, c2| c1.partial_cmp(c2).unwrap());
+
+        // (rank, LayerKey index, penalty)
+        let mut penalties: Vec<(usize, LayerKeyIndex, f64)> = ranked
+            .iter()
+            .zip(ideal_costs.iter())
+            .enumerate()
+            .map(|(rank, ((idx, w), ideal_cost))| {
+                let cost = layout.get_layerkey(idx).key.cost;
+                let penalty = w / total_weight * (cost - ideal_cost).max(0.0);
+                (rank, *idx, penalty)
+            })
+            .collect();
+
+        let cost: f64 = penalties.iter().map(|(_, _, p)| p).sum();
+
+        penalties.sort_by(|(_, _, p1), (_, _, p2)| p2.partial_cmp(p1).unwrap());
+        let worst_msgs: Vec<String> = penalties
+            .iter()
+            .filter(|(_, _, p)| *p > 0.0)
+            .take(self.n_listed)
+            .map(|(rank, idx, p)| {
+                let lk = layout.get_layerkey(idx);
+                format!(
+                    "{} (rank {}, cost {:.1} instead of {:.1}, {:>5.2}%)",
+                    lk,
+                    rank + 1,
+                    lk.key.cost,
+                    ideal_costs[*rank],
+                    100.0 * p / cost,
+                )
+            })
+            .collect();
+
+        let message = if !worst_msgs.is_empty() {
+            Some(format!("Largest rank penalties: {}", worst_msgs.join(", ")))
+        } else {
+            None
+        };
+
+        (cost, message)
+    }
+}
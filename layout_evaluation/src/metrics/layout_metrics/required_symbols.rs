@@ -0,0 +1,80 @@
+//! The layout metric [`RequiredSymbols`] penalizes symbols from a configurable inventory that
+//! are missing from the layout. This makes layouts with different symbol sets comparable: instead
+//! of being dropped, the corpus weight of each missing symbol (relative to the total unigram
+//! weight) is multiplied with a configurable penalty.
+
+use super::LayoutMetric;
+use crate::ngram_mapper::common::MissingSymbols;
+
+use keyboard_layout::layout::Layout;
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Symbols that every layout shall contain
+    pub symbols: String,
+    /// Cost per unit of (relative) corpus weight of missing symbols
+    pub penalty: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RequiredSymbols {
+    symbols: Vec<char>,
+    penalty: f64,
+}
+
+impl RequiredSymbols {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            symbols: params.symbols.chars().collect(),
+            penalty: params.penalty,
+        }
+    }
+}
+
+impl LayoutMetric for RequiredSymbols {
+    fn name(&self) -> &str {
+        "Missing Required Symbols"
+    }
+
+    fn requires_missing_symbols(&self) -> bool {
+        true
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        self.total_cost_with_missing_symbols(layout, &MissingSymbols::default())
+    }
+
+    fn total_cost_with_missing_symbols(
+        &self,
+        layout: &Layout,
+        missing_symbols: &MissingSymbols,
+    ) -> (f64, Option<String>) {
+        let missing: Vec<(char, f64)> = self
+            .symbols
+            .iter()
+            .filter(|c| {
+                missing_symbols.contains_key(c) || layout.get_layerkey_for_symbol(c).is_none()
+            })
+            .map(|c| (*c, missing_symbols.get(c).cloned().unwrap_or(0.0)))
+            .collect();
+
+        let cost = self.penalty * missing.iter().map(|(_, w)| w).sum::<f64>();
+
+        let message = if !missing.is_empty() {
+            Some(format!(
+                "Missing required symbols: {}",
+                missing
+                    .iter()
+                    .map(|(c, w)| format!("{} ({:.2}%)", c.escape_debug(), 100.0 * w))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        } else {
+            None
+        };
+
+        (cost, message)
+    }
+}
@@ -44,17 +44,9 @@ impl LayoutMetric for ShortcutKeys {
             if let Some(k) = layout.get_layerkey_for_symbol(c) {
                 // NOTE: In ArneBab's solution, the top rows do not "skip a column" as we do.
                 // Therefore, a special case needs to be made for row 3, in contrast to here.
-                if k.key.matrix_position.0 > self.within_n_leftmost_cols {
+                if layout.geometry().column(k.key_index) > self.within_n_leftmost_cols {
                     cost += self.cost;
                     bad_keys.push(*c);
-                    log::trace!(
-                        "Shorcut: {}, Finger: {:>13}, Matrix Position: {:.0} (is > {}), Cost: {:>2.2}",
-                        c.escape_debug().to_string(),
-                        format!("{:?} {:?}", k.key.hand, k.key.finger),
-                        k.key.matrix_position.0,
-                        self.within_n_leftmost_cols,
-                        self.cost
-                    );
                 }
             }
         });
@@ -36,10 +36,10 @@ impl SimilarLetterGroups {
 /// Compares how many values of [data] are not equal to other values of [data].
 /// More differences result in a higher cost.
 fn costs<T: PartialEq>(data: &[T]) -> f64 {
-    if data.is_empty() {
+    if data.len() < 2 {
         return 0.0;
     }
-    let mut cost = 0.0;
+    let mut cost: f64 = 0.0;
     let mut n = 0.0;
     for (i, d1) in data.iter().enumerate() {
         for d2 in data.iter().skip(i + 1) {
@@ -50,7 +50,7 @@ fn costs<T: PartialEq>(data: &[T]) -> f64 {
         }
     }
 
-    ((cost / n) as f64).ln_1p()
+    (cost / n).ln_1p()
 }
 
 impl LayoutMetric for SimilarLetterGroups {
@@ -59,6 +59,7 @@ impl LayoutMetric for SimilarLetterGroups {
     }
 
     fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        let geometry = layout.geometry();
         let mut cost = 0.0;
 
         for (s1, s2) in &self.letter_group_pairs {
@@ -69,10 +70,10 @@ impl LayoutMetric for SimilarLetterGroups {
             let mut v_directions: Vec<i8> = Vec::with_capacity(letters_per_group);
 
             for (c1, c2) in s1.chars().zip(s2.chars()) {
-                let key1 = &layout.get_layerkey_for_symbol(&c1).unwrap().key;
-                let key2 = &layout.get_layerkey_for_symbol(&c2).unwrap().key;
+                let key1 = layout.get_layerkey_for_symbol(&c1).unwrap().key_index;
+                let key2 = layout.get_layerkey_for_symbol(&c2).unwrap().key_index;
 
-                let hand_direction = match (&key1.hand, &key2.hand) {
+                let hand_direction = match (&geometry.hand(key1), &geometry.hand(key2)) {
                     (&Hand::Left, &Hand::Right) => 1,
                     (&Hand::Right, &Hand::Left) => -1,
                     _ => 0,
@@ -80,13 +81,13 @@ impl LayoutMetric for SimilarLetterGroups {
                 hand_directions.push(hand_direction);
 
                 // take key1 - key2 for comparability with ArneBab
-                let finger_direction = key1.finger as i8 - key2.finger as i8;
+                let finger_direction = geometry.finger(key1) as i8 - geometry.finger(key2) as i8;
                 finger_directions.push(finger_direction);
 
-                let column_distance = key2.matrix_position.0 as i8 - key1.matrix_position.0 as i8;
+                let column_distance = geometry.column(key2) as i8 - geometry.column(key1) as i8;
                 column_distances.push(column_distance);
 
-                let v_dist = key2.matrix_position.1 as i8 - key1.matrix_position.1 as i8;
+                let v_dist = geometry.row(key2) as i8 - geometry.row(key1) as i8;
                 let v_direction = match v_dist {
                     0 => 0,
                     d if d < 0 => -1,
@@ -99,17 +100,6 @@ impl LayoutMetric for SimilarLetterGroups {
                 + costs(&finger_directions)
                 + costs(&column_distances)
                 + costs(&v_directions);
-
-            if cost > 0.0 {
-                log::trace!(
-                    "{} - {}, Hand direction: {:.2}, Finger direction: {:.2}, Column distance: {:.2}, Vertical direction: {:.2}",
-                    s1, s2,
-                    costs(&hand_directions),
-                    costs(&finger_directions),
-                    costs(&column_distances),
-                    costs(&v_directions),
-                )
-            }
         }
 
         (cost, None)
@@ -46,6 +46,7 @@ impl LayoutMetric for SimilarLetters {
     }
 
     fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        let geometry = layout.geometry();
         let mut cost = 0.0;
         let mut bad_pairs: Vec<String> = Vec::new();
 
@@ -54,26 +55,28 @@ impl LayoutMetric for SimilarLetters {
                 let cost_to_add;
                 let layerkey1 = layout.get_layerkey_for_symbol(c1).unwrap();
                 let layerkey2 = layout.get_layerkey_for_symbol(c2).unwrap();
-                let key1 = &layerkey1.key;
-                let key2 = &layerkey2.key;
+                let key1 = layerkey1.key_index;
+                let key2 = layerkey2.key_index;
 
                 let on_same_layer = layerkey1.layer == layerkey2.layer;
-                let neighbor_horizontally = key1.matrix_position.1 == key2.matrix_position.1
-                    && key1.matrix_position.0.abs_diff(key2.matrix_position.0) == 1
+                let neighbor_horizontally = geometry.row(key1) == geometry.row(key2)
+                    && geometry.column(key1).abs_diff(geometry.column(key2)) == 1
                     && on_same_layer;
-                let neighbor_vertically = key1.matrix_position.0 == key2.matrix_position.0
-                    && key1.matrix_position.1.abs_diff(key2.matrix_position.1) == 1
+                let neighbor_vertically = geometry.column(key1) == geometry.column(key2)
+                    && geometry.row(key1).abs_diff(geometry.row(key2)) == 1
                     && on_same_layer;
-                let on_same_key = key1.matrix_position == key2.matrix_position;
+                let on_same_key = key1 == key2;
 
                 if on_same_key {
                     cost_to_add = params.same_key_cost;
                 } else if neighbor_horizontally || neighbor_vertically {
                     cost_to_add = params.neighboring_cost;
-                } else if key1.matrix_position.0 == key2.matrix_position.0 && on_same_layer {
+                } else if geometry.column(key1) == geometry.column(key2) && on_same_layer {
                     // If in same column
                     cost_to_add = params.same_column_cost;
-                } else if key1.symmetry_index == key2.symmetry_index && on_same_layer {
+                } else if geometry.key(key1).symmetry_index == geometry.key(key2).symmetry_index
+                    && on_same_layer
+                {
                     // If on symmetrical positions
                     cost_to_add = params.symmetric_cost;
                 } else {
@@ -81,15 +84,6 @@ impl LayoutMetric for SimilarLetters {
                     bad_pairs.push(format!("{}{}", c1, c2));
                 }
                 cost += cost_to_add;
-
-                log::trace!(
-                    "{} {:?} - {} {:?} - Cost: {}",
-                    c1,
-                    key1.matrix_position,
-                    c2,
-                    key2.matrix_position,
-                    cost_to_add
-                );
             }
         }
 
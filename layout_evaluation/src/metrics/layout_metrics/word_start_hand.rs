@@ -0,0 +1,112 @@
+//! The layout metric [`WordStartHand`] compares the fraction of words started by the left hand
+//! with a target fraction. The resulting cost is the distance of the left hand's fraction to the
+//! target (thumbs excluded).
+//!
+//! Word-initial symbols are derived from the evaluator's bigrams starting with whitespace (see
+//! [`NgramMapper::word_starts`](crate::ngram_mapper::NgramMapper::word_starts)). For corpora with
+//! hardly any whitespace (e.g. code), this is not meaningful and the overall unigrams are used
+//! instead.
+
+use super::LayoutMetric;
+
+use crate::memory::vec_bytes;
+use crate::ngrams::{Unigrams, WordStarts};
+
+use keyboard_layout::{
+    key::{Hand, HandMap},
+    layout::Layout,
+};
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Minimum fraction of the bigram weight that has to start with whitespace. Otherwise, the
+    /// unigrams are used instead of the word-initial symbols.
+    pub min_word_start_fraction: f64,
+    /// Targeted fraction of word-initial weight typed by the left hand
+    pub left_target: f64,
+    /// Number of most frequent word-initial symbols to list per hand in the message
+    pub n_listed: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct WordStartHand {
+    /// Sorted by symbol, so that the weights are summed in a deterministic order
+    word_starts: Vec<(char, f64)>,
+    left_target: f64,
+    n_listed: usize,
+}
+
+impl WordStartHand {
+    pub fn new(params: &Parameters, word_starts: &WordStarts, unigrams: &Unigrams) -> Self {
+        let word_starts = if word_starts.fraction >= params.min_word_start_fraction {
+            &word_starts.word_initial
+        } else {
+            log::warn!(
+                "Word Start Hand: Only {:.2}% of the bigrams start with whitespace, using unigrams instead of word-initial symbols",
+                100.0 * word_starts.fraction
+            );
+            unigrams
+        };
+
+        let mut word_starts: Vec<(char, f64)> =
+            word_starts.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        word_starts.sort_unstable_by_key(|(c, _)| *c);
+
+        Self {
+            word_starts,
+            left_target: params.left_target,
+            n_listed: params.n_listed,
+        }
+    }
+}
+
+impl LayoutMetric for WordStartHand {
+    fn name(&self) -> &str {
+        "Word Start Hand"
+    }
+
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self) + vec_bytes(&self.word_starts)
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+        let mut hand_weights: HandMap<f64> = HandMap::default();
+        let mut symbols: Vec<(char, f64, Hand)> = Vec::new();
+        self.word_starts.iter().for_each(|(c, w)| {
+            if let Some(lk) = layout.get_layerkey_for_symbol(c) {
+                if !layout.geometry().is_thumb(lk.key_index) {
+                    *hand_weights.get_mut(&layout.geometry().hand(lk.key_index)) += *w;
+                    symbols.push((*c, *w, layout.geometry().hand(lk.key_index)));
+                }
+            }
+        });
+        symbols.sort_by(|(c1, w1, _), (c2, w2, _)| w2.partial_cmp(w1).unwrap().then(c1.cmp(c2)));
+
+        let total_weight = hand_weights.get(&Hand::Left) + hand_weights.get(&Hand::Right);
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+        let left_fraction = hand_weights.get(&Hand::Left) / total_weight;
+
+        let top_symbols = |hand: Hand| -> String {
+            symbols
+                .iter()
+                .filter(|(_, _, h)| *h == hand)
+                .take(self.n_listed)
+                .map(|(c, w, _)| format!("{} ({:.1}%)", c, 100.0 * w / total_weight))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+
+        let message = format!(
+            "Word starts % (no thumb): {:.2} - {:.2}; Left: {}; Right: {}",
+            100.0 * left_fraction,
+            100.0 * (1.0 - left_fraction),
+            top_symbols(Hand::Left),
+            top_symbols(Hand::Right),
+        );
+
+        ((left_fraction - self.left_target).abs(), Some(message))
+    }
+}
@@ -16,7 +16,9 @@ pub mod oxey_inward_rolls;
 pub mod oxey_onehands;
 pub mod oxey_outward_rolls;
 pub mod oxey_redirects;
+pub mod pivots;
 pub mod secondary_bigrams;
+pub mod thumb_alternation;
 pub mod trigram_finger_repeats;
 pub mod trigram_rolls;
 
@@ -25,6 +27,20 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
     /// Return the name of the metric.
     fn name(&self) -> &str;
 
+    /// Approximate number of bytes of the data held by the metric (e.g. lookup tables).
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// Whether the total weight passed to the metric includes the weight of the trigrams that
+    /// were removed because the same hold modifier appears consecutively (see
+    /// [`MappedTrigrams::weight_filtered`](crate::ngram_mapper::MappedTrigrams::weight_filtered)).
+    /// Metrics normalizing by the total weight should include it, so that the denominator does
+    /// not depend on the placement of the modifiers.
+    fn includes_filtered_weight(&self) -> bool {
+        false
+    }
+
     /// Compute the cost of one trigram (if that is possible, otherwise, return `None`).
     #[inline(always)]
     fn individual_cost(
@@ -39,6 +55,19 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
         None
     }
 
+    /// Compute the total cost for the metric from the secondary bigrams (the first and third key
+    /// of each trigram) derived by the ngram mapper (see
+    /// [`MappedTrigrams::secondary_bigrams`](crate::ngram_mapper::MappedTrigrams::secondary_bigrams)).
+    /// `None` if the metric does not operate on them (then [`TrigramMetric::total_cost`] is used).
+    fn total_cost_with_secondary_bigrams(
+        &self,
+        _secondary_bigrams: &[((&LayerKey, &LayerKey), f64)],
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<(f64, Option<String>)> {
+        None
+    }
+
     /// Compute the total cost for the metric.
     fn total_cost(
         &self,
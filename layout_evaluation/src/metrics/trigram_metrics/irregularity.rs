@@ -37,6 +37,10 @@ impl TrigramMetric for Irregularity {
         "Irregularity"
     }
 
+    fn includes_filtered_weight(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -62,11 +62,11 @@ impl TrigramMetric for NoHandswitchInTrigram {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        let hand1 = k1.key.hand;
-        let hand2 = k2.key.hand;
-        let hand3 = k3.key.hand;
+        let hand1 = layout.geometry().hand(k1.key_index);
+        let hand2 = layout.geometry().hand(k2.key_index);
+        let hand3 = layout.geometry().hand(k3.key_index);
 
         // NOTE: In ArneBab's solution, a precomputed table is used, that only contains letters, period, and comma
         // Here, we use "non-fixed" keys, which should (but need not, depending on configuration), amount to the same
@@ -76,9 +76,9 @@ impl TrigramMetric for NoHandswitchInTrigram {
             return Some(0.0);
         }
 
-        if k1.key.finger == Finger::Thumb
-            || k2.key.finger == Finger::Thumb
-            || k3.key.finger == Finger::Thumb
+        if layout.geometry().is_thumb(k1.key_index)
+            || layout.geometry().is_thumb(k2.key_index)
+            || layout.geometry().is_thumb(k3.key_index)
         {
             return Some(0.0);
         }
@@ -87,16 +87,19 @@ impl TrigramMetric for NoHandswitchInTrigram {
             return Some(0.0);
         }
 
-        let pos1 = k1.key.matrix_position;
-        let pos2 = k2.key.matrix_position;
-        let pos3 = k3.key.matrix_position;
+        let pos1 = layout.geometry().matrix_position(k1.key_index);
+        let pos2 = layout.geometry().matrix_position(k2.key_index);
+        let pos3 = layout.geometry().matrix_position(k3.key_index);
 
-        let contains_repeat = (k1.key.finger == k2.key.finger && k1.key.hand == k2.key.hand)
-            || (k2.key.finger == k3.key.finger && k2.key.hand == k3.key.hand);
+        let contains_repeat = (layout.geometry().finger(k1.key_index)
+            == layout.geometry().finger(k2.key_index)
+            && layout.geometry().hand(k1.key_index) == layout.geometry().hand(k2.key_index))
+            || (layout.geometry().finger(k2.key_index) == layout.geometry().finger(k3.key_index)
+                && layout.geometry().hand(k2.key_index) == layout.geometry().hand(k3.key_index));
         let same_key = pos1 == pos2 && pos2 == pos3;
-        let contains_index = if k1.key.finger == Finger::Index
-            || k2.key.finger == Finger::Index
-            || k3.key.finger == Finger::Index
+        let contains_index = if layout.geometry().finger(k1.key_index) == Finger::Index
+            || layout.geometry().finger(k2.key_index) == Finger::Index
+            || layout.geometry().finger(k3.key_index) == Finger::Index
         {
             self.factor_contains_index
         } else {
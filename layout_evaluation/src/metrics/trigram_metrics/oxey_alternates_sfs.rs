@@ -1,10 +1,7 @@
 use super::TrigramMetric;
 
 use ahash::AHashSet;
-use keyboard_layout::{
-    key::Finger,
-    layout::{LayerKey, Layout},
-};
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
 
@@ -45,12 +42,12 @@ impl TrigramMetric for OxeyAlternatesSfs {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
         if self.exclude_thumbs
-            && (k1.key.finger == Finger::Thumb
-                || k2.key.finger == Finger::Thumb
-                || k3.key.finger == Finger::Thumb)
+            && (layout.geometry().is_thumb(k1.key_index)
+                || layout.geometry().is_thumb(k2.key_index)
+                || layout.geometry().is_thumb(k3.key_index))
         {
             return Some(0.0);
         }
@@ -69,11 +66,15 @@ impl TrigramMetric for OxeyAlternatesSfs {
             return Some(0.0);
         }
 
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
-        let h3 = k3.key.hand;
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h2 = layout.geometry().hand(k2.key_index);
+        let h3 = layout.geometry().hand(k3.key_index);
 
-        if h1 != h2 && h2 != h3 && (h1 == h3 && k1.key.finger == k3.key.finger) {
+        if h1 != h2
+            && h2 != h3
+            && (h1 == h3
+                && layout.geometry().finger(k1.key_index) == layout.geometry().finger(k3.key_index))
+        {
             Some(weight)
         } else {
             Some(0.0)
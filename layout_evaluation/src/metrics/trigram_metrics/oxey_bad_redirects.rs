@@ -2,6 +2,7 @@ use super::TrigramMetric;
 
 use ahash::AHashSet;
 use keyboard_layout::{
+    geometry::KeyGeometry,
     key::{Finger, Hand},
     layout::{LayerKey, Layout},
 };
@@ -10,9 +11,12 @@ use serde::Deserialize;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {
-    exclude_thumbs: bool,
-    exclude_modifiers: bool,
-    exclude_chars: Vec<char>,
+    /// Exclude trigrams involving thumbs
+    pub exclude_thumbs: bool,
+    /// Exclude trigrams involving modifiers
+    pub exclude_modifiers: bool,
+    /// Exclude trigrams containing any of these symbols
+    pub exclude_chars: Vec<char>,
 }
 
 #[derive(Clone, Debug)]
@@ -30,44 +34,28 @@ impl OxeyBadRedirects {
             exclude_chars: params.exclude_chars.iter().cloned().collect(),
         }
     }
-}
-
-#[inline(always)]
-fn inwards(k1: &LayerKey, k2: &LayerKey) -> bool {
-    if k1.key.hand == Hand::Left {
-        k1.key.matrix_position.0 < k2.key.matrix_position.0
-    } else {
-        k1.key.matrix_position.0 > k2.key.matrix_position.0
-    }
-}
-
-impl TrigramMetric for OxeyBadRedirects {
-    fn name(&self) -> &str {
-        "Bad Redirects"
-    }
 
+    /// Whether the trigram counts as a bad redirect (taking the configured exclusions into account).
     #[inline(always)]
-    fn individual_cost(
+    pub fn is_redirect(
         &self,
         k1: &LayerKey,
         k2: &LayerKey,
         k3: &LayerKey,
-        weight: f64,
-        _total_weight: f64,
-        _layout: &Layout,
-    ) -> Option<f64> {
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
-        let h3 = k3.key.hand;
+        layout: &Layout,
+    ) -> bool {
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h2 = layout.geometry().hand(k2.key_index);
+        let h3 = layout.geometry().hand(k3.key_index);
 
         if !(h1 == h2 && h2 == h3) {
-            return Some(0.0);
+            return false;
         }
 
         if self.exclude_modifiers
             && (k1.is_modifier.is_some() || k2.is_modifier.is_some() || k3.is_modifier.is_some())
         {
-            return Some(0.0);
+            return false;
         }
 
         if !self.exclude_chars.is_empty()
@@ -75,31 +63,59 @@ impl TrigramMetric for OxeyBadRedirects {
                 || self.exclude_chars.contains(&k2.symbol)
                 || self.exclude_chars.contains(&k3.symbol))
         {
-            return Some(0.0);
+            return false;
         }
 
-        let f1 = k1.key.finger;
-        let f2 = k2.key.finger;
-        let f3 = k3.key.finger;
+        let f1 = layout.geometry().finger(k1.key_index);
+        let f2 = layout.geometry().finger(k2.key_index);
+        let f3 = layout.geometry().finger(k3.key_index);
 
         if self.exclude_thumbs
             && (f1 == Finger::Thumb || f2 == Finger::Thumb || f3 == Finger::Thumb)
         {
-            return Some(0.0);
+            return false;
         }
 
         // if at least one key is be hit with the index finger it is not a bad redirect
         if f1 == Finger::Index || f2 == Finger::Index || f3 == Finger::Index {
-            return Some(0.0);
+            return false;
         }
 
-        let inwards1 = inwards(k1, k2);
-        let inwards2 = inwards(k2, k3);
+        let inwards1 = inwards(layout.geometry(), k1, k2);
+        let inwards2 = inwards(layout.geometry(), k2, k3);
+
+        let outwards1 = inwards(layout.geometry(), k2, k1);
+        let outwards2 = inwards(layout.geometry(), k3, k2);
 
-        let outwards1 = inwards(k2, k1);
-        let outwards2 = inwards(k3, k2);
+        (inwards1 && outwards2) || (outwards1 && inwards2)
+    }
+}
 
-        if (inwards1 && outwards2) || (outwards1 && inwards2) {
+#[inline(always)]
+fn inwards(geometry: &KeyGeometry, k1: &LayerKey, k2: &LayerKey) -> bool {
+    if geometry.hand(k1.key_index) == Hand::Left {
+        geometry.column(k1.key_index) < geometry.column(k2.key_index)
+    } else {
+        geometry.column(k1.key_index) > geometry.column(k2.key_index)
+    }
+}
+
+impl TrigramMetric for OxeyBadRedirects {
+    fn name(&self) -> &str {
+        "Bad Redirects"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if self.is_redirect(k1, k2, k3, layout) {
             Some(weight)
         } else {
             Some(0.0)
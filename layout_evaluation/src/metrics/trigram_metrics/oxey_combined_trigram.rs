@@ -2,6 +2,7 @@ use super::TrigramMetric;
 
 use ahash::AHashSet;
 use keyboard_layout::{
+    geometry::KeyGeometry,
     key::{Finger, Hand},
     layout::{LayerKey, Layout},
 };
@@ -63,11 +64,11 @@ struct TrigramTypeCounts {
 }
 
 #[inline(always)]
-fn is_inwards_movement(k1: &LayerKey, k2: &LayerKey) -> bool {
-    if k1.key.hand == Hand::Left {
-        k1.key.matrix_position.0 < k2.key.matrix_position.0
+fn is_inwards_movement(geometry: &KeyGeometry, k1: &LayerKey, k2: &LayerKey) -> bool {
+    if geometry.hand(k1.key_index) == Hand::Left {
+        geometry.column(k1.key_index) < geometry.column(k2.key_index)
     } else {
-        k1.key.matrix_position.0 > k2.key.matrix_position.0
+        geometry.column(k1.key_index) > geometry.column(k2.key_index)
     }
 }
 
@@ -94,15 +95,16 @@ impl OxeyCombinedTrigram {
 
     fn same_hand(
         &self,
+        geometry: &KeyGeometry,
         k1: &LayerKey,
         k2: &LayerKey,
         k3: &LayerKey,
         weight: f64,
         counts: &mut TrigramTypeCounts,
     ) {
-        let f1 = k1.key.finger;
-        let f2 = k2.key.finger;
-        let f3 = k3.key.finger;
+        let f1 = geometry.finger(k1.key_index);
+        let f2 = geometry.finger(k2.key_index);
+        let f3 = geometry.finger(k3.key_index);
 
         // finger repeats
         if f1 == f2 && f2 == f3 {
@@ -111,11 +113,11 @@ impl OxeyCombinedTrigram {
             counts.same_hand_single_finger_repeat += weight;
         } else {
             // rolls
-            let inwards1 = is_inwards_movement(k1, k2);
-            let inwards2 = is_inwards_movement(k2, k3);
+            let inwards1 = is_inwards_movement(geometry, k1, k2);
+            let inwards2 = is_inwards_movement(geometry, k2, k3);
 
-            let outwards1 = is_inwards_movement(k2, k1);
-            let outwards2 = is_inwards_movement(k3, k2);
+            let outwards1 = is_inwards_movement(geometry, k2, k1);
+            let outwards2 = is_inwards_movement(geometry, k3, k2);
 
             if (inwards1 && inwards2) || (outwards1 && outwards2) {
                 counts.same_hand_roll += weight;
@@ -129,23 +131,24 @@ impl OxeyCombinedTrigram {
 
     fn roll(
         &self,
+        geometry: &KeyGeometry,
         k1: &LayerKey,
         k2: &LayerKey,
         k3: &LayerKey,
         weight: f64,
         counts: &mut TrigramTypeCounts,
     ) {
-        let (kr1, kr2) = if k1.key.hand == k2.key.hand {
+        let (kr1, kr2) = if geometry.hand(k1.key_index) == geometry.hand(k2.key_index) {
             (k1, k2)
         } else {
             (k2, k3)
         };
 
-        if kr1.key.finger == kr2.key.finger {
+        if geometry.finger(kr1.key_index) == geometry.finger(kr2.key_index) {
             counts.roll_same_finger += weight;
         } else {
-            let inwards = is_inwards_movement(kr1, kr2);
-            let outwards = is_inwards_movement(kr2, kr1);
+            let inwards = is_inwards_movement(geometry, kr1, kr2);
+            let outwards = is_inwards_movement(geometry, kr2, kr1);
             if inwards {
                 counts.roll_inwards += weight;
             } else if outwards {
@@ -158,13 +161,14 @@ impl OxeyCombinedTrigram {
 
     fn alternate(
         &self,
+        geometry: &KeyGeometry,
         k1: &LayerKey,
         _k2: &LayerKey,
         k3: &LayerKey,
         weight: f64,
         counts: &mut TrigramTypeCounts,
     ) {
-        if k1.key.finger == k3.key.finger {
+        if geometry.finger(k1.key_index) == geometry.finger(k3.key_index) {
             counts.alternate_finger_repeat += weight;
         } else if k1.key == k3.key {
             counts.alternate_same_key += weight;
@@ -184,19 +188,19 @@ impl TrigramMetric for OxeyCombinedTrigram {
         trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
         // total_weight is optional for performance reasons (it can be computed from trigrams)
         _total_weight: Option<f64>,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> (f64, Option<String>) {
         let mut counts = TrigramTypeCounts::default();
 
         trigrams.iter().for_each(|((k1, k2, k3), weight)| {
-            let h1 = k1.key.hand;
-            let h2 = k2.key.hand;
-            let h3 = k3.key.hand;
+            let h1 = layout.geometry().hand(k1.key_index);
+            let h2 = layout.geometry().hand(k2.key_index);
+            let h3 = layout.geometry().hand(k3.key_index);
 
             if self.exclude_thumbs
-                && (k1.key.finger == Finger::Thumb
-                    || k2.key.finger == Finger::Thumb
-                    || k3.key.finger == Finger::Thumb)
+                && (layout.geometry().is_thumb(k1.key_index)
+                    || layout.geometry().is_thumb(k2.key_index)
+                    || layout.geometry().is_thumb(k3.key_index))
             {
                 return;
             }
@@ -218,11 +222,11 @@ impl TrigramMetric for OxeyCombinedTrigram {
             }
 
             if h1 == h2 && h2 == h3 {
-                self.same_hand(k1, k2, k3, *weight, &mut counts);
+                self.same_hand(layout.geometry(), k1, k2, k3, *weight, &mut counts);
             } else if h1 == h2 || h2 == h3 {
-                self.roll(k1, k2, k3, *weight, &mut counts);
+                self.roll(layout.geometry(), k1, k2, k3, *weight, &mut counts);
             } else {
-                self.alternate(k1, k2, k3, *weight, &mut counts);
+                self.alternate(layout.geometry(), k1, k2, k3, *weight, &mut counts);
             }
         });
 
@@ -45,7 +45,7 @@ impl TrigramMetric for OxeyDsfbs {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
         if self.exclude_modifiers && (k1.is_modifier.is_some() || k3.is_modifier.is_some()) {
             return Some(0.0);
@@ -62,15 +62,15 @@ impl TrigramMetric for OxeyDsfbs {
             return Some(0.0);
         }
 
-        let h1 = k1.key.hand;
-        let h3 = k3.key.hand;
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h3 = layout.geometry().hand(k3.key_index);
 
         if h1 != h3 {
             return Some(0.0);
         }
 
-        let f1 = k1.key.finger;
-        let f3 = k3.key.finger;
+        let f1 = layout.geometry().finger(k1.key_index);
+        let f3 = layout.geometry().finger(k3.key_index);
 
         if self.exclude_thumbs && (f1 == Finger::Thumb || f3 == Finger::Thumb) {
             return Some(0.0);
@@ -2,7 +2,7 @@ use super::TrigramMetric;
 
 use ahash::AHashSet;
 use keyboard_layout::{
-    key::{Finger, Hand},
+    key::Hand,
     layout::{LayerKey, Layout},
 };
 
@@ -45,12 +45,12 @@ impl TrigramMetric for OxeyInwardRolls {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
         if self.exclude_thumbs
-            && (k1.key.finger == Finger::Thumb
-                || k2.key.finger == Finger::Thumb
-                || k3.key.finger == Finger::Thumb)
+            && (layout.geometry().is_thumb(k1.key_index)
+                || layout.geometry().is_thumb(k2.key_index)
+                || layout.geometry().is_thumb(k3.key_index))
         {
             return Some(0.0);
         }
@@ -69,9 +69,9 @@ impl TrigramMetric for OxeyInwardRolls {
             return Some(0.0);
         }
 
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
-        let h3 = k3.key.hand;
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h2 = layout.geometry().hand(k2.key_index);
+        let h3 = layout.geometry().hand(k3.key_index);
 
         let first_roll = h1 == h2 && h2 != h3;
         let second_roll = h1 != h2 && h2 == h3;
@@ -83,14 +83,14 @@ impl TrigramMetric for OxeyInwardRolls {
         let (kr1, kr2) = if first_roll { (k1, k2) } else { (k2, k3) };
 
         // same-finger is not a roll
-        if kr1.key.finger == kr2.key.finger {
+        if layout.geometry().finger(kr1.key_index) == layout.geometry().finger(kr2.key_index) {
             return Some(0.0);
         }
 
-        let inwards: bool = if kr1.key.hand == Hand::Left {
-            kr1.key.matrix_position.0 < kr2.key.matrix_position.0
+        let inwards: bool = if layout.geometry().hand(kr1.key_index) == Hand::Left {
+            layout.geometry().column(kr1.key_index) < layout.geometry().column(kr2.key_index)
         } else {
-            kr1.key.matrix_position.0 > kr2.key.matrix_position.0
+            layout.geometry().column(kr1.key_index) > layout.geometry().column(kr2.key_index)
         };
 
         if !inwards {
@@ -2,6 +2,7 @@ use super::TrigramMetric;
 
 use ahash::AHashSet;
 use keyboard_layout::{
+    geometry::KeyGeometry,
     key::{Finger, Hand},
     layout::{LayerKey, Layout},
 };
@@ -33,11 +34,11 @@ impl OxeyOnehands {
 }
 
 #[inline(always)]
-fn inwards(k1: &LayerKey, k2: &LayerKey) -> bool {
-    if k1.key.hand == Hand::Left {
-        k1.key.matrix_position.0 < k2.key.matrix_position.0
+fn inwards(geometry: &KeyGeometry, k1: &LayerKey, k2: &LayerKey) -> bool {
+    if geometry.hand(k1.key_index) == Hand::Left {
+        geometry.column(k1.key_index) < geometry.column(k2.key_index)
     } else {
-        k1.key.matrix_position.0 > k2.key.matrix_position.0
+        geometry.column(k1.key_index) > geometry.column(k2.key_index)
     }
 }
 
@@ -54,11 +55,11 @@ impl TrigramMetric for OxeyOnehands {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        let h1 = k1.key.hand;
-        let h2 = k2.key.hand;
-        let h3 = k3.key.hand;
+        let h1 = layout.geometry().hand(k1.key_index);
+        let h2 = layout.geometry().hand(k2.key_index);
+        let h3 = layout.geometry().hand(k3.key_index);
 
         if self.exclude_modifiers
             && (k1.is_modifier.is_some() || k2.is_modifier.is_some() || k3.is_modifier.is_some())
@@ -78,9 +79,9 @@ impl TrigramMetric for OxeyOnehands {
             return Some(0.0);
         }
 
-        let f1 = k1.key.finger;
-        let f2 = k2.key.finger;
-        let f3 = k3.key.finger;
+        let f1 = layout.geometry().finger(k1.key_index);
+        let f2 = layout.geometry().finger(k2.key_index);
+        let f3 = layout.geometry().finger(k3.key_index);
 
         if self.exclude_thumbs
             && (f1 == Finger::Thumb || f2 == Finger::Thumb || f3 == Finger::Thumb)
@@ -93,11 +94,11 @@ impl TrigramMetric for OxeyOnehands {
             return Some(0.0);
         }
 
-        let inwards1 = inwards(k1, k2);
-        let inwards2 = inwards(k2, k3);
+        let inwards1 = inwards(layout.geometry(), k1, k2);
+        let inwards2 = inwards(layout.geometry(), k2, k3);
 
-        let outwards1 = inwards(k2, k1);
-        let outwards2 = inwards(k3, k2);
+        let outwards1 = inwards(layout.geometry(), k2, k1);
+        let outwards2 = inwards(layout.geometry(), k3, k2);
 
         if (inwards1 && inwards2) || (outwards1 && outwards2) {
             Some(weight)
@@ -0,0 +1,152 @@
+//! The trigram metric [`Pivots`] counts same-hand trigrams for which the horizontal direction
+//! of movement reverses (like in a redirect) and at least one of the two movements crosses rows.
+//! These pivot the wrist. The cost is the trigram's weight multiplied by the total number of
+//! crossed rows and a configurable factor.
+//!
+//! *Note:* Pivots overlap with redirects. If `exclude_redirects` is set, trigrams that are
+//! already counted by one of the enabled redirect metrics
+//! ([`OxeyRedirects`](super::oxey_redirects::OxeyRedirects) and
+//! [`OxeyBadRedirects`](super::oxey_bad_redirects::OxeyBadRedirects), with their own exclusions
+//! applied) are skipped.
+
+use super::{oxey_bad_redirects::OxeyBadRedirects, oxey_redirects::OxeyRedirects, TrigramMetric};
+
+use keyboard_layout::{
+    geometry::KeyGeometry,
+    key::Hand,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Multiply the cost by this factor
+    pub factor: f64,
+    /// Exclude trigrams involving thumbs
+    pub exclude_thumbs: bool,
+    /// Exclude trigrams involving modifiers
+    pub exclude_modifiers: bool,
+    /// Exclude trigrams that are also counted as redirects
+    pub exclude_redirects: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pivots {
+    factor: f64,
+    exclude_thumbs: bool,
+    exclude_modifiers: bool,
+    redirects: Option<OxeyRedirects>,
+    bad_redirects: Option<OxeyBadRedirects>,
+}
+
+impl Pivots {
+    /// The redirect metrics are the enabled ones of the evaluation. They are only used if
+    /// `exclude_redirects` is set.
+    pub fn new(
+        params: &Parameters,
+        redirects: Option<OxeyRedirects>,
+        bad_redirects: Option<OxeyBadRedirects>,
+    ) -> Self {
+        let (redirects, bad_redirects) = if params.exclude_redirects {
+            (redirects, bad_redirects)
+        } else {
+            (None, None)
+        };
+
+        Self {
+            factor: params.factor,
+            exclude_thumbs: params.exclude_thumbs,
+            exclude_modifiers: params.exclude_modifiers,
+            redirects,
+            bad_redirects,
+        }
+    }
+
+    /// Whether the trigram is already counted by one of the redirect metrics.
+    #[inline(always)]
+    fn is_redirect(&self, k1: &LayerKey, k2: &LayerKey, k3: &LayerKey, layout: &Layout) -> bool {
+        self.redirects
+            .as_ref()
+            .map_or(false, |m| m.is_redirect(k1, k2, k3, layout))
+            || self
+                .bad_redirects
+                .as_ref()
+                .map_or(false, |m| m.is_redirect(k1, k2, k3, layout))
+    }
+}
+
+/// Horizontal direction of the movement from `k1` to `k2` (`Greater` means inwards).
+#[inline(always)]
+fn direction(geometry: &KeyGeometry, k1: &LayerKey, k2: &LayerKey) -> Ordering {
+    let dir = geometry
+        .column(k2.key_index)
+        .cmp(&geometry.column(k1.key_index));
+    if geometry.hand(k1.key_index) == Hand::Left {
+        dir
+    } else {
+        dir.reverse()
+    }
+}
+
+impl TrigramMetric for Pivots {
+    fn name(&self) -> &str {
+        "Pivots"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if !(layout.geometry().hand(k1.key_index) == layout.geometry().hand(k2.key_index)
+            && layout.geometry().hand(k2.key_index) == layout.geometry().hand(k3.key_index))
+        {
+            return Some(0.0);
+        }
+
+        if self.exclude_modifiers
+            && (k1.is_modifier.is_some() || k2.is_modifier.is_some() || k3.is_modifier.is_some())
+        {
+            return Some(0.0);
+        }
+
+        if self.exclude_thumbs
+            && (layout.geometry().is_thumb(k1.key_index)
+                || layout.geometry().is_thumb(k2.key_index)
+                || layout.geometry().is_thumb(k3.key_index))
+        {
+            return Some(0.0);
+        }
+
+        let dir1 = direction(layout.geometry(), k1, k2);
+        let dir2 = direction(layout.geometry(), k2, k3);
+        if dir1 == Ordering::Equal || dir2 == Ordering::Equal || dir1 == dir2 {
+            return Some(0.0);
+        }
+
+        let row_distance = layout
+            .geometry()
+            .row(k1.key_index)
+            .abs_diff(layout.geometry().row(k2.key_index))
+            + layout
+                .geometry()
+                .row(k2.key_index)
+                .abs_diff(layout.geometry().row(k3.key_index));
+        if row_distance == 0 {
+            return Some(0.0);
+        }
+
+        if self.is_redirect(k1, k2, k3, layout) {
+            return Some(0.0);
+        }
+
+        Some(weight * row_distance as f64 * self.factor)
+    }
+}
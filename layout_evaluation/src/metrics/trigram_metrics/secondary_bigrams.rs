@@ -1,6 +1,10 @@
 //! The trigram metric [`SecondaryBigrams`] takes the first and last symbol of a trigram
 //! and evaluates it with all configured bigram metrics that can assign costs to
 //! individual bigrams (`individual_cost` does not return `None`).
+//!
+//! If the ngram mapper derives the secondary bigrams (see
+//! [`NgramMapperConfig::secondary_bigrams`](crate::ngram_mapper::on_demand_ngram_mapper::NgramMapperConfig::secondary_bigrams)),
+//! the metric evaluates those and its own factors and pause indicators are not used.
 
 use super::TrigramMetric;
 use crate::metrics::bigram_metrics::BigramMetric;
@@ -10,6 +14,7 @@ use crate::results::NormalizationType;
 use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
+use std::env;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {
@@ -41,6 +46,27 @@ impl SecondaryBigrams {
             initial_pause_indicators: params.initial_pause_indicators.clone(),
         }
     }
+
+    /// Sum of the weighted costs of the bigram metrics for the bigram.
+    #[inline(always)]
+    fn bigram_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        total_weight: f64,
+        layout: &Layout,
+    ) -> f64 {
+        self.bigram_metrics
+            .iter()
+            .map(|(metric_weight, _, metric)| {
+                metric_weight
+                    * metric
+                        .individual_cost(k1, k2, weight, total_weight, layout)
+                        .unwrap_or(0.0)
+            })
+            .sum()
+    }
 }
 
 impl TrigramMetric for SecondaryBigrams {
@@ -48,6 +74,10 @@ impl TrigramMetric for SecondaryBigrams {
         "Secondary Bigrams"
     }
 
+    fn includes_filtered_weight(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -74,24 +104,62 @@ impl TrigramMetric for SecondaryBigrams {
             return Some(0.0);
         }
 
-        let factor = if k1.key.hand == k2.key.hand && k2.key.hand == k3.key.hand {
+        let factor = if layout.geometry().hand(k1.key_index) == layout.geometry().hand(k2.key_index)
+            && layout.geometry().hand(k2.key_index) == layout.geometry().hand(k3.key_index)
+        {
             self.factor_no_handswitch
         } else {
             self.factor_handswitch
         };
 
-        let cost: f64 = self
-            .bigram_metrics
+        Some(factor * self.bigram_cost(k1, k3, weight, total_weight, layout))
+    }
+
+    fn total_cost_with_secondary_bigrams(
+        &self,
+        secondary_bigrams: &[((&LayerKey, &LayerKey), f64)],
+        total_weight: f64,
+        layout: &Layout,
+    ) -> Option<(f64, Option<String>)> {
+        let show_worst: bool = env::var("SHOW_WORST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(true);
+        let n_worst: usize = env::var("N_WORST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+
+        // the factors have been applied to the weights by the ngram mapper already
+        let costs: Vec<(&(&LayerKey, &LayerKey), f64)> = secondary_bigrams
             .iter()
-            .map(|(metric_weight, _, metric)| {
-                factor
-                    * metric_weight
-                    * metric
-                        .individual_cost(k1, k3, weight, total_weight, layout)
-                        .unwrap_or(0.0)
+            .map(|(bigram, weight)| {
+                let cost = self.bigram_cost(bigram.0, bigram.1, *weight, total_weight, layout);
+                (bigram, cost)
+            })
+            .collect();
+        let total_cost: f64 = costs.iter().map(|(_, c)| c).sum();
+
+        if !show_worst {
+            return Some((total_cost, None));
+        }
+
+        let mut worst: Vec<_> = costs.iter().filter(|(_, c)| *c > 0.0).collect();
+        worst.sort_by(|(_, c1), (_, c2)| c2.partial_cmp(c1).unwrap());
+        let worst_msgs: Vec<String> = worst
+            .iter()
+            .take(n_worst)
+            .map(|(bigram, cost)| {
+                format!(
+                    "{}{} ({:>5.2}%)",
+                    bigram.0,
+                    bigram.1,
+                    100.0 * cost / total_cost
+                )
             })
-            .sum();
+            .collect();
+        let msg = (!worst_msgs.is_empty()).then(|| format!("Worst: {}", worst_msgs.join(", ")));
 
-        Some(cost)
+        Some((total_cost, msg))
     }
 }
@@ -0,0 +1,87 @@
+//! The trigram metric [`ThumbAlternation`] counts trigrams in which a thumb holds a layer
+//! modifier and the same thumb has to press a regular (non-modifier) key immediately before or
+//! after. Releasing a layer and tapping a neighboring key (or the other way around) with the
+//! same thumb is awkward if both thumbs hold layers.
+//!
+//! Each such pair of keys within the trigram is counted. Pairs that are separated by the
+//! trigram's middle key are only counted if that key is typed by the same hand, as the other
+//! hand gives the thumb time to move.
+
+use super::TrigramMetric;
+
+use keyboard_layout::geometry::KeyGeometry;
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Multiply the cost by this factor if the hold and the key press are consecutive
+    pub factor_adjacent: f64,
+    /// Multiply the cost by this factor if the hold and the key press are separated by a key
+    /// of the same hand
+    pub factor_separated: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ThumbAlternation {
+    factor_adjacent: f64,
+    factor_separated: f64,
+}
+
+impl ThumbAlternation {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            factor_adjacent: params.factor_adjacent,
+            factor_separated: params.factor_separated,
+        }
+    }
+}
+
+/// Whether one of the keys is a layer hold on a thumb and the other one a regular key press of
+/// the same thumb.
+#[inline(always)]
+fn is_thumb_conflict(geometry: &KeyGeometry, k1: &LayerKey, k2: &LayerKey) -> bool {
+    if !(geometry.is_thumb(k1.key_index)
+        && geometry.is_thumb(k2.key_index)
+        && geometry.hand(k1.key_index) == geometry.hand(k2.key_index))
+    {
+        return false;
+    }
+
+    (k1.is_modifier.is_held() && k2.is_modifier.is_none())
+        || (k1.is_modifier.is_none() && k2.is_modifier.is_held())
+}
+
+impl TrigramMetric for ThumbAlternation {
+    fn name(&self) -> &str {
+        "Thumb Alternation"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        let mut factor = 0.0;
+
+        if is_thumb_conflict(layout.geometry(), k1, k2) {
+            factor += self.factor_adjacent;
+        }
+        if is_thumb_conflict(layout.geometry(), k2, k3) {
+            factor += self.factor_adjacent;
+        }
+        if layout.geometry().hand(k2.key_index) == layout.geometry().hand(k1.key_index)
+            && is_thumb_conflict(layout.geometry(), k1, k3)
+        {
+            factor += self.factor_separated;
+        }
+
+        Some(weight * factor)
+    }
+}
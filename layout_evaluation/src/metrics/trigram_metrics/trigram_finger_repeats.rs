@@ -6,10 +6,7 @@
 
 use super::TrigramMetric;
 
-use keyboard_layout::{
-    key::Finger,
-    layout::{LayerKey, Layout},
-};
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use serde::Deserialize;
 
@@ -45,15 +42,15 @@ impl TrigramMetric for TrigramFingerRepeats {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        let hand1 = k1.key.hand;
-        let hand2 = k2.key.hand;
-        let hand3 = k3.key.hand;
+        let hand1 = layout.geometry().hand(k1.key_index);
+        let hand2 = layout.geometry().hand(k2.key_index);
+        let hand3 = layout.geometry().hand(k3.key_index);
 
-        let finger1 = k1.key.finger;
-        let finger2 = k2.key.finger;
-        let finger3 = k3.key.finger;
+        let finger1 = layout.geometry().finger(k1.key_index);
+        let finger2 = layout.geometry().finger(k2.key_index);
+        let finger3 = layout.geometry().finger(k3.key_index);
 
         // exclude key repititions
         if k1 == k2 || k2 == k3 {
@@ -69,13 +66,13 @@ impl TrigramMetric for TrigramFingerRepeats {
             return Some(0.0);
         }
 
-        let pos1 = k1.key.matrix_position;
-        let pos2 = k2.key.matrix_position;
-        let pos3 = k3.key.matrix_position;
+        let pos1 = layout.geometry().matrix_position(k1.key_index);
+        let pos2 = layout.geometry().matrix_position(k2.key_index);
+        let pos3 = layout.geometry().matrix_position(k3.key_index);
 
         let mut cost = weight;
 
-        if k1.key.finger != Finger::Thumb {
+        if !layout.geometry().is_thumb(k1.key_index) {
             if pos1.0 != pos2.0 {
                 cost *= self.factor_lateral_movement;
             }
@@ -47,20 +47,24 @@ impl TrigramMetric for TrigramRolls {
         k3: &LayerKey,
         weight: f64,
         _total_weight: f64,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> Option<f64> {
-        if k1.key.hand != k2.key.hand || k2.key.hand != k3.key.hand {
+        if layout.geometry().hand(k1.key_index) != layout.geometry().hand(k2.key_index)
+            || layout.geometry().hand(k2.key_index) != layout.geometry().hand(k3.key_index)
+        {
             return Some(0.0);
         };
 
         // finger repeats are not considered rolls
-        if k1.key.finger == k2.key.finger || k2.key.finger == k3.key.finger {
+        if layout.geometry().finger(k1.key_index) == layout.geometry().finger(k2.key_index)
+            || layout.geometry().finger(k2.key_index) == layout.geometry().finger(k3.key_index)
+        {
             return Some(0.0);
         }
 
-        let pos1 = k1.key.matrix_position;
-        let pos2 = k2.key.matrix_position;
-        let pos3 = k3.key.matrix_position;
+        let pos1 = layout.geometry().matrix_position(k1.key_index);
+        let pos2 = layout.geometry().matrix_position(k2.key_index);
+        let pos3 = layout.geometry().matrix_position(k3.key_index);
 
         // exclude rolls with keys in exclude_rows
         if self.exclude_rows.contains(&pos1.1)
@@ -76,17 +80,17 @@ impl TrigramMetric for TrigramRolls {
         }
 
         // only allow rolls with keys that are directly next to each others
-        let inward1 = (k1.key.hand == Hand::Left && pos1.0 == pos2.0 - 1)
-            || (k1.key.hand == Hand::Right && pos1.0 == pos2.0 + 1);
+        let inward1 = (layout.geometry().hand(k1.key_index) == Hand::Left && pos1.0 == pos2.0 - 1)
+            || (layout.geometry().hand(k1.key_index) == Hand::Right && pos1.0 == pos2.0 + 1);
 
-        let inward2 = (k2.key.hand == Hand::Left && pos2.0 == pos3.0 - 1)
-            || (k2.key.hand == Hand::Right && pos2.0 == pos3.0 + 1);
+        let inward2 = (layout.geometry().hand(k2.key_index) == Hand::Left && pos2.0 == pos3.0 - 1)
+            || (layout.geometry().hand(k2.key_index) == Hand::Right && pos2.0 == pos3.0 + 1);
 
-        let outward1 = (k1.key.hand == Hand::Left && pos1.0 == pos2.0 + 1)
-            || (k1.key.hand == Hand::Right && pos1.0 == pos2.0 - 1);
+        let outward1 = (layout.geometry().hand(k1.key_index) == Hand::Left && pos1.0 == pos2.0 + 1)
+            || (layout.geometry().hand(k1.key_index) == Hand::Right && pos1.0 == pos2.0 - 1);
 
-        let outward2 = (k2.key.hand == Hand::Left && pos2.0 == pos3.0 + 1)
-            || (k2.key.hand == Hand::Right && pos2.0 == pos3.0 - 1);
+        let outward2 = (layout.geometry().hand(k2.key_index) == Hand::Left && pos2.0 == pos3.0 + 1)
+            || (layout.geometry().hand(k2.key_index) == Hand::Right && pos2.0 == pos3.0 - 1);
 
         // both bigrams need to have the same direction
         let mut cost = if inward1 && inward2 {
@@ -7,6 +7,7 @@ use std::{env, fmt};
 
 pub mod finger_balance;
 pub mod hand_disbalance;
+pub mod home_row_anchor;
 pub mod key_costs;
 pub mod modifier_usage;
 pub mod row_loads;
@@ -16,6 +17,11 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
     /// Return the name of the metric
     fn name(&self) -> &str;
 
+    /// Approximate number of bytes of the data held by the metric (e.g. lookup tables)
+    fn estimated_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
     /// Compute the cost of one unigram (if that is possible, otherwise, return `None`).
     #[inline(always)]
     fn individual_cost(
@@ -28,6 +34,21 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
         None
     }
 
+    /// Compute the total cost for the metric from the exact number of presses of each modifier
+    /// as counted when splitting the ngrams (if the metric is based on them, otherwise, return `None`).
+    ///
+    /// If `None` is returned or the modifier presses are not available, [`UnigramMetric::total_cost`]
+    /// is used instead.
+    fn total_cost_with_modifier_presses(
+        &self,
+        _unigrams: &[(&LayerKey, f64)],
+        _modifier_presses: &[(&LayerKey, f64)],
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<(f64, Option<String>)> {
+        None
+    }
+
     /// Compute the total cost for the metric.
     fn total_cost(
         &self,
@@ -2,6 +2,9 @@
 //! per finger with configurable intended finger loads. The metric costs come from
 //! discrepancies which are computed based on a standard deviation computation.
 //!
+//! The intended loads are scaled with the fingers' capabilities given by the keyboard. If no
+//! intended loads are configured, they are given by the capabilities alone.
+//!
 //! *Note:* In contrast to ArneBab's version of the metric, thumb keys are excluded
 //! from the discrepancy computation.
 
@@ -17,27 +20,44 @@ use serde::Deserialize;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {
-    pub intended_loads: AHashMap<(Hand, Finger), f64>,
+    /// Intended loads per finger (overriding the keyboard's finger capabilities)
+    #[serde(default)]
+    pub intended_loads: Option<AHashMap<(Hand, Finger), f64>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct FingerBalance {
-    intended_loads: AHashMap<(Hand, Finger), f64>,
+    intended_loads: Option<AHashMap<(Hand, Finger), f64>>,
 }
 
 impl FingerBalance {
     pub fn new(params: &Parameters) -> Self {
-        // normalize intended loads
-        let total_intended = params
-            .intended_loads
+        Self {
+            intended_loads: params.intended_loads.clone(),
+        }
+    }
+
+    /// The normalized intended loads of the non-thumb fingers, scaled with their capabilities
+    fn intended_loads(&self, layout: &Layout) -> Vec<((Hand, Finger), f64)> {
+        let capabilities = &layout.keyboard.finger_capabilities;
+        let intended_loads: Vec<((Hand, Finger), f64)> = HandFingerMap::<f64>::keys()
             .iter()
-            .filter(|((_h, f), _l)| *f != Finger::Thumb)
-            .fold(0.0, |acc, (_, l)| acc + l);
-        let mut intended_loads = params.intended_loads.clone();
-        intended_loads.values_mut().for_each(|l| {
-            *l /= total_intended;
-        });
-        Self { intended_loads }
+            .filter(|(_h, f)| *f != Finger::Thumb)
+            .filter_map(|(h, f)| {
+                let load = match &self.intended_loads {
+                    Some(loads) => *loads.get(&(*h, *f))?,
+                    None => 1.0,
+                };
+                Some(((*h, *f), load * capabilities.get(h, f)))
+            })
+            .collect();
+
+        // sum in a fixed order (instead of the map's), so that the result is deterministic
+        let total_intended = intended_loads.iter().fold(0.0, |acc, (_, l)| acc + l);
+        intended_loads
+            .into_iter()
+            .map(|(k, l)| (k, l / total_intended))
+            .collect()
     }
 }
 
@@ -50,7 +70,7 @@ impl UnigramMetric for FingerBalance {
         &self,
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> (f64, Option<String>) {
         let mut finger_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
@@ -59,36 +79,39 @@ impl UnigramMetric for FingerBalance {
         // is a fixed key anyways
         unigrams
             .iter()
-            .filter(|(key, _weight)| key.key.finger != Finger::Thumb)
+            .filter(|(key, _weight)| !layout.geometry().is_thumb(key.key_index))
             .for_each(|(key, weight)| {
-                *finger_loads.get_mut(&key.key.hand, &key.key.finger) += *weight;
+                *finger_loads.get_mut(
+                    &layout.geometry().hand(key.key_index),
+                    &layout.geometry().finger(key.key_index),
+                ) += *weight;
             });
         let total_weight: f64 = finger_loads.iter().sum();
+        // without (non-thumb) key presses, there is no imbalance
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
 
         // A version more similar to ArneBab's solution using the standard deviation
         let fractions: Vec<f64> = self
-            .intended_loads
+            .intended_loads(layout)
             .iter()
-            .filter(|((_hand, finger), _intended_load)| *finger != Finger::Thumb)
             .map(|((hand, finger), intended_load)| {
                 let load = finger_loads.get(hand, finger) / total_weight;
-                log::trace!(
-                    "Finger: {:>13}, Intended: {:>5.2}, Load: {:>5.2}, Fraction: {:>.4}",
-                    format!("{:?} {:?}", hand, finger),
-                    100.0 * intended_load,
-                    100.0 * load,
-                    load / intended_load,
-                );
                 load / intended_load
             })
             .collect();
 
         let mean: f64 = fractions.iter().sum::<f64>() / fractions.len() as f64;
-        let var = fractions
-            .iter()
-            .map(|f| (f - mean) * (f - mean))
-            .sum::<f64>()
-            / (fractions.len() - 1) as f64;
+        let var = if fractions.len() > 1 {
+            fractions
+                .iter()
+                .map(|f| (f - mean) * (f - mean))
+                .sum::<f64>()
+                / (fractions.len() - 1) as f64
+        } else {
+            0.0
+        };
 
         let message = format!(
             "Finger loads % (no thumb): {:.1} {:.1} {:.1} {:.1} - {:.1} {:.1} {:.1} {:.1}",
@@ -4,7 +4,7 @@
 use super::UnigramMetric;
 
 use keyboard_layout::{
-    key::{Finger, Hand, HandMap},
+    key::{Hand, HandMap},
     layout::{LayerKey, Layout},
 };
 
@@ -31,18 +31,23 @@ impl UnigramMetric for HandDisbalance {
         &self,
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> (f64, Option<String>) {
         let mut hand_loads: HandMap<f64> = HandMap::default();
         let mut total_weight = 0.0;
         unigrams
             .iter()
-            .filter(|(key, _weight)| key.key.finger != Finger::Thumb)
+            .filter(|(key, _weight)| !layout.geometry().is_thumb(key.key_index))
             .for_each(|(key, weight)| {
-                *hand_loads.get_mut(&key.key.hand) += *weight;
+                *hand_loads.get_mut(&layout.geometry().hand(key.key_index)) += *weight;
                 total_weight += *weight;
             });
 
+        // without (non-thumb) key presses, there is no disbalance
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
         let left_fraction = hand_loads.get(&Hand::Left) / total_weight;
         let right_fraction = hand_loads.get(&Hand::Right) / total_weight;
 
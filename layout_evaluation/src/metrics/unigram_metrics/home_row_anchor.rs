@@ -0,0 +1,157 @@
+//! The unigram metric [`HomeRowAnchor`] compares the load on each finger's home key with the
+//! finger's total load. A finger whose home key is hardly used tends to hover and drift away
+//! from its resting position. If the share of the home key falls short of a configurable minimum,
+//! the missing weight (multiplied by a per-finger weight) is counted as cost.
+//!
+//! The home key of a finger is the key at the finger's resting position of the keyboard unless
+//! it is configured explicitly.
+
+use super::UnigramMetric;
+
+use keyboard_layout::{
+    key::{Finger, Hand, HandFingerMap, MatrixPosition},
+    layout::{LayerKey, Layout},
+};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+
+/// Order of the fingers in the message (from left to right)
+const FINGERS: [(Hand, Finger); 10] = [
+    (Hand::Left, Finger::Pinky),
+    (Hand::Left, Finger::Ring),
+    (Hand::Left, Finger::Middle),
+    (Hand::Left, Finger::Index),
+    (Hand::Left, Finger::Thumb),
+    (Hand::Right, Finger::Thumb),
+    (Hand::Right, Finger::Index),
+    (Hand::Right, Finger::Middle),
+    (Hand::Right, Finger::Ring),
+    (Hand::Right, Finger::Pinky),
+];
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Minimum share of each finger's load that shall be on its home key (fingers that are not
+    /// listed have no minimum)
+    pub min_home_shares: AHashMap<(Hand, Finger), f64>,
+    /// Weight of the shortfall for each finger (fingers that are not listed have weight 1)
+    #[serde(default)]
+    pub finger_weights: AHashMap<(Hand, Finger), f64>,
+    /// Home keys (matrix positions) overriding the ones at the fingers' resting positions
+    #[serde(default)]
+    pub home_keys: AHashMap<(Hand, Finger), MatrixPosition>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HomeRowAnchor {
+    min_home_shares: HandFingerMap<f64>,
+    finger_weights: HandFingerMap<f64>,
+    home_keys: AHashMap<(Hand, Finger), MatrixPosition>,
+    /// If any finger has a minimum share (otherwise, the metric is a no-op)
+    active: bool,
+}
+
+impl HomeRowAnchor {
+    pub fn new(params: &Parameters) -> Self {
+        let mut min_home_shares = HandFingerMap::with_default(0.0);
+        params
+            .min_home_shares
+            .iter()
+            .for_each(|((hand, finger), share)| min_home_shares.set(hand, finger, *share));
+        let mut finger_weights = HandFingerMap::with_default(1.0);
+        params
+            .finger_weights
+            .iter()
+            .for_each(|((hand, finger), weight)| finger_weights.set(hand, finger, *weight));
+
+        Self {
+            min_home_shares,
+            finger_weights,
+            home_keys: params.home_keys.clone(),
+            active: params.min_home_shares.values().any(|share| *share > 0.0),
+        }
+    }
+
+    /// If the key is the home key of its finger.
+    fn is_home_key(&self, key: &LayerKey, layout: &Layout) -> bool {
+        let k = &key.key;
+        match self.home_keys.get(&(k.hand, k.finger)) {
+            Some(matrix_position) => k.matrix_position == *matrix_position,
+            None => {
+                k.position
+                    == *layout
+                        .keyboard
+                        .finger_resting_positions
+                        .get(&k.hand, &k.finger)
+            }
+        }
+    }
+}
+
+impl UnigramMetric for HomeRowAnchor {
+    fn name(&self) -> &str {
+        "Home Row Anchor"
+    }
+
+    fn total_cost(
+        &self,
+        unigrams: &[(&LayerKey, f64)],
+        _total_weight: Option<f64>,
+        layout: &Layout,
+    ) -> (f64, Option<String>) {
+        if !self.active {
+            return (0.0, None);
+        }
+
+        let mut finger_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        let mut home_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        unigrams.iter().for_each(|(key, weight)| {
+            *finger_loads.get_mut(
+                &layout.geometry().hand(key.key_index),
+                &layout.geometry().finger(key.key_index),
+            ) += *weight;
+            if self.is_home_key(key, layout) {
+                *home_loads.get_mut(
+                    &layout.geometry().hand(key.key_index),
+                    &layout.geometry().finger(key.key_index),
+                ) += *weight;
+            }
+        });
+
+        let mut cost = 0.0;
+        let mut shares = Vec::new();
+        for (hand, finger) in FINGERS.iter() {
+            let load = *finger_loads.get(hand, finger);
+            // a finger without any load does not drift
+            if load <= 0.0 {
+                continue;
+            }
+
+            let home_load = *home_loads.get(hand, finger);
+            let min_share = *self.min_home_shares.get(hand, finger);
+            let shortfall = (min_share * load - home_load).max(0.0);
+            cost += self.finger_weights.get(hand, finger) * shortfall;
+
+            let hand_name = match hand {
+                Hand::Left => "L",
+                Hand::Right => "R",
+            };
+            shares.push(format!(
+                "{} {:?} {:.1}%{}",
+                hand_name,
+                finger,
+                100.0 * home_load / load,
+                if shortfall > 0.0 {
+                    format!(" (< {:.0}%)", 100.0 * min_share)
+                } else {
+                    String::new()
+                },
+            ));
+        }
+
+        let message = format!("Home key share per finger: {}", shares.join(", "));
+
+        (cost, Some(message))
+    }
+}
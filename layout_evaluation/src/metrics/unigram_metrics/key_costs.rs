@@ -30,7 +30,7 @@ impl UnigramMetric for KeyCost {
         &self,
         key: &LayerKey,
         weight: f64,
-        total_weight: f64,
+        _total_weight: f64,
         layout: &Layout,
     ) -> Option<f64> {
         let modifier_cost: f64 = key
@@ -41,12 +41,6 @@ impl UnigramMetric for KeyCost {
             .sum();
         let cost = key.key.cost + modifier_cost;
 
-        // log the top scorers (with weight > 1%)
-        if weight > 0.01 * total_weight {
-            log::trace!("Unigram: {:>3}, Finger: {:<13}, Weight: {:>12.2}, Cost per key: {:>8.4}, Cost: {:>14.4}",
-                        key, format!("{:?} {:?}", key.key.hand, key.key.finger), weight, cost, weight * cost);
-        }
-
         Some(weight * cost)
     }
 }
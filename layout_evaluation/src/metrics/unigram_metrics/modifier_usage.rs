@@ -1,4 +1,8 @@
 //! The unigram metric [`ModifierUsage`] sums each modifier unigram's weight
+//!
+//! If available, the exact number of modifier presses counted during the splitting of the
+//! ngrams is used instead of the split unigrams. The message reports the number of modifier
+//! presses per 1000 keystrokes.
 
 use super::UnigramMetric;
 
@@ -11,6 +15,10 @@ pub struct Parameters {
     pub hold_cost: f64,
     pub one_shot_cost: f64,
     pub long_press_cost: f64,
+    /// Cost of tapping the toggle of a lock layer (only counted if the toggles are inserted into
+    /// the ngrams, see `lock_toggles` of the `split_modifiers` config)
+    #[serde(default)]
+    pub lock_cost: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -18,6 +26,7 @@ pub struct ModifierUsage {
     pub hold_cost: f64,
     pub one_shot_cost: f64,
     pub long_press_cost: f64,
+    pub lock_cost: f64,
 }
 
 impl ModifierUsage {
@@ -26,8 +35,42 @@ impl ModifierUsage {
             hold_cost: params.hold_cost,
             one_shot_cost: params.one_shot_cost,
             long_press_cost: params.long_press_cost,
+            lock_cost: params.lock_cost,
         }
     }
+
+    /// Cost of a press of the key if it is a modifier
+    fn modifier_key_cost(&self, key: &LayerKey) -> f64 {
+        match key.is_modifier {
+            // the members of a chord are held as well
+            LayerModifierType::Hold | LayerModifierType::Chord | LayerModifierType::ModTap => {
+                self.hold_cost
+            }
+            LayerModifierType::OneShot => self.one_shot_cost,
+            LayerModifierType::LongPress => self.long_press_cost,
+            LayerModifierType::Lock => self.lock_cost,
+            _ => 0.0,
+        }
+    }
+
+    /// Cost of the modifiers the key relies on (that were not split in ngram splitting)
+    fn unsplit_modifiers_cost(&self, key: &LayerKey) -> f64 {
+        match &key.modifiers {
+            LayerModifiers::Hold(v) | LayerModifiers::Chord(v) => self.hold_cost * v.len() as f64,
+            LayerModifiers::OneShot(v) => self.one_shot_cost * v.len() as f64,
+            LayerModifiers::LongPress => self.long_press_cost,
+            // symbols of lock layers are mapped to their base-layer keys
+            LayerModifiers::Lock(_) => 0.0,
+        }
+    }
+}
+
+/// Number of presses of modifiers the key relies on (that were not split in ngram splitting)
+fn unsplit_modifier_presses(key: &LayerKey) -> usize {
+    match &key.modifiers {
+        LayerModifiers::Hold(v) | LayerModifiers::OneShot(v) | LayerModifiers::Chord(v) => v.len(),
+        LayerModifiers::LongPress | LayerModifiers::Lock(_) => 0,
+    }
 }
 
 impl UnigramMetric for ModifierUsage {
@@ -43,21 +86,51 @@ impl UnigramMetric for ModifierUsage {
         _total_weight: f64,
         _layout: &Layout,
     ) -> Option<f64> {
-        // costs if this key is a modifier
-        let key_cost = match key.is_modifier {
-            LayerModifierType::Hold => self.hold_cost,
-            LayerModifierType::OneShot => self.one_shot_cost,
-            LayerModifierType::LongPress => self.long_press_cost,
-            _ => 0.0,
-        };
+        Some(weight * (self.modifier_key_cost(key) + self.unsplit_modifiers_cost(key)))
+    }
 
-        // costs if this key relies on modifiers (that were not split in ngram splitting)
-        let modifier_costs = match &key.modifiers {
-            LayerModifiers::Hold(v) => self.hold_cost * v.len() as f64,
-            LayerModifiers::OneShot(v) => self.one_shot_cost * v.len() as f64,
-            LayerModifiers::LongPress => self.long_press_cost,
+    fn total_cost_with_modifier_presses(
+        &self,
+        unigrams: &[(&LayerKey, f64)],
+        modifier_presses: &[(&LayerKey, f64)],
+        total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<(f64, Option<String>)> {
+        // modifiers that were split off are counted exactly once per symbol requiring them
+        // (instead of per split unigram they appear in)
+        let split_cost: f64 = modifier_presses
+            .iter()
+            .map(|(key, weight)| weight * self.modifier_key_cost(key))
+            .sum();
+        let unsplit_cost: f64 = unigrams
+            .iter()
+            .map(|(key, weight)| weight * self.unsplit_modifiers_cost(key))
+            .sum();
+
+        let n_split: f64 = modifier_presses.iter().map(|(_, w)| w).sum();
+        let n_unsplit: f64 = unigrams
+            .iter()
+            .map(|(key, weight)| weight * unsplit_modifier_presses(key) as f64)
+            .sum();
+        let n_keystrokes = total_weight + n_unsplit;
+        let per_mille = if n_keystrokes > 0.0 {
+            1000.0 * (n_split + n_unsplit) / n_keystrokes
+        } else {
+            0.0
         };
 
-        Some(weight * (key_cost + modifier_costs))
+        let mut presses: Vec<_> = modifier_presses.to_vec();
+        presses.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+        let presses_msgs: Vec<String> = presses
+            .iter()
+            .map(|(key, w)| format!("{} ({:.1}‰)", key, 1000.0 * w / n_keystrokes))
+            .collect();
+
+        let mut msg = format!("{:.1} modifier presses per 1000 keystrokes", per_mille);
+        if !presses_msgs.is_empty() {
+            msg.push_str(&format!(": {}", presses_msgs.join(", ")));
+        }
+
+        Some((split_cost + unsplit_cost, Some(msg)))
     }
 }
@@ -5,10 +5,7 @@
 use super::UnigramMetric;
 
 use itertools::Itertools;
-use keyboard_layout::{
-    key::Finger,
-    layout::{LayerKey, Layout},
-};
+use keyboard_layout::layout::{LayerKey, Layout};
 
 use ahash::AHashMap;
 use serde::Deserialize;
@@ -34,15 +31,17 @@ impl UnigramMetric for RowLoads {
         &self,
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
-        _layout: &Layout,
+        layout: &Layout,
     ) -> (f64, Option<String>) {
         let mut row_load: AHashMap<u8, f64> = AHashMap::default();
         let mut total_weight = 0.0;
         unigrams
             .iter()
-            .filter(|(key, _weight)| !key.is_fixed && key.key.finger != Finger::Thumb)
+            .filter(|(key, _weight)| !key.is_fixed && !layout.geometry().is_thumb(key.key_index))
             .for_each(|(key, weight)| {
-                *row_load.entry(key.key.matrix_position.1).or_insert(0.0) += *weight;
+                *row_load
+                    .entry(layout.geometry().row(key.key_index))
+                    .or_insert(0.0) += *weight;
                 total_weight += *weight;
             });
 
@@ -0,0 +1,127 @@
+//! The `ngram_cache` module provides a binary cache for parsed ngram frequency files.
+//!
+//! Parsing large ngram frequency files takes a while. After parsing such a file, the ngrams are
+//! stored in a binary "sidecar" file next to it (with `.bin` appended to its name). Subsequent
+//! loads deserialize the sidecar instead, as long as it is newer than the text file and the text
+//! file's size and modification time still match those recorded in the sidecar. Weights are
+//! stored as raw `f64` values, so they round-trip bit-for-bit.
+
+use crate::atomic_file;
+
+use ahash::AHashMap;
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    hash::Hash,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Version of the sidecar format (including the parsing of the ngram files). Sidecars of other
+/// versions are ignored.
+const CACHE_VERSION: u32 = 2;
+
+/// Properties of the text file a sidecar was generated from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SourceInfo {
+    len: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl SourceInfo {
+    fn of(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+        Ok(Self {
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        })
+    }
+}
+
+/// Path of the sidecar file of an ngram frequency file
+pub fn cache_path<T: AsRef<Path>>(filename: T) -> PathBuf {
+    let mut p = filename.as_ref().as_os_str().to_owned();
+    p.push(".bin");
+    PathBuf::from(p)
+}
+
+/// Load the ngrams from the sidecar of the given ngram frequency file if it is valid for the
+/// current state of the file.
+pub fn load_cached<K, T>(filename: T) -> Option<AHashMap<K, f64>>
+where
+    K: DeserializeOwned + Eq + Hash,
+    T: AsRef<Path>,
+{
+    let filename = filename.as_ref();
+    let source = SourceInfo::of(filename).ok()?;
+    let cache = cache_path(filename);
+
+    let cache_modified = fs::metadata(&cache).and_then(|m| m.modified()).ok()?;
+    let source_modified = fs::metadata(filename).and_then(|m| m.modified()).ok()?;
+    if cache_modified < source_modified {
+        return None;
+    }
+
+    let mut reader = BufReader::new(File::open(&cache).ok()?);
+    let (version, cached_source): (u32, SourceInfo) =
+        bincode::deserialize_from(&mut reader).ok()?;
+    if version != CACHE_VERSION || cached_source != source {
+        return None;
+    }
+
+    match bincode::deserialize_from(&mut reader) {
+        Ok(grams) => Some(grams),
+        Err(e) => {
+            log::warn!("Ignoring corrupt ngram cache {:?}: {}", cache, e);
+            None
+        }
+    }
+}
+
+/// Write the sidecar of the given ngram frequency file.
+pub fn store<K, T>(filename: T, grams: &AHashMap<K, f64>) -> Result<()>
+where
+    K: Serialize + Eq + Hash,
+    T: AsRef<Path>,
+{
+    let filename = filename.as_ref();
+    let source = SourceInfo::of(filename)?;
+
+    let mut data = bincode::serialize(&(CACHE_VERSION, source))?;
+    data.extend(bincode::serialize(grams)?);
+    atomic_file::write_atomic(cache_path(filename), data)?;
+
+    Ok(())
+}
+
+/// Read the ngrams of an ngram frequency file, using (and updating) its sidecar if `use_cache`
+/// is set. `parse` parses the file's content.
+pub fn load_or_parse<K, F>(filename: &str, use_cache: bool, parse: F) -> Result<AHashMap<K, f64>>
+where
+    K: Serialize + DeserializeOwned + Eq + Hash,
+    F: FnOnce(&str) -> Result<AHashMap<K, f64>>,
+{
+    if use_cache {
+        if let Some(grams) = load_cached(filename) {
+            log::debug!("Loaded ngrams of '{}' from cache", filename);
+            return Ok(grams);
+        }
+    }
+
+    let data = fs::read_to_string(filename)?;
+    let grams = parse(&data)?;
+
+    if use_cache {
+        if let Err(e) = store(filename, &grams) {
+            log::warn!("Could not write ngram cache for '{}': {}", filename, e);
+        }
+    }
+
+    Ok(grams)
+}
@@ -30,17 +30,33 @@
 //! of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
 //! former ones and modifers always come before their base key. The number of generated trigrams from a single
 //! trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
+//!
+//! Quadgrams are transformed analogously to trigrams. They are only mapped if a quadgram metric is active.
+//!
+//! Skipgrams are bigrams formed by the first and third symbol of each trigram (with a configurable decay
+//! of the trigram's weight). They are transformed like bigrams and only mapped if a metric consumes them.
 
 pub mod bigram_mapper;
 pub mod common;
+pub mod dump;
+pub mod mapping_cache;
+pub mod quadgram_mapper;
 pub mod trigram_mapper;
 pub mod unigram_mapper;
 
 pub mod on_demand_ngram_mapper;
+pub mod shared_ngram_mapper;
 
-use keyboard_layout::layout::{LayerKey, Layout};
+use crate::memory::{vec_bytes, MemoryReport};
+use crate::ngrams::{Unigrams, WordStarts};
+use common::{MissingSymbols, ShiftPolicy};
 
-use std::fmt;
+use keyboard_layout::layout::{LayerKey, LayerKeyIndex, Layout};
+
+use ahash::AHashSet;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
 
 /// Unigrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
 /// can not be generated by the layout.
@@ -49,8 +65,15 @@ pub struct MappedUnigrams<'s> {
     pub grams: Vec<(&'s LayerKey, f64)>,
     /// Total weight (frequencies) of unigrams that can not be generated by the layout
     pub weight_not_found: f64,
+    /// Weight (frequencies) of the unigrams that can not be generated by the layout per symbol (see
+    /// [`MissingSymbols`])
+    pub missing_symbols: MissingSymbols,
     /// Total weight (frequencies) of unigrams that can be generated by the layout
     pub weight_found: f64,
+    /// Exact number of presses of each modifier that was split off the source ngrams (one press
+    /// per modifier and symbol, independent of how many split ngrams contain it). `None` if the
+    /// grams were modified afterwards (e.g. resampled).
+    pub modifier_presses: Option<Vec<(&'s LayerKey, f64)>>,
 }
 
 /// Bigrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
@@ -60,26 +83,346 @@ pub struct MappedBigrams<'s> {
     pub grams: Vec<((&'s LayerKey, &'s LayerKey), f64)>,
     /// Total weight (frequencies) of unigrams that can not be generated by the layout
     pub weight_not_found: f64,
+    /// Weight (frequencies) of the unigrams that can not be generated by the layout per symbol (see
+    /// [`MissingSymbols`])
+    pub missing_symbols: MissingSymbols,
     /// Total weight (frequencies) of unigrams that can be generated by the layout
     pub weight_found: f64,
+    /// Exact number of presses of each modifier that was split off the source ngrams (one press
+    /// per modifier and symbol, independent of how many split ngrams contain it). `None` if the
+    /// grams were modified afterwards (e.g. resampled).
+    pub modifier_presses: Option<Vec<(&'s LayerKey, f64)>>,
 }
 
+/// A trigram in terms of [`LayerKey`]s
+pub type LayerKeyTrigram<'s> = (&'s LayerKey, &'s LayerKey, &'s LayerKey);
+
 /// Trigrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
 /// can not be generated by the layout.
 pub struct MappedTrigrams<'s> {
     /// Unigrams in terms of [`LayerKey`]s
-    pub grams: Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)>,
+    pub grams: Vec<(LayerKeyTrigram<'s>, f64)>,
     /// Total weight (frequencies) of unigrams that can not be generated by the layout
     pub weight_not_found: f64,
+    /// Weight (frequencies) of the unigrams that can not be generated by the layout per symbol (see
+    /// [`MissingSymbols`])
+    pub missing_symbols: MissingSymbols,
     /// Total weight (frequencies) of unigrams that can be generated by the layout
     pub weight_found: f64,
+    /// Total weight of the trigrams that were removed from `grams` because the same hold modifier
+    /// appears consecutively (it is held instead of pressed repeatedly). It is included in
+    /// `weight_found`, but not in the weights of `grams`.
+    pub weight_filtered: f64,
+    /// Bigrams of the first and third key of each trigram (see
+    /// [`NgramMapperConfig::secondary_bigrams`](on_demand_ngram_mapper::NgramMapperConfig::secondary_bigrams)).
+    /// `None` if they are not derived or the grams were modified afterwards (e.g. resampled).
+    pub secondary_bigrams: Option<Vec<((&'s LayerKey, &'s LayerKey), f64)>>,
+    /// Exact number of presses of each modifier that was split off the source ngrams (one press
+    /// per modifier and symbol, independent of how many split ngrams contain it). `None` if the
+    /// grams were modified afterwards (e.g. resampled).
+    pub modifier_presses: Option<Vec<(&'s LayerKey, f64)>>,
+}
+
+/// A quadgram in terms of [`LayerKey`]s
+pub type LayerKeyQuadgram<'s> = (&'s LayerKey, &'s LayerKey, &'s LayerKey, &'s LayerKey);
+
+/// Quadgrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
+/// can not be generated by the layout.
+pub struct MappedQuadgrams<'s> {
+    /// Quadgrams in terms of [`LayerKey`]s
+    pub grams: Vec<(LayerKeyQuadgram<'s>, f64)>,
+    /// Total weight (frequencies) of quadgrams that can not be generated by the layout
+    pub weight_not_found: f64,
+    /// Weight (frequencies) of the quadgrams that can not be generated by the layout per symbol (see
+    /// [`MissingSymbols`])
+    pub missing_symbols: MissingSymbols,
+    /// Total weight (frequencies) of quadgrams that can be generated by the layout
+    pub weight_found: f64,
+    /// Exact number of presses of each modifier that was split off the source ngrams (one press
+    /// per modifier and symbol, independent of how many split ngrams contain it). `None` if the
+    /// grams were modified afterwards (e.g. resampled).
+    pub modifier_presses: Option<Vec<(&'s LayerKey, f64)>>,
+}
+
+impl<'s> MappedUnigrams<'s> {
+    /// Approximate number of bytes allocated for the mapped unigrams
+    pub fn estimated_bytes(&self) -> usize {
+        vec_bytes(&self.grams) + self.modifier_presses.as_deref().map_or(0, vec_bytes)
+    }
+}
+
+impl<'s> MappedBigrams<'s> {
+    /// Approximate number of bytes allocated for the mapped bigrams
+    pub fn estimated_bytes(&self) -> usize {
+        vec_bytes(&self.grams) + self.modifier_presses.as_deref().map_or(0, vec_bytes)
+    }
+}
+
+impl<'s> MappedTrigrams<'s> {
+    /// Approximate number of bytes allocated for the mapped trigrams
+    pub fn estimated_bytes(&self) -> usize {
+        vec_bytes(&self.grams) + self.modifier_presses.as_deref().map_or(0, vec_bytes)
+    }
+}
+
+impl<'s> MappedQuadgrams<'s> {
+    /// Approximate number of bytes allocated for the mapped quadgrams
+    pub fn estimated_bytes(&self) -> usize {
+        vec_bytes(&self.grams) + self.modifier_presses.as_deref().map_or(0, vec_bytes)
+    }
+}
+
+/// Statistics about the mapping of the ngrams of a single order to a layout. All weights refer
+/// to the char-based ngrams, except for the generated weight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct NgramMappingStatistics {
+    /// Total weight of the char-based ngrams
+    pub total_weight: f64,
+    /// Weight of the ngrams that were mapped to the layout
+    pub found_weight: f64,
+    /// Weight of the ngrams that can not be generated by the layout
+    pub not_found_weight: f64,
+    /// Weight of the ngrams that were dropped because they contain a line break (see
+    /// [`LineBreakHandling`](common::LineBreakHandling)) or a run of whitespace (see
+    /// [`NgramMapperConfig::collapse_space_runs`](on_demand_ngram_mapper::NgramMapperConfig::collapse_space_runs))
+    pub excluded_weight: f64,
+    /// Weight of the mapped ngrams in excess of the found weight (e.g. from ngrams with modifiers
+    /// split off higher-layer symbols or from increasing the weight of common trigrams)
+    pub generated_weight: f64,
+}
+
+impl NgramMappingStatistics {
+    /// Derive the statistics from the total weight of the char-based ngrams, the weight that was
+    /// excluded, and the mapped ngrams.
+    fn new<'a, I: Iterator<Item = &'a f64>>(
+        total_weight: f64,
+        excluded_weight: f64,
+        not_found_weight: f64,
+        mapped_weights: I,
+    ) -> Self {
+        let found_weight = total_weight - not_found_weight - excluded_weight;
+        let mapped_weight: f64 = mapped_weights.sum();
+
+        Self {
+            total_weight,
+            found_weight,
+            not_found_weight,
+            excluded_weight,
+            generated_weight: mapped_weight - found_weight,
+        }
+    }
+
+    /// Fraction of the total weight that was mapped to the layout
+    pub fn found_fraction(&self) -> f64 {
+        if self.total_weight > 0.0 {
+            self.found_weight / self.total_weight
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Total weights of the char-based unigrams, bigrams, and trigrams along with the excluded
+/// weights of the bigrams and trigrams (see [`MappingStatistics`])
+pub type StatisticsWeights = ((f64, f64, f64), (f64, f64));
+
+/// Statistics about the mapping of the unigrams, bigrams, and trigrams to a layout (see
+/// [`NgramMappingStatistics`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct MappingStatistics {
+    pub unigrams: NgramMappingStatistics,
+    pub bigrams: NgramMappingStatistics,
+    pub trigrams: NgramMappingStatistics,
+}
+
+impl MappingStatistics {
+    /// Collect the statistics of mapped ngrams given the total and excluded weights of the
+    /// char-based unigrams, bigrams, and trigrams.
+    pub(crate) fn from_mapped(
+        unigrams: &MappedUnigrams,
+        bigrams: &MappedBigrams,
+        trigrams: &MappedTrigrams,
+        total_weights: (f64, f64, f64),
+        excluded_weights: (f64, f64),
+    ) -> Self {
+        Self {
+            unigrams: NgramMappingStatistics::new(
+                total_weights.0,
+                0.0,
+                unigrams.weight_not_found,
+                unigrams.grams.iter().map(|(_, w)| w),
+            ),
+            bigrams: NgramMappingStatistics::new(
+                total_weights.1,
+                excluded_weights.0,
+                bigrams.weight_not_found,
+                bigrams.grams.iter().map(|(_, w)| w),
+            ),
+            trigrams: NgramMappingStatistics::new(
+                total_weights.2,
+                excluded_weights.1,
+                trigrams.weight_not_found,
+                trigrams.grams.iter().map(|(_, w)| w),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for MappingStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>16} {:>9} {:>16} {:>16} {:>16}",
+            "", "total", "found", "not found", "excluded", "generated"
+        )?;
+        let orders = [
+            ("Unigrams", &self.unigrams),
+            ("Bigrams", &self.bigrams),
+            ("Trigrams", &self.trigrams),
+        ];
+        for (name, stats) in orders.iter() {
+            writeln!(
+                f,
+                "{:<10} {:>16.2} {:>8.4}% {:>16.2} {:>16.2} {:>16.2}",
+                name,
+                stats.total_weight,
+                100.0 * stats.found_fraction(),
+                stats.not_found_weight,
+                stats.excluded_weight,
+                stats.generated_weight,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A trigram generated from a char-based trigram when mapping it to a layout (see
+/// [`TrigramExpansion`]).
+#[derive(Debug, Clone)]
+pub struct GeneratedTrigram<'s> {
+    pub indices: (LayerKeyIndex, LayerKeyIndex, LayerKeyIndex),
+    pub keys: LayerKeyTrigram<'s>,
+    pub weight: f64,
+    /// Whether the trigram is removed from the mapped trigrams for repeating a hold modifier
+    pub filtered: bool,
+}
+
+/// The trigrams that a single char-based trigram of the ngram data is mapped to, i.e. after
+/// splitting off modifiers (see [`NgramMapper::explain_trigram`]).
+#[derive(Debug, Clone)]
+pub struct TrigramExpansion<'s> {
+    pub trigram: (char, char, char),
+    /// Weight of the trigram in the ngram data
+    pub weight: f64,
+    /// Weight of the trigram that could not be mapped (symbols not generated by the layout or
+    /// excluded line breaks)
+    pub weight_not_found: f64,
+    /// Generated trigrams sorted by descending weight
+    pub generated: Vec<GeneratedTrigram<'s>>,
+}
+
+impl fmt::Display for TrigramExpansion<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (c1, c2, c3) = self.trigram;
+        writeln!(
+            f,
+            "Trigram \"{}{}{}\" (weight {:.2}, not found {:.2}) generates {} trigrams:",
+            c1.escape_debug(),
+            c2.escape_debug(),
+            c3.escape_debug(),
+            self.weight,
+            self.weight_not_found,
+            self.generated.len(),
+        )?;
+        for g in self.generated.iter() {
+            let (k1, k2, k3) = g.keys;
+            let (i1, i2, i3) = g.indices;
+            writeln!(
+                f,
+                "  {:<12} {:>5} {:>5} {:>5} {:>14.4}{}",
+                format!("{}{}{}", k1, k2, k3),
+                i1,
+                i2,
+                i3,
+                g.weight,
+                if g.filtered { " (filtered)" } else { "" },
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Provides ngrams in terms of a [`Layout`]'s [`LayerKey`]s.
+///
+/// The [`Evaluator`](crate::evaluation::Evaluator) only depends on this trait, so custom mappers
+/// (e.g. wrapping an [`OnDemandNgramMapper`](on_demand_ngram_mapper::OnDemandNgramMapper) and
+/// adjusting its ngrams) can be passed to [`Evaluator::default`](crate::evaluation::Evaluator::default).
 pub trait NgramMapper: Send + Sync + NgramMapperClone + fmt::Debug {
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s>;
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s>;
     fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s>;
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s>;
+    /// Skipgrams, i.e. bigrams of the first and third symbol of each trigram (in terms of
+    /// [`MappedBigrams`])
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s>;
+
+    /// A mapper for a batch of layouts that generate the given `stable_symbols` identically
+    /// (see [`shared_ngram_mapper::stable_symbols`]). Ngrams consisting only of stable symbols
+    /// are mapped once with the given layout and reused for all layouts of the batch.
+    /// `None` if the mapper does not support sharing.
+    fn share_mapping(
+        &self,
+        _layout: &Layout,
+        _stable_symbols: &AHashSet<char>,
+    ) -> Option<Box<dyn NgramMapper>> {
+        None
+    }
+
+    /// The same mapper, but modeling the modifiers of the shift layer according to the given
+    /// policy. `None` if the mapper does not support shift policies.
+    fn with_shift_policy(&self, _policy: ShiftPolicy) -> Option<Box<dyn NgramMapper>> {
+        None
+    }
+
+    /// The word-initial symbols derived from the (preprocessed) bigrams together with the
+    /// (preprocessed) unigrams, e.g. for corpora with hardly any whitespace. `None` if the mapper
+    /// does not provide them.
+    fn word_starts(&self) -> Option<(&WordStarts, &Unigrams)> {
+        None
+    }
+
+    /// Statistics about how much of the ngrams' weight is mapped to the layout. `None` if the
+    /// mapper does not provide them.
+    fn mapping_statistics(&self, _layout: &Layout) -> Option<MappingStatistics> {
+        None
+    }
+
+    /// Write the unigrams, bigrams, and trigrams mapped to the layout to TSV files in the given
+    /// directory (see [`dump::write_mapped_ngrams`]).
+    fn dump_mapped_ngrams(&self, layout: &Layout, dir: &Path) -> Result<()> {
+        dump::write_mapped_ngrams(
+            dir,
+            &self.map_unigrams(layout),
+            &self.map_bigrams(layout),
+            &self.map_trigrams(layout),
+        )
+    }
+
+    /// The trigrams that a char-based trigram of the ngram data is mapped to (for inspecting how
+    /// modifiers are split off). `None` if the trigram is not part of the ngram data or the
+    /// mapper does not support explaining its mapping.
+    fn explain_trigram<'s>(
+        &self,
+        _trigram: (char, char, char),
+        _layout: &'s Layout,
+    ) -> Option<TrigramExpansion<'s>> {
+        None
+    }
+
+    /// Add size estimates of the data held by the mapper (e.g. char-based ngrams) to the report.
+    fn memory_report(&self, _report: &mut MemoryReport) {}
 }
 
 // in order to implement clone for Box<dyn LayoutMetric>, the following trick is necessary
@@ -8,33 +8,34 @@ use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
 
 use crate::ngrams::Bigrams;
 
-use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
-
-use ahash::AHashMap;
+use keyboard_layout::layout::{LayerKey, LayerKeyIndex, Layout};
 
 // Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
 // ones are used because they are smaller than a reference (u16 vs usize) and yield better
 // hashing performance.
-type BigramIndices = AHashMap<(LayerKeyIndex, LayerKeyIndex), f64>;
+pub type BigramIndices = IndexMap<(LayerKeyIndex, LayerKeyIndex)>;
 type BigramIndicesVec = Vec<((LayerKeyIndex, LayerKeyIndex), f64)>;
 
 /// Turns the [`Bigrams`]'s characters into their indices, returning a [`BigramIndicesVec`].
+///
+/// Each symbol belongs to a single [`LayerKeyIndex`], so the index tuples are as unique as the
+/// char-based bigrams and need not be merged before splitting off modifiers.
 fn map_bigrams(
     bigrams: &Bigrams,
     layout: &Layout,
-    exclude_line_breaks: bool,
-) -> (BigramIndicesVec, f64) {
+    line_breaks: LineBreakHandling,
+    sorted: bool,
+) -> (BigramIndicesVec, f64, MissingSymbols) {
     let mut not_found_weight = 0.0;
+    let mut missing_symbols = MissingSymbols::default();
     let mut bigrams_vec: BigramIndicesVec = Vec::with_capacity(bigrams.grams.len());
 
     bigrams_vec.extend(
-        bigrams
-            .grams
-            .iter()
+        entries(&bigrams.grams, sorted)
             //.filter(|((c1, c2), _weight)| !c1.is_whitespace() && !c2.is_whitespace())
             .filter_map(|((c1, c2), weight)| {
-                // Exclude bigrams that contain a line break, followed by a non-line-break character
-                if exclude_line_breaks && *c1 == '\n' && *c2 != '\n' {
+                // Exclude bigrams that contain line breaks (see `LineBreakHandling`)
+                if line_breaks.excludes(&[*c1, *c2]) {
                     return None;
                 }
 
@@ -42,6 +43,7 @@ fn map_bigrams(
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c1, *weight);
                         return None;
                     }
                 };
@@ -49,6 +51,7 @@ fn map_bigrams(
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c2, *weight);
                         return None;
                     }
                 };
@@ -57,7 +60,7 @@ fn map_bigrams(
             }),
     );
 
-    (bigrams_vec, not_found_weight)
+    (bigrams_vec, not_found_weight, missing_symbols)
 }
 
 /// Generates [`LayerKey`]-based [Bigrams] from char-based unigrams. Optionally resolves modifiers
@@ -65,29 +68,111 @@ fn map_bigrams(
 #[derive(Clone, Debug)]
 pub struct OnDemandBigramMapper {
     split_modifiers: SplitModifiersConfig,
+    shift_policy: ShiftPolicy,
+    duplicate_symbols: DuplicateSymbolPolicy,
+    /// Shares of the shifted letters starting a sentence (see [`ShiftPolicy::SentenceStart`])
+    sentence_starts: SentenceStarts,
+    /// Visit the ngrams in the order of their symbols (see [`entries`])
+    deterministic_order: bool,
 }
 
 impl OnDemandBigramMapper {
-    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
-        Self { split_modifiers }
+    pub fn new(
+        split_modifiers: SplitModifiersConfig,
+        shift_policy: ShiftPolicy,
+        deterministic_order: bool,
+    ) -> Self {
+        Self {
+            split_modifiers,
+            shift_policy,
+            duplicate_symbols: DuplicateSymbolPolicy::default(),
+            sentence_starts: SentenceStarts::default(),
+            deterministic_order,
+        }
+    }
+
+    /// Attribute the weight of symbols that the layout generates with multiple keys according to
+    /// the policy (see [`resolve_duplicate_symbols`]).
+    pub fn with_duplicate_symbols(mut self, policy: DuplicateSymbolPolicy) -> Self {
+        self.duplicate_symbols = policy;
+        self
+    }
+
+    /// Split the weight of ngrams lacking the context of a shifted letter by its share of
+    /// occurrences at the start of a sentence (see [`ShiftPolicy::SentenceStart`]).
+    pub fn with_sentence_starts(mut self, sentence_starts: SentenceStarts) -> Self {
+        self.sentence_starts = sentence_starts;
+        self
     }
 
     /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
+    ///
+    /// Additionally returns the weight of the ngrams that can not be generated by the layout (in
+    /// total and per symbol) and the number of presses of each modifier that was split off.
     pub fn layerkey_indices(
         &self,
         bigrams: &Bigrams,
         layout: &Layout,
-        exclude_line_breaks: bool,
-    ) -> (BigramIndices, f64) {
-        let (mut bigram_keys_vec, not_found_weight) =
-            map_bigrams(bigrams, layout, exclude_line_breaks);
+        line_breaks: LineBreakHandling,
+    ) -> (BigramIndices, f64, MissingSymbols, ModifierPresses) {
+        let (mut bigram_keys_vec, not_found_weight, missing_symbols) =
+            map_bigrams(bigrams, layout, line_breaks, self.deterministic_order);
+        let mut modifier_presses = ModifierPresses::default();
+        let resolved = ResolvedModifiers::new(self.shift_policy, layout)
+            .with_layer_overrides(&self.split_modifiers, layout)
+            .with_sentence_starts(&self.sentence_starts, layout);
+
+        if self.duplicate_symbols != DuplicateSymbolPolicy::CheapestOnly
+            && layout.has_duplicate_symbols()
+        {
+            bigram_keys_vec = bigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2), w)| {
+                    resolve_duplicate_symbols([k1, k2], w, layout, self.duplicate_symbols)
+                })
+                .map(|([k1, k2], w)| ((k1, k2), w))
+                .collect();
+        }
+
+        if layout.has_multi_key_symbols() {
+            bigram_keys_vec = bigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2), w)| resolve_multi_key_symbols([k1, k2], w, layout))
+                .map(|([k1, k2], w)| ((k1, k2), w))
+                .collect();
+        }
+
+        if layout.has_lock_layers() {
+            let lock_toggles = self.split_modifiers.lock_toggles;
+            bigram_keys_vec = bigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2), w)| {
+                    resolve_lock_layers(
+                        [k1, k2],
+                        w,
+                        layout,
+                        &resolved,
+                        lock_toggles,
+                        &mut modifier_presses,
+                    )
+                })
+                .map(|([k1, k2], w)| ((k1, k2), w))
+                .collect();
+        }
 
-        if layout.has_one_shot_layers() {
-            bigram_keys_vec = self.process_one_shot_modifiers(bigram_keys_vec, layout);
+        if self.shift_policy.has_one_shot_layers(layout) {
+            bigram_keys_vec = self.process_one_shot_modifiers(
+                bigram_keys_vec,
+                layout,
+                &resolved,
+                &mut modifier_presses,
+            );
         }
 
-        let bigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
-            self.process_hold_modifiers(bigram_keys_vec, layout)
+        let bigram_keys = if self.split_modifiers.splits_any_layer()
+            && self.shift_policy.has_hold_layers(layout)
+        {
+            self.process_hold_modifiers(bigram_keys_vec, layout, &resolved, &mut modifier_presses)
         } else {
             bigram_keys_vec.into_iter().collect()
         };
@@ -99,23 +184,69 @@ impl OnDemandBigramMapper {
         //         println!("After split: {}", w);
         //     });
 
-        (bigram_keys, not_found_weight)
+        (
+            bigram_keys,
+            not_found_weight,
+            missing_symbols,
+            modifier_presses,
+        )
+    }
+
+    /// Bigrams of the key of each bigram's first symbol and the first hold modifier of its second
+    /// symbol, with the weight of the bigram scaled by `factor` (see
+    /// [`CrossGramModifiersConfig`](super::on_demand_ngram_mapper::CrossGramModifiersConfig)).
+    /// Other layer mechanisms (one-shot and lock layers, multi-key and duplicate symbols) are not
+    /// considered. Returns the bigrams along with their total weight.
+    pub fn cross_gram_modifier_indices(
+        &self,
+        bigrams: &Bigrams,
+        layout: &Layout,
+        line_breaks: LineBreakHandling,
+        factor: f64,
+    ) -> (BigramIndices, f64) {
+        let mut bigram_w_map = BigramIndices::default();
+        let mut total_weight = 0.0;
+        if !self.split_modifiers.splits_any_layer() || !self.shift_policy.has_hold_layers(layout) {
+            return (bigram_w_map, total_weight);
+        }
+
+        let resolved = ResolvedModifiers::new(self.shift_policy, layout)
+            .with_layer_overrides(&self.split_modifiers, layout)
+            .with_sentence_starts(&self.sentence_starts, layout);
+        let (bigram_keys_vec, _, _) =
+            map_bigrams(bigrams, layout, line_breaks, self.deterministic_order);
+        bigram_keys_vec.into_iter().for_each(|((k1, k2), w)| {
+            let ngram = [k1, k2];
+            resolved
+                .sentence_starts(layout, &ngram, w)
+                .for_each(|(sentence_start, w)| {
+                    let (key1, _, _) = resolved.hold_in(layout, &ngram, 0, sentence_start);
+                    if let (_, [mod2, ..], _) = resolved.hold_in(layout, &ngram, 1, sentence_start)
+                    {
+                        bigram_w_map.insert_or_add_weight((key1, *mod2), factor * w);
+                        total_weight += factor * w;
+                    }
+                });
+        });
+
+        (bigram_w_map, total_weight)
     }
 
     /// Resolves &[`LayerKey`] references for [`LayerKeyIndex`] and filters bigrams that contain
-    /// repeating identical modifiers.
+    /// repeating identical modifiers. If `sorted`, the ngrams are ordered by their indices.
     pub fn get_filtered_layerkeys<'s>(
         bigrams: &BigramIndices,
         layout: &'s Layout,
+        sorted: bool,
     ) -> Vec<((&'s LayerKey, &'s LayerKey), f64)> {
         let mut layerkeys = Vec::with_capacity(bigrams.len());
 
-        layerkeys.extend(bigrams.iter().filter_map(|((idx1, idx2), w)| {
+        layerkeys.extend(entries(bigrams, sorted).filter_map(|((idx1, idx2), w)| {
             let k1 = layout.get_layerkey(idx1);
 
             // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
             // --> remove
-            match k1.is_modifier.is_hold() && idx1 == idx2 {
+            match k1.is_modifier.is_held() && idx1 == idx2 {
                 false => Some((
                     (
                         k1,                        // LayerKey 1
@@ -135,22 +266,26 @@ impl OnDemandBigramMapper {
     ///
     /// Each bigram of higher-layer symbols will transform into a series of bigrams with permutations of
     /// the involved base-keys and modifers. However, the base-key will always be after its modifier.
-    fn process_hold_modifiers(&self, bigrams: BigramIndicesVec, layout: &Layout) -> BigramIndices {
-        let mut bigram_w_map = AHashMap::with_capacity(bigrams.len() / 3);
+    /// The keys of a chord are not permuted, but pressed together in their configured order.
+    fn process_hold_modifiers(
+        &self,
+        bigrams: BigramIndicesVec,
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> BigramIndices {
+        let mut bigram_w_map = IndexMap::with_capacity_and_hasher(bigrams.len() / 3, FixedState);
 
         bigrams.into_iter().for_each(|((k1, k2), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
+            let (key1, mods1, chord1) = resolved.hold(layout, &k1, &[]);
+            let (key2, mods2, chord2) = resolved.hold(layout, &k2, &[k1]);
+            let chord_factor = self.split_modifiers.chord_cost_factor;
+            let default_factor = self.split_modifiers.same_key_mod_factor;
+            let factor1 = resolved.same_key_mod_factor(&k1, default_factor);
+            let factor2 = resolved.same_key_mod_factor(&k2, default_factor);
 
-            let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
-                _ => (k1, Vec::new()),
-            };
-
-            let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
-                _ => (k2, Vec::new()),
-            };
+            add_modifier_presses(modifier_presses, mods1, w);
+            add_modifier_presses(modifier_presses, mods2, w);
 
             bigram_w_map.insert_or_add_weight((key1, key2), w);
             // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&base2).symbol);
@@ -174,16 +309,20 @@ impl OnDemandBigramMapper {
             });
 
             // same key mods
-            TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+            TakeLayerKeys::<2>::new(key1, mods1, w, factor1)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord1, chord_factor)
+                .for_each(|([e1, e2], w)| {
+                    bigram_w_map.insert_or_add_weight((e1, e2), w);
+                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e1).symbol, layout.get_layerkey(&e2).symbol);
                 });
 
-            TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+            TakeLayerKeys::<2>::new(key2, mods2, w, factor2)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord2, chord_factor)
+                .for_each(|([e1, e2], w)| {
+                    bigram_w_map.insert_or_add_weight((e1, e2), w);
+                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e1).symbol, layout.get_layerkey(&e2).symbol);
                 });
         });
 
@@ -194,32 +333,37 @@ impl OnDemandBigramMapper {
         &self,
         bigrams: BigramIndicesVec,
         layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
     ) -> BigramIndicesVec {
         let mut processed_bigrams = Vec::with_capacity(bigrams.len());
 
         bigrams.into_iter().for_each(|((k1, k2), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
-
-            let mut keys = Vec::new();
-
-            if let LayerModifiers::OneShot(mods) = mods1 {
-                keys.extend(mods);
-                keys.push(base1);
-            } else {
-                keys.push(k1);
-            };
-
-            if let LayerModifiers::OneShot(mods) = mods2 {
-                keys.extend(mods);
-                keys.push(base2);
-            } else {
-                keys.push(k2);
-            };
-
-            keys.iter().zip(keys.iter().skip(1)).for_each(|(lk1, lk2)| {
-                processed_bigrams.push(((*lk1, *lk2), w));
-            });
+            let ngram = [k1, k2];
+            resolved
+                .sentence_starts(layout, &ngram, w)
+                .for_each(|(sentence_start, w)| {
+                    let symbols = [
+                        resolved.one_shot_in(layout, &ngram, 0, sentence_start),
+                        resolved.one_shot_in(layout, &ngram, 1, sentence_start),
+                    ];
+                    symbols
+                        .iter()
+                        .for_each(|(_, mods)| add_modifier_presses(modifier_presses, mods, w));
+
+                    let factor = self.split_modifiers.one_shot_weight_factor;
+                    self.split_modifiers
+                        .one_shot_ordering
+                        .key_sequences(&symbols)
+                        .iter()
+                        .for_each(|sequence| {
+                            processed_bigrams.extend(
+                                sequence
+                                    .ngrams(w, factor)
+                                    .map(|([lk1, lk2], w)| ((lk1, lk2), w)),
+                            );
+                        });
+                });
         });
 
         processed_bigrams
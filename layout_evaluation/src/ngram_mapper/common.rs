@@ -1,430 +1,912 @@
-/// The `common` module provides utility functions for resolving modifiers in ngrams.
-use keyboard_layout::layout::LayerKeyIndex;
+/// The `common` module provides utility functions for resolving modifiers and multi-key symbols
+/// in ngrams.
+use super::on_demand_ngram_mapper::SplitModifiersConfig;
 
-use ahash::AHashMap;
-use std::{cmp::Eq, hash::Hash, slice};
+use crate::ngrams::Trigrams;
 
-/// Iterator over unigrams of the base-layer key and each modifier.
-#[derive(Clone, Debug)]
-pub struct TakeOneLayerKey<'a> {
-    base_key: LayerKeyIndex,
-    modifiers: &'a [LayerKeyIndex],
-    weight: f64,
-    iter: Option<slice::Iter<'a, LayerKeyIndex>>,
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
+
+use ahash::{AHashMap, AHasher};
+use serde::Deserialize;
+use std::{
+    cmp::Eq,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasher, Hash},
+};
+
+/// Layer whose modifiers are modeled according to the [`ShiftPolicy`]
+pub const SHIFT_LAYER: u8 = 1;
+
+/// How the modifiers of the shift layer (the first higher layer) are modeled by the ngram
+/// mappers, independent of the modifier type configured in the layout.
+///
+/// Only the way the modifiers are pressed changes. The modifier keys keep the type configured
+/// in the layout (which e.g. determines their cost in the "Modifier Usage" metric).
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShiftPolicy {
+    /// Use the modifier type configured in the layout.
+    Layout,
+    /// The modifiers are held while pressing the key.
+    Hold,
+    /// The modifiers are tapped before the key.
+    OneShot,
+    /// The modifiers are tapped before the first letter of a run of shifted letters (within an
+    /// ngram). The following letters of the run need no modifiers. Unigrams have no context, so
+    /// each shifted letter is counted as the start of a run.
+    CapsWord,
+    /// The modifiers are tapped before a shifted letter at the start of a sentence (following
+    /// sentence-ending punctuation and a space) and held for all other symbols of the shift layer.
+    /// The weight of ngrams too short to contain that context (e.g. `" T"` or `"T"`) is split
+    /// between both according to the share of the letter's occurrences at the start of a
+    /// sentence in the trigrams (see [`SentenceStarts`]).
+    SentenceStart,
 }
 
-impl<'a> Iterator for TakeOneLayerKey<'a> {
-    type Item = (LayerKeyIndex, f64);
+impl Default for ShiftPolicy {
+    fn default() -> Self {
+        Self::Layout
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match &mut self.iter {
-            None => {
-                self.iter = Some(self.modifiers.iter());
-                Some((self.base_key, self.weight))
-            }
-            Some(iter) => iter.next().map(|m| (*m, self.weight)),
+impl fmt::Display for ShiftPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Layout => "layout",
+            Self::Hold => "hold",
+            Self::OneShot => "one_shot",
+            Self::CapsWord => "caps_word",
+            Self::SentenceStart => "sentence_start",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ShiftPolicy {
+    /// All policies that override the layout's configuration.
+    pub const OVERRIDES: [ShiftPolicy; 4] = [
+        Self::Hold,
+        Self::OneShot,
+        Self::CapsWord,
+        Self::SentenceStart,
+    ];
+
+    /// If one-shot modifiers need to be processed for the layout.
+    pub fn has_one_shot_layers(&self, layout: &Layout) -> bool {
+        layout.has_one_shot_layers()
+            || matches!(self, Self::OneShot | Self::CapsWord | Self::SentenceStart)
+    }
+
+    /// If hold modifiers need to be processed for the layout.
+    pub fn has_hold_layers(&self, layout: &Layout) -> bool {
+        layout.has_hold_layers() || matches!(self, Self::Hold | Self::SentenceStart)
+    }
+
+    /// Resolves the base-layer key and the modifiers of a key (see [`Layout::resolve_modifiers`]),
+    /// applying the policy to the modifiers of the shift layer. `preceding` are the (unresolved)
+    /// preceding keys of the ngram, the closest one last.
+    pub fn resolve_modifiers(
+        &self,
+        layout: &Layout,
+        k: &LayerKeyIndex,
+        preceding: &[LayerKeyIndex],
+    ) -> (LayerKeyIndex, LayerModifiers) {
+        let (base, mods) = layout.resolve_modifiers(k);
+        if *self == Self::Layout || layout.get_layerkey(k).layer != SHIFT_LAYER {
+            return (base, mods);
         }
+
+        let indices = match mods {
+            LayerModifiers::LongPress => return (base, mods),
+            LayerModifiers::Hold(v)
+            | LayerModifiers::OneShot(v)
+            | LayerModifiers::Lock(v)
+            | LayerModifiers::Chord(v) => v,
+        };
+        let mods = match self {
+            Self::Layout | Self::Hold => LayerModifiers::Hold(indices),
+            Self::OneShot => LayerModifiers::OneShot(indices),
+            Self::CapsWord => match preceding.last() {
+                Some(p) if continues_caps_word(layout, p, k) => LayerModifiers::OneShot(Vec::new()),
+                _ => LayerModifiers::OneShot(indices),
+            },
+            Self::SentenceStart if starts_sentence(layout, preceding, k) => {
+                LayerModifiers::OneShot(indices)
+            }
+            Self::SentenceStart => LayerModifiers::Hold(indices),
+        };
+
+        (base, mods)
     }
 }
 
-impl<'a> TakeOneLayerKey<'a> {
-    pub fn new(base_key: LayerKeyIndex, modifiers: &'a [LayerKeyIndex], weight: f64) -> Self {
-        Self {
-            base_key,
-            modifiers,
-            weight,
-            iter: None,
+/// Whether the key continues a run of shifted letters (see [`ShiftPolicy::CapsWord`]).
+fn continues_caps_word(layout: &Layout, preceding: &LayerKeyIndex, k: &LayerKeyIndex) -> bool {
+    let is_shifted_letter = |k: &LayerKeyIndex| {
+        let lk = layout.get_layerkey(k);
+        lk.layer == SHIFT_LAYER && lk.symbol.is_alphabetic()
+    };
+    is_shifted_letter(preceding) && is_shifted_letter(k)
+}
+
+/// Symbols ending a sentence (see [`ShiftPolicy::SentenceStart`])
+const SENTENCE_END: [char; 3] = ['.', '!', '?'];
+
+/// Whether the key is a shifted letter following sentence-ending punctuation and a space (see
+/// [`ShiftPolicy::SentenceStart`]).
+fn starts_sentence(layout: &Layout, preceding: &[LayerKeyIndex], k: &LayerKeyIndex) -> bool {
+    let lk = layout.get_layerkey(k);
+    if lk.layer != SHIFT_LAYER || !lk.symbol.is_alphabetic() {
+        return false;
+    }
+
+    match preceding {
+        [.., punctuation, space] => {
+            SENTENCE_END.contains(&layout.get_layerkey(punctuation).symbol)
+                && layout.get_layerkey(space).symbol == ' '
         }
+        _ => false,
     }
 }
 
-// // use length 3 for up to 2 modifiers
-// // use length 4 for up to 3 modifiers (may cost arount 10%-20% performance)
-// // or use smallvec/tinyvec that can overflow to the heap
-// pub fn take_one_layerkey(
-//     base_key: LayerKeyIndex,
-//     modifiers: &[LayerKeyIndex],
-//     weight: f64,
-// ) -> ArrayVec<[(LayerKeyIndex, f64); 3]> {
-//     let mut res = ArrayVec::<[(LayerKeyIndex, f64); 3]>::new();
-//     res.push((base_key, weight));
+/// Shares of the occurrences of letters that start a sentence (following sentence-ending
+/// punctuation and a space), derived from the trigrams. They split the weight of ngrams that are
+/// too short to tell whether a shifted letter starts a sentence (see
+/// [`ShiftPolicy::SentenceStart`]).
+#[derive(Clone, Debug, Default)]
+pub struct SentenceStarts {
+    /// Share of all occurrences of a letter
+    all: AHashMap<char, f64>,
+    /// Share of the occurrences of a letter following a space
+    after_space: AHashMap<char, f64>,
+}
+
+impl SentenceStarts {
+    /// Derive the shares from the trigrams ending with a letter. If `sorted`, the trigrams are
+    /// visited in the order of their symbols (see [`entries`]).
+    pub fn from_trigrams(trigrams: &Trigrams, sorted: bool) -> Self {
+        let mut starts: AHashMap<char, f64> = AHashMap::default();
+        let mut all: AHashMap<char, f64> = AHashMap::default();
+        let mut after_space: AHashMap<char, f64> = AHashMap::default();
+        entries(&trigrams.grams, sorted)
+            .filter(|((_, _, c3), _)| c3.is_alphabetic())
+            .for_each(|((c1, c2, c3), w)| {
+                *all.entry(*c3).or_insert(0.0) += w;
+                if *c2 == ' ' {
+                    *after_space.entry(*c3).or_insert(0.0) += w;
+                    if SENTENCE_END.contains(c1) {
+                        *starts.entry(*c3).or_insert(0.0) += w;
+                    }
+                }
+            });
+
+        let shares = |totals: AHashMap<char, f64>| {
+            totals
+                .into_iter()
+                .filter_map(|(c, total)| {
+                    let start = starts.get(&c).copied().unwrap_or(0.0);
+                    (start > 0.0).then(|| (c, start / total))
+                })
+                .collect()
+        };
 
-//     modifiers.iter().for_each(|m| {
-//         res.push((*m, weight));
-//     });
+        Self {
+            all: shares(all),
+            after_space: shares(after_space),
+        }
+    }
+}
 
-//     res
-// }
+/// Modifiers of a key continuing a run of shifted letters with [`ShiftPolicy::CapsWord`]
+static CAPS_WORD_CONTINUATION: LayerModifiers = LayerModifiers::OneShot(Vec::new());
 
-/// Iterator over bigrams of combinations of the base-layer key with each modifier
-/// and the modifiers themselves. The number of resulting bigrams depends on the number of modifiers.
+/// The base-layer keys and modifiers of all keys of a layout under a [`ShiftPolicy`]. They are
+/// resolved once per mapping instead of for each key of each ngram.
 #[derive(Clone, Debug)]
-pub struct TakeTwoLayerKey<'a> {
-    base_key: LayerKeyIndex,
-    modifiers: &'a [LayerKeyIndex],
-    weight: f64,
-    same_key_mod_factor: f64,
-    iter_inner: Option<slice::Iter<'a, LayerKeyIndex>>,
-    state_inner: Option<LayerKeyIndex>,
-    inner_variant: u8,
-    iter_outer: Option<slice::Iter<'a, LayerKeyIndex>>,
-    state_outer: Option<LayerKeyIndex>,
+pub struct ResolvedModifiers {
+    resolved: Vec<(LayerKeyIndex, LayerModifiers)>,
+    caps_word: bool,
+    /// Base-layer keys and modifiers of all keys at the start of a sentence, if they differ (see
+    /// [`ShiftPolicy::SentenceStart`])
+    sentence_start: Vec<(LayerKeyIndex, LayerModifiers)>,
+    /// Shares of all occurrences of all keys and of those following a space that start a sentence
+    /// (see [`SentenceStarts`])
+    sentence_start_shares: Vec<(f64, f64)>,
+    /// Same-key modifier factors of all keys if they differ between layers (see
+    /// [`SplitModifiersConfig::layers`])
+    same_key_mod_factors: Vec<f64>,
 }
 
-impl<'a> Iterator for TakeTwoLayerKey<'a> {
-    type Item = ((LayerKeyIndex, LayerKeyIndex), f64);
+impl ResolvedModifiers {
+    pub fn new(shift_policy: ShiftPolicy, layout: &Layout) -> Self {
+        let resolved = (0..layout.layerkeys.len())
+            .map(|i| shift_policy.resolve_modifiers(layout, &(i as LayerKeyIndex), &[]))
+            .collect();
+        let sentence_start = match shift_policy {
+            ShiftPolicy::SentenceStart => (0..layout.layerkeys.len())
+                .map(|i| ShiftPolicy::OneShot.resolve_modifiers(layout, &(i as LayerKeyIndex), &[]))
+                .collect(),
+            _ => Vec::new(),
+        };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.iter_outer.is_none() {
-            // initialization
-            self.iter_outer = Some(self.modifiers.iter());
+        Self {
+            resolved,
+            caps_word: shift_policy == ShiftPolicy::CapsWord,
+            sentence_start,
+            sentence_start_shares: Vec::new(),
+            same_key_mod_factors: Vec::new(),
         }
+    }
 
-        if self.state_inner.is_none() {
-            // initialization
-            // or inner loop ended -> advance outer loop and reinit inner one
-            self.state_outer = self.iter_outer.as_mut().unwrap().next().cloned();
-
-            // the inner iterator is a clone of the outer (plus one skip, which happened in the line above)
-            self.iter_inner = Some(self.iter_outer.as_ref().cloned().unwrap());
-            self.state_inner = self.iter_inner.as_mut().unwrap().next().cloned();
+    /// Apply the per-layer overrides of the modifier splitting (see
+    /// [`SplitModifiersConfig::layers`]). Keys of layers that are not split are pressed themselves
+    /// instead of with their hold modifiers.
+    pub fn with_layer_overrides(mut self, config: &SplitModifiersConfig, layout: &Layout) -> Self {
+        if config.layers.is_empty() {
+            return self;
+        }
 
-            // on first entry of inner loop, first return base key variant
-            if let Some(state_outer) = self.state_outer {
-                return Some(((state_outer, self.base_key), self.weight));
+        for (i, (base, mods)) in self.resolved.iter_mut().enumerate() {
+            let layer = layout.layerkeys[i].layer;
+            if matches!(mods, LayerModifiers::Hold(_) | LayerModifiers::Chord(_))
+                && !config.splits_layer(layer)
+            {
+                *base = i as LayerKeyIndex;
+                *mods = LayerModifiers::Hold(Vec::new());
             }
         }
+        self.same_key_mod_factors = layout
+            .layerkeys
+            .iter()
+            .map(|lk| config.same_key_mod_factor(lk.layer))
+            .collect();
+
+        self
+    }
+
+    /// Set the shares of the occurrences of the shifted letters that start a sentence, used for
+    /// ngrams lacking the context (see [`Self::sentence_starts`]). Only relevant with
+    /// [`ShiftPolicy::SentenceStart`].
+    pub fn with_sentence_starts(
+        mut self,
+        sentence_starts: &SentenceStarts,
+        layout: &Layout,
+    ) -> Self {
+        if self.sentence_start.is_empty() {
+            return self;
+        }
+
+        self.sentence_start_shares = layout
+            .layerkeys
+            .iter()
+            .map(|lk| {
+                if lk.layer != SHIFT_LAYER || !lk.symbol.is_alphabetic() {
+                    return (0.0, 0.0);
+                }
+                let share =
+                    |shares: &AHashMap<char, f64>| shares.get(&lk.symbol).copied().unwrap_or(0.0);
+                (
+                    share(&sentence_starts.all),
+                    share(&sentence_starts.after_space),
+                )
+            })
+            .collect();
+
+        self
+    }
+
+    /// Split the weight of an ngram by whether its first key or the key following a space at its
+    /// start starts a sentence. The ngram lacks the context to tell, so the weight is split by the
+    /// key's share of occurrences at the start of a sentence (see [`SentenceStarts`]). Yields the
+    /// position of the key if it starts a sentence (see [`Self::one_shot_in`]) along with the
+    /// weight.
+    pub fn sentence_starts(
+        &self,
+        layout: &Layout,
+        ngram: &[LayerKeyIndex],
+        w: f64,
+    ) -> impl Iterator<Item = (Option<usize>, f64)> {
+        let shares = |k: &LayerKeyIndex| self.sentence_start_shares[*k as usize];
+        let (i, share) = match ngram {
+            _ if self.sentence_start_shares.is_empty() => (0, 0.0),
+            [k, ..] if shares(k).0 > 0.0 => (0, shares(k).0),
+            [space, k, ..] if layout.get_layerkey(space).symbol == ' ' => (1, shares(k).1),
+            _ => (0, 0.0),
+        };
+
+        std::iter::once((Some(i), share))
+            .chain(std::iter::once((None, 1.0 - share)))
+            .filter(|(_, share)| *share > 0.0)
+            .map(move |(i, share)| (i, share * w))
+    }
 
-        self.state_outer?; // if outer loop ended -> no more elements -> return None
+    /// Weight factor for generated ngrams that involve two modifiers for the same key (see
+    /// [`SplitModifiersConfig::same_key_mod_factor`]).
+    #[inline(always)]
+    pub fn same_key_mod_factor(&self, k: &LayerKeyIndex, default: f64) -> f64 {
+        self.same_key_mod_factors
+            .get(*k as usize)
+            .copied()
+            .unwrap_or(default)
+    }
 
-        match self.inner_variant {
-            0 => {
-                self.inner_variant = 1;
+    /// The base-layer key and the modifiers of a key, see [`ShiftPolicy::resolve_modifiers`].
+    #[inline(always)]
+    pub fn get(
+        &self,
+        layout: &Layout,
+        k: &LayerKeyIndex,
+        preceding: &[LayerKeyIndex],
+    ) -> (LayerKeyIndex, &LayerModifiers) {
+        let (base, mods) = &self.resolved[*k as usize];
+        if !self.sentence_start.is_empty() && starts_sentence(layout, preceding, k) {
+            let (base, mods) = &self.sentence_start[*k as usize];
+            return (*base, mods);
+        }
+        match preceding.last() {
+            Some(p)
+                if self.caps_word
+                    && matches!(mods, LayerModifiers::OneShot(_))
+                    && continues_caps_word(layout, p, k) =>
+            {
+                (*base, &CAPS_WORD_CONTINUATION)
+            }
+            _ => (*base, mods),
+        }
+    }
 
-                // first variant is m1-m2
-                Some((
-                    (self.state_outer.unwrap(), self.state_inner.unwrap()),
-                    self.weight * self.same_key_mod_factor,
-                ))
+    /// The base-layer key and the modifiers of the key at position `i` of an ngram, where the key
+    /// at position `sentence_start` (if any) starts a sentence (see [`Self::sentence_starts`]).
+    #[inline(always)]
+    fn get_in(
+        &self,
+        layout: &Layout,
+        ngram: &[LayerKeyIndex],
+        i: usize,
+        sentence_start: Option<usize>,
+    ) -> (LayerKeyIndex, &LayerModifiers) {
+        match sentence_start {
+            Some(start) if start == i => {
+                let (base, mods) = &self.sentence_start[ngram[i] as usize];
+                (*base, mods)
             }
-            1 => {
-                self.inner_variant = 0;
-
-                // second variant is m2-m1
-                let res = Some((
-                    (self.state_inner.unwrap(), self.state_outer.unwrap()),
-                    self.weight * self.same_key_mod_factor,
-                ));
-                // there is no third variant -> advance inner loop
-                self.state_inner = self.iter_inner.as_mut().unwrap().next().cloned();
-
-                res
+            _ => self.get(layout, &ngram[i], &ngram[..i]),
+        }
+    }
+
+    /// The key to press for a key and its hold modifiers (or the keys of its chord) along with
+    /// whether they are pressed as a chord. Keys that are not accessed via hold modifiers or
+    /// chords are pressed themselves.
+    #[inline(always)]
+    pub fn hold(
+        &self,
+        layout: &Layout,
+        k: &LayerKeyIndex,
+        preceding: &[LayerKeyIndex],
+    ) -> (LayerKeyIndex, &[LayerKeyIndex], bool) {
+        held(k, self.get(layout, k, preceding))
+    }
+
+    /// [`Self::hold`] for the key at position `i` of an ngram, where the key at position
+    /// `sentence_start` (if any) starts a sentence (see [`Self::sentence_starts`]).
+    #[inline(always)]
+    pub fn hold_in(
+        &self,
+        layout: &Layout,
+        ngram: &[LayerKeyIndex],
+        i: usize,
+        sentence_start: Option<usize>,
+    ) -> (LayerKeyIndex, &[LayerKeyIndex], bool) {
+        held(&ngram[i], self.get_in(layout, ngram, i, sentence_start))
+    }
+
+    /// The key to press for a key and its one-shot modifiers. Keys that are not accessed via
+    /// one-shot modifiers are pressed themselves.
+    #[inline(always)]
+    pub fn one_shot(
+        &self,
+        layout: &Layout,
+        k: &LayerKeyIndex,
+        preceding: &[LayerKeyIndex],
+    ) -> (LayerKeyIndex, &[LayerKeyIndex]) {
+        one_shot(k, self.get(layout, k, preceding))
+    }
+
+    /// [`Self::one_shot`] for the key at position `i` of an ngram, where the key at position
+    /// `sentence_start` (if any) starts a sentence (see [`Self::sentence_starts`]).
+    #[inline(always)]
+    pub fn one_shot_in(
+        &self,
+        layout: &Layout,
+        ngram: &[LayerKeyIndex],
+        i: usize,
+        sentence_start: Option<usize>,
+    ) -> (LayerKeyIndex, &[LayerKeyIndex]) {
+        one_shot(&ngram[i], self.get_in(layout, ngram, i, sentence_start))
+    }
+
+    /// The base-layer key and the toggles of a key of a lock layer, if it belongs to one.
+    #[inline(always)]
+    pub fn lock(&self, k: &LayerKeyIndex) -> Option<(LayerKeyIndex, &[LayerKeyIndex])> {
+        match &self.resolved[*k as usize] {
+            (base, LayerModifiers::Lock(toggles)) => Some((*base, toggles)),
+            _ => None,
+        }
+    }
+}
+
+/// The key to press for a key with the given base-layer key and modifiers and its hold modifiers
+/// (see [`ResolvedModifiers::hold`]).
+#[inline(always)]
+fn held<'a>(
+    k: &LayerKeyIndex,
+    (base, mods): (LayerKeyIndex, &'a LayerModifiers),
+) -> (LayerKeyIndex, &'a [LayerKeyIndex], bool) {
+    match mods {
+        LayerModifiers::Hold(mods) => (base, mods, false),
+        LayerModifiers::Chord(mods) => (base, mods, true),
+        _ => (*k, &[], false),
+    }
+}
+
+/// The key to press for a key with the given base-layer key and modifiers and its one-shot
+/// modifiers (see [`ResolvedModifiers::one_shot`]).
+#[inline(always)]
+fn one_shot<'a>(
+    k: &LayerKeyIndex,
+    (base, mods): (LayerKeyIndex, &'a LayerModifiers),
+) -> (LayerKeyIndex, &'a [LayerKeyIndex]) {
+    match mods {
+        LayerModifiers::OneShot(mods) => (base, mods),
+        _ => (*k, &[]),
+    }
+}
+
+/// Order in which the one-shot modifiers of a symbol are tapped relative to its base-layer key.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OneShotOrdering {
+    /// The modifiers are tapped before the base-layer key.
+    Before,
+    /// The modifiers are tapped after the base-layer key (e.g. if the base-layer key is pressed
+    /// while the one-shot modifiers of a preceding stroke are still latched).
+    After,
+    /// The given share of the weight (clamped to `[0, 1]`) is attributed to tapping the
+    /// modifiers before the base-layer key, the rest to tapping them after it.
+    Split(f64),
+}
+
+impl Default for OneShotOrdering {
+    fn default() -> Self {
+        Self::Before
+    }
+}
+
+impl OneShotOrdering {
+    /// The orderings (whether the modifiers come before the base-layer key) with their shares
+    /// of the weight.
+    fn shares(&self) -> Vec<(bool, f64)> {
+        match *self {
+            Self::Before => vec![(true, 1.0)],
+            Self::After => vec![(false, 1.0)],
+            Self::Split(before) => {
+                let before = before.clamp(0.0, 1.0);
+                vec![(true, before), (false, 1.0 - before)]
+                    .into_iter()
+                    .filter(|(_, share)| *share > 0.0)
+                    .collect()
             }
-            _ => {
-                unimplemented!();
+        }
+    }
+
+    /// The sequences of pressed keys for the symbols of an ngram. Each symbol is given by its
+    /// (base-layer) key and its one-shot modifiers (empty for symbols without one-shot
+    /// modifiers). With [`OneShotOrdering::Split`], there is a sequence for each combination of
+    /// orderings of the symbols with modifiers.
+    pub fn key_sequences(&self, symbols: &[(LayerKeyIndex, &[LayerKeyIndex])]) -> Vec<KeySequence> {
+        let shares = self.shares();
+        let n_keys = symbols.iter().map(|(_, mods)| mods.len() + 1).sum();
+
+        let mut sequences = vec![KeySequence {
+            keys: Vec::with_capacity(n_keys),
+            one_shot: Vec::with_capacity(n_keys),
+            share: 1.0,
+        }];
+        for (key, mods) in symbols {
+            if mods.is_empty() {
+                sequences.iter_mut().for_each(|s| s.push(*key, false));
+                continue;
             }
+
+            sequences = sequences
+                .into_iter()
+                .flat_map(|sequence| {
+                    shares.iter().map(move |(before, share)| {
+                        let mut sequence = sequence.clone();
+                        if *before {
+                            mods.iter().for_each(|m| sequence.push(*m, true));
+                            sequence.push(*key, false);
+                        } else {
+                            sequence.push(*key, false);
+                            mods.iter().for_each(|m| sequence.push(*m, true));
+                        }
+                        sequence.share *= share;
+                        sequence
+                    })
+                })
+                .collect();
+        }
+
+        sequences
+    }
+}
+
+/// Keys pressed for the symbols of an ngram with one-shot modifiers (see
+/// [`OneShotOrdering::key_sequences`]) or lock-layer toggles (see [`resolve_lock_layers`]).
+#[derive(Clone, Debug)]
+pub struct KeySequence {
+    pub keys: Vec<LayerKeyIndex>,
+    /// Whether each of the keys is a one-shot modifier (or a lock-layer toggle)
+    pub one_shot: Vec<bool>,
+    /// Share of the source ngram's weight
+    pub share: f64,
+}
+
+impl KeySequence {
+    fn push(&mut self, key: LayerKeyIndex, one_shot: bool) {
+        self.keys.push(key);
+        self.one_shot.push(one_shot);
+    }
+
+    /// The ngrams of length `N` of the sequence with their weights, given the weight of the
+    /// source ngram. The weight of ngrams containing a one-shot modifier is multiplied by
+    /// `one_shot_weight_factor`.
+    pub fn ngrams<const N: usize>(
+        &self,
+        weight: f64,
+        one_shot_weight_factor: f64,
+    ) -> impl Iterator<Item = ([LayerKeyIndex; N], f64)> + '_ {
+        let weight = self.share * weight;
+        self.keys
+            .windows(N)
+            .zip(self.one_shot.windows(N))
+            .map(move |(keys, one_shot)| {
+                let mut ngram = [keys[0]; N];
+                ngram.copy_from_slice(keys);
+                if one_shot.contains(&true) {
+                    (ngram, one_shot_weight_factor * weight)
+                } else {
+                    (ngram, weight)
+                }
+            })
+    }
+}
+
+/// How the keystrokes toggling lock layers (see
+/// [`keyboard_layout::layout::LayerModifierLocations::Lock`]) are modeled. The symbols of a lock
+/// layer are typed with their base-layer keys in any case.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LockToggles {
+    /// The toggles do not appear in the ngrams.
+    None,
+    /// The toggles are tapped before the first and after the last symbol of each run of symbols
+    /// of a lock layer within an ngram. Ngrams have no context beyond their symbols, so a run at
+    /// the start (end) of an ngram is counted as entering (leaving) the layer.
+    EveryTransition,
+    /// As [`LockToggles::EveryTransition`], but the weight of ngrams containing a toggle is
+    /// multiplied by the given factor (e.g. to account for runs that extend beyond the ngrams).
+    Amortized(f64),
+}
+
+impl Default for LockToggles {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl LockToggles {
+    /// Weight factor of ngrams containing a toggle, if toggles are modeled
+    fn weight_factor(&self) -> Option<f64> {
+        match *self {
+            Self::None => None,
+            Self::EveryTransition => Some(1.0),
+            Self::Amortized(factor) => Some(factor),
         }
     }
 }
 
-impl<'a> TakeTwoLayerKey<'a> {
+/// Order of hold modifiers relative to the base-layer key of their symbol within the ngrams
+/// generated for the symbol (see [`TakeLayerKeys`]).
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldModifierOrdering {
+    /// The modifiers are pressed strictly before the base-layer key.
+    StrictlyBefore,
+    /// The modifiers surround the base-layer key: Besides being pressed before it, they are
+    /// released after it. For each ngram ending with the base-layer key, the mirrored ngram
+    /// (starting with the base-layer key) is generated as well. Its weight is multiplied by the
+    /// given factor.
+    Surrounding(f64),
+}
+
+impl Default for HoldModifierOrdering {
+    fn default() -> Self {
+        Self::StrictlyBefore
+    }
+}
+
+/// Number of permutations of `k` out of `n` elements
+fn n_permutations(n: usize, k: usize) -> usize {
+    if k > n {
+        0
+    } else {
+        (n - k + 1..=n).product()
+    }
+}
+
+/// Iterator over ngrams of length `N` that can be formed from the base-layer key and the
+/// modifiers required for accessing a (higher-layer) symbol.
+///
+/// Each ngram either consists of `N - 1` modifiers followed by the base-layer key or of `N`
+/// modifiers. Every modifier occurs at most once and the base-layer key always comes last (it
+/// is pressed after the modifiers are held). For each modifier beyond the first one in an ngram,
+/// the weight is multiplied by `same_key_mod_factor`.
+///
+/// The ngrams are generated lazily in the following order: For each combination of `N - 1`
+/// modifiers (in lexicographic order of their indices), first all of its permutations followed
+/// by the base-layer key, then for each further modifier with a larger index all permutations
+/// of the combination extended by that modifier.
+///
+/// For `N = 1`, this yields the base-layer key and each modifier. For `N > 1`, the result is
+/// empty if there are less than `N - 1` modifiers. The total number of ngrams is
+/// `P(k, N - 1) + P(k, N)` for `k` modifiers (where `P` denotes the number of permutations).
+///
+/// With [`HoldModifierOrdering::Surrounding`] (see [`TakeLayerKeys::with_ordering`]), each
+/// ngram ending with the base-layer key is directly followed by its mirrored ngram for `N > 1`.
+///
+/// If the modifiers are the keys of a chord (see [`TakeLayerKeys::with_chord`]), they are not
+/// permuted. Instead, the ngrams are the windows of length `N` of the modifiers in their given
+/// order followed by the base-layer key, the one ending with the base-layer key first.
+#[derive(Clone, Debug)]
+pub struct TakeLayerKeys<'a, const N: usize> {
+    base_key: LayerKeyIndex,
+    modifiers: &'a [LayerKeyIndex],
+    /// Weight of the ngram the keys are taken from
+    weight: f64,
+    /// Weight of ngrams containing the base-layer key
+    weight_with_base: f64,
+    /// Weight of ngrams consisting only of modifiers
+    weight_modifiers_only: f64,
+    /// Indices (into `modifiers`) of the current combination. The first `N - 1` entries are the
+    /// combination itself, the last one is the modifier it is extended by (if `extended`).
+    combination: [usize; N],
+    /// Current permutation of the positions in `combination`
+    permutation: [usize; N],
+    extended: bool,
+    /// Weight factor of the mirrored ngrams (see [`HoldModifierOrdering::Surrounding`])
+    release_factor: Option<f64>,
+    /// Mirrored ngram to be yielded next
+    pending: Option<([LayerKeyIndex; N], f64)>,
+    /// Weight factor of ngrams with multiple keys of the chord, if the modifiers form one
+    chord_cost_factor: Option<f64>,
+    remaining: usize,
+}
+
+impl<'a, const N: usize> TakeLayerKeys<'a, N> {
     pub fn new(
         base_key: LayerKeyIndex,
         modifiers: &'a [LayerKeyIndex],
         weight: f64,
         same_key_mod_factor: f64,
     ) -> Self {
+        assert!(N > 0, "ngrams need to have a length of at least one");
+
+        let mut weight_with_base = weight;
+        (2..N).for_each(|_| weight_with_base *= same_key_mod_factor);
+        let mut weight_modifiers_only = weight;
+        (1..N).for_each(|_| weight_modifiers_only *= same_key_mod_factor);
+
+        let mut combination = [0; N];
+        combination.iter_mut().enumerate().for_each(|(i, c)| *c = i);
+
+        let mut permutation = [0; N];
+        permutation.iter_mut().enumerate().for_each(|(i, p)| *p = i);
+
         Self {
             base_key,
             modifiers,
             weight,
-            same_key_mod_factor,
-            iter_inner: None,
-            state_inner: None,
-            inner_variant: 0,
-            iter_outer: None,
-            state_outer: None,
+            weight_with_base,
+            weight_modifiers_only,
+            combination,
+            permutation,
+            extended: false,
+            release_factor: None,
+            pending: None,
+            chord_cost_factor: None,
+            remaining: Self::count(modifiers.len()),
         }
     }
-}
 
-// // use length 4 for up to 2 modifiers
-// // use length 9 for up to 3 modifiers
-// pub fn take_two_layerkey(
-//     base_key: LayerKeyIndex,
-//     modifiers: &[LayerKeyIndex],
-//     weight: f64,
-//     same_key_mod_adjustment: f64,
-// ) -> ArrayVec<[((LayerKeyIndex, LayerKeyIndex), f64); 4]> {
-//     let mut res = ArrayVec::<[((LayerKeyIndex, LayerKeyIndex), f64); 4]>::new();
+    /// If `chord`, generate the ngrams for modifiers that are pressed together as a chord: The
+    /// modifiers keep their order and the weight of ngrams with multiple of them is multiplied by
+    /// `chord_cost_factor` (instead of `same_key_mod_factor`). Overrides
+    /// [`TakeLayerKeys::with_ordering`], as the keys of a chord are released together. Needs to
+    /// be called before iterating.
+    pub fn with_chord(mut self, chord: bool, chord_cost_factor: f64) -> Self {
+        if chord {
+            self.chord_cost_factor = Some(chord_cost_factor);
+            self.release_factor = None;
+            self.remaining = Self::count_chord(self.modifiers.len());
+        }
+        self
+    }
 
-//     modifiers.iter().enumerate().for_each(|(i, m1)| {
-//         res.push(((*m1, base_key), weight));
+    /// Number of ngrams generated from a chord of `n_keys` keys.
+    pub fn count_chord(n_keys: usize) -> usize {
+        (n_keys + 2).saturating_sub(N)
+    }
 
-//         modifiers.iter().skip(i + 1).for_each(|m2| {
-//             if m1 != m2 {
-//                 res.push(((*m1, *m2), same_key_mod_adjustment * weight));
-//                 res.push(((*m2, *m1), same_key_mod_adjustment * weight));
-//             }
-//         });
-//     });
+    /// The next window of the chord's keys followed by the base-layer key (see
+    /// [`TakeLayerKeys::with_chord`]).
+    fn next_chord_window(&mut self, chord_cost_factor: f64) -> ([LayerKeyIndex; N], f64) {
+        let n_windows = Self::count_chord(self.modifiers.len());
+        let start = match n_windows - self.remaining {
+            0 => n_windows - 1,
+            i => i - 1,
+        };
 
-//     res
-// }
+        let mut ngram = [self.base_key; N];
+        ngram
+            .iter_mut()
+            .zip(start..)
+            .filter(|(_, i)| *i < self.modifiers.len())
+            .for_each(|(e, i)| *e = self.modifiers[i]);
 
-/// Iterator over trigrams of combinations of the base-layer key and with two modifiers.
-/// If there is no or only one modifier, the result is empty.
-#[derive(Clone, Debug)]
-pub struct TakeThreeLayerKey<'a> {
-    base_key: LayerKeyIndex,
-    modifiers: &'a [LayerKeyIndex],
-    weight: f64,
-    same_key_mod_factor: f64,
-    iter_inner: Option<slice::Iter<'a, LayerKeyIndex>>,
-    state_inner: Option<LayerKeyIndex>,
-    inner_variant: u8,
-    iter_middle: Option<slice::Iter<'a, LayerKeyIndex>>,
-    state_middle: Option<LayerKeyIndex>,
-    middle_variant: u8,
-    iter_outer: Option<slice::Iter<'a, LayerKeyIndex>>,
-    state_outer: Option<LayerKeyIndex>,
-}
+        let n_chord_keys = (start + N).min(self.modifiers.len()) - start;
+        let weight = match n_chord_keys > 1 {
+            true => self.weight * chord_cost_factor,
+            false => self.weight,
+        };
 
-impl<'a> Iterator for TakeThreeLayerKey<'a> {
-    type Item = ((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64);
+        (ngram, weight)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.iter_outer.is_none() {
-            // initialization
-            self.iter_outer = Some(self.modifiers.iter());
+    /// Generate the ngrams for the given ordering of the modifiers relative to the base-layer
+    /// key. Needs to be called before iterating.
+    pub fn with_ordering(mut self, ordering: HoldModifierOrdering) -> Self {
+        if let HoldModifierOrdering::Surrounding(factor) = ordering {
+            if N > 1 {
+                self.release_factor = Some(factor);
+                self.remaining = Self::count_with_ordering(self.modifiers.len(), ordering);
+            }
         }
+        self
+    }
 
-        if self.state_middle.is_none() {
-            // initialization
-            // or middle iterator ended -> advance outer loop and reinit middle one
-            self.state_outer = self.iter_outer.as_mut().unwrap().next().cloned();
+    /// Number of ngrams generated from `n_modifiers` modifiers with the given ordering.
+    pub fn count_with_ordering(n_modifiers: usize, ordering: HoldModifierOrdering) -> usize {
+        match ordering {
+            // each ngram with the base-layer key is mirrored
+            HoldModifierOrdering::Surrounding(_) if N > 1 => {
+                Self::count(n_modifiers) + n_permutations(n_modifiers, N - 1)
+            }
+            _ => Self::count(n_modifiers),
+        }
+    }
 
-            self.state_outer?; // if outer iterator ended -> no more elements -> return None
+    /// Number of ngrams generated from `n_modifiers` modifiers.
+    pub fn count(n_modifiers: usize) -> usize {
+        n_permutations(n_modifiers, N - 1) + n_permutations(n_modifiers, N)
+    }
 
-            // the middle iterator is a clone of the outer (plus one skip, which happened in the line above)
-            self.iter_middle = Some(self.iter_outer.as_ref().cloned().unwrap());
-            self.state_middle = self.iter_middle.as_mut().unwrap().next().cloned();
+    /// Advance `permutation[..len]` to the next permutation in lexicographic order.
+    /// Returns `false` (and resets it) if it was the last one.
+    fn next_permutation(&mut self, len: usize) -> bool {
+        let p = &mut self.permutation[..len];
+        match (1..len).rev().find(|&i| p[i - 1] < p[i]) {
+            Some(i) => {
+                let j = (i..len).rev().find(|&j| p[i - 1] < p[j]).unwrap();
+                p.swap(i - 1, j);
+                p[i..].reverse();
+                true
+            }
+            None => {
+                p.sort_unstable();
+                false
+            }
+        }
+    }
 
-            self.state_middle?; // if middle loop ended right after being initialized -> nothing left to do  -> return None
+    /// Advance `combination[..N - 1]` to the next combination in lexicographic order.
+    fn next_combination(&mut self) {
+        let n_modifiers = self.modifiers.len();
+        let c = &mut self.combination[..N - 1];
+        if let Some(i) = (0..N - 1).rev().find(|&i| c[i] < n_modifiers - (N - 1) + i) {
+            c[i] += 1;
+            (i + 1..N - 1).for_each(|j| c[j] = c[j - 1] + 1);
         }
+    }
 
-        if self.state_inner.is_none() {
-            // on first two entries of middle loop, return base key variant
-            match self.middle_variant {
-                0 => {
-                    // first variant is m1-basekey
-                    self.middle_variant = 1;
-                    return Some((
-                        (
-                            self.state_outer.unwrap(),
-                            self.state_middle.unwrap(),
-                            self.base_key,
-                        ),
-                        self.weight * self.same_key_mod_factor,
-                    ));
-                }
-                1 => {
-                    // second variant is m2-basekey
-                    self.middle_variant = 0;
-                    let res = Some((
-                        (
-                            self.state_middle.unwrap(),
-                            self.state_outer.unwrap(),
-                            self.base_key,
-                        ),
-                        self.weight * self.same_key_mod_factor,
-                    ));
-
-                    // prepare inner iterator
-                    // the inner iterator is a clone of the middle (plus one skip)
-                    self.iter_inner = Some(self.iter_middle.as_ref().cloned().unwrap());
-                    self.state_inner = self.iter_inner.as_mut().unwrap().next().cloned();
-
-                    if self.state_inner.is_none() {
-                        // inner iterator was empty -> advance middle iterator
-                        self.state_middle = self.iter_middle.as_mut().unwrap().next().cloned();
-                    }
+    /// Move on to the next extension of the current combination or to the next combination.
+    fn next_extension(&mut self) {
+        let next = if self.extended {
+            self.combination[N - 1] + 1
+        } else if N > 1 {
+            self.combination[N - 2] + 1
+        } else {
+            0
+        };
 
-                    return res;
-                }
-                _ => (),
-            }
+        if next < self.modifiers.len() {
+            self.combination[N - 1] = next;
+            self.extended = true;
+        } else {
+            self.extended = false;
+            self.next_combination();
         }
+    }
+}
 
-        match self.inner_variant {
-            0 => {
-                self.inner_variant = 1;
-
-                // first variant is m1-m2-m3
-                Some((
-                    (
-                        self.state_outer.unwrap(),
-                        self.state_middle.unwrap(),
-                        self.state_inner.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ))
-            }
-            1 => {
-                self.inner_variant = 2;
-
-                // second variant is m1-m3-m2
-                Some((
-                    (
-                        self.state_outer.unwrap(),
-                        self.state_inner.unwrap(),
-                        self.state_middle.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ))
-            }
-            2 => {
-                self.inner_variant = 3;
-
-                // third variant is m2-m1-m3
-                Some((
-                    (
-                        self.state_middle.unwrap(),
-                        self.state_outer.unwrap(),
-                        self.state_inner.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ))
-            }
-            3 => {
-                self.inner_variant = 4;
-
-                // fourth variant is m2-m3-m1
-                Some((
-                    (
-                        self.state_middle.unwrap(),
-                        self.state_inner.unwrap(),
-                        self.state_outer.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ))
-            }
-            4 => {
-                self.inner_variant = 5;
-
-                // fifth variant is m3-m1-m2
-                Some((
-                    (
-                        self.state_inner.unwrap(),
-                        self.state_outer.unwrap(),
-                        self.state_middle.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ))
+impl<'a, const N: usize> Iterator for TakeLayerKeys<'a, N> {
+    type Item = ([LayerKeyIndex; N], f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if let Some(factor) = self.chord_cost_factor {
+            let window = self.next_chord_window(factor);
+            self.remaining -= 1;
+            return Some(window);
+        }
+        self.remaining -= 1;
+
+        if let Some(mirrored) = self.pending.take() {
+            return Some(mirrored);
+        }
+
+        let mut ngram = [self.base_key; N];
+        if self.extended {
+            ngram
+                .iter_mut()
+                .zip(self.permutation.iter())
+                .for_each(|(e, p)| *e = self.modifiers[self.combination[*p]]);
+
+            if !self.next_permutation(N) {
+                self.next_extension();
             }
-            5 => {
-                self.inner_variant = 0;
-
-                // sixth variant is m3-m2-m1
-                let res = Some((
-                    (
-                        self.state_inner.unwrap(),
-                        self.state_middle.unwrap(),
-                        self.state_outer.unwrap(),
-                    ),
-                    self.weight * self.same_key_mod_factor * self.same_key_mod_factor,
-                ));
-
-                // there is no seventh variant -> advance inner loop
-                self.state_inner = self.iter_inner.as_mut().unwrap().next().cloned();
-
-                if self.state_inner.is_none() {
-                    // inner iterator has finished -> advance middle loop
-                    self.state_middle = self.iter_middle.as_mut().unwrap().next().cloned();
-                };
-
-                res
+
+            Some((ngram, self.weight_modifiers_only))
+        } else {
+            ngram[..N - 1]
+                .iter_mut()
+                .zip(self.permutation.iter())
+                .for_each(|(e, p)| *e = self.modifiers[self.combination[*p]]);
+
+            if !self.next_permutation(N - 1) {
+                self.next_extension();
             }
-            _ => {
-                unimplemented!();
+
+            if let Some(factor) = self.release_factor {
+                let mut mirrored = ngram;
+                mirrored.reverse();
+                self.pending = Some((mirrored, factor * self.weight_with_base));
             }
+
+            Some((ngram, self.weight_with_base))
         }
     }
-}
 
-impl<'a> TakeThreeLayerKey<'a> {
-    pub fn new(
-        base_key: LayerKeyIndex,
-        modifiers: &'a [LayerKeyIndex],
-        weight: f64,
-        same_key_mod_factor: f64,
-    ) -> Self {
-        Self {
-            base_key,
-            modifiers,
-            weight,
-            same_key_mod_factor,
-            iter_inner: None,
-            state_inner: None,
-            inner_variant: 0,
-            iter_middle: None,
-            state_middle: None,
-            middle_variant: 0,
-            iter_outer: None,
-            state_outer: None,
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
 }
 
-// // use length 2 for up to 2 modifiers
-// // use length 10 for up to 3 modifiers
-// pub fn take_three_layerkey(
-//     base_key: LayerKeyIndex,
-//     modifiers: &[LayerKeyIndex],
-//     weight: f64,
-//     same_key_mod_adjustment: f64,
-// ) -> ArrayVec<[((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64); 18]> {
-//     let mut res = ArrayVec::<[((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64); 18]>::new();
-
-//     modifiers.iter().enumerate().for_each(|(i, m1)| {
-//         modifiers.iter().skip(i + 1).enumerate().for_each(|(j, m2)| {
-//             res.push(((*m1, *m2, base_key), same_key_mod_adjustment * weight));
-//             res.push(((*m2, *m1, base_key), same_key_mod_adjustment * weight));
-
-//             // the following is only relevant for keys with 3+ modifiers (which normally does not occur)
-//             modifiers.iter().skip(j + i + 2).for_each(|m3| {
-//                 res.extend(vec![
-//                     (
-//                         (*m1, *m2, *m3),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                     (
-//                         (*m1, *m3, *m2),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                     (
-//                         (*m2, *m1, *m3),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                     (
-//                         (*m2, *m3, *m1),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                     (
-//                         (*m3, *m1, *m2),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                     (
-//                         (*m3, *m2, *m1),
-//                         same_key_mod_adjustment * same_key_mod_adjustment * weight,
-//                     ),
-//                 ]);
-//             });
-//         });
-//     });
-
-//     res
-// }
+impl<'a, const N: usize> ExactSizeIterator for TakeLayerKeys<'a, N> {}
 
 pub trait NgramMap<Ngram: Eq + Hash> {
     /// Adds the ngram to the HashMap if it does not already exist.
@@ -438,3 +920,624 @@ impl<Ngram: Eq + Hash> NgramMap<Ngram> for AHashMap<Ngram, f64> {
         *self.entry(k).or_insert(0.0) += w;
     }
 }
+
+impl<Ngram: Eq + Hash> NgramMap<Ngram> for IndexMap<Ngram> {
+    #[inline(always)]
+    fn insert_or_add_weight(&mut self, k: Ngram, w: f64) {
+        *self.entry(k).or_insert(0.0) += w;
+    }
+}
+
+/// Hasher builder with fixed seeds for the maps of mapped ngrams.
+///
+/// With randomly seeded hashers, the iteration order of these maps (and thereby the order in
+/// which the metrics sum up the ngrams' costs) would change with every evaluation. With fixed
+/// seeds, it only depends on the inserted ngrams, so that repeated evaluations of a layout yield
+/// identical results.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedState;
+
+impl BuildHasher for FixedState {
+    type Hasher = AHasher;
+
+    #[inline(always)]
+    fn build_hasher(&self) -> AHasher {
+        ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher()
+    }
+}
+
+/// Weights of mapped ngrams (or keys) with a deterministic iteration order
+pub type IndexMap<K> = HashMap<K, f64, FixedState>;
+
+/// Number of presses of each modifier (in terms of the weights of the source ngrams)
+pub type ModifierPresses = IndexMap<LayerKeyIndex>;
+
+/// Weight of the ngrams that can not be generated by a layout per symbol. The weight of each
+/// such ngram is attributed to its first symbol that is not found.
+pub type MissingSymbols = AHashMap<char, f64>;
+
+/// Iterate over the entries of an ngram map. If `sorted`, the entries are visited in the order of
+/// their keys instead of the map's (hash-dependent) order, so that sums of their weights are
+/// bit-identical independent of the hasher's seeds and the platform.
+pub fn entries<'a, K: Ord, S>(
+    grams: &'a HashMap<K, f64, S>,
+    sorted: bool,
+) -> Box<dyn Iterator<Item = (&'a K, &'a f64)> + 'a> {
+    if sorted {
+        let mut entries: Vec<_> = grams.iter().collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        Box::new(entries.into_iter())
+    } else {
+        Box::new(grams.iter())
+    }
+}
+
+/// Total weight of an ngram map, summed in the order of [`entries`].
+pub fn total_weight<K: Ord, S>(grams: &HashMap<K, f64, S>, sorted: bool) -> f64 {
+    entries(grams, sorted).map(|(_, w)| w).sum()
+}
+
+/// Which ngrams containing a line break are excluded from the mapping. Line breaks within ngrams
+/// usually do not correspond to a flowing sequence of keystrokes, e.g. due to a mental pause after
+/// hitting the "Enter" key.
+///
+/// Ngrams consisting of consecutive line breaks only (e.g. `"\n\n"`) are never excluded.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineBreakHandling {
+    /// Do not exclude any ngrams (e.g. for code corpora).
+    None,
+    /// Exclude ngrams that contain a line break, followed by a non-line-break character.
+    BreakBefore,
+    /// Exclude ngrams that contain a non-line-break character, followed by a line break.
+    BreakAfter,
+    /// Exclude ngrams that contain a line break next to a non-line-break character.
+    Both,
+}
+
+impl Default for LineBreakHandling {
+    fn default() -> Self {
+        Self::BreakBefore
+    }
+}
+
+impl From<bool> for LineBreakHandling {
+    /// The meaning of the former boolean option: `true` excludes ngrams with a line break,
+    /// followed by a non-line-break character.
+    fn from(exclude: bool) -> Self {
+        if exclude {
+            Self::BreakBefore
+        } else {
+            Self::None
+        }
+    }
+}
+
+impl LineBreakHandling {
+    /// Whether the ngram consisting of the given symbols is excluded.
+    #[inline(always)]
+    pub fn excludes(&self, symbols: &[char]) -> bool {
+        let (before, after) = match self {
+            Self::None => return false,
+            Self::BreakBefore => (true, false),
+            Self::BreakAfter => (false, true),
+            Self::Both => (true, true),
+        };
+
+        symbols.windows(2).any(|c| {
+            (before && c[0] == '\n' && c[1] != '\n') || (after && c[0] != '\n' && c[1] == '\n')
+        })
+    }
+}
+
+/// Replacements of symbols in the char-based ngrams by (possibly multiple or no) other symbols,
+/// e.g. of typographic characters that a layout generates via compose sequences
+pub type SymbolSubstitutions = AHashMap<char, String>;
+
+/// The ngrams of `N` symbols that an ngram is replaced by after substituting its symbols (see
+/// [`SymbolSubstitutions`]).
+///
+/// If a symbol is replaced by multiple symbols, the substituted ngram is longer than `N` and
+/// split into sliding windows of `N` symbols. Only windows starting within the replacement of the
+/// first symbol are returned. That way, each window of a substituted text results from exactly
+/// one of the original text's ngrams (the one starting at the same position).
+pub fn substitute_symbols<const N: usize>(
+    symbols: [char; N],
+    substitutions: &SymbolSubstitutions,
+) -> Vec<[char; N]> {
+    if !symbols.iter().any(|c| substitutions.contains_key(c)) {
+        return vec![symbols];
+    }
+
+    let substituted: Vec<char> = symbols
+        .iter()
+        .flat_map(|c| match substitutions.get(c) {
+            Some(replacement) => replacement.chars().collect(),
+            None => vec![*c],
+        })
+        .collect();
+    let n_first = substitutions
+        .get(&symbols[0])
+        .map_or(1, |replacement| replacement.chars().count());
+
+    (0..n_first)
+        .filter(|start| start + N <= substituted.len())
+        .map(|start| {
+            let mut window = [' '; N];
+            window.copy_from_slice(&substituted[start..start + N]);
+            window
+        })
+        .collect()
+}
+
+/// Collapse runs of whitespace within an ngram into a single space. Single whitespace characters
+/// are replaced by a space as well. Line breaks neither are replaced nor form runs, so that they
+/// remain subject to the [`LineBreakHandling`].
+///
+/// `None` if the ngram contains a run of whitespace, i.e. if the collapsed ngram would be shorter.
+pub fn collapse_space_runs<const N: usize>(symbols: [char; N]) -> Option<[char; N]> {
+    let is_space = |c: &char| c.is_whitespace() && *c != '\n';
+    if symbols
+        .windows(2)
+        .any(|c| is_space(&c[0]) && is_space(&c[1]))
+    {
+        return None;
+    }
+
+    Some(symbols.map(|c| if is_space(&c) { ' ' } else { c }))
+}
+
+/// Deserialize a [`LineBreakHandling`] from its name or from a boolean (see
+/// [`LineBreakHandling::from`]) for compatibility with older configurations.
+pub fn deserialize_line_break_handling<'de, D>(
+    deserializer: D,
+) -> Result<LineBreakHandling, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Flag(bool),
+        Handling(LineBreakHandling),
+    }
+
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Flag(exclude) => exclude.into(),
+        Repr::Handling(handling) => handling,
+    })
+}
+
+/// Count one press of each modifier required for accessing a single symbol of a source ngram,
+/// independent of how many of the split ngrams contain the modifier.
+#[inline(always)]
+pub fn add_modifier_presses(presses: &mut ModifierPresses, modifiers: &[LayerKeyIndex], w: f64) {
+    modifiers
+        .iter()
+        .for_each(|m| presses.insert_or_add_weight(*m, w));
+}
+
+/// How the weight of a symbol that a layout generates with multiple [`LayerKey`]s (e.g. on
+/// different layers, see [`Layout::get_duplicate_symbol`]) is attributed to them.
+///
+/// [`LayerKey`]: keyboard_layout::layout::LayerKey
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateSymbolPolicy {
+    /// Use the cheapest one (see [`Layout::layerkey_cost`]) only.
+    CheapestOnly,
+    /// Split the weight evenly among all of them.
+    SplitEvenly,
+    /// Split the weight among all of them inversely proportional to their costs (see
+    /// [`Layout::layerkey_cost`]).
+    SplitByCost,
+}
+
+impl Default for DuplicateSymbolPolicy {
+    fn default() -> Self {
+        Self::CheapestOnly
+    }
+}
+
+impl DuplicateSymbolPolicy {
+    /// The fractions of the weight attributed to each of the [`LayerKeyIndex`]es generating the
+    /// same symbol.
+    pub fn fractions(&self, indices: &[LayerKeyIndex], layout: &Layout) -> Vec<f64> {
+        match self {
+            Self::CheapestOnly => {
+                let mut fractions = vec![0.0; indices.len()];
+                fractions[0] = 1.0;
+                fractions
+            }
+            Self::SplitEvenly => vec![1.0 / indices.len() as f64; indices.len()],
+            Self::SplitByCost => {
+                let inverse_costs: Vec<f64> = indices
+                    .iter()
+                    .map(|idx| 1.0 / layout.layerkey_cost(idx).max(f64::EPSILON))
+                    .collect();
+                let total: f64 = inverse_costs.iter().sum();
+                inverse_costs.iter().map(|c| c / total).collect()
+            }
+        }
+    }
+}
+
+/// Distribute the weight of an ngram among the [`LayerKeyIndex`]es generating the same symbols
+/// according to the [`DuplicateSymbolPolicy`]. The ngram may be split into several ones sharing
+/// its weight.
+pub fn resolve_duplicate_symbols<const N: usize>(
+    ngram: [LayerKeyIndex; N],
+    w: f64,
+    layout: &Layout,
+    policy: DuplicateSymbolPolicy,
+) -> Vec<([LayerKeyIndex; N], f64)> {
+    let mut resolved = vec![(ngram, w)];
+    if policy == DuplicateSymbolPolicy::CheapestOnly {
+        return resolved;
+    }
+
+    for i in 0..N {
+        let indices = match layout.get_duplicate_symbol(&ngram[i]) {
+            Some(indices) => indices,
+            None => continue,
+        };
+        let fractions = policy.fractions(indices, layout);
+
+        resolved = resolved
+            .into_iter()
+            .flat_map(|(ngram, w)| {
+                indices
+                    .iter()
+                    .zip(fractions.iter())
+                    .map(move |(k, fraction)| {
+                        let mut ngram = ngram;
+                        ngram[i] = *k;
+                        (ngram, fraction * w)
+                    })
+            })
+            .collect();
+    }
+
+    resolved
+}
+
+/// Choose the keys for symbols that are generated by multiple keys (see
+/// [`keyboard_layout::multi_key_symbol::MultiKeySymbol`]). The key for such a symbol may depend
+/// on the (resolved) preceding key in the ngram, so the ngram may be split into several ones
+/// sharing its weight.
+pub fn resolve_multi_key_symbols<const N: usize>(
+    ngram: [LayerKeyIndex; N],
+    w: f64,
+    layout: &Layout,
+) -> Vec<([LayerKeyIndex; N], f64)> {
+    let mut resolved = vec![(ngram, w)];
+
+    for i in 0..N {
+        let multi_key_symbol = match layout.get_multi_key_symbol(&ngram[i]) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        resolved = resolved
+            .into_iter()
+            .flat_map(|(ngram, w)| {
+                let preceding = (i > 0).then(|| {
+                    layout
+                        .geometry()
+                        .hand(layout.get_layerkey(&ngram[i - 1]).key_index)
+                });
+                multi_key_symbol
+                    .choose(preceding)
+                    .into_iter()
+                    .map(move |(k, fraction)| {
+                        let mut ngram = ngram;
+                        ngram[i] = k;
+                        (ngram, fraction * w)
+                    })
+            })
+            .collect();
+    }
+
+    resolved
+}
+
+/// Replace the symbols of lock layers in an ngram by their base-layer keys. Depending on
+/// [`LockToggles`], the toggles of the layers are inserted at each transition into and out of a
+/// lock layer, so the ngram may be split into several ones (each with the full weight, possibly
+/// multiplied by the factor of [`LockToggles::Amortized`]). Each inserted toggle is counted as a
+/// press of its modifier.
+pub fn resolve_lock_layers<const N: usize>(
+    ngram: [LayerKeyIndex; N],
+    w: f64,
+    layout: &Layout,
+    resolved: &ResolvedModifiers,
+    lock_toggles: LockToggles,
+    modifier_presses: &mut ModifierPresses,
+) -> Vec<([LayerKeyIndex; N], f64)> {
+    let locks: Vec<Option<(LayerKeyIndex, &[LayerKeyIndex])>> =
+        ngram.iter().map(|k| resolved.lock(k)).collect();
+    if locks.iter().all(Option::is_none) {
+        return vec![(ngram, w)];
+    }
+
+    let factor = match lock_toggles.weight_factor() {
+        Some(factor) => factor,
+        None => {
+            let mut ngram = ngram;
+            ngram
+                .iter_mut()
+                .zip(locks.iter())
+                .for_each(|(k, lock)| *k = lock.map_or(*k, |(base, _)| base));
+            return vec![(ngram, w)];
+        }
+    };
+
+    // whether two consecutive keys belong to the same lock layer
+    let same_layer = |i: usize, j: usize| {
+        locks[i].is_some()
+            && locks[j].is_some()
+            && layout.get_layerkey(&ngram[i]).layer == layout.get_layerkey(&ngram[j]).layer
+    };
+
+    let mut sequence = KeySequence {
+        keys: Vec::with_capacity(3 * N),
+        one_shot: Vec::with_capacity(3 * N),
+        share: 1.0,
+    };
+    for (i, k) in ngram.iter().enumerate() {
+        let (base, toggles) = match locks[i] {
+            Some(lock) => lock,
+            None => {
+                sequence.push(*k, false);
+                continue;
+            }
+        };
+
+        if i == 0 || !same_layer(i - 1, i) {
+            toggles.iter().for_each(|t| sequence.push(*t, true));
+            add_modifier_presses(modifier_presses, toggles, w);
+        }
+        sequence.push(base, false);
+        if i == N - 1 || !same_layer(i, i + 1) {
+            toggles.iter().for_each(|t| sequence.push(*t, true));
+            add_modifier_presses(modifier_presses, toggles, w);
+        }
+    }
+
+    sequence.ngrams::<N>(w, factor).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: LayerKeyIndex = 1;
+    const MODIFIERS: [LayerKeyIndex; 4] = [10, 11, 12, 13];
+
+    fn take<const N: usize>(n_modifiers: usize) -> Vec<([LayerKeyIndex; N], f64)> {
+        let res: Vec<_> =
+            TakeLayerKeys::<N>::new(BASE, &MODIFIERS[..n_modifiers], 2.0, 0.5).collect();
+        assert_eq!(res.len(), TakeLayerKeys::<N>::count(n_modifiers));
+        res
+    }
+
+    #[test]
+    fn take_one() {
+        assert_eq!(take::<1>(0), vec![([1], 2.0)]);
+        assert_eq!(take::<1>(1), vec![([1], 2.0), ([10], 2.0)]);
+        assert_eq!(take::<1>(2), vec![([1], 2.0), ([10], 2.0), ([11], 2.0)]);
+        assert_eq!(
+            take::<1>(3),
+            vec![([1], 2.0), ([10], 2.0), ([11], 2.0), ([12], 2.0)]
+        );
+    }
+
+    #[test]
+    fn take_two() {
+        assert_eq!(take::<2>(0), vec![]);
+        assert_eq!(take::<2>(1), vec![([10, 1], 2.0)]);
+        assert_eq!(
+            take::<2>(2),
+            vec![
+                ([10, 1], 2.0),
+                ([10, 11], 1.0),
+                ([11, 10], 1.0),
+                ([11, 1], 2.0)
+            ]
+        );
+        assert_eq!(
+            take::<2>(3),
+            vec![
+                ([10, 1], 2.0),
+                ([10, 11], 1.0),
+                ([11, 10], 1.0),
+                ([10, 12], 1.0),
+                ([12, 10], 1.0),
+                ([11, 1], 2.0),
+                ([11, 12], 1.0),
+                ([12, 11], 1.0),
+                ([12, 1], 2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn take_three() {
+        assert_eq!(take::<3>(0), vec![]);
+        assert_eq!(take::<3>(1), vec![]);
+        assert_eq!(take::<3>(2), vec![([10, 11, 1], 1.0), ([11, 10, 1], 1.0)]);
+        assert_eq!(
+            take::<3>(3),
+            vec![
+                ([10, 11, 1], 1.0),
+                ([11, 10, 1], 1.0),
+                ([10, 11, 12], 0.5),
+                ([10, 12, 11], 0.5),
+                ([11, 10, 12], 0.5),
+                ([11, 12, 10], 0.5),
+                ([12, 10, 11], 0.5),
+                ([12, 11, 10], 0.5),
+                ([10, 12, 1], 1.0),
+                ([12, 10, 1], 1.0),
+                ([11, 12, 1], 1.0),
+                ([12, 11, 1], 1.0)
+            ]
+        );
+    }
+
+    fn take_surrounding<const N: usize>(n_modifiers: usize) -> Vec<([LayerKeyIndex; N], f64)> {
+        let ordering = HoldModifierOrdering::Surrounding(0.5);
+        let res: Vec<_> = TakeLayerKeys::<N>::new(BASE, &MODIFIERS[..n_modifiers], 2.0, 0.5)
+            .with_ordering(ordering)
+            .collect();
+        assert_eq!(
+            res.len(),
+            TakeLayerKeys::<N>::count_with_ordering(n_modifiers, ordering)
+        );
+        res
+    }
+
+    #[test]
+    fn take_surrounding_mirrors_ngrams_with_base_key() {
+        assert_eq!(take_surrounding::<1>(2), take::<1>(2));
+        assert_eq!(take_surrounding::<2>(0), vec![]);
+        assert_eq!(
+            take_surrounding::<2>(1),
+            vec![([10, 1], 2.0), ([1, 10], 1.0)]
+        );
+        assert_eq!(
+            take_surrounding::<2>(2),
+            vec![
+                ([10, 1], 2.0),
+                ([1, 10], 1.0),
+                ([10, 11], 1.0),
+                ([11, 10], 1.0),
+                ([11, 1], 2.0),
+                ([1, 11], 1.0)
+            ]
+        );
+        assert_eq!(take_surrounding::<3>(1), vec![]);
+        assert_eq!(
+            take_surrounding::<3>(2),
+            vec![
+                ([10, 11, 1], 1.0),
+                ([1, 11, 10], 0.5),
+                ([11, 10, 1], 1.0),
+                ([1, 10, 11], 0.5)
+            ]
+        );
+    }
+
+    fn take_chord<const N: usize>(n_keys: usize) -> Vec<([LayerKeyIndex; N], f64)> {
+        let res: Vec<_> = TakeLayerKeys::<N>::new(BASE, &MODIFIERS[..n_keys], 2.0, 0.5)
+            .with_ordering(HoldModifierOrdering::Surrounding(0.5))
+            .with_chord(true, 0.25)
+            .collect();
+        assert_eq!(res.len(), TakeLayerKeys::<N>::count_chord(n_keys));
+        res
+    }
+
+    #[test]
+    fn take_chord_keeps_order_of_keys() {
+        assert_eq!(take_chord::<1>(2), take::<1>(2));
+        assert_eq!(take_chord::<2>(2), vec![([11, 1], 2.0), ([10, 11], 0.5)]);
+        assert_eq!(
+            take_chord::<2>(3),
+            vec![([12, 1], 2.0), ([10, 11], 0.5), ([11, 12], 0.5)]
+        );
+        assert_eq!(take_chord::<3>(2), vec![([10, 11, 1], 0.5)]);
+        assert_eq!(
+            take_chord::<3>(3),
+            vec![([11, 12, 1], 0.5), ([10, 11, 12], 0.5)]
+        );
+        assert_eq!(take_chord::<4>(2), vec![]);
+
+        // without a chord, the modifiers are permuted
+        let hold: Vec<_> = TakeLayerKeys::<2>::new(BASE, &MODIFIERS[..2], 2.0, 0.5)
+            .with_chord(false, 0.25)
+            .collect();
+        assert_eq!(hold, take::<2>(2));
+    }
+
+    #[test]
+    fn line_break_handling() {
+        let excluded = |handling: LineBreakHandling| {
+            ["a\nb", "ab\n", "\nab", "\n\n", "ab"]
+                .iter()
+                .map(|s| handling.excludes(&s.chars().collect::<Vec<char>>()))
+                .collect::<Vec<bool>>()
+        };
+
+        assert_eq!(
+            excluded(LineBreakHandling::None),
+            vec![false, false, false, false, false]
+        );
+        assert_eq!(
+            excluded(LineBreakHandling::BreakBefore),
+            vec![true, false, true, false, false]
+        );
+        assert_eq!(
+            excluded(LineBreakHandling::BreakAfter),
+            vec![true, true, false, false, false]
+        );
+        assert_eq!(
+            excluded(LineBreakHandling::Both),
+            vec![true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn substitute() {
+        let substitutions: SymbolSubstitutions = [('’', "'".to_string()), ('…', "...".to_string())]
+            .iter()
+            .cloned()
+            .collect();
+
+        assert_eq!(
+            substitute_symbols(['a', 'b'], &substitutions),
+            vec![['a', 'b']]
+        );
+        assert_eq!(
+            substitute_symbols(['s', '’', 's'], &substitutions),
+            vec![['s', '\'', 's']]
+        );
+        assert_eq!(
+            substitute_symbols(['a', '…'], &substitutions),
+            vec![['a', '.']]
+        );
+        assert_eq!(
+            substitute_symbols(['…', 'b'], &substitutions),
+            vec![['.', '.'], ['.', '.'], ['.', 'b']]
+        );
+        assert_eq!(
+            substitute_symbols(['…'], &substitutions),
+            vec![['.'], ['.'], ['.']]
+        );
+    }
+
+    #[test]
+    fn collapse_whitespace() {
+        assert_eq!(collapse_space_runs(['a', '\t', 'b']), Some(['a', ' ', 'b']));
+        assert_eq!(
+            collapse_space_runs(['a', '\n', '\t']),
+            Some(['a', '\n', ' '])
+        );
+        assert_eq!(
+            collapse_space_runs(['\n', '\n', 'b']),
+            Some(['\n', '\n', 'b'])
+        );
+        assert_eq!(collapse_space_runs(['e', ' ', ' ']), None);
+        assert_eq!(collapse_space_runs([' ', '\t', 'b']), None);
+        assert_eq!(collapse_space_runs(['\t']), Some([' ']));
+    }
+
+    #[test]
+    fn take_with_many_modifiers() {
+        // only counts are checked for more modifiers than occur in practice
+        assert_eq!(take::<1>(4).len(), 5);
+        assert_eq!(take::<2>(4).len(), 16);
+        assert_eq!(take::<3>(4).len(), 36);
+        assert_eq!(take::<4>(4).len(), 48);
+        assert_eq!(take::<4>(2).len(), 0);
+    }
+}
@@ -0,0 +1,111 @@
+//! This module provides writing mapped ngrams (in terms of [`LayerKey`]s, i.e. after splitting
+//! off modifiers) to TSV files for inspecting them with external tools.
+
+use super::{MappedBigrams, MappedTrigrams, MappedUnigrams};
+
+use crate::{atomic_file, ngrams::process_special_characters_inverse};
+
+use keyboard_layout::layout::LayerKey;
+
+use anyhow::{Context, Result};
+use std::{fs::create_dir_all, path::Path};
+
+/// Header line of the TSV files
+const HEADER: &str = "symbols\tpositions\tlayers\tmodifiers\tweight";
+
+/// Escape a symbol like in the ngram frequency files. Tabs are escaped as well, because they
+/// separate the columns.
+fn escape(symbol: char) -> String {
+    process_special_characters_inverse(&symbol.to_string()).replace('\t', "\\t")
+}
+
+/// A line of the TSV file for the ngram of the given keys: the (escaped) symbols, the matrix
+/// positions (column and row), the layers, and whether the keys are modifiers.
+fn line(keys: &[&LayerKey], weight: f64) -> String {
+    let symbols: String = keys.iter().map(|k| escape(k.symbol)).collect();
+    let positions: Vec<String> = keys
+        .iter()
+        .map(|k| format!("{},{}", k.key.matrix_position.0, k.key.matrix_position.1))
+        .collect();
+    let layers: Vec<String> = keys.iter().map(|k| k.layer.to_string()).collect();
+    let modifiers: Vec<String> = keys
+        .iter()
+        .map(|k| k.is_modifier.is_some().to_string())
+        .collect();
+
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        symbols,
+        positions.join(" "),
+        layers.join(" "),
+        modifiers.join(" "),
+        weight
+    )
+}
+
+/// Write the ngrams (given as keys and weights) sorted by descending weight to a TSV file.
+fn write_tsv(path: &Path, mut grams: Vec<(Vec<&LayerKey>, f64)>) -> Result<()> {
+    grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+    let mut data = String::from(HEADER);
+    data.push('\n');
+    grams.iter().for_each(|(keys, weight)| {
+        data.push_str(&line(keys, *weight));
+        data.push('\n');
+    });
+
+    atomic_file::write_atomic(path, data)
+        .with_context(|| format!("Could not write mapped ngrams to {}", path.display()))
+}
+
+/// Write the mapped unigrams, bigrams, and trigrams to the files `1-grams.tsv`, `2-grams.tsv`,
+/// and `3-grams.tsv` in the given directory (which is created if necessary).
+///
+/// Each line contains the symbols of an ngram (escaped like in the ngram frequency files), the
+/// matrix positions and layers of its keys, whether they are modifiers, and its weight. The lines
+/// are sorted by descending weight.
+pub fn write_mapped_ngrams<P: AsRef<Path>>(
+    dir: P,
+    unigrams: &MappedUnigrams,
+    bigrams: &MappedBigrams,
+    trigrams: &MappedTrigrams,
+) -> Result<()> {
+    let dir = dir.as_ref();
+    create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+
+    write_tsv(
+        &dir.join("1-grams.tsv"),
+        unigrams.grams.iter().map(|(k, w)| (vec![*k], *w)).collect(),
+    )?;
+    write_tsv(
+        &dir.join("2-grams.tsv"),
+        bigrams
+            .grams
+            .iter()
+            .map(|((k1, k2), w)| (vec![*k1, *k2], *w))
+            .collect(),
+    )?;
+    write_tsv(
+        &dir.join("3-grams.tsv"),
+        trigrams
+            .grams
+            .iter()
+            .map(|((k1, k2, k3), w)| (vec![*k1, *k2, *k3], *w))
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_symbols() {
+        assert_eq!(escape('a'), "a");
+        assert_eq!(escape('\n'), "\\n");
+        assert_eq!(escape('\t'), "\\t");
+        assert_eq!(escape('\\'), "\\\\");
+    }
+}
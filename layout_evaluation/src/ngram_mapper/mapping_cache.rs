@@ -0,0 +1,151 @@
+//! This module provides a bounded (least recently used) cache for the [`LayerKeyIndex`]-based
+//! mappings of the [`OnDemandNgramMapper`](super::on_demand_ngram_mapper::OnDemandNgramMapper).
+//!
+//! Optimizers (in particular simulated annealing) propose the same layouts many times. The
+//! mappings are keyed by the [`layout_hash`] of a layout, so a re-proposed layout is not mapped
+//! again even if the results of the evaluation are not cached (or the cache key differs).
+//!
+//! [`LayerKeyIndex`]: keyboard_layout::layout::LayerKeyIndex
+
+use crate::fingerprint::layout_hash;
+
+use keyboard_layout::layout::Layout;
+
+use ahash::AHashMap;
+use parking_lot::Mutex;
+use priority_queue::DoublePriorityQueue;
+use std::sync::Arc;
+
+/// Number of lookups after which the hit rate of a cache is logged (at debug level)
+const LOG_INTERVAL: usize = 1000;
+
+/// Number of lookups of a [`MappingCache`] that found (hits) or did not find (misses) a mapping.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct CacheStatistics {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStatistics {
+    /// Fraction of lookups that found a mapping
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+impl std::ops::Add for CacheStatistics {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheState<T> {
+    mappings: AHashMap<u64, Arc<T>>,
+    /// Layout hashes by the lookup that used them last
+    last_used: DoublePriorityQueue<u64, usize>,
+    statistics: CacheStatistics,
+}
+
+/// Cache for the mappings of up to `capacity` layouts. If full, the mapping of the least recently
+/// used layout is evicted. A capacity of zero disables the cache.
+#[derive(Debug)]
+pub struct MappingCache<T> {
+    name: &'static str,
+    capacity: usize,
+    state: Mutex<CacheState<T>>,
+}
+
+impl<T> MappingCache<T> {
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity,
+            state: Mutex::new(CacheState {
+                mappings: AHashMap::default(),
+                last_used: DoublePriorityQueue::new(),
+                statistics: CacheStatistics::default(),
+            }),
+        }
+    }
+
+    /// The cached mapping for the layout or the one computed by `f` (and inserted into the cache).
+    pub fn get_or_insert_with<F: FnOnce() -> T>(&self, layout: &Layout, f: F) -> Arc<T> {
+        if self.capacity == 0 {
+            return Arc::new(f());
+        }
+
+        let key = layout_hash(layout);
+        {
+            let mut state = self.state.lock();
+            let mapping = state.mappings.get(&key).cloned();
+            if let Some(mapping) = mapping {
+                state.statistics.hits += 1;
+                self.touch(&mut state, key);
+                return mapping;
+            }
+        }
+
+        // do not hold the lock while mapping, other threads may use the cache meanwhile
+        let mapping = Arc::new(f());
+        {
+            let mut state = self.state.lock();
+            state.statistics.misses += 1;
+            state.mappings.insert(key, mapping.clone());
+            self.touch(&mut state, key);
+            while state.mappings.len() > self.capacity {
+                if let Some((evicted, _)) = state.last_used.pop_min() {
+                    state.mappings.remove(&evicted);
+                }
+            }
+        }
+
+        mapping
+    }
+
+    /// Mark the layout as used by the latest lookup and log the hit rate every [`LOG_INTERVAL`]
+    /// lookups.
+    fn touch(&self, state: &mut CacheState<T>, key: u64) {
+        let lookups = state.statistics.hits + state.statistics.misses;
+        state.last_used.push(key, lookups);
+        if lookups % LOG_INTERVAL == 0 {
+            log::debug!(
+                "{} mapping cache: {:.1}% hits in {} lookups",
+                self.name,
+                100.0 * state.statistics.hit_rate(),
+                lookups,
+            );
+        }
+    }
+
+    /// Number of hits and misses so far
+    pub fn statistics(&self) -> CacheStatistics {
+        self.state.lock().statistics
+    }
+
+    /// Number of cached mappings
+    pub fn len(&self) -> usize {
+        self.state.lock().mappings.len()
+    }
+
+    /// Whether no mappings are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Approximate number of bytes allocated for the cached mappings (with the given estimate of
+    /// a single mapping)
+    pub fn estimated_bytes(&self, bytes: impl Fn(&T) -> usize) -> usize {
+        self.state.lock().mappings.values().map(|m| bytes(m)).sum()
+    }
+}
@@ -1,15 +1,31 @@
 //! This module provides an implementation of the [`NgramMapper`] trait.
 
-use super::bigram_mapper::OnDemandBigramMapper;
-use super::trigram_mapper::OnDemandTrigramMapper;
-use super::unigram_mapper::OnDemandUnigramMapper;
-use super::{MappedBigrams, MappedTrigrams, MappedUnigrams, NgramMapper};
+use super::bigram_mapper::{BigramIndices, OnDemandBigramMapper};
+use super::common::{
+    collapse_space_runs, deserialize_line_break_handling, entries, substitute_symbols,
+    total_weight, DuplicateSymbolPolicy, HoldModifierOrdering, LineBreakHandling, LockToggles,
+    ModifierPresses, NgramMap, OneShotOrdering, SentenceStarts, ShiftPolicy, SymbolSubstitutions,
+};
+use super::mapping_cache::{CacheStatistics, MappingCache};
+use super::quadgram_mapper::{OnDemandQuadgramMapper, QuadgramIndices};
+use super::shared_ngram_mapper::{SharedMapping, SharedNgramMapper};
+use super::trigram_mapper::{OnDemandTrigramMapper, TrigramIndices};
+use super::unigram_mapper::{OnDemandUnigramMapper, UnigramIndices};
+use super::{
+    GeneratedTrigram, MappedBigrams, MappedQuadgrams, MappedTrigrams, MappedUnigrams,
+    MappingStatistics, NgramMapper, StatisticsWeights, TrigramExpansion,
+};
 
-use crate::ngrams::{Bigrams, Trigrams, Unigrams};
+use crate::memory::MemoryReport;
+use crate::ngrams::{
+    Bigrams, IncreaseCommonNgramsConfig, Quadgrams, Trigrams, Unigrams, WordStarts,
+};
 
-use keyboard_layout::layout::Layout;
+use keyboard_layout::layout::{LayerKey, Layout};
 
+use ahash::{AHashMap, AHashSet};
 use serde::Deserialize;
+use std::{hash::Hash, sync::Arc};
 
 /// Configuration parameters for the modifier splitting process.
 #[derive(Clone, Deserialize, Debug)]
@@ -18,6 +34,161 @@ pub struct SplitModifiersConfig {
     pub enabled: bool,
     /// Weight factor for generated ngrams that involve two modifiers for the same key.
     pub same_key_mod_factor: f64,
+    /// Order of one-shot modifiers relative to the base-layer key of their symbol
+    #[serde(default)]
+    pub one_shot_ordering: OneShotOrdering,
+    /// Order of hold modifiers relative to the base-layer key of their symbol
+    #[serde(default)]
+    pub hold_mod_ordering: HoldModifierOrdering,
+    /// Weight factor for generated ngrams that involve a one-shot modifier.
+    #[serde(default = "default_one_shot_weight_factor")]
+    pub one_shot_weight_factor: f64,
+    /// Weight factor for generated ngrams that involve multiple keys of the same chord (see
+    /// [`LayerModifiers::Chord`](keyboard_layout::layout::LayerModifiers::Chord)). The keys of a
+    /// chord are pressed together in their configured order instead of being permuted.
+    #[serde(default = "default_chord_cost_factor")]
+    pub chord_cost_factor: f64,
+    /// Maximum number of trigrams generated from a single trigram when splitting off hold
+    /// modifiers. If more would be generated, only the ones with the highest weight are kept and
+    /// their weights are scaled to conserve the total weight. No limit if unset.
+    #[serde(default)]
+    pub max_generated_per_trigram: Option<usize>,
+    /// Split the modifiers off the trigrams using multiple threads (of the global rayon thread
+    /// pool). Only has an effect with the `parallel` feature. Not worthwhile if multiple layouts
+    /// are evaluated in parallel anyway (as in the optimizers).
+    #[serde(default)]
+    pub parallel: bool,
+    /// Whether (and how) the toggles of lock layers are inserted into the ngrams at transitions
+    /// into and out of a lock layer
+    #[serde(default)]
+    pub lock_toggles: LockToggles,
+    /// Overrides of the modifier splitting for the symbols of individual layers (by their index
+    /// in the layout, i.e. `1` for the shift layer). Layers without an override are split if
+    /// `enabled` is set.
+    #[serde(default)]
+    pub layers: AHashMap<u8, LayerSplitModifiersConfig>,
+}
+
+fn default_one_shot_weight_factor() -> f64 {
+    1.0
+}
+
+fn default_chord_cost_factor() -> f64 {
+    1.0
+}
+
+impl SplitModifiersConfig {
+    /// Whether the symbols of the layer are split into their hold modifiers and base-layer keys.
+    pub fn splits_layer(&self, layer: u8) -> bool {
+        self.layers
+            .get(&layer)
+            .map_or(self.enabled, |override_config| override_config.enabled)
+    }
+
+    /// Whether the symbols of any layer are split into their hold modifiers and base-layer keys.
+    pub fn splits_any_layer(&self) -> bool {
+        self.enabled
+            || self
+                .layers
+                .values()
+                .any(|override_config| override_config.enabled)
+    }
+
+    /// Weight factor for generated ngrams that involve two modifiers for the same symbol of the
+    /// layer.
+    pub fn same_key_mod_factor(&self, layer: u8) -> f64 {
+        self.layers
+            .get(&layer)
+            .and_then(|override_config| override_config.same_key_mod_factor)
+            .unwrap_or(self.same_key_mod_factor)
+    }
+}
+
+/// Override of the modifier splitting for the symbols of a single layer (see
+/// [`SplitModifiersConfig::layers`]).
+#[derive(Clone, Deserialize, Debug)]
+pub struct LayerSplitModifiersConfig {
+    /// Whether to split the symbols of the layer into their hold modifiers and base-layer keys.
+    pub enabled: bool,
+    /// Weight factor for generated ngrams that involve two modifiers for the same symbol of the
+    /// layer. [`SplitModifiersConfig::same_key_mod_factor`] if unset.
+    #[serde(default)]
+    pub same_key_mod_factor: Option<f64>,
+}
+
+/// Configuration parameters for deriving secondary bigrams (the first and third key of each mapped
+/// trigram) for the trigram metrics (see [`MappedTrigrams::secondary_bigrams`]).
+#[derive(Clone, Deserialize, Debug)]
+pub struct SecondaryBigramsConfig {
+    /// Whether to derive the secondary bigrams
+    pub enabled: bool,
+    /// Factor to apply to a trigram's weight for the secondary bigram if the trigram involves no
+    /// handswitch
+    #[serde(default = "default_factor_no_handswitch")]
+    pub factor_no_handswitch: f64,
+    /// Factor to apply to a trigram's weight for the secondary bigram if the trigram involves a
+    /// handswitch
+    #[serde(default = "default_factor_handswitch")]
+    pub factor_handswitch: f64,
+    /// Exclude trigrams starting with one of these symbols, followed by a whitespace and a
+    /// symbol that is neither (a mental pause)
+    #[serde(default = "default_initial_pause_indicators")]
+    pub initial_pause_indicators: Vec<char>,
+}
+
+impl Default for SecondaryBigramsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            factor_no_handswitch: default_factor_no_handswitch(),
+            factor_handswitch: default_factor_handswitch(),
+            initial_pause_indicators: default_initial_pause_indicators(),
+        }
+    }
+}
+
+fn default_factor_no_handswitch() -> f64 {
+    0.7
+}
+
+fn default_factor_handswitch() -> f64 {
+    0.8
+}
+
+fn default_initial_pause_indicators() -> Vec<char> {
+    vec![',', '.']
+}
+
+/// Configuration parameters for the (experimental) bigrams between the key of a symbol and the
+/// first hold modifier of the following symbol, approximating the transition between the
+/// modifiers of consecutive ngrams (e.g. releasing shift after a word and pressing the modifier
+/// of the next symbol's layer).
+///
+/// For each char-based bigram whose second symbol is split into hold modifiers, a bigram of the
+/// (base-layer) key of the first symbol and the first of these modifiers is added to the mapped
+/// bigrams with the bigram's weight scaled by `factor`. The modifier splitting of the bigram
+/// itself generates such a bigram already, so this only increases its weight. One-shot and lock
+/// layers as well as multi-key and duplicate symbols are not considered.
+#[derive(Clone, Deserialize, Debug)]
+pub struct CrossGramModifiersConfig {
+    /// Whether to add the bigrams
+    pub enabled: bool,
+    /// Factor to apply to a bigram's weight for the added bigram
+    #[serde(default = "default_cross_gram_modifiers_factor")]
+    pub factor: f64,
+}
+
+impl Default for CrossGramModifiersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            factor: default_cross_gram_modifiers_factor(),
+        }
+    }
+}
+
+fn default_cross_gram_modifiers_factor() -> f64 {
+    0.5
 }
 
 /// Configuration parameters for the [`OnDemandNgramMapper`].
@@ -25,8 +196,162 @@ pub struct SplitModifiersConfig {
 pub struct NgramMapperConfig {
     /// Parameters for the modifiers splitting process.
     pub split_modifiers: SplitModifiersConfig,
-    /// Exclude ngrams that contain a line break, followed by a non-line-break character
-    pub exclude_line_breaks: bool,
+    /// Which ngrams containing a line break to exclude. A boolean (as in older configurations)
+    /// is interpreted as [`LineBreakHandling::BreakBefore`] (`true`) or
+    /// [`LineBreakHandling::None`] (`false`).
+    #[serde(default, deserialize_with = "deserialize_line_break_handling")]
+    pub exclude_line_breaks: LineBreakHandling,
+    /// Collapse runs of whitespace (except for line breaks) within each ngram into a single
+    /// space before mapping it. Ngrams that would become shorter are dropped. Single whitespace
+    /// characters (e.g. tabs in unigrams) are replaced by a space.
+    #[serde(default)]
+    pub collapse_space_runs: bool,
+    /// Replace symbols of the char-based ngrams by other symbols before mapping them (e.g.
+    /// typographic characters that are generated via compose sequences). Ngrams containing
+    /// symbols replaced by multiple symbols are split into the ngrams of the replacement (see
+    /// [`substitute_symbols`]). Substitutions are applied before line breaks are excluded and
+    /// whitespace runs are collapsed.
+    #[serde(default)]
+    pub substitutions: SymbolSubstitutions,
+    /// Sequences of symbols (e.g. a dead key followed by a base symbol) that generate a symbol
+    /// if the layout to evaluate does not generate it directly. Contrary to the `substitutions`,
+    /// these are applied for each layout individually, when mapping its ngrams. Ngrams are split
+    /// in the same way as for the `substitutions` before the modifiers are split off.
+    #[serde(default)]
+    pub compose_sequences: SymbolSubstitutions,
+    /// How the modifiers of the shift layer are modeled
+    #[serde(default)]
+    pub shift_policy: ShiftPolicy,
+    /// How the weight of symbols that the layout generates with multiple keys (e.g. on different
+    /// layers) is attributed to them
+    #[serde(default)]
+    pub duplicate_symbols: DuplicateSymbolPolicy,
+    /// Factor applied to a trigram's weight for the skipgram (the bigram of its first and third
+    /// symbol) derived from it
+    #[serde(default = "default_skipgram_decay")]
+    pub skipgram_decay: f64,
+    /// Increase the weight of the most common trigrams after modifiers have been split off
+    #[serde(default = "default_increase_common_trigrams")]
+    pub increase_common_trigrams: IncreaseCommonNgramsConfig,
+    /// Derive secondary bigrams from the mapped trigrams
+    #[serde(default)]
+    pub secondary_bigrams: SecondaryBigramsConfig,
+    /// Add bigrams between the key of a symbol and the hold modifiers of the next one
+    #[serde(default)]
+    pub cross_gram_modifiers: CrossGramModifiersConfig,
+    /// Process the ngrams in the order of their symbols (and the mapped ngrams in the order of
+    /// their indices) instead of the order of the hash maps holding them. Weights are then
+    /// summed in a fixed order, making the evaluation results bit-identical across runs and
+    /// platforms at the cost of some sorting.
+    #[serde(default)]
+    pub deterministic_order: bool,
+    /// Number of layouts whose mappings are kept (per ngram order) for re-use if the same layout
+    /// is mapped again (see [`MappingCache`]). Zero disables the cache.
+    #[serde(default)]
+    pub mapping_cache_size: usize,
+}
+
+fn default_skipgram_decay() -> f64 {
+    1.0
+}
+
+fn default_increase_common_trigrams() -> IncreaseCommonNgramsConfig {
+    IncreaseCommonNgramsConfig {
+        enabled: false,
+        ..Default::default()
+    }
+}
+
+/// Derive char-based skipgrams from trigrams: the first and third symbol of each trigram form a
+/// skipgram with the trigram's weight multiplied by `decay`.
+fn skipgrams_from_trigrams(
+    trigrams: &Trigrams,
+    decay: f64,
+    line_breaks: LineBreakHandling,
+    sorted: bool,
+) -> Bigrams {
+    let mut grams = AHashMap::default();
+    entries(&trigrams.grams, sorted)
+        // the same trigrams as in the trigram mapping are excluded
+        .filter(|((c1, c2, c3), _)| !line_breaks.excludes(&[*c1, *c2, *c3]))
+        .for_each(|((c1, _, c3), w)| grams.insert_or_add_weight((*c1, *c3), decay * w));
+
+    Bigrams { grams }
+}
+
+/// Substitute the symbols of the char-based ngrams (see [`substitute_symbols`]), merging the
+/// weights of ngrams that become identical.
+fn substituted<K: Eq + Hash + Ord, const N: usize>(
+    grams: &AHashMap<K, f64>,
+    symbols: impl Fn(&K) -> [char; N],
+    key: impl Fn([char; N]) -> K,
+    substitutions: &SymbolSubstitutions,
+    sorted: bool,
+) -> AHashMap<K, f64> {
+    let mut substituted = AHashMap::default();
+    entries(grams, sorted).for_each(|(k, w)| {
+        substitute_symbols(symbols(k), substitutions)
+            .into_iter()
+            .for_each(|s| substituted.insert_or_add_weight(key(s), *w))
+    });
+
+    substituted
+}
+
+/// Collapse the whitespace runs of the char-based ngrams (see [`collapse_space_runs`]), merging the
+/// weights of ngrams that become identical. Returns the collapsed ngrams along with the weight of
+/// the ngrams that were dropped because they contain a run.
+fn collapsed<K: Eq + Hash + Ord, const N: usize>(
+    grams: &AHashMap<K, f64>,
+    symbols: impl Fn(&K) -> [char; N],
+    key: impl Fn([char; N]) -> K,
+    sorted: bool,
+) -> (AHashMap<K, f64>, f64) {
+    let mut collapsed = AHashMap::default();
+    let mut dropped_weight = 0.0;
+    entries(grams, sorted).for_each(|(k, w)| match collapse_space_runs(symbols(k)) {
+        Some(c) => collapsed.insert_or_add_weight(key(c), *w),
+        None => dropped_weight += w,
+    });
+
+    (collapsed, dropped_weight)
+}
+
+/// Resolve &[`LayerKey`] references for the modifiers in [`ModifierPresses`]. If `sorted`, the
+/// modifiers are ordered by their indices.
+pub(super) fn get_modifier_presses<'s>(
+    modifier_presses: &ModifierPresses,
+    layout: &'s Layout,
+    sorted: bool,
+) -> Vec<(&'s LayerKey, f64)> {
+    entries(modifier_presses, sorted)
+        .map(|(idx, w)| (layout.get_layerkey(idx), *w))
+        .collect()
+}
+
+/// Caches for the mappings of each ngram order (see [`NgramMapperConfig::mapping_cache_size`])
+/// along with the total weight of the char-based ngrams they were mapped from.
+#[derive(Debug)]
+struct MappingCaches {
+    unigrams: MappingCache<(SharedMapping<UnigramIndices>, f64)>,
+    bigrams: MappingCache<(SharedMapping<BigramIndices>, f64)>,
+    /// Mapped trigrams along with their total weight, including the weight added by increasing the
+    /// common trigrams
+    trigrams: MappingCache<(SharedMapping<TrigramIndices>, f64)>,
+    quadgrams: MappingCache<(SharedMapping<QuadgramIndices>, f64)>,
+    skipgrams: MappingCache<(SharedMapping<BigramIndices>, f64)>,
+}
+
+impl MappingCaches {
+    fn new(capacity: usize) -> Self {
+        Self {
+            unigrams: MappingCache::new("Unigram", capacity),
+            bigrams: MappingCache::new("Bigram", capacity),
+            trigrams: MappingCache::new("Trigram", capacity),
+            quadgrams: MappingCache::new("Quadgram", capacity),
+            skipgrams: MappingCache::new("Skipgram", capacity),
+        }
+    }
 }
 
 /// Implements the [`NgramMapper`] trait for generating ngrams in terms of [`LayerKey`]s for a given [`Layout`].
@@ -35,81 +360,777 @@ pub struct OnDemandNgramMapper {
     unigrams: Unigrams,
     bigrams: Bigrams,
     trigrams: Trigrams,
+    quadgrams: Quadgrams,
+    /// Skipgrams derived from the trigrams
+    skipgrams: Bigrams,
+    /// Word-initial symbols derived from the bigrams
+    word_starts: WordStarts,
     unigram_mapper: OnDemandUnigramMapper,
     bigram_mapper: OnDemandBigramMapper,
     trigram_mapper: OnDemandTrigramMapper,
+    quadgram_mapper: OnDemandQuadgramMapper,
+    /// Weights of the bigrams and trigrams that were dropped when collapsing whitespace runs
+    collapsed_weights: (f64, f64),
+    /// Mappings of recently mapped layouts (shared between clones of the mapper)
+    caches: Arc<MappingCaches>,
     config: NgramMapperConfig,
 }
 
 impl OnDemandNgramMapper {
-    /// Generate a [`OnDemandNgramMapper`] with given char-based ngrams.
+    /// Generate a [`OnDemandNgramMapper`] with given char-based ngrams (without quadgrams).
     pub fn with_ngrams(
-        unigrams: Unigrams,
-        bigrams: Bigrams,
-        trigrams: Trigrams,
+        mut unigrams: Unigrams,
+        mut bigrams: Bigrams,
+        mut trigrams: Trigrams,
         config: NgramMapperConfig,
     ) -> Self {
+        let sorted = config.deterministic_order;
+        if !config.substitutions.is_empty() {
+            let substitutions = &config.substitutions;
+            unigrams.grams = substituted(&unigrams.grams, |c| [*c], |[c]| c, substitutions, sorted);
+            bigrams.grams = substituted(
+                &bigrams.grams,
+                |(c1, c2)| [*c1, *c2],
+                |[c1, c2]| (c1, c2),
+                substitutions,
+                sorted,
+            );
+            trigrams.grams = substituted(
+                &trigrams.grams,
+                |(c1, c2, c3)| [*c1, *c2, *c3],
+                |[c1, c2, c3]| (c1, c2, c3),
+                substitutions,
+                sorted,
+            );
+        }
+
+        let mut collapsed_weights = (0.0, 0.0);
+        if config.collapse_space_runs {
+            unigrams.grams = collapsed(&unigrams.grams, |c| [*c], |[c]| c, sorted).0;
+            let (grams, dropped_weight) = collapsed(
+                &bigrams.grams,
+                |(c1, c2)| [*c1, *c2],
+                |[c1, c2]| (c1, c2),
+                sorted,
+            );
+            bigrams.grams = grams;
+            collapsed_weights.0 = dropped_weight;
+            let (grams, dropped_weight) = collapsed(
+                &trigrams.grams,
+                |(c1, c2, c3)| [*c1, *c2, *c3],
+                |[c1, c2, c3]| (c1, c2, c3),
+                sorted,
+            );
+            trigrams.grams = grams;
+            collapsed_weights.1 = dropped_weight;
+        }
+
+        let skipgrams = skipgrams_from_trigrams(
+            &trigrams,
+            config.skipgram_decay,
+            config.exclude_line_breaks,
+            sorted,
+        );
+        let word_starts = bigrams.word_starts();
+
         Self {
             unigrams,
             bigrams,
             trigrams,
-            unigram_mapper: OnDemandUnigramMapper::new(config.split_modifiers.clone()),
-            bigram_mapper: OnDemandBigramMapper::new(config.split_modifiers.clone()),
-            trigram_mapper: OnDemandTrigramMapper::new(config.split_modifiers.clone()),
+            quadgrams: Quadgrams::default(),
+            skipgrams,
+            word_starts,
+            unigram_mapper: OnDemandUnigramMapper::new(
+                config.split_modifiers.clone(),
+                config.shift_policy,
+                sorted,
+            )
+            .with_duplicate_symbols(config.duplicate_symbols),
+            bigram_mapper: OnDemandBigramMapper::new(
+                config.split_modifiers.clone(),
+                config.shift_policy,
+                sorted,
+            )
+            .with_duplicate_symbols(config.duplicate_symbols),
+            trigram_mapper: OnDemandTrigramMapper::new(
+                config.split_modifiers.clone(),
+                config.shift_policy,
+                config.increase_common_trigrams.clone(),
+                sorted,
+            )
+            .with_duplicate_symbols(config.duplicate_symbols),
+            quadgram_mapper: OnDemandQuadgramMapper::new(
+                config.split_modifiers.clone(),
+                config.shift_policy,
+                sorted,
+            )
+            .with_duplicate_symbols(config.duplicate_symbols),
+            collapsed_weights,
+            caches: Arc::new(MappingCaches::new(config.mapping_cache_size)),
             config,
         }
+        .with_sentence_starts()
+    }
+
+    /// Pass the shares of the shifted letters starting a sentence, derived from the trigrams, to
+    /// the mappers (see [`ShiftPolicy::SentenceStart`]).
+    fn with_sentence_starts(mut self) -> Self {
+        if self.config.shift_policy != ShiftPolicy::SentenceStart {
+            return self;
+        }
+
+        let sentence_starts =
+            SentenceStarts::from_trigrams(&self.trigrams, self.config.deterministic_order);
+        self.unigram_mapper = self
+            .unigram_mapper
+            .with_sentence_starts(sentence_starts.clone());
+        self.bigram_mapper = self
+            .bigram_mapper
+            .with_sentence_starts(sentence_starts.clone());
+        self.trigram_mapper = self
+            .trigram_mapper
+            .with_sentence_starts(sentence_starts.clone());
+        self.quadgram_mapper = self.quadgram_mapper.with_sentence_starts(sentence_starts);
+
+        self
+    }
+
+    /// Add char-based quadgrams (for quadgram metrics).
+    pub fn with_quadgrams(mut self, mut quadgrams: Quadgrams) -> Self {
+        if !self.config.substitutions.is_empty() {
+            quadgrams.grams = substituted(
+                &quadgrams.grams,
+                |(c1, c2, c3, c4)| [*c1, *c2, *c3, *c4],
+                |[c1, c2, c3, c4]| (c1, c2, c3, c4),
+                &self.config.substitutions,
+                self.config.deterministic_order,
+            );
+        }
+        if self.config.collapse_space_runs {
+            quadgrams.grams = collapsed(
+                &quadgrams.grams,
+                |(c1, c2, c3, c4)| [*c1, *c2, *c3, *c4],
+                |[c1, c2, c3, c4]| (c1, c2, c3, c4),
+                self.config.deterministic_order,
+            )
+            .0;
+        }
+        self.quadgrams = quadgrams;
+        self.caches = Arc::new(MappingCaches::new(self.config.mapping_cache_size));
+        self
+    }
+
+    /// The same mapper for a subset of the ngrams.
+    /// The same mapper for the ngrams whose symbols fulfill the predicate for all symbols
+    /// (`all == true`) or for not all symbols (`all == false`).
+    fn with_ngram_subsets<P: Fn(&char) -> bool>(&self, predicate: P, all: bool) -> Self {
+        let mut subset = self.clone();
+        subset.caches = Arc::new(MappingCaches::new(self.config.mapping_cache_size));
+        subset.unigrams.grams.retain(|c, _| predicate(c) == all);
+        subset
+            .bigrams
+            .grams
+            .retain(|(c1, c2), _| (predicate(c1) && predicate(c2)) == all);
+        subset
+            .trigrams
+            .grams
+            .retain(|(c1, c2, c3), _| (predicate(c1) && predicate(c2) && predicate(c3)) == all);
+        subset.quadgrams.grams.retain(|(c1, c2, c3, c4), _| {
+            (predicate(c1) && predicate(c2) && predicate(c3) && predicate(c4)) == all
+        });
+        subset
+            .skipgrams
+            .grams
+            .retain(|(c1, c2), _| (predicate(c1) && predicate(c2)) == all);
+
+        subset
+    }
+
+    /// Number of lookups in the mapping caches (of all ngram orders) that found the mapping of the
+    /// layout (hits) or had to map the ngrams (misses). See
+    /// [`NgramMapperConfig::mapping_cache_size`].
+    pub fn mapping_cache_statistics(&self) -> CacheStatistics {
+        let caches = &self.caches;
+        caches.unigrams.statistics()
+            + caches.bigrams.statistics()
+            + caches.trigrams.statistics()
+            + caches.quadgrams.statistics()
+            + caches.skipgrams.statistics()
+    }
+
+    /// The secondary bigrams of mapped trigrams (see [`NgramMapperConfig::secondary_bigrams`]).
+    /// `None` if they are not enabled.
+    pub(super) fn secondary_bigrams<'s>(
+        &self,
+        trigrams: &TrigramIndices,
+        layout: &'s Layout,
+    ) -> Option<Vec<((&'s LayerKey, &'s LayerKey), f64)>> {
+        let config = &self.config.secondary_bigrams;
+        config.enabled.then(|| {
+            let bigrams = OnDemandTrigramMapper::secondary_bigrams(
+                trigrams,
+                layout,
+                config,
+                self.deterministic_order(),
+            );
+            OnDemandBigramMapper::get_filtered_layerkeys(
+                &bigrams,
+                layout,
+                self.deterministic_order(),
+            )
+        })
+    }
+
+    /// Whether the ngrams are processed in a deterministic order (see
+    /// [`NgramMapperConfig::deterministic_order`]).
+    pub(super) fn deterministic_order(&self) -> bool {
+        self.config.deterministic_order
+    }
+
+    /// Total weight of char-based ngrams.
+    fn weight_sum<K: Ord>(&self, grams: &AHashMap<K, f64>) -> f64 {
+        total_weight(grams, self.config.deterministic_order)
+    }
+
+    /// Total weights of the unigrams, bigrams, trigrams, quadgrams, and skipgrams.
+    pub(super) fn total_weights(&self) -> (f64, f64, f64, f64, f64) {
+        (
+            self.weight_sum(&self.unigrams.grams),
+            self.weight_sum(&self.bigrams.grams),
+            self.weight_sum(&self.trigrams.grams),
+            self.weight_sum(&self.quadgrams.grams),
+            self.weight_sum(&self.skipgrams.grams),
+        )
+    }
+
+    /// Total weights of the unigrams, bigrams, and trigrams along with the weights of the bigrams
+    /// and trigrams that are excluded from the mapping (for the [`MappingStatistics`]). Excluded
+    /// are ngrams containing a line break (see [`NgramMapperConfig::exclude_line_breaks`]) and
+    /// ngrams dropped when collapsing whitespace runs (see
+    /// [`NgramMapperConfig::collapse_space_runs`]).
+    pub(super) fn statistics_weights(&self) -> StatisticsWeights {
+        let line_breaks = self.config.exclude_line_breaks;
+        let sorted = self.config.deterministic_order;
+        let bigrams: f64 = entries(&self.bigrams.grams, sorted)
+            .filter(|((c1, c2), _)| line_breaks.excludes(&[*c1, *c2]))
+            .map(|(_, w)| w)
+            .sum();
+        let trigrams: f64 = entries(&self.trigrams.grams, sorted)
+            .filter(|((c1, c2, c3), _)| line_breaks.excludes(&[*c1, *c2, *c3]))
+            .map(|(_, w)| w)
+            .sum();
+
+        (
+            (
+                self.weight_sum(&self.unigrams.grams),
+                self.weight_sum(&self.bigrams.grams) + self.collapsed_weights.0,
+                self.weight_sum(&self.trigrams.grams) + self.collapsed_weights.1,
+            ),
+            (
+                bigrams + self.collapsed_weights.0,
+                trigrams + self.collapsed_weights.1,
+            ),
+        )
+    }
+
+    /// The compose sequences of the symbols that the layout does not generate (see
+    /// [`NgramMapperConfig::compose_sequences`]).
+    fn active_compose_sequences(&self, layout: &Layout) -> SymbolSubstitutions {
+        self.config
+            .compose_sequences
+            .iter()
+            .filter(|(c, _)| layout.get_layerkey_index_for_symbol(c).is_none())
+            .map(|(c, sequence)| (*c, sequence.clone()))
+            .collect()
+    }
+
+    /// The char-based ngrams with their symbols replaced by the compose sequences that are active
+    /// for the layout. `None` if there are none.
+    fn composed<K: Eq + Hash + Ord, const N: usize>(
+        &self,
+        grams: &AHashMap<K, f64>,
+        layout: &Layout,
+        symbols: impl Fn(&K) -> [char; N],
+        key: impl Fn([char; N]) -> K,
+    ) -> Option<AHashMap<K, f64>> {
+        if self.config.compose_sequences.is_empty() {
+            return None;
+        }
+        let sequences = self.active_compose_sequences(layout);
+
+        (!sequences.is_empty())
+            .then(|| substituted(grams, symbols, key, &sequences, self.deterministic_order()))
+    }
+
+    /// Map the char-based unigrams to [`LayerKeyIndex`](keyboard_layout::layout::LayerKeyIndex)es.
+    pub(super) fn unigram_indices(&self, layout: &Layout) -> SharedMapping<UnigramIndices> {
+        self.unigram_mapping(layout).0
+    }
+
+    /// Map the char-based unigrams after expanding the active compose sequences. Returns the
+    /// mapping along with the total weight of the expanded unigrams.
+    fn unigram_mapping(&self, layout: &Layout) -> (SharedMapping<UnigramIndices>, f64) {
+        let composed = self
+            .composed(&self.unigrams.grams, layout, |c| [*c], |[c]| c)
+            .map(|grams| Unigrams { grams });
+        let unigrams = composed.as_ref().unwrap_or(&self.unigrams);
+        let mapping = self.unigram_mapper.layerkey_indices(unigrams, layout);
+
+        (mapping.into(), self.weight_sum(&unigrams.grams))
+    }
+
+    /// Map the char-based bigrams to [`LayerKeyIndex`](keyboard_layout::layout::LayerKeyIndex)es.
+    pub(super) fn bigram_indices(&self, layout: &Layout) -> SharedMapping<BigramIndices> {
+        self.bigram_mapping(layout).0
+    }
+
+    /// Map the char-based bigrams after expanding the active compose sequences. Returns the
+    /// mapping along with the total weight of the expanded bigrams.
+    fn bigram_mapping(&self, layout: &Layout) -> (SharedMapping<BigramIndices>, f64) {
+        let composed = self
+            .composed(
+                &self.bigrams.grams,
+                layout,
+                |(c1, c2)| [*c1, *c2],
+                |[c1, c2]| (c1, c2),
+            )
+            .map(|grams| Bigrams { grams });
+        let bigrams = composed.as_ref().unwrap_or(&self.bigrams);
+        let mut mapping: SharedMapping<BigramIndices> = self
+            .bigram_mapper
+            .layerkey_indices(bigrams, layout, self.config.exclude_line_breaks)
+            .into();
+        let mut total_weight = self.weight_sum(&bigrams.grams);
+
+        let cross_gram = &self.config.cross_gram_modifiers;
+        if cross_gram.enabled {
+            let (cross_gram_bigrams, added_weight) =
+                self.bigram_mapper.cross_gram_modifier_indices(
+                    bigrams,
+                    layout,
+                    self.config.exclude_line_breaks,
+                    cross_gram.factor,
+                );
+            entries(&cross_gram_bigrams, self.deterministic_order()).for_each(|(k, w)| {
+                mapping.indices.insert_or_add_weight(*k, *w);
+            });
+            total_weight += added_weight;
+        }
+
+        (mapping, total_weight)
+    }
+
+    /// Map the char-based trigrams to [`LayerKeyIndex`](keyboard_layout::layout::LayerKeyIndex)es.
+    pub(super) fn trigram_indices(&self, layout: &Layout) -> SharedMapping<TrigramIndices> {
+        self.trigram_mapping(layout).0
+    }
+
+    /// The char-based trigrams after expanding the active compose sequences. `None` if there are
+    /// none.
+    fn composed_trigrams(&self, layout: &Layout) -> Option<Trigrams> {
+        self.composed(
+            &self.trigrams.grams,
+            layout,
+            |(c1, c2, c3)| [*c1, *c2, *c3],
+            |[c1, c2, c3]| (c1, c2, c3),
+        )
+        .map(|grams| Trigrams { grams })
+    }
+
+    /// Map the char-based trigrams after expanding the active compose sequences. Returns the
+    /// mapping along with the total weight of the expanded trigrams.
+    fn trigram_mapping(&self, layout: &Layout) -> (SharedMapping<TrigramIndices>, f64) {
+        let composed = self.composed_trigrams(layout);
+        let trigrams = composed.as_ref().unwrap_or(&self.trigrams);
+        let mapping =
+            self.trigram_mapper
+                .layerkey_indices(trigrams, layout, self.config.exclude_line_breaks);
+
+        (mapping.into(), self.weight_sum(&trigrams.grams))
+    }
+
+    /// Increase the weight of the most common trigrams of a complete mapping (see
+    /// [`NgramMapperConfig::increase_common_trigrams`]). Returns the added weight.
+    pub(super) fn increase_common_trigrams(&self, trigrams: &mut TrigramIndices) -> f64 {
+        self.trigram_mapper.increase_common(trigrams)
+    }
+
+    /// Map the char-based quadgrams to [`LayerKeyIndex`](keyboard_layout::layout::LayerKeyIndex)es.
+    pub(super) fn quadgram_indices(&self, layout: &Layout) -> SharedMapping<QuadgramIndices> {
+        self.quadgram_mapping(layout).0
+    }
+
+    /// Map the char-based quadgrams after expanding the active compose sequences. Returns the
+    /// mapping along with the total weight of the expanded quadgrams.
+    fn quadgram_mapping(&self, layout: &Layout) -> (SharedMapping<QuadgramIndices>, f64) {
+        let composed = self
+            .composed(
+                &self.quadgrams.grams,
+                layout,
+                |(c1, c2, c3, c4)| [*c1, *c2, *c3, *c4],
+                |[c1, c2, c3, c4]| (c1, c2, c3, c4),
+            )
+            .map(|grams| Quadgrams { grams });
+        let quadgrams = composed.as_ref().unwrap_or(&self.quadgrams);
+        let mapping = self.quadgram_mapper.layerkey_indices(
+            quadgrams,
+            layout,
+            self.config.exclude_line_breaks,
+        );
+
+        (mapping.into(), self.weight_sum(&quadgrams.grams))
+    }
+
+    /// Map the skipgrams (the first and third symbol of each trigram, see
+    /// [`NgramMapperConfig::skipgram_decay`]) to [`LayerKeyIndex`](keyboard_layout::layout::LayerKeyIndex)es.
+    /// Modifiers are processed in the same way as for bigrams.
+    pub fn skipgram_indices(&self, layout: &Layout) -> SharedMapping<BigramIndices> {
+        self.skipgram_mapping(layout).0
+    }
+
+    /// Map the skipgrams after expanding the active compose sequences (of the trigrams they are
+    /// derived from). Returns the mapping along with the total weight of the expanded skipgrams.
+    fn skipgram_mapping(&self, layout: &Layout) -> (SharedMapping<BigramIndices>, f64) {
+        let composed = self.composed_trigrams(layout).map(|trigrams| {
+            skipgrams_from_trigrams(
+                &trigrams,
+                self.config.skipgram_decay,
+                self.config.exclude_line_breaks,
+                self.deterministic_order(),
+            )
+        });
+        let skipgrams = composed.as_ref().unwrap_or(&self.skipgrams);
+        let mapping =
+            self.bigram_mapper
+                .layerkey_indices(skipgrams, layout, self.config.exclude_line_breaks);
+
+        (mapping.into(), self.weight_sum(&skipgrams.grams))
     }
 }
 
 impl NgramMapper for OnDemandNgramMapper {
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
         // map char-based unigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) =
-            self.unigram_mapper.layerkey_indices(&self.unigrams, layout);
-        let weight_found = self.unigrams.total_weight() - weight_not_found;
+        let cached = self
+            .caches
+            .unigrams
+            .get_or_insert_with(layout, || self.unigram_mapping(layout));
+        let (mapping, total_weight) = &*cached;
+        let weight_found = total_weight - mapping.weight_not_found;
         // map LayerKeyIndex to &LayerKey
-        let grams = OnDemandUnigramMapper::get_layerkeys(&key_indices, layout);
+        let grams = OnDemandUnigramMapper::get_layerkeys(
+            &mapping.indices,
+            layout,
+            self.deterministic_order(),
+        );
 
         MappedUnigrams {
             grams,
-            weight_not_found,
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols.clone(),
             weight_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.deterministic_order(),
+            )),
         }
     }
 
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
         // map char-based bigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) = self.bigram_mapper.layerkey_indices(
-            &self.bigrams,
+        let cached = self
+            .caches
+            .bigrams
+            .get_or_insert_with(layout, || self.bigram_mapping(layout));
+        let (mapping, total_weight) = &*cached;
+        let weight_found = total_weight - mapping.weight_not_found;
+        // map LayerKeyIndex to &LayerKey
+        let grams = OnDemandBigramMapper::get_filtered_layerkeys(
+            &mapping.indices,
             layout,
-            self.config.exclude_line_breaks,
+            self.deterministic_order(),
         );
-        let weight_found = self.bigrams.total_weight() - weight_not_found;
-        // map LayerKeyIndex to &LayerKey
-        let grams = OnDemandBigramMapper::get_filtered_layerkeys(&key_indices, layout);
 
         MappedBigrams {
             grams,
-            weight_not_found,
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols.clone(),
             weight_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.deterministic_order(),
+            )),
         }
     }
 
     fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s> {
         // map char-based trigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) = self.trigram_mapper.layerkey_indices(
-            &self.trigrams,
+        let cached = self.caches.trigrams.get_or_insert_with(layout, || {
+            let (mut mapping, total_weight) = self.trigram_mapping(layout);
+            let added_weight = self.increase_common_trigrams(&mut mapping.indices);
+            (mapping, total_weight + added_weight)
+        });
+        let (mapping, total_weight) = &*cached;
+        let weight_found = total_weight - mapping.weight_not_found;
+        // map LayerKeyIndex to &LayerKey
+        let (grams, weight_filtered) = OnDemandTrigramMapper::get_filtered_layerkeys(
+            &mapping.indices,
             layout,
-            self.config.exclude_line_breaks,
+            self.deterministic_order(),
         );
-        let weight_found = self.trigrams.total_weight() - weight_not_found;
-        // map LayerKeyIndex to &LayerKey
-        let grams = OnDemandTrigramMapper::get_filtered_layerkeys(&key_indices, layout);
 
         MappedTrigrams {
             grams,
-            weight_not_found,
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols.clone(),
+            weight_found,
+            weight_filtered,
+            secondary_bigrams: self.secondary_bigrams(&mapping.indices, layout),
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s> {
+        // map char-based quadgrams to LayerKeyIndex
+        let cached = self
+            .caches
+            .quadgrams
+            .get_or_insert_with(layout, || self.quadgram_mapping(layout));
+        let (mapping, total_weight) = &*cached;
+        let weight_found = total_weight - mapping.weight_not_found;
+        // map LayerKeyIndex to &LayerKey
+        let grams = OnDemandQuadgramMapper::get_filtered_layerkeys(
+            &mapping.indices,
+            layout,
+            self.deterministic_order(),
+        );
+
+        MappedQuadgrams {
+            grams,
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols.clone(),
             weight_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
+        let cached = self
+            .caches
+            .skipgrams
+            .get_or_insert_with(layout, || self.skipgram_mapping(layout));
+        let (mapping, total_weight) = &*cached;
+
+        MappedBigrams {
+            grams: OnDemandBigramMapper::get_filtered_layerkeys(
+                &mapping.indices,
+                layout,
+                self.deterministic_order(),
+            ),
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols.clone(),
+            weight_found: total_weight - mapping.weight_not_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.deterministic_order(),
+            )),
+        }
+    }
+
+    fn share_mapping(
+        &self,
+        layout: &Layout,
+        stable_symbols: &AHashSet<char>,
+    ) -> Option<Box<dyn NgramMapper>> {
+        // whether a symbol is composed depends on the layout, so is the mapping of the symbols
+        // of its compose sequence. Likewise, the weight of a stable symbol may be split among
+        // keys that are not stable. The bigrams added for the modifiers of the following symbol
+        // are not part of the total weights.
+        if !self.config.compose_sequences.is_empty()
+            || self.config.duplicate_symbols != DuplicateSymbolPolicy::CheapestOnly
+            || self.config.cross_gram_modifiers.enabled
+        {
+            return None;
+        }
+
+        let stable = |c: &char| stable_symbols.contains(c);
+        let stable_mapper = self.with_ngram_subsets(stable, true);
+        let variant_mapper = self.with_ngram_subsets(stable, false);
+
+        Some(Box::new(
+            SharedNgramMapper::new(
+                stable_mapper.unigram_indices(layout),
+                stable_mapper.bigram_indices(layout),
+                stable_mapper.trigram_indices(layout),
+                stable_mapper.quadgram_indices(layout),
+                stable_mapper.skipgram_indices(layout),
+                self.total_weights(),
+                variant_mapper,
+            )
+            .with_statistics_weights(self.statistics_weights()),
+        ))
+    }
+
+    fn mapping_statistics(&self, layout: &Layout) -> Option<MappingStatistics> {
+        let (total_weights, excluded_weights) = self.statistics_weights();
+        Some(MappingStatistics::from_mapped(
+            &self.map_unigrams(layout),
+            &self.map_bigrams(layout),
+            &self.map_trigrams(layout),
+            total_weights,
+            excluded_weights,
+        ))
+    }
+
+    fn word_starts(&self) -> Option<(&WordStarts, &Unigrams)> {
+        Some((&self.word_starts, &self.unigrams))
+    }
+
+    fn with_shift_policy(&self, policy: ShiftPolicy) -> Option<Box<dyn NgramMapper>> {
+        let config = NgramMapperConfig {
+            shift_policy: policy,
+            ..self.config.clone()
+        };
+
+        // only the mappers change, the ngrams have been processed (e.g. substituted) already
+        let mapper = Self::with_ngrams(
+            Unigrams {
+                grams: AHashMap::default(),
+            },
+            Bigrams {
+                grams: AHashMap::default(),
+            },
+            Trigrams {
+                grams: AHashMap::default(),
+            },
+            config,
+        );
+
+        Some(Box::new(
+            Self {
+                unigram_mapper: mapper.unigram_mapper,
+                bigram_mapper: mapper.bigram_mapper,
+                trigram_mapper: mapper.trigram_mapper,
+                quadgram_mapper: mapper.quadgram_mapper,
+                caches: mapper.caches,
+                config: mapper.config,
+                ..self.clone()
+            }
+            .with_sentence_starts(),
+        ))
+    }
+
+    fn explain_trigram<'s>(
+        &self,
+        trigram: (char, char, char),
+        layout: &'s Layout,
+    ) -> Option<TrigramExpansion<'s>> {
+        let weight = *self.trigrams.grams.get(&trigram)?;
+        let grams: AHashMap<_, _> = std::iter::once((trigram, weight)).collect();
+        let grams = self
+            .composed(
+                &grams,
+                layout,
+                |(c1, c2, c3)| [*c1, *c2, *c3],
+                |[c1, c2, c3]| (c1, c2, c3),
+            )
+            .unwrap_or(grams);
+        let (indices, weight_not_found, _, _) = self.trigram_mapper.layerkey_indices(
+            &Trigrams { grams },
+            layout,
+            self.config.exclude_line_breaks,
+        );
+
+        let mut generated: Vec<GeneratedTrigram> = entries(&indices, true)
+            .map(|(idx, w)| GeneratedTrigram {
+                indices: *idx,
+                keys: (
+                    layout.get_layerkey(&idx.0),
+                    layout.get_layerkey(&idx.1),
+                    layout.get_layerkey(&idx.2),
+                ),
+                weight: *w,
+                filtered: OnDemandTrigramMapper::repeats_hold_modifier(idx, layout),
+            })
+            .collect();
+        generated.sort_by(|g1, g2| g2.weight.partial_cmp(&g1.weight).unwrap());
+
+        Some(TrigramExpansion {
+            trigram,
+            weight,
+            weight_not_found,
+            generated,
+        })
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add(
+            "Unigrams",
+            self.unigrams.grams.len(),
+            self.unigrams.estimated_bytes(),
+        );
+        report.add(
+            "Bigrams",
+            self.bigrams.grams.len(),
+            self.bigrams.estimated_bytes(),
+        );
+        report.add(
+            "Trigrams",
+            self.trigrams.grams.len(),
+            self.trigrams.estimated_bytes(),
+        );
+        if !self.quadgrams.grams.is_empty() {
+            report.add(
+                "Quadgrams",
+                self.quadgrams.grams.len(),
+                self.quadgrams.estimated_bytes(),
+            );
+        }
+        report.add(
+            "Skipgrams",
+            self.skipgrams.grams.len(),
+            self.skipgrams.estimated_bytes(),
+        );
+        report.add(
+            "Word starts",
+            self.word_starts.word_initial.grams.len(),
+            self.word_starts.word_initial.estimated_bytes(),
+        );
+        let caches = &self.caches;
+        let cached_layouts = caches.unigrams.len()
+            + caches.bigrams.len()
+            + caches.trigrams.len()
+            + caches.quadgrams.len()
+            + caches.skipgrams.len();
+        if cached_layouts > 0 {
+            report.add(
+                "Mapping cache",
+                cached_layouts,
+                caches
+                    .unigrams
+                    .estimated_bytes(|(m, _)| m.estimated_bytes())
+                    + caches.bigrams.estimated_bytes(|(m, _)| m.estimated_bytes())
+                    + caches
+                        .trigrams
+                        .estimated_bytes(|(m, _)| m.estimated_bytes())
+                    + caches
+                        .quadgrams
+                        .estimated_bytes(|(m, _)| m.estimated_bytes())
+                    + caches
+                        .skipgrams
+                        .estimated_bytes(|(m, _)| m.estimated_bytes()),
+            );
         }
     }
 }
@@ -0,0 +1,375 @@
+//! This module provides an implementation of quadgram mapping functionalities
+//! used by the [`OnDemandNgramMapper`].
+
+use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig, LayerKeyQuadgram};
+
+use crate::ngrams::Quadgrams;
+
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+
+// Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
+// ones are used because they are smaller than a reference (u16 vs usize) and yield better
+// hashing performance.
+pub type QuadgramIndices = IndexMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex, LayerKeyIndex)>;
+type QuadgramIndicesVec = Vec<([LayerKeyIndex; 4], f64)>;
+/// Keys of (parts of) quadgrams along with their weights
+type PartialQuadgrams = Vec<(Vec<LayerKeyIndex>, f64)>;
+
+/// Ways of splitting the four positions of a quadgram into parts taken from consecutive keys
+/// (compositions of four).
+const COMPOSITIONS: [&[usize]; 8] = [
+    &[1, 1, 1, 1],
+    &[2, 1, 1],
+    &[1, 2, 1],
+    &[1, 1, 2],
+    &[3, 1],
+    &[2, 2],
+    &[1, 3],
+    &[4],
+];
+
+/// Turns the [`Quadgrams`]'s characters into their indices, returning a [`QuadgramIndicesVec`].
+fn map_quadgrams(
+    quadgrams: &Quadgrams,
+    layout: &Layout,
+    line_breaks: LineBreakHandling,
+    sorted: bool,
+) -> (QuadgramIndicesVec, f64, MissingSymbols) {
+    let mut not_found_weight = 0.0;
+    let mut missing_symbols = MissingSymbols::default();
+    let mut quadgrams_vec = Vec::with_capacity(quadgrams.grams.len());
+
+    quadgrams_vec.extend(entries(&quadgrams.grams, sorted).filter_map(
+        |((c1, c2, c3, c4), weight)| {
+            let symbols = [*c1, *c2, *c3, *c4];
+
+            // Exclude quadgrams that contain line breaks (see `LineBreakHandling`)
+            if line_breaks.excludes(&symbols) {
+                return None;
+            }
+
+            let mut indices = [0; 4];
+            for (idx, c) in indices.iter_mut().zip(symbols.iter()) {
+                match layout.get_layerkey_index_for_symbol(c) {
+                    Some(i) => *idx = i,
+                    None => {
+                        not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c, *weight);
+                        return None;
+                    }
+                }
+            }
+
+            Some((indices, *weight))
+        },
+    ));
+
+    (quadgrams_vec, not_found_weight, missing_symbols)
+}
+
+/// The keys generated by [`TakeLayerKeys`] with `n` keys (up to four) along with their weight
+/// relative to the weight of the source ngram. If `chord`, the modifiers are the keys of a chord
+/// (see [`TakeLayerKeys::with_chord`]).
+fn take_layerkeys(
+    key: LayerKeyIndex,
+    mods: &[LayerKeyIndex],
+    n: usize,
+    same_key_mod_factor: f64,
+    ordering: HoldModifierOrdering,
+    chord: bool,
+    chord_cost_factor: f64,
+) -> PartialQuadgrams {
+    match n {
+        1 => TakeLayerKeys::<1>::new(key, mods, 1.0, 1.0)
+            .with_chord(chord, chord_cost_factor)
+            .map(|(keys, _)| (keys.to_vec(), 1.0))
+            .collect(),
+        2 => TakeLayerKeys::<2>::new(key, mods, 1.0, same_key_mod_factor)
+            .with_ordering(ordering)
+            .with_chord(chord, chord_cost_factor)
+            .map(|(keys, w)| (keys.to_vec(), w))
+            .collect(),
+        3 => TakeLayerKeys::<3>::new(key, mods, 1.0, same_key_mod_factor)
+            .with_ordering(ordering)
+            .with_chord(chord, chord_cost_factor)
+            .map(|(keys, w)| (keys.to_vec(), w))
+            .collect(),
+        4 => TakeLayerKeys::<4>::new(key, mods, 1.0, same_key_mod_factor)
+            .with_ordering(ordering)
+            .with_chord(chord, chord_cost_factor)
+            .map(|(keys, w)| (keys.to_vec(), w))
+            .collect(),
+        _ => unreachable!("quadgrams consist of four keys"),
+    }
+}
+
+/// Generates [`LayerKey`]-based quadgrams from char-based quadgrams. Optionally resolves
+/// modifiers for higher-layer symbols of the layout.
+#[derive(Clone, Debug)]
+pub struct OnDemandQuadgramMapper {
+    split_modifiers: SplitModifiersConfig,
+    shift_policy: ShiftPolicy,
+    duplicate_symbols: DuplicateSymbolPolicy,
+    /// Shares of the shifted letters starting a sentence (see [`ShiftPolicy::SentenceStart`])
+    sentence_starts: SentenceStarts,
+    /// Visit the ngrams in the order of their symbols (see [`entries`])
+    deterministic_order: bool,
+}
+
+impl OnDemandQuadgramMapper {
+    pub fn new(
+        split_modifiers: SplitModifiersConfig,
+        shift_policy: ShiftPolicy,
+        deterministic_order: bool,
+    ) -> Self {
+        Self {
+            split_modifiers,
+            shift_policy,
+            duplicate_symbols: DuplicateSymbolPolicy::default(),
+            sentence_starts: SentenceStarts::default(),
+            deterministic_order,
+        }
+    }
+
+    /// Attribute the weight of symbols that the layout generates with multiple keys according to
+    /// the policy (see [`resolve_duplicate_symbols`]).
+    pub fn with_duplicate_symbols(mut self, policy: DuplicateSymbolPolicy) -> Self {
+        self.duplicate_symbols = policy;
+        self
+    }
+
+    /// Split the weight of ngrams lacking the context of a shifted letter by its share of
+    /// occurrences at the start of a sentence (see [`ShiftPolicy::SentenceStart`]).
+    pub fn with_sentence_starts(mut self, sentence_starts: SentenceStarts) -> Self {
+        self.sentence_starts = sentence_starts;
+        self
+    }
+
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based quadgrams, optionally resolving modifiers for higer-layer symbols.
+    ///
+    /// Additionally returns the weight of the ngrams that can not be generated by the layout (in
+    /// total and per symbol) and the number of presses of each modifier that was split off.
+    pub fn layerkey_indices(
+        &self,
+        quadgrams: &Quadgrams,
+        layout: &Layout,
+        line_breaks: LineBreakHandling,
+    ) -> (QuadgramIndices, f64, MissingSymbols, ModifierPresses) {
+        let (mut quadgram_keys_vec, not_found_weight, missing_symbols) =
+            map_quadgrams(quadgrams, layout, line_breaks, self.deterministic_order);
+        let mut modifier_presses = ModifierPresses::default();
+        let resolved = ResolvedModifiers::new(self.shift_policy, layout)
+            .with_layer_overrides(&self.split_modifiers, layout)
+            .with_sentence_starts(&self.sentence_starts, layout);
+
+        if self.duplicate_symbols != DuplicateSymbolPolicy::CheapestOnly
+            && layout.has_duplicate_symbols()
+        {
+            quadgram_keys_vec = quadgram_keys_vec
+                .into_iter()
+                .flat_map(|(keys, w)| {
+                    resolve_duplicate_symbols(keys, w, layout, self.duplicate_symbols)
+                })
+                .collect();
+        }
+
+        if layout.has_multi_key_symbols() {
+            quadgram_keys_vec = quadgram_keys_vec
+                .into_iter()
+                .flat_map(|(keys, w)| resolve_multi_key_symbols(keys, w, layout))
+                .collect();
+        }
+
+        if layout.has_lock_layers() {
+            let lock_toggles = self.split_modifiers.lock_toggles;
+            quadgram_keys_vec = quadgram_keys_vec
+                .into_iter()
+                .flat_map(|(keys, w)| {
+                    resolve_lock_layers(
+                        keys,
+                        w,
+                        layout,
+                        &resolved,
+                        lock_toggles,
+                        &mut modifier_presses,
+                    )
+                })
+                .collect();
+        }
+
+        if self.shift_policy.has_one_shot_layers(layout) {
+            quadgram_keys_vec = self.process_one_shot_modifiers(
+                quadgram_keys_vec,
+                layout,
+                &resolved,
+                &mut modifier_presses,
+            );
+        }
+
+        let quadgram_keys = if self.split_modifiers.splits_any_layer()
+            && self.shift_policy.has_hold_layers(layout)
+        {
+            self.process_hold_modifiers(quadgram_keys_vec, layout, &resolved, &mut modifier_presses)
+        } else {
+            quadgram_keys_vec
+                .into_iter()
+                .map(|([k1, k2, k3, k4], w)| ((k1, k2, k3, k4), w))
+                .collect()
+        };
+
+        (
+            quadgram_keys,
+            not_found_weight,
+            missing_symbols,
+            modifier_presses,
+        )
+    }
+
+    /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`] and filters quadgrams that contain
+    /// repeating identical modifiers. If `sorted`, the ngrams are ordered by their indices.
+    pub fn get_filtered_layerkeys<'s>(
+        quadgrams: &QuadgramIndices,
+        layout: &'s Layout,
+        sorted: bool,
+    ) -> Vec<(LayerKeyQuadgram<'s>, f64)> {
+        let mut layerkeys = Vec::with_capacity(quadgrams.len());
+
+        layerkeys.extend(
+            entries(quadgrams, sorted).filter_map(|((idx1, idx2, idx3, idx4), w)| {
+                let k2 = layout.get_layerkey(idx2);
+                let k3 = layout.get_layerkey(idx3);
+
+                // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
+                // --> remove
+                let repeated_hold = (k2.is_modifier.is_held() && (idx1 == idx2 || idx2 == idx3))
+                    || (k3.is_modifier.is_held() && (idx2 == idx3 || idx3 == idx4));
+                match repeated_hold {
+                    false => Some((
+                        (
+                            layout.get_layerkey(idx1), // LayerKey 1
+                            k2,                        // LayerKey 2
+                            k3,                        // LayerKey 3
+                            layout.get_layerkey(idx4), // LayerKey 4
+                        ),
+                        *w,
+                    )),
+                    true => None,
+                }
+            }),
+        );
+
+        layerkeys
+    }
+
+    /// Map all quadgrams to base-layer quadgrams, potentially generating multiple quadgrams
+    /// with modifiers for those with higer-layer keys.
+    ///
+    /// Analogous to the trigrams, the four positions of each generated quadgram are taken from
+    /// consecutive keys of the source quadgram (each contributing at least one of its modifiers
+    /// or its base-layer key), with keys from latter parts always after former ones and modifiers
+    /// always before their base key.
+    fn process_hold_modifiers(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> QuadgramIndices {
+        let mut quadgram_w_map =
+            IndexMap::with_capacity_and_hasher(quadgrams.len() / 3, FixedState);
+        let same_key_mod_factor = self.split_modifiers.same_key_mod_factor;
+        let ordering = self.split_modifiers.hold_mod_ordering;
+        let chord_cost_factor = self.split_modifiers.chord_cost_factor;
+
+        quadgrams.into_iter().for_each(|(keys, w)| {
+            let mut keys_with_mods = Vec::with_capacity(4);
+            for (i, k) in keys.iter().enumerate() {
+                let (key, mods, chord) = resolved.hold(layout, k, &keys[..i]);
+                add_modifier_presses(modifier_presses, mods, w);
+                let factor = resolved.same_key_mod_factor(k, same_key_mod_factor);
+                keys_with_mods.push((key, mods, factor, chord));
+            }
+
+            // the keys of each part of a composition for each source key (by part size)
+            let takes: Vec<Vec<PartialQuadgrams>> = keys_with_mods
+                .iter()
+                .map(|(key, mods, factor, chord)| {
+                    (0..=4)
+                        .map(|n| match n {
+                            0 => Vec::new(),
+                            n => take_layerkeys(
+                                *key,
+                                mods,
+                                n,
+                                *factor,
+                                ordering,
+                                *chord,
+                                chord_cost_factor,
+                            ),
+                        })
+                        .collect()
+                })
+                .collect();
+
+            for composition in COMPOSITIONS.iter() {
+                for start in 0..=(4 - composition.len()) {
+                    let mut partial: PartialQuadgrams = vec![(Vec::new(), w)];
+                    for (i, n) in composition.iter().enumerate() {
+                        partial = partial
+                            .iter()
+                            .flat_map(|(prefix, pw)| {
+                                takes[start + i][*n].iter().map(move |(part, tw)| {
+                                    let mut keys = prefix.clone();
+                                    keys.extend(part);
+                                    (keys, pw * tw)
+                                })
+                            })
+                            .collect();
+                    }
+
+                    partial.into_iter().for_each(|(e, w)| {
+                        quadgram_w_map.insert_or_add_weight((e[0], e[1], e[2], e[3]), w)
+                    });
+                }
+            }
+        });
+
+        quadgram_w_map
+    }
+
+    fn process_one_shot_modifiers(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> QuadgramIndicesVec {
+        let mut processed_quadgrams = Vec::with_capacity(quadgrams.len());
+
+        quadgrams.into_iter().for_each(|(source_keys, w)| {
+            resolved
+                .sentence_starts(layout, &source_keys, w)
+                .for_each(|(sentence_start, w)| {
+                    let mut symbols = Vec::with_capacity(4);
+
+                    for i in 0..source_keys.len() {
+                        let (key, mods) =
+                            resolved.one_shot_in(layout, &source_keys, i, sentence_start);
+                        add_modifier_presses(modifier_presses, mods, w);
+                        symbols.push((key, mods));
+                    }
+
+                    let factor = self.split_modifiers.one_shot_weight_factor;
+                    self.split_modifiers
+                        .one_shot_ordering
+                        .key_sequences(&symbols)
+                        .iter()
+                        .for_each(|sequence| {
+                            processed_quadgrams.extend(sequence.ngrams(w, factor))
+                        });
+                });
+        });
+
+        processed_quadgrams
+    }
+}
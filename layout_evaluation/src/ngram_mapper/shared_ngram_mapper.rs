@@ -0,0 +1,335 @@
+//! This module provides an implementation of the [`NgramMapper`] trait that shares the mapping of
+//! ngrams between layouts that differ only in some of their symbols (e.g. in higher layers).
+//!
+//! The mapping of each char-based ngram only depends on the [`LayerKey`]s of its own symbols
+//! (and the modifiers required for them). Ngrams that consist of "stable" symbols only (see
+//! [`stable_symbols`]) therefore map to the same [`LayerKeyIndex`]-based ngrams for all layouts of
+//! a batch. These are mapped once up front and only the remaining ngrams are mapped for each
+//! layout individually.
+
+use super::bigram_mapper::{BigramIndices, OnDemandBigramMapper};
+use super::common::{IndexMap, MissingSymbols, ModifierPresses, NgramMap};
+use super::on_demand_ngram_mapper::{get_modifier_presses, OnDemandNgramMapper};
+use super::quadgram_mapper::{OnDemandQuadgramMapper, QuadgramIndices};
+use super::trigram_mapper::{OnDemandTrigramMapper, TrigramIndices};
+use super::unigram_mapper::{OnDemandUnigramMapper, UnigramIndices};
+use super::{
+    MappedBigrams, MappedQuadgrams, MappedTrigrams, MappedUnigrams, MappingStatistics, NgramMapper,
+    StatisticsWeights,
+};
+
+use crate::memory::MemoryReport;
+
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+
+use ahash::AHashSet;
+use std::hash::Hash;
+
+/// [`LayerKeyIndex`]-based ngrams along with the statistics of their mapping.
+#[derive(Clone, Debug)]
+pub struct SharedMapping<I> {
+    pub indices: I,
+    pub weight_not_found: f64,
+    pub missing_symbols: MissingSymbols,
+    pub modifier_presses: ModifierPresses,
+}
+
+impl<I> From<(I, f64, MissingSymbols, ModifierPresses)> for SharedMapping<I> {
+    fn from(
+        (indices, weight_not_found, missing_symbols, modifier_presses): (
+            I,
+            f64,
+            MissingSymbols,
+            ModifierPresses,
+        ),
+    ) -> Self {
+        Self {
+            indices,
+            weight_not_found,
+            missing_symbols,
+            modifier_presses,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> SharedMapping<IndexMap<K>> {
+    /// Combine the shared mapping with the mapping of the remaining ngrams.
+    fn merged(&self, other: SharedMapping<IndexMap<K>>) -> SharedMapping<IndexMap<K>> {
+        let mut merged = self.clone();
+        other
+            .indices
+            .into_iter()
+            .for_each(|(k, w)| merged.indices.insert_or_add_weight(k, w));
+        other
+            .modifier_presses
+            .into_iter()
+            .for_each(|(k, w)| merged.modifier_presses.insert_or_add_weight(k, w));
+        merged.weight_not_found += other.weight_not_found;
+        other
+            .missing_symbols
+            .into_iter()
+            .for_each(|(c, w)| merged.missing_symbols.insert_or_add_weight(c, w));
+
+        merged
+    }
+
+    /// Approximate number of bytes allocated for the mapping
+    pub(super) fn estimated_bytes(&self) -> usize {
+        let entry_bytes = std::mem::size_of::<(K, f64)>();
+        self.indices.capacity() * entry_bytes
+            + self.modifier_presses.capacity() * std::mem::size_of::<(LayerKeyIndex, f64)>()
+    }
+}
+
+/// Symbols that are generated identically by all given layouts, i.e. with the same
+/// [`LayerKeyIndex`], the same [`LayerKey`], and the same modifiers. Symbols that are not
+/// generated by any of the layouts count as stable as well.
+///
+/// The layouts need to be based on the same keyboard.
+pub fn stable_symbols(layouts: &[&Layout]) -> AHashSet<char> {
+    let mut symbols: AHashSet<char> = layouts
+        .iter()
+        .flat_map(|l| l.layerkeys.iter().map(|lk| lk.symbol))
+        .collect();
+
+    let first = match layouts.first() {
+        Some(l) => l,
+        None => return symbols,
+    };
+
+    symbols.retain(|c| {
+        let idx = first.get_layerkey_index_for_symbol(c);
+        layouts.iter().all(|l| {
+            let other_idx = l.get_layerkey_index_for_symbol(c);
+            if other_idx != idx {
+                return false;
+            }
+
+            let idx = match idx {
+                Some(idx) => idx,
+                None => return true,
+            };
+            let (base, modifiers) = first.resolve_modifiers(&idx);
+            let (other_base, other_modifiers) = l.resolve_modifiers(&idx);
+
+            base == other_base
+                && modifiers == other_modifiers
+                && first.get_layerkey(&idx) == l.get_layerkey(&idx)
+                && first.get_layerkey(&base) == l.get_layerkey(&base)
+                && modifiers
+                    .layerkey_indices()
+                    .iter()
+                    .all(|m| first.get_layerkey(m) == l.get_layerkey(m))
+        })
+    });
+
+    symbols
+}
+
+/// Implements the [`NgramMapper`] trait for a batch of layouts sharing their stable symbols.
+///
+/// Created with [`NgramMapper::share_mapping`] of an [`OnDemandNgramMapper`].
+#[derive(Clone, Debug)]
+pub struct SharedNgramMapper {
+    unigrams: SharedMapping<UnigramIndices>,
+    bigrams: SharedMapping<BigramIndices>,
+    trigrams: SharedMapping<TrigramIndices>,
+    quadgrams: SharedMapping<QuadgramIndices>,
+    skipgrams: SharedMapping<BigramIndices>,
+    /// Total weights of all unigrams, bigrams, trigrams, quadgrams, and skipgrams
+    total_weights: (f64, f64, f64, f64, f64),
+    /// Total weights of all unigrams, bigrams, and trigrams and the weights of the excluded bigrams
+    /// and trigrams (for the [`MappingStatistics`])
+    statistics_weights: StatisticsWeights,
+    /// Mapper for the ngrams containing at least one symbol that is not stable
+    variant_mapper: OnDemandNgramMapper,
+}
+
+impl SharedNgramMapper {
+    pub fn new(
+        unigrams: SharedMapping<UnigramIndices>,
+        bigrams: SharedMapping<BigramIndices>,
+        trigrams: SharedMapping<TrigramIndices>,
+        quadgrams: SharedMapping<QuadgramIndices>,
+        skipgrams: SharedMapping<BigramIndices>,
+        total_weights: (f64, f64, f64, f64, f64),
+        variant_mapper: OnDemandNgramMapper,
+    ) -> Self {
+        Self {
+            unigrams,
+            bigrams,
+            trigrams,
+            quadgrams,
+            skipgrams,
+            total_weights,
+            statistics_weights: (
+                (total_weights.0, total_weights.1, total_weights.2),
+                (0.0, 0.0),
+            ),
+            variant_mapper,
+        }
+    }
+
+    /// Set the total weights of all unigrams, bigrams, and trigrams and the weights of the
+    /// bigrams and trigrams that are excluded from the mapping (for the [`MappingStatistics`]).
+    pub fn with_statistics_weights(mut self, statistics_weights: StatisticsWeights) -> Self {
+        self.statistics_weights = statistics_weights;
+        self
+    }
+}
+
+impl NgramMapper for SharedNgramMapper {
+    fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
+        let mapping = self
+            .unigrams
+            .merged(self.variant_mapper.unigram_indices(layout));
+
+        MappedUnigrams {
+            grams: OnDemandUnigramMapper::get_layerkeys(
+                &mapping.indices,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            ),
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols,
+            weight_found: self.total_weights.0 - mapping.weight_not_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
+        let mapping = self
+            .bigrams
+            .merged(self.variant_mapper.bigram_indices(layout));
+
+        MappedBigrams {
+            grams: OnDemandBigramMapper::get_filtered_layerkeys(
+                &mapping.indices,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            ),
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols,
+            weight_found: self.total_weights.1 - mapping.weight_not_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s> {
+        let mut mapping = self
+            .trigrams
+            .merged(self.variant_mapper.trigram_indices(layout));
+        let added_weight = self
+            .variant_mapper
+            .increase_common_trigrams(&mut mapping.indices);
+        let (grams, weight_filtered) = OnDemandTrigramMapper::get_filtered_layerkeys(
+            &mapping.indices,
+            layout,
+            self.variant_mapper.deterministic_order(),
+        );
+
+        MappedTrigrams {
+            grams,
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols,
+            weight_found: self.total_weights.2 - mapping.weight_not_found + added_weight,
+            weight_filtered,
+            secondary_bigrams: self
+                .variant_mapper
+                .secondary_bigrams(&mapping.indices, layout),
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s> {
+        let mapping = self
+            .quadgrams
+            .merged(self.variant_mapper.quadgram_indices(layout));
+
+        MappedQuadgrams {
+            grams: OnDemandQuadgramMapper::get_filtered_layerkeys(
+                &mapping.indices,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            ),
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols,
+            weight_found: self.total_weights.3 - mapping.weight_not_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            )),
+        }
+    }
+
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
+        let mapping = self
+            .skipgrams
+            .merged(self.variant_mapper.skipgram_indices(layout));
+
+        MappedBigrams {
+            grams: OnDemandBigramMapper::get_filtered_layerkeys(
+                &mapping.indices,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            ),
+            weight_not_found: mapping.weight_not_found,
+            missing_symbols: mapping.missing_symbols,
+            weight_found: self.total_weights.4 - mapping.weight_not_found,
+            modifier_presses: Some(get_modifier_presses(
+                &mapping.modifier_presses,
+                layout,
+                self.variant_mapper.deterministic_order(),
+            )),
+        }
+    }
+
+    fn mapping_statistics(&self, layout: &Layout) -> Option<MappingStatistics> {
+        Some(MappingStatistics::from_mapped(
+            &self.map_unigrams(layout),
+            &self.map_bigrams(layout),
+            &self.map_trigrams(layout),
+            self.statistics_weights.0,
+            self.statistics_weights.1,
+        ))
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        self.variant_mapper.memory_report(report);
+        report.add(
+            "Shared mapped unigrams",
+            self.unigrams.indices.len(),
+            self.unigrams.estimated_bytes(),
+        );
+        report.add(
+            "Shared mapped bigrams",
+            self.bigrams.indices.len(),
+            self.bigrams.estimated_bytes(),
+        );
+        report.add(
+            "Shared mapped trigrams",
+            self.trigrams.indices.len(),
+            self.trigrams.estimated_bytes(),
+        );
+        if !self.quadgrams.indices.is_empty() {
+            report.add(
+                "Shared mapped quadgrams",
+                self.quadgrams.indices.len(),
+                self.quadgrams.estimated_bytes(),
+            );
+        }
+    }
+}
@@ -1,40 +1,42 @@
 //! This module provides an implementation of trigram mapping functionalities
 //! used by the [`OnDemandNgramMapper`].
 
-use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
+use super::bigram_mapper::BigramIndices;
+use super::on_demand_ngram_mapper::{SecondaryBigramsConfig, SplitModifiersConfig};
+use super::{common::*, LayerKeyTrigram};
 
-use crate::ngrams::Trigrams;
+use crate::ngrams::{increase_common_weights, IncreaseCommonNgramsConfig, Trigrams};
 
-use ahash::AHashMap;
-use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
 
 // Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
 // ones are used because they are smaller than a reference (u16 vs usize) and yield better
 // hashing performance.
-pub type TrigramIndices = AHashMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64>;
+pub type TrigramIndices = IndexMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex)>;
 type TrigramIndicesVec = Vec<((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64)>;
 
 /// Turns the [`Trigrams`]'s characters into their indices, returning a [`TrigramIndicesVec`].
+///
+/// Each symbol belongs to a single [`LayerKeyIndex`], so the index tuples are as unique as the
+/// char-based trigrams and need not be merged before splitting off modifiers.
 fn map_trigrams(
     trigrams: &Trigrams,
     layout: &Layout,
-    exclude_line_breaks: bool,
-) -> (TrigramIndicesVec, f64) {
+    line_breaks: LineBreakHandling,
+    sorted: bool,
+) -> (TrigramIndicesVec, f64, MissingSymbols) {
     let mut not_found_weight = 0.0;
+    let mut missing_symbols = MissingSymbols::default();
     let mut trigrams_vec = Vec::with_capacity(trigrams.grams.len());
 
     trigrams_vec.extend(
-        trigrams
-            .grams
-            .iter()
+        entries(&trigrams.grams, sorted)
             //.filter(|((c1, c2, c3), _weight)| {
             //    !c1.is_whitespace() && !c2.is_whitespace() && !c3.is_whitespace()
             //})
             .filter_map(|((c1, c2, c3), weight)| {
-                // Exclude trigrams that contain a line break, followed by a non-line-break character
-                if exclude_line_breaks
-                    && ((*c1 == '\n' && *c2 != '\n') || (*c2 == '\n' && *c3 != '\n'))
-                {
+                // Exclude trigrams that contain line breaks (see `LineBreakHandling`)
+                if line_breaks.excludes(&[*c1, *c2, *c3]) {
                     return None;
                 }
 
@@ -42,6 +44,7 @@ fn map_trigrams(
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c1, *weight);
                         return None;
                     }
                 };
@@ -49,6 +52,7 @@ fn map_trigrams(
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c2, *weight);
                         return None;
                     }
                 };
@@ -56,6 +60,7 @@ fn map_trigrams(
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c3, *weight);
                         return None;
                     }
                 };
@@ -64,7 +69,41 @@ fn map_trigrams(
             }),
     );
 
-    (trigrams_vec, not_found_weight)
+    (trigrams_vec, not_found_weight, missing_symbols)
+}
+
+/// Keep only the `max` highest-weight trigrams generated from a single trigram (preferring the
+/// earlier generated ones, i.e. those with the base keys first, among equal weights) and scale
+/// their weights such that the total weight is conserved. Returns whether trigrams were dropped.
+fn cap_generated(generated: &mut TrigramIndicesVec, max: usize) -> bool {
+    if generated.len() <= max {
+        return false;
+    }
+
+    // the same trigram can be generated multiple times, e.g. from different modifiers
+    let mut merged: TrigramIndicesVec = Vec::with_capacity(generated.len());
+    generated.iter().for_each(
+        |(trigram, w)| match merged.iter_mut().find(|(t, _)| t == trigram) {
+            Some((_, merged_w)) => *merged_w += w,
+            None => merged.push((*trigram, *w)),
+        },
+    );
+    *generated = merged;
+    if generated.len() <= max {
+        return false;
+    }
+
+    let total_weight: f64 = generated.iter().map(|(_, w)| w).sum();
+    generated.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+    generated.truncate(max);
+
+    let kept_weight: f64 = generated.iter().map(|(_, w)| w).sum();
+    if kept_weight > 0.0 {
+        let factor = total_weight / kept_weight;
+        generated.iter_mut().for_each(|(_, w)| *w *= factor);
+    }
+
+    true
 }
 
 /// Generates [`LayerKey`]-based trigrams from char-based unigrams. Optionally resolves modifiers
@@ -72,63 +111,231 @@ fn map_trigrams(
 #[derive(Clone, Debug)]
 pub struct OnDemandTrigramMapper {
     split_modifiers: SplitModifiersConfig,
+    shift_policy: ShiftPolicy,
+    duplicate_symbols: DuplicateSymbolPolicy,
+    /// Shares of the shifted letters starting a sentence (see [`ShiftPolicy::SentenceStart`])
+    sentence_starts: SentenceStarts,
+    increase_common: IncreaseCommonNgramsConfig,
+    /// Visit the ngrams in the order of their symbols (see [`entries`])
+    deterministic_order: bool,
 }
 
 impl OnDemandTrigramMapper {
-    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
-        Self { split_modifiers }
+    pub fn new(
+        split_modifiers: SplitModifiersConfig,
+        shift_policy: ShiftPolicy,
+        increase_common: IncreaseCommonNgramsConfig,
+        deterministic_order: bool,
+    ) -> Self {
+        Self {
+            split_modifiers,
+            shift_policy,
+            duplicate_symbols: DuplicateSymbolPolicy::default(),
+            sentence_starts: SentenceStarts::default(),
+            increase_common,
+            deterministic_order,
+        }
+    }
+
+    /// Attribute the weight of symbols that the layout generates with multiple keys according to
+    /// the policy (see [`resolve_duplicate_symbols`]).
+    pub fn with_duplicate_symbols(mut self, policy: DuplicateSymbolPolicy) -> Self {
+        self.duplicate_symbols = policy;
+        self
+    }
+
+    /// Split the weight of ngrams lacking the context of a shifted letter by its share of
+    /// occurrences at the start of a sentence (see [`ShiftPolicy::SentenceStart`]).
+    pub fn with_sentence_starts(mut self, sentence_starts: SentenceStarts) -> Self {
+        self.sentence_starts = sentence_starts;
+        self
+    }
+
+    /// Increase the weight of the most common [`LayerKeyIndex`]-based trigrams (after modifiers
+    /// have been split off), see [`IncreaseCommonNgramsConfig`]. The fraction is relative to the
+    /// total weight of all mapped trigrams, so this needs to be applied to the complete mapping of
+    /// a layout. Returns the added weight.
+    pub fn increase_common(&self, trigrams: &mut TrigramIndices) -> f64 {
+        if !self.increase_common.enabled {
+            return 0.0;
+        }
+
+        let total_weight = total_weight(trigrams, self.deterministic_order);
+        if self.deterministic_order {
+            let mut weights: Vec<_> = trigrams.iter_mut().collect();
+            weights.sort_unstable_by_key(|(k, _)| **k);
+            increase_common_weights(
+                weights.into_iter().map(|(_, w)| w),
+                total_weight,
+                &self.increase_common,
+            )
+        } else {
+            increase_common_weights(trigrams.values_mut(), total_weight, &self.increase_common)
+        }
     }
 
     /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
+    ///
+    /// Additionally returns the weight of the ngrams that can not be generated by the layout (in
+    /// total and per symbol) and the number of presses of each modifier that was split off.
     pub fn layerkey_indices(
         &self,
         trigrams: &Trigrams,
         layout: &Layout,
-        exclude_line_breaks: bool,
-    ) -> (TrigramIndices, f64) {
-        let (mut trigram_keys_vec, not_found_weight) =
-            map_trigrams(trigrams, layout, exclude_line_breaks);
+        line_breaks: LineBreakHandling,
+    ) -> (TrigramIndices, f64, MissingSymbols, ModifierPresses) {
+        let (mut trigram_keys_vec, not_found_weight, missing_symbols) =
+            map_trigrams(trigrams, layout, line_breaks, self.deterministic_order);
+        let mut modifier_presses = ModifierPresses::default();
+        let resolved = ResolvedModifiers::new(self.shift_policy, layout)
+            .with_layer_overrides(&self.split_modifiers, layout)
+            .with_sentence_starts(&self.sentence_starts, layout);
+
+        if self.duplicate_symbols != DuplicateSymbolPolicy::CheapestOnly
+            && layout.has_duplicate_symbols()
+        {
+            trigram_keys_vec = trigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2, k3), w)| {
+                    resolve_duplicate_symbols([k1, k2, k3], w, layout, self.duplicate_symbols)
+                })
+                .map(|([k1, k2, k3], w)| ((k1, k2, k3), w))
+                .collect();
+        }
+
+        if layout.has_multi_key_symbols() {
+            trigram_keys_vec = trigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2, k3), w)| resolve_multi_key_symbols([k1, k2, k3], w, layout))
+                .map(|([k1, k2, k3], w)| ((k1, k2, k3), w))
+                .collect();
+        }
+
+        if layout.has_lock_layers() {
+            let lock_toggles = self.split_modifiers.lock_toggles;
+            trigram_keys_vec = trigram_keys_vec
+                .into_iter()
+                .flat_map(|((k1, k2, k3), w)| {
+                    resolve_lock_layers(
+                        [k1, k2, k3],
+                        w,
+                        layout,
+                        &resolved,
+                        lock_toggles,
+                        &mut modifier_presses,
+                    )
+                })
+                .map(|([k1, k2, k3], w)| ((k1, k2, k3), w))
+                .collect();
+        }
 
-        if layout.has_one_shot_layers() {
-            trigram_keys_vec = self.process_one_shot_modifiers(trigram_keys_vec, layout);
+        if self.shift_policy.has_one_shot_layers(layout) {
+            trigram_keys_vec = self.process_one_shot_modifiers(
+                trigram_keys_vec,
+                layout,
+                &resolved,
+                &mut modifier_presses,
+            );
         }
 
-        let trigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
-            self.process_hold_modifiers(trigram_keys_vec, layout)
+        let trigram_keys = if self.split_modifiers.splits_any_layer()
+            && self.shift_policy.has_hold_layers(layout)
+        {
+            self.process_hold_modifiers(trigram_keys_vec, layout, &resolved, &mut modifier_presses)
         } else {
             trigram_keys_vec.into_iter().collect()
         };
 
-        (trigram_keys, not_found_weight)
+        (
+            trigram_keys,
+            not_found_weight,
+            missing_symbols,
+            modifier_presses,
+        )
+    }
+
+    /// Whether the same hold modifier appears consecutively in the trigram. It is usually held
+    /// instead of repeatedly pressed, so such trigrams are removed from the mapped trigrams.
+    pub fn repeats_hold_modifier(
+        (idx1, idx2, idx3): &(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex),
+        layout: &Layout,
+    ) -> bool {
+        layout.get_layerkey(idx2).is_modifier.is_held() && (idx1 == idx2 || idx2 == idx3)
     }
 
     /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`] and filters trigrams that contain
-    /// repeating identical modifiers.
+    /// repeating identical modifiers. If `sorted`, the ngrams are ordered by their indices.
+    /// Returns the filtered trigrams along with the total weight of the removed ones.
     pub fn get_filtered_layerkeys<'s>(
         trigrams: &TrigramIndices,
         layout: &'s Layout,
-    ) -> Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)> {
+        sorted: bool,
+    ) -> (Vec<(LayerKeyTrigram<'s>, f64)>, f64) {
         let mut layerkeys = Vec::with_capacity(trigrams.len());
+        let mut filtered_weight = 0.0;
 
-        layerkeys.extend(trigrams.iter().filter_map(|((idx1, idx2, idx3), w)| {
-            let k2 = layout.get_layerkey(idx2);
-
-            // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
-            // --> remove
-            match k2.is_modifier.is_hold() && (idx1 == idx2 || idx2 == idx3) {
+        layerkeys.extend(entries(trigrams, sorted).filter_map(|(trigram, w)| {
+            match Self::repeats_hold_modifier(trigram, layout) {
                 false => Some((
                     (
-                        layout.get_layerkey(idx1), // LayerKey 1
-                        k2,                        // LayerKey 2
-                        layout.get_layerkey(idx3), // LayerKey 3
+                        layout.get_layerkey(&trigram.0), // LayerKey 1
+                        layout.get_layerkey(&trigram.1), // LayerKey 2
+                        layout.get_layerkey(&trigram.2), // LayerKey 3
                     ),
                     *w,
                 )),
-                true => None,
+                true => {
+                    filtered_weight += w;
+                    None
+                }
             }
         }));
 
-        layerkeys
+        (layerkeys, filtered_weight)
+    }
+
+    /// Derive the secondary bigrams (the first and third key of each trigram) from mapped trigrams.
+    /// The trigram's weight is multiplied by the factor of [`SecondaryBigramsConfig`] depending on
+    /// whether all three keys are typed with the same hand. Trigrams removed by
+    /// [`Self::get_filtered_layerkeys`], trigrams with the same modifier as first and third key,
+    /// and trigrams containing a mental pause (see
+    /// [`SecondaryBigramsConfig::initial_pause_indicators`]) do not contribute.
+    pub fn secondary_bigrams(
+        trigrams: &TrigramIndices,
+        layout: &Layout,
+        config: &SecondaryBigramsConfig,
+        sorted: bool,
+    ) -> BigramIndices {
+        let mut bigrams = BigramIndices::default();
+        entries(trigrams, sorted).for_each(|(trigram, w)| {
+            let (idx1, _, idx3) = trigram;
+            let k1 = layout.get_layerkey(idx1);
+            let k2 = layout.get_layerkey(&trigram.1);
+            let k3 = layout.get_layerkey(idx3);
+
+            let filtered = Self::repeats_hold_modifier(trigram, layout);
+            let same_modifier = idx1 == idx3 && k1.is_modifier.is_some();
+            // a pause indicator, followed by a whitespace and a normal letter
+            let pause = config.initial_pause_indicators.contains(&k1.symbol)
+                && k2.symbol == ' '
+                && !config.initial_pause_indicators.contains(&k3.symbol)
+                && k3.symbol != ' ';
+            if filtered || same_modifier || pause {
+                return;
+            }
+
+            let factor = if layout.geometry().hand(k1.key_index)
+                == layout.geometry().hand(k2.key_index)
+                && layout.geometry().hand(k2.key_index) == layout.geometry().hand(k3.key_index)
+            {
+                config.factor_no_handswitch
+            } else {
+                config.factor_handswitch
+            };
+            bigrams.insert_or_add_weight((*idx1, *idx3), factor * w);
+        });
+
+        bigrams
     }
 
     /// Map all trigrams to base-layer trigrams, potentially generating multiple trigrams
@@ -136,186 +343,235 @@ impl OnDemandTrigramMapper {
     ///
     /// Each trigram of higher-layer symbols will transform into a series of various trigrams with permutations
     /// of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
-    /// former ones and modifers always come before their base key. The number of generated trigrams from a single
-    /// trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
-
+    /// former ones and modifers always come before their base key. The keys of a chord are not permuted, but
+    /// pressed together in their configured order (see [`TakeLayerKeys::with_chord`]). The number of generated
+    /// trigrams from a single trigram can be large (tens of trigrams) if multiple symbols of the trigram are
+    /// accessed using multiple modifiers.
+    /// It can be limited with [`SplitModifiersConfig::max_generated_per_trigram`].
+    //
     // this is one of the most intensive functions of the layout evaluation
     fn process_hold_modifiers(
         &self,
         trigrams: TrigramIndicesVec,
         layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
     ) -> TrigramIndices {
-        let mut trigram_w_map = AHashMap::with_capacity(trigrams.len() / 3);
-        trigrams.into_iter().for_each(|((k1, k2, k3), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
-            let (base3, mods3) = layout.resolve_modifiers(&k3);
+        #[cfg(feature = "parallel")]
+        let (trigram_w_map, capped) = if self.split_modifiers.parallel {
+            self.expand_hold_modifiers_parallel(&trigrams, layout, resolved, modifier_presses)
+        } else {
+            self.expand_hold_modifiers(&trigrams, layout, resolved, modifier_presses)
+        };
+        #[cfg(not(feature = "parallel"))]
+        let (trigram_w_map, capped) =
+            self.expand_hold_modifiers(&trigrams, layout, resolved, modifier_presses);
+
+        if capped > 0 {
+            log::debug!(
+                "Capped the trigrams generated from {} trigrams to {}",
+                capped,
+                self.split_modifiers
+                    .max_generated_per_trigram
+                    .unwrap_or_default(),
+            );
+        }
 
-            let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
-                _ => (k1, Vec::new()),
-            };
+        trigram_w_map
+    }
 
-            let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
-                _ => (k2, Vec::new()),
-            };
+    /// Split the modifiers off a chunk of trigrams in parallel (see [`Self::expand_hold_modifiers`]).
+    ///
+    /// The chunks do not depend on the number of threads and their results are merged in their
+    /// order, so the summed weights are identical for each run.
+    #[cfg(feature = "parallel")]
+    fn expand_hold_modifiers_parallel(
+        &self,
+        trigrams: &[((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64)],
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> (TrigramIndices, usize) {
+        use rayon::prelude::*;
+
+        const CHUNK_SIZE: usize = 4096;
+
+        let chunk_results: Vec<_> = trigrams
+            .par_chunks(CHUNK_SIZE)
+            .map(|chunk| {
+                let mut chunk_presses = ModifierPresses::default();
+                let (chunk_map, capped) =
+                    self.expand_hold_modifiers(chunk, layout, resolved, &mut chunk_presses);
+                (chunk_map, chunk_presses, capped)
+            })
+            .collect();
+
+        let mut trigram_w_map = IndexMap::with_capacity_and_hasher(trigrams.len() / 3, FixedState);
+        let mut capped = 0;
+        chunk_results
+            .into_iter()
+            .for_each(|(chunk_map, chunk_presses, chunk_capped)| {
+                // the chunk maps iterate in an order that only depends on their content
+                chunk_map
+                    .into_iter()
+                    .for_each(|(trigram, w)| trigram_w_map.insert_or_add_weight(trigram, w));
+                chunk_presses
+                    .into_iter()
+                    .for_each(|(m, w)| modifier_presses.insert_or_add_weight(m, w));
+                capped += chunk_capped;
+            });
 
-            let (key3, mods3) = match mods3 {
-                LayerModifiers::Hold(mods) => (base3, mods),
-                _ => (k3, Vec::new()),
-            };
+        (trigram_w_map, capped)
+    }
 
-            let k1_take_one = TakeOneLayerKey::new(key1, &mods1, w);
-            let k2_take_one = TakeOneLayerKey::new(key2, &mods2, w);
-            let k3_take_one = TakeOneLayerKey::new(key3, &mods3, w);
-
-            let k1_take_two =
-                TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor);
-            let k2_take_two =
-                TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor);
-            let k3_take_two =
-                TakeTwoLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor);
-
-            k1_take_one.clone().for_each(|(e1, _)| {
-                k2_take_one.clone().for_each(|(e2, _)| {
-                    k3_take_one.clone().for_each(|(e3, _)| {
-                        // log::trace!(
-                        //     "one each:                    {}{}{}",
-                        //     layout.get_layerkey(&e1).symbol,
-                        //     layout.get_layerkey(&e2).symbol,
-                        //     layout.get_layerkey(&e3).symbol,
-                        // );
-                        trigram_w_map.insert_or_add_weight((e1, e2, e3), w);
+    /// Generate the trigrams for a (chunk of) trigrams with modifiers split off (see
+    /// [`Self::process_hold_modifiers`]). Additionally returns the number of trigrams whose
+    /// generated trigrams were capped.
+    fn expand_hold_modifiers(
+        &self,
+        trigrams: &[((LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64)],
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> (TrigramIndices, usize) {
+        let mut trigram_w_map = IndexMap::with_capacity_and_hasher(trigrams.len() / 3, FixedState);
+        let mut generated = TrigramIndicesVec::new();
+        let mut capped = 0;
+        trigrams.iter().for_each(|&((k1, k2, k3), w)| {
+            generated.clear();
+
+            let (key1, mods1, chord1) = resolved.hold(layout, &k1, &[]);
+            let (key2, mods2, chord2) = resolved.hold(layout, &k2, &[k1]);
+            let (key3, mods3, chord3) = resolved.hold(layout, &k3, &[k1, k2]);
+            let chord_factor = self.split_modifiers.chord_cost_factor;
+            let default_factor = self.split_modifiers.same_key_mod_factor;
+            let factor1 = resolved.same_key_mod_factor(&k1, default_factor);
+            let factor2 = resolved.same_key_mod_factor(&k2, default_factor);
+            let factor3 = resolved.same_key_mod_factor(&k3, default_factor);
+
+            add_modifier_presses(modifier_presses, mods1, w);
+            add_modifier_presses(modifier_presses, mods2, w);
+            add_modifier_presses(modifier_presses, mods3, w);
+
+            let k1_take_one =
+                TakeLayerKeys::<1>::new(key1, mods1, w, 1.0).with_chord(chord1, chord_factor);
+            let k2_take_one =
+                TakeLayerKeys::<1>::new(key2, mods2, w, 1.0).with_chord(chord2, chord_factor);
+            let k3_take_one =
+                TakeLayerKeys::<1>::new(key3, mods3, w, 1.0).with_chord(chord3, chord_factor);
+
+            let k1_take_two = TakeLayerKeys::<2>::new(key1, mods1, w, factor1)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord1, chord_factor);
+            let k2_take_two = TakeLayerKeys::<2>::new(key2, mods2, w, factor2)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord2, chord_factor);
+            let k3_take_two = TakeLayerKeys::<2>::new(key3, mods3, w, factor3)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord3, chord_factor);
+
+            k1_take_one.clone().for_each(|([e1], _)| {
+                k2_take_one.clone().for_each(|([e2], _)| {
+                    k3_take_one.clone().for_each(|([e3], _)| {
+                        generated.push(((e1, e2, e3), w));
                     });
                 });
             });
 
-            k1_take_two.for_each(|((e1, e2), w1)| {
-                k2_take_one.clone().for_each(|(e3, _)| {
-                    // log::trace!(
-                    //     "two of first, one of second: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+            k1_take_two.for_each(|([e1, e2], w1)| {
+                k2_take_one.clone().for_each(|([e3], _)| {
+                    generated.push(((e1, e2, e3), w1));
                 });
             });
 
-            k1_take_one.for_each(|(e1, _)| {
-                k2_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
-                    //     "one of first, two of second: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+            k1_take_one.for_each(|([e1], _)| {
+                k2_take_two.clone().for_each(|([e2, e3], w1)| {
+                    generated.push(((e1, e2, e3), w1));
                 });
             });
 
-            k2_take_two.for_each(|((e1, e2), w1)| {
-                k3_take_one.clone().for_each(|(e3, _)| {
-                    // log::trace!(
-                    //     "two of second, one of third: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+            k2_take_two.for_each(|([e1, e2], w1)| {
+                k3_take_one.clone().for_each(|([e3], _)| {
+                    generated.push(((e1, e2, e3), w1));
                 });
             });
 
-            k2_take_one.for_each(|(e1, _)| {
-                k3_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
-                    //     "one of second, two of third: {}{}{}",
-                    //     layout.get_layerkey(&e1).symbol,
-                    //     layout.get_layerkey(&e2).symbol,
-                    //     layout.get_layerkey(&e3).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight((e1, e2, e3), w1);
+            k2_take_one.for_each(|([e1], _)| {
+                k3_take_two.clone().for_each(|([e2, e3], w1)| {
+                    generated.push(((e1, e2, e3), w1));
                 });
             });
 
-            TakeThreeLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of first:              {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
+            TakeLayerKeys::<3>::new(key1, mods1, w, factor1)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord1, chord_factor)
+                .for_each(|([e1, e2, e3], w)| {
+                    generated.push(((e1, e2, e3), w));
                 });
 
-            TakeThreeLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of second:             {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
+            TakeLayerKeys::<3>::new(key2, mods2, w, factor2)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord2, chord_factor)
+                .for_each(|([e1, e2, e3], w)| {
+                    generated.push(((e1, e2, e3), w));
                 });
 
-            TakeThreeLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor)
-                .for_each(|(e, w)| {
-                    // log::trace!(
-                    //     "three of third:              {}{}{}",
-                    //     layout.get_layerkey(&e.0).symbol,
-                    //     layout.get_layerkey(&e.1).symbol,
-                    //     layout.get_layerkey(&e.2).symbol,
-                    // );
-                    trigram_w_map.insert_or_add_weight(e, w);
+            TakeLayerKeys::<3>::new(key3, mods3, w, factor3)
+                .with_ordering(self.split_modifiers.hold_mod_ordering)
+                .with_chord(chord3, chord_factor)
+                .for_each(|([e1, e2, e3], w)| {
+                    generated.push(((e1, e2, e3), w));
                 });
+
+            if let Some(max) = self.split_modifiers.max_generated_per_trigram {
+                if cap_generated(&mut generated, max) {
+                    capped += 1;
+                }
+            }
+
+            generated.iter().for_each(|(trigram, w)| {
+                trigram_w_map.insert_or_add_weight(*trigram, *w);
+            });
         });
 
-        trigram_w_map
+        (trigram_w_map, capped)
     }
 
     fn process_one_shot_modifiers(
         &self,
         trigrams: TrigramIndicesVec,
         layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
     ) -> TrigramIndicesVec {
         let mut processed_trigrams = Vec::with_capacity(trigrams.len());
 
         trigrams.into_iter().for_each(|((k1, k2, k3), w)| {
-            let (base1, mods1) = layout.resolve_modifiers(&k1);
-            let (base2, mods2) = layout.resolve_modifiers(&k2);
-            let (base3, mods3) = layout.resolve_modifiers(&k3);
-
-            let mut keys = Vec::new();
-
-            if let LayerModifiers::OneShot(mods) = mods1 {
-                keys.extend(mods);
-                keys.push(base1);
-            } else {
-                keys.push(k1);
-            };
-
-            if let LayerModifiers::OneShot(mods) = mods2 {
-                keys.extend(mods);
-                keys.push(base2);
-            } else {
-                keys.push(k2);
-            };
-
-            if let LayerModifiers::OneShot(mods) = mods3 {
-                keys.extend(mods);
-                keys.push(base3);
-            } else {
-                keys.push(k3);
-            };
-
-            keys.iter()
-                .zip(keys.iter().skip(1))
-                .zip(keys.iter().skip(2))
-                .for_each(|((lk1, lk2), lk3)| {
-                    processed_trigrams.push(((*lk1, *lk2, *lk3), w));
+            let ngram = [k1, k2, k3];
+            resolved
+                .sentence_starts(layout, &ngram, w)
+                .for_each(|(sentence_start, w)| {
+                    let symbols = [
+                        resolved.one_shot_in(layout, &ngram, 0, sentence_start),
+                        resolved.one_shot_in(layout, &ngram, 1, sentence_start),
+                        resolved.one_shot_in(layout, &ngram, 2, sentence_start),
+                    ];
+                    symbols
+                        .iter()
+                        .for_each(|(_, mods)| add_modifier_presses(modifier_presses, mods, w));
+
+                    let factor = self.split_modifiers.one_shot_weight_factor;
+                    self.split_modifiers
+                        .one_shot_ordering
+                        .key_sequences(&symbols)
+                        .iter()
+                        .for_each(|sequence| {
+                            processed_trigrams.extend(
+                                sequence
+                                    .ngrams(w, factor)
+                                    .map(|([lk1, lk2, lk3], w)| ((lk1, lk2, lk3), w)),
+                            );
+                        });
                 });
         });
 
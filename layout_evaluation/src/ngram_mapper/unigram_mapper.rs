@@ -5,30 +5,33 @@ use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
 
 use crate::ngrams::Unigrams;
 
-use ahash::AHashMap;
-use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
+use keyboard_layout::layout::{LayerKey, LayerKeyIndex, Layout};
 
 // Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
 // ones are used because they are smaller than a reference (u16 vs usize) and yield better
 // hashing performance.
-type UnigramIndices = AHashMap<LayerKeyIndex, f64>;
+pub type UnigramIndices = IndexMap<LayerKeyIndex>;
 type UnigramIndicesVec = Vec<(LayerKeyIndex, f64)>;
 
 /// Turns the [`Unigrams`]'s characters into their indices, returning a [`UnigramIndicesVec`].
-fn map_unigrams(unigrams: &Unigrams, layout: &Layout) -> (UnigramIndicesVec, f64) {
+fn map_unigrams(
+    unigrams: &Unigrams,
+    layout: &Layout,
+    sorted: bool,
+) -> (UnigramIndicesVec, f64, MissingSymbols) {
     let mut not_found_weight = 0.0;
+    let mut missing_symbols = MissingSymbols::default();
     let mut unigrams_vec = Vec::with_capacity(unigrams.grams.len());
 
     unigrams_vec.extend(
-        unigrams
-            .grams
-            .iter()
+        entries(&unigrams.grams, sorted)
             //.filter(|(c, _weight)| !c.is_whitespace())
             .filter_map(|(c, weight)| {
                 let layerkeyidx = match layout.get_layerkey_index_for_symbol(c) {
                     Some(idx) => idx,
                     None => {
                         not_found_weight += *weight;
+                        missing_symbols.insert_or_add_weight(*c, *weight);
                         return None;
                     }
                 };
@@ -37,7 +40,7 @@ fn map_unigrams(unigrams: &Unigrams, layout: &Layout) -> (UnigramIndicesVec, f64
             }),
     );
 
-    (unigrams_vec, not_found_weight)
+    (unigrams_vec, not_found_weight, missing_symbols)
 }
 
 /// Generates [`LayerKey`]-based unigrams from char-based unigrams. Optionally resolves modifiers
@@ -45,37 +48,130 @@ fn map_unigrams(unigrams: &Unigrams, layout: &Layout) -> (UnigramIndicesVec, f64
 #[derive(Clone, Debug)]
 pub struct OnDemandUnigramMapper {
     split_modifiers: SplitModifiersConfig,
+    shift_policy: ShiftPolicy,
+    duplicate_symbols: DuplicateSymbolPolicy,
+    /// Shares of the shifted letters starting a sentence (see [`ShiftPolicy::SentenceStart`])
+    sentence_starts: SentenceStarts,
+    /// Visit the ngrams in the order of their symbols (see [`entries`])
+    deterministic_order: bool,
 }
 
 impl OnDemandUnigramMapper {
-    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
-        Self { split_modifiers }
+    pub fn new(
+        split_modifiers: SplitModifiersConfig,
+        shift_policy: ShiftPolicy,
+        deterministic_order: bool,
+    ) -> Self {
+        Self {
+            split_modifiers,
+            shift_policy,
+            duplicate_symbols: DuplicateSymbolPolicy::default(),
+            sentence_starts: SentenceStarts::default(),
+            deterministic_order,
+        }
+    }
+
+    /// Attribute the weight of symbols that the layout generates with multiple keys according to
+    /// the policy (see [`resolve_duplicate_symbols`]).
+    pub fn with_duplicate_symbols(mut self, policy: DuplicateSymbolPolicy) -> Self {
+        self.duplicate_symbols = policy;
+        self
+    }
+
+    /// Split the weight of ngrams lacking the context of a shifted letter by its share of
+    /// occurrences at the start of a sentence (see [`ShiftPolicy::SentenceStart`]).
+    pub fn with_sentence_starts(mut self, sentence_starts: SentenceStarts) -> Self {
+        self.sentence_starts = sentence_starts;
+        self
     }
 
     /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
-    pub fn layerkey_indices(&self, unigrams: &Unigrams, layout: &Layout) -> (UnigramIndices, f64) {
-        let (mut unigram_keys_vec, not_found_weight) = map_unigrams(unigrams, layout);
+    ///
+    /// Additionally returns the weight of the ngrams that can not be generated by the layout (in
+    /// total and per symbol) and the number of presses of each modifier that was split off.
+    pub fn layerkey_indices(
+        &self,
+        unigrams: &Unigrams,
+        layout: &Layout,
+    ) -> (UnigramIndices, f64, MissingSymbols, ModifierPresses) {
+        let (mut unigram_keys_vec, not_found_weight, missing_symbols) =
+            map_unigrams(unigrams, layout, self.deterministic_order);
+        let mut modifier_presses = ModifierPresses::default();
+        let resolved = ResolvedModifiers::new(self.shift_policy, layout)
+            .with_layer_overrides(&self.split_modifiers, layout)
+            .with_sentence_starts(&self.sentence_starts, layout);
+
+        if self.duplicate_symbols != DuplicateSymbolPolicy::CheapestOnly
+            && layout.has_duplicate_symbols()
+        {
+            unigram_keys_vec = unigram_keys_vec
+                .into_iter()
+                .flat_map(|(k, w)| {
+                    resolve_duplicate_symbols([k], w, layout, self.duplicate_symbols)
+                })
+                .map(|([k], w)| (k, w))
+                .collect();
+        }
 
-        if layout.has_one_shot_layers() {
-            unigram_keys_vec = self.process_one_shot_modifiers(unigram_keys_vec, layout);
+        if layout.has_multi_key_symbols() {
+            unigram_keys_vec = unigram_keys_vec
+                .into_iter()
+                .flat_map(|(k, w)| resolve_multi_key_symbols([k], w, layout))
+                .map(|([k], w)| (k, w))
+                .collect();
         }
 
-        let unigram_keys = if self.split_modifiers.enabled && layout.has_hold_layers() {
-            Self::process_hold_modifiers(unigram_keys_vec, layout)
+        if layout.has_lock_layers() {
+            let lock_toggles = self.split_modifiers.lock_toggles;
+            unigram_keys_vec = unigram_keys_vec
+                .into_iter()
+                .flat_map(|(k, w)| {
+                    resolve_lock_layers(
+                        [k],
+                        w,
+                        layout,
+                        &resolved,
+                        lock_toggles,
+                        &mut modifier_presses,
+                    )
+                })
+                .map(|([k], w)| (k, w))
+                .collect();
+        }
+
+        if self.shift_policy.has_one_shot_layers(layout) {
+            unigram_keys_vec = self.process_one_shot_modifiers(
+                unigram_keys_vec,
+                layout,
+                &resolved,
+                &mut modifier_presses,
+            );
+        }
+
+        let unigram_keys = if self.split_modifiers.splits_any_layer()
+            && self.shift_policy.has_hold_layers(layout)
+        {
+            self.process_hold_modifiers(unigram_keys_vec, layout, &resolved, &mut modifier_presses)
         } else {
             unigram_keys_vec.into_iter().collect()
         };
 
-        (unigram_keys, not_found_weight)
+        (
+            unigram_keys,
+            not_found_weight,
+            missing_symbols,
+            modifier_presses,
+        )
     }
 
-    /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`]
+    /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`]. If `sorted`, the unigrams are ordered
+    /// by their indices.
     pub fn get_layerkeys<'s>(
         unigrams: &UnigramIndices,
         layout: &'s Layout,
+        sorted: bool,
     ) -> Vec<(&'s LayerKey, f64)> {
-        unigrams
-            .iter()
+        entries(unigrams, sorted)
             .map(|(k1, w)| (layout.get_layerkey(k1), *w))
             .collect()
     }
@@ -84,20 +180,23 @@ impl OnDemandUnigramMapper {
     /// with modifiers for those with higer-layer keys.
     ///
     /// Each unigram of a higher-layer symbol will transform into a unigram with the base-layer key and one
-    /// for each modifier involved in accessing the higher layer.
-    fn process_hold_modifiers(unigrams: UnigramIndicesVec, layout: &Layout) -> UnigramIndices {
-        let mut idx_w_map = AHashMap::with_capacity(unigrams.len() / 3);
+    /// for each modifier (or key of the chord) involved in accessing the higher layer.
+    fn process_hold_modifiers(
+        &self,
+        unigrams: UnigramIndicesVec,
+        layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
+    ) -> UnigramIndices {
+        let mut idx_w_map = IndexMap::with_capacity_and_hasher(unigrams.len() / 3, FixedState);
         unigrams.into_iter().for_each(|(k, w)| {
-            let (base, mods) = layout.resolve_modifiers(&k);
-
-            let (key, mods) = match mods {
-                LayerModifiers::Hold(mods) => (base, mods),
-                _ => (k, Vec::new()),
-            };
+            let (key, mods, _) = resolved.hold(layout, &k, &[]);
+            add_modifier_presses(modifier_presses, mods, w);
 
             // Make sure we don't have any duplicate unigrams by adding them up.
-            TakeOneLayerKey::new(key, &mods, w)
-                .for_each(|(idx, w)| idx_w_map.insert_or_add_weight(idx, w));
+            // (the same-key modifier factor is irrelevant for unigrams)
+            TakeLayerKeys::<1>::new(key, mods, w, 1.0)
+                .for_each(|([idx], w)| idx_w_map.insert_or_add_weight(idx, w));
 
             // if base.symbol == ' ' {
             // println!(
@@ -121,17 +220,29 @@ impl OnDemandUnigramMapper {
         &self,
         unigrams: UnigramIndicesVec,
         layout: &Layout,
+        resolved: &ResolvedModifiers,
+        modifier_presses: &mut ModifierPresses,
     ) -> UnigramIndicesVec {
         let mut processed_unigrams = Vec::with_capacity(unigrams.len());
 
         unigrams.into_iter().for_each(|(k, w)| {
-            let (base, mods) = layout.resolve_modifiers(&k);
-            if let LayerModifiers::OneShot(mods) = mods {
-                processed_unigrams.extend(mods.iter().map(|m| (*m, w)));
-                processed_unigrams.push((base, w));
-            } else {
-                processed_unigrams.push((k, w));
-            }
+            resolved
+                .sentence_starts(layout, &[k], w)
+                .for_each(|(sentence_start, w)| {
+                    let symbol = resolved.one_shot_in(layout, &[k], 0, sentence_start);
+                    add_modifier_presses(modifier_presses, symbol.1, w);
+
+                    // the ordering does not change the unigrams, but their weights are split alike
+                    let factor = self.split_modifiers.one_shot_weight_factor;
+                    self.split_modifiers
+                        .one_shot_ordering
+                        .key_sequences(&[symbol])
+                        .iter()
+                        .for_each(|sequence| {
+                            processed_unigrams
+                                .extend(sequence.ngrams(w, factor).map(|([k], w)| (k, w)));
+                        });
+                });
         });
 
         processed_unigrams
@@ -0,0 +1,251 @@
+//! The `ngram_stats` module describes how the weight of ngrams is distributed among them: the
+//! total weight, the number of distinct ngrams, their entropy, the most frequent ngrams, and how
+//! many ngrams are needed to cover given shares of the total weight.
+//!
+//! This helps to choose sensible thresholds for increasing the weight of common ngrams (see
+//! [`IncreaseCommonNgramsConfig`](crate::ngrams::IncreaseCommonNgramsConfig)) and for pruning
+//! rare ones (see [`PruneNgramsConfig`](crate::ngrams::PruneNgramsConfig)).
+
+use crate::ngrams::{process_special_characters_inverse, Bigrams, Quadgrams, Trigrams, Unigrams};
+
+use ahash::AHashMap;
+use serde::Serialize;
+use std::fmt;
+
+/// Shares of the total weight for which the covering ngrams are reported by default
+pub const DEFAULT_COVERAGE_LEVELS: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// A frequent ngram.
+#[derive(Clone, Debug, Serialize)]
+pub struct TopNgram {
+    /// Symbols of the ngram (line breaks and backslashes escaped as in the ngram files)
+    pub ngram: String,
+    pub weight: f64,
+    /// Share of the total weight
+    pub fraction: f64,
+}
+
+/// The most frequent ngrams needed to cover a share of the total weight.
+#[derive(Clone, Debug, Serialize)]
+pub struct Coverage {
+    /// Share of the total weight to be covered
+    pub level: f64,
+    /// Number of the most frequent ngrams needed
+    pub ngrams: usize,
+    /// Share of the number of distinct ngrams
+    pub ngrams_fraction: f64,
+    /// Share of the total weight of the least frequent of these ngrams. Pruning ngrams with a
+    /// `minimum_fraction` up to this value keeps the covered weight.
+    pub min_fraction: f64,
+}
+
+/// Statistics of the weight distribution of ngrams of one order.
+#[derive(Clone, Debug, Serialize)]
+pub struct NgramStatistics {
+    pub total_weight: f64,
+    /// Number of distinct ngrams
+    pub distinct: usize,
+    /// Shannon entropy of the ngrams' relative frequencies (in bits)
+    pub entropy: f64,
+    /// The most frequent ngrams (sorted by descending weight)
+    pub top: Vec<TopNgram>,
+    /// Number of ngrams covering each of the requested shares of the total weight
+    pub coverage: Vec<Coverage>,
+}
+
+impl NgramStatistics {
+    /// Compute the statistics of ngrams with the `n_top` most frequent ngrams and the coverage
+    /// of the given shares of the total weight (in `[0, 1]`).
+    pub fn new<K: Ord>(
+        grams: &AHashMap<K, f64>,
+        symbols: impl Fn(&K) -> String,
+        n_top: usize,
+        levels: &[f64],
+    ) -> Self {
+        // ties are sorted by symbols, so that the result is deterministic
+        let mut sorted: Vec<(&K, f64)> = grams.iter().map(|(k, w)| (k, *w)).collect();
+        sorted.sort_by(|(k1, w1), (k2, w2)| w2.partial_cmp(w1).unwrap().then(k1.cmp(k2)));
+
+        let total_weight: f64 = sorted.iter().map(|(_, w)| w).sum();
+        let fraction = |w: f64| {
+            if total_weight > 0.0 {
+                w / total_weight
+            } else {
+                0.0
+            }
+        };
+
+        let entropy = -sorted
+            .iter()
+            .map(|(_, w)| fraction(*w))
+            .filter(|p| *p > 0.0)
+            .map(|p| p * p.log2())
+            .sum::<f64>();
+
+        let top = sorted
+            .iter()
+            .take(n_top)
+            .map(|(k, w)| TopNgram {
+                ngram: process_special_characters_inverse(&symbols(k)),
+                weight: *w,
+                fraction: fraction(*w),
+            })
+            .collect();
+
+        // cumulative weights of the most frequent ngrams
+        let mut cumulative = Vec::with_capacity(sorted.len());
+        sorted.iter().fold(0.0, |acc, (_, w)| {
+            cumulative.push(acc + w);
+            acc + w
+        });
+        let coverage = levels
+            .iter()
+            .map(|level| {
+                let ngrams = if *level <= 0.0 || sorted.is_empty() {
+                    0
+                } else {
+                    // the first ngram whose cumulative weight reaches the level (rounding errors
+                    // may prevent reaching the total weight)
+                    let covering = cumulative
+                        .iter()
+                        .position(|c| *c >= level * total_weight)
+                        .unwrap_or(sorted.len() - 1);
+                    covering + 1
+                };
+
+                Coverage {
+                    level: *level,
+                    ngrams,
+                    ngrams_fraction: if sorted.is_empty() {
+                        0.0
+                    } else {
+                        ngrams as f64 / sorted.len() as f64
+                    },
+                    min_fraction: match ngrams {
+                        0 => 0.0,
+                        n => fraction(sorted[n - 1].1),
+                    },
+                }
+            })
+            .collect();
+
+        Self {
+            total_weight,
+            distinct: sorted.len(),
+            entropy,
+            top,
+            coverage,
+        }
+    }
+}
+
+impl fmt::Display for NgramStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Total weight: {:.2}, distinct: {}, entropy: {:.3} bits",
+            self.total_weight, self.distinct, self.entropy
+        )?;
+
+        if !self.coverage.is_empty() {
+            writeln!(
+                f,
+                "{:>10} {:>12} {:>10} {:>14}",
+                "coverage", "ngrams", "distinct", "min fraction"
+            )?;
+            for c in self.coverage.iter() {
+                writeln!(
+                    f,
+                    "{:>9.2}% {:>12} {:>9.4}% {:>13.6}%",
+                    100.0 * c.level,
+                    c.ngrams,
+                    100.0 * c.ngrams_fraction,
+                    100.0 * c.min_fraction,
+                )?;
+            }
+        }
+
+        if !self.top.is_empty() {
+            writeln!(f, "Most frequent:")?;
+            for t in self.top.iter() {
+                writeln!(
+                    f,
+                    "{:>10} {:>16.2} {:>8.4}%",
+                    format!("'{}'", t.ngram),
+                    t.weight,
+                    100.0 * t.fraction
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of the ngrams of all orders (e.g. of an ngram directory).
+#[derive(Clone, Debug, Serialize)]
+pub struct NgramsStatistics {
+    pub unigrams: NgramStatistics,
+    pub bigrams: NgramStatistics,
+    pub trigrams: NgramStatistics,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quadgrams: Option<NgramStatistics>,
+}
+
+impl NgramsStatistics {
+    /// Compute the statistics of ngrams of all orders (see [`NgramStatistics::new`]).
+    pub fn new(
+        unigrams: &Unigrams,
+        bigrams: &Bigrams,
+        trigrams: &Trigrams,
+        quadgrams: Option<&Quadgrams>,
+        n_top: usize,
+        levels: &[f64],
+    ) -> Self {
+        Self {
+            unigrams: NgramStatistics::new(&unigrams.grams, |c| c.to_string(), n_top, levels),
+            bigrams: NgramStatistics::new(
+                &bigrams.grams,
+                |(c1, c2)| [*c1, *c2].iter().collect(),
+                n_top,
+                levels,
+            ),
+            trigrams: NgramStatistics::new(
+                &trigrams.grams,
+                |(c1, c2, c3)| [*c1, *c2, *c3].iter().collect(),
+                n_top,
+                levels,
+            ),
+            quadgrams: quadgrams.map(|quadgrams| {
+                NgramStatistics::new(
+                    &quadgrams.grams,
+                    |(c1, c2, c3, c4)| [*c1, *c2, *c3, *c4].iter().collect(),
+                    n_top,
+                    levels,
+                )
+            }),
+        }
+    }
+}
+
+impl fmt::Display for NgramsStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let orders = [
+            ("Unigrams", Some(&self.unigrams)),
+            ("Bigrams", Some(&self.bigrams)),
+            ("Trigrams", Some(&self.trigrams)),
+            ("Quadgrams", self.quadgrams.as_ref()),
+        ];
+        for (i, (name, stats)) in orders.iter().enumerate() {
+            if let Some(stats) = stats {
+                if i > 0 {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{}", name)?;
+                write!(f, "{}", stats)?;
+            }
+        }
+
+        Ok(())
+    }
+}
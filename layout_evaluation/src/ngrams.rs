@@ -1,16 +1,31 @@
 //! The `ngrams` module provides structs for reading (and to some extent modifying)
-//! ngram (unigram, bigram, trigram) data that serve as the underlying data for layout
+//! ngram (unigram, bigram, trigram, quadgram) data that serve as the underlying data for layout
 //! evaluations.
 
+use crate::atomic_file::AtomicFile;
+use crate::corpus;
+use crate::graphemes::{self, GraphemeClusters};
+use crate::json_corpus;
+use crate::memory::hashmap_bytes;
+use crate::ngram_cache;
 use crate::ngram_mapper::common::NgramMap;
+use crate::normalization::TextNormalization;
+use crate::results::MetricType;
+use crate::token_filter::TokenFilter;
+
+use keyboard_layout::script;
 
 use ahash::AHashMap;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use serde::Deserialize;
 use std::{
-    fs::{self, create_dir_all, File},
-    io::{BufWriter, Write},
-    path::Path,
+    borrow::Cow,
+    fmt,
+    fs::create_dir_all,
+    hash::Hash,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
 /// Configuration parameters for ngram processing
@@ -18,6 +33,107 @@ use std::{
 pub struct NgramsConfig {
     /// Parameters for the increase in weight of common ngrams (with already high frequency).
     pub increase_common_ngrams: IncreaseCommonNgramsConfig,
+    /// How to treat combining marks (e.g. Hebrew niqqud or Arabic harakat).
+    #[serde(default)]
+    pub combining_marks: CombiningMarks,
+    /// Parameters for dropping rare ngrams after loading them.
+    #[serde(default)]
+    pub prune: PruneNgramsConfig,
+}
+
+/// Treatment of combining marks in the ngram data.
+///
+/// Marks that are removed are taken out of the ngrams instead of being counted as
+/// "not found" by the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum CombiningMarks {
+    /// Remove the optional vowel points and cantillation marks of scripts that are usually
+    /// written without them (Hebrew, Arabic, Syriac, Thaana). Keep all other marks.
+    Auto,
+    /// Keep all combining marks.
+    Keep,
+    /// Remove all combining marks.
+    Remove,
+}
+
+impl Default for CombiningMarks {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl CombiningMarks {
+    /// Whether the given character shall be removed from the ngrams.
+    pub fn removes(&self, c: char) -> bool {
+        match self {
+            Self::Auto => script::is_optional_mark(c),
+            Self::Keep => false,
+            Self::Remove => script::is_combining_mark(c),
+        }
+    }
+
+    /// Remove the corresponding marks from a text.
+    pub fn apply_to_text(&self, text: &str) -> String {
+        text.chars().filter(|c| !self.removes(*c)).collect()
+    }
+
+    /// Remove the corresponding marks from ngrams.
+    ///
+    /// Unigrams and trigrams containing a removed mark are dropped. Bigrams containing a
+    /// removed mark are dropped as well, but trigrams with a removed mark in their middle
+    /// (only) are added to the bigrams consisting of their outer symbols, as these were
+    /// actually typed consecutively. Longer sequences of marks are not reconstructed.
+    pub fn apply(
+        &self,
+        unigrams: &Unigrams,
+        bigrams: &Bigrams,
+        trigrams: &Trigrams,
+    ) -> (Unigrams, Bigrams, Trigrams) {
+        if *self == Self::Keep {
+            return (unigrams.clone(), bigrams.clone(), trigrams.clone());
+        }
+
+        let unigrams = Unigrams {
+            grams: unigrams
+                .grams
+                .iter()
+                .filter(|(c, _)| !self.removes(**c))
+                .map(|(c, w)| (*c, *w))
+                .collect(),
+        };
+
+        let mut bigram_grams: AHashMap<(char, char), f64> = bigrams
+            .grams
+            .iter()
+            .filter(|((c1, c2), _)| !self.removes(*c1) && !self.removes(*c2))
+            .map(|(c, w)| (*c, *w))
+            .collect();
+        trigrams
+            .grams
+            .iter()
+            .filter(|((c1, c2, c3), _)| {
+                self.removes(*c2) && !self.removes(*c1) && !self.removes(*c3)
+            })
+            .for_each(|((c1, _, c3), w)| {
+                bigram_grams.insert_or_add_weight((*c1, *c3), *w);
+            });
+        let bigrams = Bigrams {
+            grams: bigram_grams,
+        };
+
+        let trigrams = Trigrams {
+            grams: trigrams
+                .grams
+                .iter()
+                .filter(|((c1, c2, c3), _)| {
+                    !self.removes(*c1) && !self.removes(*c2) && !self.removes(*c3)
+                })
+                .map(|(c, w)| (*c, *w))
+                .collect(),
+        };
+
+        (unigrams, bigrams, trigrams)
+    }
 }
 
 /// Configuration parameters for process of increasing the weight of common ngrams.
@@ -56,13 +172,116 @@ pub fn increase_common_ngrams<T>(
     }
 
     let total_weight: f64 = symbol_weights.values().sum();
+    increase_common_weights(symbol_weights.values_mut(), total_weight, config);
+}
+
+/// Increase the weights exceeding the critical fraction of the total weight (see
+/// [`IncreaseCommonNgramsConfig`]). Returns the added weight.
+pub fn increase_common_weights<'a, I: Iterator<Item = &'a mut f64>>(
+    weights: I,
+    total_weight: f64,
+    config: &IncreaseCommonNgramsConfig,
+) -> f64 {
     let critical_point = config.critical_fraction * total_weight;
 
-    symbol_weights.values_mut().for_each(|weight| {
+    let mut added_weight = 0.0;
+    weights.for_each(|weight| {
         if *weight > critical_point && total_weight > config.total_weight_threshold {
-            *weight += (*weight - critical_point) * (config.factor - 1.0);
+            let increment = (*weight - critical_point) * (config.factor - 1.0);
+            *weight += increment;
+            added_weight += increment;
         }
     });
+
+    added_weight
+}
+
+/// Configuration parameters for dropping the long tail of rare ngrams.
+///
+/// An ngram is dropped if its weight is below any of the thresholds. The dropped weight is
+/// not redistributed; it is reported instead (see [`PrunedNgrams`]).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PruneNgramsConfig {
+    /// Minimum weight of an ngram relative to the total weight of all ngrams of its order.
+    #[serde(default)]
+    pub minimum_fraction: f64,
+    /// Minimum absolute weight of an ngram.
+    #[serde(default)]
+    pub minimum_weight: f64,
+}
+
+impl PruneNgramsConfig {
+    /// Whether any ngrams can be dropped.
+    pub fn enabled(&self) -> bool {
+        self.minimum_fraction > 0.0 || self.minimum_weight > 0.0
+    }
+}
+
+/// The ngrams dropped by pruning.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PrunedNgrams {
+    /// Number of dropped ngrams.
+    pub count: usize,
+    /// Combined weight of the dropped ngrams.
+    pub weight: f64,
+    /// Total weight of all ngrams before pruning.
+    pub total_weight: f64,
+}
+
+impl PrunedNgrams {
+    /// The dropped weight relative to the total weight before pruning.
+    pub fn fraction(&self) -> f64 {
+        if self.total_weight > 0.0 {
+            self.weight / self.total_weight
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Drop the ngrams below the configured thresholds and return what has been dropped.
+pub fn prune_ngrams<T>(
+    symbol_weights: &mut AHashMap<T, f64>,
+    config: &PruneNgramsConfig,
+) -> PrunedNgrams {
+    let total_weight: f64 = symbol_weights.values().sum();
+    let threshold = config
+        .minimum_weight
+        .max(config.minimum_fraction * total_weight);
+
+    let mut pruned = PrunedNgrams {
+        total_weight,
+        ..Default::default()
+    };
+    symbol_weights.retain(|_, weight| {
+        let keep = *weight >= threshold;
+        if !keep {
+            pruned.count += 1;
+            pruned.weight += *weight;
+        }
+
+        keep
+    });
+
+    pruned
+}
+
+/// Combine the ngrams of a primary source (e.g. a personal typing log) with those of a fallback
+/// source (e.g. a generic corpus): ngrams contained in the primary source keep their primary
+/// weight, the others get their fallback weight multiplied by `fallback_scale`.
+pub fn ngrams_with_fallback<T: Clone + Eq + Hash>(
+    primary: &AHashMap<T, f64>,
+    fallback: &AHashMap<T, f64>,
+    fallback_scale: f64,
+) -> AHashMap<T, f64> {
+    let mut grams = primary.clone();
+    fallback.iter().for_each(|(gram, w)| {
+        if !primary.contains_key(gram) {
+            grams.insert(gram.clone(), fallback_scale * w);
+        }
+    });
+
+    grams
 }
 
 /// Holds a hashmap of unigrams (single chars) with corresponding frequency (here often called "weight").
@@ -71,26 +290,378 @@ pub struct Unigrams {
     pub grams: AHashMap<char, f64>,
 }
 
+/// Word-initial symbols derived from the bigrams starting with whitespace (see
+/// [`Bigrams::word_starts`]).
+#[derive(Clone, Debug)]
+pub struct WordStarts {
+    /// Weights of the symbols starting a word
+    pub word_initial: Unigrams,
+    /// Fraction of the bigram weight that starts with whitespace (e.g. close to zero for code)
+    pub fraction: f64,
+}
+
+/// Whether a character belongs to one of the private use areas of Unicode. These characters are
+/// used for key actions (e.g. backspace or arrow keys) and escaped in ngram files.
+fn is_private_use(c: char) -> bool {
+    ('\u{e000}'..='\u{f8ff}').contains(&c) || c >= '\u{f0000}'
+}
+
+/// Parses an escaped character of the form `u{<hex>}` at the start of the given string. Returns
+/// the character and the remaining string.
+fn unicode_escape(s: &str) -> Option<(char, &str)> {
+    let s = s.strip_prefix("u{")?;
+    let end = s.find('}')?;
+    let c = u32::from_str_radix(&s[..end], 16)
+        .ok()
+        .and_then(char::from_u32)?;
+
+    Some((c, &s[end + 1..]))
+}
+
+/// Resolves the escape sequences of ngram files: `\n`, `\\`, and `\u{<hex>}`.
 fn process_special_characters(s: &str) -> String {
-    s.replace("\\n", "\n").replace("\\\\", "\\")
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('\\') {
+        result.push_str(&rest[..i]);
+        let escaped = &rest[i + 1..];
+        rest = if let Some(r) = escaped.strip_prefix('n') {
+            result.push('\n');
+            r
+        } else if let Some(r) = escaped.strip_prefix('\\') {
+            result.push('\\');
+            r
+        } else if let Some((c, r)) = unicode_escape(escaped) {
+            result.push(c);
+            r
+        } else {
+            result.push('\\');
+            escaped
+        };
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Escapes backslashes, newlines, and characters of the private use areas for ngram files (see
+/// [`process_special_characters`]).
+pub(crate) fn process_special_characters_inverse(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if is_private_use(c) => result.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Characters that separate words if only ngrams within words are collected (see
+/// [`TextOptions::word_internal_only`])
+pub const DEFAULT_WORD_SEPARATORS: &str =
+    " \t\n!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~\u{a0}„“”‚‘’«»‹›–—…¿¡·";
+
+/// Options for collecting ngrams from text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Unicode normalization applied to the text
+    pub normalization: TextNormalization,
+    /// Replace uppercase letters by their lowercase forms, so that the ngrams do not rely on
+    /// the layout's resolution of higher layers for capitalization
+    pub fold_case: bool,
+    /// Only collect ngrams within words, i.e. restart the sliding window of bigrams, trigrams,
+    /// and quadgrams at each word separator. Unigrams (including those of the separators) are
+    /// collected as usual.
+    pub word_internal_only: bool,
+    /// Characters separating words for `word_internal_only` (default:
+    /// [`DEFAULT_WORD_SEPARATORS`])
+    pub word_separators: Option<String>,
+    /// Treatment of grapheme clusters consisting of multiple characters (e.g. emoji sequences)
+    pub grapheme_clusters: GraphemeClusters,
+}
+
+impl TextOptions {
+    /// Whether ngrams are restarted at the given character.
+    fn separates(&self, c: char) -> bool {
+        self.word_internal_only
+            && self
+                .word_separators
+                .as_deref()
+                .unwrap_or(DEFAULT_WORD_SEPARATORS)
+                .contains(c)
+    }
+}
+
+/// Weights of the ngrams collected from a text that need to be reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextWeights {
+    /// Weight of the ngrams that contain case-folded letters (see [`TextOptions::fold_case`])
+    pub folded: f64,
+    /// Weight of the ngrams that were not collected, because they contain a skipped grapheme
+    /// cluster (see [`GraphemeClusters::Skip`])
+    pub skipped: f64,
 }
 
-fn process_special_characters_inverse(s: &str) -> String {
-    s.replace('\\', "\\\\").replace('\n', "\\n")
+impl std::ops::AddAssign for TextWeights {
+    fn add_assign(&mut self, other: Self) {
+        self.folded += other.folded;
+        self.skipped += other.skipped;
+    }
+}
+
+/// An element of the ngrams collected from a text (see [`text_chars`]).
+#[derive(Debug, Clone, Copy)]
+struct TextChar {
+    c: char,
+    /// Whether the character is a case-folded letter
+    folded: bool,
+    /// Whether the element stands for a skipped grapheme cluster
+    skipped: bool,
+}
+
+/// The parts of a text's characters (see [`text_chars`]) to collect ngrams from: the whole text
+/// or its words (see [`TextOptions::word_internal_only`]).
+fn text_segments<'a>(
+    chars: &'a [TextChar],
+    options: &'a TextOptions,
+) -> impl Iterator<Item = &'a [TextChar]> {
+    chars.split(move |c| options.separates(c.c))
+}
+
+/// The lowercase form of an uppercase letter. Letters whose lowercase form consists of
+/// multiple characters (e.g. "İ") are kept, so that folding never changes the number of ngrams.
+fn fold_char(c: char) -> char {
+    if !c.is_uppercase() {
+        return c;
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+/// The text without carriage returns.
+fn without_cr(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.chars().filter(|c| *c != '\r').collect())
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// The elements of the ngrams collected from a text: its characters or the first characters of
+/// its grapheme clusters (see [`TextOptions::grapheme_clusters`]), optionally case-folded.
+fn text_chars(text: &str, options: &TextOptions) -> Vec<TextChar> {
+    let text_char = |c: char, skipped: bool| {
+        let folded = if options.fold_case { fold_char(c) } else { c };
+        TextChar {
+            c: folded,
+            folded: folded != c,
+            skipped,
+        }
+    };
+
+    let text = without_cr(text);
+    match options.grapheme_clusters {
+        GraphemeClusters::Chars => text.chars().map(|c| text_char(c, false)).collect(),
+        mode => graphemes::clusters(&text)
+            .map(|cluster| {
+                let mut chars = cluster.chars();
+                let first = chars.next().unwrap();
+                let skipped = mode == GraphemeClusters::Skip && chars.next().is_some();
+                text_char(first, skipped)
+            })
+            .collect(),
+    }
+}
+
+/// Collect the ngrams of length `N` from a text (processed according to the options) and pass
+/// them to `add`.
+fn collect_ngrams<const N: usize>(
+    text: &str,
+    options: &TextOptions,
+    mut add: impl FnMut([char; N]),
+) -> TextWeights {
+    let text = options.normalization.apply(text);
+    let chars = text_chars(&text, options);
+
+    let mut weights = TextWeights::default();
+    let collect_window = |w: &[TextChar]| {
+        if w.iter().any(|c| c.skipped) {
+            weights.skipped += 1.0;
+            return;
+        }
+        let mut ngram = [' '; N];
+        ngram.iter_mut().zip(w).for_each(|(e, c)| *e = c.c);
+        add(ngram);
+        if w.iter().any(|c| c.folded) {
+            weights.folded += 1.0;
+        }
+    };
+
+    if N == 1 {
+        // unigrams (including those of word separators) are collected as usual
+        chars.windows(N).for_each(collect_window);
+    } else {
+        text_segments(&chars, options)
+            .flat_map(|segment| segment.windows(N))
+            .for_each(collect_window);
+    }
+
+    weights
+}
+
+/// Joins chunks of a text streamed chunk-wise with the end of the preceding chunk, such that the
+/// ngrams spanning chunk boundaries are collected (exactly once).
+#[derive(Debug, Clone)]
+pub struct ChunkOverlap {
+    /// Number of characters to carry over into the next chunk (the ngram length minus one)
+    len: usize,
+    /// Whether grapheme clusters are carried over instead of characters
+    clusters: bool,
+    carry: String,
+}
+
+impl ChunkOverlap {
+    /// Overlap for collecting ngrams of length `n`.
+    pub fn new(n: usize) -> Self {
+        Self {
+            len: n.saturating_sub(1),
+            clusters: false,
+            carry: String::new(),
+        }
+    }
+
+    /// Overlap for collecting ngrams of length `n` with the given options. If the ngrams'
+    /// elements are grapheme clusters (see [`TextOptions::grapheme_clusters`]), whole clusters
+    /// are carried over.
+    pub fn with_options(n: usize, options: &TextOptions) -> Self {
+        Self {
+            clusters: options.grapheme_clusters != GraphemeClusters::Chars,
+            ..Self::new(n)
+        }
+    }
+
+    /// The chunk prefixed by the last characters of the preceding chunks. As these are fewer
+    /// than the ngram length, each ngram of the joined text contains a character of the chunk.
+    pub fn join(&mut self, chunk: &str) -> String {
+        let mut joined = std::mem::take(&mut self.carry);
+        joined.push_str(chunk);
+
+        if self.clusters {
+            let text = without_cr(&joined);
+            let clusters: Vec<&str> = graphemes::clusters(&text).collect();
+            self.carry = clusters[clusters.len().saturating_sub(self.len)..].concat();
+        } else {
+            let mut carry: Vec<char> = joined
+                .chars()
+                .rev()
+                .filter(|c| *c != '\r')
+                .take(self.len)
+                .collect();
+            carry.reverse();
+            self.carry = carry.into_iter().collect();
+        }
+
+        joined
+    }
 }
 
 impl Unigrams {
     /// Collect unigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
-        let mut grams = AHashMap::default();
+        let mut unigrams = Self {
+            grams: AHashMap::default(),
+        };
+        unigrams.add_text(text);
+
+        Ok(unigrams)
+    }
+
+    /// Collect unigrams from a reader of UTF-8 text. The text is processed chunk-wise, so that
+    /// only the counts need to be held in memory.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let mut overlap = ChunkOverlap::new(1);
+        corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))?;
+
+        Ok(ngrams)
+    }
+
+    /// Collect unigrams from given text after applying the given Unicode normalization to it.
+    pub fn from_text_normalized(text: &str, normalization: TextNormalization) -> Result<Self> {
+        Unigrams::from_text(&normalization.apply(text))
+    }
+
+    /// Collect unigrams from given text after removing the tokens matched by the filter (e.g.
+    /// URLs) from it.
+    pub fn from_text_filtered(text: &str, filter: &TokenFilter) -> Result<Self> {
+        Unigrams::from_text(&filter.apply(text).0)
+    }
+
+    /// Collect unigrams from the files at the given paths (directories are walked recursively).
+    ///
+    /// The files are streamed one after another and their counts are added up. Unigrams do not span
+    /// file boundaries. Files that are not valid UTF-8 are skipped with a warning.
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let files = corpus::text_files(paths, &[])?;
+        corpus::for_each_file(&files, |reader| {
+            let mut overlap = ChunkOverlap::new(1);
+            corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))
+        })?;
+
+        Ok(ngrams)
+    }
+
+    /// Add the unigrams of the given text to the counts.
+    pub fn add_text(&mut self, text: &str) {
         let chars = text.chars().filter(|c| *c != '\r');
         chars
             //.filter(|c| !c.is_whitespace())
             .for_each(|c| {
-                grams.insert_or_add_weight(c, 1.0);
+                self.grams.insert_or_add_weight(c, 1.0);
             });
+    }
 
-        Ok(Self { grams })
+    /// Add the counts of other unigrams (e.g. those of text appended to a corpus) to the counts.
+    pub fn merge(&mut self, other: Self) {
+        other
+            .grams
+            .into_iter()
+            .for_each(|(k, w)| self.grams.insert_or_add_weight(k, w));
+    }
+
+    /// Collect unigrams from given text with the given options. Also returns the weights of the
+    /// unigrams that contain case-folded letters and of those skipped due to grapheme clusters.
+    pub fn from_text_with_options(
+        text: &str,
+        options: &TextOptions,
+    ) -> Result<(Self, TextWeights)> {
+        let mut unigrams = Self {
+            grams: AHashMap::default(),
+        };
+        let weights = unigrams.add_text_with_options(text, options);
+
+        Ok((unigrams, weights))
+    }
+
+    /// Add the unigrams of the given text (processed according to the options) to the counts.
+    /// Returns the weights of the added unigrams that contain case-folded letters and of those
+    /// skipped due to grapheme clusters (see [`TextWeights`]).
+    pub fn add_text_with_options(&mut self, text: &str, options: &TextOptions) -> TextWeights {
+        collect_ngrams(text, options, |[c]| {
+            self.grams.insert_or_add_weight(c, 1.0);
+        })
     }
 
     /// Read unigrams and weights from a string containing lines with unigrams and their weights.
@@ -112,10 +683,36 @@ impl Unigrams {
         Ok(Unigrams { grams })
     }
 
+    /// Read unigrams from the `characters` map of a JSON corpus (see [`crate::json_corpus`]).
+    pub fn from_json(data: &str) -> Result<Self> {
+        let grams = json_corpus::required_grams(data, json_corpus::UNIGRAMS_KEY, |[c]| c)?;
+
+        Ok(Self { grams })
+    }
+
     /// Read unigrams and weights from a file containing lines with unigrams and their weights.
+    /// JSON corpora (see [`crate::json_corpus`]) are detected by their extension or their
+    /// first character.
+    ///
+    /// The parsed unigrams are cached in a binary sidecar file (see [`crate::ngram_cache`]).
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
-        Unigrams::from_frequencies_str(&data)
+        Unigrams::from_file_with_cache(filename, true)
+    }
+
+    /// Read unigrams and weights from a file containing lines with unigrams and their weights,
+    /// optionally using a binary sidecar file as cache (see [`crate::ngram_cache`]).
+    pub fn from_file_with_cache(filename: &str, use_cache: bool) -> Result<Self> {
+        // the sidecar of a JSON corpus would be shared by all ngram orders
+        let use_cache = use_cache && !json_corpus::has_json_extension(filename);
+        let grams = ngram_cache::load_or_parse(filename, use_cache, |data| {
+            if json_corpus::is_json(filename, data) {
+                Ok(Unigrams::from_json(data)?.grams)
+            } else {
+                Ok(Unigrams::from_frequencies_str(data)?.grams)
+            }
+        })?;
+
+        Ok(Self { grams })
     }
 
     /// Total weight of all combined unigrams
@@ -123,6 +720,11 @@ impl Unigrams {
         self.grams.values().sum()
     }
 
+    /// Approximate number of bytes allocated for the unigrams
+    pub fn estimated_bytes(&self) -> usize {
+        hashmap_bytes(&self.grams)
+    }
+
     /// Return a reduced set of the unigrams containing only the most common unigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -159,6 +761,20 @@ impl Unigrams {
         Self { grams }
     }
 
+    /// Frequencies in the format of the ngram files (sorted by weight, descending)
+    pub fn frequencies_str(&self) -> String {
+        let mut grams: Vec<(char, f64)> = self.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+        let mut s = String::new();
+        grams.iter().for_each(|(c, w)| {
+            let processed = process_special_characters_inverse(&c.to_string());
+            s.push_str(&format!("{} {}\n", w, processed));
+        });
+
+        s
+    }
+
     /// Save frequencies to file
     pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
         let p = filename.as_ref();
@@ -170,18 +786,15 @@ impl Unigrams {
             )
         })?;
 
-        let mut grams: Vec<(char, f64)> = self.grams.iter().map(|(c, w)| (*c, *w)).collect();
-        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
-
-        let file = File::create(&filename)
+        let mut buf_writer = AtomicFile::create(p)
             .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
-        let mut buf_writer = BufWriter::new(file);
-        grams.iter().for_each(|(c, w)| {
-            let processed = process_special_characters_inverse(&c.to_string());
-            writeln!(&mut buf_writer, "{} {}", w, processed).unwrap();
-        });
+        buf_writer
+            .write_all(self.frequencies_str().as_bytes())
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))?;
 
-        Ok(())
+        buf_writer
+            .commit()
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))
     }
 
     pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
@@ -189,6 +802,21 @@ impl Unigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Drop the ngrams below the configured thresholds.
+    pub fn prune(&self, params: &PruneNgramsConfig) -> (Self, PrunedNgrams) {
+        let mut grams = self.grams.clone();
+        let pruned = prune_ngrams(&mut grams, params);
+        (Self { grams }, pruned)
+    }
+
+    /// Use the weights of the primary unigrams where present and the fallback weights scaled by
+    /// `fallback_scale` for the other unigrams (see [`ngrams_with_fallback`]).
+    pub fn merge_with_fallback(primary: &Self, fallback: &Self, fallback_scale: f64) -> Self {
+        Self {
+            grams: ngrams_with_fallback(&primary.grams, &fallback.grams, fallback_scale),
+        }
+    }
 }
 
 /// Holds a hashmap of bigrams (two chars) with corresponding frequency (here often called "weight").
@@ -200,17 +828,96 @@ pub struct Bigrams {
 impl Bigrams {
     /// Collect bigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
-        let mut grams = AHashMap::default();
+        let mut bigrams = Self {
+            grams: AHashMap::default(),
+        };
+        bigrams.add_text(text);
+
+        Ok(bigrams)
+    }
+
+    /// Collect bigrams from a reader of UTF-8 text. The text is processed chunk-wise, so that
+    /// only the counts need to be held in memory.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let mut overlap = ChunkOverlap::new(2);
+        corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))?;
+
+        Ok(ngrams)
+    }
+
+    /// Collect bigrams from given text after applying the given Unicode normalization to it.
+    pub fn from_text_normalized(text: &str, normalization: TextNormalization) -> Result<Self> {
+        Bigrams::from_text(&normalization.apply(text))
+    }
+
+    /// Collect bigrams from given text after removing the tokens matched by the filter (e.g.
+    /// URLs) from it.
+    pub fn from_text_filtered(text: &str, filter: &TokenFilter) -> Result<Self> {
+        Bigrams::from_text(&filter.apply(text).0)
+    }
+
+    /// Collect bigrams from the files at the given paths (directories are walked recursively).
+    ///
+    /// The files are streamed one after another and their counts are added up. Bigrams do not span
+    /// file boundaries. Files that are not valid UTF-8 are skipped with a warning.
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let files = corpus::text_files(paths, &[])?;
+        corpus::for_each_file(&files, |reader| {
+            let mut overlap = ChunkOverlap::new(2);
+            corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))
+        })?;
+
+        Ok(ngrams)
+    }
+
+    /// Add the bigrams of the given text to the counts.
+    pub fn add_text(&mut self, text: &str) {
         let chars = text.chars().filter(|c| *c != '\r');
         chars
             .clone()
             .zip(chars.clone().skip(1))
             //.filter(|(c1, c2)| !c1.is_whitespace() && !c2.is_whitespace())
             .for_each(|c| {
-                grams.insert_or_add_weight(c, 1.0);
+                self.grams.insert_or_add_weight(c, 1.0);
             });
+    }
 
-        Ok(Self { grams })
+    /// Add the counts of other bigrams (e.g. those of text appended to a corpus) to the counts.
+    /// Bigrams spanning the boundary of the texts are not counted (as with [`Self::add_text`]).
+    pub fn merge(&mut self, other: Self) {
+        other
+            .grams
+            .into_iter()
+            .for_each(|(k, w)| self.grams.insert_or_add_weight(k, w));
+    }
+
+    /// Collect bigrams from given text with the given options. Also returns the weights of the
+    /// bigrams that contain case-folded letters and of those skipped due to grapheme clusters.
+    pub fn from_text_with_options(
+        text: &str,
+        options: &TextOptions,
+    ) -> Result<(Self, TextWeights)> {
+        let mut bigrams = Self {
+            grams: AHashMap::default(),
+        };
+        let weights = bigrams.add_text_with_options(text, options);
+
+        Ok((bigrams, weights))
+    }
+
+    /// Add the bigrams of the given text (processed according to the options) to the counts.
+    /// Returns the weights of the added bigrams that contain case-folded letters and of those
+    /// skipped due to grapheme clusters (see [`TextWeights`]).
+    pub fn add_text_with_options(&mut self, text: &str, options: &TextOptions) -> TextWeights {
+        collect_ngrams(text, options, |[c1, c2]| {
+            self.grams.insert_or_add_weight((c1, c2), 1.0);
+        })
     }
 
     /// Read bigrams and weights from a string containing lines with bigrams and their weights.
@@ -231,10 +938,37 @@ impl Bigrams {
         Ok(Bigrams { grams })
     }
 
+    /// Read bigrams from the `bigrams` map of a JSON corpus (see [`crate::json_corpus`]).
+    pub fn from_json(data: &str) -> Result<Self> {
+        let grams =
+            json_corpus::required_grams(data, json_corpus::BIGRAMS_KEY, |[c1, c2]| (c1, c2))?;
+
+        Ok(Self { grams })
+    }
+
     /// Read bigrams and weights from a file containing lines with bigrams and their weights.
+    /// JSON corpora (see [`crate::json_corpus`]) are detected by their extension or their
+    /// first character.
+    ///
+    /// The parsed bigrams are cached in a binary sidecar file (see [`crate::ngram_cache`]).
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
-        Bigrams::from_frequencies_str(&data)
+        Bigrams::from_file_with_cache(filename, true)
+    }
+
+    /// Read bigrams and weights from a file containing lines with bigrams and their weights,
+    /// optionally using a binary sidecar file as cache (see [`crate::ngram_cache`]).
+    pub fn from_file_with_cache(filename: &str, use_cache: bool) -> Result<Self> {
+        // the sidecar of a JSON corpus would be shared by all ngram orders
+        let use_cache = use_cache && !json_corpus::has_json_extension(filename);
+        let grams = ngram_cache::load_or_parse(filename, use_cache, |data| {
+            if json_corpus::is_json(filename, data) {
+                Ok(Bigrams::from_json(data)?.grams)
+            } else {
+                Ok(Bigrams::from_frequencies_str(data)?.grams)
+            }
+        })?;
+
+        Ok(Self { grams })
     }
 
     /// Total weight of all combined bigrams
@@ -242,6 +976,11 @@ impl Bigrams {
         self.grams.values().sum()
     }
 
+    /// Approximate number of bytes allocated for the bigrams
+    pub fn estimated_bytes(&self) -> usize {
+        hashmap_bytes(&self.grams)
+    }
+
     /// Return a reduced set of the bigrams containing only the most common bigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -268,6 +1007,37 @@ impl Bigrams {
         Self { grams }
     }
 
+    /// Derive the word-initial symbols along with the fraction of the bigram weight they make up
+    /// (see [`WordStarts`]).
+    pub fn word_starts(&self) -> WordStarts {
+        let word_initial = self.word_initial();
+        let total_weight = self.total_weight();
+        let fraction = if total_weight > 0.0 {
+            word_initial.total_weight() / total_weight
+        } else {
+            0.0
+        };
+
+        WordStarts {
+            word_initial,
+            fraction,
+        }
+    }
+
+    /// Derive the weights of word-initial symbols from bigrams starting with whitespace
+    /// (the second symbol of each such bigram starts a word).
+    pub fn word_initial(&self) -> Unigrams {
+        let mut grams = AHashMap::default();
+        self.grams
+            .iter()
+            .filter(|((c1, c2), _)| c1.is_whitespace() && !c2.is_whitespace())
+            .for_each(|((_, c2), w)| {
+                grams.insert_or_add_weight(*c2, *w);
+            });
+
+        Unigrams { grams }
+    }
+
     // Return a reduced set of bigrams filtering out those containing a given character
     pub fn exclude_char(&self, exclude: &char) -> Self {
         let grams: AHashMap<(char, char), f64> = self
@@ -284,6 +1054,22 @@ impl Bigrams {
         Self { grams }
     }
 
+    /// Frequencies in the format of the ngram files (sorted by weight, descending)
+    pub fn frequencies_str(&self) -> String {
+        let mut grams: Vec<((char, char), f64)> =
+            self.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+        let mut s = String::new();
+        grams.iter().for_each(|((c1, c2), w)| {
+            let processed1 = process_special_characters_inverse(&c1.to_string());
+            let processed2 = process_special_characters_inverse(&c2.to_string());
+            s.push_str(&format!("{} {}{}\n", w, processed1, processed2));
+        });
+
+        s
+    }
+
     /// Save frequencies to file
     pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
         let p = filename.as_ref();
@@ -295,20 +1081,15 @@ impl Bigrams {
             )
         })?;
 
-        let mut grams: Vec<((char, char), f64)> =
-            self.grams.iter().map(|(c, w)| (*c, *w)).collect();
-        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
-
-        let file = File::create(&filename)
+        let mut buf_writer = AtomicFile::create(p)
             .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
-        let mut buf_writer = BufWriter::new(file);
-        grams.iter().for_each(|((c1, c2), w)| {
-            let processed1 = process_special_characters_inverse(&c1.to_string());
-            let processed2 = process_special_characters_inverse(&c2.to_string());
-            writeln!(&mut buf_writer, "{} {}{}", w, processed1, processed2).unwrap();
-        });
+        buf_writer
+            .write_all(self.frequencies_str().as_bytes())
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))?;
 
-        Ok(())
+        buf_writer
+            .commit()
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))
     }
 
     pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
@@ -316,6 +1097,21 @@ impl Bigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Drop the ngrams below the configured thresholds.
+    pub fn prune(&self, params: &PruneNgramsConfig) -> (Self, PrunedNgrams) {
+        let mut grams = self.grams.clone();
+        let pruned = prune_ngrams(&mut grams, params);
+        (Self { grams }, pruned)
+    }
+
+    /// Use the weights of the primary bigrams where present and the fallback weights scaled by
+    /// `fallback_scale` for the other bigrams (see [`ngrams_with_fallback`]).
+    pub fn merge_with_fallback(primary: &Self, fallback: &Self, fallback_scale: f64) -> Self {
+        Self {
+            grams: ngrams_with_fallback(&primary.grams, &fallback.grams, fallback_scale),
+        }
+    }
 }
 
 /// Holds a hashmap of trigrams (three chars) with corresponding frequency (here often called "weight").
@@ -327,7 +1123,56 @@ pub struct Trigrams {
 impl Trigrams {
     /// Collect trigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
-        let mut grams = AHashMap::default();
+        let mut trigrams = Self {
+            grams: AHashMap::default(),
+        };
+        trigrams.add_text(text);
+
+        Ok(trigrams)
+    }
+
+    /// Collect trigrams from a reader of UTF-8 text. The text is processed chunk-wise, so that
+    /// only the counts need to be held in memory.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let mut overlap = ChunkOverlap::new(3);
+        corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))?;
+
+        Ok(ngrams)
+    }
+
+    /// Collect trigrams from given text after applying the given Unicode normalization to it.
+    pub fn from_text_normalized(text: &str, normalization: TextNormalization) -> Result<Self> {
+        Trigrams::from_text(&normalization.apply(text))
+    }
+
+    /// Collect trigrams from given text after removing the tokens matched by the filter (e.g.
+    /// URLs) from it.
+    pub fn from_text_filtered(text: &str, filter: &TokenFilter) -> Result<Self> {
+        Trigrams::from_text(&filter.apply(text).0)
+    }
+
+    /// Collect trigrams from the files at the given paths (directories are walked recursively).
+    ///
+    /// The files are streamed one after another and their counts are added up. Trigrams do not span
+    /// file boundaries. Files that are not valid UTF-8 are skipped with a warning.
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let files = corpus::text_files(paths, &[])?;
+        corpus::for_each_file(&files, |reader| {
+            let mut overlap = ChunkOverlap::new(3);
+            corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))
+        })?;
+
+        Ok(ngrams)
+    }
+
+    /// Add the trigrams of the given text to the counts.
+    pub fn add_text(&mut self, text: &str) {
         let chars = text.chars().filter(|c| *c != '\r');
         chars
             .clone()
@@ -337,10 +1182,40 @@ impl Trigrams {
             //    !c1.is_whitespace() && !c2.is_whitespace() && !c3.is_whitespace()
             //})
             .for_each(|((c1, c2), c3)| {
-                grams.insert_or_add_weight((c1, c2, c3), 1.0);
+                self.grams.insert_or_add_weight((c1, c2, c3), 1.0);
             });
+    }
 
-        Ok(Self { grams })
+    /// Add the counts of other trigrams (e.g. those of text appended to a corpus) to the counts.
+    /// Trigrams spanning the boundary of the texts are not counted (as with [`Self::add_text`]).
+    pub fn merge(&mut self, other: Self) {
+        other
+            .grams
+            .into_iter()
+            .for_each(|(k, w)| self.grams.insert_or_add_weight(k, w));
+    }
+
+    /// Collect trigrams from given text with the given options. Also returns the weights of the
+    /// trigrams that contain case-folded letters and of those skipped due to grapheme clusters.
+    pub fn from_text_with_options(
+        text: &str,
+        options: &TextOptions,
+    ) -> Result<(Self, TextWeights)> {
+        let mut trigrams = Self {
+            grams: AHashMap::default(),
+        };
+        let weights = trigrams.add_text_with_options(text, options);
+
+        Ok((trigrams, weights))
+    }
+
+    /// Add the trigrams of the given text (processed according to the options) to the counts.
+    /// Returns the weights of the added trigrams that contain case-folded letters and of those
+    /// skipped due to grapheme clusters (see [`TextWeights`]).
+    pub fn add_text_with_options(&mut self, text: &str, options: &TextOptions) -> TextWeights {
+        collect_ngrams(text, options, |[c1, c2, c3]| {
+            self.grams.insert_or_add_weight((c1, c2, c3), 1.0);
+        })
     }
 
     /// Read trigrams and weights from a string containing lines with trigrams and their weights.
@@ -361,10 +1236,38 @@ impl Trigrams {
         Ok(Trigrams { grams })
     }
 
+    /// Read trigrams from the `trigrams` map of a JSON corpus (see [`crate::json_corpus`]).
+    pub fn from_json(data: &str) -> Result<Self> {
+        let grams = json_corpus::required_grams(data, json_corpus::TRIGRAMS_KEY, |[c1, c2, c3]| {
+            (c1, c2, c3)
+        })?;
+
+        Ok(Self { grams })
+    }
+
     /// Read trigrams and weights from a file containing lines with trigrams and their weights.
+    /// JSON corpora (see [`crate::json_corpus`]) are detected by their extension or their
+    /// first character.
+    ///
+    /// The parsed trigrams are cached in a binary sidecar file (see [`crate::ngram_cache`]).
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
-        Trigrams::from_frequencies_str(&data)
+        Trigrams::from_file_with_cache(filename, true)
+    }
+
+    /// Read trigrams and weights from a file containing lines with trigrams and their weights,
+    /// optionally using a binary sidecar file as cache (see [`crate::ngram_cache`]).
+    pub fn from_file_with_cache(filename: &str, use_cache: bool) -> Result<Self> {
+        // the sidecar of a JSON corpus would be shared by all ngram orders
+        let use_cache = use_cache && !json_corpus::has_json_extension(filename);
+        let grams = ngram_cache::load_or_parse(filename, use_cache, |data| {
+            if json_corpus::is_json(filename, data) {
+                Ok(Trigrams::from_json(data)?.grams)
+            } else {
+                Ok(Trigrams::from_frequencies_str(data)?.grams)
+            }
+        })?;
+
+        Ok(Self { grams })
     }
 
     /// Total weight of all combined trigrams
@@ -372,6 +1275,11 @@ impl Trigrams {
         self.grams.values().sum()
     }
 
+    /// Approximate number of bytes allocated for the trigrams
+    pub fn estimated_bytes(&self) -> usize {
+        hashmap_bytes(&self.grams)
+    }
+
     /// Return a reduced set of the trigrams containing only the most common trigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -415,6 +1323,26 @@ impl Trigrams {
         Self { grams }
     }
 
+    /// Frequencies in the format of the ngram files (sorted by weight, descending)
+    pub fn frequencies_str(&self) -> String {
+        let mut grams: Vec<((char, char, char), f64)> =
+            self.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+        let mut s = String::new();
+        grams.iter().for_each(|((c1, c2, c3), w)| {
+            let processed1 = process_special_characters_inverse(&c1.to_string());
+            let processed2 = process_special_characters_inverse(&c2.to_string());
+            let processed3 = process_special_characters_inverse(&c3.to_string());
+            s.push_str(&format!(
+                "{} {}{}{}\n",
+                w, processed1, processed2, processed3
+            ));
+        });
+
+        s
+    }
+
     /// Save frequencies to file
     pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
         let p = filename.as_ref();
@@ -426,26 +1354,260 @@ impl Trigrams {
             )
         })?;
 
-        let mut grams: Vec<((char, char, char), f64)> =
+        let mut buf_writer = AtomicFile::create(p)
+            .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
+        buf_writer
+            .write_all(self.frequencies_str().as_bytes())
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))?;
+
+        buf_writer
+            .commit()
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))
+    }
+
+    pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
+        let mut grams = self.grams.clone();
+        increase_common_ngrams(&mut grams, params);
+        Self { grams }
+    }
+
+    /// Drop the ngrams below the configured thresholds.
+    pub fn prune(&self, params: &PruneNgramsConfig) -> (Self, PrunedNgrams) {
+        let mut grams = self.grams.clone();
+        let pruned = prune_ngrams(&mut grams, params);
+        (Self { grams }, pruned)
+    }
+
+    /// Use the weights of the primary trigrams where present and the fallback weights scaled by
+    /// `fallback_scale` for the other trigrams (see [`ngrams_with_fallback`]).
+    pub fn merge_with_fallback(primary: &Self, fallback: &Self, fallback_scale: f64) -> Self {
+        Self {
+            grams: ngrams_with_fallback(&primary.grams, &fallback.grams, fallback_scale),
+        }
+    }
+}
+
+/// Holds a hashmap of quadgrams (four chars) with corresponding frequency (here often called "weight").
+#[derive(Clone, Debug, Default)]
+pub struct Quadgrams {
+    pub grams: AHashMap<(char, char, char, char), f64>,
+}
+
+impl Quadgrams {
+    /// Collect quadgrams from given text.
+    pub fn from_text(text: &str) -> Result<Self> {
+        let mut quadgrams = Self::default();
+        quadgrams.add_text(text);
+
+        Ok(quadgrams)
+    }
+
+    /// Collect quadgrams from a reader of UTF-8 text. The text is processed chunk-wise, so that
+    /// only the counts need to be held in memory.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let mut overlap = ChunkOverlap::new(4);
+        corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))?;
+
+        Ok(ngrams)
+    }
+
+    /// Collect quadgrams from given text after applying the given Unicode normalization to it.
+    pub fn from_text_normalized(text: &str, normalization: TextNormalization) -> Result<Self> {
+        Quadgrams::from_text(&normalization.apply(text))
+    }
+
+    /// Collect quadgrams from given text after removing the tokens matched by the filter (e.g.
+    /// URLs) from it.
+    pub fn from_text_filtered(text: &str, filter: &TokenFilter) -> Result<Self> {
+        Quadgrams::from_text(&filter.apply(text).0)
+    }
+
+    /// Collect quadgrams from the files at the given paths (directories are walked recursively).
+    ///
+    /// The files are streamed one after another and their counts are added up. Quadgrams do not span
+    /// file boundaries. Files that are not valid UTF-8 are skipped with a warning.
+    pub fn from_paths(paths: &[PathBuf]) -> Result<Self> {
+        let mut ngrams = Self {
+            grams: AHashMap::default(),
+        };
+        let files = corpus::text_files(paths, &[])?;
+        corpus::for_each_file(&files, |reader| {
+            let mut overlap = ChunkOverlap::new(4);
+            corpus::for_each_chunk(reader, |chunk| ngrams.add_text(&overlap.join(chunk)))
+        })?;
+
+        Ok(ngrams)
+    }
+
+    /// Add the quadgrams of the given text to the counts.
+    pub fn add_text(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().filter(|c| *c != '\r').collect();
+        chars.windows(4).for_each(|q| {
+            self.grams
+                .insert_or_add_weight((q[0], q[1], q[2], q[3]), 1.0);
+        });
+    }
+
+    /// Add the counts of other quadgrams (e.g. those of text appended to a corpus) to the counts.
+    /// Quadgrams spanning the boundary of the texts are not counted (as with [`Self::add_text`]).
+    pub fn merge(&mut self, other: Self) {
+        other
+            .grams
+            .into_iter()
+            .for_each(|(k, w)| self.grams.insert_or_add_weight(k, w));
+    }
+
+    /// Collect quadgrams from given text with the given options. Also returns the weights of the
+    /// quadgrams that contain case-folded letters and of those skipped due to grapheme clusters.
+    pub fn from_text_with_options(
+        text: &str,
+        options: &TextOptions,
+    ) -> Result<(Self, TextWeights)> {
+        let mut quadgrams = Self {
+            grams: AHashMap::default(),
+        };
+        let weights = quadgrams.add_text_with_options(text, options);
+
+        Ok((quadgrams, weights))
+    }
+
+    /// Add the quadgrams of the given text (processed according to the options) to the counts.
+    /// Returns the weights of the added quadgrams that contain case-folded letters and of those
+    /// skipped due to grapheme clusters (see [`TextWeights`]).
+    pub fn add_text_with_options(&mut self, text: &str, options: &TextOptions) -> TextWeights {
+        collect_ngrams(text, options, |[c1, c2, c3, c4]| {
+            self.grams.insert_or_add_weight((c1, c2, c3, c4), 1.0);
+        })
+    }
+
+    /// Read quadgrams and weights from a string containing lines with quadgrams and their weights.
+    pub fn from_frequencies_str(data: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        for line in data.lines() {
+            let mut parts = line.trim_start().splitn(2, ' ');
+            let weight: f64 = parts.next().unwrap().parse().unwrap();
+            let quadgram = parts.next().unwrap();
+            let quadgram = process_special_characters(quadgram);
+            let c: Vec<char> = quadgram.chars().collect();
+            if c.len() != 4 {
+                log::info!("Len of quadgram {} is unequal four: {:?}", quadgram, c);
+                continue;
+            }
+            grams.insert_or_add_weight((c[0], c[1], c[2], c[3]), weight);
+        }
+
+        Ok(Quadgrams { grams })
+    }
+
+    /// Read quadgrams and weights from a file containing lines with quadgrams and their weights.
+    ///
+    /// The parsed quadgrams are cached in a binary sidecar file (see [`crate::ngram_cache`]).
+    pub fn from_file(filename: &str) -> Result<Self> {
+        Quadgrams::from_file_with_cache(filename, true)
+    }
+
+    /// Read quadgrams and weights from a file containing lines with quadgrams and their weights,
+    /// optionally using a binary sidecar file as cache (see [`crate::ngram_cache`]).
+    pub fn from_file_with_cache(filename: &str, use_cache: bool) -> Result<Self> {
+        let grams = ngram_cache::load_or_parse(filename, use_cache, |data| {
+            Ok(Quadgrams::from_frequencies_str(data)?.grams)
+        })?;
+
+        Ok(Self { grams })
+    }
+
+    /// Total weight of all combined quadgrams
+    pub fn total_weight(&self) -> f64 {
+        self.grams.values().sum()
+    }
+
+    /// Approximate number of bytes allocated for the quadgrams
+    pub fn estimated_bytes(&self) -> usize {
+        hashmap_bytes(&self.grams)
+    }
+
+    /// Return a reduced set of the quadgrams containing only the most common quadgrams up to a
+    /// given combined fraction.
+    pub fn tops(&self, fraction: f64) -> Self {
+        let target_weight = fraction * self.total_weight();
+        let mut total_weight = 0.0;
+        let mut sorted_grams: Vec<((char, char, char, char), f64)> =
+            self.grams.clone().into_iter().collect();
+        sorted_grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+        let grams: AHashMap<(char, char, char, char), f64> = sorted_grams
+            .iter()
+            .take_while(|(_c, w)| {
+                let res = total_weight < target_weight;
+                total_weight += *w;
+
+                res
+            })
+            .cloned()
+            .collect();
+
+        log::info!(
+            "Quadgrams: Reducing from originally {} to the top {} ngrams.",
+            self.grams.len(),
+            grams.len()
+        );
+        Self { grams }
+    }
+
+    // Return a reduced set of quadgrams filtering out those containing a given character
+    pub fn exclude_char(&self, exclude: &char) -> Self {
+        let grams: AHashMap<(char, char, char, char), f64> = self
+            .grams
+            .iter()
+            .filter(|((c1, c2, c3, c4), _)| {
+                *c1 != *exclude && *c2 != *exclude && *c3 != *exclude && *c4 != *exclude
+            })
+            .map(|(q, w)| (*q, *w))
+            .collect();
+        Self { grams }
+    }
+
+    /// Frequencies in the format of the ngram files (sorted by weight, descending)
+    pub fn frequencies_str(&self) -> String {
+        let mut grams: Vec<((char, char, char, char), f64)> =
             self.grams.iter().map(|(c, w)| (*c, *w)).collect();
         grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
 
-        let file = File::create(&filename)
-            .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
-        let mut buf_writer = BufWriter::new(file);
-        grams.iter().for_each(|((c1, c2, c3), w)| {
-            let processed1 = process_special_characters_inverse(&c1.to_string());
-            let processed2 = process_special_characters_inverse(&c2.to_string());
-            let processed3 = process_special_characters_inverse(&c3.to_string());
-            writeln!(
-                &mut buf_writer,
-                "{} {}{}{}",
-                w, processed1, processed2, processed3
-            )
-            .unwrap();
+        let mut s = String::new();
+        grams.iter().for_each(|((c1, c2, c3, c4), w)| {
+            s.push_str(&w.to_string());
+            s.push(' ');
+            [c1, c2, c3, c4].iter().for_each(|c| {
+                s.push_str(&process_special_characters_inverse(&c.to_string()));
+            });
+            s.push('\n');
         });
 
-        Ok(())
+        s
+    }
+
+    /// Save frequencies to file
+    pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
+        let p = filename.as_ref();
+        create_dir_all(p.parent().unwrap()).map_err(|e| {
+            format!(
+                "Unable to create directory '{}': {}",
+                p.to_str().unwrap(),
+                e
+            )
+        })?;
+
+        let mut buf_writer = AtomicFile::create(p)
+            .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
+        buf_writer
+            .write_all(self.frequencies_str().as_bytes())
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))?;
+
+        buf_writer
+            .commit()
+            .map_err(|e| format!("Unable to write file '{}': {}", p.to_str().unwrap(), e))
     }
 
     pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
@@ -453,4 +1615,325 @@ impl Trigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Drop the ngrams below the configured thresholds.
+    pub fn prune(&self, params: &PruneNgramsConfig) -> (Self, PrunedNgrams) {
+        let mut grams = self.grams.clone();
+        let pruned = prune_ngrams(&mut grams, params);
+        (Self { grams }, pruned)
+    }
+
+    /// Use the weights of the primary quadgrams where present and the fallback weights scaled by
+    /// `fallback_scale` for the other quadgrams (see [`ngrams_with_fallback`]).
+    pub fn merge_with_fallback(primary: &Self, fallback: &Self, fallback_scale: f64) -> Self {
+        Self {
+            grams: ngrams_with_fallback(&primary.grams, &fallback.grams, fallback_scale),
+        }
+    }
+}
+
+/// Read the ngram files `1-grams.txt`, `2-grams.txt`, and `3-grams.txt` from a directory.
+/// Instead of a directory, a JSON corpus file can be given (see [`crate::json_corpus`]).
+///
+/// Missing files result in empty ngrams. The types of the metrics that can not be evaluated
+/// without them are returned along with the ngrams (see [`crate::evaluation::Evaluator::skip_metric_types`]).
+/// At least one of the files has to exist.
+pub fn read_ngrams_dir(dir: &str) -> Result<(Unigrams, Bigrams, Trigrams, Vec<MetricType>)> {
+    read_ngrams_dir_with_cache(dir, true)
+}
+
+/// Read the ngram files from a directory (see [`read_ngrams_dir`]), optionally using binary
+/// sidecar files as cache (see [`crate::ngram_cache`]).
+pub fn read_ngrams_dir_with_cache(
+    dir: &str,
+    use_cache: bool,
+) -> Result<(Unigrams, Bigrams, Trigrams, Vec<MetricType>)> {
+    if Path::new(dir).is_file() {
+        return json_corpus::read_corpus(dir);
+    }
+
+    let mut missing = Vec::new();
+    let mut path_if_exists = |filename: &str, metric_type: MetricType| {
+        let p = Path::new(dir).join(filename);
+        if p.exists() {
+            log::info!("Reading ngram file: '{:?}'", p);
+            Some(p.to_str().unwrap().to_string())
+        } else {
+            log::warn!(
+                "Ngram file '{:?}' does not exist, skipping all {:?} metrics",
+                p,
+                metric_type
+            );
+            missing.push(metric_type);
+            None
+        }
+    };
+
+    let unigrams = match path_if_exists("1-grams.txt", MetricType::Unigram) {
+        Some(p) => Unigrams::from_file_with_cache(&p, use_cache)?,
+        None => Unigrams {
+            grams: AHashMap::default(),
+        },
+    };
+    let bigrams = match path_if_exists("2-grams.txt", MetricType::Bigram) {
+        Some(p) => Bigrams::from_file_with_cache(&p, use_cache)?,
+        None => Bigrams {
+            grams: AHashMap::default(),
+        },
+    };
+    let trigrams = match path_if_exists("3-grams.txt", MetricType::Trigram) {
+        Some(p) => Trigrams::from_file_with_cache(&p, use_cache)?,
+        None => Trigrams {
+            grams: AHashMap::default(),
+        },
+    };
+
+    if missing.len() == 3 {
+        bail!("No ngram files found in '{}'", dir);
+    }
+
+    Ok((unigrams, bigrams, trigrams, missing))
+}
+
+/// An ngram directory along with the weight of its ngrams when blending multiple corpora.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedNgramsDir {
+    /// Directory containing the ngram files
+    pub dir: String,
+    /// Share of the corpus in the blend
+    pub weight: f64,
+}
+
+impl FromStr for WeightedNgramsDir {
+    type Err = anyhow::Error;
+
+    /// Parse a directory with an optional weight in the form `dir:weight` (default weight 1.0).
+    fn from_str(s: &str) -> Result<Self> {
+        // the weight is separated by the last colon (directories may contain colons themselves)
+        if let Some((dir, weight)) = s.rsplit_once(':') {
+            if let Ok(weight) = weight.parse::<f64>() {
+                if !weight.is_finite() || weight < 0.0 {
+                    bail!("Invalid weight {} for ngram directory '{}'", weight, dir);
+                }
+                return Ok(Self {
+                    dir: dir.to_string(),
+                    weight,
+                });
+            }
+        }
+
+        Ok(Self {
+            dir: s.to_string(),
+            weight: 1.0,
+        })
+    }
+}
+
+impl fmt::Display for WeightedNgramsDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weight == 1.0 {
+            write!(f, "{}", self.dir)
+        } else {
+            write!(f, "{}:{}", self.dir, self.weight)
+        }
+    }
+}
+
+/// Add the ngrams normalized to a total weight of one and scaled by `weight`.
+///
+/// The first ngrams with a positive total weight determine the absolute level of the result.
+fn add_normalized<T: Clone + Eq + Hash>(
+    res: &mut AHashMap<T, f64>,
+    level: &mut Option<f64>,
+    grams: &AHashMap<T, f64>,
+    weight: f64,
+) {
+    let total: f64 = grams.values().sum();
+    if total <= 0.0 {
+        return;
+    }
+    let level = *level.get_or_insert(total);
+    grams
+        .iter()
+        .for_each(|(gram, w)| res.insert_or_add_weight(gram.clone(), level * weight * w / total));
+}
+
+/// Read the ngram files of multiple directories and blend them according to their weights.
+///
+/// Each corpus is normalized to a total weight of one before it is scaled by its weight, so that
+/// the weights determine the shares of the corpora regardless of their sizes. As some metrics
+/// (e.g. "Irregularity") depend on the absolute ngram weights, the blend is then scaled to the
+/// total weight of the first corpus (as with the `ngram_merge` binary). Corpora with weight zero
+/// are not read. A single corpus is used as is (see [`read_ngrams_dir`]). With `use_cache`, the
+/// ngram files are cached in binary sidecar files (see [`crate::ngram_cache`]).
+///
+/// Metric types are only skipped if the corresponding ngram file is missing in all corpora. If it
+/// is only missing in some of them, the ngrams of that order are blended from the others.
+pub fn read_weighted_ngrams_dirs(
+    dirs: &[WeightedNgramsDir],
+    use_cache: bool,
+) -> Result<(Unigrams, Bigrams, Trigrams, Vec<MetricType>)> {
+    let dirs: Vec<&WeightedNgramsDir> = dirs
+        .iter()
+        .filter(|d| {
+            if d.weight == 0.0 {
+                log::info!("Skipping ngram directory '{}' with weight zero", d.dir);
+            }
+            d.weight > 0.0
+        })
+        .collect();
+
+    match dirs.as_slice() {
+        [] => Err(anyhow!("No ngram directory with a positive weight given")),
+        [dir] => read_ngrams_dir_with_cache(&dir.dir, use_cache),
+        _ => {
+            let mut unigrams = AHashMap::default();
+            let mut bigrams = AHashMap::default();
+            let mut trigrams = AHashMap::default();
+            let mut levels = (None, None, None);
+            let mut missing_per_dir = Vec::new();
+
+            for dir in dirs.iter() {
+                let (u, b, t, missing) = read_ngrams_dir_with_cache(&dir.dir, use_cache)?;
+                add_normalized(&mut unigrams, &mut levels.0, &u.grams, dir.weight);
+                add_normalized(&mut bigrams, &mut levels.1, &b.grams, dir.weight);
+                add_normalized(&mut trigrams, &mut levels.2, &t.grams, dir.weight);
+                missing_per_dir.push(missing);
+            }
+
+            Ok((
+                Unigrams { grams: unigrams },
+                Bigrams { grams: bigrams },
+                Trigrams { grams: trigrams },
+                missing_in_all(&missing_per_dir),
+            ))
+        }
+    }
+}
+
+/// The metric types whose ngrams are missing in all corpora (given the missing ones per corpus).
+fn missing_in_all(missing_per_dir: &[Vec<MetricType>]) -> Vec<MetricType> {
+    let mut missing_counts: Vec<(MetricType, usize)> = Vec::new();
+    for metric_type in missing_per_dir.iter().flatten() {
+        match missing_counts.iter_mut().find(|(m, _)| m == metric_type) {
+            Some((_, n)) => *n += 1,
+            None => missing_counts.push((metric_type.clone(), 1)),
+        }
+    }
+
+    missing_counts
+        .into_iter()
+        .filter_map(|(metric_type, n)| {
+            if n < missing_per_dir.len() {
+                log::warn!(
+                    "{:?} ngrams are missing in {} of {} corpora, blending the others",
+                    metric_type,
+                    n,
+                    missing_per_dir.len()
+                );
+                None
+            } else {
+                Some(metric_type)
+            }
+        })
+        .collect()
+}
+
+/// Ngrams of multiple corpora that are kept separately, so that they can be blended with
+/// different weights without reading the ngram files again (see [`read_weighted_ngrams_dirs`]).
+///
+/// As all corpora are held in memory, this needs more memory than blending them while reading.
+#[derive(Clone, Debug)]
+pub struct TaggedNgrams {
+    /// Directories of the corpora
+    pub dirs: Vec<String>,
+    corpora: Vec<(Unigrams, Bigrams, Trigrams)>,
+    missing: Vec<MetricType>,
+}
+
+impl TaggedNgrams {
+    /// Read the ngram files of all given directories (including those with weight zero, as
+    /// their weight may be changed later on).
+    pub fn read(dirs: &[String], use_cache: bool) -> Result<Self> {
+        if dirs.is_empty() {
+            bail!("No ngram directory given");
+        }
+
+        let mut corpora = Vec::with_capacity(dirs.len());
+        let mut missing_per_dir = Vec::with_capacity(dirs.len());
+        for dir in dirs {
+            let (u, b, t, missing) = read_ngrams_dir_with_cache(dir, use_cache)?;
+            corpora.push((u, b, t));
+            missing_per_dir.push(missing);
+        }
+
+        Ok(Self {
+            dirs: dirs.to_vec(),
+            corpora,
+            missing: missing_in_all(&missing_per_dir),
+        })
+    }
+
+    /// The types of the metrics that need to be skipped, because their ngrams are missing in
+    /// all corpora.
+    pub fn missing_metric_types(&self) -> &[MetricType] {
+        &self.missing
+    }
+
+    /// Blend the corpora with the given weights (one per corpus) in the same way as
+    /// [`read_weighted_ngrams_dirs`].
+    pub fn blend(&self, weights: &[f64]) -> Result<(Unigrams, Bigrams, Trigrams)> {
+        if weights.len() != self.corpora.len() {
+            bail!(
+                "Got {} weights for {} ngram directories",
+                weights.len(),
+                self.corpora.len()
+            );
+        }
+        if let Some(w) = weights.iter().find(|w| !w.is_finite() || **w < 0.0) {
+            bail!("Invalid weight {} for blending ngrams", w);
+        }
+
+        let weighted: Vec<(&(Unigrams, Bigrams, Trigrams), f64)> = self
+            .corpora
+            .iter()
+            .zip(weights.iter())
+            .filter(|(_, w)| **w > 0.0)
+            .map(|(c, w)| (c, *w))
+            .collect();
+
+        match weighted.as_slice() {
+            [] => Err(anyhow!("No ngram directory with a positive weight given")),
+            [(corpus, _)] => Ok((*corpus).clone()),
+            _ => {
+                let mut unigrams = AHashMap::default();
+                let mut bigrams = AHashMap::default();
+                let mut trigrams = AHashMap::default();
+                let mut levels = (None, None, None);
+                for ((u, b, t), weight) in weighted {
+                    add_normalized(&mut unigrams, &mut levels.0, &u.grams, weight);
+                    add_normalized(&mut bigrams, &mut levels.1, &b.grams, weight);
+                    add_normalized(&mut trigrams, &mut levels.2, &t.grams, weight);
+                }
+
+                Ok((
+                    Unigrams { grams: unigrams },
+                    Bigrams { grams: bigrams },
+                    Trigrams { grams: trigrams },
+                ))
+            }
+        }
+    }
+}
+
+/// Read the (optional) quadgram file `4-grams.txt` from a directory. Returns `None` if it does
+/// not exist.
+pub fn read_quadgrams_dir(dir: &str) -> Result<Option<Quadgrams>> {
+    let p = Path::new(dir).join("4-grams.txt");
+    if !p.exists() {
+        return Ok(None);
+    }
+
+    log::info!("Reading ngram file: '{:?}'", p);
+    Ok(Some(Quadgrams::from_file(p.to_str().unwrap())?))
 }
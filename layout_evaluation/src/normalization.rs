@@ -0,0 +1,67 @@
+//! The `normalization` module provides the Unicode normalization of texts before ngrams are
+//! collected from them. With NFC normalization, e.g. the precomposed "ä" and "a" followed by a
+//! combining diaeresis result in the same ngrams.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// Unicode normalization applied to texts before collecting ngrams from them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TextNormalization {
+    /// Use the text as is.
+    None,
+    /// Canonical composition (NFC), i.e. combine characters with combining marks into
+    /// precomposed characters wherever possible.
+    Nfc,
+}
+
+impl Default for TextNormalization {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl TextNormalization {
+    /// Normalize the text.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::None => Cow::Borrowed(text),
+            Self::Nfc => nfc(text),
+        }
+    }
+}
+
+/// Normalize the text to the Unicode normalization form C (canonical composition).
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(text);
+    }
+
+    Cow::Owned(text.nfc().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_decomposed_characters() {
+        assert_eq!(nfc("a\u{0308}"), "ä");
+        assert_eq!(nfc("A\u{030A}"), "Å");
+        // the marks are reordered before composing
+        assert_eq!(nfc("a\u{0308}\u{0323}"), "\u{1EA1}\u{0308}");
+        // Hangul syllables are composed algorithmically
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11A8}"), "\u{AC01}");
+        // composition exclusions and singletons
+        assert_eq!(nfc("\u{0958}"), "\u{0915}\u{093C}");
+        assert_eq!(nfc("\u{212B}"), "Å");
+    }
+
+    #[test]
+    fn keeps_normalized_text() {
+        assert!(matches!(nfc("Hello World"), Cow::Borrowed(_)));
+        assert_eq!(nfc("äöüß Ωμέγα שָׁלוֹם"), "äöüß Ωμέγα שָׁלוֹם");
+        assert_eq!(nfc("\u{0308}a"), "\u{0308}a");
+    }
+}
@@ -1,8 +1,9 @@
 //! The `results` module contains structs representing the results of metric evaluations.
 
+use ahash::AHashMap;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::{fmt, slice};
+use std::{collections::HashMap, fmt, slice};
 
 /// The [`NormalizationType`] specifies how the total cost of a metric evaluation shall be normalized.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -24,6 +25,9 @@ pub enum MetricType {
     Unigram,
     Bigram,
     Trigram,
+    Quadgram,
+    /// Bigrams of the first and third symbol of each trigram
+    Skipgram,
 }
 
 /// Describes the result of an individual metric evaluation.
@@ -59,6 +63,10 @@ pub struct MetricResults {
     pub found_weight: f64,
     /// The total amount of weight (ngram frequencies) from ngrams that contained symbols that coult not be mapped by the layout.
     pub not_found_weight: f64,
+    /// The weight of ngrams that could not be mapped by the layout per symbol (attributed to the
+    /// first symbol of each ngram that was not found).
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub missing_symbols: AHashMap<char, f64>,
     /// A list of the individual metric results.
     pub metric_costs: Vec<NormalizedMetricResult>,
 }
@@ -72,7 +80,11 @@ impl fmt::Display for MetricResults {
             writeln!(
                 f,
                 "  Not found: {:.4}% of {:.4}",
-                100.0 * self.not_found_weight / (self.not_found_weight + self.found_weight),
+                if self.not_found_weight > 0.0 {
+                    100.0 * self.not_found_weight / (self.not_found_weight + self.found_weight)
+                } else {
+                    0.0
+                },
                 self.not_found_weight + self.found_weight
             )?;
         }
@@ -96,10 +108,28 @@ impl MetricResults {
             metric_type,
             found_weight,
             not_found_weight,
+            missing_symbols: AHashMap::default(),
             metric_costs: Vec::new(),
         }
     }
 
+    /// Record the weight of the ngrams that could not be mapped per symbol.
+    pub fn with_missing_symbols(mut self, missing_symbols: AHashMap<char, f64>) -> Self {
+        self.missing_symbols = missing_symbols;
+        self
+    }
+
+    /// The `n` symbols with the highest weight of ngrams that could not be mapped (sorted by
+    /// descending weight).
+    pub fn top_missing_symbols(&self, n: usize) -> Vec<(char, f64)> {
+        let mut symbols: Vec<(char, f64)> =
+            self.missing_symbols.iter().map(|(c, w)| (*c, *w)).collect();
+        symbols.sort_by(|(c1, w1), (c2, w2)| w2.partial_cmp(w1).unwrap().then(c1.cmp(c2)));
+        symbols.truncate(n);
+
+        symbols
+    }
+
     pub fn add_result(&mut self, metric_cost: MetricResult) {
         let weighted_cost = self.compute_metric_cost(&metric_cost, true, true);
         let unweighted_cost = self.compute_metric_cost(&metric_cost, true, false);
@@ -112,20 +142,20 @@ impl MetricResults {
 
     /// Normalize a metric's cost value with given normalization strategy.
     fn normalize_value(&self, val: f64, normalization_type: &NormalizationType) -> f64 {
-        let mut res = match normalization_type {
-            NormalizationType::Fixed(t) => val / t,
-            NormalizationType::WeightFound(t) => val / (t * self.found_weight),
-            NormalizationType::WeightAll(t) => {
-                val / (t * (self.found_weight + self.not_found_weight))
-            }
+        let divisor = match normalization_type {
+            NormalizationType::Fixed(t) => *t,
+            NormalizationType::WeightFound(t) => t * self.found_weight,
+            NormalizationType::WeightAll(t) => t * (self.found_weight + self.not_found_weight),
         };
 
-        // instead of NAN, we prefer having 0.0 cost
-        if res.is_nan() {
-            res = 0.0
+        // without any (found) weight, there is nothing to be charged for; instead of NAN or
+        // infinite costs, we prefer having 0.0 cost
+        let res = val / divisor;
+        if res.is_finite() {
+            res
+        } else {
+            0.0
         }
-
-        res
     }
 
     /// Helper function for weighting and normalizing individual metric's results.
@@ -167,16 +197,66 @@ impl MetricResults {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EvaluationResult {
     layout: String,
+    /// Name of the weight profile used for the evaluation (`None` for the default weights).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    /// Types of metrics that were skipped due to missing ngram data (a reduced metric set).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    skipped_metric_types: Vec<MetricType>,
     individual_results: Vec<MetricResults>,
+    /// Results of the individual keyboards if the layout was evaluated on multiple keyboards
+    /// (see [`EvaluationResult::combined`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keyboard_results: Vec<KeyboardResult>,
+}
+
+/// The results of a layout on one of multiple keyboards it is evaluated on.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyboardResult {
+    /// Name of the keyboard (e.g. its layout config)
+    pub keyboard: String,
+    /// Share of the keyboard in the combined result
+    pub weight: f64,
+    pub result: EvaluationResult,
 }
 
 impl fmt::Display for EvaluationResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.individual_results
-            .iter()
-            .fold(Ok(()), |acc, results| {
-                acc.and_then(|_| writeln!(f, "{}", results))
+        if self.keyboard_results.is_empty() {
+            self.individual_results
+                .iter()
+                .try_for_each(|results| writeln!(f, "{}", results))?;
+        } else {
+            self.keyboard_results.iter().try_for_each(|kr| {
+                writeln!(
+                    f,
+                    "{} {} (weight {:.2})\n",
+                    "Keyboard:".bold(),
+                    kr.keyboard.bold(),
+                    kr.weight
+                )?;
+                writeln!(f, "{}", kr.result)
             })?;
+            writeln!(f, "{}", "Combined result".bold())?;
+        }
+
+        if let Some(profile) = &self.profile {
+            writeln!(f, "Weight profile: {}", profile.bold())?;
+        }
+
+        if !self.skipped_metric_types.is_empty() {
+            let skipped: Vec<String> = self
+                .skipped_metric_types
+                .iter()
+                .map(|t| format!("{:?}", t))
+                .collect();
+            writeln!(
+                f,
+                "{} {} metrics skipped (missing ngram data)",
+                "Reduced metric set:".yellow().bold(),
+                skipped.join(", ")
+            )?;
+        }
 
         writeln!(
             f,
@@ -193,7 +273,148 @@ impl EvaluationResult {
     pub fn new(layout: String, individual_results: Vec<MetricResults>) -> Self {
         Self {
             layout,
+            profile: None,
+            skipped_metric_types: Vec::new(),
+            individual_results,
+            keyboard_results: Vec::new(),
+        }
+    }
+
+    /// Combine the results of a layout on multiple keyboards. The weights of the keyboards are
+    /// normalized to a sum of one, so that the combined cost is the weighted mean of the
+    /// keyboards' costs. The combined metric results hold the (reweighted) metric results of all
+    /// keyboards.
+    pub fn combined(layout: String, mut keyboard_results: Vec<KeyboardResult>) -> Self {
+        let total_weight: f64 = keyboard_results.iter().map(|kr| kr.weight).sum();
+        if total_weight > 0.0 {
+            keyboard_results
+                .iter_mut()
+                .for_each(|kr| kr.weight /= total_weight);
+        }
+
+        let individual_results = keyboard_results
+            .iter()
+            .flat_map(|kr| {
+                let weight = kr.weight;
+                kr.result
+                    .reweighted_with(None, &|mc| weight * mc.weight)
+                    .individual_results
+            })
+            .collect();
+        let mut skipped_metric_types: Vec<MetricType> = Vec::new();
+        keyboard_results
+            .iter()
+            .flat_map(|kr| kr.result.skipped_metric_types.iter())
+            .for_each(|t| {
+                if !skipped_metric_types.contains(t) {
+                    skipped_metric_types.push(t.clone());
+                }
+            });
+
+        Self {
+            layout,
+            profile: None,
+            skipped_metric_types,
+            individual_results,
+            keyboard_results,
+        }
+    }
+
+    /// Results of the individual keyboards (empty if the layout was evaluated on a single
+    /// keyboard only).
+    pub fn keyboard_results(&self) -> &[KeyboardResult] {
+        &self.keyboard_results
+    }
+
+    /// Record the types of metrics that were skipped due to missing ngram data.
+    pub fn with_skipped_metric_types(mut self, skipped_metric_types: Vec<MetricType>) -> Self {
+        self.skipped_metric_types = skipped_metric_types;
+        self
+    }
+
+    /// Types of metrics that were skipped due to missing ngram data. Results with skipped
+    /// metrics are not comparable with those of the full metric set.
+    pub fn skipped_metric_types(&self) -> &[MetricType] {
+        &self.skipped_metric_types
+    }
+
+    /// Record the name of the weight profile used for the evaluation.
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// The weight of the symbols that could not be mapped by the layout, as found in the
+    /// results of the lowest evaluated ngram type (usually unigrams).
+    pub fn missing_symbols(&self) -> Option<&AHashMap<char, f64>> {
+        self.individual_results
+            .iter()
+            .find(|mr| mr.metric_type != MetricType::Layout)
+            .map(|mr| &mr.missing_symbols)
+    }
+
+    /// The evaluated layout.
+    pub fn layout(&self) -> &str {
+        &self.layout
+    }
+
+    /// Name of the weight profile used for the evaluation.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// Generate a copy of the results with new metric weights (as given by the `weight`
+    /// function for each metric result). The metrics' costs are left unchanged.
+    pub fn reweighted<F>(&self, profile: Option<String>, weight: F) -> Self
+    where
+        F: Fn(&MetricResult) -> f64,
+    {
+        if !self.keyboard_results.is_empty() {
+            let keyboard_results = self
+                .keyboard_results
+                .iter()
+                .map(|kr| KeyboardResult {
+                    keyboard: kr.keyboard.clone(),
+                    weight: kr.weight,
+                    result: kr.result.reweighted_with(profile.clone(), &weight),
+                })
+                .collect();
+            return Self::combined(self.layout.clone(), keyboard_results).with_profile(profile);
+        }
+
+        self.reweighted_with(profile, &weight)
+    }
+
+    fn reweighted_with(
+        &self,
+        profile: Option<String>,
+        weight: &dyn Fn(&MetricResult) -> f64,
+    ) -> Self {
+        let individual_results = self
+            .individual_results
+            .iter()
+            .map(|mr| {
+                let mut reweighted = MetricResults::new(
+                    mr.metric_type.clone(),
+                    mr.found_weight,
+                    mr.not_found_weight,
+                )
+                .with_missing_symbols(mr.missing_symbols.clone());
+                mr.metric_costs.iter().for_each(|mc| {
+                    let mut core = mc.core.clone();
+                    core.weight = weight(&mc.core);
+                    reweighted.add_result(core);
+                });
+                reweighted
+            })
+            .collect();
+
+        Self {
+            layout: self.layout.clone(),
+            profile,
+            skipped_metric_types: self.skipped_metric_types.clone(),
             individual_results,
+            keyboard_results: Vec::new(),
         }
     }
 
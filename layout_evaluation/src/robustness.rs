@@ -0,0 +1,589 @@
+//! The `robustness` module provides an analysis of how sensitive a layout's evaluation is
+//! to the exact ngram frequencies of the corpus.
+//!
+//! The ngram weights are bootstrap-resampled multiple times and the layout is evaluated on each
+//! resample. The ngrams are mapped to the layout only once; the resampling operates on the
+//! mapped ngrams. Instead of drawing from a multinomial distribution over all occurrences of
+//! ngrams in the corpus, each ngram's count is replaced by a Poisson-distributed count with the
+//! original count as its mean ("Poisson bootstrap"), the usual large-sample approximation of
+//! multinomial resampling. Frequent ngrams therefore vary less (relative to their weight) than
+//! rare ones, just like when resampling the corpus text itself.
+//!
+//! The weights of the ngrams are not necessarily counts: many corpora are normalized, and the
+//! mapping changes the weights further (e.g. when increasing the weight of common ngrams or
+//! splitting off modifiers). The counts are therefore derived from an explicit effective sample
+//! size, the number of ngram occurrences the corpus is assumed to consist of. The weights of
+//! each ngram type are scaled such that they sum up to this number before drawing the counts,
+//! and scaled back afterwards. The spread of the results thus depends on the sample size only,
+//! not on the scale of the weights.
+//!
+//! The count of an ngram depends only on the seed, the index of the resample, and the ngram's
+//! symbols. Resamples with the same index are therefore identical for different layouts, which
+//! allows for paired comparisons of layouts.
+
+use crate::evaluation::Evaluator;
+use crate::ngram_mapper::{
+    common::MissingSymbols, MappedBigrams, MappedQuadgrams, MappedTrigrams, MappedUnigrams,
+};
+use crate::results::{EvaluationResult, MetricType};
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use ahash::AHashMap;
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt;
+
+/// Means above which Poisson-distributed numbers are generated by transformed rejection instead
+/// of multiplying uniformly distributed numbers
+const POISSON_REJECTION_THRESHOLD: f64 = 10.0;
+
+/// SplitMix64 step, used both for hashing and for generating random numbers.
+///
+/// In contrast to the hashers of the standard library, its output is guaranteed to be stable.
+#[inline(always)]
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Uniformly distributed random numbers in [0, 1) for the ngram consisting of `symbols`.
+fn uniform_numbers(seed: u64, resample: usize, symbols: &[char]) -> impl FnMut() -> f64 {
+    let mut state = seed;
+    let mut mix = |v: u64| {
+        state ^= v;
+        state = splitmix64(&mut state);
+    };
+    mix(resample as u64);
+    symbols.iter().for_each(|c| mix(*c as u64));
+
+    move || (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Natural logarithm of `k!`.
+fn ln_factorial(k: f64) -> f64 {
+    if k < 10.0 {
+        return (2..=k as u64).map(|i| (i as f64).ln()).sum();
+    }
+
+    // Stirling series
+    let k2 = k * k;
+    k * k.ln() - k + 0.5 * (2.0 * std::f64::consts::PI * k).ln() + 1.0 / (12.0 * k)
+        - 1.0 / (360.0 * k2 * k)
+        + 1.0 / (1260.0 * k2 * k2 * k)
+}
+
+/// Draws a Poisson-distributed number with mean `lambda`.
+///
+/// Uses Knuth's algorithm for small means and the transformed rejection method "PTRS"
+/// (W. Hörmann, 1993) for larger ones.
+fn poisson<F: FnMut() -> f64>(lambda: f64, uniform: &mut F) -> f64 {
+    if lambda <= 0.0 {
+        return 0.0;
+    }
+
+    if lambda < POISSON_REJECTION_THRESHOLD {
+        let limit = (-lambda).exp();
+        let mut k = 0;
+        let mut p = uniform();
+        while p > limit {
+            k += 1;
+            p *= uniform();
+        }
+        return k as f64;
+    }
+
+    let slam = lambda.sqrt();
+    let log_lambda = lambda.ln();
+    let b = 0.931 + 2.53 * slam;
+    let a = -0.059 + 0.02483 * b;
+    let inv_alpha = 1.1239 + 1.1328 / (b - 3.4);
+    let v_r = 0.9277 - 3.6224 / (b - 2.0);
+    loop {
+        let u = uniform() - 0.5;
+        let v = uniform();
+        let us = 0.5 - u.abs();
+        let k = ((2.0 * a / us + b) * u + lambda + 0.43).floor();
+        if us >= 0.07 && v <= v_r {
+            return k;
+        }
+        if k < 0.0 || (us < 0.013 && v > us) {
+            continue;
+        }
+        if (v * inv_alpha / (a / (us * us) + b)).ln() <= -lambda + k * log_lambda - ln_factorial(k)
+        {
+            return k;
+        }
+    }
+}
+
+/// Draws the resampled count of the ngram consisting of `symbols`: a Poisson-distributed
+/// number with the original count as its mean.
+fn resampled_count(count: f64, seed: u64, resample: usize, symbols: &[char]) -> f64 {
+    poisson(count, &mut uniform_numbers(seed, resample, symbols))
+}
+
+/// Resample weights of mapped ngrams, treating them as `sample_size` ngram occurrences in total
+/// (including the ones not found). The weight statistics are scaled accordingly.
+fn resample<T: Copy, F>(
+    grams: &[(T, f64)],
+    weight_found: f64,
+    weight_not_found: f64,
+    symbols: F,
+    sample_size: f64,
+    seed: u64,
+    resample: usize,
+) -> (Vec<(T, f64)>, f64, f64)
+where
+    F: Fn(&T) -> Vec<char>,
+{
+    let total_weight = weight_found + weight_not_found;
+    if total_weight <= 0.0 || sample_size <= 0.0 {
+        return (grams.to_vec(), weight_found, weight_not_found);
+    }
+
+    // weights per occurrence
+    let unit = total_weight / sample_size;
+    let resampled: Vec<(T, f64)> = grams
+        .iter()
+        .map(|(g, w)| {
+            let count = resampled_count(*w / unit, seed, resample, &symbols(g));
+            (*g, count * unit)
+        })
+        .collect();
+
+    let original_total: f64 = grams.iter().map(|(_, w)| w).sum();
+    let resampled_total: f64 = resampled.iter().map(|(_, w)| w).sum();
+    let scale = if original_total > 0.0 {
+        resampled_total / original_total
+    } else {
+        1.0
+    };
+
+    (resampled, weight_found * scale, weight_not_found * scale)
+}
+
+/// Resample mapped bigrams (or skipgrams), scaling the weight statistics accordingly.
+fn resample_bigrams<'s>(
+    m: &MappedBigrams<'s>,
+    sample_size: f64,
+    seed: u64,
+    resample_idx: usize,
+) -> MappedBigrams<'s> {
+    let (grams, weight_found, weight_not_found) = resample(
+        &m.grams,
+        m.weight_found,
+        m.weight_not_found,
+        |(k1, k2): &(&LayerKey, &LayerKey)| vec![k1.symbol, k2.symbol],
+        sample_size,
+        seed,
+        resample_idx,
+    );
+    MappedBigrams {
+        grams,
+        weight_found,
+        weight_not_found,
+        missing_symbols: scale_missing_symbols(
+            &m.missing_symbols,
+            m.weight_not_found,
+            weight_not_found,
+        ),
+        modifier_presses: None,
+    }
+}
+
+/// Scale the per-symbol weights of missing ngrams along with their total weight.
+fn scale_missing_symbols(
+    missing_symbols: &MissingSymbols,
+    weight_not_found: f64,
+    resampled_weight_not_found: f64,
+) -> MissingSymbols {
+    let scale = if weight_not_found > 0.0 {
+        resampled_weight_not_found / weight_not_found
+    } else {
+        1.0
+    };
+
+    missing_symbols
+        .iter()
+        .map(|(c, w)| (*c, w * scale))
+        .collect()
+}
+
+/// Summary statistics of a list of values.
+#[derive(Clone, Debug, Serialize)]
+pub struct Statistics {
+    pub mean: f64,
+    pub std: f64,
+    /// Lower bound of the 90% interval (5% quantile)
+    pub lower: f64,
+    /// Upper bound of the 90% interval (95% quantile)
+    pub upper: f64,
+}
+
+impl Statistics {
+    /// Statistics of the given values (all zero if there are none).
+    pub fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self {
+                mean: 0.0,
+                std: 0.0,
+                lower: 0.0,
+                upper: 0.0,
+            };
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let std = if values.len() > 1 {
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let quantile = |q: f64| {
+            let idx = (q * (sorted.len() - 1) as f64).round() as usize;
+            sorted[idx]
+        };
+
+        Self {
+            mean,
+            std,
+            lower: quantile(0.05),
+            upper: quantile(0.95),
+        }
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8.2} ± {:>6.2}  [{:>8.2}, {:>8.2}]",
+            self.mean, self.std, self.lower, self.upper
+        )
+    }
+}
+
+/// The results of evaluating a layout on multiple resamples of the ngram data.
+#[derive(Clone, Debug, Serialize)]
+pub struct RobustnessResult {
+    pub layout: String,
+    /// Total costs of the individual resamples
+    pub total_costs: Vec<f64>,
+    /// Statistics of the total cost
+    pub total: Statistics,
+    /// Statistics of the weighted costs of the individual metrics
+    pub metrics: Vec<(MetricType, String, Statistics)>,
+}
+
+impl RobustnessResult {
+    pub fn from_results(layout: &str, results: &[EvaluationResult]) -> Self {
+        let total_costs: Vec<f64> = results.iter().map(|r| r.total_cost()).collect();
+
+        let mut metric_costs: Vec<(MetricType, String, Vec<f64>)> = Vec::new();
+        let mut metric_indices: AHashMap<(String, String), usize> = AHashMap::default();
+        for result in results {
+            for metric_results in result.iter() {
+                for metric_cost in metric_results.metric_costs.iter() {
+                    let key = (
+                        format!("{:?}", metric_results.metric_type),
+                        metric_cost.core.name.clone(),
+                    );
+                    let idx = *metric_indices.entry(key).or_insert_with(|| {
+                        metric_costs.push((
+                            metric_results.metric_type.clone(),
+                            metric_cost.core.name.clone(),
+                            Vec::new(),
+                        ));
+                        metric_costs.len() - 1
+                    });
+                    metric_costs[idx].2.push(metric_cost.weighted_cost);
+                }
+            }
+        }
+
+        Self {
+            layout: layout.to_string(),
+            total: Statistics::from_values(&total_costs),
+            total_costs,
+            metrics: metric_costs
+                .into_iter()
+                .map(|(t, name, costs)| (t, name, Statistics::from_values(&costs)))
+                .collect(),
+        }
+    }
+
+    /// Fraction of resamples for which this layout has a lower total cost than `other`.
+    ///
+    /// Both results need to be computed with the same seed.
+    pub fn fraction_better_than(&self, other: &RobustnessResult) -> f64 {
+        let n = self.total_costs.len().min(other.total_costs.len());
+        if n == 0 {
+            return 0.0;
+        }
+
+        let n_better = self
+            .total_costs
+            .iter()
+            .zip(&other.total_costs)
+            .filter(|(c1, c2)| c1 < c2)
+            .count();
+
+        n_better as f64 / n as f64
+    }
+}
+
+impl fmt::Display for RobustnessResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({} resamples; mean ± std [90% interval])",
+            "Robustness:".bold(),
+            self.total_costs.len()
+        )?;
+        for (metric_type, name, stats) in self.metrics.iter() {
+            writeln!(
+                f,
+                "  {} {}",
+                format!("{:<45}", format!("{} ({:?})", name, metric_type)).bold(),
+                stats,
+            )?;
+        }
+        writeln!(
+            f,
+            "  {} {}",
+            format!("{:<45}", "Total cost").bold(),
+            format!("{}", self.total).green().bold()
+        )
+    }
+}
+
+impl Evaluator {
+    /// Evaluate a layout on `n_resamples` bootstrap resamples of the ngram data, assuming that
+    /// the ngrams of each type consist of `sample_size` occurrences.
+    ///
+    /// The ngrams are mapped only once. Each resample requires a full pass of all metrics.
+    pub fn evaluate_layout_resampled(
+        &self,
+        layout: &Layout,
+        n_resamples: usize,
+        sample_size: f64,
+        seed: u64,
+    ) -> Vec<EvaluationResult> {
+        let unigrams = self.map_unigrams_if_required(layout);
+        let bigrams = self.map_bigrams_if_required(layout);
+        let trigrams = self.map_trigrams_if_required(layout);
+        let quadgrams = self.map_quadgrams_if_required(layout);
+        let skipgrams = self.map_skipgrams_if_required(layout);
+
+        (0..n_resamples)
+            .map(|r| {
+                let unigrams = unigrams.as_ref().map(|m| {
+                    let (grams, weight_found, weight_not_found) = resample(
+                        &m.grams,
+                        m.weight_found,
+                        m.weight_not_found,
+                        |k: &&LayerKey| vec![k.symbol],
+                        sample_size,
+                        seed,
+                        r,
+                    );
+                    MappedUnigrams {
+                        grams,
+                        weight_found,
+                        weight_not_found,
+                        missing_symbols: scale_missing_symbols(
+                            &m.missing_symbols,
+                            m.weight_not_found,
+                            weight_not_found,
+                        ),
+                        modifier_presses: None,
+                    }
+                });
+                let bigrams = bigrams
+                    .as_ref()
+                    .map(|m| resample_bigrams(m, sample_size, seed, r));
+                let trigrams = trigrams.as_ref().map(|m| {
+                    let (grams, weight_found, weight_not_found) = resample(
+                        &m.grams,
+                        m.weight_found,
+                        m.weight_not_found,
+                        |(k1, k2, k3): &(&LayerKey, &LayerKey, &LayerKey)| {
+                            vec![k1.symbol, k2.symbol, k3.symbol]
+                        },
+                        sample_size,
+                        seed,
+                        r,
+                    );
+                    MappedTrigrams {
+                        grams,
+                        weight_found,
+                        weight_not_found,
+                        missing_symbols: scale_missing_symbols(
+                            &m.missing_symbols,
+                            m.weight_not_found,
+                            weight_not_found,
+                        ),
+                        weight_filtered: if m.weight_found > 0.0 {
+                            m.weight_filtered * weight_found / m.weight_found
+                        } else {
+                            m.weight_filtered
+                        },
+                        secondary_bigrams: None,
+                        modifier_presses: None,
+                    }
+                });
+                let quadgrams = quadgrams.as_ref().map(|m| {
+                    let (grams, weight_found, weight_not_found) = resample(
+                        &m.grams,
+                        m.weight_found,
+                        m.weight_not_found,
+                        |(k1, k2, k3, k4): &(&LayerKey, &LayerKey, &LayerKey, &LayerKey)| {
+                            vec![k1.symbol, k2.symbol, k3.symbol, k4.symbol]
+                        },
+                        sample_size,
+                        seed,
+                        r,
+                    );
+                    MappedQuadgrams {
+                        grams,
+                        weight_found,
+                        weight_not_found,
+                        missing_symbols: scale_missing_symbols(
+                            &m.missing_symbols,
+                            m.weight_not_found,
+                            weight_not_found,
+                        ),
+                        modifier_presses: None,
+                    }
+                });
+
+                self.evaluate_mapped_ngrams(
+                    layout,
+                    unigrams.as_ref(),
+                    bigrams.as_ref(),
+                    trigrams.as_ref(),
+                    quadgrams.as_ref(),
+                    skipgrams
+                        .as_ref()
+                        .map(|m| resample_bigrams(m, sample_size, seed, r))
+                        .as_ref(),
+                )
+            })
+            .collect()
+    }
+
+    /// Evaluate the robustness of a layout's evaluation with respect to the ngram data, assuming
+    /// that the ngrams of each type consist of `sample_size` occurrences.
+    pub fn evaluate_robustness(
+        &self,
+        layout: &Layout,
+        n_resamples: usize,
+        sample_size: f64,
+        seed: u64,
+    ) -> RobustnessResult {
+        let results = self.evaluate_layout_resampled(layout, n_resamples, sample_size, seed);
+        RobustnessResult::from_results(&layout.as_text(), &results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mean and variance of `n` resampled counts of an ngram with given count
+    fn resampled_moments(weight: f64, n: usize) -> (f64, f64) {
+        let values: Vec<f64> = (0..n)
+            .map(|r| resampled_count(weight, 42, r, &['x']))
+            .collect();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        (mean, var)
+    }
+
+    #[test]
+    fn resampled_counts_are_deterministic() {
+        let w1 = resampled_count(100.0, 1, 3, &['a', 'b']);
+        let w2 = resampled_count(100.0, 1, 3, &['a', 'b']);
+        assert_eq!(w1, w2);
+        assert_eq!(resampled_count(0.0, 1, 3, &['a', 'b']), 0.0);
+    }
+
+    #[test]
+    fn variance_scales_with_count() {
+        // the variance of a Poisson distribution equals its mean
+        for weight in [0.5, 4.0, 25.0, 1000.0, 1e6] {
+            let (mean, var) = resampled_moments(weight, 20000);
+            assert!(
+                (mean / weight - 1.0).abs() < 0.05,
+                "weight {}: mean {}",
+                weight,
+                mean
+            );
+            assert!(
+                (var / weight - 1.0).abs() < 0.1,
+                "weight {}: variance {}",
+                weight,
+                var
+            );
+        }
+    }
+
+    #[test]
+    fn frequent_ngrams_vary_less_relative_to_their_weight() {
+        let grams = [('a', 1.0), ('b', 10000.0)];
+        let relative_std = |idx: usize| {
+            let values: Vec<f64> = (0..2000)
+                .map(|r| {
+                    let (resampled, _, _) =
+                        resample(&grams, 10001.0, 0.0, |c| vec![*c], 10001.0, 7, r);
+                    resampled[idx].1 / grams[idx].1
+                })
+                .collect();
+            Statistics::from_values(&values).std
+        };
+
+        assert!((relative_std(0) - 1.0).abs() < 0.1);
+        assert!((relative_std(1) - 0.01).abs() < 0.001);
+    }
+
+    #[test]
+    fn resampling_does_not_depend_on_the_scale_of_the_weights() {
+        let grams = [('a', 0.25), ('b', 0.75)];
+        let scaled_grams = [('a', 250.0), ('b', 750.0)];
+        for r in 0..100 {
+            let (resampled, found, _) = resample(&grams, 1.0, 0.0, |c| vec![*c], 1000.0, 3, r);
+            let (scaled, scaled_found, _) =
+                resample(&scaled_grams, 1000.0, 0.0, |c| vec![*c], 1000.0, 3, r);
+            for ((_, w), (_, scaled_w)) in resampled.iter().zip(&scaled) {
+                assert!((w * 1000.0 - scaled_w).abs() < 1e-9);
+            }
+            assert!((found * 1000.0 - scaled_found).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let values: Vec<f64> = (1..=21).map(|v| v as f64).collect();
+        let stats = Statistics::from_values(&values);
+        assert_eq!(stats.mean, 11.0);
+        assert_eq!(stats.lower, 2.0);
+        assert_eq!(stats.upper, 20.0);
+        assert!((stats.std - 6.2048).abs() < 1e-3);
+    }
+
+    #[test]
+    fn statistics_of_no_values() {
+        let stats = Statistics::from_values(&[]);
+        assert_eq!(
+            (stats.mean, stats.std, stats.lower, stats.upper),
+            (0.0, 0.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! The `token_filter` module provides the removal of tokens that are not typed character by
+//! character (e.g. URLs, hashes, or encoded data) from texts before ngrams are collected from
+//! them.
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::borrow::Cow;
+
+/// Patterns of the tokens that are removed by default.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    // URLs
+    r"\b(?:https?|ftp)://\S+",
+    r"\bwww\.\S+",
+    // long hexadecimal strings (e.g. hashes)
+    r"\b(?:0x)?[0-9a-fA-F]{16,}\b",
+    // base64-like runs
+    r"[A-Za-z0-9+/]{24,}={0,2}",
+];
+
+/// Removes the tokens matching any of a set of regular expressions from texts.
+///
+/// Matches consisting of letters only (i.e. long words) are never removed.
+#[derive(Debug, Clone)]
+pub struct TokenFilter {
+    regex: Regex,
+}
+
+impl Default for TokenFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_PATTERNS).expect("default patterns are valid")
+    }
+}
+
+impl TokenFilter {
+    /// Create a filter removing the tokens that match any of the given patterns.
+    pub fn new<T: AsRef<str>>(patterns: &[T]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Err(anyhow!("No patterns for excluding tokens given"));
+        }
+        for pattern in patterns {
+            Regex::new(pattern.as_ref())
+                .map_err(|e| anyhow!("Invalid pattern '{}': {}", pattern.as_ref(), e))?;
+        }
+
+        let combined: Vec<String> = patterns
+            .iter()
+            .map(|p| format!("(?:{})", p.as_ref()))
+            .collect();
+        Ok(Self {
+            regex: Regex::new(&combined.join("|"))?,
+        })
+    }
+
+    /// Remove the matching tokens from the text. Also returns the number of removed characters
+    /// (i.e. the removed unigram weight).
+    pub fn apply<'a>(&self, text: &'a str) -> (Cow<'a, str>, usize) {
+        let mut res = String::new();
+        let mut last_end = 0;
+        let mut n_removed = 0;
+        for m in self.regex.find_iter(text) {
+            if m.as_str().chars().all(char::is_alphabetic) {
+                continue;
+            }
+            res.push_str(&text[last_end..m.start()]);
+            n_removed += m.as_str().chars().count();
+            last_end = m.end();
+        }
+
+        if last_end == 0 {
+            return (Cow::Borrowed(text), 0);
+        }
+        res.push_str(&text[last_end..]);
+
+        (Cow::Owned(res), n_removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_default_tokens() {
+        let filter = TokenFilter::default();
+        let remove = |text: &str| filter.apply(text).0.to_string();
+
+        assert_eq!(remove("see www.example.org/a?b=c."), "see ");
+        assert_eq!(remove("commit 3f2a9c0d4e5b6a7f8e9d0c1b2a3f4e5d"), "commit ");
+        assert_eq!(
+            remove("key: aGVsbG8gd29ybGQgaGVsbG8gd29ybGQ= end"),
+            "key:  end"
+        );
+        // words and short numbers are kept
+        assert_eq!(
+            remove("Donaudampfschifffahrtsgesellschaftskapitaen 2024 deadbeef"),
+            "Donaudampfschifffahrtsgesellschaftskapitaen 2024 deadbeef"
+        );
+    }
+
+    #[test]
+    fn custom_patterns() {
+        let filter = TokenFilter::new(&["@\\w+"]).unwrap();
+        assert_eq!(filter.apply("hi @alice and @bob"), ("hi  and ".into(), 10));
+        assert!(TokenFilter::new(&["("]).is_err());
+        assert!(TokenFilter::new::<&str>(&[]).is_err());
+    }
+}
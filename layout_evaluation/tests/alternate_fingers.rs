@@ -0,0 +1,137 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::{Finger, Hand, MatrixPosition},
+    keyboard::KeyboardError,
+    layout::Layout,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::bigram_metrics::{
+        finger_repeats::FingerRepeats, finger_travel_distance::FingerTravelDistance,
+        movement_pattern::MovementPattern, BigramMetric,
+    },
+};
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+/// Neo with "b" on the key between both index fingers ([6, 3]) and "y" above it
+const LAYOUT: &str = "xvlcykhgfqwßuiaeosnrtdüöäpbzm,.j";
+const ALTERNATE: &str = "alternate_fingers: [{position: [6, 3], hand: Right, finger: Index}]";
+
+/// The standard layout config, extended with the given keyboard entry
+fn layout_config(name: &str, keyboard_entry: &str) -> anyhow::Result<LayoutConfig> {
+    common::layout_config_from_yaml_override(
+        LAYOUT_CONFIG,
+        name,
+        &format!("keyboard:\n  {}\n", keyboard_entry),
+    )
+}
+
+/// The layout on the standard keyboard, extended with the given keyboard entry (if any)
+fn layout(keyboard_entry: Option<(&str, &str)>) -> Layout {
+    match keyboard_entry {
+        Some((name, entry)) => {
+            common::generate_layout_from_config(layout_config(name, entry).unwrap(), LAYOUT)
+        }
+        None => common::generate_layout(LAYOUT_CONFIG, LAYOUT),
+    }
+}
+
+fn bigram_cost(metric: &dyn BigramMetric, layout: &Layout, bigram: (char, char)) -> f64 {
+    let k1 = layout.get_layerkey_for_symbol(&bigram.0).unwrap();
+    let k2 = layout.get_layerkey_for_symbol(&bigram.1).unwrap();
+    metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+}
+
+#[test]
+fn alternate_finger_avoids_finger_repeat() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let params = eval_params.metrics.finger_repeats.unwrap().params;
+    let penalty = params.alternate_finger_penalty;
+    let metric = FingerRepeats::new(&params);
+
+    let single = layout(None);
+    let dual = layout(Some(("finger_repeats", ALTERNATE)));
+    let b = dual.get_layerkey_for_symbol(&'b').unwrap();
+    let y = dual.get_layerkey_for_symbol(&'y').unwrap();
+    assert_eq!(b.key.matrix_position, MatrixPosition(6, 3));
+    assert_eq!(b.key.alternate_finger, Some((Hand::Right, Finger::Index)));
+    assert_eq!((y.key.hand, y.key.finger), (Hand::Left, Finger::Index));
+
+    // "by" is a finger repeat of the left index, unless "b" is pressed with the right one
+    let cost = bigram_cost(&metric, &single, ('b', 'y'));
+    assert!(cost > penalty);
+    assert_eq!(bigram_cost(&metric, &dual, ('b', 'y')), penalty);
+    assert_eq!(bigram_cost(&metric, &dual, ('y', 'b')), penalty);
+
+    // for keys without alternate finger, nothing changes
+    assert_eq!(
+        bigram_cost(&metric, &dual, ('c', 'y')),
+        bigram_cost(&metric, &single, ('c', 'y'))
+    );
+}
+
+#[test]
+fn alternate_finger_is_used_only_if_cheaper() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let params = eval_params.metrics.movement_pattern.unwrap().params;
+    let metric = MovementPattern::new(&params);
+
+    let single = layout(None);
+    let dual = layout(Some(("movement_pattern", ALTERNATE)));
+
+    // "v" is typed with the left ring finger, "b" can be typed with the right hand instead
+    let cost = bigram_cost(&metric, &single, ('v', 'b'));
+    let dual_cost = bigram_cost(&metric, &dual, ('v', 'b'));
+    assert!(cost > 0.0);
+    assert!(dual_cost < cost);
+
+    // "by" does not involve a movement between different fingers of one hand
+    assert_eq!(bigram_cost(&metric, &single, ('b', 'y')), 0.0);
+    assert_eq!(bigram_cost(&metric, &dual, ('b', 'y')), 0.0);
+}
+
+#[test]
+fn wide_keys_shorten_distances() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let metric =
+        FingerTravelDistance::new(&eval_params.metrics.finger_travel_distance.unwrap().params);
+
+    let narrow = layout(None);
+    // "c" is on the key left of "y"
+    let wide = layout(Some(("key_widths", "key_widths: {[6, 1]: 2.0}")));
+    let y = wide.get_layerkey_for_symbol(&'y').unwrap();
+    assert_eq!(y.key.width, 2.0);
+    assert_eq!(wide.get_layerkey_for_symbol(&'c').unwrap().key.width, 1.0);
+
+    let narrow_distance = bigram_cost(&metric, &narrow, ('c', 'y'));
+    let wide_distance = bigram_cost(&metric, &wide, ('c', 'y'));
+    assert!(wide_distance < narrow_distance);
+    assert_eq!(
+        bigram_cost(&metric, &wide, ('c', 'b')),
+        bigram_cost(&metric, &narrow, ('c', 'b'))
+    );
+}
+
+#[test]
+fn unknown_positions_are_rejected() {
+    let err = layout_config(
+        "unknown_alternate",
+        "alternate_fingers: [{position: [9, 3], hand: Right, finger: Index}]",
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KeyboardError>(),
+        Some(KeyboardError::UnknownAlternateFingerPosition(
+            MatrixPosition(9, 3)
+        ))
+    ));
+
+    let err = layout_config("invalid_width", "key_widths: {[6, 1]: 0.0}").unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KeyboardError>(),
+        Some(KeyboardError::InvalidKeyWidth(MatrixPosition(6, 1), _))
+    ));
+}
@@ -0,0 +1,76 @@
+use layout_evaluation::ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams};
+
+const TEXT: &str = "Das ist ein Text, über den man\nvielleicht öfter nachdenkt: ßüöä xyz!";
+
+/// Pseudo-random split points (a linear congruential generator, so that failures are
+/// reproducible).
+fn split_points() -> Vec<usize> {
+    let n = TEXT.chars().count();
+    let mut state: u64 = 42;
+    (0..50)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % (n + 1)
+        })
+        .collect()
+}
+
+fn split(at: usize) -> (String, String) {
+    (
+        TEXT.chars().take(at).collect(),
+        TEXT.chars().skip(at).collect(),
+    )
+}
+
+macro_rules! check_append {
+    ($ngrams:ident) => {
+        for at in split_points() {
+            let (a, b) = split(at);
+            let separate_a = $ngrams::from_text(&a).unwrap();
+            let separate_b = $ngrams::from_text(&b).unwrap();
+            let total = separate_a.total_weight() + separate_b.total_weight();
+
+            let mut appended = $ngrams::from_text(&a).unwrap();
+            appended.add_text(&b);
+            assert_eq!(appended.total_weight(), total, "split at {}", at);
+
+            let mut merged = separate_a;
+            merged.merge(separate_b);
+            assert_eq!(merged.total_weight(), total, "split at {}", at);
+            assert_eq!(merged.grams.len(), appended.grams.len(), "split at {}", at);
+            for (k, w) in appended.grams.iter() {
+                assert_eq!(merged.grams.get(k), Some(w), "split at {}", at);
+            }
+        }
+    };
+}
+
+#[test]
+fn append_unigrams() {
+    check_append!(Unigrams);
+    // unigrams do not span texts
+    let (a, b) = split(10);
+    let mut appended = Unigrams::from_text(&a).unwrap();
+    appended.add_text(&b);
+    assert_eq!(
+        appended.total_weight(),
+        Unigrams::from_text(TEXT).unwrap().total_weight()
+    );
+}
+
+#[test]
+fn append_bigrams() {
+    check_append!(Bigrams);
+}
+
+#[test]
+fn append_trigrams() {
+    check_append!(Trigrams);
+}
+
+#[test]
+fn append_quadgrams() {
+    check_append!(Quadgrams);
+}
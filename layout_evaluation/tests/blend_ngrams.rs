@@ -0,0 +1,152 @@
+use layout_evaluation::ngrams::{
+    read_weighted_ngrams_dirs, Bigrams, TaggedNgrams, Trigrams, Unigrams, WeightedNgramsDir,
+};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Write the ngram files of `text` to a new directory.
+fn ngrams_dir(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_blend_{}_{}",
+        name,
+        std::process::id()
+    ));
+    Unigrams::from_text(text)
+        .unwrap()
+        .save_frequencies(dir.join("1-grams.txt"))
+        .unwrap();
+    Bigrams::from_text(text)
+        .unwrap()
+        .save_frequencies(dir.join("2-grams.txt"))
+        .unwrap();
+    Trigrams::from_text(text)
+        .unwrap()
+        .save_frequencies(dir.join("3-grams.txt"))
+        .unwrap();
+
+    dir
+}
+
+fn weighted(dir: &Path, weight: f64) -> WeightedNgramsDir {
+    WeightedNgramsDir {
+        dir: dir.to_str().unwrap().to_string(),
+        weight,
+    }
+}
+
+#[test]
+fn parse_weighted_dirs() {
+    let parse = |s: &str| s.parse::<WeightedNgramsDir>();
+
+    assert_eq!(parse("ngrams/deu:0.6").unwrap().weight, 0.6);
+    assert_eq!(parse("ngrams/deu:0.6").unwrap().dir, "ngrams/deu");
+    assert_eq!(parse("ngrams/deu").unwrap().weight, 1.0);
+    // colons that are not followed by a weight belong to the path
+    assert_eq!(parse("C:\\ngrams").unwrap().dir, "C:\\ngrams");
+    assert!(parse("ngrams/deu:-1").is_err());
+
+    assert_eq!(
+        parse("ngrams/deu:0.6").unwrap().to_string(),
+        "ngrams/deu:0.6"
+    );
+    assert_eq!(parse("ngrams/deu").unwrap().to_string(), "ngrams/deu");
+}
+
+#[test]
+fn corpora_are_normalized_before_weighting() {
+    // disjoint character sets and different sizes
+    let short = ngrams_dir("short", "abc");
+    let long = ngrams_dir("long", "xyzxyzxyzxyz");
+
+    let (unigrams, bigrams, trigrams, missing) =
+        read_weighted_ngrams_dirs(&[weighted(&short, 0.6), weighted(&long, 0.4)], false).unwrap();
+    assert!(missing.is_empty());
+
+    // the first corpus determines the absolute level
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    assert!(close(unigrams.total_weight(), 3.0));
+    let share = |chars: &str| -> f64 {
+        chars.chars().map(|c| unigrams.grams[&c]).sum::<f64>() / unigrams.total_weight()
+    };
+    assert!(close(share("abc"), 0.6));
+    assert!(close(share("xyz"), 0.4));
+    assert!(close(unigrams.grams[&'a'], unigrams.grams[&'b']));
+
+    // no ngrams spanning both corpora
+    assert!(!bigrams.grams.contains_key(&('c', 'x')));
+    assert!(close(trigrams.grams[&('a', 'b', 'c')], 0.6 * 1.0));
+
+    fs::remove_dir_all(short).unwrap();
+    fs::remove_dir_all(long).unwrap();
+}
+
+#[test]
+fn single_and_zero_weight_corpora() {
+    let dir = ngrams_dir("single", "Hello World");
+    let missing_dir = std::env::temp_dir().join("layout_evaluation_blend_does_not_exist");
+
+    // a single corpus is used as is; corpora with zero weight are not even read
+    let (unigrams, _, _, _) =
+        read_weighted_ngrams_dirs(&[weighted(&dir, 0.3), weighted(&missing_dir, 0.0)], false)
+            .unwrap();
+    assert_eq!(
+        unigrams.grams,
+        Unigrams::from_text("Hello World").unwrap().grams
+    );
+
+    assert!(read_weighted_ngrams_dirs(&[weighted(&dir, 0.0)], false).is_err());
+    assert!(read_weighted_ngrams_dirs(&[weighted(&missing_dir, 1.0)], false).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn tagged_ngrams_can_be_reweighted() {
+    let first = ngrams_dir("tagged_first", "abcabc");
+    let second = ngrams_dir("tagged_second", "abxyz");
+    let dirs = [
+        first.to_str().unwrap().to_string(),
+        second.to_str().unwrap().to_string(),
+    ];
+    let tagged = TaggedNgrams::read(&dirs, false).unwrap();
+    assert!(tagged.missing_metric_types().is_empty());
+
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    for weights in [[0.7, 0.3], [0.2, 0.8]] {
+        let (unigrams, bigrams, trigrams) = tagged.blend(&weights).unwrap();
+        let (expected_unigrams, expected_bigrams, expected_trigrams, _) =
+            read_weighted_ngrams_dirs(
+                &[weighted(&first, weights[0]), weighted(&second, weights[1])],
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(unigrams.grams.len(), expected_unigrams.grams.len());
+        assert!(unigrams
+            .grams
+            .iter()
+            .all(|(c, w)| close(*w, expected_unigrams.grams[c])));
+        assert!(bigrams
+            .grams
+            .iter()
+            .all(|(c, w)| close(*w, expected_bigrams.grams[c])));
+        assert!(trigrams
+            .grams
+            .iter()
+            .all(|(c, w)| close(*w, expected_trigrams.grams[c])));
+    }
+
+    // a single corpus is used as is
+    let (unigrams, _, _) = tagged.blend(&[0.0, 0.5]).unwrap();
+    assert_eq!(unigrams.grams, Unigrams::from_text("abxyz").unwrap().grams);
+
+    assert!(tagged.blend(&[1.0]).is_err());
+    assert!(tagged.blend(&[0.0, 0.0]).is_err());
+    assert!(tagged.blend(&[1.0, -1.0]).is_err());
+
+    fs::remove_dir_all(first).unwrap();
+    fs::remove_dir_all(second).unwrap();
+}
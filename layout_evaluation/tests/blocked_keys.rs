@@ -0,0 +1,97 @@
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::MatrixPosition,
+    keyboard::Keyboard,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::{LayoutError, NeoLayoutGenerator},
+};
+
+use std::sync::Arc;
+
+const ISO: &str = "../config/keyboard/standard_iso.yml";
+const ANSI: &str = "../config/keyboard/standard_ansi.yml";
+/// The layout with "´" on the key right of the right Mod3 (ISO only)
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtd´üöäpzbm,.j";
+
+/// The key right of the right Mod3 (missing on ANSI keyboards)
+const ISO_KEY: MatrixPosition = MatrixPosition(19, 2);
+
+/// Layout config with the ISO key being part of the layout string
+fn layout_config(filename: &str) -> LayoutConfig {
+    let mut layout_config = LayoutConfig::from_yaml(filename).unwrap();
+    layout_config.base_layout.unlocked_positions = vec![ISO_KEY];
+
+    layout_config
+}
+
+fn layout_generator(layout_config: LayoutConfig) -> NeoLayoutGenerator {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+}
+
+fn error(result: anyhow::Result<impl std::fmt::Debug>) -> LayoutError {
+    result.unwrap_err().downcast::<LayoutError>().unwrap()
+}
+
+#[test]
+fn iso_layout_errors_on_ansi() {
+    let iso = layout_generator(layout_config(ISO));
+    assert!(iso.generate(LAYOUT).is_ok());
+
+    let mut layout_config = layout_config(ANSI);
+    layout_config.base_layout.drop_blocked_symbols = false;
+    let ansi = layout_generator(layout_config);
+
+    assert!(matches!(
+        error(ansi.generate(LAYOUT)),
+        LayoutError::BlockedSymbols(symbols) if symbols == "´"
+    ));
+}
+
+#[test]
+fn blocked_symbols_can_be_dropped() {
+    let ansi = layout_generator(layout_config(ANSI));
+    assert!(!ansi.permutable_keys().contains(&'´'));
+
+    let layout = ansi.generate(LAYOUT).unwrap();
+    assert_eq!(layout.as_text(), LAYOUT.replace('´', ""));
+    assert!(layout.get_layerkey_for_symbol(&'´').is_none());
+    assert!(layout.get_layerkey_for_symbol(&'⇚').is_none());
+}
+
+#[test]
+fn blocked_keys_are_plotted() {
+    let layout = layout_generator(layout_config(ANSI))
+        .generate(LAYOUT)
+        .unwrap();
+    let plot = layout.plot();
+
+    assert_eq!(plot.matches("▓▓").count(), 2);
+    // blocked keys do not shift the borders of the plot
+    let widths: Vec<usize> = plot.lines().map(|l| l.chars().count()).collect();
+    assert!(widths.iter().all(|w| *w == widths[0]));
+}
+
+#[test]
+fn blocked_keys_can_not_hold_symbols() {
+    let mut layout_config = LayoutConfig::from_yaml(ANSI).unwrap();
+    // the first blocked key is the one right of the right Mod3, holding "´" in the base layer
+    layout_config.base_layout.drop_blocked_symbols = false;
+
+    assert!(matches!(
+        error(layout_config.validate()),
+        LayoutError::BlockedKeySymbols(ISO_KEY, symbols) if symbols.starts_with('´')
+    ));
+}
+
+#[test]
+fn blocked_keys_can_not_be_modifiers() {
+    let mut layout_config = LayoutConfig::from_yaml(ANSI).unwrap();
+    layout_config.base_layout.modifiers =
+        LayoutConfig::from_yaml(ISO).unwrap().base_layout.modifiers;
+
+    assert!(matches!(
+        error(layout_config.validate()),
+        LayoutError::BlockedModifier(MatrixPosition(1, 3))
+    ));
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use keyboard_layout::layout::{LayerModifiers, Layout};
+use layout_evaluation::ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper};
+
+/// A symbol of the fourth layer, which is accessed via a chord of two keys
+const SYMBOL: char = '₁';
+const CHORD_COST_FACTOR: f64 = 0.5;
+
+/// The standard layout with the fourth layer accessed via chords
+fn layout() -> Layout {
+    common::layout_from_yaml_override(
+        "chord_layers",
+        r#"
+base_layout:
+  modifiers:
+    - Left: { type: hold, value: [[0,3]] }
+      Right: { type: hold, value: [[18,3]] }
+    - Left: { type: hold, value: [[0,2]] }
+      Right: { type: hold, value: [[18,2]] }
+    - Left: { type: hold, value: [[1,3]] }
+      Right: { type: hold, value: [[16,4]] }
+    - Left: { type: chord, value: [[0,3], [1,3]] }
+      Right: { type: chord, value: [[18,3], [16,4]] }
+    - Left: { type: hold, value: [[0,2], [1,3]] }
+      Right: { type: hold, value: [[18,2], [16,4]] }
+"#,
+    )
+}
+
+fn ngram_mapper(text: &str) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.split_modifiers.chord_cost_factor = CHORD_COST_FACTOR;
+
+    common::ngram_mapper_with(text, config)
+}
+
+/// Symbols of the base-layer key and the two keys of the chord of [`SYMBOL`]
+fn keys(layout: &Layout) -> (char, char, char) {
+    let (base, mods) =
+        layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&SYMBOL).unwrap());
+    assert!(matches!(mods, LayerModifiers::Chord(_)));
+    let mods = mods.layerkey_indices();
+    assert_eq!(mods.len(), 2);
+
+    (
+        layout.get_layerkey(&base).symbol,
+        layout.get_layerkey(&mods[0]).symbol,
+        layout.get_layerkey(&mods[1]).symbol,
+    )
+}
+
+/// Trigrams of a text as sorted (symbols, weight) pairs
+fn trigrams(layout: &Layout, text: &[char]) -> Vec<(String, f64)> {
+    let text: String = text.iter().collect();
+    let mut trigrams: Vec<(String, f64)> = ngram_mapper(&text)
+        .map_trigrams(layout)
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ([k1.symbol, k2.symbol, k3.symbol].iter().collect(), *w))
+        .collect();
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    trigrams
+}
+
+/// Trigrams with the weight of the source trigram and those with both keys of the chord
+fn expected(single: &[[char; 3]], chord: &[[char; 3]]) -> Vec<(String, f64)> {
+    let mut trigrams: Vec<(String, f64)> = single
+        .iter()
+        .map(|t| (t.iter().collect(), 1.0))
+        .chain(
+            chord
+                .iter()
+                .map(|t| (t.iter().collect(), CHORD_COST_FACTOR)),
+        )
+        .collect();
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    trigrams
+}
+
+#[test]
+fn chord_at_first_position() {
+    let layout = layout();
+    let (base, c1, c2) = keys(&layout);
+
+    assert_eq!(
+        trigrams(&layout, &[SYMBOL, 'a', 'e']),
+        expected(
+            &[
+                [base, 'a', 'e'],
+                [c1, 'a', 'e'],
+                [c2, 'a', 'e'],
+                [c2, base, 'a']
+            ],
+            &[[c1, c2, 'a'], [c1, c2, base]],
+        )
+    );
+}
+
+#[test]
+fn chord_at_middle_position() {
+    let layout = layout();
+    let (base, c1, c2) = keys(&layout);
+
+    assert_eq!(
+        trigrams(&layout, &['a', SYMBOL, 'e']),
+        expected(
+            &[
+                ['a', base, 'e'],
+                ['a', c1, 'e'],
+                ['a', c2, 'e'],
+                ['a', c2, base],
+                [c2, base, 'e']
+            ],
+            &[['a', c1, c2], [c1, c2, 'e'], [c1, c2, base]],
+        )
+    );
+}
+
+#[test]
+fn chord_at_last_position() {
+    let layout = layout();
+    let (base, c1, c2) = keys(&layout);
+
+    assert_eq!(
+        trigrams(&layout, &['a', 'e', SYMBOL]),
+        expected(
+            &[
+                ['a', 'e', base],
+                ['a', 'e', c1],
+                ['a', 'e', c2],
+                ['e', c2, base]
+            ],
+            &[['e', c1, c2], [c1, c2, base]],
+        )
+    );
+}
+
+#[test]
+fn chord_keys_are_not_permuted_in_bigrams() {
+    let layout = layout();
+    let (base, c1, c2) = keys(&layout);
+
+    let text: String = ['a', SYMBOL].iter().collect();
+    let mapper = ngram_mapper(&text);
+    let mut bigrams: Vec<(String, f64)> = mapper
+        .map_bigrams(&layout)
+        .grams
+        .iter()
+        .map(|((k1, k2), w)| ([k1.symbol, k2.symbol].iter().collect(), *w))
+        .collect();
+    bigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut expected: Vec<(String, f64)> = vec![
+        (['a', base].iter().collect(), 1.0),
+        (['a', c1].iter().collect(), 1.0),
+        (['a', c2].iter().collect(), 1.0),
+        ([c2, base].iter().collect(), 1.0),
+        ([c1, c2].iter().collect(), CHORD_COST_FACTOR),
+    ];
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(bigrams, expected);
+
+    // each key of the chord is pressed once for the symbol
+    let mut unigrams: Vec<(char, f64)> = mapper
+        .map_unigrams(&layout)
+        .grams
+        .iter()
+        .map(|(k, w)| (k.symbol, *w))
+        .collect();
+    unigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut expected = vec![('a', 1.0), (base, 1.0), (c1, 1.0), (c2, 1.0)];
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(unigrams, expected);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngram_mapper::{
+    common::LineBreakHandling, on_demand_ngram_mapper::OnDemandNgramMapper, MappingStatistics,
+    NgramMapper,
+};
+
+use std::collections::HashMap;
+
+use common::layout;
+
+/// Runs of spaces, tabs, and line breaks
+const TEXT: &str = "a  b\tc\n\tb c";
+
+fn mapper(collapse_space_runs: bool, line_breaks: LineBreakHandling) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.collapse_space_runs = collapse_space_runs;
+    config.exclude_line_breaks = line_breaks;
+
+    common::ngram_mapper_with(TEXT, config)
+}
+
+fn statistics(mapper: &OnDemandNgramMapper, layout: &Layout) -> MappingStatistics {
+    mapper.mapping_statistics(layout).unwrap()
+}
+
+#[test]
+fn runs_are_dropped_and_single_whitespace_is_merged() {
+    let layout = layout();
+    let mapper = mapper(true, LineBreakHandling::BreakBefore);
+
+    let unigrams: HashMap<char, f64> = mapper
+        .map_unigrams(&layout)
+        .grams
+        .iter()
+        .map(|(k, w)| (k.symbol, *w))
+        .collect();
+    // unigrams contain no runs, only the tabs are replaced
+    assert_eq!(unigrams.get(&' '), Some(&5.0));
+    assert_eq!(unigrams.get(&'\t'), None);
+
+    let bigrams: HashMap<(char, char), f64> = mapper
+        .map_bigrams(&layout)
+        .grams
+        .iter()
+        .map(|((k1, k2), w)| ((k1.symbol, k2.symbol), *w))
+        .collect();
+    assert_eq!(bigrams.get(&(' ', 'b')), Some(&2.0));
+    assert_eq!(bigrams.get(&('b', ' ')), Some(&2.0));
+    assert_eq!(bigrams.get(&(' ', 'c')), Some(&2.0));
+    assert_eq!(bigrams.get(&(' ', ' ')), None);
+
+    let trigrams: HashMap<(char, char, char), f64> = mapper
+        .map_trigrams(&layout)
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ((k1.symbol, k2.symbol, k3.symbol), *w))
+        .collect();
+    assert_eq!(trigrams.get(&(' ', 'b', ' ')), Some(&2.0));
+    assert_eq!(trigrams.get(&('b', ' ', 'c')), Some(&2.0));
+    assert_eq!(trigrams.get(&(' ', 'c', '\n')), Some(&1.0));
+    assert!(trigrams
+        .keys()
+        .all(|(c1, c2, c3)| ![c1, c2, c3].contains(&&'\t')));
+}
+
+#[test]
+fn dropped_runs_and_line_breaks_are_excluded() {
+    let layout = layout();
+
+    // bigrams: "  " (run), "\n\t" (line break)
+    // trigrams: "a  ", "  b" (runs), "c\n\t", "\n\tb" (line breaks)
+    let stats = statistics(&mapper(true, LineBreakHandling::BreakBefore), &layout);
+    assert_eq!(stats.bigrams.total_weight, 10.0);
+    assert_eq!(stats.bigrams.excluded_weight, 2.0);
+    assert_eq!(stats.bigrams.found_weight, 8.0);
+    assert_eq!(stats.trigrams.total_weight, 9.0);
+    assert_eq!(stats.trigrams.excluded_weight, 4.0);
+    assert_eq!(stats.trigrams.found_weight, 5.0);
+
+    // the line breaks are not collapsed, so they are still subject to the line-break handling
+    let stats = statistics(&mapper(true, LineBreakHandling::None), &layout);
+    assert_eq!(stats.bigrams.excluded_weight, 1.0);
+    assert_eq!(stats.trigrams.excluded_weight, 2.0);
+
+    // trigrams: "\tc\n" (collapsed to " c\n") and "c\n\t" additionally
+    let stats = statistics(&mapper(true, LineBreakHandling::Both), &layout);
+    assert_eq!(stats.trigrams.excluded_weight, 5.0);
+
+    let stats = statistics(&mapper(false, LineBreakHandling::BreakBefore), &layout);
+    assert_eq!(stats.bigrams.excluded_weight, 1.0);
+    assert_eq!(stats.trigrams.excluded_weight, 2.0);
+
+    // the statistics are the same with a shared mapping
+    let mapper = mapper(true, LineBreakHandling::BreakBefore);
+    let shared = mapper.share_mapping(&layout, &Default::default()).unwrap();
+    assert_eq!(
+        shared.mapping_statistics(&layout).unwrap(),
+        statistics(&mapper, &layout)
+    );
+}
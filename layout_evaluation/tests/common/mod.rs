@@ -0,0 +1,143 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::{NgramMapperConfig, OnDemandNgramMapper},
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+pub const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+pub const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+pub const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+/// The layout generator of the given (validated) layout config file.
+pub fn layout_generator(layout_config: &str) -> NeoLayoutGenerator {
+    let layout_config = LayoutConfig::from_yaml(layout_config).unwrap();
+    layout_config.validate().unwrap();
+
+    layout_generator_from_config(layout_config)
+}
+
+/// The layout generator of the given (parsed) layout config.
+pub fn layout_generator_from_config(layout_config: LayoutConfig) -> NeoLayoutGenerator {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+}
+
+/// Generate the layout with the given layout config file.
+pub fn generate_layout(layout_config: &str, layout: &str) -> Layout {
+    layout_generator(layout_config).generate(layout).unwrap()
+}
+
+/// Generate the layout with the given (parsed) layout config.
+pub fn generate_layout_from_config(layout_config: LayoutConfig, layout: &str) -> Layout {
+    layout_generator_from_config(layout_config)
+        .generate(layout)
+        .unwrap()
+}
+
+/// Read the layout config consisting of the YAML entries `yaml` that extend the config file
+/// `base`.
+///
+/// As `extends` is resolved relative to files, the config is written to a temporary directory
+/// (named after `name`), which is removed again after reading it.
+pub fn layout_config_from_yaml_override(
+    base: &str,
+    name: &str,
+    yaml: &str,
+) -> anyhow::Result<LayoutConfig> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let base = fs::canonicalize(base).unwrap();
+    let dir = std::env::temp_dir().join(format!(
+        "{}_{}_{}",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join(format!("{}.yml", name));
+    fs::write(&config, format!("extends: {:?}\n{}", base, yaml)).unwrap();
+
+    let layout_config = LayoutConfig::from_yaml(config.to_str().unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+
+    layout_config
+}
+
+/// The layout [`LAYOUT`] on the standard keyboard with the YAML entries `yaml` overriding the
+/// standard layout config (see [`layout_config_from_yaml_override`]).
+pub fn layout_from_yaml_override(name: &str, yaml: &str) -> Layout {
+    let layout_config = layout_config_from_yaml_override(LAYOUT_CONFIG, name, yaml).unwrap();
+    generate_layout_from_config(layout_config, LAYOUT)
+}
+
+/// The layout [`LAYOUT`] on the standard keyboard.
+pub fn layout() -> Layout {
+    generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+/// The standard layout with the fourth layer locked by a single toggle key (on [16, 4]).
+pub fn lock_layer_layout() -> Layout {
+    layout_from_yaml_override(
+        "lock_layer",
+        r#"
+base_layout:
+  modifiers:
+    - Left: { type: hold, value: [[0,3]] }
+      Right: { type: hold, value: [[18,3]] }
+    - Left: { type: hold, value: [[0,2]] }
+      Right: { type: hold, value: [[18,2]] }
+    - Left: { type: hold, value: [[1,3]] }
+      Right: { type: hold, value: [[16,4]] }
+    - Left: { type: lock, value: [[16,4]] }
+      Right: { type: lock, value: [[16,4]] }
+    - Left: { type: hold, value: [[0,2], [1,3]] }
+      Right: { type: hold, value: [[18,2], [16,4]] }
+"#,
+    )
+}
+
+/// The ngram mapper configuration of the default evaluation parameters.
+pub fn ngram_mapper_config() -> NgramMapperConfig {
+    EvaluationParameters::from_yaml(EVALUATION_PARAMETERS)
+        .unwrap()
+        .ngram_mapper
+}
+
+/// A mapper for the ngrams of the given text with the given configuration.
+pub fn ngram_mapper_with(text: &str, config: NgramMapperConfig) -> OnDemandNgramMapper {
+    OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(text).unwrap(),
+        Bigrams::from_text(text).unwrap(),
+        Trigrams::from_text(text).unwrap(),
+        config,
+    )
+}
+
+/// An evaluator with the default metrics for the ngrams of the given text.
+pub fn evaluator(text: &str) -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = ngram_mapper_with(text, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+/// A mapper for the ngrams of the given text with the default configuration.
+pub fn ngram_mapper(text: &str) -> OnDemandNgramMapper {
+    ngram_mapper_with(text, ngram_mapper_config())
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper};
+
+use std::collections::HashMap;
+
+use common::layout;
+
+/// A mapper for the text with the given compose sequences
+fn mapper(text: &str, compose_sequences: &[(char, &str)]) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.compose_sequences = compose_sequences
+        .iter()
+        .map(|(c, sequence)| (*c, sequence.to_string()))
+        .collect();
+
+    common::ngram_mapper_with(text, config)
+}
+
+fn trigrams(mapper: &OnDemandNgramMapper, layout: &Layout) -> HashMap<(char, char, char), f64> {
+    mapper
+        .map_trigrams(layout)
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ((k1.symbol, k2.symbol, k3.symbol), *w))
+        .collect()
+}
+
+#[test]
+fn composed_symbol_expands_into_its_sequence() {
+    let layout = layout();
+    // the layout does not generate "é", but the dead key "`" on the base layer
+    let without = mapper("aébc", &[]).map_trigrams(&layout);
+    assert!(without.weight_not_found > 0.0);
+
+    let composed = mapper("aébc", &[('é', "`e")]);
+    let mapped = composed.map_trigrams(&layout);
+    assert_eq!(mapped.weight_not_found, 0.0);
+    assert!(mapped.missing_symbols.is_empty());
+
+    // the trigram "aéb" becomes "a`e", those of "ébc" become "`eb" and "ebc"
+    let expected = trigrams(&mapper("a`ebc", &[]), &layout);
+    assert_eq!(trigrams(&composed, &layout), expected);
+    assert_eq!(mapped.weight_found, 3.0);
+
+    let unigrams = composed.map_unigrams(&layout);
+    assert_eq!(unigrams.weight_not_found, 0.0);
+    assert_eq!(unigrams.weight_found, 5.0);
+}
+
+#[test]
+fn generated_symbol_is_not_composed() {
+    let layout = layout();
+    // the layout generates "x" directly
+    let composed = mapper("axb", &[('x', "`e")]);
+
+    assert_eq!(
+        trigrams(&composed, &layout),
+        trigrams(&mapper("axb", &[]), &layout)
+    );
+}
@@ -0,0 +1,120 @@
+use layout_evaluation::{
+    corpus,
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+use std::{fs, path::PathBuf, slice};
+
+const TEXT_A: &str = "Der schnelle braune Fuchs\r\nspringt über den faulen Hund.";
+const TEXT_B: &str = "The quick brown fox jumps over the lazy dog.";
+
+/// Write a small corpus to a new directory: two text files (one in a subdirectory), a file
+/// that does not match the patterns, and a file that is not valid UTF-8.
+fn corpus_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_corpus_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), TEXT_A).unwrap();
+    fs::write(dir.join("sub").join("b.md"), TEXT_B).unwrap();
+    fs::write(dir.join("notes.csv"), "a,b,c").unwrap();
+    fs::write(dir.join("invalid.txt"), [0xff, 0xfe, b'x', b'y']).unwrap();
+
+    dir
+}
+
+#[test]
+fn from_paths_merges_counts_of_all_files() {
+    let dir = corpus_dir("merge");
+    let patterns = corpus::parse_patterns("*.txt,*.md");
+    let files = corpus::text_files(slice::from_ref(&dir), &patterns).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            dir.join("a.txt"),
+            dir.join("invalid.txt"),
+            dir.join("sub").join("b.md")
+        ]
+    );
+
+    // the invalid file is skipped, the counts of the others add up
+    let mut expected = Unigrams::from_text(TEXT_A).unwrap();
+    expected.add_text(TEXT_B);
+    let mut unigrams = Unigrams::from_text("").unwrap();
+    let n_read = corpus::for_each_file(&files, |reader| {
+        corpus::for_each_chunk(reader, |chunk| unigrams.add_text(chunk))
+    })
+    .unwrap();
+    assert_eq!(n_read, 2);
+    assert_eq!(unigrams.grams, expected.grams);
+
+    // without patterns, all files are read
+    let all = Bigrams::from_paths(slice::from_ref(&dir)).unwrap();
+    let mut expected = Bigrams::from_text(TEXT_A).unwrap();
+    expected.add_text(TEXT_B);
+    expected.add_text("a,b,c");
+    assert_eq!(all.grams, expected.grams);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn from_paths_of_single_file_equals_from_text() {
+    let dir = corpus_dir("single");
+    let file = dir.join("a.txt");
+
+    assert_eq!(
+        Unigrams::from_paths(slice::from_ref(&file)).unwrap().grams,
+        Unigrams::from_text(TEXT_A).unwrap().grams
+    );
+    assert_eq!(
+        Bigrams::from_paths(slice::from_ref(&file)).unwrap().grams,
+        Bigrams::from_text(TEXT_A).unwrap().grams
+    );
+    assert_eq!(
+        Trigrams::from_paths(&[file]).unwrap().grams,
+        Trigrams::from_text(TEXT_A).unwrap().grams
+    );
+
+    assert!(Unigrams::from_paths(&[dir.join("missing.txt")]).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn large_files_are_streamed_in_chunks() {
+    let dir = corpus_dir("large");
+    // much larger than a read of the file, with multi-byte characters split across reads
+    let text = format!("{}\nΩμέγα שָׁלוֹם ☺☺☺ ", TEXT_A).repeat(1000);
+    let file = dir.join("large.txt");
+    fs::write(&file, &text).unwrap();
+
+    let mut n_chunks = 0;
+    corpus::for_each_file(slice::from_ref(&file), |reader| {
+        corpus::for_each_chunk(reader, |_| n_chunks += 1)
+    })
+    .unwrap();
+    assert!(n_chunks > 1);
+
+    assert_eq!(
+        Unigrams::from_paths(slice::from_ref(&file)).unwrap().grams,
+        Unigrams::from_text(&text).unwrap().grams
+    );
+    assert_eq!(
+        Bigrams::from_paths(slice::from_ref(&file)).unwrap().grams,
+        Bigrams::from_text(&text).unwrap().grams
+    );
+    assert_eq!(
+        Trigrams::from_paths(slice::from_ref(&file)).unwrap().grams,
+        Trigrams::from_text(&text).unwrap().grams
+    );
+    assert_eq!(
+        Quadgrams::from_paths(&[file]).unwrap().grams,
+        Quadgrams::from_text(&text).unwrap().grams
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use layout_evaluation::ngram_mapper::{
+    on_demand_ngram_mapper::OnDemandNgramMapper, MappedBigrams, NgramMapper,
+};
+
+use common::layout;
+
+fn ngram_mapper(text: &str, enabled: bool) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.cross_gram_modifiers.enabled = enabled;
+    config.cross_gram_modifiers.factor = 0.5;
+
+    common::ngram_mapper_with(text, config)
+}
+
+fn weight(bigrams: &MappedBigrams, symbols: (char, char)) -> f64 {
+    bigrams
+        .grams
+        .iter()
+        .filter(|((k1, k2), _)| (k1.symbol, k2.symbol) == symbols)
+        .map(|(_, w)| w)
+        .sum()
+}
+
+#[test]
+fn adds_bigram_of_key_and_following_modifier() {
+    let layout = layout();
+    let shift = layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&'X').unwrap());
+    let shift = layout.get_layerkey(&shift.1.layerkey_indices()[0]).symbol;
+
+    let plain = ngram_mapper("aX", false).map_bigrams(&layout);
+    let cross_gram = ngram_mapper("aX", true).map_bigrams(&layout);
+
+    assert_eq!(
+        weight(&cross_gram, ('a', shift)),
+        weight(&plain, ('a', shift)) + 0.5
+    );
+    assert_eq!(weight(&cross_gram, ('a', 'x')), weight(&plain, ('a', 'x')));
+    assert_eq!(cross_gram.weight_found, plain.weight_found + 0.5);
+
+    // nothing is added for symbols without modifiers
+    let plain = ngram_mapper("Xa", false).map_bigrams(&layout);
+    let cross_gram = ngram_mapper("Xa", true).map_bigrams(&layout);
+    assert_eq!(cross_gram.weight_found, plain.weight_found);
+}
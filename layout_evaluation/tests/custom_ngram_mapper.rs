@@ -0,0 +1,85 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{
+        on_demand_ngram_mapper::OnDemandNgramMapper, MappedBigrams, MappedQuadgrams,
+        MappedTrigrams, MappedUnigrams, NgramMapper,
+    },
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// A mapper modeling word completion: A share of the trigrams at the end of words (followed by a
+/// space) is not typed.
+#[derive(Clone, Debug)]
+struct WordCompletionMapper {
+    mapper: OnDemandNgramMapper,
+    completed: f64,
+}
+
+impl NgramMapper for WordCompletionMapper {
+    fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
+        self.mapper.map_unigrams(layout)
+    }
+
+    fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
+        self.mapper.map_bigrams(layout)
+    }
+
+    fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s> {
+        let mut trigrams = self.mapper.map_trigrams(layout);
+        trigrams
+            .grams
+            .iter_mut()
+            .filter(|((_, _, k3), _)| k3.symbol == ' ')
+            .for_each(|(_, w)| *w *= 1.0 - self.completed);
+        trigrams.modifier_presses = None;
+        trigrams
+    }
+
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s> {
+        self.mapper.map_quadgrams(layout)
+    }
+
+    fn map_skipgrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
+        self.mapper.map_skipgrams(layout)
+    }
+}
+
+fn evaluator(mapper: impl NgramMapper + Clone + 'static) -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+fn on_demand_mapper() -> OnDemandNgramMapper {
+    common::ngram_mapper(TEXT)
+}
+
+#[test]
+fn evaluator_accepts_custom_ngram_mapper() {
+    let layout = layout();
+    let expected = evaluator(on_demand_mapper())
+        .evaluate_layout(&layout)
+        .total_cost();
+
+    let unchanged = WordCompletionMapper {
+        mapper: on_demand_mapper(),
+        completed: 0.0,
+    };
+    let total_cost = evaluator(unchanged).evaluate_layout(&layout).total_cost();
+    assert!((total_cost - expected).abs() < 1e-9);
+
+    let completing = WordCompletionMapper {
+        mapper: on_demand_mapper(),
+        completed: 0.5,
+    };
+    let total_cost = evaluator(completing).evaluate_layout(&layout).total_cost();
+    assert!(total_cost.is_finite());
+    assert_ne!(total_cost, expected);
+}
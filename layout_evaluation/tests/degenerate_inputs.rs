@@ -0,0 +1,198 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    results::EvaluationResult,
+};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const TWO_KEYS_CONFIG: &str = "tests/fixtures/two_keys.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund.";
+
+/// The degenerate ngram data sets.
+#[derive(Clone, Copy, Debug)]
+enum Corpus {
+    /// No ngrams at all
+    Empty,
+    /// Ngrams of a text with all weights set to zero
+    ZeroWeights,
+    /// Ngrams that contain no symbol of the layout
+    Unknown,
+    /// Regular ngrams (for the degenerate layouts)
+    Regular,
+}
+
+fn ngrams(corpus: Corpus) -> (Unigrams, Bigrams, Trigrams) {
+    let text = match corpus {
+        Corpus::Empty => "",
+        Corpus::Unknown => "§§$$",
+        _ => TEXT,
+    };
+    let mut unigrams = Unigrams::from_text(text).unwrap();
+    let mut bigrams = Bigrams::from_text(text).unwrap();
+    let mut trigrams = Trigrams::from_text(text).unwrap();
+
+    if let Corpus::ZeroWeights = corpus {
+        unigrams.grams.values_mut().for_each(|w| *w = 0.0);
+        bigrams.grams.values_mut().for_each(|w| *w = 0.0);
+        trigrams.grams.values_mut().for_each(|w| *w = 0.0);
+    }
+
+    (unigrams, bigrams, trigrams)
+}
+
+fn evaluator(corpus: Corpus) -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let (unigrams, bigrams, trigrams) = ngrams(corpus);
+    let mapper =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+fn generate(layout_config: LayoutConfig, layout: &str) -> Layout {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+
+    layout_generator.generate(layout).unwrap()
+}
+
+fn standard_layout() -> Layout {
+    generate(LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap(), LAYOUT)
+}
+
+/// The standard layout reduced to its base layer (without any modifiers).
+fn single_layer_layout() -> Layout {
+    let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    layout_config
+        .base_layout
+        .keys
+        .iter_mut()
+        .flatten()
+        .for_each(|layers| layers.truncate(1));
+    layout_config.base_layout.modifiers.clear();
+    layout_config.base_layout.fixed_layers.clear();
+
+    generate(layout_config, LAYOUT)
+}
+
+/// A keyboard with a single key per hand.
+fn two_keys_layout() -> Layout {
+    generate(LayoutConfig::from_yaml(TWO_KEYS_CONFIG).unwrap(), "ab")
+}
+
+fn assert_finite(result: &EvaluationResult, case: &str) {
+    assert!(
+        result.total_cost().is_finite(),
+        "{}: total cost {}",
+        case,
+        result.total_cost()
+    );
+    for mr in result.iter() {
+        assert!(
+            mr.found_weight.is_finite() && mr.not_found_weight.is_finite(),
+            "{}: weights of {:?}",
+            case,
+            mr.metric_type
+        );
+        for mc in mr.metric_costs.iter() {
+            assert!(
+                mc.core.cost.is_finite()
+                    && mc.weighted_cost.is_finite()
+                    && mc.unweighted_cost.is_finite(),
+                "{}: {} = {} (weighted {}, unweighted {})",
+                case,
+                mc.core.name,
+                mc.core.cost,
+                mc.weighted_cost,
+                mc.unweighted_cost
+            );
+        }
+    }
+}
+
+/// Evaluate a layout with all public entry points and check that no result contains NaN or
+/// infinite values.
+fn check(layout: &Layout, corpus: Corpus, case: &str) -> EvaluationResult {
+    let evaluator = evaluator(corpus);
+
+    let result = evaluator.evaluate_layout(layout);
+    assert_finite(&result, case);
+    // printing must not panic either
+    let _ = format!("{}", result);
+
+    let summary = evaluator.key_summary(layout);
+    assert!(summary.total_cost.is_finite(), "{}: key summary", case);
+    assert!(
+        summary.keys.iter().all(|k| k.cost.is_finite()),
+        "{}: key costs",
+        case
+    );
+    let _ = format!("{}", summary.top(3));
+
+    for n_resamples in [0, 3] {
+        let robustness = evaluator.evaluate_robustness(layout, n_resamples, 1000.0, 0);
+        let stats =
+            std::iter::once(&robustness.total).chain(robustness.metrics.iter().map(|(_, _, s)| s));
+        for s in stats {
+            assert!(
+                s.mean.is_finite()
+                    && s.std.is_finite()
+                    && s.lower.is_finite()
+                    && s.upper.is_finite(),
+                "{}: robustness with {} resamples",
+                case,
+                n_resamples
+            );
+        }
+        let _ = format!("{}", robustness);
+    }
+
+    let _ = evaluator.memory_report(layout);
+
+    result
+}
+
+#[test]
+fn degenerate_ngrams_have_zero_costs() {
+    let layout = standard_layout();
+
+    for corpus in [Corpus::Empty, Corpus::ZeroWeights, Corpus::Unknown] {
+        let result = check(&layout, corpus, &format!("{:?} ngrams", corpus));
+
+        // without (found) ngram weight, only the layout metrics contribute
+        let ngram_cost: f64 = result
+            .iter()
+            .filter(|mr| mr.found_weight <= 0.0)
+            .flat_map(|mr| mr.metric_costs.iter())
+            .map(|mc| mc.weighted_cost)
+            .sum();
+        assert_eq!(ngram_cost, 0.0, "{:?} ngrams", corpus);
+    }
+}
+
+#[test]
+fn degenerate_layouts() {
+    for (case, layout) in [
+        ("single layer", single_layer_layout()),
+        ("one key per hand", two_keys_layout()),
+    ] {
+        for corpus in [
+            Corpus::Regular,
+            Corpus::Empty,
+            Corpus::ZeroWeights,
+            Corpus::Unknown,
+        ] {
+            check(&layout, corpus, &format!("{}, {:?} ngrams", case, corpus));
+        }
+    }
+}
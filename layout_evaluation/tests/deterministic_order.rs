@@ -0,0 +1,82 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+use ahash::AHashMap;
+use std::hash::Hash;
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox (jumps) over the [lazy] dog! {a: 1 + 2 = 3} \
+                    \"Quoted\" TEXT & more; 100% <tags> with $cash, #hash, @mail ~ `code`?\n\
+                    Zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich.";
+
+/// The same ngrams, inserted into a new map in ascending or descending order of their symbols.
+fn reinserted<K: Eq + Hash + Ord + Copy>(
+    grams: &AHashMap<K, f64>,
+    reverse: bool,
+) -> AHashMap<K, f64> {
+    let mut entries: Vec<(K, f64)> = grams.iter().map(|(k, w)| (*k, *w)).collect();
+    entries.sort_by_key(|(k, _)| *k);
+    if reverse {
+        entries.reverse();
+    }
+
+    let mut reinserted = AHashMap::default();
+    entries.into_iter().for_each(|(k, w)| {
+        reinserted.insert(k, w);
+    });
+
+    reinserted
+}
+
+fn evaluator(reverse: bool) -> Evaluator {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.deterministic_order = true;
+
+    let unigrams = Unigrams::from_text(TEXT).unwrap();
+    let bigrams = Bigrams::from_text(TEXT).unwrap();
+    let trigrams = Trigrams::from_text(TEXT).unwrap();
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams {
+            grams: reinserted(&unigrams.grams, reverse),
+        },
+        Bigrams {
+            grams: reinserted(&bigrams.grams, reverse),
+        },
+        Trigrams {
+            grams: reinserted(&trigrams.grams, reverse),
+        },
+        eval_params.ngram_mapper,
+    );
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+#[test]
+fn shuffled_insertion_order_yields_identical_costs() {
+    let layout = layout();
+    let ascending = evaluator(false).evaluate_layout(&layout);
+    let descending = evaluator(true).evaluate_layout(&layout);
+
+    assert_eq!(
+        ascending.total_cost().to_bits(),
+        descending.total_cost().to_bits()
+    );
+    for (a, d) in ascending.iter().zip(descending.iter()) {
+        for (ac, dc) in a.metric_costs.iter().zip(d.metric_costs.iter()) {
+            assert_eq!(
+                ac.core.cost.to_bits(),
+                dc.core.cost.to_bits(),
+                "{}",
+                ac.core.name
+            );
+        }
+    }
+}
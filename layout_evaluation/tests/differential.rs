@@ -0,0 +1,127 @@
+mod common;
+
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{
+    differential::{compare_results, Disagreement},
+    evaluation::{evaluate_layout, EvaluationContext},
+    ngram_mapper::common::ShiftPolicy,
+    results::EvaluationResult,
+};
+
+use proptest::{
+    collection::vec,
+    prelude::*,
+    sample::select,
+    test_runner::{RngAlgorithm, TestError, TestRng, TestRunner},
+};
+
+use common::{LAYOUT, LAYOUT_CONFIG};
+
+/// Symbols of the generated corpora
+const ALPHABET: &str = "enirstadhulcgmobwfkzpvßjyxqäöü ENIRSTADHULC,.\n";
+
+/// Relative tolerance for the comparison of costs
+const TOLERANCE: f64 = 1e-9;
+
+/// Small corpora of the symbols of the [`ALPHABET`]
+fn corpus() -> impl Strategy<Value = String> {
+    vec(select(ALPHABET.chars().collect::<Vec<char>>()), 1..200)
+        .prop_map(|symbols| symbols.into_iter().collect())
+}
+
+/// Permutations of the standard layout
+fn layout_str() -> impl Strategy<Value = String> {
+    Just(LAYOUT.chars().collect::<Vec<char>>())
+        .prop_shuffle()
+        .prop_map(|symbols| symbols.into_iter().collect())
+}
+
+/// The disagreements of an implementation with [`evaluate_layout`] for a corpus and a layout.
+fn disagreements<A>(corpus: &str, layout_str: &str, actual: A, tolerance: f64) -> Vec<Disagreement>
+where
+    A: Fn(&EvaluationContext, &Layout) -> EvaluationResult,
+{
+    let context = common::evaluator(corpus);
+    let layout = common::layout_generator(LAYOUT_CONFIG)
+        .generate(layout_str)
+        .unwrap();
+
+    compare_results(
+        &evaluate_layout(&context, &layout),
+        &actual(&context, &layout),
+        tolerance,
+    )
+}
+
+fn report(disagreements: &[Disagreement]) -> String {
+    let report: Vec<String> = disagreements.iter().map(|d| d.to_string()).collect();
+    report.join("\n")
+}
+
+/// A broken implementation that evaluates capital letters as if they were typed with one-shot
+/// modifiers.
+fn one_shot(context: &EvaluationContext, layout: &Layout) -> EvaluationResult {
+    context
+        .with_shift_policy(ShiftPolicy::OneShot)
+        .unwrap()
+        .evaluate_layout(layout)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn evaluation_is_deterministic(corpus in corpus(), layout_str in layout_str()) {
+        let disagreements = disagreements(&corpus, &layout_str, evaluate_layout, 0.0);
+        prop_assert!(disagreements.is_empty(), "{}", report(&disagreements));
+    }
+
+    #[test]
+    fn shared_mapping_agrees(corpus in corpus(), layout_str in layout_str()) {
+        let shared_mapping = |context: &EvaluationContext, layout: &Layout| {
+            context
+                .with_shared_mapping(&[layout])
+                .evaluate_layout(layout)
+        };
+        let disagreements = disagreements(&corpus, &layout_str, shared_mapping, TOLERANCE);
+        prop_assert!(disagreements.is_empty(), "{}", report(&disagreements));
+    }
+
+    #[test]
+    fn layout_shift_policy_agrees(corpus in corpus(), layout_str in layout_str()) {
+        let layout_policy = |context: &EvaluationContext, layout: &Layout| {
+            context
+                .with_shift_policy(ShiftPolicy::Layout)
+                .unwrap()
+                .evaluate_layout(layout)
+        };
+        let disagreements = disagreements(&corpus, &layout_str, layout_policy, TOLERANCE);
+        prop_assert!(disagreements.is_empty(), "{}", report(&disagreements));
+    }
+}
+
+#[test]
+fn disagreements_are_found_and_shrunk() {
+    let config = ProptestConfig {
+        failure_persistence: None,
+        max_shrink_iters: 10_000,
+        ..ProptestConfig::with_cases(32)
+    };
+    let mut runner =
+        TestRunner::new_with_rng(config, TestRng::deterministic_rng(RngAlgorithm::ChaCha));
+
+    let result = runner.run(&(corpus(), layout_str()), |(corpus, layout_str)| {
+        let disagreements = disagreements(&corpus, &layout_str, one_shot, TOLERANCE);
+        prop_assert!(disagreements.is_empty(), "{}", report(&disagreements));
+        Ok(())
+    });
+
+    match result {
+        Err(TestError::Fail(_, (corpus, _))) => {
+            // the disagreement requires a shifted symbol within a bigram, and nothing else
+            assert!(corpus.chars().any(|c| c.is_uppercase()), "{:?}", corpus);
+            assert!(corpus.chars().count() <= 2, "{:?}", corpus);
+        }
+        result => panic!("The broken implementation was not detected: {:?}", result),
+    }
+}
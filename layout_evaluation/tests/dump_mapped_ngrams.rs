@@ -0,0 +1,71 @@
+mod common;
+
+use layout_evaluation::{config::EvaluationParameters, ngram_mapper::NgramMapper};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// A capital letter and a tab (split into a modifier and a base key), and line breaks
+const TEXT: &str = "Ab\tbb\n\nb";
+
+fn test_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_dump_mapped_ngrams_{}",
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// The lines of a TSV file split into columns (without the header)
+fn rows(dir: &Path, filename: &str) -> Vec<Vec<String>> {
+    let data = fs::read_to_string(dir.join(filename)).unwrap();
+    let mut lines = data.lines();
+    assert_eq!(
+        lines.next(),
+        Some("symbols\tpositions\tlayers\tmodifiers\tweight")
+    );
+
+    lines
+        .map(|l| l.split('\t').map(|c| c.to_string()).collect())
+        .collect()
+}
+
+#[test]
+fn mapped_ngrams_are_written_sorted_and_escaped() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = common::ngram_mapper_with(TEXT, eval_params.ngram_mapper);
+    let layout = layout();
+    let dir = test_dir();
+    mapper.dump_mapped_ngrams(&layout, &dir).unwrap();
+
+    for (filename, n) in [("1-grams.tsv", 1), ("2-grams.tsv", 2), ("3-grams.tsv", 3)].iter() {
+        let rows = rows(&dir, filename);
+        assert!(!rows.is_empty());
+        assert!(rows.iter().all(|r| r.len() == 5
+            && r[1].split(' ').count() == *n
+            && r[2].split(' ').count() == *n
+            && r[3].split(' ').count() == *n));
+
+        let weights: Vec<f64> = rows.iter().map(|r| r[4].parse().unwrap()).collect();
+        assert!(weights.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    let unigrams = rows(&dir, "1-grams.tsv");
+    // "b" is the most common symbol
+    assert_eq!(unigrams[0][0], "b");
+    assert_eq!(unigrams[0][2], "0");
+    assert_eq!(unigrams[0][3], "false");
+    // the modifiers for "A" and the tab are split off
+    assert!(unigrams.iter().any(|r| r[3] == "true"));
+
+    let bigrams = rows(&dir, "2-grams.tsv");
+    let symbols: Vec<&str> = bigrams.iter().map(|r| r[0].as_str()).collect();
+    assert!(symbols.contains(&"\\n\\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use keyboard_layout::{key::MatrixPosition, layout::Layout};
+use layout_evaluation::ngram_mapper::{
+    common::DuplicateSymbolPolicy, on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper,
+};
+
+const LAYOUT_CONFIG: &str = "tests/fixtures/duplicate_symbol.yml";
+const TEXT: &str = "c/a";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, "ac/")
+}
+
+fn mapper(policy: DuplicateSymbolPolicy) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.duplicate_symbols = policy;
+
+    common::ngram_mapper_with(TEXT, config)
+}
+
+/// Weights of the unigram "/" typed on the shift layer (with the "a" key) and on the base layer
+/// (of its own key)
+fn slash_weights(policy: DuplicateSymbolPolicy) -> (f64, f64) {
+    let layout = layout();
+    let unigrams = mapper(policy).map_unigrams(&layout);
+    let weight = |matrix_position: MatrixPosition| -> f64 {
+        unigrams
+            .grams
+            .iter()
+            .filter(|(k, _)| k.key.matrix_position == matrix_position)
+            .map(|(_, w)| w)
+            .sum()
+    };
+
+    // the "a" key types the unigram "a" as well
+    (
+        weight(MatrixPosition(0, 0)) - 1.0,
+        weight(MatrixPosition(2, 0)),
+    )
+}
+
+#[test]
+fn layout_lists_duplicate_symbol_by_cost() {
+    let layout = layout();
+    let idx = layout.get_layerkey_index_for_symbol(&'/').unwrap();
+    let indices = layout.get_duplicate_symbol(&idx).unwrap();
+
+    assert!(layout.has_duplicate_symbols());
+    assert_eq!(indices.len(), 2);
+    // shift (1) plus the "a" key (1) is cheaper than the other key (3)
+    assert_eq!(layout.get_layerkey(&indices[0]).layer, 1);
+    assert_eq!(layout.layerkey_cost(&indices[0]), 2.0);
+    assert_eq!(layout.layerkey_cost(&indices[1]), 3.0);
+    assert!(layout
+        .get_duplicate_symbol(&layout.get_layerkey_index_for_symbol(&'c').unwrap())
+        .is_none());
+}
+
+#[test]
+fn cheapest_only() {
+    assert_eq!(
+        slash_weights(DuplicateSymbolPolicy::CheapestOnly),
+        (1.0, 0.0)
+    );
+}
+
+#[test]
+fn split_evenly() {
+    assert_eq!(
+        slash_weights(DuplicateSymbolPolicy::SplitEvenly),
+        (0.5, 0.5)
+    );
+}
+
+#[test]
+fn split_by_cost() {
+    let (shifted, base) = slash_weights(DuplicateSymbolPolicy::SplitByCost);
+    assert!((shifted - 0.6).abs() < 1e-9);
+    assert!((base - 0.4).abs() < 1e-9);
+}
+
+#[test]
+fn trigram_weight_is_split() {
+    let layout = layout();
+    for policy in [
+        DuplicateSymbolPolicy::CheapestOnly,
+        DuplicateSymbolPolicy::SplitEvenly,
+        DuplicateSymbolPolicy::SplitByCost,
+    ] {
+        let trigrams = mapper(policy).map_trigrams(&layout);
+        let on_own_key: f64 = trigrams
+            .grams
+            .iter()
+            .filter(|((_, k2, _), _)| k2.symbol == '/' && k2.layer == 0)
+            .map(|(_, w)| w)
+            .sum();
+        let expected = slash_weights(policy).1;
+        assert!((on_own_key - expected).abs() < 1e-9, "{:?}", policy);
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use layout_evaluation::ngram_mapper::NgramMapper;
+
+use common::{layout, ngram_mapper};
+
+/// Trigrams as strings with their weights, sorted
+fn sorted(grams: impl Iterator<Item = (String, f64)>) -> Vec<(String, u64)> {
+    let mut grams: Vec<(String, u64)> = grams.map(|(s, w)| (s, w.to_bits())).collect();
+    grams.sort();
+    grams
+}
+
+#[test]
+fn expansion_lists_the_mapped_trigrams() {
+    let layout = layout();
+    let mapper = ngram_mapper("Xen");
+    let expansion = mapper.explain_trigram(('X', 'e', 'n'), &layout).unwrap();
+
+    assert_eq!(expansion.weight, 1.0);
+    assert_eq!(expansion.weight_not_found, 0.0);
+    assert!(expansion.generated.len() > 1);
+    assert!(expansion
+        .generated
+        .windows(2)
+        .all(|g| g[0].weight >= g[1].weight));
+
+    // the text consists of the single trigram, so its expansion makes up the mapped trigrams
+    let mapped = mapper.map_trigrams(&layout);
+    let expected = sorted(
+        mapped
+            .grams
+            .iter()
+            .map(|((k1, k2, k3), w)| (format!("{}{}{}", k1, k2, k3), *w)),
+    );
+    let explained = sorted(
+        expansion
+            .generated
+            .iter()
+            .filter(|g| !g.filtered)
+            .map(|g| (format!("{}{}{}", g.keys.0, g.keys.1, g.keys.2), g.weight)),
+    );
+    assert_eq!(explained, expected);
+}
+
+#[test]
+fn unknown_trigram_is_not_explained() {
+    let layout = layout();
+    let mapper = ngram_mapper("Xen");
+
+    assert!(mapper.explain_trigram(('a', 'b', 'c'), &layout).is_none());
+}
@@ -0,0 +1,47 @@
+use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+
+#[test]
+fn primary_weights_take_precedence() {
+    let primary = Unigrams::from_text("aab").unwrap();
+    let fallback = Unigrams::from_text("aaaaccc").unwrap();
+    let merged = Unigrams::merge_with_fallback(&primary, &fallback, 0.5);
+
+    // in both sources: only the primary weight
+    assert_eq!(merged.grams.get(&'a'), Some(&2.0));
+    // only in the primary source
+    assert_eq!(merged.grams.get(&'b'), Some(&1.0));
+    // only in the fallback source: scaled fallback weight
+    assert_eq!(merged.grams.get(&'c'), Some(&1.5));
+    assert_eq!(merged.grams.len(), 3);
+}
+
+#[test]
+fn fallback_for_all_orders() {
+    let primary = "abab";
+    let fallback = "abcabc";
+
+    let bigrams = Bigrams::merge_with_fallback(
+        &Bigrams::from_text(primary).unwrap(),
+        &Bigrams::from_text(fallback).unwrap(),
+        2.0,
+    );
+    assert_eq!(bigrams.grams.get(&('a', 'b')), Some(&2.0));
+    assert_eq!(bigrams.grams.get(&('b', 'a')), Some(&1.0));
+    assert_eq!(bigrams.grams.get(&('b', 'c')), Some(&4.0));
+
+    let trigrams = Trigrams::merge_with_fallback(
+        &Trigrams::from_text(primary).unwrap(),
+        &Trigrams::from_text(fallback).unwrap(),
+        2.0,
+    );
+    assert_eq!(trigrams.grams.get(&('a', 'b', 'a')), Some(&1.0));
+    assert_eq!(trigrams.grams.get(&('a', 'b', 'c')), Some(&4.0));
+}
+
+#[test]
+fn empty_primary_uses_fallback() {
+    let primary = Unigrams::from_text("").unwrap();
+    let fallback = Unigrams::from_text("xyz").unwrap();
+    let merged = Unigrams::merge_with_fallback(&primary, &fallback, 1.0);
+    assert_eq!(merged.total_weight(), 3.0);
+}
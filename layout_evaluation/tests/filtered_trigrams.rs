@@ -0,0 +1,38 @@
+mod common;
+
+use layout_evaluation::ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper};
+
+use common::layout;
+
+fn mapper(text: &str) -> OnDemandNgramMapper {
+    common::ngram_mapper(text)
+}
+
+/// Total weight of the mapped trigrams and of the filtered ones
+fn weights(text: &str) -> (f64, f64) {
+    let layout = layout();
+    let mapped = mapper(text).map_trigrams(&layout);
+    (
+        mapped.grams.iter().map(|(_, w)| w).sum(),
+        mapped.weight_filtered,
+    )
+}
+
+#[test]
+fn filtered_weight_completes_total_weight() {
+    // capitals of the same hand share a shift key: the trigrams with consecutive shifts are
+    // filtered (the shift key is held)
+    let (same_hand, same_hand_filtered) = weights("UIA");
+    // alternating hands use the left and right shift keys
+    let (alternating, alternating_filtered) = weights("USA");
+
+    assert!(same_hand_filtered > 0.0);
+    assert_eq!(alternating_filtered, 0.0);
+    assert!(same_hand < alternating);
+    assert!((same_hand + same_hand_filtered - alternating).abs() < 1e-9);
+}
+
+#[test]
+fn nothing_is_filtered_without_modifiers() {
+    assert_eq!(weights("uia"), (1.0, 0.0));
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::{Finger, Hand},
+    keyboard::{Keyboard, KeyboardError},
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::{
+        bigram_metrics::{
+            finger_repeats::FingerRepeats, movement_pattern::MovementPattern, BigramMetric,
+        },
+        unigram_metrics::{
+            finger_balance::{self, FingerBalance},
+            UnigramMetric,
+        },
+    },
+};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+/// The standard keyboard with the given capability of the left pinky
+fn layout(left_pinky: f64) -> Layout {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let mut keyboard = Keyboard::from_yaml_object(layout_config.keyboard);
+    keyboard
+        .finger_capabilities
+        .set(&Hand::Left, &Finger::Pinky, left_pinky);
+    let layout_generator =
+        NeoLayoutGenerator::from_object(layout_config.base_layout, Arc::new(keyboard));
+
+    layout_generator.generate(LAYOUT).unwrap()
+}
+
+fn bigram_cost(metric: &dyn BigramMetric, layout: &Layout, bigram: (char, char)) -> f64 {
+    let k1 = layout.get_layerkey_for_symbol(&bigram.0).unwrap();
+    let k2 = layout.get_layerkey_for_symbol(&bigram.1).unwrap();
+    metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+}
+
+fn finger_balance_cost(params: &finger_balance::Parameters, layout: &Layout) -> f64 {
+    // the left pinky ("u") is used more than the other fingers
+    let unigrams: Vec<_> = [('u', 5.0), ('i', 1.0), ('a', 1.0), ('e', 1.0), ('n', 2.0)]
+        .iter()
+        .map(|(c, w)| (layout.get_layerkey_for_symbol(c).unwrap(), *w))
+        .collect();
+
+    FingerBalance::new(params)
+        .total_cost(&unigrams, None, layout)
+        .0
+}
+
+#[test]
+fn weak_finger_increases_finger_repeats() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let metric = FingerRepeats::new(&eval_params.metrics.finger_repeats.unwrap().params);
+    let normal = layout(1.0);
+    let weak = layout(0.5);
+
+    // "x" and "u" are typed with the left pinky
+    let cost = bigram_cost(&metric, &normal, ('x', 'u'));
+    assert!(cost > 0.0);
+    assert!((bigram_cost(&metric, &weak, ('x', 'u')) - 2.0 * cost).abs() < 1e-9);
+    // "d" and "r" are typed with the right ring finger
+    assert_eq!(
+        bigram_cost(&metric, &weak, ('d', 'r')),
+        bigram_cost(&metric, &normal, ('d', 'r'))
+    );
+}
+
+#[test]
+fn weak_finger_increases_movement_pattern() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let metric = MovementPattern::new(&eval_params.metrics.movement_pattern.unwrap().params);
+    let normal = layout(1.0);
+    let weak = layout(0.5);
+
+    // "x" is typed with the left pinky, "i" with the left ring finger
+    assert!(bigram_cost(&metric, &weak, ('x', 'i')) > bigram_cost(&metric, &normal, ('x', 'i')));
+    // "v" and "a" are typed with the ring and middle finger
+    assert_eq!(
+        bigram_cost(&metric, &weak, ('v', 'a')),
+        bigram_cost(&metric, &normal, ('v', 'a'))
+    );
+}
+
+#[test]
+fn weak_finger_increases_finger_balance() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let params = eval_params.metrics.finger_balance.unwrap().params;
+    assert!(
+        finger_balance_cost(&params, &layout(0.5)) > finger_balance_cost(&params, &layout(1.0))
+    );
+
+    // without intended loads, the capabilities are used
+    let params = finger_balance::Parameters {
+        intended_loads: None,
+    };
+    assert!(
+        finger_balance_cost(&params, &layout(0.5)) > finger_balance_cost(&params, &layout(1.0))
+    );
+}
+
+#[test]
+fn capabilities_are_read_from_keyboard_config() {
+    let read_config = |capability: f64| {
+        common::layout_config_from_yaml_override(
+            LAYOUT_CONFIG,
+            "finger_capabilities",
+            &format!(
+                "keyboard:\n  finger_capabilities: {{Right: {{Ring: {}}}}}\n",
+                capability
+            ),
+        )
+    };
+
+    let keyboard = Keyboard::from_yaml_object(read_config(0.7).unwrap().keyboard);
+    assert_eq!(
+        *keyboard
+            .finger_capabilities
+            .get(&Hand::Right, &Finger::Ring),
+        0.7
+    );
+    assert_eq!(
+        *keyboard.finger_capabilities.get(&Hand::Left, &Finger::Ring),
+        1.0
+    );
+
+    let err = read_config(0.0).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KeyboardError>(),
+        Some(KeyboardError::InvalidFingerCapability(
+            Hand::Right,
+            Finger::Ring,
+            _
+        ))
+    ));
+}
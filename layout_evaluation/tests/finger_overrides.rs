@@ -0,0 +1,80 @@
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::{Finger, MatrixPosition},
+    keyboard::{FingerOverride, Keyboard, KeyboardError},
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::bigram_metrics::{
+        finger_repeats::{FingerRepeats, Parameters},
+        BigramMetric,
+    },
+};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+/// Neo with "x" on the key of "ä" (bottom row, left middle finger)
+const LAYOUT: &str = "ävlcwkhgfqyßuiaeosnrtdüöxpzbm,.j";
+
+/// The angle mod: the left keys of the bottom row are pressed by the finger to the right
+const ANGLE_MOD: &str = "
+- {position: [2, 3], hand: Left, finger: Ring}
+- {position: [3, 3], hand: Left, finger: Middle}
+- {position: [4, 3], hand: Left, finger: Index}
+";
+
+fn keyboard() -> Keyboard {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    Keyboard::from_yaml_object(layout_config.keyboard)
+}
+
+fn layout(keyboard: Keyboard) -> Layout {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let layout_generator =
+        NeoLayoutGenerator::from_object(layout_config.base_layout, Arc::new(keyboard));
+
+    layout_generator.generate(LAYOUT).unwrap()
+}
+
+fn finger_repeats_cost(layout: &Layout, bigram: (char, char)) -> f64 {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let params: Parameters = eval_params.metrics.finger_repeats.unwrap().params;
+    let metric = FingerRepeats::new(&params);
+
+    let k1 = layout.get_layerkey_for_symbol(&bigram.0).unwrap();
+    let k2 = layout.get_layerkey_for_symbol(&bigram.1).unwrap();
+    metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+}
+
+#[test]
+fn angle_mod_changes_finger_repeats() {
+    let overrides: Vec<FingerOverride> = serde_yaml::from_str(ANGLE_MOD).unwrap();
+
+    let standard = layout(keyboard());
+    let angle_mod = layout(keyboard().with_finger_overrides(&overrides).unwrap());
+
+    let x = angle_mod.get_layerkey_for_symbol(&'x').unwrap();
+    assert_eq!(x.key.matrix_position, MatrixPosition(4, 3));
+    assert_eq!(x.key.finger, Finger::Index);
+
+    // "x" and "c" are typed by the middle and the index finger on a standard keyboard, but
+    // both by the index finger with the angle mod
+    assert_eq!(finger_repeats_cost(&standard, ('x', 'c')), 0.0);
+    assert!(finger_repeats_cost(&angle_mod, ('x', 'c')) > 0.0);
+}
+
+#[test]
+fn overridden_positions_need_to_exist() {
+    let overrides: Vec<FingerOverride> =
+        serde_yaml::from_str("- {position: [9, 9], hand: Left, finger: Ring}").unwrap();
+
+    assert!(matches!(
+        keyboard().with_finger_overrides(&overrides),
+        Err(KeyboardError::UnknownOverridePosition(MatrixPosition(9, 9)))
+    ));
+}
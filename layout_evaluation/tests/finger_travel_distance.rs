@@ -0,0 +1,49 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::metrics::bigram_metrics::{
+    finger_travel_distance::{FingerTravelDistance, Parameters},
+    BigramMetric,
+};
+
+use common::layout;
+
+fn cost(metric: &FingerTravelDistance, layout: &Layout, bigram: (char, char)) -> f64 {
+    let k1 = layout.get_layerkey(&layout.get_layerkey_index_for_symbol(&bigram.0).unwrap());
+    let k2 = layout.get_layerkey(&layout.get_layerkey_index_for_symbol(&bigram.1).unwrap());
+    metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+}
+
+#[test]
+fn two_row_jump_travels_farther_than_adjacent_row() {
+    let layout = layout();
+    let metric = FingerTravelDistance::new(&Parameters {
+        finger_speed_factors: Default::default(),
+    });
+
+    // "x", "u", and "ü" are typed by the left pinky in the top, home, and bottom row
+    let adjacent = cost(&metric, &layout, ('x', 'u'));
+    let jump = cost(&metric, &layout, ('x', 'ü'));
+
+    assert!((19.0..21.0).contains(&adjacent), "{}", adjacent);
+    assert!(jump > 1.9 * adjacent, "{} vs. {}", jump, adjacent);
+
+    // no cost for different fingers
+    assert_eq!(cost(&metric, &layout, ('x', 'v')), 0.0);
+}
+
+#[test]
+fn distances_are_scaled_by_finger_speed_factors() {
+    let layout = layout();
+    let plain = FingerTravelDistance::new(&Parameters {
+        finger_speed_factors: Default::default(),
+    });
+    let slow_pinky = FingerTravelDistance::new(&Parameters {
+        finger_speed_factors: std::iter::once((keyboard_layout::key::Finger::Pinky, 2.0)).collect(),
+    });
+
+    assert_eq!(
+        cost(&slow_pinky, &layout, ('x', 'u')),
+        2.0 * cost(&plain, &layout, ('x', 'u'))
+    );
+}
@@ -0,0 +1,97 @@
+use layout_evaluation::{
+    graphemes::{clusters, GraphemeClusters},
+    ngrams::{Bigrams, ChunkOverlap, TextOptions, Trigrams, Unigrams},
+};
+
+/// A family (joined by zero-width joiners) and a thumb with skin tone modifier
+const TEXT: &str = "hi \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} ok \u{1F44D}\u{1F3FD}!";
+
+fn options(grapheme_clusters: GraphemeClusters) -> TextOptions {
+    TextOptions {
+        grapheme_clusters,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn emoji_sequences_are_clusters() {
+    let c: Vec<&str> = clusters(TEXT).collect();
+    assert_eq!(c.len(), 10);
+    assert_eq!(c[3], "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}");
+    assert_eq!(c[8], "\u{1F44D}\u{1F3FD}");
+    // zero-width joiners only join pictographic characters
+    let c: Vec<&str> = clusters("a\u{200D}b").collect();
+    assert_eq!(c, vec!["a\u{200D}", "b"]);
+}
+
+#[test]
+fn chars_split_clusters() {
+    let (unigrams, weights) =
+        Unigrams::from_text_with_options(TEXT, &options(GraphemeClusters::Chars)).unwrap();
+    assert_eq!(unigrams.grams, Unigrams::from_text(TEXT).unwrap().grams);
+    assert_eq!(unigrams.grams.get(&'\u{200D}'), Some(&2.0));
+    assert_eq!(weights.skipped, 0.0);
+}
+
+#[test]
+fn first_char_of_clusters() {
+    let options = options(GraphemeClusters::FirstChar);
+
+    let (unigrams, _) = Unigrams::from_text_with_options(TEXT, &options).unwrap();
+    assert_eq!(unigrams.total_weight(), 10.0);
+    assert_eq!(unigrams.grams.get(&'\u{1F468}'), Some(&1.0));
+    assert_eq!(unigrams.grams.get(&'\u{1F44D}'), Some(&1.0));
+    for c in ['\u{200D}', '\u{1F469}', '\u{1F467}', '\u{1F3FD}'] {
+        assert!(!unigrams.grams.contains_key(&c), "{:?}", c);
+    }
+
+    let (bigrams, _) = Bigrams::from_text_with_options(TEXT, &options).unwrap();
+    assert_eq!(bigrams.total_weight(), 9.0);
+    assert_eq!(bigrams.grams.get(&(' ', '\u{1F468}')), Some(&1.0));
+    assert_eq!(bigrams.grams.get(&('\u{1F468}', ' ')), Some(&1.0));
+    assert_eq!(bigrams.grams.get(&('\u{1F44D}', '!')), Some(&1.0));
+}
+
+#[test]
+fn skip_clusters() {
+    let options = options(GraphemeClusters::Skip);
+
+    let (unigrams, weights) = Unigrams::from_text_with_options(TEXT, &options).unwrap();
+    assert_eq!(unigrams.total_weight(), 8.0);
+    assert_eq!(weights.skipped, 2.0);
+    assert!(unigrams.grams.keys().all(|c| c.is_ascii()));
+
+    // ngrams containing a skipped cluster are not collected
+    let (bigrams, weights) = Bigrams::from_text_with_options(TEXT, &options).unwrap();
+    assert_eq!(weights.skipped, 4.0);
+    assert_eq!(bigrams.total_weight() + weights.skipped, 9.0);
+    assert!(bigrams
+        .grams
+        .keys()
+        .all(|(c1, c2)| c1.is_ascii() && c2.is_ascii()));
+
+    let (trigrams, weights) = Trigrams::from_text_with_options(TEXT, &options).unwrap();
+    assert_eq!(trigrams.total_weight() + weights.skipped, 8.0);
+    assert_eq!(trigrams.grams.get(&('o', 'k', ' ')), Some(&1.0));
+}
+
+#[test]
+fn clusters_are_carried_across_chunks() {
+    let options = options(GraphemeClusters::FirstChar);
+    let (whole, _) = Trigrams::from_text_with_options(TEXT, &options).unwrap();
+
+    // chunks end before whitespace (as with `corpus::for_each_chunk`)
+    let chunks = [
+        "hi",
+        " \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+        " ok",
+        " \u{1F44D}\u{1F3FD}!",
+    ];
+    let mut overlap = ChunkOverlap::with_options(3, &options);
+    let mut streamed = Trigrams::from_text("").unwrap();
+    for chunk in chunks {
+        streamed.add_text_with_options(&overlap.join(chunk), &options);
+    }
+
+    assert_eq!(streamed.grams, whole.grams);
+}
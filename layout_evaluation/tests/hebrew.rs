@@ -0,0 +1,91 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::{Bigrams, CombiningMarks, Trigrams, Unigrams},
+};
+
+use std::fs;
+
+use common::EVALUATION_PARAMETERS;
+
+const CORPUS: &str = "tests/fixtures/hebrew.txt";
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard_hebrew.yml";
+const LAYOUT: &str = "/'קראטוןםפ,;שדגכעיחלךףזסבהנמצתץ.";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+fn ngram_mapper(text: &str, combining_marks: CombiningMarks) -> OnDemandNgramMapper {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+
+    let (unigrams, bigrams, trigrams) = combining_marks.apply(
+        &Unigrams::from_text(text).unwrap(),
+        &Bigrams::from_text(text).unwrap(),
+        &Trigrams::from_text(text).unwrap(),
+    );
+
+    OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper)
+}
+
+fn not_found_weights(mapper: &OnDemandNgramMapper, layout: &Layout) -> [f64; 3] {
+    [
+        mapper.map_unigrams(layout).weight_not_found,
+        mapper.map_bigrams(layout).weight_not_found,
+        mapper.map_trigrams(layout).weight_not_found,
+    ]
+}
+
+#[test]
+fn niqqud_is_removed_from_ngrams() {
+    let text = fs::read_to_string(CORPUS).unwrap();
+    let layout = layout();
+
+    let mapper = ngram_mapper(&text, CombiningMarks::Keep);
+    assert!(not_found_weights(&mapper, &layout).iter().all(|w| *w > 0.0));
+
+    let mapper = ngram_mapper(&text, CombiningMarks::Auto);
+    assert_eq!(not_found_weights(&mapper, &layout), [0.0, 0.0, 0.0]);
+
+    // compared to removing the marks from the text itself, unigrams are identical and only
+    // bigrams across multiple consecutive marks are missing
+    let stripped = CombiningMarks::Auto.apply_to_text(&text);
+    let mapper_stripped = ngram_mapper(&stripped, CombiningMarks::Keep);
+    assert_eq!(
+        mapper.map_unigrams(&layout).weight_found,
+        mapper_stripped.map_unigrams(&layout).weight_found,
+    );
+    let bigram_weight = mapper.map_bigrams(&layout).weight_found;
+    let bigram_weight_stripped = mapper_stripped.map_bigrams(&layout).weight_found;
+    assert!(bigram_weight <= bigram_weight_stripped);
+    assert!(bigram_weight > 0.95 * bigram_weight_stripped);
+}
+
+#[test]
+fn evaluate_hebrew_layout() {
+    let text = fs::read_to_string(CORPUS).unwrap();
+    let text = CombiningMarks::Auto.apply_to_text(&text);
+    let layout = layout();
+
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let evaluator = Evaluator::default(Box::new(ngram_mapper(&text, CombiningMarks::Keep)))
+        .default_metrics(&eval_params.metrics);
+
+    let result = evaluator.evaluate_layout(&layout);
+    assert!(result.total_cost().is_finite());
+    for metric_results in result.iter() {
+        assert_eq!(metric_results.not_found_weight, 0.0);
+    }
+
+    // right-to-left symbols are isolated in the plot
+    let plot = layout.plot();
+    assert!(plot.contains("\u{2068}"));
+    assert_eq!(
+        plot.matches('\u{2068}').count(),
+        plot.matches('\u{2069}').count()
+    );
+}
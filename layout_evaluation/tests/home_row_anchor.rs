@@ -0,0 +1,84 @@
+mod common;
+
+use keyboard_layout::{
+    key::{Finger, Hand},
+    layout::{LayerKey, Layout},
+};
+use layout_evaluation::metrics::unigram_metrics::{
+    home_row_anchor::{HomeRowAnchor, Parameters},
+    UnigramMetric,
+};
+
+use ahash::AHashMap;
+
+use common::layout;
+
+fn params(min_home_shares: &[((Hand, Finger), f64)]) -> Parameters {
+    Parameters {
+        min_home_shares: min_home_shares.iter().cloned().collect(),
+        finger_weights: [((Hand::Left, Finger::Pinky), 2.0)]
+            .iter()
+            .cloned()
+            .collect(),
+        home_keys: AHashMap::default(),
+    }
+}
+
+/// Unigrams with three presses of the left pinky's top key ('x') per press of its home key ('u').
+fn unigrams(layout: &Layout) -> Vec<(&LayerKey, f64)> {
+    let home = layout.get_layerkey_for_symbol(&'u').unwrap();
+    let top = layout.get_layerkey_for_symbol(&'x').unwrap();
+    for k in [home, top] {
+        assert_eq!((k.key.hand, k.key.finger), (Hand::Left, Finger::Pinky));
+    }
+
+    vec![
+        (home, 1.0),
+        (top, 3.0),
+        (layout.get_layerkey_for_symbol(&'n').unwrap(), 1.0),
+    ]
+}
+
+#[test]
+fn shortfall_is_penalized() {
+    let layout = layout();
+    let unigrams = unigrams(&layout);
+    let metric = HomeRowAnchor::new(&params(&[
+        ((Hand::Left, Finger::Pinky), 0.5),
+        ((Hand::Right, Finger::Index), 0.5),
+    ]));
+
+    let (cost, message) = metric.total_cost(&unigrams, None, &layout);
+
+    // the left pinky's home key carries 1.0 instead of 0.5 * 4.0 (with weight 2.0)
+    assert!((cost - 2.0).abs() < 1e-9);
+    let message = message.unwrap();
+    assert!(message.contains("L Pinky 25.0% (< 50%)"), "{}", message);
+    assert!(message.ends_with("R Index 100.0%"), "{}", message);
+}
+
+#[test]
+fn explicit_home_keys() {
+    let layout = layout();
+    let unigrams = unigrams(&layout);
+    let mut params = params(&[((Hand::Left, Finger::Pinky), 0.5)]);
+    let top = layout.get_layerkey_for_symbol(&'x').unwrap();
+    params
+        .home_keys
+        .insert((Hand::Left, Finger::Pinky), top.key.matrix_position);
+    let metric = HomeRowAnchor::new(&params);
+
+    let (cost, message) = metric.total_cost(&unigrams, None, &layout);
+
+    assert_eq!(cost, 0.0);
+    assert!(message.unwrap().contains("L Pinky 75.0%"));
+}
+
+#[test]
+fn zero_minimums_are_a_no_op() {
+    let layout = layout();
+    let unigrams = unigrams(&layout);
+    let metric = HomeRowAnchor::new(&params(&[((Hand::Left, Finger::Pinky), 0.0)]));
+
+    assert_eq!(metric.total_cost(&unigrams, None, &layout), (0.0, None));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{
+        on_demand_ngram_mapper::{NgramMapperConfig, OnDemandNgramMapper},
+        MappedTrigrams, NgramMapper,
+    },
+    ngrams::IncreaseCommonNgramsConfig,
+};
+
+use std::collections::HashMap;
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// Frequent trigrams, some of them with capital letters (i.e. with modifiers to split off)
+const TEXT: &str = "Die Die Die Die Die die der das ABC xyz";
+
+fn config(enabled: bool) -> NgramMapperConfig {
+    let mut config = common::ngram_mapper_config();
+    config.increase_common_trigrams = IncreaseCommonNgramsConfig {
+        enabled,
+        critical_fraction: 0.05,
+        factor: 3.0,
+        total_weight_threshold: 5.0,
+    };
+    config
+}
+
+fn mapper(enabled: bool) -> OnDemandNgramMapper {
+    common::ngram_mapper_with(TEXT, config(enabled))
+}
+
+fn total(trigrams: &MappedTrigrams) -> f64 {
+    trigrams.grams.iter().map(|(_, w)| *w).sum()
+}
+
+#[test]
+fn common_trigrams_are_increased_after_splitting_modifiers() {
+    let layout = layout();
+    let plain = mapper(false).map_trigrams(&layout);
+    let increased = mapper(true).map_trigrams(&layout);
+    assert_eq!(plain.grams.len(), increased.grams.len());
+
+    let increased_weights: HashMap<(char, char, char), f64> = increased
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ((k1.symbol, k2.symbol, k3.symbol), *w))
+        .collect();
+
+    let critical_point = 0.05 * total(&plain);
+    let mut n_increased = 0;
+    let mut n_modifier_trigrams = 0;
+    for ((k1, k2, k3), plain_weight) in plain.grams.iter() {
+        let weight = increased_weights[&(k1.symbol, k2.symbol, k3.symbol)];
+        if *plain_weight > critical_point {
+            let expected = plain_weight + (plain_weight - critical_point) * 2.0;
+            assert!((weight - expected).abs() < 1e-9);
+            n_increased += 1;
+            if [k1, k2, k3].iter().any(|k| k.is_modifier.is_some()) {
+                n_modifier_trigrams += 1;
+            }
+        } else {
+            assert_eq!(*plain_weight, weight);
+        }
+    }
+    assert!(n_increased > 0);
+    // trigrams with (split off) modifiers are increased like all others
+    assert!(n_modifier_trigrams > 0);
+
+    // the added weight is part of the total weight metrics normalize with
+    let added = total(&increased) - total(&plain);
+    assert!(added > 0.0);
+    assert!((increased.weight_found - plain.weight_found - added).abs() < 1e-9);
+    assert_eq!(increased.weight_not_found, plain.weight_not_found);
+}
+
+#[test]
+fn shared_mapping_increases_the_same_trigrams() {
+    let layout = layout();
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let evaluator =
+        Evaluator::default(Box::new(mapper(true))).default_metrics(&eval_params.metrics);
+    let shared = evaluator.with_shared_mapping(&[&layout]);
+
+    let cost = evaluator.evaluate_layout(&layout).total_cost();
+    let shared_cost = shared.evaluate_layout(&layout).total_cost();
+    assert!((cost - shared_cost).abs() < 1e-9);
+}
@@ -0,0 +1,96 @@
+use layout_evaluation::{
+    ngrams::{read_ngrams_dir, Bigrams, Trigrams, Unigrams},
+    results::MetricType,
+};
+
+use std::{fs, path::PathBuf};
+
+const CORPUS: &str = r#"{
+    "language": "test",
+    "characters": { "a": 3, "ä": 2.5, "\n": 1 },
+    "bigrams": { "aä": 2, "\na": 1 },
+    "trigrams": { "aäa": 1 },
+    "skipgrams": { "aa": 1 }
+}"#;
+
+/// Write a corpus to a file with the given name in a new directory.
+fn corpus_file(name: &str, data: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_json_corpus_{}_{}",
+        name.replace('.', "_"),
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let p = dir.join(name);
+    fs::write(&p, data).unwrap();
+
+    p
+}
+
+#[test]
+fn read_json_maps() {
+    let unigrams = Unigrams::from_json(CORPUS).unwrap();
+    assert_eq!(unigrams.grams.len(), 3);
+    assert_eq!(unigrams.grams.get(&'ä'), Some(&2.5));
+    assert_eq!(unigrams.grams.get(&'\n'), Some(&1.0));
+
+    let bigrams = Bigrams::from_json(CORPUS).unwrap();
+    assert_eq!(bigrams.grams.get(&('a', 'ä')), Some(&2.0));
+    assert_eq!(bigrams.grams.get(&('\n', 'a')), Some(&1.0));
+
+    let trigrams = Trigrams::from_json(CORPUS).unwrap();
+    assert_eq!(trigrams.grams.get(&('a', 'ä', 'a')), Some(&1.0));
+}
+
+#[test]
+fn invalid_json_corpora() {
+    let err = Unigrams::from_json(r#"{ "characters": { "ab": 1 } }"#).unwrap_err();
+    assert!(err.to_string().contains("expected 1 character(s), found 2"));
+
+    let err = Bigrams::from_json(r#"{ "characters": { "a": 1 } }"#).unwrap_err();
+    assert!(err.to_string().contains("no 'bigrams' map"));
+
+    let err = Unigrams::from_json(r#"{ "characters": { "a": "many" } }"#).unwrap_err();
+    assert!(err.to_string().contains("Invalid weight"));
+
+    // lone surrogates are no valid characters
+    let err = Unigrams::from_json(r#"{ "characters": { "\ud800": 1 } }"#).unwrap_err();
+    assert!(format!("{:#}", err).starts_with("Invalid JSON corpus: "));
+}
+
+#[test]
+fn detect_json_files() {
+    // by extension
+    let p = corpus_file("corpus.json", CORPUS);
+    let unigrams = Unigrams::from_file(p.to_str().unwrap()).unwrap();
+    assert_eq!(unigrams.grams.get(&'a'), Some(&3.0));
+    // the same file for another order
+    let bigrams = Bigrams::from_file(p.to_str().unwrap()).unwrap();
+    assert_eq!(bigrams.grams.get(&('a', 'ä')), Some(&2.0));
+
+    // by content
+    let p = corpus_file("2-grams.txt", CORPUS);
+    let bigrams = Bigrams::from_file_with_cache(p.to_str().unwrap(), false).unwrap();
+    assert_eq!(bigrams.grams.get(&('\n', 'a')), Some(&1.0));
+}
+
+#[test]
+fn read_json_corpus_instead_of_dir() {
+    let p = corpus_file("complete.json", CORPUS);
+    let (unigrams, bigrams, trigrams, missing) = read_ngrams_dir(p.to_str().unwrap()).unwrap();
+    assert_eq!(unigrams.total_weight(), 6.5);
+    assert_eq!(bigrams.total_weight(), 3.0);
+    assert_eq!(trigrams.total_weight(), 1.0);
+    assert!(missing.is_empty());
+
+    let p = corpus_file(
+        "no_trigrams.json",
+        r#"{ "characters": { "a": 1 }, "bigrams": {} }"#,
+    );
+    let (_, _, trigrams, missing) = read_ngrams_dir(p.to_str().unwrap()).unwrap();
+    assert!(trigrams.grams.is_empty());
+    assert_eq!(missing, vec![MetricType::Trigram]);
+
+    let p = corpus_file("empty.json", "{}");
+    assert!(read_ngrams_dir(p.to_str().unwrap()).is_err());
+}
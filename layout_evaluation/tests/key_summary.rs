@@ -0,0 +1,39 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+
+use common::LAYOUT_CONFIG;
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog. \
+                    Zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich.";
+
+fn layout(layout_str: &str) -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, layout_str)
+}
+
+#[test]
+fn key_costs_add_up_to_total_cost() {
+    let evaluator = common::evaluator(TEXT);
+
+    for layout_str in [
+        "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j",
+        "qwertzuiopüasdfghjklöäyxcvbnm,.ß",
+    ] {
+        let layout = layout(layout_str);
+        let total_cost = evaluator.evaluate_layout(&layout).total_cost();
+        let summary = evaluator.key_summary(&layout);
+
+        assert!((summary.total_cost - total_cost).abs() < 1e-9);
+        assert!((summary.attributed_cost() - total_cost).abs() < 1e-6 * total_cost);
+        for key in summary.keys.iter() {
+            let metrics_cost: f64 = key.metrics.iter().map(|m| m.cost).sum();
+            assert!((key.cost - metrics_cost).abs() < 1e-9);
+        }
+
+        let top = summary.top(3);
+        assert_eq!(top.keys.len(), 3);
+        assert!(top.keys[0].cost >= top.keys[1].cost && top.keys[1].cost >= top.keys[2].cost);
+        assert!((top.attributed_cost() - total_cost).abs() < 1e-6 * total_cost);
+    }
+}
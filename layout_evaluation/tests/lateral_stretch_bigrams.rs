@@ -0,0 +1,126 @@
+mod common;
+
+use keyboard_layout::{
+    key::{Finger, Hand, MatrixPosition},
+    layout::{LayerKey, Layout},
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::bigram_metrics::{
+        lateral_stretch_bigrams::{self, LateralStretchBigrams},
+        BigramMetric,
+    },
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+// positions of QWERTY keys on the standard keyboard
+const QWERTY_B: MatrixPosition = MatrixPosition(6, 3);
+const QWERTY_C: MatrixPosition = MatrixPosition(4, 3);
+const QWERTY_E: MatrixPosition = MatrixPosition(4, 1);
+const QWERTY_R: MatrixPosition = MatrixPosition(5, 1);
+
+fn params() -> lateral_stretch_bigrams::Parameters {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.metrics.lateral_stretch_bigrams.unwrap().params
+}
+
+/// The base layer key at the given position
+fn key(layout: &Layout, pos: MatrixPosition) -> &LayerKey {
+    layout
+        .layerkeys
+        .iter()
+        .find(|k| k.layer == 0 && k.key.matrix_position == pos)
+        .unwrap()
+}
+
+fn bigram_cost(
+    metric: &dyn BigramMetric,
+    layout: &Layout,
+    bigram: (MatrixPosition, MatrixPosition),
+) -> f64 {
+    let k1 = key(layout, bigram.0);
+    let k2 = key(layout, bigram.1);
+    metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+}
+
+#[test]
+fn column_skips_of_adjacent_fingers_are_penalized() {
+    let layout = layout();
+    let metric = LateralStretchBigrams::new(&params());
+
+    let b = &key(&layout, QWERTY_B).key;
+    let e = &key(&layout, QWERTY_E).key;
+    assert_eq!((b.hand, b.finger), (Hand::Left, Finger::Index));
+    assert_eq!((e.hand, e.finger), (Hand::Left, Finger::Middle));
+
+    // "be": index on the center column, then middle finger two columns apart
+    let cost = bigram_cost(&metric, &layout, (QWERTY_B, QWERTY_E));
+    assert!(cost > 0.0);
+    assert_eq!(bigram_cost(&metric, &layout, (QWERTY_E, QWERTY_B)), cost);
+
+    // "ce": same finger, no lateral stretch
+    assert_eq!(bigram_cost(&metric, &layout, (QWERTY_C, QWERTY_E)), 0.0);
+    // "re": adjacent fingers on adjacent columns
+    assert_eq!(bigram_cost(&metric, &layout, (QWERTY_R, QWERTY_E)), 0.0);
+}
+
+#[test]
+fn distance_exponent_and_finger_pair_weights_are_applied() {
+    let layout = layout();
+    let mut params = params();
+
+    params.distance_exponent = 1.0;
+    params.finger_pair_weights = vec![lateral_stretch_bigrams::FingerPairWeight {
+        fingers: (Finger::Middle, Finger::Index),
+        weight: 3.0,
+    }];
+    let linear = bigram_cost(
+        &LateralStretchBigrams::new(&params),
+        &layout,
+        (QWERTY_B, QWERTY_E),
+    );
+    assert_eq!(linear, 6.0);
+
+    params.distance_exponent = 2.0;
+    let squared = bigram_cost(
+        &LateralStretchBigrams::new(&params),
+        &layout,
+        (QWERTY_B, QWERTY_E),
+    );
+    assert_eq!(squared, 12.0);
+
+    // unlisted finger pairs are not penalized
+    params.finger_pair_weights.clear();
+    assert_eq!(
+        bigram_cost(
+            &LateralStretchBigrams::new(&params),
+            &layout,
+            (QWERTY_B, QWERTY_E)
+        ),
+        0.0
+    );
+}
+
+#[test]
+fn worst_bigrams_are_reported() {
+    let layout = layout();
+    let metric = LateralStretchBigrams::new(&params());
+
+    let bigrams = [
+        ((key(&layout, QWERTY_B), key(&layout, QWERTY_E)), 2.0),
+        ((key(&layout, QWERTY_C), key(&layout, QWERTY_E)), 5.0),
+    ];
+    let (cost, message) = metric.total_cost(&bigrams, Some(7.0), &layout);
+    assert_eq!(
+        cost,
+        bigram_cost(&metric, &layout, (QWERTY_B, QWERTY_E)) * 2.0
+    );
+    let message = message.unwrap();
+    assert!(message.contains("Worst"), "{}", message);
+    assert!(
+        message.contains(key(&layout, QWERTY_B).symbol),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use keyboard_layout::{key::MatrixPosition, layout::LayerKey};
+use layout_evaluation::ngram_mapper::{
+    on_demand_ngram_mapper::{LayerSplitModifiersConfig, OnDemandNgramMapper},
+    MappedTrigrams, NgramMapper,
+};
+
+use common::layout;
+
+/// A shifted letter followed by a symbol of the third layer (index 2)
+const TEXT: &str = "X[e";
+
+/// The modifiers of the shift layer and of the third layer
+const SHIFT: [MatrixPosition; 2] = [MatrixPosition(0, 3), MatrixPosition(18, 3)];
+const MOD3: [MatrixPosition; 2] = [MatrixPosition(0, 2), MatrixPosition(18, 2)];
+
+/// A mapper with the given overrides of the modifier splitting
+fn mapper(layers: &[(u8, bool)]) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.split_modifiers.layers = layers
+        .iter()
+        .map(|(layer, enabled)| {
+            (
+                *layer,
+                LayerSplitModifiersConfig {
+                    enabled: *enabled,
+                    same_key_mod_factor: None,
+                },
+            )
+        })
+        .collect();
+
+    common::ngram_mapper_with(TEXT, config)
+}
+
+fn keys<'s>(trigrams: &MappedTrigrams<'s>) -> Vec<&'s LayerKey> {
+    trigrams
+        .grams
+        .iter()
+        .flat_map(|((k1, k2, k3), _)| [*k1, *k2, *k3])
+        .collect()
+}
+
+fn is_any(key: &LayerKey, positions: &[MatrixPosition]) -> bool {
+    positions.contains(&key.key.matrix_position)
+}
+
+#[test]
+fn only_shift_layer_is_split() {
+    let layout = layout();
+    let trigrams = mapper(&[(2, false)]).map_trigrams(&layout);
+    let keys = keys(&trigrams);
+
+    // the shifted letter is split into the shift key and its base-layer key ...
+    assert!(keys.iter().any(|k| is_any(k, &SHIFT)));
+    assert!(keys.iter().any(|k| k.symbol == 'x'));
+    assert!(!keys.iter().any(|k| k.symbol == 'X'));
+    // ... while the symbol of the third layer is kept
+    assert!(keys.iter().any(|k| k.symbol == '[' && k.layer == 2));
+    assert!(!keys.iter().any(|k| is_any(k, &MOD3)));
+
+    let presses = trigrams.modifier_presses.unwrap();
+    assert_eq!(presses.len(), 1);
+    assert!(is_any(presses[0].0, &SHIFT));
+}
+
+#[test]
+fn all_layers_are_split_without_overrides() {
+    let layout = layout();
+    let trigrams = mapper(&[]).map_trigrams(&layout);
+    let keys = keys(&trigrams);
+
+    assert!(keys.iter().any(|k| is_any(k, &SHIFT)));
+    assert!(keys.iter().any(|k| is_any(k, &MOD3)));
+    assert!(!keys.iter().any(|k| k.layer > 0));
+}
+
+#[test]
+fn override_matching_the_default_changes_nothing() {
+    let layout = layout();
+    let weights = |trigrams: MappedTrigrams| {
+        let mut weights: Vec<(String, u64)> = trigrams
+            .grams
+            .iter()
+            .map(|((k1, k2, k3), w)| (format!("{}{}{}", k1, k2, k3), w.to_bits()))
+            .collect();
+        weights.sort();
+        weights
+    };
+
+    assert_eq!(
+        weights(mapper(&[(1, true), (2, true)]).map_trigrams(&layout)),
+        weights(mapper(&[]).map_trigrams(&layout))
+    );
+}
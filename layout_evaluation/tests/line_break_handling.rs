@@ -0,0 +1,65 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters, evaluation::Evaluator, ngram_mapper::common::LineBreakHandling,
+};
+
+use std::fs;
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "ab\ncd";
+
+/// The default evaluation parameters with the given value for `exclude_line_breaks`
+fn eval_params(value: &str) -> EvaluationParameters {
+    let data = fs::read_to_string(EVALUATION_PARAMETERS).unwrap();
+    let data = data.replacen(
+        "  exclude_line_breaks: break_before\n",
+        &format!("  exclude_line_breaks: {}\n", value),
+        1,
+    );
+    EvaluationParameters::from_str_with_profile(&data, None).unwrap()
+}
+
+#[test]
+fn boolean_configurations_keep_working() {
+    let handling = |value: &str| eval_params(value).ngram_mapper.exclude_line_breaks;
+
+    assert_eq!(handling("break_before"), LineBreakHandling::BreakBefore);
+    assert_eq!(handling("true"), LineBreakHandling::BreakBefore);
+    assert_eq!(handling("false"), LineBreakHandling::None);
+    assert_eq!(handling("break_after"), LineBreakHandling::BreakAfter);
+    assert_eq!(handling("both"), LineBreakHandling::Both);
+    assert_eq!(handling("none"), LineBreakHandling::None);
+
+    let data = fs::read_to_string(EVALUATION_PARAMETERS).unwrap();
+    let data = data.replacen("  exclude_line_breaks: break_before\n", "", 1);
+    assert_eq!(
+        EvaluationParameters::from_str_with_profile(&data, None)
+            .unwrap()
+            .ngram_mapper
+            .exclude_line_breaks,
+        LineBreakHandling::BreakBefore
+    );
+}
+
+#[test]
+fn bigrams_and_trigrams_honor_the_setting() {
+    let layout = layout();
+    let excluded_weights = |value: &str| {
+        let mapper = common::ngram_mapper_with(TEXT, eval_params(value).ngram_mapper);
+        let stats = Evaluator::default(Box::new(mapper))
+            .mapping_statistics(&layout)
+            .unwrap();
+        (
+            stats.bigrams.excluded_weight,
+            stats.trigrams.excluded_weight,
+        )
+    };
+
+    // bigrams "b\n" and "\nc", trigrams "ab\n", "b\nc", and "\ncd"
+    assert_eq!(excluded_weights("none"), (0.0, 0.0));
+    assert_eq!(excluded_weights("break_before"), (1.0, 2.0));
+    assert_eq!(excluded_weights("break_after"), (1.0, 2.0));
+    assert_eq!(excluded_weights("both"), (2.0, 3.0));
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    ngram_mapper::{
+        common::{LineBreakHandling, LockToggles, ModifierPresses},
+        trigram_mapper::OnDemandTrigramMapper,
+    },
+    ngrams::Trigrams,
+};
+
+use common::EVALUATION_PARAMETERS;
+
+fn index(layout: &Layout, c: char) -> LayerKeyIndex {
+    layout.get_layerkey_index_for_symbol(&c).unwrap()
+}
+
+/// The toggle of the lock layer
+fn toggle(layout: &Layout) -> LayerKeyIndex {
+    let toggles = layout.lock_toggles(&index(layout, '₁')).unwrap();
+    assert_eq!(toggles.len(), 1);
+    toggles[0]
+}
+
+/// Mapped trigrams of the text (as sorted symbols) and the modifier presses
+fn mapped(
+    layout: &Layout,
+    text: &str,
+    lock_toggles: LockToggles,
+) -> (Vec<(String, f64)>, ModifierPresses) {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.split_modifiers.lock_toggles = lock_toggles;
+
+    let mapper = OnDemandTrigramMapper::new(
+        eval_params.ngram_mapper.split_modifiers,
+        eval_params.ngram_mapper.shift_policy,
+        eval_params.ngram_mapper.increase_common_trigrams,
+        true,
+    );
+    let (trigrams, _, _, presses) = mapper.layerkey_indices(
+        &Trigrams::from_text(text).unwrap(),
+        layout,
+        LineBreakHandling::None,
+    );
+
+    let mut trigrams: Vec<_> = trigrams
+        .into_iter()
+        .map(|((k1, k2, k3), w)| {
+            let symbols = [k1, k2, k3]
+                .iter()
+                .map(|k| layout.get_layerkey(k).symbol)
+                .collect();
+            (symbols, w)
+        })
+        .collect();
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (trigrams, presses)
+}
+
+fn expected(trigrams: &[[char; 3]], weight: f64) -> Vec<(String, f64)> {
+    let mut trigrams: Vec<_> = trigrams
+        .iter()
+        .map(|t| (t.iter().collect(), weight))
+        .collect();
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    trigrams
+}
+
+#[test]
+fn layout_exposes_lock_toggles() {
+    let layout = common::lock_layer_layout();
+    let t = toggle(&layout);
+    assert!(layout.get_layerkey(&t).is_modifier.is_lock());
+    assert!(layout.has_lock_layers());
+    assert_eq!(layout.lock_toggles(&index(&layout, 'a')), None);
+    assert_eq!(layout.lock_toggles(&index(&layout, 'A')), None);
+}
+
+#[test]
+fn trigram_entering_and_leaving_lock_layer() {
+    let layout = common::lock_layer_layout();
+    let t = toggle(&layout);
+    let toggle = layout.get_layerkey(&t).symbol;
+
+    let (trigrams, presses) = mapped(&layout, "a₁e", LockToggles::None);
+    assert_eq!(trigrams, expected(&[['a', '1', 'e']], 1.0));
+    assert!(presses.is_empty());
+
+    let entering_and_leaving = [
+        ['a', toggle, '1'],
+        [toggle, '1', toggle],
+        ['1', toggle, 'e'],
+    ];
+    let (trigrams, presses) = mapped(&layout, "a₁e", LockToggles::EveryTransition);
+    assert_eq!(trigrams, expected(&entering_and_leaving, 1.0));
+    assert_eq!(presses[&t], 2.0);
+
+    let (trigrams, presses) = mapped(&layout, "a₁e", LockToggles::Amortized(0.5));
+    assert_eq!(trigrams, expected(&entering_and_leaving, 0.5));
+    assert_eq!(presses[&t], 2.0);
+}
+
+#[test]
+fn run_of_lock_layer_symbols_is_toggled_once() {
+    let layout = common::lock_layer_layout();
+    let t = toggle(&layout);
+    let toggle = layout.get_layerkey(&t).symbol;
+
+    let (trigrams, presses) = mapped(&layout, "₁₂e", LockToggles::EveryTransition);
+    let run = [['1', '2', toggle], ['2', toggle, 'e'], [toggle, '1', '2']];
+    assert_eq!(trigrams, expected(&run, 1.0));
+    assert_eq!(presses[&t], 2.0);
+}
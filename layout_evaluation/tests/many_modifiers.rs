@@ -0,0 +1,107 @@
+mod common;
+
+use keyboard_layout::{config::LayoutConfig, layout::Layout};
+use layout_evaluation::ngram_mapper::NgramMapper;
+
+use common::{ngram_mapper, LAYOUT, LAYOUT_CONFIG};
+
+/// A symbol of the fourth layer
+const SYMBOL: char = '₁';
+
+/// The standard layout config with the given hold modifiers for the fourth layer
+fn layout_config(name: &str, left: &str, right: &str) -> anyhow::Result<LayoutConfig> {
+    common::layout_config_from_yaml_override(
+        LAYOUT_CONFIG,
+        name,
+        &format!(
+            r#"
+base_layout:
+  modifiers:
+    - Left: {{ type: hold, value: [[0,3]] }}
+      Right: {{ type: hold, value: [[18,3]] }}
+    - Left: {{ type: hold, value: [[0,2]] }}
+      Right: {{ type: hold, value: [[18,2]] }}
+    - Left: {{ type: hold, value: [[1,3]] }}
+      Right: {{ type: hold, value: [[16,4]] }}
+    - Left: {{ type: hold, value: {} }}
+      Right: {{ type: hold, value: {} }}
+    - Left: {{ type: hold, value: [[0,2], [1,3]] }}
+      Right: {{ type: hold, value: [[18,2], [16,4]] }}
+"#,
+            left, right
+        ),
+    )
+}
+
+/// The standard layout where the fourth layer requires four modifiers
+fn layout() -> Layout {
+    let layout_config = layout_config(
+        "many_modifiers_four",
+        "[[0,3], [1,3], [0,2], [0,4]]",
+        "[[18,3], [16,4], [18,2], [19,4]]",
+    )
+    .unwrap();
+
+    common::generate_layout_from_config(layout_config, LAYOUT)
+}
+
+/// Symbols of the base-layer key and the modifiers of [`SYMBOL`]
+fn keys(layout: &Layout) -> (char, Vec<char>) {
+    let (base, mods) =
+        layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&SYMBOL).unwrap());
+
+    (
+        layout.get_layerkey(&base).symbol,
+        mods.layerkey_indices()
+            .iter()
+            .map(|m| layout.get_layerkey(m).symbol)
+            .collect(),
+    )
+}
+
+#[test]
+fn four_modifiers_are_all_mapped() {
+    let layout = layout();
+    let (base, mods) = keys(&layout);
+    assert_eq!(mods.len(), 4);
+
+    let mut expected: Vec<char> = mods.clone();
+    expected.push(base);
+    expected.sort_unstable();
+
+    let mapped = ngram_mapper(&SYMBOL.to_string()).map_unigrams(&layout);
+    let mut unigrams: Vec<char> = mapped.grams.iter().map(|(k, _)| k.symbol).collect();
+    unigrams.sort_unstable();
+    assert_eq!(unigrams, expected);
+
+    let text: String = ['a', SYMBOL, 'e'].iter().collect();
+    let trigrams = ngram_mapper(&text).map_trigrams(&layout);
+    assert!(!trigrams.grams.is_empty());
+    for ((k1, k2, k3), _) in trigrams.grams.iter() {
+        for k in [k1, k2, k3].iter() {
+            assert!(
+                k.symbol == 'a' || k.symbol == 'e' || expected.contains(&k.symbol),
+                "{:?}",
+                (k1.symbol, k2.symbol, k3.symbol)
+            );
+        }
+    }
+    // every modifier is part of some trigram
+    for m in mods.iter() {
+        assert!(trigrams
+            .grams
+            .iter()
+            .any(|((k1, k2, k3), _)| [k1, k2, k3].iter().any(|k| k.symbol == *m)));
+    }
+}
+
+#[test]
+fn more_than_four_modifiers_are_rejected() {
+    let err = layout_config(
+        "many_modifiers_five",
+        "[[0,3], [1,3], [0,2], [0,4], [2,4]]",
+        "[[18,3], [16,4], [18,2], [19,4], [17,4]]",
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", err).contains("at most 4"), "{:#}", err);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngram_mapper::{
+    mapping_cache::CacheStatistics, on_demand_ngram_mapper::OnDemandNgramMapper, MappedTrigrams,
+    NgramMapper,
+};
+
+use common::{LAYOUT, LAYOUT_CONFIG};
+
+const OTHER_LAYOUT: &str = "kuü.ävgcljfßhieaodtrnsxyö,qbpwmz";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+fn layout(layout_str: &str) -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, layout_str)
+}
+
+fn mapper(cache_size: usize) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.mapping_cache_size = cache_size;
+    common::ngram_mapper_with(TEXT, config)
+}
+
+/// Trigrams as strings with their weights, sorted
+fn trigrams(mapped: &MappedTrigrams) -> Vec<(String, u64)> {
+    let mut grams: Vec<(String, u64)> = mapped
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| (format!("{}{}{}", k1, k2, k3), w.to_bits()))
+        .collect();
+    grams.sort();
+    grams
+}
+
+#[test]
+fn identical_layout_is_not_mapped_again() {
+    let mapper = mapper(4);
+    let layout1 = layout(LAYOUT);
+    // a separately generated, but identical layout
+    let layout2 = layout(LAYOUT);
+    let first = mapper.map_trigrams(&layout1);
+    let second = mapper.map_trigrams(&layout2);
+
+    assert_eq!(
+        mapper.mapping_cache_statistics(),
+        CacheStatistics { hits: 1, misses: 1 }
+    );
+    assert_eq!(trigrams(&first), trigrams(&second));
+    assert_eq!(first.weight_found, second.weight_found);
+    assert_eq!(first.weight_not_found, second.weight_not_found);
+
+    // clones share the cache
+    mapper.clone().map_trigrams(&layout(LAYOUT));
+    assert_eq!(mapper.mapping_cache_statistics().hits, 2);
+}
+
+#[test]
+fn cached_mapping_equals_uncached_mapping() {
+    let cached = mapper(4);
+    cached.map_trigrams(&layout(LAYOUT));
+
+    assert_eq!(
+        trigrams(&cached.map_trigrams(&layout(LAYOUT))),
+        trigrams(&mapper(0).map_trigrams(&layout(LAYOUT)))
+    );
+}
+
+#[test]
+fn least_recently_used_layout_is_evicted() {
+    let mapper = mapper(1);
+    mapper.map_bigrams(&layout(LAYOUT));
+    mapper.map_bigrams(&layout(OTHER_LAYOUT));
+    mapper.map_bigrams(&layout(LAYOUT));
+    assert_eq!(
+        mapper.mapping_cache_statistics(),
+        CacheStatistics { hits: 0, misses: 3 }
+    );
+
+    mapper.map_bigrams(&layout(LAYOUT));
+    assert_eq!(mapper.mapping_cache_statistics().hits, 1);
+}
+
+#[test]
+fn disabled_cache_maps_every_time() {
+    let mapper = mapper(0);
+    mapper.map_unigrams(&layout(LAYOUT));
+    mapper.map_unigrams(&layout(LAYOUT));
+
+    assert_eq!(
+        mapper.mapping_cache_statistics(),
+        CacheStatistics::default()
+    );
+}
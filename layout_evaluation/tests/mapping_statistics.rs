@@ -0,0 +1,78 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{common::LineBreakHandling, MappingStatistics, NgramMappingStatistics},
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// A capital letter (split into a modifier and its base key), a line break (excluded), and a
+/// symbol that the layout can not generate
+const TEXT: &str = "Ab\ncd☺";
+
+fn evaluator() -> Evaluator {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.exclude_line_breaks = LineBreakHandling::BreakBefore;
+    let mapper = common::ngram_mapper_with(TEXT, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+/// Compare all weights except for the generated one
+fn assert_weights(
+    stats: &NgramMappingStatistics,
+    total_weight: f64,
+    found_weight: f64,
+    not_found_weight: f64,
+    excluded_weight: f64,
+) {
+    assert_eq!(
+        (
+            stats.total_weight,
+            stats.found_weight,
+            stats.not_found_weight,
+            stats.excluded_weight
+        ),
+        (
+            total_weight,
+            found_weight,
+            not_found_weight,
+            excluded_weight
+        )
+    );
+}
+
+#[test]
+fn weights_per_ngram_order() {
+    let layout = layout();
+    let stats = evaluator().mapping_statistics(&layout).unwrap();
+
+    // "A", "b", "\n", "c", "d" are found, "☺" is not
+    assert_weights(&stats.unigrams, 6.0, 5.0, 1.0, 0.0);
+    // "\nc" is excluded, "d☺" is not found
+    assert_weights(&stats.bigrams, 5.0, 3.0, 1.0, 1.0);
+    // "b\nc" and "\ncd" are excluded, "cd☺" is not found
+    assert_weights(&stats.trigrams, 4.0, 1.0, 1.0, 2.0);
+    assert!((stats.unigrams.found_fraction() - 5.0 / 6.0).abs() < 1e-12);
+
+    // the modifier for "A" generates additional ngrams
+    assert!(stats.unigrams.generated_weight > 0.0);
+    assert!(stats.bigrams.generated_weight > 0.0);
+    assert!(stats.trigrams.generated_weight > 0.0);
+}
+
+#[test]
+fn shared_mapping_yields_same_statistics() {
+    let layout = layout();
+    let evaluator = evaluator();
+    let stats = evaluator.mapping_statistics(&layout).unwrap();
+    let shared = evaluator
+        .with_shared_mapping(&[&layout])
+        .mapping_statistics(&layout)
+        .unwrap();
+
+    assert_eq!(shared, stats);
+    assert_ne!(stats, MappingStatistics::default());
+}
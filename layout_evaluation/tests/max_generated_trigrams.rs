@@ -0,0 +1,75 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    ngram_mapper::{common::LineBreakHandling, trigram_mapper::OnDemandTrigramMapper},
+    ngrams::Trigrams,
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// Mapped trigrams of the text (sorted by their indices) for the given limit
+fn mapped_trigrams(text: &str, max_generated: Option<usize>) -> Vec<(String, f64)> {
+    let layout = layout();
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params
+        .ngram_mapper
+        .split_modifiers
+        .max_generated_per_trigram = max_generated;
+
+    let mapper = OnDemandTrigramMapper::new(
+        eval_params.ngram_mapper.split_modifiers,
+        eval_params.ngram_mapper.shift_policy,
+        eval_params.ngram_mapper.increase_common_trigrams,
+        true,
+    );
+    let (trigrams, _, _, _) = mapper.layerkey_indices(
+        &Trigrams::from_text(text).unwrap(),
+        &layout,
+        LineBreakHandling::None,
+    );
+
+    let mut trigrams: Vec<_> = trigrams.into_iter().collect();
+    trigrams.sort_by_key(|(k, _)| *k);
+    trigrams
+        .into_iter()
+        .map(|((k1, k2, k3), w)| {
+            let symbols = [k1, k2, k3]
+                .iter()
+                .map(|k| layout.get_layerkey(k).symbol)
+                .collect();
+            (symbols, w)
+        })
+        .collect()
+}
+
+fn total_weight(trigrams: &[(String, f64)]) -> f64 {
+    trigrams.iter().map(|(_, w)| w).sum()
+}
+
+#[test]
+fn cap_limits_generated_trigrams_and_conserves_weight() {
+    // three symbols of the fourth layer, each requiring two modifiers
+    let text = "₁₂₃";
+    let uncapped = mapped_trigrams(text, None);
+    assert!(uncapped.len() > 8, "{:?}", uncapped);
+
+    let capped = mapped_trigrams(text, Some(8));
+    assert!(capped.len() <= 8, "{:?}", capped);
+    assert!((total_weight(&capped) - total_weight(&uncapped)).abs() < 1e-9);
+
+    // the highest-weight trigram (the base keys of all three symbols) is kept
+    let (best, _) = uncapped
+        .iter()
+        .max_by(|(_, w1), (_, w2)| w1.partial_cmp(w2).unwrap())
+        .unwrap();
+    assert!(capped.iter().any(|(t, _)| t == best));
+}
+
+#[test]
+fn cap_above_generated_count_changes_nothing() {
+    for text in ["₁₂₃", "Xen", "xen"] {
+        let uncapped = mapped_trigrams(text, None);
+        assert_eq!(mapped_trigrams(text, Some(1000)), uncapped);
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use keyboard_layout::{
+    key::{Hand, MatrixPosition},
+    keyboard::KeyboardError,
+    layout::Layout,
+    neo_layout_generator::LayoutError,
+};
+
+use common::LAYOUT;
+
+const ORTHO: &str = "../config/keyboard/ortho.yml";
+const STANDARD: &str = "../config/keyboard/standard.yml";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+fn layout(layout_config: &str) -> Layout {
+    common::generate_layout(layout_config, LAYOUT)
+}
+
+#[test]
+fn symbols_move_to_mirror_partners() {
+    let layout = layout(ORTHO);
+    let mirrored = layout.mirrored(layout.keyboard.mirror_map()).unwrap();
+
+    for c in ['e', 'E', 'n', '(', ' '] {
+        let k = layout.get_layerkey_for_symbol(&c).unwrap();
+        let m = mirrored.get_layerkey_for_symbol(&c).unwrap();
+        assert_eq!(
+            m.key.matrix_position,
+            layout.keyboard.mirror_map()[&k.key.matrix_position]
+        );
+        assert_eq!(m.layer, k.layer);
+    }
+
+    // "e" is typed with the right hand now, and thus shifted with the left hand
+    let e = mirrored.get_layerkey_for_symbol(&'E').unwrap();
+    assert_eq!(e.key.hand, Hand::Right);
+    let shift = mirrored.get_layerkey(&e.modifiers.layerkey_indices()[0]);
+    assert_eq!(shift.key.hand, Hand::Left);
+
+    assert_ne!(mirrored.plot(), layout.plot());
+    // mirroring twice restores the layout
+    let restored = mirrored.mirrored(layout.keyboard.mirror_map()).unwrap();
+    assert_eq!(restored.plot(), layout.plot());
+}
+
+#[test]
+fn combined_cost_is_weighted_mean() {
+    let layout = layout(ORTHO);
+    let mirrored = layout.mirrored(layout.keyboard.mirror_map()).unwrap();
+    let normal_cost = common::evaluator(TEXT)
+        .evaluate_layout(&layout)
+        .total_cost();
+    let mirrored_cost = common::evaluator(TEXT)
+        .evaluate_layout(&mirrored)
+        .total_cost();
+
+    let result = common::evaluator(TEXT)
+        .evaluate_mirrored(&layout, 0.25)
+        .unwrap();
+    let keyboards: Vec<(&str, f64)> = result
+        .keyboard_results()
+        .iter()
+        .map(|kr| (kr.keyboard.as_str(), kr.weight))
+        .collect();
+    assert_eq!(keyboards, vec![("normal", 0.75), ("mirrored", 0.25)]);
+    assert_eq!(result.layout(), LAYOUT);
+
+    let expected = 0.75 * normal_cost + 0.25 * mirrored_cost;
+    assert!((result.total_cost() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn keys_without_mirror_partner_are_rejected() {
+    // the standard keyboard declares no mirror pairs
+    let layout = layout(STANDARD);
+    let err = layout.mirrored(layout.keyboard.mirror_map()).err().unwrap();
+    assert!(matches!(
+        err.downcast_ref::<LayoutError>(),
+        Some(LayoutError::NoMirrorPartner(_))
+    ));
+
+    let err = common::layout_config_from_yaml_override(
+        ORTHO,
+        "mirrored_layouts",
+        "keyboard:\n  mirror_pairs: [[[1,0], [18,0]]]\n",
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<KeyboardError>(),
+        Some(KeyboardError::MissingMirrorPartner(MatrixPosition(2, 0)))
+    ));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{read_ngrams_dir, Bigrams, Trigrams, Unigrams},
+    results::MetricType,
+};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    slice,
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// Write the ngram files of `TEXT` to a new directory, leaving out the file of the given order.
+fn ngrams_dir(missing_file: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_missing_{}_{}",
+        missing_file,
+        std::process::id()
+    ));
+    Unigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("1-grams.txt"))
+        .unwrap();
+    Bigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("2-grams.txt"))
+        .unwrap();
+    Trigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("3-grams.txt"))
+        .unwrap();
+    fs::remove_file(dir.join(missing_file)).unwrap();
+
+    dir
+}
+
+fn evaluator(dir: &Path) -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let (unigrams, bigrams, trigrams, missing) = read_ngrams_dir(dir.to_str().unwrap()).unwrap();
+    let mapper =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper))
+        .default_metrics(&eval_params.metrics)
+        .skip_metric_types(&missing)
+}
+
+#[test]
+fn evaluate_with_missing_ngram_files() {
+    let layout = layout();
+
+    for (missing_file, metric_type) in [
+        ("1-grams.txt", MetricType::Unigram),
+        ("2-grams.txt", MetricType::Bigram),
+        ("3-grams.txt", MetricType::Trigram),
+    ] {
+        let dir = ngrams_dir(missing_file);
+        let evaluator = evaluator(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            evaluator.skipped_metric_types(),
+            slice::from_ref(&metric_type)
+        );
+
+        let result = evaluator.evaluate_layout(&layout);
+        assert_eq!(result.skipped_metric_types(), slice::from_ref(&metric_type));
+        assert!(result.iter().all(|mr| mr.metric_type != metric_type));
+        assert!(result.iter().any(|mr| !mr.metric_costs.is_empty()));
+        assert!(result.total_cost().is_finite() && result.total_cost() > 0.0);
+        assert!(format!("{}", result).contains("Reduced metric set"));
+    }
+}
+
+#[test]
+fn no_ngram_files_is_an_error() {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_missing_all_{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    let result = read_ngrams_dir(dir.to_str().unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(result.is_err());
+}
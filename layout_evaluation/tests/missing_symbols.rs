@@ -0,0 +1,86 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{
+        common::MissingSymbols, on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper,
+    },
+    results::MetricType,
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// Contains two symbols that are not part of the layout
+const TEXT: &str = "ab☺c♞♞d";
+
+fn ngram_mapper() -> OnDemandNgramMapper {
+    common::ngram_mapper(TEXT)
+}
+
+fn sorted(missing: &MissingSymbols) -> Vec<(char, f64)> {
+    let mut missing: Vec<(char, f64)> = missing.iter().map(|(c, w)| (*c, *w)).collect();
+    missing.sort_by_key(|(c, _)| *c);
+    missing
+}
+
+#[test]
+fn missing_weight_is_attributed_to_first_missing_symbol() {
+    let layout = layout();
+    let mapper = ngram_mapper();
+
+    let unigrams = mapper.map_unigrams(&layout);
+    assert_eq!(sorted(&unigrams.missing_symbols), [('☺', 1.0), ('♞', 2.0)]);
+
+    // "b☺", "☺c" vs. "c♞", "♞♞", "♞d"
+    let bigrams = mapper.map_bigrams(&layout);
+    assert_eq!(sorted(&bigrams.missing_symbols), [('☺', 2.0), ('♞', 3.0)]);
+
+    // "☺c♞" counts for '☺' only
+    let trigrams = mapper.map_trigrams(&layout);
+    assert_eq!(sorted(&trigrams.missing_symbols), [('☺', 3.0), ('♞', 2.0)]);
+
+    assert_eq!(
+        unigrams.missing_symbols.values().sum::<f64>(),
+        unigrams.weight_not_found
+    );
+    assert_eq!(
+        bigrams.missing_symbols.values().sum::<f64>(),
+        bigrams.weight_not_found
+    );
+    assert_eq!(
+        trigrams.missing_symbols.values().sum::<f64>(),
+        trigrams.weight_not_found
+    );
+}
+
+#[test]
+fn missing_symbols_are_part_of_the_results() {
+    let layout = layout();
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let evaluator =
+        Evaluator::default(Box::new(ngram_mapper())).default_metrics(&eval_params.metrics);
+
+    let result = evaluator.evaluate_layout(&layout);
+    assert_eq!(
+        sorted(result.missing_symbols().unwrap()),
+        [('☺', 1.0), ('♞', 2.0)]
+    );
+
+    let bigram_results = result
+        .iter()
+        .find(|mr| mr.metric_type == MetricType::Bigram)
+        .unwrap();
+    assert_eq!(bigram_results.top_missing_symbols(1), vec![('♞', 3.0)]);
+    assert!(result
+        .iter()
+        .filter(|mr| mr.metric_type == MetricType::Layout)
+        .all(|mr| mr.missing_symbols.is_empty()));
+
+    // the weights survive reweighting
+    let reweighted = result.reweighted(None, |mc| mc.weight);
+    assert_eq!(
+        sorted(reweighted.missing_symbols().unwrap()),
+        sorted(result.missing_symbols().unwrap())
+    );
+}
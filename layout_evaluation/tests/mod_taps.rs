@@ -0,0 +1,132 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::Hand,
+    layout::{LayerKey, LayerModifierType, Layout},
+    layout_generator::LayoutGenerator,
+    mod_tap::ModTapError,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    metrics::bigram_metrics::{
+        same_hand_mod_tap::{Parameters, SameHandModTap},
+        BigramMetric,
+    },
+    ngram_mapper::NgramMapper,
+};
+
+use common::{EVALUATION_PARAMETERS, LAYOUT};
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/standard_home_row_mods.yml";
+
+/// The (single) hold modifier required for typing the given symbol.
+fn hold_modifier(layout: &Layout, c: char) -> &LayerKey {
+    let key = layout.get_layerkey_for_symbol(&c).unwrap();
+    let modifiers = key.modifiers.layerkey_indices();
+    assert_eq!(modifiers.len(), 1);
+
+    layout.get_layerkey(&modifiers[0])
+}
+
+#[test]
+fn shift_is_held_on_home_row() {
+    let layout = common::layout_generator(LAYOUT_CONFIG)
+        .generate(LAYOUT)
+        .unwrap();
+    let a = layout.get_layerkey_for_symbol(&'a').unwrap();
+    let r = layout.get_layerkey_for_symbol(&'r').unwrap();
+
+    // capitals of the right hand are shifted with "a", those of the left hand with "r"
+    let shift = hold_modifier(&layout, 'N');
+    assert_eq!(shift.is_modifier, LayerModifierType::ModTap);
+    assert_eq!(shift.key.matrix_position, a.key.matrix_position);
+    let shift = hold_modifier(&layout, 'E');
+    assert_eq!(shift.key.matrix_position, r.key.matrix_position);
+
+    // the higher layers keep their modifiers
+    let mod3 = hold_modifier(&layout, '(');
+    assert_eq!(mod3.is_modifier, LayerModifierType::Hold);
+
+    // tapping the keys still generates their symbols
+    assert!(a.is_modifier.is_none());
+    assert_eq!(a.layer, 0);
+}
+
+#[test]
+fn mod_tap_moves_with_its_symbol() {
+    // "a" moves to the right hand (swapping with "n")
+    let layout_str = LAYOUT.replace('a', "_").replace('n', "a").replace('_', "n");
+    let layout = common::layout_generator(LAYOUT_CONFIG)
+        .generate(&layout_str)
+        .unwrap();
+    let a = layout.get_layerkey_for_symbol(&'a').unwrap();
+    assert_eq!(a.key.hand, Hand::Right);
+
+    // both mod-taps are on the right hand now, the first one is used for shifting the left hand
+    let shift = hold_modifier(&layout, 'E');
+    assert_eq!(shift.key.matrix_position, a.key.matrix_position);
+    // the right hand is shifted with the regular left shift
+    let shift = hold_modifier(&layout, 'A');
+    assert_eq!(shift.is_modifier, LayerModifierType::Hold);
+    assert_eq!(shift.key.hand, Hand::Left);
+}
+
+#[test]
+fn split_modifiers_use_mod_tap() {
+    let layout = common::layout_generator(LAYOUT_CONFIG)
+        .generate(LAYOUT)
+        .unwrap();
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.split_modifiers.enabled = true;
+    let text = "N";
+    let mapper = common::ngram_mapper_with(text, eval_params.ngram_mapper);
+
+    let mut unigrams: Vec<(char, LayerModifierType, f64)> = mapper
+        .map_unigrams(&layout)
+        .grams
+        .iter()
+        .map(|(k, w)| (k.symbol, k.is_modifier, *w))
+        .collect();
+    unigrams.sort_by_key(|(c, _, _)| *c);
+
+    assert_eq!(
+        unigrams,
+        vec![
+            ('a', LayerModifierType::ModTap, 1.0),
+            ('n', LayerModifierType::None, 1.0)
+        ]
+    );
+}
+
+#[test]
+fn same_hand_after_mod_tap() {
+    let layout = common::layout_generator(LAYOUT_CONFIG)
+        .generate(LAYOUT)
+        .unwrap();
+    let metric = SameHandModTap::new(&Parameters {});
+    let cost =
+        |k1: &LayerKey, k2: &LayerKey| metric.individual_cost(k1, k2, 1.0, 1.0, &layout).unwrap();
+    let key = |c: char| layout.get_layerkey_for_symbol(&c).unwrap();
+
+    // holding "a" (left hand) for a capital of the right hand
+    let shift = hold_modifier(&layout, 'N');
+    assert_eq!(cost(shift, key('n')), 0.0);
+    assert_eq!(cost(shift, key('e')), 1.0);
+    assert_eq!(cost(shift, key('a')), 1.0);
+    // only holds of mod-taps are penalized
+    assert_eq!(cost(key('a'), key('e')), 0.0);
+    assert_eq!(cost(hold_modifier(&layout, '('), key('e')), 0.0);
+}
+
+#[test]
+fn mod_tap_needs_layer_with_modifiers() {
+    let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    layout_config.base_layout.mod_taps[0].hold = 9;
+
+    let err = layout_config.validate().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ModTapError>(),
+        Some(ModTapError::UnknownLayer(_, 9, 5))
+    ));
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use keyboard_layout::{key::MatrixPosition, layout::LayerKey};
+use layout_evaluation::ngram_mapper::NgramMapper;
+
+use common::{layout, ngram_mapper};
+
+/// The right shift key
+const SHIFT: MatrixPosition = MatrixPosition(18, 3);
+
+fn is_shift(key: &LayerKey) -> bool {
+    key.key.matrix_position == SHIFT
+}
+
+#[test]
+fn single_shifted_trigram_counts_one_shift_press() {
+    let layout = layout();
+    let mapper = ngram_mapper("Xen");
+    let trigrams = mapper.map_trigrams(&layout);
+
+    // the shift key appears in several of the split trigrams ...
+    let split_weight: f64 = trigrams
+        .grams
+        .iter()
+        .filter(|((k1, k2, k3), _)| is_shift(k1) || is_shift(k2) || is_shift(k3))
+        .map(|(_, w)| w)
+        .sum();
+    assert!(split_weight > 1.0);
+
+    // ... but it is pressed only once
+    let presses = trigrams.modifier_presses.unwrap();
+    assert_eq!(presses.len(), 1);
+    assert!(is_shift(presses[0].0));
+    assert_eq!(presses[0].1, 1.0);
+}
+
+#[test]
+fn unshifted_trigram_counts_no_presses() {
+    let layout = layout();
+    let mapper = ngram_mapper("xen");
+
+    assert!(mapper
+        .map_trigrams(&layout)
+        .modifier_presses
+        .unwrap()
+        .is_empty());
+}
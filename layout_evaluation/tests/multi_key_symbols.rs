@@ -0,0 +1,112 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::Hand,
+    keyboard::Keyboard,
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+    multi_key_symbol::{MultiKeyPolicy, MultiKeySymbolYAML},
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::ngram_mapper::NgramMapper;
+
+use std::sync::Arc;
+
+use common::{ngram_mapper, LAYOUT};
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+/// The crkbd layout with an additional space key on the left thumb (besides the one on the
+/// right thumb).
+fn layout(policy: MultiKeyPolicy) -> Layout {
+    let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    layout_config.base_layout.keys[3][2] = vec![" ".to_string()];
+    layout_config.base_layout.multi_key_symbols = vec![MultiKeySymbolYAML {
+        symbol: ' ',
+        policy,
+    }];
+
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+
+    layout_generator.generate(LAYOUT).unwrap()
+}
+
+/// The hands typing the space of the trigram `c1 space c3` along with their weights.
+fn space_hands(policy: MultiKeyPolicy, c1: char, c3: char) -> Vec<(Hand, f64)> {
+    let layout = layout(policy);
+    let text: String = [c1, ' ', c3].iter().collect();
+    let mapped = ngram_mapper(&text).map_trigrams(&layout);
+
+    let mut hands: Vec<(Hand, f64)> = mapped
+        .grams
+        .iter()
+        .filter(|((k1, k2, k3), _)| k1.symbol == c1 && k2.symbol == ' ' && k3.symbol == c3)
+        .map(|((_, k2, _), w)| {
+            assert!(layout.geometry().is_thumb(k2.key_index));
+            (layout.geometry().hand(k2.key_index), *w)
+        })
+        .collect();
+    hands.sort_by_key(|(h, _)| *h as usize);
+
+    hands
+}
+
+#[test]
+fn multi_key_symbols_need_multiple_fixed_keys() {
+    let layout = common::generate_layout(LAYOUT_CONFIG, LAYOUT);
+    let symbols = [MultiKeySymbolYAML {
+        symbol: ' ',
+        policy: MultiKeyPolicy::Alternate,
+    }];
+    assert!(layout.with_multi_key_symbols(&symbols).is_err());
+}
+
+#[test]
+fn alternate() {
+    // 'e' is typed by the left hand, 't' by the right one
+    assert_eq!(
+        space_hands(MultiKeyPolicy::Alternate, 'e', 't'),
+        vec![(Hand::Right, 1.0)]
+    );
+    assert_eq!(
+        space_hands(MultiKeyPolicy::Alternate, 't', 'e'),
+        vec![(Hand::Left, 1.0)]
+    );
+
+    // without a preceding key, the weight is split
+    let layout = layout(MultiKeyPolicy::Alternate);
+    let mapped = ngram_mapper(" t").map_bigrams(&layout);
+    let mut weights: Vec<(Hand, f64)> = mapped
+        .grams
+        .iter()
+        .map(|((k1, _), w)| (k1.key.hand, *w))
+        .collect();
+    weights.sort_by_key(|(h, _)| *h as usize);
+    assert_eq!(weights, vec![(Hand::Left, 0.5), (Hand::Right, 0.5)]);
+}
+
+#[test]
+fn fixed() {
+    for (c1, c3) in [('e', 't'), ('t', 'e')] {
+        assert_eq!(
+            space_hands(MultiKeyPolicy::FixedLeft, c1, c3),
+            vec![(Hand::Left, 1.0)]
+        );
+        assert_eq!(
+            space_hands(MultiKeyPolicy::FixedRight, c1, c3),
+            vec![(Hand::Right, 1.0)]
+        );
+    }
+}
+
+#[test]
+fn split_weight() {
+    for (c1, c3) in [('e', 't'), ('t', 'e')] {
+        assert_eq!(
+            space_hands(MultiKeyPolicy::SplitWeight, c1, c3),
+            vec![(Hand::Left, 0.5), (Hand::Right, 0.5)]
+        );
+    }
+}
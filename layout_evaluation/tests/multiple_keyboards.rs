@@ -0,0 +1,152 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    keyboard::{FingerOverride, Keyboard},
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{evaluation::KeyboardVariant, fingerprint::layout_hash};
+
+use std::sync::Arc;
+
+use common::LAYOUT;
+
+const STANDARD: &str = "../config/keyboard/standard.yml";
+const ORTHO: &str = "../config/keyboard/ortho.yml";
+const HEBREW: &str = "../config/keyboard/standard_hebrew.yml";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// The angle mod: the left keys of the bottom row are pressed by the finger to the right
+const ANGLE_MOD: &str = "
+- {position: [2, 3], hand: Left, finger: Ring}
+- {position: [3, 3], hand: Left, finger: Middle}
+- {position: [4, 3], hand: Left, finger: Index}
+";
+
+fn layout_generator(layout_config: &str, overrides: &[FingerOverride]) -> NeoLayoutGenerator {
+    let layout_config = LayoutConfig::from_yaml(layout_config).unwrap();
+    let keyboard = Keyboard::from_yaml_object(layout_config.keyboard)
+        .with_finger_overrides(overrides)
+        .unwrap();
+
+    NeoLayoutGenerator::from_object(layout_config.base_layout, Arc::new(keyboard))
+}
+
+fn layout(layout_config: &str, overrides: &[FingerOverride]) -> Layout {
+    layout_generator(layout_config, overrides)
+        .generate(LAYOUT)
+        .unwrap()
+}
+
+fn variant(name: &str, weight: f64, layout_generator: NeoLayoutGenerator) -> KeyboardVariant {
+    KeyboardVariant {
+        name: name.to_string(),
+        weight,
+        layout_generator: Box::new(layout_generator),
+    }
+}
+
+#[test]
+fn combined_cost_is_weighted_mean() {
+    let standard_cost = common::evaluator(TEXT)
+        .evaluate_layout(&layout(STANDARD, &[]))
+        .total_cost();
+    let ortho_cost = common::evaluator(TEXT)
+        .evaluate_layout(&layout(ORTHO, &[]))
+        .total_cost();
+    assert!((standard_cost - ortho_cost).abs() > 1e-9);
+
+    let evaluator = common::evaluator(TEXT)
+        .with_keyboards(
+            "standard",
+            1.0,
+            &layout_generator(STANDARD, &[]),
+            vec![variant("ortho", 3.0, layout_generator(ORTHO, &[]))],
+        )
+        .unwrap();
+    let result = evaluator.evaluate_layout(&layout(STANDARD, &[]));
+
+    let keyboards: Vec<(&str, f64)> = result
+        .keyboard_results()
+        .iter()
+        .map(|kr| (kr.keyboard.as_str(), kr.weight))
+        .collect();
+    assert_eq!(keyboards, vec![("standard", 0.25), ("ortho", 0.75)]);
+    assert!((result.keyboard_results()[0].result.total_cost() - standard_cost).abs() < 1e-9);
+    assert!((result.keyboard_results()[1].result.total_cost() - ortho_cost).abs() < 1e-9);
+
+    let expected = 0.25 * standard_cost + 0.75 * ortho_cost;
+    assert!((result.total_cost() - expected).abs() < 1e-9);
+    assert_eq!(result.layout(), LAYOUT);
+
+    let text = result.to_string();
+    assert!(text.contains("standard"));
+    assert!(text.contains("ortho"));
+}
+
+#[test]
+fn keyboards_with_same_matrix_are_evaluated_separately() {
+    let overrides: Vec<FingerOverride> = serde_yaml::from_str(ANGLE_MOD).unwrap();
+    let angle_mod_cost = common::evaluator(TEXT)
+        .evaluate_layout(&layout(STANDARD, &overrides))
+        .total_cost();
+
+    // the layouts on both keyboards consist of the same symbols on the same matrix positions,
+    // but must not share their cached ngram mappings
+    assert_ne!(
+        layout_hash(&layout(STANDARD, &[])),
+        layout_hash(&layout(STANDARD, &overrides))
+    );
+    let evaluator = common::evaluator(TEXT)
+        .with_keyboards(
+            "standard",
+            1.0,
+            &layout_generator(STANDARD, &[]),
+            vec![variant(
+                "angle_mod",
+                1.0,
+                layout_generator(STANDARD, &overrides),
+            )],
+        )
+        .unwrap();
+    let result = evaluator.evaluate_layout(&layout(STANDARD, &[]));
+
+    assert!((result.keyboard_results()[1].result.total_cost() - angle_mod_cost).abs() < 1e-9);
+}
+
+#[test]
+fn reweighting_keeps_keyboards() {
+    let evaluator = common::evaluator(TEXT)
+        .with_keyboards(
+            "standard",
+            1.0,
+            &layout_generator(STANDARD, &[]),
+            vec![variant("ortho", 1.0, layout_generator(ORTHO, &[]))],
+        )
+        .unwrap();
+    let result = evaluator.evaluate_layout(&layout(STANDARD, &[]));
+    let doubled = result.reweighted(Some("doubled".to_string()), |mc| 2.0 * mc.weight);
+
+    assert_eq!(doubled.keyboard_results().len(), 2);
+    assert_eq!(doubled.profile(), Some("doubled"));
+    assert!((doubled.total_cost() - 2.0 * result.total_cost()).abs() < 1e-6);
+}
+
+#[test]
+fn keyboards_with_other_symbols_are_rejected() {
+    let result = common::evaluator(TEXT).with_keyboards(
+        "standard",
+        1.0,
+        &layout_generator(STANDARD, &[]),
+        vec![
+            variant("ortho", 1.0, layout_generator(ORTHO, &[])),
+            variant("hebrew", 1.0, layout_generator(HEBREW, &[])),
+        ],
+    );
+
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains("'hebrew'"), "{}", message);
+}
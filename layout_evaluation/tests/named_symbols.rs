@@ -0,0 +1,92 @@
+mod common;
+
+use keyboard_layout::{key::MatrixPosition, layout::Layout};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngrams::{Bigrams, Unigrams},
+};
+
+use std::fs;
+
+use common::EVALUATION_PARAMETERS;
+
+const LAYOUT_CONFIG: &str = "tests/fixtures/named_symbols.yml";
+const BACKSPACE: char = '\u{e000}';
+const ENTER: char = '\u{e001}';
+/// A text with typos corrected by backspace and lines ended by enter
+const TEXT: &str = "ab\u{e000}\u{e000}ba\u{e001}aab\u{e000}\u{e001}";
+
+fn layout(layout: &str) -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, layout)
+}
+
+#[test]
+fn plot_shows_names() {
+    let layout_str: String = ['a', BACKSPACE, 'b'].iter().collect();
+    let layout = layout(&layout_str);
+
+    let plot = layout.plot();
+    assert!(plot.contains('⌫'));
+    assert!(plot.contains('⏎'));
+    assert!(!plot.contains(BACKSPACE));
+    assert!(!plot.contains(ENTER));
+
+    // the layout string keeps the symbols themselves
+    assert_eq!(layout.as_text(), layout_str);
+}
+
+#[test]
+fn named_symbols_are_permuted_and_use_key_costs() {
+    let layout = layout(&[BACKSPACE, 'a', 'b'].iter().collect::<String>());
+
+    let backspace = layout.get_layerkey_for_symbol(&BACKSPACE).unwrap();
+    assert_eq!(backspace.key.matrix_position, MatrixPosition(0, 0));
+    assert_eq!(backspace.key.cost, 1.0);
+    assert_eq!(backspace.name.as_deref(), Some("⌫"));
+    assert_eq!(backspace.to_string(), "⌫");
+
+    let enter = layout.get_layerkey_for_symbol(&ENTER).unwrap();
+    assert_eq!(enter.key.cost, 4.0);
+    assert_eq!(enter.to_string(), "⏎");
+
+    assert_eq!(layout.get_layerkey_for_symbol(&'a').unwrap().name, None);
+}
+
+#[test]
+fn ngram_files_round_trip() {
+    let text = format!("{}\\\n\\n", TEXT);
+    let unigrams = Unigrams::from_text(&text).unwrap();
+    let bigrams = Bigrams::from_text(&text).unwrap();
+
+    // the characters of the private use areas are escaped
+    let frequencies = unigrams.frequencies_str();
+    assert!(frequencies.contains("\\u{e000}"));
+    assert!(!frequencies.contains(BACKSPACE));
+    assert!(!bigrams.frequencies_str().contains(ENTER));
+
+    let dir = std::env::temp_dir().join(format!("named_symbols_{}", std::process::id()));
+    let unigrams_file = dir.join("1-grams.txt");
+    unigrams.save_frequencies(&unigrams_file).unwrap();
+    let read = Unigrams::from_file_with_cache(unigrams_file.to_str().unwrap(), false).unwrap();
+    assert_eq!(read.grams, unigrams.grams);
+
+    let read = Bigrams::from_frequencies_str(&bigrams.frequencies_str()).unwrap();
+    assert_eq!(read.grams, bigrams.grams);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn named_symbols_are_evaluated() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = common::ngram_mapper_with(TEXT, eval_params.ngram_mapper);
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+
+    let result =
+        evaluator.evaluate_layout(&layout(&['a', BACKSPACE, 'b'].iter().collect::<String>()));
+    assert!(result.total_cost().is_finite());
+    for metric_results in result.iter() {
+        assert_eq!(metric_results.not_found_weight, 0.0);
+    }
+}
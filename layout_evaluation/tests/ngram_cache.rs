@@ -0,0 +1,107 @@
+use layout_evaluation::{
+    ngram_cache,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+use ahash::AHashMap;
+use std::{fs, path::PathBuf};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund.\n\
+                    The quick brown fox jumps over the lazy dog.";
+
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_evaluation_ngram_cache_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Weights as bits, sorted by ngram
+fn bits<K: Clone + Ord>(grams: &AHashMap<K, f64>) -> Vec<(K, u64)> {
+    let mut bits: Vec<(K, u64)> = grams
+        .iter()
+        .map(|(k, w)| (k.clone(), w.to_bits()))
+        .collect();
+    bits.sort();
+    bits
+}
+
+#[test]
+fn cached_ngrams_equal_parsed_ngrams() {
+    let dir = test_dir("roundtrip");
+
+    // weights that are not representable exactly in decimal
+    let mut unigrams = Unigrams::from_text(TEXT).unwrap();
+    unigrams.grams.values_mut().for_each(|w| *w /= 3.0);
+    let bigrams = Bigrams::from_text(TEXT).unwrap().tops(0.7);
+    let trigrams = Trigrams::from_text(TEXT).unwrap();
+    let files = [
+        dir.join("1-grams.txt"),
+        dir.join("2-grams.txt"),
+        dir.join("3-grams.txt"),
+    ];
+    unigrams.save_frequencies(&files[0]).unwrap();
+    bigrams.save_frequencies(&files[1]).unwrap();
+    trigrams.save_frequencies(&files[2]).unwrap();
+    let f = |i: usize| files[i].to_str().unwrap().to_string();
+
+    let parsed = (
+        Unigrams::from_file_with_cache(&f(0), false).unwrap(),
+        Bigrams::from_file_with_cache(&f(1), false).unwrap(),
+        Trigrams::from_file_with_cache(&f(2), false).unwrap(),
+    );
+    assert!(files.iter().all(|p| !ngram_cache::cache_path(p).exists()));
+
+    // the first load parses the files and writes the caches, the second one loads them
+    for _ in 0..2 {
+        let loaded = (
+            Unigrams::from_file(&f(0)).unwrap(),
+            Bigrams::from_file(&f(1)).unwrap(),
+            Trigrams::from_file(&f(2)).unwrap(),
+        );
+        assert_eq!(bits(&loaded.0.grams), bits(&parsed.0.grams));
+        assert_eq!(bits(&loaded.1.grams), bits(&parsed.1.grams));
+        assert_eq!(bits(&loaded.2.grams), bits(&parsed.2.grams));
+    }
+
+    let cached: AHashMap<(char, char, char), f64> = ngram_cache::load_cached(&files[2]).unwrap();
+    assert_eq!(bits(&cached), bits(&parsed.2.grams));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cache_is_invalidated_by_changes() {
+    let dir = test_dir("invalidation");
+    let file = dir.join("1-grams.txt");
+    let filename = file.to_str().unwrap();
+
+    Unigrams::from_text("aab")
+        .unwrap()
+        .save_frequencies(&file)
+        .unwrap();
+    Unigrams::from_file(filename).unwrap();
+    assert!(ngram_cache::load_cached::<char, _>(&file).is_some());
+
+    // a changed file is parsed again (and the cache is updated)
+    Unigrams::from_text("xyzz")
+        .unwrap()
+        .save_frequencies(&file)
+        .unwrap();
+    assert!(ngram_cache::load_cached::<char, _>(&file).is_none());
+    let unigrams = Unigrams::from_file(filename).unwrap();
+    assert_eq!(unigrams.grams.get(&'z'), Some(&2.0));
+    assert!(!unigrams.grams.contains_key(&'a'));
+    assert!(ngram_cache::load_cached::<char, _>(&file).is_some());
+
+    // corrupt caches are ignored
+    fs::write(ngram_cache::cache_path(&file), [1, 2, 3]).unwrap();
+    assert!(ngram_cache::load_cached::<char, _>(&file).is_none());
+    assert_eq!(Unigrams::from_file(filename).unwrap().grams, unigrams.grams);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,70 @@
+use layout_evaluation::{
+    ngram_stats::{NgramStatistics, NgramsStatistics},
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+use ahash::AHashMap;
+
+#[test]
+fn uniform_ngrams() {
+    let grams: AHashMap<char, f64> = ['a', 'b', 'c', 'd'].iter().map(|c| (*c, 2.0)).collect();
+    let stats = NgramStatistics::new(&grams, |c| c.to_string(), 2, &[0.5, 1.0]);
+
+    assert_eq!(stats.total_weight, 8.0);
+    assert_eq!(stats.distinct, 4);
+    assert!((stats.entropy - 2.0).abs() < 1e-12);
+    // ties are sorted by symbols
+    let top: Vec<&str> = stats.top.iter().map(|t| t.ngram.as_str()).collect();
+    assert_eq!(top, vec!["a", "b"]);
+    assert_eq!(stats.coverage[0].ngrams, 2);
+    assert_eq!(stats.coverage[0].ngrams_fraction, 0.5);
+    assert_eq!(stats.coverage[1].ngrams, 4);
+    assert_eq!(stats.coverage[1].min_fraction, 0.25);
+}
+
+#[test]
+fn skewed_ngrams() {
+    let grams: AHashMap<char, f64> = [('a', 90.0), ('b', 9.0), ('\n', 1.0)]
+        .iter()
+        .cloned()
+        .collect();
+    let stats = NgramStatistics::new(&grams, |c| c.to_string(), 10, &[0.5, 0.9, 0.99, 1.0]);
+
+    let coverage: Vec<usize> = stats.coverage.iter().map(|c| c.ngrams).collect();
+    assert_eq!(coverage, vec![1, 1, 2, 3]);
+    assert_eq!(stats.coverage[2].min_fraction, 0.09);
+    assert_eq!(stats.top.len(), 3);
+    assert_eq!(stats.top[0].fraction, 0.9);
+    // line breaks are escaped as in the ngram files
+    assert_eq!(stats.top[2].ngram, "\\n");
+}
+
+#[test]
+fn empty_ngrams() {
+    let grams: AHashMap<char, f64> = AHashMap::default();
+    let stats = NgramStatistics::new(&grams, |c| c.to_string(), 10, &[0.5]);
+
+    assert_eq!(stats.total_weight, 0.0);
+    assert_eq!(stats.entropy, 0.0);
+    assert!(stats.top.is_empty());
+    assert_eq!(stats.coverage[0].ngrams, 0);
+}
+
+#[test]
+fn statistics_of_all_orders() {
+    let text = "abcabcabd";
+    let stats = NgramsStatistics::new(
+        &Unigrams::from_text(text).unwrap(),
+        &Bigrams::from_text(text).unwrap(),
+        &Trigrams::from_text(text).unwrap(),
+        None,
+        1,
+        &[1.0],
+    );
+
+    assert_eq!(stats.unigrams.distinct, 4);
+    assert_eq!(stats.bigrams.top[0].ngram, "ab");
+    assert_eq!(stats.bigrams.top[0].weight, 3.0);
+    assert_eq!(stats.trigrams.top[0].ngram, "abc");
+    assert!(stats.quadgrams.is_none());
+}
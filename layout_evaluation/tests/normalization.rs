@@ -0,0 +1,99 @@
+use layout_evaluation::{
+    ngrams::{Bigrams, TextOptions, Trigrams, Unigrams},
+    normalization::TextNormalization,
+};
+
+const PRECOMPOSED: &str = "Mädchen über Äpfel, Åse og Ørjan.";
+const DECOMPOSED: &str = "Ma\u{0308}dchen u\u{0308}ber A\u{0308}pfel, A\u{030A}se og Ørjan.";
+
+#[test]
+fn nfc_ngrams_of_decomposed_text_equal_precomposed_ones() {
+    let nfc = TextNormalization::Nfc;
+
+    assert_eq!(
+        Unigrams::from_text_normalized(DECOMPOSED, nfc)
+            .unwrap()
+            .grams,
+        Unigrams::from_text(PRECOMPOSED).unwrap().grams
+    );
+    assert_eq!(
+        Bigrams::from_text_normalized(DECOMPOSED, nfc)
+            .unwrap()
+            .grams,
+        Bigrams::from_text(PRECOMPOSED).unwrap().grams
+    );
+    assert_eq!(
+        Trigrams::from_text_normalized(DECOMPOSED, nfc)
+            .unwrap()
+            .grams,
+        Trigrams::from_text(PRECOMPOSED).unwrap().grams
+    );
+}
+
+#[test]
+fn text_is_not_normalized_by_default() {
+    let unigrams = Unigrams::from_text(DECOMPOSED).unwrap();
+    assert_eq!(unigrams.grams.get(&'\u{0308}'), Some(&3.0));
+    assert!(!unigrams.grams.contains_key(&'ä'));
+
+    assert_eq!(TextNormalization::default(), TextNormalization::None);
+    assert_eq!(
+        Unigrams::from_text_normalized(DECOMPOSED, TextNormalization::default())
+            .unwrap()
+            .grams,
+        unigrams.grams
+    );
+}
+
+#[test]
+fn folding_case_keeps_total_weight() {
+    let text = "Der Fuchs. THE FOX! İstanbul";
+    let options = TextOptions {
+        fold_case: true,
+        ..Default::default()
+    };
+
+    let (unigrams, weights) = Unigrams::from_text_with_options(text, &options).unwrap();
+    let plain = Unigrams::from_text(text).unwrap();
+    assert_eq!(unigrams.total_weight(), plain.total_weight());
+    // D, F, T, H, E, F, O, X (the dotted "İ" lowercases to two characters and is kept)
+    assert_eq!(weights.folded, 8.0);
+    assert_eq!(unigrams.grams.get(&'f'), Some(&2.0));
+    assert_eq!(unigrams.grams.get(&'İ'), Some(&1.0));
+    assert!(!unigrams.grams.contains_key(&'D'));
+
+    let (bigrams, weights) = Bigrams::from_text_with_options(text, &options).unwrap();
+    assert_eq!(
+        bigrams.total_weight(),
+        Bigrams::from_text(text).unwrap().total_weight()
+    );
+    assert_eq!(bigrams.grams.get(&('t', 'h')), Some(&1.0));
+    // bigrams containing any of the folded letters
+    assert_eq!(weights.folded, 11.0);
+
+    let (trigrams, _) = Trigrams::from_text_with_options(text, &options).unwrap();
+    assert_eq!(
+        trigrams.total_weight(),
+        Trigrams::from_text(text).unwrap().total_weight()
+    );
+
+    // without folding, the result is the same as without options
+    let (unfolded, weights) =
+        Trigrams::from_text_with_options(text, &TextOptions::default()).unwrap();
+    assert_eq!(unfolded.grams, Trigrams::from_text(text).unwrap().grams);
+    assert_eq!(weights.folded, 0.0);
+}
+
+#[test]
+fn word_internal_ngrams_restart_at_separators() {
+    let options = TextOptions {
+        word_internal_only: true,
+        ..Default::default()
+    };
+
+    let (bigrams, _) = Bigrams::from_text_with_options("to be", &options).unwrap();
+    assert!(bigrams.grams.contains_key(&('t', 'o')));
+    assert!(bigrams.grams.contains_key(&('b', 'e')));
+    assert!(!bigrams.grams.contains_key(&('o', ' ')));
+    assert!(!bigrams.grams.contains_key(&(' ', 'b')));
+}
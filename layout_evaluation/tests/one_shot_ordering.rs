@@ -0,0 +1,176 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngram_mapper::{
+    common::OneShotOrdering, on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper,
+};
+
+/// A symbol of the fourth layer, which requires two modifiers
+const SYMBOL: char = '₁';
+
+/// The standard layout with one-shot modifiers for the fourth layer
+fn layout() -> Layout {
+    common::layout_from_yaml_override(
+        "one_shot_ordering",
+        r#"
+base_layout:
+  modifiers:
+    - Left: { type: hold, value: [[0,3]] }
+      Right: { type: hold, value: [[18,3]] }
+    - Left: { type: hold, value: [[0,2]] }
+      Right: { type: hold, value: [[18,2]] }
+    - Left: { type: hold, value: [[1,3]] }
+      Right: { type: hold, value: [[16,4]] }
+    - Left: { type: one_shot, value: [[0,3], [1,3]] }
+      Right: { type: one_shot, value: [[18,3], [16,4]] }
+    - Left: { type: hold, value: [[0,2], [1,3]] }
+      Right: { type: hold, value: [[18,2], [16,4]] }
+"#,
+    )
+}
+
+fn ngram_mapper(text: &str, ordering: OneShotOrdering, weight_factor: f64) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.split_modifiers.one_shot_ordering = ordering;
+    config.split_modifiers.one_shot_weight_factor = weight_factor;
+
+    common::ngram_mapper_with(text, config)
+}
+
+/// Symbols of the base-layer key and the two modifiers of [`SYMBOL`]
+fn keys(layout: &Layout) -> (char, char, char) {
+    let (base, mods) =
+        layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&SYMBOL).unwrap());
+    let mods = mods.layerkey_indices();
+    assert_eq!(mods.len(), 2);
+
+    (
+        layout.get_layerkey(&base).symbol,
+        layout.get_layerkey(&mods[0]).symbol,
+        layout.get_layerkey(&mods[1]).symbol,
+    )
+}
+
+/// Trigrams of a text as sorted (symbols, weight) pairs
+fn trigrams(
+    layout: &Layout,
+    text: &[char],
+    ordering: OneShotOrdering,
+    weight_factor: f64,
+) -> Vec<(String, f64)> {
+    let text: String = text.iter().collect();
+    let mut trigrams: Vec<(String, f64)> = ngram_mapper(&text, ordering, weight_factor)
+        .map_trigrams(layout)
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ([k1.symbol, k2.symbol, k3.symbol].iter().collect(), *w))
+        .collect();
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    trigrams
+}
+
+fn expected(trigrams: &[[char; 3]], weight: f64) -> Vec<(String, f64)> {
+    trigrams
+        .iter()
+        .map(|t| (t.iter().collect(), weight))
+        .collect()
+}
+
+fn sorted(mut trigrams: Vec<(String, f64)>) -> Vec<(String, f64)> {
+    trigrams.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    trigrams
+}
+
+#[test]
+fn modifiers_in_middle_of_trigram() {
+    let layout = layout();
+    let (base, m1, m2) = keys(&layout);
+
+    let text = ['a', SYMBOL, 'e'];
+    let trigrams = |ordering| trigrams(&layout, &text, ordering, 1.0);
+
+    let before = [['a', m1, m2], [m1, m2, base], [m2, base, 'e']];
+    assert_eq!(
+        trigrams(OneShotOrdering::Before),
+        sorted(expected(&before, 1.0))
+    );
+
+    let after = [['a', base, m1], [base, m1, m2], [m1, m2, 'e']];
+    assert_eq!(
+        trigrams(OneShotOrdering::After),
+        sorted(expected(&after, 1.0))
+    );
+
+    let mut split = expected(&before, 0.25);
+    split.extend(expected(&after, 0.75));
+    assert_eq!(trigrams(OneShotOrdering::Split(0.25)), sorted(split));
+}
+
+#[test]
+fn orderings_are_consistent_across_ngram_orders() {
+    let layout = layout();
+    let text: String = ['a', SYMBOL, 'e', SYMBOL].iter().collect();
+
+    for ordering in [
+        OneShotOrdering::Before,
+        OneShotOrdering::After,
+        OneShotOrdering::Split(0.5),
+    ] {
+        let mapper = ngram_mapper(&text, ordering, 1.0);
+        let unigrams = mapper.map_unigrams(&layout);
+        let bigrams = mapper.map_bigrams(&layout);
+        let trigrams = mapper.map_trigrams(&layout);
+
+        // each source ngram is expanded separately: "a₁", "₁e", and "e₁" yield three bigrams
+        // each, "a₁e" yields three trigrams and "₁e₁" five
+        let unigram_weight: f64 = unigrams.grams.iter().map(|(_, w)| w).sum();
+        let bigram_weight: f64 = bigrams.grams.iter().map(|(_, w)| w).sum();
+        let trigram_weight: f64 = trigrams.grams.iter().map(|(_, w)| w).sum();
+        assert_eq!(unigram_weight, 8.0);
+        assert_eq!(bigram_weight, 9.0);
+        assert_eq!(trigram_weight, 8.0);
+
+        // both modifiers are pressed once for "₁" in each of the three bigrams, regardless of
+        // the ordering
+        let presses: f64 = bigrams
+            .modifier_presses
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|(_, w)| w)
+            .sum();
+        assert_eq!(presses, 3.0 * 2.0);
+    }
+}
+
+#[test]
+fn one_shot_weight_factor_at_each_trigram_position() {
+    let layout = layout();
+    let (base, m1, m2) = keys(&layout);
+    let trigrams = |text: &[char], weight_factor| {
+        trigrams(&layout, text, OneShotOrdering::Before, weight_factor)
+    };
+
+    // first position: only the trigram without modifiers keeps its weight
+    let mut first = expected(&[[m1, m2, base], [m2, base, 'a']], 0.5);
+    first.extend(expected(&[[base, 'a', 'e']], 1.0));
+    assert_eq!(trigrams(&[SYMBOL, 'a', 'e'], 0.5), sorted(first));
+
+    let middle = [['a', m1, m2], [m1, m2, base], [m2, base, 'e']];
+    assert_eq!(
+        trigrams(&['a', SYMBOL, 'e'], 0.5),
+        sorted(expected(&middle, 0.5))
+    );
+
+    let last = [['a', 'e', m1], ['e', m1, m2], [m1, m2, base]];
+    assert_eq!(
+        trigrams(&['a', 'e', SYMBOL], 0.5),
+        sorted(expected(&last, 0.5))
+    );
+
+    // the default factor leaves the weights unchanged
+    assert_eq!(
+        trigrams(&['a', 'e', SYMBOL], 1.0),
+        sorted(expected(&last, 1.0))
+    );
+}
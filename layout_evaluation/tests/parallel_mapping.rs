@@ -0,0 +1,69 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    config::EvaluationParameters,
+    ngram_mapper::{
+        common::{LineBreakHandling, ModifierPresses},
+        trigram_mapper::{OnDemandTrigramMapper, TrigramIndices},
+    },
+    ngrams::Trigrams,
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const NGRAMS: &str = "../ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4/3-grams.txt";
+
+fn mapped(
+    trigrams: &Trigrams,
+    layout: &Layout,
+    parallel: bool,
+) -> (TrigramIndices, ModifierPresses) {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.split_modifiers.parallel = parallel;
+
+    let mapper = OnDemandTrigramMapper::new(
+        eval_params.ngram_mapper.split_modifiers,
+        eval_params.ngram_mapper.shift_policy,
+        eval_params.ngram_mapper.increase_common_trigrams,
+        false,
+    );
+    let (trigrams, _, _, presses) =
+        mapper.layerkey_indices(trigrams, layout, LineBreakHandling::None);
+
+    (trigrams, presses)
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(1.0)
+}
+
+#[test]
+fn parallel_mapping_matches_sequential_mapping() {
+    let layout = layout();
+    let trigrams = Trigrams::from_file(NGRAMS).unwrap();
+
+    let (sequential, sequential_presses) = mapped(&trigrams, &layout, false);
+    let (parallel, parallel_presses) = mapped(&trigrams, &layout, true);
+
+    assert_eq!(parallel.len(), sequential.len());
+    for (trigram, w) in sequential.iter() {
+        assert!(close(parallel[trigram], *w), "{:?}", trigram);
+    }
+    assert_eq!(parallel_presses.len(), sequential_presses.len());
+    for (modifier, w) in sequential_presses.iter() {
+        assert!(close(parallel_presses[modifier], *w));
+    }
+
+    // the chunks are merged in a fixed order
+    let (repeated, _) = mapped(&trigrams, &layout, true);
+    let total = |t: &TrigramIndices| {
+        let mut weights: Vec<_> = t.iter().collect();
+        weights.sort_unstable_by_key(|(k, _)| **k);
+        weights.into_iter().map(|(_, w)| w).sum::<f64>()
+    };
+    assert_eq!(total(&repeated).to_bits(), total(&parallel).to_bits());
+    assert_eq!(repeated, parallel);
+}
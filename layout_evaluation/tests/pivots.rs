@@ -0,0 +1,113 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::metrics::trigram_metrics::{
+    oxey_bad_redirects::{self, OxeyBadRedirects},
+    oxey_redirects::{self, OxeyRedirects},
+    pivots::{Parameters, Pivots},
+    TrigramMetric,
+};
+
+use common::LAYOUT;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+fn params(exclude_redirects: bool) -> Parameters {
+    Parameters {
+        factor: 1.0,
+        exclude_thumbs: true,
+        exclude_modifiers: true,
+        exclude_redirects,
+    }
+}
+
+fn redirects() -> OxeyRedirects {
+    OxeyRedirects::new(&oxey_redirects::Parameters {
+        exclude_thumbs: false,
+        exclude_modifiers: false,
+        exclude_chars: Vec::new(),
+    })
+}
+
+fn bad_redirects(exclude_chars: &[char]) -> OxeyBadRedirects {
+    OxeyBadRedirects::new(&oxey_bad_redirects::Parameters {
+        exclude_thumbs: true,
+        exclude_modifiers: false,
+        exclude_chars: exclude_chars.to_vec(),
+    })
+}
+
+fn cost(metric: &Pivots, layout: &Layout, trigram: &str) -> f64 {
+    let keys: Vec<_> = trigram
+        .chars()
+        .map(|c| layout.get_layerkey_for_symbol(&c).unwrap())
+        .collect();
+
+    metric
+        .individual_cost(keys[0], keys[1], keys[2], 1.0, 1.0, layout)
+        .unwrap()
+}
+
+#[test]
+fn pivots() {
+    let layout = layout();
+    let metric = Pivots::new(&params(false), None, None);
+
+    // 'l' (top row) -> 'a' (home row, inwards) -> 'ö' (bottom row, outwards)
+    assert_eq!(cost(&metric, &layout, "laö"), 2.0);
+    // 'l' -> 'w' (same row, inwards) -> 'a' (home row, outwards, involving the index finger)
+    assert_eq!(cost(&metric, &layout, "lwa"), 1.0);
+    // 'q' (top row) -> ',' (bottom row, inwards) -> 't' (home row, outwards) on the right hand
+    assert_eq!(cost(&metric, &layout, "q,t"), 3.0);
+}
+
+#[test]
+fn no_pivots() {
+    let layout = layout();
+    let metric = Pivots::new(&params(false), None, None);
+
+    // a redirect within a single row
+    assert_eq!(cost(&metric, &layout, "aei"), 0.0);
+    // a roll crossing rows
+    assert_eq!(cost(&metric, &layout, "üic"), 0.0);
+    // a hand switch
+    assert_eq!(cost(&metric, &layout, "lat"), 0.0);
+}
+
+#[test]
+fn exclude_redirects() {
+    let layout = layout();
+
+    // redirect metrics are ignored if the exclusion is disabled
+    let metric = Pivots::new(&params(false), Some(redirects()), Some(bad_redirects(&[])));
+    assert_eq!(cost(&metric, &layout, "laö"), 2.0);
+    assert_eq!(cost(&metric, &layout, "lwa"), 1.0);
+
+    // both redirect metrics enabled
+    let metric = Pivots::new(&params(true), Some(redirects()), Some(bad_redirects(&[])));
+    assert_eq!(cost(&metric, &layout, "laö"), 0.0);
+    assert_eq!(cost(&metric, &layout, "lwa"), 0.0);
+
+    // only the (index finger) redirects are enabled, bad redirects still count as pivots
+    let metric = Pivots::new(&params(true), Some(redirects()), None);
+    assert_eq!(cost(&metric, &layout, "laö"), 2.0);
+    assert_eq!(cost(&metric, &layout, "lwa"), 0.0);
+
+    // trigrams excluded from the redirect metric are not excluded from the pivots
+    let metric = Pivots::new(
+        &params(true),
+        Some(redirects()),
+        Some(bad_redirects(&['ö'])),
+    );
+    assert_eq!(cost(&metric, &layout, "laö"), 2.0);
+    assert_eq!(cost(&metric, &layout, "lwa"), 0.0);
+
+    // no redirect metric enabled
+    let metric = Pivots::new(&params(true), None, None);
+    assert_eq!(cost(&metric, &layout, "laö"), 2.0);
+    assert_eq!(cost(&metric, &layout, "lwa"), 1.0);
+}
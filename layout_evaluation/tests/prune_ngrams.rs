@@ -0,0 +1,105 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::{Bigrams, PruneNgramsConfig, PrunedNgrams, Trigrams, Unigrams},
+};
+
+use std::time::Instant;
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const NGRAMS: &str = "../ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4";
+
+/// The evaluator along with the number of trigrams mapped to the layout
+fn evaluator(
+    unigrams: Unigrams,
+    bigrams: Bigrams,
+    trigrams: Trigrams,
+    layout: &Layout,
+) -> (Evaluator, usize) {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+    let n_trigrams = mapper.map_trigrams(layout).grams.len();
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+
+    (evaluator, n_trigrams)
+}
+
+#[test]
+fn thresholds() {
+    let unigrams = Unigrams::from_text("aaaaaaaabbc").unwrap();
+
+    let config = PruneNgramsConfig {
+        minimum_fraction: 0.1,
+        ..Default::default()
+    };
+    let (pruned_unigrams, pruned) = unigrams.prune(&config);
+    assert_eq!(pruned_unigrams.grams.len(), 2);
+    assert_eq!(
+        pruned,
+        PrunedNgrams {
+            count: 1,
+            weight: 1.0,
+            total_weight: 11.0
+        }
+    );
+
+    let config = PruneNgramsConfig {
+        minimum_weight: 3.0,
+        ..Default::default()
+    };
+    let (pruned_unigrams, pruned) = unigrams.prune(&config);
+    assert_eq!(pruned_unigrams.grams.keys().collect::<Vec<_>>(), vec![&'a']);
+    assert_eq!(pruned.weight, 3.0);
+
+    assert!(!PruneNgramsConfig::default().enabled());
+    assert_eq!(unigrams.prune(&PruneNgramsConfig::default()).1.count, 0);
+}
+
+#[test]
+fn pruning_shrinks_mapping_with_small_cost_change() {
+    let path = |f: &str| format!("{}/{}", NGRAMS, f);
+    let unigrams = Unigrams::from_file(&path("1-grams.txt")).unwrap();
+    let bigrams = Bigrams::from_file(&path("2-grams.txt")).unwrap();
+    let trigrams = Trigrams::from_file(&path("3-grams.txt")).unwrap();
+
+    let config = PruneNgramsConfig {
+        minimum_fraction: 1e-5,
+        ..Default::default()
+    };
+    let (pruned_unigrams, pruned_1) = unigrams.prune(&config);
+    let (pruned_bigrams, pruned_2) = bigrams.prune(&config);
+    let (pruned_trigrams, pruned_3) = trigrams.prune(&config);
+    assert!(pruned_3.count > 0);
+    let max_fraction = [pruned_1, pruned_2, pruned_3]
+        .iter()
+        .map(|p| p.fraction())
+        .fold(0.0, f64::max);
+
+    let layout = layout();
+    let (full, n_full) = evaluator(unigrams, bigrams, trigrams, &layout);
+    let (pruned, n_pruned) = evaluator(pruned_unigrams, pruned_bigrams, pruned_trigrams, &layout);
+    assert!(n_pruned < n_full);
+
+    let start = Instant::now();
+    let cost_full = full.evaluate_layout(&layout).total_cost();
+    let time_full = start.elapsed();
+    let start = Instant::now();
+    let cost_pruned = pruned.evaluate_layout(&layout).total_cost();
+    let time_pruned = start.elapsed();
+    println!(
+        "mapped trigrams: {} -> {}, evaluation time: {:?} -> {:?}, total cost: {:.2} -> {:.2}, \
+         pruned weight fraction: {:.4}",
+        n_full, n_pruned, time_full, time_pruned, cost_full, cost_pruned, max_fraction
+    );
+
+    // the rare ngrams that are dropped tend to be expensive, but the total cost changes less
+    // than the dropped weight
+
+    assert!((cost_pruned - cost_full).abs() / cost_full < max_fraction);
+}
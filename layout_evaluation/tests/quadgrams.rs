@@ -0,0 +1,121 @@
+mod common;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    metrics::quadgram_metrics::QuadgramMetric,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::Quadgrams,
+    results::{MetricType, NormalizationType},
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+fn ngram_mapper(text: &str) -> OnDemandNgramMapper {
+    common::ngram_mapper(text).with_quadgrams(Quadgrams::from_text(text).unwrap())
+}
+
+/// Weight of the quadgrams that are typed with a single hand
+#[derive(Clone, Debug)]
+struct OneHandRuns;
+
+impl QuadgramMetric for OneHandRuns {
+    fn name(&self) -> &str {
+        "One-Hand Runs"
+    }
+
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        k4: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        let hand = k1.key.hand;
+        let one_hand = [k2, k3, k4].iter().all(|k| k.key.hand == hand);
+        Some(if one_hand { weight } else { 0.0 })
+    }
+}
+
+fn evaluator(text: &str, with_quadgram_metric: bool) -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mut evaluator =
+        Evaluator::default(Box::new(ngram_mapper(text))).default_metrics(&eval_params.metrics);
+    if with_quadgram_metric {
+        evaluator.quadgram_metric(
+            Box::new(OneHandRuns),
+            1.0,
+            NormalizationType::WeightFound(1.0),
+        );
+    }
+    evaluator
+}
+
+#[test]
+fn quadgrams_from_text_and_frequencies() {
+    let quadgrams = Quadgrams::from_text("abcde abcd\n").unwrap();
+    assert_eq!(quadgrams.grams.get(&('a', 'b', 'c', 'd')), Some(&2.0));
+    assert_eq!(quadgrams.grams.get(&('d', 'e', ' ', 'a')), Some(&1.0));
+    assert_eq!(quadgrams.total_weight(), 8.0);
+
+    let reloaded = Quadgrams::from_frequencies_str(&quadgrams.frequencies_str()).unwrap();
+    assert_eq!(reloaded.grams, quadgrams.grams);
+}
+
+#[test]
+fn hold_modifiers_are_split_off() {
+    let layout = layout();
+    let shift = layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&'X').unwrap());
+    let shift = layout.get_layerkey(&shift.1.layerkey_indices()[0]).symbol;
+
+    let quadgrams = ngram_mapper("Xabc").map_quadgrams(&layout);
+    let symbols: Vec<(char, char, char, char)> = quadgrams
+        .grams
+        .iter()
+        .map(|((k1, k2, k3, k4), _)| (k1.symbol, k2.symbol, k3.symbol, k4.symbol))
+        .collect();
+
+    assert!(symbols.contains(&('x', 'a', 'b', 'c')));
+    assert!(symbols.contains(&(shift, 'a', 'b', 'c')));
+    assert!(symbols.contains(&(shift, 'x', 'a', 'b')));
+    assert!(quadgrams.modifier_presses.is_some());
+}
+
+#[test]
+fn quadgram_metrics_are_evaluated_on_demand() {
+    let layout = layout();
+    let text = "The quick brown fox jumps over the lazy dog.";
+
+    let without = evaluator(text, false).evaluate_layout(&layout);
+    assert!(without
+        .iter()
+        .all(|r| r.metric_type != MetricType::Quadgram));
+
+    let with = evaluator(text, true).evaluate_layout(&layout);
+    let quadgram_results = with
+        .iter()
+        .find(|r| r.metric_type == MetricType::Quadgram)
+        .unwrap();
+    assert_eq!(quadgram_results.metric_costs.len(), 1);
+    assert!(quadgram_results.found_weight > 0.0);
+    assert!(with.total_cost() > without.total_cost());
+}
+
+#[test]
+fn shared_mapping_agrees_for_quadgrams() {
+    let layout = layout();
+    let evaluator = evaluator("Hello World, the Quick Brown Fox.", true);
+
+    let expected = evaluator.evaluate_layout(&layout).total_cost();
+    let shared = evaluator
+        .with_shared_mapping(&[&layout])
+        .evaluate_layout(&layout)
+        .total_cost();
+
+    // the order of summation may differ between mappers
+    assert!((expected - shared).abs() < 1e-9 * expected.abs().max(1.0));
+}
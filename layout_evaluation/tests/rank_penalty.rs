@@ -0,0 +1,75 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    metrics::layout_metrics::{
+        rank_penalty::{Parameters, RankPenalty},
+        LayoutMetric,
+    },
+    ngram_cache,
+    ngrams::Unigrams,
+};
+
+use std::fs;
+
+use common::LAYOUT;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+/// The metric ranking the symbols by their frequency in `text`.
+fn metric(name: &str, text: &str) -> RankPenalty {
+    let path = std::env::temp_dir().join(format!(
+        "layout_evaluation_rank_penalty_{}_{}.txt",
+        name,
+        std::process::id()
+    ));
+    Unigrams::from_text(text)
+        .unwrap()
+        .save_frequencies(&path)
+        .unwrap();
+
+    let metric = RankPenalty::new(&Parameters {
+        unigrams_filename: path.to_str().unwrap().to_string(),
+        n_listed: 5,
+    });
+
+    // the metric reads the file (and writes its cache sidecar) on construction
+    fs::remove_file(ngram_cache::cache_path(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    metric
+}
+
+#[test]
+fn greedy_layout() {
+    let layout = layout();
+    // 'a', 'e', 'n', and 'r' are on the cheapest keys (cost 2)
+    let metric = metric("greedy", "aaaaeeennr");
+
+    let (cost, message) = metric.total_cost(&layout);
+    assert_eq!(cost, 0.0);
+    assert_eq!(message, None);
+}
+
+#[test]
+fn misplaced_symbols() {
+    let layout = layout();
+    // 'x' (cost 25) and 'v' (cost 20) take the ranks 2 and 3 that belong on keys of cost 2
+    let metric = metric("misplaced", "aaaaxxxvve");
+
+    let (cost, message) = metric.total_cost(&layout);
+    let x_penalty = 0.3 * (25.0 - 2.0);
+    let v_penalty = 0.2 * (20.0 - 2.0);
+    assert!((cost - (x_penalty + v_penalty)).abs() < 1e-9);
+
+    // the largest penalty is listed first, symbols on ideal keys are not listed
+    assert_eq!(
+        message.unwrap(),
+        "Largest rank penalties: x (rank 2, cost 25.0 instead of 2.0, 65.71%), \
+         v (rank 3, cost 20.0 instead of 2.0, 34.29%)"
+    );
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    metrics::layout_metrics::required_symbols::{self, RequiredSymbols},
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    results::{MetricType, NormalizationType},
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// "☃" is not part of the layout and makes up a tenth of the unigram weight
+const TEXT: &str = "Schnee☃man";
+
+fn mapper() -> OnDemandNgramMapper {
+    common::ngram_mapper(TEXT)
+}
+
+/// Evaluate the layout with only the required symbols metric and return its cost and message
+fn required_symbols_result(symbols: &str) -> (f64, Option<String>) {
+    let mut evaluator = Evaluator::default(Box::new(mapper()));
+    evaluator.layout_metric(
+        Box::new(RequiredSymbols::new(&required_symbols::Parameters {
+            symbols: symbols.to_string(),
+            penalty: 10.0,
+        })),
+        1.0,
+        NormalizationType::Fixed(1.0),
+    );
+
+    let result = evaluator.evaluate_layout(&layout());
+    let layout_results = result
+        .iter()
+        .find(|mr| mr.metric_type == MetricType::Layout)
+        .unwrap();
+    let metric = &layout_results.metric_costs[0].core;
+
+    (metric.cost, metric.message.clone())
+}
+
+#[test]
+fn missing_symbols_are_penalized_by_corpus_weight() {
+    let (cost, message) = required_symbols_result("e☃");
+    assert!((cost - 10.0 * 0.1).abs() < 1e-9);
+    assert_eq!(
+        message.as_deref(),
+        Some("Missing required symbols: ☃ (10.00%)")
+    );
+}
+
+#[test]
+fn missing_symbols_without_corpus_weight_are_listed() {
+    let (cost, message) = required_symbols_result("✓e");
+    assert_eq!(cost, 0.0);
+    assert_eq!(
+        message.as_deref(),
+        Some("Missing required symbols: ✓ (0.00%)")
+    );
+}
+
+#[test]
+fn complete_layouts_cost_nothing() {
+    let (cost, message) = required_symbols_result("aeiouäöüß");
+    assert_eq!(cost, 0.0);
+    assert_eq!(message, None);
+}
+
+#[test]
+fn metric_is_read_from_evaluation_config() {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let required = eval_params.metrics.required_symbols.as_mut().unwrap();
+    assert!(!required.enabled);
+    required.enabled = true;
+    required.params.symbols.push('☃');
+    let penalty = required.params.penalty;
+
+    let evaluator = Evaluator::default(Box::new(mapper())).default_metrics(&eval_params.metrics);
+    let result = evaluator.evaluate_layout(&layout());
+    let metric = result
+        .iter()
+        .flat_map(|mr| mr.metric_costs.iter())
+        .find(|mc| mc.core.name == "Missing Required Symbols")
+        .unwrap();
+    assert!((metric.core.cost - penalty * 0.1).abs() < 1e-9);
+    assert!(metric.core.message.as_ref().unwrap().contains('☃'));
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+/// A corpus whose weights are normalized (they sum up to 1 per ngram type)
+const NORMALIZED_CORPUS: &str = "../ngrams/oxey_english";
+
+/// An evaluator for the normalized corpus with all weights multiplied by `scale`
+fn evaluator(scale: f64) -> Evaluator {
+    let mut eval_params = EvaluationParameters::from_yaml(common::EVALUATION_PARAMETERS).unwrap();
+    // the irregularity metric multiplies the weights of bigrams, so its cost depends on their
+    // scale even without resampling
+    if let Some(irregularity) = eval_params.metrics.irregularity.as_mut() {
+        irregularity.enabled = false;
+    }
+
+    let file = |n: usize| format!("{}/{}-grams.txt", NORMALIZED_CORPUS, n);
+    let mut unigrams = Unigrams::from_file_with_cache(&file(1), false).unwrap();
+    let mut bigrams = Bigrams::from_file_with_cache(&file(2), false).unwrap();
+    let mut trigrams = Trigrams::from_file_with_cache(&file(3), false).unwrap();
+    unigrams.grams.values_mut().for_each(|w| *w *= scale);
+    bigrams.grams.values_mut().for_each(|w| *w *= scale);
+    trigrams.grams.values_mut().for_each(|w| *w *= scale);
+
+    let mapper =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+#[test]
+fn robustness_of_a_normalized_corpus() {
+    let layout = common::layout();
+
+    let mut spreads = Vec::new();
+    for scale in [1.0, 1e6] {
+        let evaluator = evaluator(scale);
+        let total_cost = evaluator.evaluate_layout(&layout).total_cost();
+        let robustness = evaluator.evaluate_robustness(&layout, 20, 100_000.0, 0);
+
+        assert!(
+            robustness.total.lower <= total_cost && total_cost <= robustness.total.upper,
+            "scale {}: total cost {} outside of [{}, {}]",
+            scale,
+            total_cost,
+            robustness.total.lower,
+            robustness.total.upper
+        );
+        assert!(robustness.total.std > 0.0);
+        assert!(robustness.total.std < 0.01 * total_cost);
+
+        spreads.push(robustness.total.upper - robustness.total.lower);
+    }
+
+    assert!(
+        (spreads[0] / spreads[1] - 1.0).abs() < 1e-3,
+        "interval widths {:?}",
+        spreads
+    );
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog, again.";
+
+fn mapper(text: &str, secondary_bigrams: bool) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.secondary_bigrams.enabled = secondary_bigrams;
+    common::ngram_mapper_with(text, config)
+}
+
+fn secondary_bigrams_cost(mapper: OnDemandNgramMapper, layout: &Layout) -> f64 {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+
+    evaluator
+        .evaluate_layout(layout)
+        .iter()
+        .flat_map(|results| results.metric_costs.iter())
+        .find(|result| result.core.name == "Secondary Bigrams")
+        .unwrap()
+        .core
+        .cost
+}
+
+#[test]
+fn secondary_bigrams_are_weighted_by_handswitch() {
+    let layout = layout();
+
+    // "u", "i", and "a" are typed with the left hand
+    let same_hand = mapper("uia", true).map_trigrams(&layout);
+    let bigrams = same_hand.secondary_bigrams.unwrap();
+    assert_eq!(bigrams.len(), 1);
+    let ((k1, k2), w) = bigrams[0];
+    assert_eq!((k1.symbol, k2.symbol, w), ('u', 'a', 0.7));
+
+    // "n" is typed with the right hand
+    let handswitch = mapper("una", true).map_trigrams(&layout);
+    let bigrams = handswitch.secondary_bigrams.unwrap();
+    assert_eq!(bigrams.len(), 1);
+    assert_eq!(bigrams[0].1, 0.8);
+
+    // a mental pause
+    let pause = mapper(", a", true).map_trigrams(&layout);
+    assert!(pause.secondary_bigrams.unwrap().is_empty());
+
+    assert!(mapper("uia", false)
+        .map_trigrams(&layout)
+        .secondary_bigrams
+        .is_none());
+}
+
+#[test]
+fn metric_cost_equals_cost_of_derived_secondary_bigrams() {
+    let layout = layout();
+    let derived = secondary_bigrams_cost(mapper(TEXT, true), &layout);
+    let individual = secondary_bigrams_cost(mapper(TEXT, false), &layout);
+
+    assert!(derived > 0.0);
+    assert!((derived - individual).abs() < 1e-9 * individual);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig, grouped_layout_generator::GroupedLayoutGenerator, keyboard::Keyboard,
+    layout::Layout, layout_generator::LayoutGenerator,
+};
+use layout_evaluation::ngram_mapper::shared_ngram_mapper;
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/ortho.yml";
+const BASE_LAYER: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const HIGHER_LAYERS: [&str; 3] = [
+    "ſ…_[]^!<>=&@\\/{}*?()-:#$|~`+%\"';",
+    "…ſ[_]^<!>=@&/\\}{*?)(-:$#|~`+\"%';",
+    "@&=><!^][_…ſ:-)(?*}{/\\';\"%+`~|$#",
+];
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox (jumps) over the [lazy] dog! {a: 1 + 2 = 3} \
+                    \"Quoted\" text & more; 100% <tags> with $cash, #hash, @mail ~ `code`?";
+
+fn layouts() -> Vec<Layout> {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = GroupedLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+
+    HIGHER_LAYERS
+        .iter()
+        .map(|higher| {
+            layout_generator
+                .generate(&format!("{}{}", BASE_LAYER, higher))
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn stable_symbols_exclude_moved_higher_layer_symbols() {
+    let layouts = layouts();
+    let layouts: Vec<&Layout> = layouts.iter().collect();
+    let stable = shared_ngram_mapper::stable_symbols(&layouts);
+
+    assert!(BASE_LAYER
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .all(|c| stable.contains(&c)));
+    assert!(stable.contains(&'X'));
+    assert!(!stable.contains(&'ſ'));
+    assert!(!stable.contains(&'('));
+    // also generated by higher layers that are moved
+    assert!(!stable.contains(&','));
+}
+
+#[test]
+fn shared_mapping_matches_independent_evaluations() {
+    let layouts = layouts();
+    let evaluator = common::evaluator(TEXT);
+    let shared = evaluator.with_shared_mapping(&layouts.iter().collect::<Vec<_>>());
+
+    for layout in layouts.iter() {
+        let expected = evaluator.evaluate_layout(layout);
+        let result = shared.evaluate_layout(layout);
+
+        assert!((expected.total_cost() - result.total_cost()).abs() < 1e-9);
+        for (mr_expected, mr) in expected.iter().zip(result.iter()) {
+            assert_eq!(mr_expected.metric_type, mr.metric_type);
+            assert!((mr_expected.found_weight - mr.found_weight).abs() < 1e-9);
+            assert!((mr_expected.not_found_weight - mr.not_found_weight).abs() < 1e-9);
+            for (c_expected, c) in mr_expected.metric_costs.iter().zip(mr.metric_costs.iter()) {
+                assert_eq!(c_expected.core.name, c.core.name);
+                assert!(
+                    (c_expected.weighted_cost - c.weighted_cost).abs() < 1e-9,
+                    "{}: {} != {}",
+                    c.core.name,
+                    c_expected.weighted_cost,
+                    c.weighted_cost
+                );
+            }
+        }
+    }
+}
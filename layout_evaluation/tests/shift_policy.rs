@@ -0,0 +1,184 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{
+        common::ShiftPolicy, on_demand_ngram_mapper::OnDemandNgramMapper, MappedBigrams,
+        MappedTrigrams, NgramMapper,
+    },
+};
+
+use common::{layout, EVALUATION_PARAMETERS};
+use std::collections::BTreeMap;
+
+fn ngram_mapper(text: &str, shift_policy: ShiftPolicy) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.shift_policy = shift_policy;
+
+    common::ngram_mapper_with(text, config)
+}
+
+fn presses(bigrams: &MappedBigrams) -> f64 {
+    bigrams
+        .modifier_presses
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|(_, w)| w)
+        .sum()
+}
+
+/// Bigrams as sorted (symbol, symbol, weight) triples
+fn symbols(bigrams: &MappedBigrams) -> Vec<(char, char, f64)> {
+    let mut symbols: Vec<(char, char, f64)> = bigrams
+        .grams
+        .iter()
+        .map(|((k1, k2), w)| (k1.symbol, k2.symbol, *w))
+        .collect();
+    symbols.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    symbols
+}
+
+/// Weights of the bigrams summed by their symbols
+fn weights(bigrams: &MappedBigrams) -> BTreeMap<(char, char), f64> {
+    let mut weights = BTreeMap::new();
+    bigrams.grams.iter().for_each(|((k1, k2), w)| {
+        *weights.entry((k1.symbol, k2.symbol)).or_insert(0.0) += w;
+    });
+    weights
+}
+
+/// Trigrams as sorted (symbols, weight) pairs
+fn trigram_symbols(trigrams: &MappedTrigrams) -> Vec<((char, char, char), f64)> {
+    let mut symbols: Vec<((char, char, char), f64)> = trigrams
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ((k1.symbol, k2.symbol, k3.symbol), *w))
+        .collect();
+    symbols.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    symbols
+}
+
+#[test]
+fn layout_policy_uses_configured_modifiers() {
+    // the shift layer of the standard layout config is a hold layer
+    let layout = layout();
+    let text = "Hello World, XVL";
+    let configured = ngram_mapper(text, ShiftPolicy::Layout).map_bigrams(&layout);
+    let hold = ngram_mapper(text, ShiftPolicy::Hold).map_bigrams(&layout);
+
+    assert_eq!(symbols(&configured), symbols(&hold));
+    assert_eq!(presses(&configured), presses(&hold));
+}
+
+#[test]
+fn one_shot_taps_modifier_before_key() {
+    let layout = layout();
+    let shift = layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&'X').unwrap());
+    let shift = layout.get_layerkey(&shift.1.layerkey_indices()[0]).symbol;
+    let bigrams = ngram_mapper("Xe", ShiftPolicy::OneShot).map_bigrams(&layout);
+
+    let mut expected = vec![(shift, 'x', 1.0), ('x', 'e', 1.0)];
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(symbols(&bigrams), expected);
+    assert_eq!(presses(&bigrams), 1.0);
+}
+
+#[test]
+fn caps_word_needs_modifiers_only_at_start_of_run() {
+    let layout = layout();
+    let one_shot = ngram_mapper("XVL", ShiftPolicy::OneShot).map_bigrams(&layout);
+    let caps_word = ngram_mapper("XVL", ShiftPolicy::CapsWord).map_bigrams(&layout);
+
+    // "XV" and "VL": two presses each with one-shot shift, one each with caps word
+    assert_eq!(presses(&one_shot), 4.0);
+    assert_eq!(presses(&caps_word), 2.0);
+    assert!(symbols(&caps_word)
+        .iter()
+        .any(|(c1, c2, _)| *c1 == 'x' && *c2 == 'v'));
+
+    // runs end at unshifted symbols
+    let interrupted = ngram_mapper("X.V", ShiftPolicy::CapsWord).map_bigrams(&layout);
+    assert_eq!(presses(&interrupted), 2.0);
+}
+
+#[test]
+fn sentence_start_taps_modifier_only_after_sentence_end() {
+    let layout = layout();
+    let trigrams =
+        |text, policy| trigram_symbols(&ngram_mapper(text, policy).map_trigrams(&layout));
+
+    // a capital letter following ". " is typed with a one-shot shift
+    let sentence_start = trigrams(". T", ShiftPolicy::SentenceStart);
+    assert_eq!(sentence_start, trigrams(". T", ShiftPolicy::OneShot));
+    assert_ne!(sentence_start, trigrams(". T", ShiftPolicy::Hold));
+
+    // within a word, shift is held
+    let mid_word = trigrams("xTy", ShiftPolicy::SentenceStart);
+    assert_eq!(mid_word, trigrams("xTy", ShiftPolicy::Hold));
+    assert_ne!(mid_word, trigrams("xTy", ShiftPolicy::OneShot));
+}
+
+#[test]
+fn sentence_start_splits_ngrams_lacking_the_context() {
+    let layout = layout();
+    let bigrams = |text, policy| ngram_mapper(text, policy).map_bigrams(&layout);
+
+    // the bigram " T" lacks the punctuation, but each "T" of the corpus starts a sentence
+    let sentence_start = bigrams(". T", ShiftPolicy::SentenceStart);
+    assert_eq!(
+        symbols(&sentence_start),
+        symbols(&bigrams(". T", ShiftPolicy::OneShot))
+    );
+    assert_ne!(
+        symbols(&sentence_start),
+        symbols(&bigrams(". T", ShiftPolicy::Hold))
+    );
+
+    // half of the "T"s start a sentence, so the bigrams " T" and "T " are split evenly
+    let text = ". T x T";
+    let sentence_start = weights(&bigrams(text, ShiftPolicy::SentenceStart));
+    let one_shot = weights(&bigrams(text, ShiftPolicy::OneShot));
+    let hold = weights(&bigrams(text, ShiftPolicy::Hold));
+    let symbols: Vec<_> = one_shot.keys().chain(hold.keys()).collect();
+    for s in symbols {
+        let weight = |weights: &BTreeMap<(char, char), f64>| *weights.get(s).unwrap_or(&0.0);
+        let expected = 0.5 * weight(&one_shot) + 0.5 * weight(&hold);
+        assert!(
+            (weight(&sentence_start) - expected).abs() < 1e-9,
+            "{:?}: {} instead of {}",
+            s,
+            weight(&sentence_start),
+            expected
+        );
+    }
+}
+
+#[test]
+fn evaluator_switches_shift_policy() {
+    let layout = layout();
+    let text = "The Quick Brown Fox. HELLO WORLD";
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let evaluator = Evaluator::default(Box::new(ngram_mapper(text, ShiftPolicy::Layout)))
+        .default_metrics(&eval_params.metrics);
+
+    let cost = |policy| {
+        evaluator
+            .with_shift_policy(policy)
+            .unwrap()
+            .evaluate_layout(&layout)
+            .total_cost()
+    };
+    let configured = evaluator.evaluate_layout(&layout).total_cost();
+
+    // the order of summation may differ between mappers
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9 * a.abs().max(1.0);
+    assert!(close(cost(ShiftPolicy::Layout), configured));
+    assert!(close(cost(ShiftPolicy::Hold), configured));
+    assert!(!close(cost(ShiftPolicy::OneShot), configured));
+    assert!(!close(
+        cost(ShiftPolicy::CapsWord),
+        cost(ShiftPolicy::OneShot)
+    ));
+}
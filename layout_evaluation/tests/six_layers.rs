@@ -0,0 +1,121 @@
+mod common;
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::{Hand, MatrixPosition},
+    keyboard::Keyboard,
+    layout::{LayerModifierLocations, LayerModifiers, Layout, ModifierError, ModifierLocation},
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{config::EvaluationParameters, evaluation::Evaluator};
+
+use std::sync::Arc;
+
+use common::EVALUATION_PARAMETERS;
+
+const LAYOUT_CONFIG: &str = "tests/fixtures/six_layers.yml";
+const LAYOUT: &str = "abcdef";
+/// A text using the symbols of all six layers
+const TEXT: &str = "abcDef123?(α∀)∅ε∞fA";
+
+fn generate(layout_config: LayoutConfig, layout: &str) -> Result<Layout, anyhow::Error> {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+
+    layout_generator.generate(layout)
+}
+
+fn layout(layout: &str) -> Layout {
+    generate(LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap(), layout).unwrap()
+}
+
+/// The symbols of the modifiers of a symbol
+fn modifier_symbols(layout: &Layout, c: char) -> (LayerModifiers, Vec<char>) {
+    let (_, mods) = layout.resolve_modifiers(&layout.get_layerkey_index_for_symbol(&c).unwrap());
+    let symbols = mods
+        .layerkey_indices()
+        .iter()
+        .map(|idx| layout.get_layerkey(idx).symbol)
+        .collect();
+
+    (mods, symbols)
+}
+
+#[test]
+fn each_layer_has_its_own_modifiers() {
+    let layout = layout(LAYOUT);
+    assert_eq!(layout.n_layers(), 6);
+
+    for (layer, c) in ['a', 'A', '1', '!', 'α', '∀'].iter().enumerate() {
+        assert_eq!(
+            layout.get_layerkey_for_symbol(c).unwrap().layer as usize,
+            layer
+        );
+    }
+
+    // "a" is typed with the left hand, so the modifiers of the right hand are used
+    let (mods, symbols) = modifier_symbols(&layout, 'A');
+    assert!(matches!(mods, LayerModifiers::Hold(_)));
+    assert_eq!(symbols, vec!['⇪']);
+    let (mods, symbols) = modifier_symbols(&layout, '1');
+    assert!(matches!(mods, LayerModifiers::OneShot(_)));
+    assert_eq!(symbols, vec!['⌘']);
+    let (mods, symbols) = modifier_symbols(&layout, '!');
+    assert!(matches!(mods, LayerModifiers::Lock(_)));
+    assert_eq!(symbols, vec!['⌘', '⇪']);
+    let (mods, symbols) = modifier_symbols(&layout, 'α');
+    assert!(matches!(mods, LayerModifiers::Hold(_)));
+    assert_eq!(symbols, vec!['⌘', '⇪']);
+    let (mods, _) = modifier_symbols(&layout, '∀');
+    assert_eq!(mods, LayerModifiers::LongPress);
+}
+
+#[test]
+fn all_layers_move_with_the_base_layer() {
+    let layout = layout("fedcba");
+    let a = layout.get_layerkey_for_symbol(&'a').unwrap();
+    let forall = layout.get_layerkey_for_symbol(&'∀').unwrap();
+    assert_eq!(a.key.matrix_position, MatrixPosition(8, 0));
+    assert_eq!(forall.key.matrix_position, a.key.matrix_position);
+
+    // the symbols of a right-hand key are accessed with the left hand's modifiers
+    let (_, symbols) = modifier_symbols(&layout, 'α');
+    assert_eq!(symbols, vec!['⇧', '⌥']);
+
+    let plots: Vec<String> = (0..layout.n_layers())
+        .map(|layer| layout.plot_layer(layer))
+        .collect();
+    assert!(plots[0].contains('a'));
+    assert!(plots[5].contains('∀'));
+}
+
+#[test]
+fn all_layers_are_evaluated() {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = common::ngram_mapper_with(TEXT, eval_params.ngram_mapper);
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+
+    let result = evaluator.evaluate_layout(&layout(LAYOUT));
+    assert!(result.total_cost().is_finite());
+    for metric_results in result.iter() {
+        assert_eq!(metric_results.not_found_weight, 0.0);
+    }
+}
+
+#[test]
+fn unknown_modifier_names_layer() {
+    let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let unknown = ModifierLocation::Position(MatrixPosition(3, 1));
+    layout_config.base_layout.modifiers[3].insert(
+        Hand::Left,
+        LayerModifierLocations::Hold(vec![unknown.clone()]),
+    );
+
+    let err = generate(layout_config, LAYOUT).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ModifierError>(),
+        Some(&ModifierError::NotFound(4, unknown))
+    );
+    assert!(err.to_string().contains("layer 4"));
+}
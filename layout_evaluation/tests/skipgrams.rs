@@ -0,0 +1,85 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    results::{EvaluationResult, MetricType},
+};
+
+use std::fs;
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// The default evaluation parameters with "Finger Repeats" evaluated on skipgrams.
+fn eval_params_with_skipgrams() -> EvaluationParameters {
+    let data = fs::read_to_string(EVALUATION_PARAMETERS).unwrap();
+    let data = data.replacen(
+        "  finger_repeats:\n    enabled: true\n",
+        "  finger_repeats:\n    enabled: true\n    skipgrams: true\n",
+        1,
+    );
+    EvaluationParameters::from_str_with_profile(&data, None).unwrap()
+}
+
+fn metric_names(result: &EvaluationResult, metric_type: MetricType) -> Vec<String> {
+    result
+        .iter()
+        .filter(|mr| mr.metric_type == metric_type)
+        .flat_map(|mr| mr.metric_costs.iter().map(|mc| mc.core.name.clone()))
+        .collect()
+}
+
+#[test]
+fn trigram_yields_decayed_skipgram() {
+    let mut eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    eval_params.ngram_mapper.skipgram_decay = 0.25;
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text("abc").unwrap(),
+        Bigrams::from_text("abc").unwrap(),
+        Trigrams::from_text("abc").unwrap(),
+        eval_params.ngram_mapper,
+    );
+
+    let layout = layout();
+    let skipgrams = mapper.map_skipgrams(&layout);
+    let grams: Vec<((char, char), f64)> = skipgrams
+        .grams
+        .iter()
+        .map(|((k1, k2), w)| ((k1.symbol, k2.symbol), *w))
+        .collect();
+    assert_eq!(grams, vec![(('a', 'c'), 0.25)]);
+    assert_eq!(skipgrams.weight_found, 0.25);
+    assert_eq!(skipgrams.weight_not_found, 0.0);
+}
+
+#[test]
+fn bigram_metrics_can_consume_skipgrams() {
+    let eval_params = eval_params_with_skipgrams();
+    let mapper = common::ngram_mapper_with(TEXT, eval_params.ngram_mapper);
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+
+    let layout = layout();
+    let result = evaluator.evaluate_layout(&layout);
+    assert_eq!(
+        metric_names(&result, MetricType::Skipgram),
+        vec!["Finger Repeats"]
+    );
+    assert!(!metric_names(&result, MetricType::Bigram).contains(&"Finger Repeats".to_string()));
+
+    // sharing the mapping yields the same skipgram costs
+    let shared = evaluator.with_shared_mapping(&[&layout]);
+    assert!((shared.evaluate_layout(&layout).total_cost() - result.total_cost()).abs() < 1e-9);
+
+    // skipgrams are derived from trigrams
+    let reduced = evaluator.skip_metric_types(&[MetricType::Trigram]);
+    assert_eq!(
+        reduced.skipped_metric_types(),
+        &[MetricType::Trigram, MetricType::Skipgram]
+    );
+    assert!(metric_names(&reduced.evaluate_layout(&layout), MetricType::Skipgram).is_empty());
+}
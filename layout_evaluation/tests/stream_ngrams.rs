@@ -0,0 +1,56 @@
+use layout_evaluation::{
+    corpus,
+    ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams},
+};
+
+use std::io::BufReader;
+
+/// Multi-byte characters (that are split across reads), line breaks, and words longer than a read
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund.\r\n\
+                    Ωμέγα שָׁלוֹם ☺☺☺ Donaudampfschifffahrtsgesellschaft\n\
+                    The quick brown fox jumps over the lazy dog.";
+
+/// A reader returning (at most) 7 bytes per read
+fn reader(text: &str) -> BufReader<&[u8]> {
+    BufReader::with_capacity(7, text.as_bytes())
+}
+
+#[test]
+fn streamed_counts_equal_in_memory_counts() {
+    assert_eq!(
+        Unigrams::from_reader(reader(TEXT)).unwrap().grams,
+        Unigrams::from_text(TEXT).unwrap().grams
+    );
+    assert_eq!(
+        Bigrams::from_reader(reader(TEXT)).unwrap().grams,
+        Bigrams::from_text(TEXT).unwrap().grams
+    );
+    assert_eq!(
+        Trigrams::from_reader(reader(TEXT)).unwrap().grams,
+        Trigrams::from_text(TEXT).unwrap().grams
+    );
+    assert_eq!(
+        Quadgrams::from_reader(reader(TEXT)).unwrap().grams,
+        Quadgrams::from_text(TEXT).unwrap().grams
+    );
+}
+
+#[test]
+fn chunks_are_valid_text() {
+    let mut chunks = Vec::new();
+    corpus::for_each_chunk(reader(TEXT), |chunk| chunks.push(chunk.to_string())).unwrap();
+
+    assert!(chunks.len() > 1);
+    assert_eq!(chunks.concat(), TEXT);
+    // chunks end before whitespace, i.e. words are not split
+    assert!(chunks
+        .iter()
+        .skip(1)
+        .all(|c| c.starts_with(char::is_whitespace)));
+
+    let invalid: &[u8] = b"abc \xff def";
+    assert!(corpus::for_each_chunk(BufReader::with_capacity(7, invalid), |_| ()).is_err());
+    // a truncated multi-byte character at the end
+    let truncated = &"abc ü".as_bytes()[..5];
+    assert!(corpus::for_each_chunk(truncated, |_| ()).is_err());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use layout_evaluation::{
+    config::EvaluationParameters,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+    ngrams::{Bigrams, Trigrams},
+};
+
+use std::{collections::HashMap, fs};
+
+use common::{layout, EVALUATION_PARAMETERS};
+
+/// A mapper for the text with the given substitutions (as YAML lines)
+fn mapper(text: &str, substitutions: &str) -> OnDemandNgramMapper {
+    let data = fs::read_to_string(EVALUATION_PARAMETERS).unwrap();
+    let data = data.replacen(
+        "  substitutions: {}\n",
+        &format!("  substitutions:\n{}", substitutions),
+        1,
+    );
+    let eval_params = EvaluationParameters::from_str_with_profile(&data, None).unwrap();
+
+    common::ngram_mapper_with(text, eval_params.ngram_mapper)
+}
+
+#[test]
+fn single_symbol_substitution() {
+    let layout = layout();
+    // the layout does not generate the interrobang
+    let without = mapper("a‽b", "    \"x\": \"y\"\n").map_bigrams(&layout);
+    assert_eq!(without.weight_not_found, 2.0);
+
+    let bigrams = mapper("a‽b", "    \"‽\": \"?\"\n").map_bigrams(&layout);
+    assert_eq!(bigrams.weight_not_found, 0.0);
+    assert!(bigrams.missing_symbols.is_empty());
+    assert_eq!(bigrams.weight_found, 2.0);
+}
+
+#[test]
+fn multi_symbol_substitution_rewindows_ngrams() {
+    let layout = layout();
+    let mapper = mapper("a…bc", "    \"…\": \"...\"\n");
+    // the same ngrams as for the substituted text
+    let expected = "a...bc";
+
+    let bigrams: HashMap<(char, char), f64> = mapper
+        .map_bigrams(&layout)
+        .grams
+        .iter()
+        .map(|((k1, k2), w)| ((k1.symbol, k2.symbol), *w))
+        .collect();
+    let expected_bigrams: HashMap<(char, char), f64> = Bigrams::from_text(expected)
+        .unwrap()
+        .grams
+        .into_iter()
+        .collect();
+    assert_eq!(bigrams, expected_bigrams);
+
+    let trigrams: HashMap<(char, char, char), f64> = mapper
+        .map_trigrams(&layout)
+        .grams
+        .iter()
+        .map(|((k1, k2, k3), w)| ((k1.symbol, k2.symbol, k3.symbol), *w))
+        .collect();
+    let expected_trigrams: HashMap<(char, char, char), f64> = Trigrams::from_text(expected)
+        .unwrap()
+        .grams
+        .into_iter()
+        .collect();
+    assert_eq!(trigrams, expected_trigrams);
+}
+
+#[test]
+fn substitution_precedes_line_break_exclusion() {
+    let layout = layout();
+    // the paragraph separator becomes a line break, followed by "b"
+    let mapper = mapper("a\u{2029}b", "    \"\\u2029\": \"\\n\"\n");
+    let stats = mapper.mapping_statistics(&layout).unwrap();
+
+    assert_eq!(stats.bigrams.excluded_weight, 1.0);
+    assert_eq!(stats.bigrams.found_weight, 1.0);
+    assert_eq!(stats.bigrams.not_found_weight, 0.0);
+}
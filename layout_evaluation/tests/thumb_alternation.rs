@@ -0,0 +1,77 @@
+mod common;
+
+use keyboard_layout::{
+    key::{Finger, Hand},
+    layout::{LayerKey, LayerModifierType, Layout},
+};
+use layout_evaluation::metrics::trigram_metrics::{
+    thumb_alternation::{Parameters, ThumbAlternation},
+    TrigramMetric,
+};
+
+use common::LAYOUT;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+/// The (single) hold modifier required for typing the given symbol.
+fn hold_modifier(layout: &Layout, c: char) -> &LayerKey {
+    let key = layout.get_layerkey_for_symbol(&c).unwrap();
+    let modifiers = key.modifiers.layerkey_indices();
+    assert_eq!(modifiers.len(), 1);
+
+    layout.get_layerkey(&modifiers[0])
+}
+
+#[test]
+fn same_thumb_hold_and_press() {
+    let layout = layout();
+    let metric = ThumbAlternation::new(&Parameters {
+        factor_adjacent: 1.0,
+        factor_separated: 0.5,
+    });
+    let cost = |k1: &LayerKey, k2: &LayerKey, k3: &LayerKey| {
+        metric
+            .individual_cost(k1, k2, k3, 1.0, 1.0, &layout)
+            .unwrap()
+    };
+
+    // '−' is on the fourth layer of a key of the left pinky, which is held with the right thumb
+    let right_hold = hold_modifier(&layout, '−');
+    assert_eq!(right_hold.is_modifier, LayerModifierType::Hold);
+    assert_eq!(
+        (right_hold.key.hand, right_hold.key.finger),
+        (Hand::Right, Finger::Thumb)
+    );
+    // '7' is on the fourth layer of a key of the right hand, held with the left thumb
+    let left_hold = hold_modifier(&layout, '7');
+    assert_eq!(left_hold.key.hand, Hand::Left);
+
+    let space = layout.get_layerkey_for_symbol(&' ').unwrap();
+    assert_eq!(
+        (space.key.hand, space.key.finger),
+        (Hand::Right, Finger::Thumb)
+    );
+    let left = layout.get_layerkey_for_symbol(&'e').unwrap();
+    assert_eq!(left.key.hand, Hand::Left);
+    let right = layout.get_layerkey_for_symbol(&'n').unwrap();
+    assert_eq!(right.key.hand, Hand::Right);
+
+    // consecutive hold and press of the same thumb (in both orders)
+    assert_eq!(cost(right_hold, space, left), 1.0);
+    assert_eq!(cost(left, right_hold, space), 1.0);
+    assert_eq!(cost(space, right_hold, left), 1.0);
+
+    // separated by a key of the other hand
+    assert_eq!(cost(right_hold, left, space), 0.0);
+    // separated by a key of the same hand
+    assert_eq!(cost(right_hold, right, space), 0.5);
+
+    // different thumbs
+    assert_eq!(cost(left_hold, space, right), 0.0);
+    // no regular key press of a thumb
+    assert_eq!(cost(right_hold, right, left), 0.0);
+}
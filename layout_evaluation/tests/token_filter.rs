@@ -0,0 +1,30 @@
+use layout_evaluation::{
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    token_filter::TokenFilter,
+};
+
+const TEXT: &str = "visit https://example.com now";
+const FILTERED: &str = "visit  now";
+
+#[test]
+fn urls_do_not_contribute_ngrams() {
+    let filter = TokenFilter::default();
+
+    assert_eq!(
+        Unigrams::from_text_filtered(TEXT, &filter).unwrap().grams,
+        Unigrams::from_text(FILTERED).unwrap().grams
+    );
+    assert_eq!(
+        Bigrams::from_text_filtered(TEXT, &filter).unwrap().grams,
+        Bigrams::from_text(FILTERED).unwrap().grams
+    );
+    assert_eq!(
+        Trigrams::from_text_filtered(TEXT, &filter).unwrap().grams,
+        Trigrams::from_text(FILTERED).unwrap().grams
+    );
+
+    // the removed weight is the number of removed characters
+    let (filtered, n_removed) = filter.apply(TEXT);
+    assert_eq!(filtered, FILTERED);
+    assert_eq!(n_removed, "https://example.com".len());
+}
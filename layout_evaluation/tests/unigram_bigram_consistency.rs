@@ -0,0 +1,68 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngram_mapper::{
+    common::ShiftPolicy, on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper,
+};
+
+/// Capital letters (one-shot shift) and symbols of the (locked) fourth layer
+const TEXT: &str = "Ein ₁₂ Test, der ₃ Endet.";
+
+fn ngram_mapper(text: &str) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.shift_policy = ShiftPolicy::OneShot;
+
+    common::ngram_mapper_with(text, config)
+}
+
+fn total_unigram_weight(layout: &Layout, text: &str) -> f64 {
+    let unigrams = ngram_mapper(text).map_unigrams(layout);
+    unigrams.grams.iter().map(|(_, w)| w).sum()
+}
+
+fn total_bigram_weight(layout: &Layout, text: &str) -> f64 {
+    let bigrams = ngram_mapper(text).map_bigrams(layout);
+    bigrams.grams.iter().map(|(_, w)| w).sum()
+}
+
+#[test]
+fn unigram_weight_matches_bigram_weight() {
+    let layout = common::lock_layer_layout();
+
+    // number of keystrokes for each symbol of the text, as modeled by the unigram mapper
+    let keystrokes: Vec<f64> = TEXT
+        .chars()
+        .map(|c| total_unigram_weight(&layout, &c.to_string()))
+        .collect();
+    assert_eq!(keystrokes[0], 2.0, "one-shot shift and the key");
+    assert!(keystrokes.iter().all(|k| *k >= 1.0));
+
+    let unigram_weight = total_unigram_weight(&layout, TEXT);
+    assert_eq!(unigram_weight, keystrokes.iter().sum::<f64>());
+
+    // Each bigram is split into the bigrams of the keystrokes of its two symbols. The
+    // keystrokes within an inner symbol are therefore part of two bigrams, whereas those of the
+    // first and last symbol of the text are part of only one.
+    let (first, last) = (keystrokes[0], keystrokes[keystrokes.len() - 1]);
+    let n_bigrams = (keystrokes.len() - 1) as f64;
+    assert_eq!(
+        total_bigram_weight(&layout, TEXT),
+        2.0 * unigram_weight - first - last - n_bigrams
+    );
+}
+
+#[test]
+fn lock_layer_symbols_are_mapped_to_base_keys() {
+    let layout = common::lock_layer_layout();
+
+    let unigrams = ngram_mapper(TEXT).map_unigrams(&layout);
+    assert!(unigrams.grams.iter().all(|(k, _)| k.layer == 0));
+    // '₁' is typed with the key of '1'
+    assert!(unigrams.grams.iter().any(|(k, _)| k.symbol == '1'));
+
+    let bigrams = ngram_mapper(TEXT).map_bigrams(&layout);
+    assert!(bigrams
+        .grams
+        .iter()
+        .all(|((k1, k2), _)| k1.layer == 0 && k2.layer == 0));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use keyboard_layout::layout::Layout;
+use layout_evaluation::{
+    metrics::layout_metrics::{
+        word_start_hand::{Parameters, WordStartHand},
+        LayoutMetric,
+    },
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, NgramMapper},
+};
+
+use std::collections::HashMap;
+
+use common::LAYOUT;
+
+const LAYOUT_CONFIG: &str = "../config/keyboard/crkbd.yml";
+
+fn layout() -> Layout {
+    common::generate_layout(LAYOUT_CONFIG, LAYOUT)
+}
+
+fn mapper(text: &str, collapse_space_runs: bool) -> OnDemandNgramMapper {
+    let mut config = common::ngram_mapper_config();
+    config.collapse_space_runs = collapse_space_runs;
+
+    common::ngram_mapper_with(text, config)
+}
+
+fn metric(mapper: &OnDemandNgramMapper) -> WordStartHand {
+    let (word_starts, unigrams) = mapper.word_starts().unwrap();
+
+    WordStartHand::new(
+        &Parameters {
+            min_word_start_fraction: 0.1,
+            left_target: 0.5,
+            n_listed: 2,
+        },
+        word_starts,
+        unigrams,
+    )
+}
+
+#[test]
+fn word_starts_are_derived_from_the_preprocessed_bigrams() {
+    // words start with 'a' (left hand) three times and with 'n' (right hand) once
+    let text = " an  an\tan na";
+
+    for collapse_space_runs in [false, true] {
+        let mapper = mapper(text, collapse_space_runs);
+        let (word_starts, _) = mapper.word_starts().unwrap();
+
+        let word_initial: HashMap<char, f64> = word_starts
+            .word_initial
+            .grams
+            .iter()
+            .map(|(c, w)| (*c, *w))
+            .collect();
+        assert_eq!(
+            word_initial,
+            [('a', 3.0), ('n', 1.0)].iter().cloned().collect()
+        );
+
+        // the bigram of two spaces is dropped when collapsing whitespace runs
+        let n_bigrams = if collapse_space_runs { 11.0 } else { 12.0 };
+        assert_eq!(word_starts.fraction, 4.0 / n_bigrams);
+    }
+
+    let (cost, message) = metric(&mapper(text, true)).total_cost(&layout());
+    assert_eq!(cost, 0.25);
+    assert!(message
+        .unwrap()
+        .starts_with("Word starts % (no thumb): 75.00 - 25.00; Left: a (75.0%); Right: n (25.0%)"));
+}
+
+#[test]
+fn unigrams_are_used_for_text_without_whitespace() {
+    // the only word starts with 'n', but the unigrams are mostly typed by the left hand
+    let text = "naaaa";
+
+    let mapper = mapper(text, false);
+    let (word_starts, _) = mapper.word_starts().unwrap();
+    assert!(word_starts.word_initial.grams.is_empty());
+    assert_eq!(word_starts.fraction, 0.0);
+
+    let (cost, message) = metric(&mapper).total_cost(&layout());
+    assert!((cost - 0.3).abs() < 1e-9);
+    assert!(message
+        .unwrap()
+        .starts_with("Word starts % (no thumb): 80.00 - 20.00; Left: a (80.0%); Right: n (20.0%)"));
+}
@@ -0,0 +1,120 @@
+//! The `blacklist` module provides a hard filter for layouts that must not be the result of an
+//! optimization, e.g. already published layouts.
+
+use keyboard_layout::layout::Layout;
+
+/// Cost of an (imaginary) layout identical to a blacklisted one. This is far above the cost of
+/// any realistic layout.
+pub const INFEASIBLE_COST: f64 = 1e9;
+
+/// A list of forbidden layouts. Candidate layouts whose similarity (see [`Layout::similarity`])
+/// to any of them exceeds the threshold are considered infeasible.
+#[derive(Clone, Debug)]
+pub struct Blacklist {
+    layouts: Vec<Layout>,
+    threshold: f64,
+}
+
+impl Blacklist {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            layouts: Vec::new(),
+            threshold,
+        }
+    }
+
+    pub fn add(&mut self, layout: Layout) {
+        self.layouts.push(layout);
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// The highest similarity of the layout to any blacklisted layout together with the
+    /// corresponding blacklisted layout.
+    pub fn max_similarity(&self, layout: &Layout) -> Option<(f64, &Layout)> {
+        self.layouts
+            .iter()
+            .map(|l| (layout.similarity(l), l))
+            .max_by(|(s1, _), (s2, _)| s1.partial_cmp(s2).unwrap())
+    }
+
+    /// Whether the layout is too similar to any blacklisted layout.
+    pub fn forbids(&self, layout: &Layout) -> bool {
+        self.layouts
+            .iter()
+            .any(|l| layout.similarity(l) > self.threshold)
+    }
+
+    /// The cost to use for a forbidden layout (`None` if the layout is allowed).
+    ///
+    /// The cost decreases with the similarity to the closest blacklisted layout. This way, an
+    /// optimization starting from a forbidden layout can still find its way out of the forbidden
+    /// region, whereas a constant (or infinite) cost would not give any direction.
+    pub fn infeasible_cost(&self, layout: &Layout) -> Option<f64> {
+        self.max_similarity(layout)
+            .map(|(similarity, _)| similarity)
+            .filter(|similarity| *similarity > self.threshold)
+            .map(|similarity| INFEASIBLE_COST * similarity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use keyboard_layout::{
+        config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+        neo_layout_generator::NeoLayoutGenerator,
+    };
+    use std::sync::Arc;
+
+    fn layout_generator() -> NeoLayoutGenerator {
+        let layout_config = LayoutConfig::from_yaml("../../config/keyboard/standard.yml").unwrap();
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+    }
+
+    #[test]
+    fn forbid_similar_layouts() {
+        let generator = layout_generator();
+        let mut blacklist = Blacklist::new(0.9);
+        blacklist.add(
+            generator
+                .generate("xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+                .unwrap(),
+        );
+
+        let same = generator
+            .generate("xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+            .unwrap();
+        assert_eq!(blacklist.max_similarity(&same).unwrap().0, 1.0);
+        assert!(blacklist.forbids(&same));
+
+        // one swapped pair: 30 of 32 keys are the same
+        let swapped = generator
+            .generate("vxlcwkhgfqyßuiaeosnrtdüöäpzbm,.j")
+            .unwrap();
+        assert_eq!(blacklist.max_similarity(&swapped).unwrap().0, 30.0 / 32.0);
+        assert!(blacklist.forbids(&swapped));
+        assert!(
+            blacklist.infeasible_cost(&swapped).unwrap()
+                < blacklist.infeasible_cost(&same).unwrap()
+        );
+
+        let different = generator
+            .generate("kuü.ävgcljfßqhieaodtrnsxyö,pwmbz")
+            .unwrap();
+        assert!(!blacklist.forbids(&different));
+        assert_eq!(blacklist.infeasible_cost(&different), None);
+    }
+}
@@ -0,0 +1,1190 @@
+//! The `constraints` module provides sets of optimization constraints that can be stored in
+//! standalone files (e.g. `constraints.yml`) and shared independently of the optimization
+//! parameters:
+//!
+//! - `pins`: symbols that have to be placed on a given key
+//! - `regions`: symbols that have to be placed on one of a set of keys (e.g. the left half)
+//! - `keycap_groups`: symbols that have to stay on a set of keys (see [`keyboard_layout::keycap_group`])
+//! - `same_hand`, `same_finger`, `preserve_relative_order`: symbols that have to be placed relative
+//!   to each other (see [`crate::symbol_groups`])
+//! - `hand_balance`: the split of the load between both hands (see [`crate::hand_balance`])
+//!
+//! Keys are specified by their matrix positions (`[column, row]`), as in the layout configuration.
+//! Constraint sets from several sources can be merged. Contradicting constraints are reported as
+//! [`Conflict`]s instead of silently preferring one of them.
+
+use crate::{
+    hand_balance::{HandBalance, HandBalanceParameters},
+    symbol_groups::{KeyPlacement, SymbolGroup, SymbolGroupKind},
+    LayoutPermutator,
+};
+
+use keyboard_layout::{
+    key::MatrixPosition,
+    keycap_group::{KeycapGroup, KeycapGroupError, KeycapGroupYAML},
+    layout::Layout,
+    layout_generator::LayoutGenerator,
+};
+use layout_evaluation::evaluation::Evaluator;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs::File};
+use thiserror::Error;
+
+/// Format a matrix position as in the YAML files.
+fn fmt_position(p: &MatrixPosition) -> String {
+    format!("[{}, {}]", p.0, p.1)
+}
+
+/// Two constraints that can not be satisfied at the same time (or an invalid constraint).
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum Conflict {
+    #[error("Symbol '{0}' is pinned to {} and to {}", fmt_position(.1), fmt_position(.2))]
+    PinnedTwice(char, MatrixPosition, MatrixPosition),
+    #[error("Key {} is pinned to symbol '{1}' and to symbol '{2}'", fmt_position(.0))]
+    KeyPinnedTwice(MatrixPosition, char, char),
+    #[error("Symbol '{0}' is pinned to {}, outside of the keys of its keycap group '{2}'", fmt_position(.1))]
+    PinOutsideGroup(char, MatrixPosition, String),
+    #[error("Symbol '{0}' is pinned to {}, a key of keycap group '{2}' it is not part of", fmt_position(.1))]
+    PinInForeignGroup(char, MatrixPosition, String),
+    #[error("Symbol '{0}' has a region without keys")]
+    EmptyRegion(char),
+    #[error("Symbol '{0}' has two different regions")]
+    RegionTwice(char),
+    #[error("Symbol '{0}' is pinned to {}, outside of its region", fmt_position(.1))]
+    PinOutsideRegion(char, MatrixPosition),
+    #[error("The region of symbol '{0}' contains no key of its keycap group '{1}'")]
+    RegionOutsideGroup(char, String),
+    #[error("Symbol group '{0}' contains symbol '{1}' more than once")]
+    DuplicateInSymbolGroup(String, char),
+    #[error("{0}")]
+    KeycapGroup(String),
+    #[error("Different hand balance constraints: {0:?} and {1:?}")]
+    HandBalance(HandBalanceParameters, HandBalanceParameters),
+    #[error(
+        "Invalid hand balance constraint: target ({0}) and margin ({1}) need to be between 0 and 1"
+    )]
+    InvalidHandBalance(f64, f64),
+}
+
+#[derive(Error, Debug)]
+pub enum ConstraintError {
+    #[error("Conflicting constraints: {}", .0.iter().map(|c| c.to_string()).collect::<Vec<_>>().join("; "))]
+    Conflicts(Vec<Conflict>),
+    #[error("Symbol '{0}' of a constraint is not part of the layout")]
+    UnknownSymbol(char),
+    #[error("Key {} of a constraint is not a non-fixed key of the layout", fmt_position(.0))]
+    UnknownPosition(MatrixPosition),
+}
+
+/// A symbol that has to be placed on a given key.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Pin {
+    pub symbol: char,
+    pub position: MatrixPosition,
+}
+
+/// A symbol that has to be placed on one of a set of keys.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub symbol: char,
+    pub positions: Vec<MatrixPosition>,
+}
+
+/// A rule of a [`ConstraintSet`] that is violated by a layout.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// A pinned symbol is placed on another key (at the given index of the layout string)
+    Pin {
+        symbol: char,
+        pinned: MatrixPosition,
+        placed: MatrixPosition,
+        index: usize,
+    },
+    /// A symbol is placed on a key outside of its region
+    Region {
+        symbol: char,
+        placed: MatrixPosition,
+        index: usize,
+    },
+    /// A symbol of a keycap group is placed on a key outside of the group
+    KeycapGroup {
+        group: String,
+        symbol: char,
+        placed: MatrixPosition,
+        index: usize,
+    },
+    /// The symbols of a group are not placed relative to each other as required
+    SymbolGroup {
+        kind: SymbolGroupKind,
+        symbols: String,
+    },
+    /// The fraction of the load on the left hand is outside of the margin
+    HandBalance {
+        left_fraction: f64,
+        target: f64,
+        margin: f64,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Pin {
+                symbol,
+                pinned,
+                placed,
+                index,
+            } => write!(
+                f,
+                "Symbol '{}' is pinned to key {}, but placed on key {} (index {} of the layout string)",
+                symbol,
+                fmt_position(pinned),
+                fmt_position(placed),
+                index,
+            ),
+            Violation::Region {
+                symbol,
+                placed,
+                index,
+            } => write!(
+                f,
+                "Symbol '{}' is placed on key {} (index {} of the layout string) outside of its region",
+                symbol,
+                fmt_position(placed),
+                index,
+            ),
+            Violation::KeycapGroup {
+                group,
+                symbol,
+                placed,
+                index,
+            } => write!(
+                f,
+                "Symbol '{}' of keycap group '{}' is placed on key {} (index {} of the layout string) outside of the group's keys",
+                symbol,
+                group,
+                fmt_position(placed),
+                index,
+            ),
+            Violation::SymbolGroup { kind, symbols } => {
+                write!(f, "Symbols '{}' are not placed {}", symbols, kind)
+            }
+            Violation::HandBalance {
+                left_fraction,
+                target,
+                margin,
+            } => write!(
+                f,
+                "The left hand carries {:.1}% of the load, outside of {:.1}% ± {:.1}%",
+                100.0 * left_fraction,
+                100.0 * target,
+                100.0 * margin,
+            ),
+        }
+    }
+}
+
+/// A set of constraints for the optimization.
+///
+/// Corresponds to a YAML file (unknown entries are rejected).
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConstraintSet {
+    /// Symbols that have to be placed on given keys
+    pub pins: Vec<Pin>,
+    /// Symbols that have to be placed on one of a set of keys
+    pub regions: Vec<Region>,
+    /// Symbols that have to stay on a set of keys as a group
+    pub keycap_groups: Vec<KeycapGroupYAML>,
+    /// Groups of symbols that have to be placed on the same hand
+    pub same_hand: Vec<String>,
+    /// Groups of symbols that have to be placed on keys of the same finger
+    pub same_finger: Vec<String>,
+    /// Groups of symbols that have to be placed in the given order (row by row, left to right)
+    pub preserve_relative_order: Vec<String>,
+    /// Split of the load between both hands
+    pub hand_balance: Option<HandBalanceParameters>,
+}
+
+/// If two keycap groups consist of the same symbols and keys.
+fn same_group(g1: &KeycapGroupYAML, g2: &KeycapGroupYAML) -> bool {
+    g1.symbols.chars().count() == g2.symbols.chars().count()
+        && g1.positions.len() == g2.positions.len()
+        && g1.symbols.chars().all(|c| g2.symbols.contains(c))
+        && g1.positions.iter().all(|p| g2.positions.contains(p))
+}
+
+/// If two regions consist of the same keys.
+fn same_positions(r1: &Region, r2: &Region) -> bool {
+    r1.positions.iter().all(|p| r2.positions.contains(p))
+        && r2.positions.iter().all(|p| r1.positions.contains(p))
+}
+
+impl ConstraintSet {
+    /// Read a constraint set from a YAML file and check it for conflicts.
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let constraints: Self = serde_yaml::from_reader(f)?;
+        constraints.validate()?;
+
+        Ok(constraints)
+    }
+
+    /// Read a constraint set from a YAML string and check it for conflicts.
+    pub fn from_yaml_str(data: &str) -> Result<Self> {
+        let constraints: Self = serde_yaml::from_str(data)?;
+        constraints.validate()?;
+
+        Ok(constraints)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+            && self.regions.is_empty()
+            && self.keycap_groups.is_empty()
+            && self.same_hand.is_empty()
+            && self.same_finger.is_empty()
+            && self.preserve_relative_order.is_empty()
+            && self.hand_balance.is_none()
+    }
+
+    /// The symbols of all pins.
+    pub fn pinned_symbols(&self) -> String {
+        self.pins.iter().map(|p| p.symbol).collect()
+    }
+
+    /// All symbol groups of the set.
+    pub fn symbol_groups(&self) -> Vec<SymbolGroup> {
+        let groups = |kind: SymbolGroupKind, groups: &[String]| {
+            groups
+                .iter()
+                .map(|symbols| SymbolGroup {
+                    kind,
+                    symbols: symbols.chars().collect(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        groups(SymbolGroupKind::SameHand, &self.same_hand)
+            .into_iter()
+            .chain(groups(SymbolGroupKind::SameFinger, &self.same_finger))
+            .chain(groups(
+                SymbolGroupKind::RelativeOrder,
+                &self.preserve_relative_order,
+            ))
+            .collect()
+    }
+
+    /// All pairs of constraints of the set that contradict each other and all invalid constraints.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+
+        for (i, p1) in self.pins.iter().enumerate() {
+            for p2 in self.pins.iter().skip(i + 1) {
+                if p1.symbol == p2.symbol && p1.position != p2.position {
+                    conflicts.push(Conflict::PinnedTwice(p1.symbol, p1.position, p2.position));
+                } else if p1.position == p2.position && p1.symbol != p2.symbol {
+                    conflicts.push(Conflict::KeyPinnedTwice(p1.position, p1.symbol, p2.symbol));
+                }
+            }
+        }
+
+        for (i, r1) in self.regions.iter().enumerate() {
+            if r1.positions.is_empty() {
+                conflicts.push(Conflict::EmptyRegion(r1.symbol));
+            }
+            for r2 in self.regions.iter().skip(i + 1) {
+                if r1.symbol == r2.symbol && !same_positions(r1, r2) {
+                    conflicts.push(Conflict::RegionTwice(r1.symbol));
+                }
+            }
+            for pin in self.pins.iter().filter(|p| p.symbol == r1.symbol) {
+                if !r1.positions.contains(&pin.position) {
+                    conflicts.push(Conflict::PinOutsideRegion(pin.symbol, pin.position));
+                }
+            }
+            for group in self.keycap_groups.iter() {
+                if group.symbols.contains(r1.symbol)
+                    && !r1.positions.iter().any(|p| group.positions.contains(p))
+                {
+                    conflicts.push(Conflict::RegionOutsideGroup(
+                        r1.symbol,
+                        group.symbols.clone(),
+                    ));
+                }
+            }
+        }
+
+        for (i, g1) in self.keycap_groups.iter().enumerate() {
+            let n_symbols = g1.symbols.chars().count();
+            if n_symbols != g1.positions.len() {
+                conflicts.push(Conflict::KeycapGroup(
+                    KeycapGroupError::SizeMismatch(
+                        g1.symbols.clone(),
+                        n_symbols,
+                        g1.positions.len(),
+                    )
+                    .to_string(),
+                ));
+            }
+            for g2 in self.keycap_groups.iter().skip(i + 1) {
+                g1.symbols
+                    .chars()
+                    .filter(|c| g2.symbols.contains(*c))
+                    .for_each(|c| {
+                        conflicts.push(Conflict::KeycapGroup(
+                            KeycapGroupError::DuplicateSymbol(c).to_string(),
+                        ))
+                    });
+                g1.positions
+                    .iter()
+                    .filter(|p| g2.positions.contains(p))
+                    .for_each(|p| {
+                        conflicts.push(Conflict::KeycapGroup(
+                            KeycapGroupError::DuplicatePosition(*p).to_string(),
+                        ))
+                    });
+            }
+        }
+
+        for pin in self.pins.iter() {
+            for group in self.keycap_groups.iter() {
+                let has_symbol = group.symbols.contains(pin.symbol);
+                let has_position = group.positions.contains(&pin.position);
+                if has_symbol && !has_position {
+                    conflicts.push(Conflict::PinOutsideGroup(
+                        pin.symbol,
+                        pin.position,
+                        group.symbols.clone(),
+                    ));
+                } else if has_position && !has_symbol {
+                    conflicts.push(Conflict::PinInForeignGroup(
+                        pin.symbol,
+                        pin.position,
+                        group.symbols.clone(),
+                    ));
+                }
+            }
+        }
+
+        for group in self.symbol_groups() {
+            for (i, c) in group.symbols.iter().enumerate() {
+                if group.symbols[..i].contains(c) {
+                    conflicts.push(Conflict::DuplicateInSymbolGroup(group.to_string(), *c));
+                }
+            }
+        }
+
+        if let Some(hb) = &self.hand_balance {
+            let valid = |x: f64| (0.0..=1.0).contains(&x);
+            if !valid(hb.target) || !valid(hb.margin) {
+                conflicts.push(Conflict::InvalidHandBalance(hb.target, hb.margin));
+            }
+        }
+
+        conflicts
+    }
+
+    /// Check the set for conflicts.
+    pub fn validate(&self) -> Result<(), ConstraintError> {
+        let conflicts = self.conflicts();
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(ConstraintError::Conflicts(conflicts))
+        }
+    }
+
+    /// The union of both sets (identical constraints are only kept once). Fails with all
+    /// conflicts of the union.
+    pub fn merge(&self, other: &ConstraintSet) -> Result<Self, ConstraintError> {
+        let mut merged = self.clone();
+        let mut conflicts = Vec::new();
+
+        for pin in other.pins.iter() {
+            if !merged.pins.contains(pin) {
+                merged.pins.push(*pin);
+            }
+        }
+        for region in other.regions.iter() {
+            if !merged
+                .regions
+                .iter()
+                .any(|r| r.symbol == region.symbol && same_positions(r, region))
+            {
+                merged.regions.push(region.clone());
+            }
+        }
+        for group in other.keycap_groups.iter() {
+            if !merged.keycap_groups.iter().any(|g| same_group(g, group)) {
+                merged.keycap_groups.push(group.clone());
+            }
+        }
+        let merge_symbol_groups = |merged: &mut Vec<String>, others: &[String]| {
+            for group in others.iter() {
+                if !merged.contains(group) {
+                    merged.push(group.clone());
+                }
+            }
+        };
+        merge_symbol_groups(&mut merged.same_hand, &other.same_hand);
+        merge_symbol_groups(&mut merged.same_finger, &other.same_finger);
+        merge_symbol_groups(
+            &mut merged.preserve_relative_order,
+            &other.preserve_relative_order,
+        );
+        match (&self.hand_balance, &other.hand_balance) {
+            (Some(hb1), Some(hb2)) if hb1 != hb2 => {
+                conflicts.push(Conflict::HandBalance(hb1.clone(), hb2.clone()));
+            }
+            (None, Some(hb)) => merged.hand_balance = Some(hb.clone()),
+            _ => {}
+        }
+
+        conflicts.extend(merged.conflicts());
+        if conflicts.is_empty() {
+            Ok(merged)
+        } else {
+            Err(ConstraintError::Conflicts(conflicts))
+        }
+    }
+
+    /// Index of the layout string corresponding to the key at the given position.
+    fn string_index(
+        layout_str: &str,
+        layout: &Layout,
+        position: &MatrixPosition,
+    ) -> Result<usize, ConstraintError> {
+        layout
+            .layerkeys
+            .iter()
+            .find(|lk| lk.layer == 0 && !lk.is_fixed && lk.key.matrix_position == *position)
+            .and_then(|lk| layout_str.chars().position(|c| c == lk.symbol))
+            .ok_or(ConstraintError::UnknownPosition(*position))
+    }
+
+    /// Index of the symbol in the layout string.
+    fn symbol_index(layout_str: &str, symbol: char) -> Result<usize, ConstraintError> {
+        layout_str
+            .chars()
+            .position(|c| c == symbol)
+            .ok_or(ConstraintError::UnknownSymbol(symbol))
+    }
+
+    /// Move the pinned symbols to their keys by swapping them with the symbols on these keys.
+    /// `layout` needs to be generated from `layout_str`.
+    pub fn apply_pins(&self, layout_str: &str, layout: &Layout) -> Result<String, ConstraintError> {
+        let mut chars: Vec<char> = layout_str.chars().collect();
+        for pin in self.pins.iter() {
+            // the indices of the keys do not change when swapping symbols
+            let i = Self::string_index(layout_str, layout, &pin.position)?;
+            let j = chars
+                .iter()
+                .position(|c| *c == pin.symbol)
+                .ok_or(ConstraintError::UnknownSymbol(pin.symbol))?;
+            chars.swap(i, j);
+        }
+
+        Ok(chars.into_iter().collect())
+    }
+
+    /// The keycap groups in terms of the layout string. `layout` needs to be generated from
+    /// `layout_str`.
+    pub fn keycap_groups(
+        &self,
+        layout_str: &str,
+        layout: &Layout,
+    ) -> Result<Vec<KeycapGroup>, ConstraintError> {
+        self.keycap_groups
+            .iter()
+            .map(|group| {
+                group
+                    .symbols
+                    .chars()
+                    .try_for_each(|c| Self::symbol_index(layout_str, c).map(|_| ()))?;
+                Ok(KeycapGroup {
+                    symbols: group.symbols.chars().collect(),
+                    indices: group
+                        .positions
+                        .iter()
+                        .map(|p| Self::string_index(layout_str, layout, p))
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The regions in terms of the layout string. `layout` needs to be generated from
+    /// `layout_str`.
+    pub fn regions(
+        &self,
+        layout_str: &str,
+        layout: &Layout,
+    ) -> Result<Vec<(char, Vec<usize>)>, ConstraintError> {
+        self.regions
+            .iter()
+            .map(|region| {
+                Self::symbol_index(layout_str, region.symbol)?;
+                Ok((
+                    region.symbol,
+                    region
+                        .positions
+                        .iter()
+                        .map(|p| Self::string_index(layout_str, layout, p))
+                        .collect::<Result<_, _>>()?,
+                ))
+            })
+            .collect()
+    }
+
+    /// A [`LayoutPermutator`] respecting all constraints for optimizing the given layout. The
+    /// pinned symbols are moved to their keys (and fixed in addition to `fixed_characters`).
+    pub fn permutator(
+        &self,
+        layout_str: &str,
+        fixed_characters: &str,
+        layout_generator: &dyn LayoutGenerator,
+        evaluator: &Evaluator,
+    ) -> Result<LayoutPermutator> {
+        let layout = layout_generator.generate(layout_str)?;
+        let keycap_groups = self.keycap_groups(layout_str, &layout)?;
+        // the indices of the keys do not change when moving the pinned symbols
+        let regions = self.regions(layout_str, &layout)?;
+        let pinned = self.apply_pins(layout_str, &layout)?;
+        if pinned != layout_str {
+            log::info!("Moved pinned symbols: {} -> {}", layout_str, pinned);
+        }
+        let fixed = format!("{}{}", fixed_characters, self.pinned_symbols());
+
+        let placements = KeyPlacement::from_layout(layout_str, &layout)
+            .ok_or_else(|| anyhow::anyhow!("Layout '{}' contains unknown symbols", layout_str))?;
+        let mut pm = LayoutPermutator::new(&pinned, &fixed)
+            .with_keycap_groups(&keycap_groups)?
+            .with_regions(&regions)?
+            .with_symbol_groups(&self.symbol_groups(), placements)?;
+        if let Some(hand_balance) = &self.hand_balance {
+            // the keys of the layout string do not change when moving symbols
+            pm = pm.with_hand_balance(HandBalance::new(
+                hand_balance,
+                &pinned,
+                &layout_generator.generate(&pinned)?,
+                evaluator,
+            ));
+        }
+
+        Ok(pm)
+    }
+
+    /// All rules of the set that are violated by the layout. The hand balance is only checked
+    /// if an evaluator (providing the unigrams) is given. `layout` needs to be generated from
+    /// `layout_str`.
+    pub fn check(
+        &self,
+        layout_str: &str,
+        layout: &Layout,
+        evaluator: Option<&Evaluator>,
+    ) -> Result<Vec<Violation>, ConstraintError> {
+        let mut violations = Vec::new();
+        let placed = |c: char| -> Result<(MatrixPosition, usize), ConstraintError> {
+            let index = Self::symbol_index(layout_str, c)?;
+            let key = layout
+                .get_layerkey_for_symbol(&c)
+                .ok_or(ConstraintError::UnknownSymbol(c))?;
+            Ok((key.key.matrix_position, index))
+        };
+
+        for pin in self.pins.iter() {
+            let (position, index) = placed(pin.symbol)?;
+            if position != pin.position {
+                violations.push(Violation::Pin {
+                    symbol: pin.symbol,
+                    pinned: pin.position,
+                    placed: position,
+                    index,
+                });
+            }
+        }
+
+        for region in self.regions.iter() {
+            let (position, index) = placed(region.symbol)?;
+            if !region.positions.contains(&position) {
+                violations.push(Violation::Region {
+                    symbol: region.symbol,
+                    placed: position,
+                    index,
+                });
+            }
+        }
+
+        for group in self.keycap_groups.iter() {
+            for c in group.symbols.chars() {
+                let (position, index) = placed(c)?;
+                if !group.positions.contains(&position) {
+                    violations.push(Violation::KeycapGroup {
+                        group: group.symbols.clone(),
+                        symbol: c,
+                        placed: position,
+                        index,
+                    });
+                }
+            }
+        }
+
+        for group in self.symbol_groups() {
+            let keys: Vec<KeyPlacement> = group
+                .symbols
+                .iter()
+                .map(|c| {
+                    layout
+                        .get_layerkey_for_symbol(c)
+                        .map(|k| (&k.key).into())
+                        .ok_or(ConstraintError::UnknownSymbol(*c))
+                })
+                .collect::<Result<_, _>>()?;
+            if !group.is_satisfied_by(&keys) {
+                violations.push(Violation::SymbolGroup {
+                    kind: group.kind,
+                    symbols: group.to_string(),
+                });
+            }
+        }
+
+        if let (Some(params), Some(evaluator)) = (&self.hand_balance, evaluator) {
+            let hand_balance = HandBalance::new(params, layout_str, layout, evaluator);
+            if let Some(left_fraction) = hand_balance.left_fraction(layout_str) {
+                if (left_fraction - params.target).abs() > params.margin {
+                    violations.push(Violation::HandBalance {
+                        left_fraction,
+                        target: params.target,
+                        margin: params.margin,
+                    });
+                }
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand_balance::HandBalanceMode;
+
+    use rand::seq::SliceRandom;
+
+    use keyboard_layout::{
+        config::LayoutConfig, key::Hand, keyboard::Keyboard,
+        neo_layout_generator::NeoLayoutGenerator,
+    };
+    use layout_evaluation::{
+        config::EvaluationParameters,
+        ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+        ngrams::{Bigrams, Trigrams, Unigrams},
+    };
+    use std::sync::Arc;
+
+    const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+
+    fn generator() -> NeoLayoutGenerator {
+        let layout_config = LayoutConfig::from_yaml("../../config/keyboard/standard.yml").unwrap();
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+    }
+
+    fn evaluator() -> Evaluator {
+        const TEXT: &str = "Zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich.";
+        let eval_params =
+            EvaluationParameters::from_yaml("../../config/evaluation/default.yml").unwrap();
+        let mapper = OnDemandNgramMapper::with_ngrams(
+            Unigrams::from_text(TEXT).unwrap(),
+            Bigrams::from_text(TEXT).unwrap(),
+            Trigrams::from_text(TEXT).unwrap(),
+            eval_params.ngram_mapper,
+        );
+        Evaluator::default(Box::new(mapper))
+    }
+
+    /// Matrix position of the key of a symbol in the reference layout.
+    fn position(c: char) -> MatrixPosition {
+        let layout = generator().generate(LAYOUT).unwrap();
+        layout
+            .get_layerkey_for_symbol(&c)
+            .unwrap()
+            .key
+            .matrix_position
+    }
+
+    fn pin(symbol: char, at: char) -> Pin {
+        Pin {
+            symbol,
+            position: position(at),
+        }
+    }
+
+    fn group(symbols: &str, at: &str) -> KeycapGroupYAML {
+        KeycapGroupYAML {
+            symbols: symbols.to_string(),
+            positions: at.chars().map(position).collect(),
+        }
+    }
+
+    fn region(symbol: char, at: &str) -> Region {
+        Region {
+            symbol,
+            positions: at.chars().map(position).collect(),
+        }
+    }
+
+    fn hand_balance(margin: f64) -> HandBalanceParameters {
+        HandBalanceParameters {
+            target: 0.5,
+            margin,
+            mode: HandBalanceMode::Repair,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let constraints = ConstraintSet::from_yaml_str(
+            "
+pins:
+  - symbol: e
+    position: [3, 2]
+regions:
+  - symbol: z
+    positions: [[1, 3], [2, 3], [3, 3]]
+keycap_groups:
+  - symbols: ',.'
+    positions: [[9, 3], [10, 3]]
+same_hand: ['([{']
+same_finger: [qj]
+preserve_relative_order: ['0123']
+hand_balance:
+  target: 0.45
+  margin: 0.05
+  mode: reject
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            constraints.pins,
+            vec![Pin {
+                symbol: 'e',
+                position: MatrixPosition(3, 2)
+            }]
+        );
+        assert_eq!(
+            constraints.regions,
+            vec![Region {
+                symbol: 'z',
+                positions: vec![
+                    MatrixPosition(1, 3),
+                    MatrixPosition(2, 3),
+                    MatrixPosition(3, 3)
+                ],
+            }]
+        );
+        assert_eq!(constraints.keycap_groups[0].symbols, ",.");
+        assert_eq!(
+            constraints.hand_balance,
+            Some(HandBalanceParameters {
+                target: 0.45,
+                margin: 0.05,
+                mode: HandBalanceMode::Reject,
+            })
+        );
+        assert_eq!(constraints.pinned_symbols(), "e");
+        assert_eq!(
+            constraints.symbol_groups(),
+            vec![
+                SymbolGroup {
+                    kind: SymbolGroupKind::SameHand,
+                    symbols: vec!['(', '[', '{'],
+                },
+                SymbolGroup {
+                    kind: SymbolGroupKind::SameFinger,
+                    symbols: vec!['q', 'j'],
+                },
+                SymbolGroup {
+                    kind: SymbolGroupKind::RelativeOrder,
+                    symbols: vec!['0', '1', '2', '3'],
+                },
+            ]
+        );
+
+        // round trip
+        let yaml = constraints.to_yaml().unwrap();
+        assert_eq!(ConstraintSet::from_yaml_str(&yaml).unwrap(), constraints);
+
+        // all entries are optional
+        assert!(ConstraintSet::from_yaml_str("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_unknown_and_invalid_entries() {
+        // unsupported constraint types must not be ignored silently
+        assert!(ConstraintSet::from_yaml_str("masks: []").is_err());
+        assert!(ConstraintSet::from_yaml_str("pins: [{symbol: e, key: [3, 2]}]").is_err());
+        assert!(ConstraintSet::from_yaml_str("pins: [{symbol: ea, position: [3, 2]}]").is_err());
+        assert!(ConstraintSet::from_yaml_str(
+            "hand_balance: {target: 0.5, margin: 0.1, mode: ignore}"
+        )
+        .is_err());
+        // parsed, but conflicting
+        assert!(ConstraintSet::from_yaml_str(
+            "hand_balance: {target: 1.5, margin: 0.1, mode: repair}"
+        )
+        .is_err());
+        assert!(ConstraintSet::from_yaml_str(
+            "pins: [{symbol: e, position: [3, 2]}, {symbol: e, position: [4, 2]}]"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn conflicts_within_a_set() {
+        let valid = ConstraintSet {
+            pins: vec![pin('e', 'e'), pin(',', '.')],
+            regions: Vec::new(),
+            keycap_groups: vec![group(",.", ",."), group("ü", "ü")],
+            hand_balance: Some(hand_balance(0.1)),
+            ..Default::default()
+        };
+        assert_eq!(valid.conflicts(), Vec::new());
+
+        let conflicts = |set: ConstraintSet| set.conflicts();
+        assert_eq!(
+            conflicts(ConstraintSet {
+                pins: vec![pin('e', 'e'), pin('e', 'a')],
+                ..Default::default()
+            }),
+            vec![Conflict::PinnedTwice('e', position('e'), position('a'))]
+        );
+        assert_eq!(
+            conflicts(ConstraintSet {
+                pins: vec![pin('e', 'e'), pin('a', 'e')],
+                ..Default::default()
+            }),
+            vec![Conflict::KeyPinnedTwice(position('e'), 'e', 'a')]
+        );
+        assert_eq!(
+            conflicts(ConstraintSet {
+                pins: vec![pin(',', 'x')],
+                keycap_groups: vec![group(",.", ",.")],
+                ..Default::default()
+            }),
+            vec![Conflict::PinOutsideGroup(
+                ',',
+                position('x'),
+                ",.".to_string()
+            )]
+        );
+        assert_eq!(
+            conflicts(ConstraintSet {
+                pins: vec![pin('x', ',')],
+                keycap_groups: vec![group(",.", ",.")],
+                ..Default::default()
+            }),
+            vec![Conflict::PinInForeignGroup(
+                'x',
+                position(','),
+                ",.".to_string()
+            )]
+        );
+        assert_eq!(
+            conflicts(ConstraintSet {
+                pins: vec![pin('z', 'x')],
+                regions: vec![
+                    region('z', "äöü"),
+                    region('z', "üöä"),
+                    region('p', "pb"),
+                    region('p', "pz"),
+                    region(',', "xv"),
+                    region('j', ""),
+                ],
+                keycap_groups: vec![group(",.", ",.")],
+                ..Default::default()
+            }),
+            vec![
+                Conflict::PinOutsideRegion('z', position('x')),
+                Conflict::PinOutsideRegion('z', position('x')),
+                Conflict::RegionTwice('p'),
+                Conflict::RegionOutsideGroup(',', ",.".to_string()),
+                Conflict::EmptyRegion('j'),
+            ]
+        );
+        assert_eq!(
+            conflicts(ConstraintSet {
+                same_hand: vec!["([{".to_string(), "([(".to_string()],
+                ..Default::default()
+            }),
+            vec![Conflict::DuplicateInSymbolGroup("([(".to_string(), '(')]
+        );
+        // all conflicts are reported
+        assert_eq!(
+            conflicts(ConstraintSet {
+                keycap_groups: vec![group(",.", ",."), group(".", "."), group("ab", "a")],
+                hand_balance: Some(hand_balance(-0.1)),
+                ..Default::default()
+            })
+            .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let set1 = ConstraintSet {
+            pins: vec![pin('e', 'e')],
+            regions: vec![region('z', "xvl")],
+            keycap_groups: vec![group(",.", ",.")],
+            hand_balance: None,
+            ..Default::default()
+        };
+        let set2 = ConstraintSet {
+            pins: vec![pin('e', 'e'), pin('a', 'a')],
+            regions: vec![region('z', "lvx")],
+            // the same group, listed in another order
+            keycap_groups: vec![group(".,", ".,"), group("ü", "ü")],
+            hand_balance: Some(hand_balance(0.1)),
+            ..Default::default()
+        };
+
+        let merged = set1.merge(&set2).unwrap();
+        assert_eq!(merged.pins, vec![pin('e', 'e'), pin('a', 'a')]);
+        assert_eq!(merged.regions, vec![region('z', "xvl")]);
+        assert_eq!(merged.keycap_groups.len(), 2);
+        assert_eq!(merged.hand_balance, Some(hand_balance(0.1)));
+        assert_eq!(merged.merge(&ConstraintSet::default()).unwrap(), merged);
+        assert_eq!(ConstraintSet::default().merge(&merged).unwrap(), merged);
+
+        // conflicts between the sets are reported
+        let conflicting = ConstraintSet {
+            pins: vec![pin('e', 'a')],
+            regions: vec![region('z', "äö")],
+            keycap_groups: vec![group(",", "j")],
+            hand_balance: Some(hand_balance(0.2)),
+            ..Default::default()
+        };
+        match merged.merge(&conflicting) {
+            Err(ConstraintError::Conflicts(conflicts)) => {
+                assert!(conflicts
+                    .contains(&Conflict::HandBalance(hand_balance(0.1), hand_balance(0.2))));
+                assert!(conflicts.contains(&Conflict::PinnedTwice(
+                    'e',
+                    position('e'),
+                    position('a')
+                )));
+                assert!(conflicts.contains(&Conflict::KeyPinnedTwice(position('a'), 'a', 'e')));
+                assert!(conflicts.contains(&Conflict::RegionTwice('z')));
+                assert!(conflicts.contains(&Conflict::KeycapGroup(
+                    KeycapGroupError::DuplicateSymbol(',').to_string()
+                )));
+            }
+            other => panic!("Expected conflicts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check() {
+        let generator = generator();
+        let evaluator = evaluator();
+        let constraints = ConstraintSet {
+            pins: vec![pin('e', 'e'), pin('x', 'q')],
+            regions: Vec::new(),
+            keycap_groups: vec![group(",.", ",."), group("üö", "üö")],
+            hand_balance: Some(hand_balance(0.0)),
+            ..Default::default()
+        };
+
+        let layout = generator.generate(LAYOUT).unwrap();
+        let violations = constraints.check(LAYOUT, &layout, None).unwrap();
+        assert_eq!(
+            violations,
+            vec![Violation::Pin {
+                symbol: 'x',
+                pinned: position('q'),
+                placed: position('x'),
+                index: 0,
+            }]
+        );
+        assert!(violations[0]
+            .to_string()
+            .contains("(index 0 of the layout string)"));
+
+        // swap ',' with 'j' (outside of its group) and 'ü' with 'ö' (within its group)
+        let other = "xvlcwkhgfqyßuiaeosnrtdöüäpzbmj.,";
+        let layout = generator.generate(other).unwrap();
+        let violations = constraints.check(other, &layout, Some(&evaluator)).unwrap();
+        assert!(violations.contains(&Violation::KeycapGroup {
+            group: ",.".to_string(),
+            symbol: ',',
+            placed: position('j'),
+            index: 31,
+        }));
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, Violation::HandBalance { .. })));
+        assert_eq!(violations.len(), 3);
+
+        // "q" and "j" (now on the former key of ",") are typed with different fingers of the right hand
+        let groups = ConstraintSet {
+            same_hand: vec!["qj".to_string()],
+            same_finger: vec!["qj".to_string()],
+            preserve_relative_order: vec!["qj".to_string(), "jq".to_string()],
+            ..Default::default()
+        };
+        let violations = groups.check(other, &layout, None).unwrap();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::SymbolGroup {
+                    kind: SymbolGroupKind::SameFinger,
+                    symbols: "qj".to_string(),
+                },
+                Violation::SymbolGroup {
+                    kind: SymbolGroupKind::RelativeOrder,
+                    symbols: "jq".to_string(),
+                }
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "Symbols 'qj' are not placed on the same finger"
+        );
+
+        // unknown symbols are errors, not violations
+        let unknown = ConstraintSet {
+            pins: vec![Pin {
+                symbol: '☃',
+                position: position('e'),
+            }],
+            ..Default::default()
+        };
+        assert!(unknown.check(LAYOUT, &layout, None).is_err());
+    }
+
+    #[test]
+    fn permutator_respects_pins() {
+        let generator = generator();
+        let evaluator = evaluator();
+        let constraints = ConstraintSet {
+            pins: vec![pin('x', 'q'), pin('q', 'e')],
+            regions: Vec::new(),
+            keycap_groups: vec![group(",.", ",.")],
+            hand_balance: None,
+            ..Default::default()
+        };
+
+        let pm = constraints
+            .permutator(LAYOUT, "", &generator, &evaluator)
+            .unwrap();
+        let layout_str = pm.generate_string(&pm.get_permutable_indices());
+        let layout = generator.generate(&layout_str).unwrap();
+        assert_eq!(
+            constraints.check(&layout_str, &layout, None).unwrap(),
+            vec![]
+        );
+
+        for _ in 0..20 {
+            let candidate = pm.generate_string(&pm.generate_random());
+            let layout = generator.generate(&candidate).unwrap();
+            assert_eq!(
+                constraints.check(&candidate, &layout, None).unwrap(),
+                vec![]
+            );
+        }
+    }
+
+    #[test]
+    fn permutator_respects_regions() {
+        let generator = generator();
+        let evaluator = evaluator();
+        let layout = generator.generate(LAYOUT).unwrap();
+        let left_half: Vec<MatrixPosition> = layout
+            .layerkeys
+            .iter()
+            .filter(|lk| lk.layer == 0 && !lk.is_fixed && lk.key.hand == Hand::Left)
+            .map(|lk| lk.key.matrix_position)
+            .collect();
+        let bottom_row: Vec<MatrixPosition> = left_half
+            .iter()
+            .filter(|p| p.1 == position('.').1)
+            .cloned()
+            .collect();
+
+        // "k", "." (on the right hand), and the pinned "x" start outside of their regions
+        let constraints = ConstraintSet {
+            pins: vec![pin('x', 'q')],
+            regions: vec![
+                Region {
+                    symbol: 'k',
+                    positions: left_half,
+                },
+                Region {
+                    symbol: '.',
+                    positions: bottom_row.clone(),
+                },
+                Region {
+                    symbol: 'x',
+                    positions: vec![position('q'), position('j')],
+                },
+            ],
+            keycap_groups: vec![group("üö", "üö")],
+            hand_balance: None,
+            ..Default::default()
+        };
+        assert_eq!(constraints.check(LAYOUT, &layout, None).unwrap().len(), 4);
+
+        let pm = constraints
+            .permutator(LAYOUT, "", &generator, &evaluator)
+            .unwrap();
+        let check = |perm: &[usize]| {
+            let layout_str = pm.generate_string(perm);
+            let layout = generator.generate(&layout_str).unwrap();
+            assert_eq!(
+                constraints.check(&layout_str, &layout, None).unwrap(),
+                vec![]
+            );
+        };
+
+        // simulated annealing: consecutive swaps
+        let mut perm = pm.get_permutable_indices();
+        for _ in 0..1000 {
+            perm = pm.perform_n_swaps(&perm, 2);
+            check(&perm);
+        }
+        // genetic algorithm: random genomes and unaware mutations
+        for _ in 0..200 {
+            let mut genome = pm.generate_random();
+            check(&genome);
+            genome.shuffle(&mut rand::thread_rng());
+            check(&genome);
+        }
+
+        // more symbols than keys in a region
+        let crowded = ConstraintSet {
+            regions: "ykhgfpzbm"
+                .chars()
+                .map(|symbol| Region {
+                    symbol,
+                    positions: bottom_row.clone(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert!(crowded
+            .permutator(LAYOUT, "", &generator, &evaluator)
+            .is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! The `hand_balance` module provides a hard constraint on the split of the unigram load
+//! between both hands. Candidate layouts whose split deviates from the target by more than a
+//! margin are either repaired (see [`crate::LayoutPermutator::with_hand_balance`]) or rejected
+//! before they get evaluated.
+//!
+//! As for the "Hand Disbalance" metric, thumb keys are not counted. Presses of modifiers are not
+//! counted either: they are fixed keys and only the base layer keys of the symbols can be moved.
+
+use keyboard_layout::{key::Hand, layout::Layout};
+use layout_evaluation::evaluation::Evaluator;
+
+use ahash::AHashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// What to do with candidates whose hand split is outside of the margin.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandBalanceMode {
+    /// Swap the lightest symbols across hands until the split is within the margin (candidates
+    /// that can not be repaired this way are rejected)
+    Repair,
+    /// Reject the candidates without evaluating them
+    Reject,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HandBalanceParameters {
+    /// Target fraction of the load on the left hand
+    pub target: f64,
+    /// Maximum deviation of the left hand's fraction from the target
+    pub margin: f64,
+    /// Repair or reject candidates outside of the margin
+    pub mode: HandBalanceMode,
+}
+
+/// How a candidate fared with respect to the constraint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Balanced,
+    Repaired,
+    Rejected,
+}
+
+#[derive(Default, Debug)]
+struct Counters {
+    checked: AtomicUsize,
+    repaired: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+/// The hand balance constraint for the layouts of a [`crate::LayoutPermutator`].
+///
+/// The counters of repaired and rejected candidates are shared between all clones.
+#[derive(Clone, Debug)]
+pub struct HandBalance {
+    params: HandBalanceParameters,
+    /// Hand of the key at each index of the layout string (`None` for thumb keys)
+    hands: Vec<Option<Hand>>,
+    /// Unigram weight of the base layer key of each symbol (including its higher layers)
+    weights: AHashMap<char, f64>,
+    counters: Arc<Counters>,
+}
+
+impl HandBalance {
+    /// Determine the hands of the keys and the weights of the symbols from the layout
+    /// corresponding to `layout_str` and the unigrams of the evaluator.
+    pub fn new(
+        params: &HandBalanceParameters,
+        layout_str: &str,
+        layout: &Layout,
+        evaluator: &Evaluator,
+    ) -> Self {
+        let hands = layout_str
+            .chars()
+            .map(|c| {
+                layout
+                    .get_layerkey_for_symbol(&c)
+                    .filter(|k| !layout.geometry().is_thumb(k.key_index))
+                    .map(|k| layout.geometry().hand(k.key_index))
+            })
+            .collect();
+
+        let mut weights = AHashMap::default();
+        evaluator
+            .map_unigrams(layout)
+            .grams
+            .iter()
+            .filter(|(k, _)| k.layer == 0)
+            .for_each(|(k, w)| *weights.entry(k.symbol).or_insert(0.0) += *w);
+
+        Self {
+            params: params.clone(),
+            hands,
+            weights,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    pub fn params(&self) -> &HandBalanceParameters {
+        &self.params
+    }
+
+    /// Hand of the key at the given index of the layout string (`None` for thumb keys).
+    pub(crate) fn hand(&self, index: usize) -> Option<Hand> {
+        self.hands.get(index).cloned().flatten()
+    }
+
+    /// Unigram weight of the given symbol of the layout string.
+    pub(crate) fn weight(&self, c: char) -> f64 {
+        self.weights.get(&c).cloned().unwrap_or(0.0)
+    }
+
+    /// Deviation of the left hand's fraction of the given loads from the target (zero without
+    /// any load).
+    pub(crate) fn deviation(&self, left: f64, right: f64) -> f64 {
+        let total = left + right;
+        if total <= 0.0 {
+            return 0.0;
+        }
+
+        left / total - self.params.target
+    }
+
+    /// Fraction of the load on the left hand for a layout string (with the same keys as the one
+    /// the constraint was created with). `None` if there is no load at all.
+    pub fn left_fraction(&self, layout_str: &str) -> Option<f64> {
+        let mut loads = [0.0, 0.0];
+        layout_str.chars().enumerate().for_each(|(i, c)| {
+            if let Some(hand) = self.hand(i) {
+                loads[hand as usize] += self.weight(c);
+            }
+        });
+
+        let total = loads[0] + loads[1];
+        (total > 0.0).then(|| loads[0] / total)
+    }
+
+    pub(crate) fn record(&self, outcome: Outcome) {
+        self.counters.checked.fetch_add(1, Ordering::Relaxed);
+        match outcome {
+            Outcome::Balanced => {}
+            Outcome::Repaired => {
+                self.counters.repaired.fetch_add(1, Ordering::Relaxed);
+            }
+            Outcome::Rejected => {
+                self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Number of checked, repaired, and rejected candidates.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        (
+            self.counters.checked.load(Ordering::Relaxed),
+            self.counters.repaired.load(Ordering::Relaxed),
+            self.counters.rejected.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl fmt::Display for HandBalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (checked, repaired, rejected) = self.counts();
+        write!(
+            f,
+            "Hand balance (left hand {:.1}% ± {:.1}%): {} of {} candidates repaired, {} rejected",
+            100.0 * self.params.target,
+            100.0 * self.params.margin,
+            repaired,
+            checked,
+            rejected,
+        )
+    }
+}
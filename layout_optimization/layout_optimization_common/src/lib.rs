@@ -1,4 +1,126 @@
-use rand::{seq::SliceRandom, thread_rng};
+pub mod blacklist;
+pub mod constraints;
+pub mod hand_balance;
+pub mod symbol_groups;
+
+use hand_balance::{HandBalance, HandBalanceMode, Outcome};
+use symbol_groups::{KeyPlacement, SymbolGroup, SymbolGroupKind};
+
+use keyboard_layout::{key::Hand, keycap_group::KeycapGroup};
+
+use anyhow::{bail, Result};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use std::borrow::Cow;
+
+/// A keycap group in terms of a permutation: the elements of the permutation (belonging to the
+/// group's symbols) may only take the group's indices of the layout string.
+#[derive(Clone, Debug)]
+struct PermutationGroup {
+    elements: Vec<usize>,
+    indices: Vec<usize>,
+}
+
+/// A region in terms of a permutation: the element (belonging to the region's symbol) may only
+/// take the region's indices of the layout string.
+#[derive(Clone, Debug)]
+struct PermutationRegion {
+    element: usize,
+    indices: Vec<usize>,
+}
+
+/// A symbol of a symbol group in terms of a permutation.
+#[derive(Clone, Copy, Debug)]
+enum Member {
+    /// The element of the permutation belonging to the symbol
+    Permutable(usize),
+    /// The index of the fixed symbol in the layout string
+    Fixed(usize),
+}
+
+impl Member {
+    /// The index of the symbol in the layout string.
+    fn index(&self, permutation: &[usize]) -> usize {
+        match self {
+            Member::Permutable(j) => permutation[*j],
+            Member::Fixed(i) => *i,
+        }
+    }
+}
+
+/// A symbol group in terms of a permutation.
+#[derive(Clone, Debug)]
+struct PermutationSymbolGroup {
+    group: SymbolGroup,
+    members: Vec<Member>,
+}
+
+impl PermutationSymbolGroup {
+    fn contains(&self, element: usize) -> bool {
+        self.members
+            .iter()
+            .any(|m| matches!(m, Member::Permutable(j) if *j == element))
+    }
+
+    /// The elements of the permutation belonging to the group's symbols (in the group's order).
+    fn elements(&self) -> impl Iterator<Item = usize> + '_ {
+        self.members.iter().filter_map(|m| match m {
+            Member::Permutable(j) => Some(*j),
+            Member::Fixed(_) => None,
+        })
+    }
+}
+
+/// Maximal number of random permutations to draw until one satisfies the symbol groups.
+const MAX_RANDOM_DRAWS: usize = 100;
+
+// The random draws of seeded permutations must not change with the version of `rand`, whose
+// `shuffle` and `choose` are not value-stable. Therefore, they only rely on the raw output of the
+// random number generator.
+
+/// A random index below `n` (`n` > 0).
+fn random_index<R: Rng + ?Sized>(n: usize, rng: &mut R) -> usize {
+    ((rng.next_u64() as u128 * n as u128) >> 64) as usize
+}
+
+/// Shuffle the slice in place (Fisher-Yates).
+fn shuffle<T, R: Rng + ?Sized>(values: &mut [T], rng: &mut R) {
+    for i in (1..values.len()).rev() {
+        values.swap(i, random_index(i + 1, rng));
+    }
+}
+
+/// A random element of the slice (`None` if it is empty).
+fn choose<'a, T, R: Rng + ?Sized>(values: &'a [T], rng: &mut R) -> Option<&'a T> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(&values[random_index(values.len(), rng)])
+}
+
+/// Try to assign the region with the given number (and all regions already assigned) to the
+/// indices of a swap class, reassigning other regions if necessary (augmenting path).
+fn assign_region(
+    region: usize,
+    regions: &[&PermutationRegion],
+    class_indices: &[usize],
+    owners: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for (k, i) in class_indices.iter().enumerate() {
+        if visited[k] || !regions[region].indices.contains(i) {
+            continue;
+        }
+        visited[k] = true;
+        if owners[k].map_or(true, |owner| {
+            assign_region(owner, regions, class_indices, owners, visited)
+        }) {
+            owners[k] = Some(region);
+            return true;
+        }
+    }
+
+    false
+}
 
 #[derive(Clone, Debug)]
 pub struct LayoutPermutator {
@@ -6,6 +128,12 @@ pub struct LayoutPermutator {
     perm_indices: Vec<usize>,
     fixed_keys: Vec<char>,
     fixed_indices: Vec<usize>,
+    groups: Vec<PermutationGroup>,
+    regions: Vec<PermutationRegion>,
+    symbol_groups: Vec<PermutationSymbolGroup>,
+    /// Placement of the key at each index of the layout string (only with symbol groups)
+    placements: Vec<KeyPlacement>,
+    hand_balance: Option<HandBalance>,
 }
 
 impl LayoutPermutator {
@@ -29,9 +157,586 @@ impl LayoutPermutator {
             perm_indices,
             fixed_keys,
             fixed_indices,
+            groups: Vec::new(),
+            regions: Vec::new(),
+            symbol_groups: Vec::new(),
+            placements: Vec::new(),
+            hand_balance: None,
         }
     }
 
+    /// Restrict the permutations to keep the symbols of each keycap group on the group's
+    /// indices of the layout string. Group symbols that are fixed (and already placed on one
+    /// of the group's indices) are ignored.
+    pub fn with_keycap_groups(mut self, keycap_groups: &[KeycapGroup]) -> Result<Self> {
+        for group in keycap_groups {
+            let mut elements = Vec::new();
+            let mut fixed_indices = Vec::new();
+            for c in group.symbols.iter() {
+                if let Some(j) = self.perm_keys.iter().position(|pc| pc == c) {
+                    elements.push(j);
+                } else if let Some(k) = self.fixed_keys.iter().position(|fc| fc == c) {
+                    if !group.indices.contains(&self.fixed_indices[k]) {
+                        bail!(
+                            "Symbol '{}' of keycap group '{}' is fixed outside of the group's keys",
+                            c,
+                            group
+                        );
+                    }
+                    fixed_indices.push(self.fixed_indices[k]);
+                } else {
+                    bail!(
+                        "Symbol '{}' of keycap group '{}' is not part of the layout",
+                        c,
+                        group
+                    );
+                }
+            }
+
+            let mut indices = Vec::new();
+            for i in group.indices.iter() {
+                if self.perm_indices.contains(i) {
+                    indices.push(*i);
+                } else if !fixed_indices.contains(i) {
+                    bail!(
+                        "Keycap group '{}' contains a key that is occupied by a fixed symbol",
+                        group
+                    );
+                }
+            }
+
+            if elements.len() != indices.len() {
+                bail!(
+                    "Keycap group '{}' has {} permutable symbols, but {} permutable keys",
+                    group,
+                    elements.len(),
+                    indices.len()
+                );
+            }
+
+            if !elements.is_empty() {
+                self.groups.push(PermutationGroup { elements, indices });
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Restrict the permutations to keep each given symbol on one of the given indices of the
+    /// layout string (its region). Other symbols may be placed on these indices as well. Needs to
+    /// be called after [`LayoutPermutator::with_keycap_groups`]. Fails if a symbol is fixed
+    /// outside of its region or if the regions can not be satisfied at the same time.
+    pub fn with_regions(mut self, regions: &[(char, Vec<usize>)]) -> Result<Self> {
+        for (c, region_indices) in regions {
+            if let Some(j) = self.perm_keys.iter().position(|pc| pc == c) {
+                // the region is restricted to the keys the symbol may be swapped to
+                let class_indices: Vec<usize> =
+                    match self.groups.iter().find(|g| g.elements.contains(&j)) {
+                        Some(group) => group.indices.clone(),
+                        None => self
+                            .perm_indices
+                            .iter()
+                            .filter(|i| !self.groups.iter().any(|g| g.indices.contains(i)))
+                            .cloned()
+                            .collect(),
+                    };
+                let indices: Vec<usize> = region_indices
+                    .iter()
+                    .filter(|i| class_indices.contains(i))
+                    .cloned()
+                    .collect();
+                if indices.is_empty() {
+                    bail!(
+                        "Symbol '{}' has no key within its region that it may be placed on",
+                        c
+                    );
+                }
+                self.regions.push(PermutationRegion {
+                    element: j,
+                    indices,
+                });
+            } else if let Some(k) = self.fixed_keys.iter().position(|fc| fc == c) {
+                if !region_indices.contains(&self.fixed_indices[k]) {
+                    bail!("Symbol '{}' is fixed outside of its region", c);
+                }
+            } else {
+                bail!("Symbol '{}' of a region is not part of the layout", c);
+            }
+        }
+
+        let mut indices = self.repair_groups(&self.perm_indices);
+        if !self.assign_regions(&mut indices) {
+            bail!("The regions can not be satisfied at the same time");
+        }
+
+        Ok(self)
+    }
+
+    /// Restrict the permutations to keep the symbols of each group on the same hand, on the same
+    /// finger, or in the given order. `placements` holds the key at each index of the layout
+    /// string. Needs to be called after [`LayoutPermutator::with_regions`]. Permutations
+    /// violating a group are repaired by swapping symbols of the group with symbols outside of
+    /// all groups (or by reordering them). Fails if the initial layout can not be repaired.
+    pub fn with_symbol_groups(
+        mut self,
+        groups: &[SymbolGroup],
+        placements: Vec<KeyPlacement>,
+    ) -> Result<Self> {
+        if groups.is_empty() {
+            return Ok(self);
+        }
+        if placements.len() != self.perm_keys.len() + self.fixed_keys.len() {
+            bail!(
+                "Got {} key placements for a layout string of length {}",
+                placements.len(),
+                self.perm_keys.len() + self.fixed_keys.len()
+            );
+        }
+
+        for group in groups {
+            let members = group
+                .symbols
+                .iter()
+                .map(|c| {
+                    if let Some(j) = self.perm_keys.iter().position(|pc| pc == c) {
+                        Ok(Member::Permutable(j))
+                    } else if let Some(k) = self.fixed_keys.iter().position(|fc| fc == c) {
+                        Ok(Member::Fixed(self.fixed_indices[k]))
+                    } else {
+                        bail!(
+                            "Symbol '{}' of symbol group '{}' is not part of the layout",
+                            c,
+                            group
+                        )
+                    }
+                })
+                .collect::<Result<_>>()?;
+            self.symbol_groups.push(PermutationSymbolGroup {
+                group: group.clone(),
+                members,
+            });
+        }
+        self.placements = placements;
+
+        if self.violates_symbol_groups(&self.perm_indices) {
+            bail!("The symbol groups can not be satisfied together with the other constraints");
+        }
+
+        Ok(self)
+    }
+
+    /// If the permutation satisfies the symbol group.
+    fn satisfies_symbol_group(
+        &self,
+        group: &PermutationSymbolGroup,
+        permutation: &[usize],
+    ) -> bool {
+        let keys: Vec<KeyPlacement> = group
+            .members
+            .iter()
+            .map(|m| self.placements[m.index(permutation)])
+            .collect();
+
+        group.group.is_satisfied_by(&keys)
+    }
+
+    /// If the permutation violates a symbol group (or a region) even after repairing it. Such
+    /// permutations are infeasible.
+    pub fn violates_symbol_groups(&self, permutation: &[usize]) -> bool {
+        if self.symbol_groups.is_empty() {
+            return false;
+        }
+
+        let repaired = self.repair(permutation);
+        self.regions
+            .iter()
+            .any(|r| !r.indices.contains(&repaired[r.element]))
+            || self
+                .symbol_groups
+                .iter()
+                .any(|g| !self.satisfies_symbol_group(g, &repaired))
+    }
+
+    /// Move the symbols of each group to the same hand or finger (the one of a fixed symbol of
+    /// the group or the one holding most of its symbols) by swapping them with symbols outside
+    /// of all groups, and reorder the symbols of order groups among their keys. The permutation
+    /// may still violate a group afterwards.
+    fn repair_symbol_groups(&self, permutation: &mut [usize]) {
+        if self.symbol_groups.is_empty() {
+            return;
+        }
+
+        let classes = self.swap_classes();
+        let in_group = |j: usize| self.symbol_groups.iter().any(|g| g.contains(j));
+
+        for group in self.symbol_groups.iter() {
+            if self.satisfies_symbol_group(group, permutation) {
+                continue;
+            }
+
+            if group.group.kind == SymbolGroupKind::RelativeOrder {
+                // the symbols of each class take their keys in the group's order
+                for class in classes.iter() {
+                    let elements: Vec<usize> =
+                        group.elements().filter(|j| class.contains(j)).collect();
+                    let mut indices: Vec<usize> =
+                        elements.iter().map(|j| permutation[*j]).collect();
+                    indices.sort_by_key(|i| self.placements[*i].reading_order());
+                    for (j, i) in elements.into_iter().zip(indices) {
+                        permutation[j] = i;
+                    }
+                }
+                continue;
+            }
+
+            let member_indices: Vec<usize> =
+                group.members.iter().map(|m| m.index(permutation)).collect();
+            let same_keys = |i1: usize, i2: usize| {
+                group
+                    .group
+                    .same_keys(&self.placements[i1], &self.placements[i2])
+            };
+            let target = group
+                .members
+                .iter()
+                .find_map(|m| match m {
+                    Member::Fixed(i) => Some(*i),
+                    Member::Permutable(_) => None,
+                })
+                .unwrap_or_else(|| {
+                    *member_indices
+                        .iter()
+                        .max_by_key(|i| {
+                            member_indices
+                                .iter()
+                                .filter(|o| same_keys(**i, **o))
+                                .count()
+                        })
+                        .unwrap()
+                });
+
+            for j in group.elements() {
+                if same_keys(permutation[j], target) {
+                    continue;
+                }
+                let partner = classes
+                    .iter()
+                    .find(|class| class.contains(&j))
+                    .and_then(|class| {
+                        class
+                            .iter()
+                            .filter(|k| !in_group(**k) && same_keys(permutation[**k], target))
+                            .filter(|k| {
+                                self.within_region(j, permutation[**k])
+                                    && self.within_region(**k, permutation[j])
+                            })
+                            .min_by_key(|k| permutation[**k])
+                    });
+                if let Some(k) = partner {
+                    permutation.swap(j, *k);
+                }
+            }
+        }
+    }
+
+    /// If the element of the permutation may be placed on the index of the layout string (wrt.
+    /// its region).
+    fn within_region(&self, element: usize, index: usize) -> bool {
+        self.regions
+            .iter()
+            .find(|r| r.element == element)
+            .map_or(true, |r| r.indices.contains(&index))
+    }
+
+    /// If both elements of the permutation stay within their regions (and their symbol groups
+    /// remain satisfied) when swapping them.
+    fn swappable(&self, permutation: &[usize], a: usize, b: usize) -> bool {
+        if !self.within_region(a, permutation[b]) || !self.within_region(b, permutation[a]) {
+            return false;
+        }
+
+        let mut groups = self
+            .symbol_groups
+            .iter()
+            .filter(|g| g.contains(a) || g.contains(b))
+            .peekable();
+        if groups.peek().is_none() {
+            return true;
+        }
+        let mut swapped = permutation.to_vec();
+        swapped.swap(a, b);
+
+        groups.all(|g| self.satisfies_symbol_group(g, &swapped))
+    }
+
+    /// Reassign the elements of each swap class so that all of them lie within their regions.
+    /// Elements within their regions keep their indices as far as possible. Returns `false` if
+    /// the regions can not be satisfied.
+    fn assign_regions(&self, permutation: &mut [usize]) -> bool {
+        if self.regions.is_empty() {
+            return true;
+        }
+
+        for class in self.swap_classes() {
+            let regions: Vec<&PermutationRegion> = self
+                .regions
+                .iter()
+                .filter(|r| class.contains(&r.element))
+                .collect();
+            if regions
+                .iter()
+                .all(|r| r.indices.contains(&permutation[r.element]))
+            {
+                continue;
+            }
+
+            let class_indices: Vec<usize> = class.iter().map(|j| permutation[*j]).collect();
+            let mut owners: Vec<Option<usize>> = vec![None; class_indices.len()];
+            for (n, r) in regions.iter().enumerate() {
+                if r.indices.contains(&permutation[r.element]) {
+                    let k = class_indices
+                        .iter()
+                        .position(|i| *i == permutation[r.element])
+                        .unwrap();
+                    owners[k] = Some(n);
+                }
+            }
+            for n in 0..regions.len() {
+                if owners.contains(&Some(n)) {
+                    continue;
+                }
+                let mut visited = vec![false; class_indices.len()];
+                if !assign_region(n, &regions, &class_indices, &mut owners, &mut visited) {
+                    return false;
+                }
+            }
+
+            // elements without region keep their indices unless taken by a region's element
+            let previous: Vec<usize> = permutation.to_vec();
+            let mut released: Vec<usize> = regions
+                .iter()
+                .map(|r| previous[r.element])
+                .filter(|i| {
+                    let k = class_indices.iter().position(|ci| ci == i).unwrap();
+                    owners[k].is_none()
+                })
+                .collect();
+            released.sort_unstable();
+            for (k, owner) in owners.iter().enumerate() {
+                if let Some(n) = owner {
+                    permutation[regions[*n].element] = class_indices[k];
+                }
+            }
+            let mut released = released.into_iter();
+            for j in class
+                .iter()
+                .filter(|j| !regions.iter().any(|r| r.element == **j))
+            {
+                let k = class_indices
+                    .iter()
+                    .position(|i| *i == previous[*j])
+                    .unwrap();
+                if owners[k].is_some() {
+                    permutation[*j] = released.next().unwrap();
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Constrain the split of the unigram load between both hands. Permutations outside of the
+    /// constraint's margin are repaired (when generating their strings) by deterministically
+    /// swapping the lightest symbols across hands, respecting fixed keys and keycap groups.
+    pub fn with_hand_balance(mut self, hand_balance: HandBalance) -> Self {
+        self.hand_balance = Some(hand_balance);
+        self
+    }
+
+    pub fn hand_balance(&self) -> Option<&HandBalance> {
+        self.hand_balance.as_ref()
+    }
+
+    /// Swap group symbols that are placed outside of their group's indices with the symbols
+    /// occupying these indices, so that the permutation satisfies all keycap groups. Afterwards,
+    /// symbols outside of their regions are moved into them and symbol groups are repaired (see
+    /// [`LayoutPermutator::violates_symbol_groups`]).
+    pub fn repair(&self, permutation: &[usize]) -> Vec<usize> {
+        let mut indices = self.repair_groups(permutation);
+
+        // regions are checked to be satisfiable when adding them
+        assert!(self.assign_regions(&mut indices));
+        self.repair_symbol_groups(&mut indices);
+
+        indices
+    }
+
+    /// Swap group symbols that are placed outside of their group's indices with the symbols
+    /// occupying these indices.
+    fn repair_groups(&self, permutation: &[usize]) -> Vec<usize> {
+        let mut indices: Vec<usize> = permutation.to_vec();
+
+        for group in self.groups.iter() {
+            let misplaced: Vec<usize> = group
+                .elements
+                .iter()
+                .filter(|j| !group.indices.contains(&indices[**j]))
+                .cloned()
+                .collect();
+            let intruders: Vec<usize> = (0..indices.len())
+                .filter(|j| !group.elements.contains(j) && group.indices.contains(&indices[*j]))
+                .collect();
+
+            for (a, b) in misplaced.into_iter().zip(intruders) {
+                indices.swap(a, b);
+            }
+        }
+
+        indices
+    }
+
+    /// The permutation repaired wrt. the keycap groups and regions (if necessary).
+    fn with_groups_repaired<'a>(&self, permutation: &'a [usize]) -> Cow<'a, [usize]> {
+        if self.groups.is_empty() && self.regions.is_empty() && self.symbol_groups.is_empty() {
+            Cow::Borrowed(permutation)
+        } else {
+            Cow::Owned(self.repair(permutation))
+        }
+    }
+
+    /// The loads of the left and the right hand (excluding thumbs) for the permutation.
+    fn hand_loads(&self, hand_balance: &HandBalance, permutation: &[usize]) -> (f64, f64) {
+        let mut loads = [0.0, 0.0];
+        self.fixed_indices
+            .iter()
+            .zip(self.fixed_keys.iter())
+            .chain(permutation.iter().zip(self.perm_keys.iter()))
+            .for_each(|(i, c)| {
+                if let Some(hand) = hand_balance.hand(*i) {
+                    loads[hand as usize] += hand_balance.weight(*c);
+                }
+            });
+
+        (loads[0], loads[1])
+    }
+
+    /// Swap symbols of the heavier hand with lighter ones of the other hand (within the same
+    /// swap class) until the split of the loads is within the margin. The lightest symbols of
+    /// the heavier hand are moved first, each symbol at most once. Returns `None` if the margin
+    /// can not be reached this way.
+    fn balance(&self, hand_balance: &HandBalance, permutation: &[usize]) -> Option<Vec<usize>> {
+        let mut indices: Vec<usize> = permutation.to_vec();
+        let weights: Vec<f64> = self
+            .perm_keys
+            .iter()
+            .map(|c| hand_balance.weight(*c))
+            .collect();
+        let by_weight = |a: &usize, b: &usize| {
+            weights[*a]
+                .partial_cmp(&weights[*b])
+                .unwrap()
+                .then(a.cmp(b))
+        };
+        let classes = self.swap_classes();
+        let mut moved = vec![false; indices.len()];
+
+        loop {
+            let (left, right) = self.hand_loads(hand_balance, &indices);
+            let deviation = hand_balance.deviation(left, right);
+            if deviation.abs() <= hand_balance.params().margin {
+                return Some(indices);
+            }
+
+            let (heavy, light) = if deviation > 0.0 {
+                (Hand::Left, Hand::Right)
+            } else {
+                (Hand::Right, Hand::Left)
+            };
+            // moving more weight than this would leave a larger disbalance to the other side
+            let max_transfer = 2.0 * deviation.abs() * (left + right);
+            let on_hand =
+                |j: usize, hand: Hand| !moved[j] && hand_balance.hand(indices[j]) == Some(hand);
+
+            let swap = classes
+                .iter()
+                .flat_map(|class| {
+                    class
+                        .iter()
+                        .filter(|h| on_hand(**h, heavy))
+                        .filter_map(|h| {
+                            class
+                                .iter()
+                                .filter(|l| on_hand(**l, light))
+                                .filter(|l| self.swappable(&indices, *h, **l))
+                                .filter(|l| {
+                                    let transfer = weights[*h] - weights[**l];
+                                    transfer > 0.0 && transfer < max_transfer
+                                })
+                                .min_by(|a, b| by_weight(a, b))
+                                .map(|l| (*h, *l))
+                        })
+                        .collect::<Vec<(usize, usize)>>()
+                })
+                .min_by(|(h1, _), (h2, _)| by_weight(h1, h2))?;
+
+            indices.swap(swap.0, swap.1);
+            moved[swap.0] = true;
+            moved[swap.1] = true;
+        }
+    }
+
+    /// The permutation repaired wrt. the hand balance constraint if it is outside of the
+    /// constraint's margin and the constraint's mode is [`HandBalanceMode::Repair`].
+    fn balanced(&self, permutation: &[usize]) -> Option<Vec<usize>> {
+        let hand_balance = self
+            .hand_balance
+            .as_ref()
+            .filter(|hb| hb.params().mode == HandBalanceMode::Repair)?;
+        let (left, right) = self.hand_loads(hand_balance, permutation);
+        if hand_balance.deviation(left, right).abs() <= hand_balance.params().margin {
+            return None;
+        }
+
+        self.balance(hand_balance, permutation)
+    }
+
+    /// Check the permutation against the hand balance constraint (if any) and record the
+    /// outcome in the constraint's counters. Returns the deviation of the left hand's fraction
+    /// beyond the margin if the permutation is infeasible, i.e. rejected or not repairable.
+    pub fn hand_balance_violation(&self, permutation: &[usize]) -> Option<f64> {
+        let hand_balance = self.hand_balance.as_ref()?;
+        let permutation = self.with_groups_repaired(permutation);
+        let (left, right) = self.hand_loads(hand_balance, &permutation);
+        let excess = hand_balance.deviation(left, right).abs() - hand_balance.params().margin;
+
+        let outcome = if excess <= 0.0 {
+            Outcome::Balanced
+        } else if hand_balance.params().mode == HandBalanceMode::Repair
+            && self.balance(hand_balance, &permutation).is_some()
+        {
+            Outcome::Repaired
+        } else {
+            Outcome::Rejected
+        };
+        hand_balance.record(outcome);
+
+        (outcome == Outcome::Rejected).then(|| excess)
+    }
+
+    /// Elements of the permutation that may be swapped with each other (the free ones and
+    /// one class per keycap group). Classes with less than two elements are omitted. Swaps
+    /// within a class may still be restricted by regions.
+    fn swap_classes(&self) -> Vec<Vec<usize>> {
+        let free: Vec<usize> = (0..self.perm_keys.len())
+            .filter(|j| !self.groups.iter().any(|g| g.elements.contains(j)))
+            .collect();
+
+        std::iter::once(free)
+            .chain(self.groups.iter().map(|g| g.elements.clone()))
+            .filter(|class| class.len() > 1)
+            .collect()
+    }
+
     pub fn generate_string(&self, permutation: &[usize]) -> String {
         let mut res: Vec<char> = vec!['-'; self.fixed_keys.len() + self.perm_keys.len()];
 
@@ -40,6 +745,13 @@ impl LayoutPermutator {
             .zip(self.fixed_keys.iter())
             .for_each(|(i, c)| res[*i] = *c);
 
+        // permutations produced by operations unaware of keycap groups or the hand balance
+        // (e.g. mutations of the genetic algorithm) need to be repaired first
+        let mut permutation = self.with_groups_repaired(permutation);
+        if let Some(balanced) = self.balanced(&permutation) {
+            permutation = Cow::Owned(balanced);
+        }
+
         permutation
             .iter()
             .zip(self.perm_keys.iter())
@@ -49,8 +761,65 @@ impl LayoutPermutator {
     }
 
     pub fn generate_random(&self) -> Vec<usize> {
+        self.generate_random_with_rng(&mut thread_rng())
+    }
+
+    /// Same as [`LayoutPermutator::generate_random`], but using the given random number
+    /// generator (e.g. a seeded one for reproducible results).
+    pub fn generate_random_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<usize> {
+        if self.symbol_groups.is_empty() {
+            return self.shuffle_with_rng(rng);
+        }
+
+        // draw again if a permutation can not be repaired wrt. the symbol groups (after too
+        // many draws, the last one is returned and rejected when being evaluated)
+        let mut indices = self.repair(&self.shuffle_with_rng(rng));
+        for _ in 1..MAX_RANDOM_DRAWS {
+            if !self.violates_symbol_groups(&indices) {
+                break;
+            }
+            indices = self.repair(&self.shuffle_with_rng(rng));
+        }
+
+        indices
+    }
+
+    /// A random permutation satisfying the keycap groups and regions.
+    fn shuffle_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<usize> {
+        if self.groups.is_empty() {
+            let mut indices: Vec<usize> = self.perm_indices.to_vec();
+            shuffle(&mut indices, rng);
+            assert!(self.assign_regions(&mut indices));
+
+            return indices;
+        }
+
         let mut indices: Vec<usize> = self.perm_indices.to_vec();
-        indices.shuffle(&mut thread_rng());
+
+        // free symbols are shuffled among the indices that belong to no group
+        let free_elements: Vec<usize> = (0..self.perm_keys.len())
+            .filter(|j| !self.groups.iter().any(|g| g.elements.contains(j)))
+            .collect();
+        let mut free_indices: Vec<usize> = self
+            .perm_indices
+            .iter()
+            .filter(|i| !self.groups.iter().any(|g| g.indices.contains(i)))
+            .cloned()
+            .collect();
+        shuffle(&mut free_indices, rng);
+        for (j, i) in free_elements.into_iter().zip(free_indices) {
+            indices[j] = i;
+        }
+
+        // group symbols are shuffled among their group's indices
+        for group in self.groups.iter() {
+            let mut group_indices = group.indices.clone();
+            shuffle(&mut group_indices, rng);
+            for (j, i) in group.elements.iter().zip(group_indices) {
+                indices[*j] = i;
+            }
+        }
+        assert!(self.assign_regions(&mut indices));
 
         indices
     }
@@ -58,16 +827,55 @@ impl LayoutPermutator {
     /// Takes in a Layout, switches [nr_switches] keys in that layout, then returns it.
     /// Layout, in this case, is a [Vec<usize>].
     pub fn perform_n_swaps(&self, permutation: &[usize], nr_switches: usize) -> Vec<usize> {
+        self.perform_n_swaps_with_rng(permutation, nr_switches, &mut thread_rng())
+    }
+
+    /// Same as [`LayoutPermutator::perform_n_swaps`], but using the given random number
+    /// generator (e.g. a seeded one for reproducible results).
+    pub fn perform_n_swaps_with_rng<R: Rng + ?Sized>(
+        &self,
+        permutation: &[usize],
+        nr_switches: usize,
+        rng: &mut R,
+    ) -> Vec<usize> {
         let mut indices: Vec<usize> = permutation.to_vec();
-        let vec: Vec<usize> = (0..permutation.len()).collect();
-        let rng = &mut thread_rng();
 
-        // Perform nr_switches switches
+        if !self.groups.is_empty() || !self.regions.is_empty() || !self.symbol_groups.is_empty() {
+            // only swap symbols that belong to the same class (free or the same keycap group)
+            // and stay within their regions and symbol groups, choosing the first symbol uniformly among all
+            // swappable ones
+            let classes = self.swap_classes();
+            let swappable: Vec<(usize, &Vec<usize>)> = classes
+                .iter()
+                .flat_map(|class| class.iter().map(move |j| (*j, class)))
+                .collect();
+            if swappable.is_empty() {
+                return indices;
+            }
+
+            for _ in 0..nr_switches {
+                let (sw0, class) = choose(&swappable, rng).unwrap();
+                let candidates: Vec<&usize> = class
+                    .iter()
+                    .filter(|j| *j != sw0 && self.swappable(&indices, *sw0, **j))
+                    .collect();
+                if let Some(sw1) = choose(&candidates, rng) {
+                    indices.swap(*sw0, **sw1);
+                }
+            }
+
+            return indices;
+        }
+
+        // Perform nr_switches switches of two distinct keys
+        let n = permutation.len();
         for _ in 0..nr_switches {
-            let mut sw = vec.choose_multiple(rng, 2);
-            let sw0 = sw.next().unwrap();
-            let sw1 = sw.next().unwrap();
-            indices.swap(*sw0, *sw1);
+            let sw0 = random_index(n, rng);
+            let mut sw1 = random_index(n - 1, rng);
+            if sw1 >= sw0 {
+                sw1 += 1;
+            }
+            indices.swap(sw0, sw1);
         }
 
         indices
@@ -83,22 +891,346 @@ impl LayoutPermutator {
         sw_to.shuffle(rng);
 
         // Perform nr_switches switches
-        for (from, to) in sw_from.into_iter().zip(sw_to.into_iter()) {
+        for (from, to) in sw_from.into_iter().zip(sw_to) {
             indices[*to] = permutation[*from];
         }
 
-        indices
+        self.repair(&indices)
     }
 
+    /// The permutation corresponding to the initial layout (repaired if it violates a keycap
+    /// group, a region, or a symbol group).
     pub fn get_permutable_indices(&self) -> Vec<usize> {
-        self.perm_indices.clone()
+        self.repair(&self.perm_indices)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    use rand::{rngs::StdRng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn seeded_permutations_are_reproducible() {
+        let permutator = LayoutPermutator::new("abcdefghij", "cf");
+
+        let random = |seed| permutator.generate_random_with_rng(&mut StdRng::seed_from_u64(seed));
+        assert_eq!(random(1), random(1));
+
+        let layout = permutator.generate_string(&random(1));
+        assert_eq!(layout.find('c'), Some(2));
+        assert_eq!(layout.find('f'), Some(5));
+        let mut chars: Vec<char> = layout.chars().collect();
+        chars.sort_unstable();
+        assert_eq!(chars.into_iter().collect::<String>(), "abcdefghij");
+
+        let perm = permutator.get_permutable_indices();
+        let swapped =
+            |seed| permutator.perform_n_swaps_with_rng(&perm, 3, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(swapped(2), swapped(2));
+        assert_eq!(permutator.generate_string(&swapped(2)).find('c'), Some(2));
+    }
+
+    #[test]
+    fn seeded_permutations_are_value_stable() {
+        // the web app's random and perturbed layouts are reproduced from their seeds
+        let rng = |seed| ChaCha8Rng::seed_from_u64(seed);
+        let permutator = LayoutPermutator::new("abcdefghij", "cf");
+        let random = permutator.generate_string(&permutator.generate_random_with_rng(&mut rng(42)));
+        assert_eq!(random, "dechgfajib");
+        let swapped = permutator.perform_n_swaps_with_rng(
+            &permutator.get_permutable_indices(),
+            3,
+            &mut rng(42),
+        );
+        assert_eq!(permutator.generate_string(&swapped), "adcbhfgjie");
+
+        let groups = [KeycapGroup {
+            symbols: vec!['c', 'd'],
+            indices: vec![0, 7],
+        }];
+        let permutator = LayoutPermutator::new("abcdefghij", "i")
+            .with_keycap_groups(&groups)
+            .unwrap();
+        let random = permutator.generate_string(&permutator.generate_random_with_rng(&mut rng(7)));
+        assert_eq!(random, "dbjfgaecih");
+        let swapped = permutator.perform_n_swaps_with_rng(
+            &permutator.get_permutable_indices(),
+            3,
+            &mut rng(7),
+        );
+        assert_eq!(permutator.generate_string(&swapped), "chaebfjdig");
+    }
+
+    #[test]
+    fn keycap_groups_are_kept() {
+        // "c" and "d" have to stay on the indices 0 and 7, "i" is fixed
+        let groups = [KeycapGroup {
+            symbols: vec!['c', 'd'],
+            indices: vec![0, 7],
+        }];
+        let permutator = LayoutPermutator::new("abcdefghij", "i")
+            .with_keycap_groups(&groups)
+            .unwrap();
+        let on_group_keys = |layout: &str| {
+            groups[0].misplaced_symbols(layout).is_empty() && layout.find('i') == Some(8)
+        };
+
+        let initial = permutator.generate_string(&permutator.get_permutable_indices());
+        assert!(on_group_keys(&initial));
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let perm = permutator.generate_random_with_rng(&mut rng);
+            assert!(on_group_keys(&permutator.generate_string(&perm)));
+
+            let swapped = permutator.perform_n_swaps_with_rng(&perm, 5, &mut rng);
+            assert_eq!(permutator.repair(&swapped), swapped);
+        }
+
+        // arbitrary permutations get repaired
+        let mut perm = permutator.get_permutable_indices();
+        perm.shuffle(&mut rng);
+        assert!(on_group_keys(&permutator.generate_string(&perm)));
+
+        let fixed_elsewhere = LayoutPermutator::new("abcdefghij", "c").with_keycap_groups(&groups);
+        assert!(fixed_elsewhere.is_err());
+    }
+
+    #[test]
+    fn regions_are_kept() {
+        // "c" and "d" have to stay on the indices 0 and 7, "i" is fixed
+        let groups = [KeycapGroup {
+            symbols: vec!['c', 'd'],
+            indices: vec![0, 7],
+        }];
+        // index 0 belongs to the group, index 8 to the fixed "i"
+        let regions = [
+            ('a', vec![0, 1, 2]),
+            ('b', vec![1, 2]),
+            ('e', vec![8, 9]),
+            ('i', vec![8]),
+        ];
+        let permutator = LayoutPermutator::new("abcdefghij", "i")
+            .with_keycap_groups(&groups)
+            .unwrap()
+            .with_regions(&regions)
+            .unwrap();
+        let within_constraints = |perm: &[usize]| {
+            let layout = permutator.generate_string(perm);
+            let position = |c| layout.chars().position(|lc| lc == c).unwrap();
+            groups[0].misplaced_symbols(&layout).is_empty()
+                && [1, 2].contains(&position('a'))
+                && [1, 2].contains(&position('b'))
+                && position('e') == 9
+                && position('i') == 8
+        };
+
+        let mut perm = permutator.get_permutable_indices();
+        assert!(within_constraints(&perm));
+
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..500 {
+            // mutations of the simulated annealing need no repair
+            perm = permutator.perform_n_swaps_with_rng(&perm, 3, &mut rng);
+            assert_eq!(permutator.repair(&perm), perm);
+            assert!(within_constraints(&perm));
+
+            let random = permutator.generate_random_with_rng(&mut rng);
+            assert_eq!(permutator.repair(&random), random);
+            assert!(within_constraints(&random));
+
+            // arbitrary permutations (e.g. from the genetic algorithm) get repaired
+            let mut shuffled = random.clone();
+            shuffled.shuffle(&mut rng);
+            assert!(within_constraints(&shuffled));
+            assert!(within_constraints(&permutator.switch_n_keys(&random, 4)));
+        }
+
+        // "a" and "b" still get swapped
+        let layouts: Vec<String> = (0..20)
+            .map(|_| permutator.generate_string(&permutator.generate_random_with_rng(&mut rng)))
+            .collect();
+        assert!(layouts
+            .iter()
+            .any(|l| l.starts_with("da") || l.starts_with("ca")));
+        assert!(layouts
+            .iter()
+            .any(|l| l.starts_with("db") || l.starts_with("cb")));
+
+        let with_regions = |regions: &[(char, Vec<usize>)]| {
+            LayoutPermutator::new("abcdefghij", "i")
+                .with_keycap_groups(&groups)
+                .unwrap()
+                .with_regions(regions)
+        };
+        // three symbols on two keys
+        assert!(with_regions(&[('a', vec![1, 2]), ('b', vec![1, 2]), ('e', vec![1, 2])]).is_err());
+        // only keys of the group or fixed keys
+        assert!(with_regions(&[('a', vec![0, 8])]).is_err());
+        assert!(with_regions(&[('i', vec![0])]).is_err());
+        assert!(with_regions(&[('☃', vec![0])]).is_err());
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn symbol_groups_are_kept() {
+        use keyboard_layout::key::{Finger, MatrixPosition};
+        use symbol_groups::SymbolGroupKind::*;
+
+        // two rows of six keys, each column belongs to another finger
+        let fingers = [Finger::Ring, Finger::Middle, Finger::Index];
+        let placements: Vec<KeyPlacement> = (0..12)
+            .map(|i| {
+                let column = i % 6;
+                KeyPlacement {
+                    hand: if column < 3 { Hand::Left } else { Hand::Right },
+                    finger: fingers[if column < 3 { column } else { 5 - column }],
+                    position: MatrixPosition(column as u8, (i / 6) as u8),
+                }
+            })
+            .collect();
+        let group = |kind, symbols: &str| SymbolGroup {
+            kind,
+            symbols: symbols.chars().collect(),
+        };
+        // "a" is on the left hand, "e" and "k" on the right one
+        let groups = [
+            group(SameHand, "aek"),
+            group(SameFinger, "bh"),
+            group(RelativeOrder, "dgj"),
+        ];
+        let permutator = |fixed: &str, groups: &[SymbolGroup]| {
+            LayoutPermutator::new("abcdefghijkl", fixed)
+                .with_symbol_groups(groups, placements.clone())
+        };
+        let pm = permutator("h", &groups).unwrap();
+        let satisfied = |perm: &[usize]| {
+            let layout = pm.generate_string(perm);
+            groups.iter().all(|g| {
+                let keys: Vec<KeyPlacement> = g
+                    .symbols
+                    .iter()
+                    .map(|c| placements[layout.chars().position(|lc| lc == *c).unwrap()])
+                    .collect();
+                g.is_satisfied_by(&keys)
+            }) && layout.find('h') == Some(7)
+        };
+
+        let mut perm = pm.get_permutable_indices();
+        assert!(satisfied(&perm));
+        // "a" takes the key of "f", the first symbol on the right hand outside of all groups
+        assert_eq!(pm.generate_string(&perm).find('a'), Some(5));
+
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut n_repaired = 0;
+        for _ in 0..500 {
+            perm = pm.perform_n_swaps_with_rng(&perm, 2, &mut rng);
+            assert!(satisfied(&perm));
+            assert!(!pm.violates_symbol_groups(&perm));
+
+            let random = pm.generate_random_with_rng(&mut rng);
+            assert!(satisfied(&random));
+
+            // arbitrary permutations are either repaired or infeasible
+            let mut shuffled = random.clone();
+            shuffled.shuffle(&mut rng);
+            if !pm.violates_symbol_groups(&shuffled) {
+                assert!(satisfied(&shuffled));
+                n_repaired += 1;
+            }
+        }
+        assert!(n_repaired > 0);
+
+        // seven symbols on the same hand
+        assert!(permutator("", &[group(SameHand, "abcdefg")]).is_err());
+        // fixed symbols in the wrong order
+        assert!(permutator("ab", &[group(RelativeOrder, "ba")]).is_err());
+        assert!(permutator("", &[group(SameHand, "a☃")]).is_err());
+    }
+
+    #[test]
+    fn hand_balance_is_enforced() {
+        use hand_balance::HandBalanceParameters;
+        use keyboard_layout::{
+            config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+            neo_layout_generator::NeoLayoutGenerator,
+        };
+        use layout_evaluation::{
+            config::EvaluationParameters,
+            evaluation::Evaluator,
+            ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+            ngrams::{Bigrams, Trigrams, Unigrams},
+        };
+        use std::sync::Arc;
+
+        const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+        const TEXT: &str = "Zwölf Boxkämpfer jagen Viktor quer über den großen Sylter Deich. \
+                            Franz jagt im komplett verwahrlosten Taxi quer durch Bayern.";
+
+        let layout_config = LayoutConfig::from_yaml("../../config/keyboard/standard.yml").unwrap();
+        let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+        let generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+        let eval_params =
+            EvaluationParameters::from_yaml("../../config/evaluation/default.yml").unwrap();
+        let mapper = OnDemandNgramMapper::with_ngrams(
+            Unigrams::from_text(TEXT).unwrap(),
+            Bigrams::from_text(TEXT).unwrap(),
+            Trigrams::from_text(TEXT).unwrap(),
+            eval_params.ngram_mapper,
+        );
+        let evaluator = Evaluator::default(Box::new(mapper));
+        let layout = generator.generate(LAYOUT).unwrap();
+
+        let params = |mode| HandBalanceParameters {
+            target: 0.5,
+            margin: 0.03,
+            mode,
+        };
+        let permutator = |mode| {
+            LayoutPermutator::new(LAYOUT, "ae").with_hand_balance(HandBalance::new(
+                &params(mode),
+                LAYOUT,
+                &layout,
+                &evaluator,
+            ))
+        };
+        let repairing = permutator(HandBalanceMode::Repair);
+        let rejecting = permutator(HandBalanceMode::Reject);
+        let unconstrained = LayoutPermutator::new(LAYOUT, "ae");
+        let hand_balance = repairing.hand_balance().unwrap();
+        let position = |layout: &str, c| layout.chars().position(|lc| lc == c);
+
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut n_unbalanced = 0;
+        for _ in 0..50 {
+            let perm = repairing.generate_random_with_rng(&mut rng);
+            let original = unconstrained.generate_string(&perm);
+            let deviation = (hand_balance.left_fraction(&original).unwrap() - 0.5).abs();
+
+            let rejected = rejecting.hand_balance_violation(&perm);
+            assert_eq!(rejected.is_some(), deviation > 0.03);
+            assert_eq!(rejecting.generate_string(&perm), original);
+            n_unbalanced += rejected.is_some() as usize;
+
+            let repaired = repairing.generate_string(&perm);
+            assert_eq!(repaired, repairing.generate_string(&perm));
+            assert_eq!(position(&repaired, 'a'), position(LAYOUT, 'a'));
+            assert_eq!(position(&repaired, 'e'), position(LAYOUT, 'e'));
+            if repairing.hand_balance_violation(&perm).is_none() {
+                let deviation = (hand_balance.left_fraction(&repaired).unwrap() - 0.5).abs();
+                assert!(deviation <= 0.03);
+            } else {
+                assert_eq!(repaired, original);
+            }
+        }
+
+        assert!(n_unbalanced > 0);
+        let (checked, repaired, rejected) = hand_balance.counts();
+        assert_eq!(checked, 50);
+        assert_eq!(repaired + rejected, n_unbalanced);
+        assert!(repaired > 0);
+        assert_eq!(rejecting.hand_balance().unwrap().counts().2, n_unbalanced);
     }
 }
@@ -0,0 +1,104 @@
+//! The `symbol_groups` module provides constraints on the placement of symbols relative to each
+//! other: groups of symbols that have to be placed on the same hand, on keys of the same finger,
+//! or in a given order (row by row, left to right). In contrast to pins and regions, the keys are
+//! not specified, so the optimizers may move the symbols as long as the group's constraint holds
+//! (see [`crate::LayoutPermutator::with_symbol_groups`]).
+
+use keyboard_layout::{
+    key::{Finger, Hand, Key, MatrixPosition},
+    layout::Layout,
+};
+
+use std::fmt;
+
+/// How the symbols of a [`SymbolGroup`] have to be placed relative to each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolGroupKind {
+    /// All symbols on keys of the same hand
+    SameHand,
+    /// All symbols on keys of the same finger
+    SameFinger,
+    /// The symbols in the given order, row by row and left to right
+    RelativeOrder,
+}
+
+impl fmt::Display for SymbolGroupKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolGroupKind::SameHand => write!(f, "on the same hand"),
+            SymbolGroupKind::SameFinger => write!(f, "on the same finger"),
+            SymbolGroupKind::RelativeOrder => {
+                write!(f, "in the given order (row by row, left to right)")
+            }
+        }
+    }
+}
+
+/// The properties of a key that are relevant for symbol groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPlacement {
+    pub hand: Hand,
+    pub finger: Finger,
+    pub position: MatrixPosition,
+}
+
+impl From<&Key> for KeyPlacement {
+    fn from(key: &Key) -> Self {
+        Self {
+            hand: key.hand,
+            finger: key.finger,
+            position: key.matrix_position,
+        }
+    }
+}
+
+impl KeyPlacement {
+    /// The placements of the keys at each index of the layout string. `layout` needs to be
+    /// generated from `layout_str`. `None` if a symbol of the string is not part of the layout.
+    pub fn from_layout(layout_str: &str, layout: &Layout) -> Option<Vec<Self>> {
+        layout_str
+            .chars()
+            .map(|c| layout.get_layerkey_for_symbol(&c).map(|k| (&k.key).into()))
+            .collect()
+    }
+
+    /// Rank of the key when reading the keyboard row by row, left to right.
+    pub(crate) fn reading_order(&self) -> (u8, u8) {
+        (self.position.1, self.position.0)
+    }
+}
+
+/// A group of symbols that have to be placed relative to each other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolGroup {
+    pub kind: SymbolGroupKind,
+    pub symbols: Vec<char>,
+}
+
+impl SymbolGroup {
+    /// If both keys are interchangeable wrt. the group's hand or finger constraint.
+    pub(crate) fn same_keys(&self, k1: &KeyPlacement, k2: &KeyPlacement) -> bool {
+        match self.kind {
+            SymbolGroupKind::SameHand => k1.hand == k2.hand,
+            SymbolGroupKind::SameFinger => k1.hand == k2.hand && k1.finger == k2.finger,
+            SymbolGroupKind::RelativeOrder => k1 == k2,
+        }
+    }
+
+    /// If the keys of the group's symbols (given in the order of the symbols) satisfy the
+    /// group's constraint.
+    pub fn is_satisfied_by(&self, keys: &[KeyPlacement]) -> bool {
+        match self.kind {
+            SymbolGroupKind::RelativeOrder => keys
+                .windows(2)
+                .all(|w| w[0].reading_order() < w[1].reading_order()),
+            _ => keys.windows(2).all(|w| self.same_keys(&w[0], &w[1])),
+        }
+    }
+}
+
+impl fmt::Display for SymbolGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbols.iter().collect::<String>())
+    }
+}
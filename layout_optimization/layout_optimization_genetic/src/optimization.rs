@@ -1,7 +1,9 @@
 use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
 use layout_evaluation::{cache::Cache, evaluation::Evaluator};
 
-use layout_optimization_common::LayoutPermutator;
+use layout_optimization_common::{
+    blacklist::Blacklist, constraints::ConstraintSet, LayoutPermutator,
+};
 
 use anyhow::Result;
 use colored::Colorize;
@@ -13,7 +15,6 @@ use genevo::{
     operator::{prelude::*, CrossoverOp, GeneticOperator},
     population::Population,
     prelude::*,
-    random::SliceRandom,
     simulation::simulator::Simulator,
     types::fmt::Display,
 };
@@ -58,13 +59,27 @@ pub struct FitnessCalc {
     permutator: LayoutPermutator,
     layout_generator: Box<dyn LayoutGenerator>,
     result_cache: Option<Cache<usize>>,
+    blacklist: Option<Arc<Blacklist>>,
 }
 
 impl FitnessFunction<Genotype, usize> for FitnessCalc {
     fn fitness_of(&self, genome: &Genotype) -> usize {
+        // Layouts violating the hand balance constraint or a symbol group (even after repairing
+        // them) are infeasible (without evaluation)
+        if self.permutator.hand_balance_violation(genome).is_some()
+            || self.permutator.violates_symbol_groups(genome)
+        {
+            return self.lowest_possible_fitness();
+        }
+
         let layout_str = self.permutator.generate_string(genome);
         let l = self.layout_generator.generate(&layout_str).unwrap();
 
+        // Layouts forbidden by the blacklist are infeasible
+        if self.blacklist.as_ref().map_or(false, |b| b.forbids(&l)) {
+            return self.lowest_possible_fitness();
+        }
+
         // Get & return the evaluation-result
         match &self.result_cache {
             Some(result_cache) => result_cache.get_or_insert_with(&layout_str, || {
@@ -88,13 +103,13 @@ impl FitnessFunction<Genotype, usize> for FitnessCalc {
 }
 
 struct LayoutBuilder {
-    indices: Vec<usize>,
+    permutator: LayoutPermutator,
 }
 
 impl LayoutBuilder {
     fn with_permutable_layout(layout_prototype: &LayoutPermutator) -> Self {
         Self {
-            indices: layout_prototype.get_permutable_indices(),
+            permutator: layout_prototype.clone(),
         }
     }
 }
@@ -104,9 +119,7 @@ impl GenomeBuilder<Vec<usize>> for LayoutBuilder {
     where
         R: Rng + Sized,
     {
-        let mut s: Vec<usize> = self.indices.clone();
-        s.shuffle(rng);
-        s
+        self.permutator.generate_random_with_rng(rng)
     }
 }
 
@@ -221,16 +234,26 @@ pub type MySimulator = Simulator<
     GenerationLimit,
 >;
 
+#[allow(clippy::too_many_arguments)]
 pub fn init_optimization(
     params: &Parameters,
     evaluator: &Evaluator,
     layout_str: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     fixed_characters: &str,
     start_with_layout: bool,
     cache_results: bool,
+    blacklist: Option<Arc<Blacklist>>,
+    constraints: Option<&ConstraintSet>,
 ) -> (MySimulator, LayoutPermutator) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    let pm = match constraints {
+        Some(constraints) => constraints
+            .permutator(layout_str, fixed_characters, layout_generator, evaluator)
+            .expect("Could not apply the constraints"),
+        None => LayoutPermutator::new(layout_str, fixed_characters)
+            .with_keycap_groups(&layout_generator.keycap_groups())
+            .expect("Could not apply keycap groups"),
+    };
     let initial_population: Population<Genotype> = if start_with_layout {
         build_population()
             .with_genome_builder(FromGivenLayoutBuilder::with_permutable_layout(&pm))
@@ -254,8 +277,9 @@ pub fn init_optimization(
             .with_evaluation(FitnessCalc {
                 evaluator: Arc::new(evaluator.clone()),
                 permutator: pm.clone(),
-                layout_generator: layout_generator.clone(),
+                layout_generator: layout_generator.clone_box(),
                 result_cache,
+                blacklist,
             })
             .with_selection(MaximizeSelector::new(
                 params.selection_ratio,
@@ -275,14 +299,17 @@ pub fn init_optimization(
     (sim, pm)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn optimize(
     params: &Parameters,
     evaluator: &Evaluator,
     layout_str: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     fixed_characters: &str,
     start_with_layout: bool,
     cache_results: bool,
+    blacklist: Option<Arc<Blacklist>>,
+    constraints: Option<&ConstraintSet>,
 ) -> (String, Layout) {
     let (mut sim, pm) = init_optimization(
         params,
@@ -292,6 +319,8 @@ pub fn optimize(
         fixed_characters,
         start_with_layout,
         cache_results,
+        blacklist,
+        constraints,
     );
 
     log::info!("Starting optimization with: {:?}", params);
@@ -341,6 +370,9 @@ pub fn optimize(
                     step.processing_time.fmt(),
                     pm.generate_string(&best_solution.solution.genome)
                 );
+                if let Some(hand_balance) = pm.hand_balance() {
+                    log::info!("{}", hand_balance);
+                }
             }
             Ok(SimResult::Final(step, processing_time, duration, _stop_reason)) => {
                 let layout_str = pm.generate_string(&all_time_best.as_ref().unwrap().1);
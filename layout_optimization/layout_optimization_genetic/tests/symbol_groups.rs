@@ -0,0 +1,64 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+use layout_optimization_common::constraints::ConstraintSet;
+use layout_optimization_genetic::optimization::{optimize, Parameters};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+const CONSTRAINTS: &str = "
+same_hand: [qjxz]
+same_finger: [ßy]
+";
+
+#[test]
+fn optimize_with_same_hand_group() {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(TEXT).unwrap(),
+        Bigrams::from_text(TEXT).unwrap(),
+        Trigrams::from_text(TEXT).unwrap(),
+        eval_params.ngram_mapper,
+    );
+    let evaluator = Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics);
+    let constraints = ConstraintSet::from_yaml_str(CONSTRAINTS).unwrap();
+
+    // random genomes, mutations, and crossovers are repaired (or rejected)
+    let params = Parameters {
+        population_size: 20,
+        generation_limit: 20,
+        mutation_rate: 0.5,
+        ..Default::default()
+    };
+    let (layout_str, layout) = optimize(
+        &params,
+        &evaluator,
+        LAYOUT,
+        &layout_generator,
+        "",
+        false,
+        false,
+        None,
+        Some(&constraints),
+    );
+
+    assert_eq!(
+        constraints.check(&layout_str, &layout, None).unwrap(),
+        vec![]
+    );
+}
@@ -0,0 +1,260 @@
+//! Statistics of the moves proposed during Simulated Annealing and the Kirkpatrick-style
+//! initialization of the temperature from them.
+//!
+//! `argmin` accepts a move that worsens the cost by `delta` at temperature `t` with the
+//! probability `1 / (1 + exp(delta / t))`. This probability never exceeds 0.5, so only target
+//! acceptance rates of worsening moves below 0.5 can be reached.
+
+use layout_optimization_common::LayoutPermutator;
+
+use argmin::core::KV;
+use serde::Deserialize;
+use std::{
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Parameters of the automatic initialization of the temperature.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AutoInitTemperature {
+    /// Initial acceptance rate of worsening moves to aim for (between 0 and 0.5)
+    pub target_acceptance: f64,
+
+    /// Number of random neighbors of the initial layout to sample
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+}
+
+fn default_samples() -> usize {
+    100
+}
+
+/// The cost of the last evaluated layout, shared between the problem and the observers.
+#[derive(Clone, Debug, Default)]
+pub struct LastCost(Arc<AtomicU64>);
+
+impl LastCost {
+    pub fn set(&self, cost: f64) {
+        self.0.store(cost.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Acceptance statistics of the moves proposed within an interval of iterations.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AcceptanceStats {
+    /// Iteration at the end of the interval
+    pub iter: u64,
+    /// Temperature at the end of the interval
+    pub temperature: f64,
+    /// Number of proposed moves
+    pub proposed: u64,
+    /// Number of proposed moves that were worse than the current layout
+    pub worse_proposed: u64,
+    /// Number of accepted moves that were worse than the current layout
+    pub worse_accepted: u64,
+    /// Mean of the cost differences of the proposed moves
+    pub delta_mean: f64,
+    /// Variance of the cost differences of the proposed moves
+    pub delta_variance: f64,
+    /// Cost of the current layout
+    pub current_cost: f64,
+    /// Cost of the best layout so far
+    pub best_cost: f64,
+}
+
+impl AcceptanceStats {
+    /// Header of the CSV rows written by [`AcceptanceStats::to_csv`].
+    pub const CSV_HEADER: &'static str = "process,iter,temperature,proposed,worse_proposed,\
+        worse_accepted,worse_acceptance_rate,delta_mean,delta_variance,current_cost,best_cost";
+
+    /// Fraction of the worsening moves that got accepted (if there were any).
+    pub fn worse_acceptance_rate(&self) -> Option<f64> {
+        match self.worse_proposed {
+            0 => None,
+            n => Some(self.worse_accepted as f64 / n as f64),
+        }
+    }
+
+    /// The statistics as a CSV row (without line break).
+    pub fn to_csv(&self, process_name: &str) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            process_name,
+            self.iter,
+            self.temperature,
+            self.proposed,
+            self.worse_proposed,
+            self.worse_accepted,
+            self.worse_acceptance_rate()
+                .map(|r| r.to_string())
+                .unwrap_or_default(),
+            self.delta_mean,
+            self.delta_variance,
+            self.current_cost,
+            self.best_cost,
+        )
+    }
+
+    /// Adds the statistics (that are not part of the solver's state) to a key-value store.
+    pub fn push_to_kv(&self, kv: &mut KV) {
+        kv.push("stats_proposed", Rc::new(self.proposed))
+            .push("stats_worse_proposed", Rc::new(self.worse_proposed))
+            .push("stats_worse_accepted", Rc::new(self.worse_accepted))
+            .push("stats_delta_mean", Rc::new(self.delta_mean))
+            .push("stats_delta_variance", Rc::new(self.delta_variance));
+        if let Some(rate) = self.worse_acceptance_rate() {
+            kv.push("stats_worse_acceptance_rate", Rc::new(rate));
+        }
+    }
+}
+
+impl fmt::Display for AcceptanceStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "temp: {:.5}°, worse accepted: {}/{} ({}), delta: {:.2} ± {:.2}, current: {:.1}, best: {:.1}",
+            self.temperature,
+            self.worse_accepted,
+            self.worse_proposed,
+            self.worse_acceptance_rate()
+                .map(|r| format!("{:.1}%", 100.0 * r))
+                .unwrap_or_else(|| "-".to_string()),
+            self.delta_mean,
+            self.delta_variance.sqrt(),
+            self.current_cost,
+            self.best_cost,
+        )
+    }
+}
+
+/// Accumulates the proposed moves of an interval of iterations.
+#[derive(Clone, Debug, Default)]
+pub struct AcceptanceCounter {
+    proposed: u64,
+    worse_proposed: u64,
+    worse_accepted: u64,
+    delta_sum: f64,
+    delta_squared_sum: f64,
+}
+
+impl AcceptanceCounter {
+    /// Records a proposed move changing the cost by `delta`.
+    pub fn record(&mut self, delta: f64, accepted: bool) {
+        self.proposed += 1;
+        self.delta_sum += delta;
+        self.delta_squared_sum += delta * delta;
+        if delta > 0.0 {
+            self.worse_proposed += 1;
+            if accepted {
+                self.worse_accepted += 1;
+            }
+        }
+    }
+
+    /// Returns the statistics of the recorded moves and starts a new interval.
+    pub fn finish_interval(
+        &mut self,
+        iter: u64,
+        temperature: f64,
+        current_cost: f64,
+        best_cost: f64,
+    ) -> AcceptanceStats {
+        let n = self.proposed.max(1) as f64;
+        let delta_mean = self.delta_sum / n;
+        let stats = AcceptanceStats {
+            iter,
+            temperature,
+            proposed: self.proposed,
+            worse_proposed: self.worse_proposed,
+            worse_accepted: self.worse_accepted,
+            delta_mean,
+            delta_variance: (self.delta_squared_sum / n - delta_mean * delta_mean).max(0.0),
+            current_cost,
+            best_cost,
+        };
+        *self = Self::default();
+
+        stats
+    }
+}
+
+/// Cost differences between `samples` random neighbors of the initial layout and the initial
+/// layout itself.
+///
+/// `cost` returns `None` for infeasible layouts. Their (prohibitively high) costs would
+/// dominate the temperature, so infeasible neighbors are skipped. If the initial layout is
+/// infeasible itself, there are no deltas at all.
+pub fn neighbor_deltas<F: Fn(&[usize]) -> Option<f64>>(
+    initial_indices: &[usize],
+    permutator: &LayoutPermutator,
+    key_switches: usize,
+    samples: usize,
+    cost: F,
+) -> Vec<f64> {
+    let initial_cost = match cost(initial_indices) {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    (0..samples)
+        .filter_map(|_| cost(&permutator.perform_n_swaps(initial_indices, key_switches)))
+        .map(|c| c - initial_cost)
+        .collect()
+}
+
+/// Expected acceptance rate of the worsening moves among `deltas` at the given temperature.
+pub fn worse_acceptance_rate(deltas: &[f64], temperature: f64) -> f64 {
+    let (sum, n) = deltas
+        .iter()
+        .filter(|d| **d > 0.0)
+        .fold((0.0, 0), |(sum, n), d| {
+            (sum + 1.0 / (1.0 + (d / temperature).exp()), n + 1)
+        });
+    match n {
+        0 => 1.0,
+        n => sum / n as f64,
+    }
+}
+
+/// The temperature at which the worsening moves among `deltas` get accepted with the target
+/// rate. Returns `None` if there are no (finite) worsening moves or the target is not within
+/// (0, 0.5).
+pub fn temperature_for_acceptance_rate(deltas: &[f64], target: f64) -> Option<f64> {
+    let worse: Vec<f64> = deltas
+        .iter()
+        .cloned()
+        .filter(|d| *d > 0.0 && d.is_finite())
+        .collect();
+    if worse.is_empty() || target <= 0.0 || target >= 0.5 {
+        return None;
+    }
+
+    // the rate increases with the temperature, so bisect (geometrically) between a temperature
+    // that is too low and one that is high enough
+    let max_delta = worse.iter().cloned().fold(0.0, f64::max);
+    let mut low = max_delta * 1e-9;
+    let mut high = max_delta;
+    while worse_acceptance_rate(&worse, high) < target {
+        low = high;
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let mid = (low * high).sqrt();
+        if worse_acceptance_rate(&worse, mid) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    Some(high)
+}
@@ -1,3 +1,4 @@
+pub mod acceptance;
 pub mod optimization;
 
 #[cfg(test)]
@@ -1,7 +1,13 @@
 use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
 use layout_evaluation::{cache::Cache, evaluation::Evaluator};
 
-use layout_optimization_common::LayoutPermutator;
+use crate::acceptance::{self, AcceptanceCounter, AutoInitTemperature, LastCost};
+
+use layout_optimization_common::{
+    blacklist::{Blacklist, INFEASIBLE_COST},
+    constraints::ConstraintSet,
+    LayoutPermutator,
+};
 
 use anyhow::Result;
 use colored::Colorize;
@@ -32,6 +38,19 @@ pub struct Parameters {
     // Parameters for the [Executor].
     /// Set maximum number of iterations (defaults to `std::u64::MAX`)
     pub max_iters: u64,
+
+    /// Determine the initial temperature from a burn-in (overrides `init_temp`).
+    #[serde(default)]
+    pub auto_init_temperature: Option<AutoInitTemperature>,
+
+    /// Collect acceptance statistics over intervals of this many iterations (defaults to 100 if
+    /// only `stats_file` is given).
+    #[serde(default)]
+    pub stats_interval: Option<u64>,
+
+    /// Append the acceptance statistics to this CSV file.
+    #[serde(default)]
+    pub stats_file: Option<String>,
 }
 
 impl Default for Parameters {
@@ -43,6 +62,9 @@ impl Default for Parameters {
             stall_accepted: 5000,
             // Parameters for the [Executor].
             max_iters: 100_000,
+            auto_init_temperature: None,
+            stats_interval: None,
+            stats_file: None,
         }
     }
 }
@@ -72,25 +94,68 @@ pub struct AnnealingStruct {
     layout_generator: Box<dyn LayoutGenerator>,
     key_switches: usize,
     result_cache: Option<Cache<f64>>,
+    blacklist: Option<Arc<Blacklist>>,
+    last_cost: LastCost,
 }
 
-impl CostFunction for AnnealingStruct {
-    type Param = Vec<usize>;
-    type Output = f64;
+impl AnnealingStruct {
+    fn evaluate(&self, param: &[usize]) -> f64 {
+        if let Some(excess) = self.permutator.hand_balance_violation(param) {
+            return INFEASIBLE_COST * (1.0 + excess);
+        }
+        if self.permutator.violates_symbol_groups(param) {
+            return INFEASIBLE_COST;
+        }
 
-    /// Evaluate param (= the layout-vector).
-    fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
         let evaluate_layout_str = |layout_str: &str| -> f64 {
             let l = self.layout_generator.generate(layout_str).unwrap();
+            if let Some(cost) = self.blacklist.as_ref().and_then(|b| b.infeasible_cost(&l)) {
+                return cost;
+            }
             self.evaluator.evaluate_layout(&l).total_cost()
         };
 
         let layout_string = self.permutator.generate_string(param);
-        let evaluation_result = match &self.result_cache {
+        match &self.result_cache {
             Some(result_cache) => result_cache
                 .get_or_insert_with(&layout_string, || evaluate_layout_str(&layout_string)),
             None => evaluate_layout_str(&layout_string),
-        };
+        }
+    }
+
+    /// The cost of the layout, or `None` if it is infeasible (forbidden by the blacklist or
+    /// violating the hand balance constraint or a symbol group).
+    fn feasible_cost(&self, param: &[usize]) -> Option<f64> {
+        if self.permutator.hand_balance_violation(param).is_some()
+            || self.permutator.violates_symbol_groups(param)
+        {
+            return None;
+        }
+
+        let l = self
+            .layout_generator
+            .generate(&self.permutator.generate_string(param))
+            .unwrap();
+        if self.blacklist.as_ref().map_or(false, |b| b.forbids(&l)) {
+            return None;
+        }
+
+        Some(self.evaluator.evaluate_layout(&l).total_cost())
+    }
+}
+
+impl CostFunction for AnnealingStruct {
+    type Param = Vec<usize>;
+    type Output = f64;
+
+    /// Evaluate param (= the layout-vector).
+    /// Layouts forbidden by the blacklist are infeasible and get a prohibitively high cost.
+    /// The same holds for layouts violating the hand balance constraint, whose cost increases
+    /// with the deviation from the allowed hand split, and for layouts violating a symbol group.
+    fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
+        let evaluation_result = self.evaluate(param);
+        // remember the cost of the proposed layout for the acceptance statistics
+        self.last_cost.set(evaluation_result);
 
         Ok(evaluation_result)
     }
@@ -213,6 +278,73 @@ impl Observe<SaIterState> for IterationObserver {
     }
 }
 
+/// An observer that collects the acceptance statistics over intervals of iterations. It logs
+/// them, appends them to a file and passes them to a progress observer (as additional entries
+/// of the key-value store).
+struct AcceptanceObserver {
+    id: String,
+    interval: u64,
+    last_cost: LastCost,
+    counter: AcceptanceCounter,
+    log_stats: bool,
+    stats_file: Option<String>,
+    progress: Option<CustomObserver>,
+}
+
+impl Observe<SaIterState> for AcceptanceObserver {
+    fn observe_iter(&mut self, state: &SaIterState, kv: &KV) -> Result<(), Error> {
+        // the observers are called before the iteration counter is incremented
+        let iters_done = state.iter + 1;
+        let accepted = kv
+            .kv
+            .iter()
+            .any(|(key, value)| *key == "acc" && value.to_string() == "true");
+        self.counter
+            .record(self.last_cost.get() - state.prev_cost, accepted);
+
+        let mut stats = None;
+        if iters_done % self.interval == 0 {
+            let temperature = kv
+                .kv
+                .iter()
+                .find(|(key, _)| *key == "t")
+                .and_then(|(_, value)| value.to_string().parse().ok())
+                .unwrap_or(f64::NAN);
+            stats = Some(self.counter.finish_interval(
+                iters_done,
+                temperature,
+                state.cost,
+                state.best_cost,
+            ));
+        }
+
+        if let Some(stats) = &stats {
+            if self.log_stats {
+                log::info!("{} {}", format!("{}:", self.id).yellow().bold(), stats);
+            }
+            if let Some(filename) = &self.stats_file {
+                layout_evaluation::atomic_file::append_durable(
+                    filename,
+                    format!("{}\n", stats.to_csv(&self.id)),
+                )
+                .unwrap_or_else(|e| {
+                    log::error!("Could not append statistics to {}: {}", filename, e)
+                });
+            }
+        }
+
+        match (&mut self.progress, stats) {
+            (Some(progress), Some(stats)) => {
+                let mut kv = kv.clone();
+                stats.push_to_kv(&mut kv);
+                progress.observe_iter(state, &kv)
+            }
+            (Some(progress), None) => progress.observe_iter(state, kv),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
 /// Calculates the mean of a vec containing f64-values.
 fn mean(list: &[f64]) -> f64 {
     let sum: f64 = list.iter().sum();
@@ -228,7 +360,7 @@ fn get_cost_sd(
     initial_indices: &[usize],
     evaluator: Arc<Evaluator>,
     permutator: &LayoutPermutator,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     key_pair_switches: usize,
 ) -> f64 {
     const USED_NEIGHBORS: u16 = 100;
@@ -264,14 +396,23 @@ pub fn optimize(
     params: &Parameters,
     layout_str: &str,
     fixed_characters: &str,
-    layout_generator: &Box<dyn LayoutGenerator>,
+    layout_generator: &dyn LayoutGenerator,
     start_with_layout: bool,
     evaluator: &Evaluator,
     log_everything: bool,
     result_cache: Option<Cache<f64>>,
+    blacklist: Option<Arc<Blacklist>>,
+    constraints: Option<&ConstraintSet>,
     custom_observer: Option<CustomObserver>,
 ) -> (String, Layout) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    let pm = match constraints {
+        Some(constraints) => constraints
+            .permutator(layout_str, fixed_characters, layout_generator, evaluator)
+            .expect("Could not apply the constraints"),
+        None => LayoutPermutator::new(layout_str, fixed_characters)
+            .with_keycap_groups(&layout_generator.keycap_groups())
+            .expect("Could not apply keycap groups"),
+    };
     // Get initial Layout.
     let initial_indices = match start_with_layout {
         true => pm.get_permutable_indices(),
@@ -289,7 +430,55 @@ pub fn optimize(
     }
     println!("Average init_temp: {}", mean(&init_temp_vec)); */
 
-    let init_temp = match params.init_temp {
+    let problem = AnnealingStruct {
+        evaluator: Arc::new(evaluator.clone()),
+        permutator: pm.clone(),
+        layout_generator: layout_generator.clone_box(),
+        key_switches: params.key_switches,
+        result_cache,
+        blacklist,
+        last_cost: LastCost::default(),
+    };
+    let last_cost = problem.last_cost.clone();
+
+    let burn_in_temp = params.auto_init_temperature.as_ref().and_then(|auto| {
+        log::info!(
+            "{} Sampling {} neighbors for the initial temperature",
+            format!("{}:", process_name).yellow().bold(),
+            auto.samples,
+        );
+        let deltas = acceptance::neighbor_deltas(
+            &initial_indices,
+            &pm,
+            params.key_switches,
+            auto.samples,
+            |param| problem.feasible_cost(param),
+        );
+        log::info!(
+            "{} {} of {} sampled neighbors are feasible",
+            format!("{}:", process_name).yellow().bold(),
+            deltas.len(),
+            auto.samples,
+        );
+        let init_temp =
+            acceptance::temperature_for_acceptance_rate(&deltas, auto.target_acceptance);
+        match init_temp {
+            Some(t) => log::info!(
+                "{} Initial temperature = {}° (acceptance rate of worse neighbors: {:.1}%)",
+                format!("{}:", process_name).yellow().bold(),
+                t,
+                100.0 * auto.target_acceptance,
+            ),
+            None => log::warn!(
+                "{} Could not reach an acceptance rate of {} with the sampled neighbors",
+                format!("{}:", process_name).yellow().bold(),
+                auto.target_acceptance,
+            ),
+        }
+        init_temp
+    });
+
+    let init_temp = match burn_in_temp.or(params.init_temp) {
         Some(t) => t,
         None => {
             log::info!(
@@ -311,13 +500,6 @@ pub fn optimize(
             init_temp
         }
     };
-    let problem = AnnealingStruct {
-        evaluator: Arc::new(evaluator.clone()),
-        permutator: pm.clone(),
-        layout_generator: layout_generator.clone(),
-        key_switches: params.key_switches,
-        result_cache,
-    };
 
     // Create new SA solver with some parameters (see docs for details)
     // This essentially just prepares the SA solver. It is not run yet, nor does it know anything about the problem it is about to solve.
@@ -342,8 +524,23 @@ pub fn optimize(
                 .max_iters(params.max_iters)
         })
         .timer(false);
+    let acceptance_observer = |progress: Option<CustomObserver>| AcceptanceObserver {
+        id: process_name.to_string(),
+        interval: params.stats_interval.unwrap_or(100).max(1),
+        last_cost: last_cost.clone(),
+        counter: AcceptanceCounter::default(),
+        log_stats: progress.is_none(),
+        stats_file: params.stats_file.clone(),
+        progress,
+    };
+    let collect_stats = params.stats_interval.is_some() || params.stats_file.is_some();
     match custom_observer {
-        // If a custom Observer was supplied, only use that Observer.
+        // If a custom Observer was supplied, only use that Observer (which also receives the
+        // acceptance statistics).
+        Some(observer) if collect_stats => {
+            executor =
+                executor.add_observer(acceptance_observer(Some(observer)), ObserverMode::Always);
+        }
         Some(observer) => {
             executor = executor.add_observer(observer, ObserverMode::Always);
         }
@@ -367,6 +564,9 @@ pub fn optimize(
             executor = executor
                 .add_observer(best_observer, ObserverMode::NewBest)
                 .add_observer(iter_observer, iter_observer_mode);
+            if collect_stats {
+                executor = executor.add_observer(acceptance_observer(None), ObserverMode::Always);
+            }
         }
     }
 
@@ -378,6 +578,14 @@ pub fn optimize(
     );
     let res = executor.run().unwrap();
 
+    if let Some(hand_balance) = pm.hand_balance() {
+        log::info!(
+            "{} {}",
+            format!("{}:", process_name).yellow().bold(),
+            hand_balance
+        );
+    }
+
     let best_layout_param = res.state().get_best_param().unwrap();
     let best_layout_str = pm.generate_string(best_layout_param);
     let best_layout = layout_generator.generate(&best_layout_str).unwrap();
@@ -0,0 +1,219 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+use layout_optimization_common::{
+    blacklist::INFEASIBLE_COST,
+    constraints::ConstraintSet,
+    hand_balance::{HandBalance, HandBalanceMode, HandBalanceParameters},
+    LayoutPermutator,
+};
+use layout_optimization_sa::{
+    acceptance::{self, AcceptanceStats, AutoInitTemperature},
+    optimization::{optimize, Parameters},
+};
+
+use std::{fs, sync::Arc};
+
+const LAYOUT_CONFIG: &str = "../../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+fn layout_generator() -> NeoLayoutGenerator {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+}
+
+fn evaluator() -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(TEXT).unwrap(),
+        Bigrams::from_text(TEXT).unwrap(),
+        Trigrams::from_text(TEXT).unwrap(),
+        eval_params.ngram_mapper,
+    );
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+#[test]
+fn temperature_for_identical_deltas() {
+    let deltas = [-3.0, 2.0, 2.0, 2.0];
+    let t = acceptance::temperature_for_acceptance_rate(&deltas, 0.25).unwrap();
+
+    // 1 / (1 + exp(2 / t)) = 0.25  <=>  t = 2 / ln(3)
+    assert!((t - 2.0 / 3f64.ln()).abs() < 1e-9, "{}", t);
+    assert!((acceptance::worse_acceptance_rate(&deltas, t) - 0.25).abs() < 1e-9);
+
+    assert_eq!(
+        acceptance::temperature_for_acceptance_rate(&deltas, 0.5),
+        None
+    );
+    assert_eq!(
+        acceptance::temperature_for_acceptance_rate(&[-1.0], 0.25),
+        None
+    );
+}
+
+#[test]
+fn burn_in_reaches_target_acceptance_rate() {
+    let layout_generator = layout_generator();
+    let evaluator = evaluator();
+    let permutator = LayoutPermutator::new(LAYOUT, "");
+    let initial_indices = permutator.get_permutable_indices();
+    let cost = |param: &[usize]| {
+        let layout = layout_generator
+            .generate(&permutator.generate_string(param))
+            .unwrap();
+        Some(evaluator.evaluate_layout(&layout).total_cost())
+    };
+    let target = 0.3;
+
+    let burn_in = acceptance::neighbor_deltas(&initial_indices, &permutator, 1, 300, cost);
+    let t = acceptance::temperature_for_acceptance_rate(&burn_in, target).unwrap();
+    assert!((acceptance::worse_acceptance_rate(&burn_in, t) - target).abs() < 1e-6);
+
+    // the temperature generalizes to other neighbors of the initial layout
+    let other = acceptance::neighbor_deltas(&initial_indices, &permutator, 1, 300, cost);
+    let rate = acceptance::worse_acceptance_rate(&other, t);
+    assert!((rate - target).abs() < 0.05, "{}", rate);
+}
+
+#[test]
+fn optimize_appends_statistics() {
+    let layout_generator = layout_generator();
+    let evaluator = evaluator();
+    let stats_file = std::env::temp_dir().join(format!(
+        "layout_optimization_sa_stats_{}.csv",
+        std::process::id()
+    ));
+    let params = Parameters {
+        init_temp: Some(1.0),
+        key_switches: 1,
+        stall_accepted: 100,
+        max_iters: 20,
+        stats_interval: Some(5),
+        stats_file: Some(stats_file.to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+
+    optimize(
+        "Test",
+        &params,
+        LAYOUT,
+        "",
+        &layout_generator,
+        true,
+        &evaluator,
+        false,
+        None,
+        None,
+        None,
+        None,
+    );
+
+    let content = fs::read_to_string(&stats_file).unwrap();
+    fs::remove_file(&stats_file).unwrap();
+    let rows: Vec<Vec<&str>> = content.lines().map(|l| l.split(',').collect()).collect();
+    let columns = AcceptanceStats::CSV_HEADER.split(',').count();
+
+    assert_eq!(rows.len(), 4);
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(row.len(), columns);
+        assert_eq!(row[0], "Test");
+        assert_eq!(row[1], (5 * (i + 1)).to_string());
+        // five proposed moves per interval
+        assert_eq!(row[3], "5");
+    }
+}
+
+#[test]
+fn burn_in_skips_infeasible_neighbors() {
+    let layout_generator = layout_generator();
+    let evaluator = evaluator();
+
+    // a tight hand balance constraint around the split of the initial layout rejects most
+    // neighbors that move a symbol to the other hand
+    let layout = layout_generator.generate(LAYOUT).unwrap();
+    let mut hand_balance = HandBalanceParameters {
+        target: 0.5,
+        margin: 1e-4,
+        mode: HandBalanceMode::Reject,
+    };
+    hand_balance.target = HandBalance::new(&hand_balance, LAYOUT, &layout, &evaluator)
+        .left_fraction(LAYOUT)
+        .unwrap();
+    let constraints = ConstraintSet {
+        hand_balance: Some(hand_balance),
+        ..Default::default()
+    };
+
+    let permutator = constraints
+        .permutator(LAYOUT, "", &layout_generator, &evaluator)
+        .unwrap();
+    let initial_indices = permutator.get_permutable_indices();
+    assert!(permutator
+        .hand_balance_violation(&initial_indices)
+        .is_none());
+    let rejected = (0..100)
+        .map(|_| permutator.perform_n_swaps(&initial_indices, 1))
+        .filter(|p| permutator.hand_balance_violation(p).is_some())
+        .count();
+    assert!(rejected > 0);
+
+    let stats_file = std::env::temp_dir().join(format!(
+        "layout_optimization_sa_burn_in_{}.csv",
+        std::process::id()
+    ));
+    let params = Parameters {
+        auto_init_temperature: Some(AutoInitTemperature {
+            target_acceptance: 0.3,
+            samples: 50,
+        }),
+        key_switches: 1,
+        stall_accepted: 100,
+        max_iters: 5,
+        stats_interval: Some(5),
+        stats_file: Some(stats_file.to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    optimize(
+        "Test",
+        &params,
+        LAYOUT,
+        "",
+        &layout_generator,
+        true,
+        &evaluator,
+        false,
+        None,
+        None,
+        Some(&constraints),
+        None,
+    );
+
+    let content = fs::read_to_string(&stats_file).unwrap();
+    fs::remove_file(&stats_file).unwrap();
+    let temperature: f64 = content
+        .lines()
+        .next()
+        .unwrap()
+        .split(',')
+        .nth(2)
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    // the temperature is derived from the feasible neighbors only
+    assert!(temperature > 0.0);
+    assert!(temperature < 1e-3 * INFEASIBLE_COST, "{}", temperature);
+}
@@ -0,0 +1,108 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{read_ngrams_dir, Bigrams, Trigrams, Unigrams},
+    results::MetricType,
+};
+use layout_optimization_sa::optimization::{optimize, Parameters};
+
+use std::{fs, sync::Arc};
+
+const LAYOUT_CONFIG: &str = "../../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+fn layout_generator() -> NeoLayoutGenerator {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard)
+}
+
+/// Build an evaluator from ngram files of `TEXT`, leaving out the file of the given order.
+fn evaluator(missing_file: &str) -> Evaluator {
+    let dir = std::env::temp_dir().join(format!(
+        "layout_optimization_sa_missing_{}_{}",
+        missing_file,
+        std::process::id()
+    ));
+    Unigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("1-grams.txt"))
+        .unwrap();
+    Bigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("2-grams.txt"))
+        .unwrap();
+    Trigrams::from_text(TEXT)
+        .unwrap()
+        .save_frequencies(dir.join("3-grams.txt"))
+        .unwrap();
+    fs::remove_file(dir.join(missing_file)).unwrap();
+
+    let (unigrams, bigrams, trigrams, missing) = read_ngrams_dir(dir.to_str().unwrap()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
+
+    Evaluator::default(Box::new(mapper))
+        .default_metrics(&eval_params.metrics)
+        .skip_metric_types(&missing)
+}
+
+#[test]
+fn optimize_with_missing_ngram_files() {
+    let layout_generator = layout_generator();
+    let params = Parameters {
+        init_temp: Some(1.0),
+        key_switches: 1,
+        stall_accepted: 100,
+        max_iters: 20,
+        ..Default::default()
+    };
+
+    for (missing_file, metric_type) in [
+        ("1-grams.txt", MetricType::Unigram),
+        ("2-grams.txt", MetricType::Bigram),
+        ("3-grams.txt", MetricType::Trigram),
+    ] {
+        let evaluator = evaluator(missing_file);
+        let initial_cost = evaluator
+            .evaluate_layout(&layout_generator.generate(LAYOUT).unwrap())
+            .total_cost();
+
+        let (layout_str, layout) = optimize(
+            "Test",
+            &params,
+            LAYOUT,
+            "",
+            &layout_generator,
+            true,
+            &evaluator,
+            false,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let mut chars: Vec<char> = layout_str.chars().collect();
+        let mut expected: Vec<char> = LAYOUT.chars().collect();
+        chars.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(chars, expected);
+
+        let result = evaluator.evaluate_layout(&layout);
+        assert_eq!(result.skipped_metric_types(), [metric_type]);
+        assert!(result.total_cost().is_finite());
+        assert!(result.total_cost() <= initial_cost);
+    }
+}
@@ -0,0 +1,86 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+use layout_optimization_common::constraints::{ConstraintSet, Violation};
+use layout_optimization_sa::optimization::{optimize, Parameters};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../../config/keyboard/standard.yml";
+const EVALUATION_PARAMETERS: &str = "../../config/evaluation/default.yml";
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// "x" starts on the left hand, "q", "j", and "z" on the right one
+const CONSTRAINTS: &str = "
+same_hand: [qjxz]
+preserve_relative_order: ['üöä']
+";
+
+fn evaluator() -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(TEXT).unwrap(),
+        Bigrams::from_text(TEXT).unwrap(),
+        Trigrams::from_text(TEXT).unwrap(),
+        eval_params.ngram_mapper,
+    );
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+#[test]
+fn optimize_with_same_hand_group() {
+    let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let evaluator = evaluator();
+
+    let constraints = ConstraintSet::from_yaml_str(CONSTRAINTS).unwrap();
+    let initial = layout_generator.generate(LAYOUT).unwrap();
+    assert!(matches!(
+        constraints
+            .check(LAYOUT, &initial, None)
+            .unwrap()
+            .as_slice(),
+        [Violation::SymbolGroup { .. }]
+    ));
+
+    let params = Parameters {
+        init_temp: Some(1.0),
+        key_switches: 2,
+        stall_accepted: 100,
+        max_iters: 200,
+        ..Default::default()
+    };
+    for start_with_layout in [true, false] {
+        let (layout_str, layout) = optimize(
+            "Test",
+            &params,
+            LAYOUT,
+            "",
+            &layout_generator,
+            start_with_layout,
+            &evaluator,
+            false,
+            None,
+            None,
+            Some(&constraints),
+            None,
+        );
+
+        assert_eq!(
+            constraints.check(&layout_str, &layout, None).unwrap(),
+            vec![]
+        );
+        assert!(evaluator.evaluate_layout(&layout).total_cost().is_finite());
+    }
+}
@@ -0,0 +1,125 @@
+use keyboard_layout::{
+    config::LayoutConfig,
+    key::MatrixPosition,
+    keyboard::Keyboard,
+    layout_generator::LayoutGenerator,
+    neo_layout_generator::{LayoutError, NeoLayoutGenerator},
+};
+use layout_evaluation::{
+    config::EvaluationParameters,
+    evaluation::Evaluator,
+    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+use layout_optimization_common::constraints::ConstraintSet;
+use layout_optimization_sa::optimization::{optimize, Parameters};
+
+use std::sync::Arc;
+
+const LAYOUT_CONFIG: &str = "../../config/keyboard/crkbd.yml";
+const EVALUATION_PARAMETERS: &str = "../../config/evaluation/default.yml";
+/// The space key of the right thumb is the last key of the layout string
+const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j ";
+const TEXT: &str = "Der schnelle braune Fuchs springt über den faulen Hund. \
+                    The quick brown fox jumps over the lazy dog.";
+
+/// The space key of the right thumb
+const THUMB: MatrixPosition = MatrixPosition(13, 4);
+
+/// Pins space to the upper right key
+const CONSTRAINTS: &str = "
+pins:
+  - symbol: ' '
+    position: [18, 1]
+";
+
+/// Layout config of the crkbd with the space key of the right thumb unlocked
+fn layout_config() -> LayoutConfig {
+    let mut layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG).unwrap();
+    layout_config.base_layout.unlocked_positions = vec![THUMB];
+
+    layout_config
+}
+
+fn evaluator() -> Evaluator {
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap();
+    let mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(TEXT).unwrap(),
+        Bigrams::from_text(TEXT).unwrap(),
+        Trigrams::from_text(TEXT).unwrap(),
+        eval_params.ngram_mapper,
+    );
+
+    Evaluator::default(Box::new(mapper)).default_metrics(&eval_params.metrics)
+}
+
+#[test]
+fn optimize_with_unlocked_thumb() {
+    let layout_config = layout_config();
+    layout_config.validate().unwrap();
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let evaluator = evaluator();
+
+    let constraints = ConstraintSet::from_yaml_str(CONSTRAINTS).unwrap();
+
+    // space and 'ß' swap their keys when applying the pin
+    let initial_str: String = LAYOUT.replace('ß', "_").replace(' ', "ß").replace('_', " ");
+    let initial = layout_generator.generate(&initial_str).unwrap();
+    let eszett = initial.get_layerkey_for_symbol(&'ß').unwrap();
+    assert!(initial.geometry().is_thumb(eszett.key_index));
+    let initial_cost = evaluator.evaluate_layout(&initial).total_cost();
+    assert!(initial_cost.is_finite());
+
+    let params = Parameters {
+        init_temp: Some(1.0),
+        key_switches: 1,
+        stall_accepted: 100,
+        max_iters: 50,
+        ..Default::default()
+    };
+    let (layout_str, layout) = optimize(
+        "Test",
+        &params,
+        LAYOUT,
+        "",
+        &layout_generator,
+        true,
+        &evaluator,
+        false,
+        None,
+        None,
+        Some(&constraints),
+        None,
+    );
+
+    let mut chars: Vec<char> = layout_str.chars().collect();
+    let mut expected: Vec<char> = LAYOUT.chars().collect();
+    chars.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(chars, expected);
+
+    // space stays pinned, the thumb holds a permuted symbol
+    assert_eq!(layout_str.chars().nth(11), Some(' '));
+    let thumb_symbol = layout_str.chars().last().unwrap();
+    let thumb_key = layout.get_layerkey_for_symbol(&thumb_symbol).unwrap();
+    assert!(layout.geometry().is_thumb(thumb_key.key_index));
+    assert_eq!(thumb_key.key.matrix_position, THUMB);
+
+    let cost = evaluator.evaluate_layout(&layout).total_cost();
+    assert!(cost.is_finite());
+    assert!(cost <= initial_cost);
+}
+
+#[test]
+fn modifiers_can_not_be_unlocked() {
+    let mut layout_config = layout_config();
+    // the hold modifier of the fourth layer
+    layout_config.base_layout.unlocked_positions = vec![MatrixPosition(14, 4)];
+
+    let err = layout_config.validate().unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<LayoutError>(),
+        Some(LayoutError::UnlockedModifier(MatrixPosition(14, 4)))
+    ));
+}
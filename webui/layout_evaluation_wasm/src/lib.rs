@@ -3,20 +3,22 @@ mod utils;
 use argmin::core::{observers::Observe, Error, State, KV};
 use genevo::prelude::*;
 use instant::Instant;
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
 use serde::Serialize;
-use std::{str::FromStr, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 use wasm_bindgen::prelude::*;
 
 use keyboard_layout::{
     config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
-    neo_layout_generator::NeoLayoutGenerator,
+    neo_layout_generator::{BaseLayoutError, NeoLayoutGenerator},
 };
 
 use layout_evaluation::{
     cache::Cache,
     config::EvaluationParameters,
     evaluation::Evaluator,
-    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    fingerprint,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, MappingStatistics},
     ngrams::{Bigrams, Trigrams, Unigrams},
     results::EvaluationResult,
 };
@@ -47,20 +49,47 @@ struct LayoutEvaluation {
     printed: Option<String>,
     plot: Option<String>,
     layout: Option<String>,
+    /// Fingerprint of the configuration the evaluation was performed with
+    fingerprint: Option<String>,
+    /// Weight of the symbols that can not be generated by the layout
+    missing_symbols: HashMap<char, f64>,
+    /// How much of the ngrams' weight is mapped to the layout
+    mapping_statistics: Option<MappingStatistics>,
 }
 
 impl From<EvaluationResult> for LayoutEvaluation {
     fn from(res: EvaluationResult) -> Self {
         Self {
             total_cost: res.total_cost(),
+            missing_symbols: res
+                .missing_symbols()
+                .map(|m| m.iter().map(|(c, w)| (*c, *w)).collect())
+                .unwrap_or_default(),
             details: res,
             printed: None,
             plot: None,
             layout: None,
+            fingerprint: None,
+            mapping_statistics: None,
         }
     }
 }
 
+/// Create a JS `Error` with the given name, so that the frontend can distinguish the cause.
+fn js_error(name: &str, message: &str) -> JsValue {
+    let err = js_sys::Error::new(message);
+    err.set_name(name);
+    err.into()
+}
+
+/// Random number generator for reproducible results given a seed.
+///
+/// ChaCha8 is used explicitly (instead of e.g. `StdRng`) because its output is guaranteed to
+/// be the same on all platforms and in all versions of the crate.
+fn seeded_rng(seed: u32) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed as u64)
+}
+
 #[wasm_bindgen]
 pub struct LayoutPlotter {
     layout_generator: NeoLayoutGenerator,
@@ -71,8 +100,16 @@ impl LayoutPlotter {
     pub fn new(layout_cfg_str: &str) -> Result<LayoutPlotter, JsValue> {
         utils::set_panic_hook();
 
-        let layout_cfg = LayoutConfig::from_str(layout_cfg_str)
+        let layout_cfg: LayoutConfig = serde_yaml::from_str(layout_cfg_str)
             .map_err(|e| format!("Could not read layout config: {:?}", e))?;
+        // mismatches of the base layout are thrown as `BaseLayoutError`, so that the frontend can
+        // point to their location
+        layout_cfg
+            .validate()
+            .map_err(|e| match e.downcast_ref::<BaseLayoutError>() {
+                Some(err) => js_error("BaseLayoutError", &err.to_string()),
+                None => JsValue::from(format!("Could not read layout config: {:?}", e)),
+            })?;
 
         let keyboard = Arc::new(Keyboard::from_yaml_object(layout_cfg.keyboard));
 
@@ -89,11 +126,55 @@ impl LayoutPlotter {
             .map_err(|e| format!("Could not plot the layout: {:?}", e))?;
         Ok(layout.plot_layer(layer))
     }
+
+    /// Generate a random layout. The same seed always results in the same layout.
+    pub fn random_layout(&self, seed: u32) -> String {
+        // sort the keys for independence of the order in the layout generator's hashmap
+        let mut keys = self.layout_generator.permutable_keys();
+        keys.sort_unstable();
+        let layout_str: String = keys.into_iter().collect();
+
+        let permutator = LayoutPermutator::new(&layout_str, "")
+            .with_keycap_groups(&self.layout_generator.keycap_groups())
+            .expect("Could not apply keycap groups");
+        let permutation = permutator.generate_random_with_rng(&mut seeded_rng(seed));
+        permutator.generate_string(&permutation)
+    }
+
+    /// Perform `n_swaps` random swaps of keys in the given layout. The same seed always results
+    /// in the same swaps.
+    ///
+    /// Throws an `InvalidLayoutError` if the layout is not valid for the layout config.
+    pub fn perturb_layout(
+        &self,
+        layout_str: &str,
+        n_swaps: usize,
+        seed: u32,
+    ) -> Result<String, JsValue> {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        self.layout_generator.generate(&layout_str).map_err(|e| {
+            js_error(
+                "InvalidLayoutError",
+                &format!("Invalid layout '{}': {}", layout_str, e),
+            )
+        })?;
+
+        let permutator = LayoutPermutator::new(&layout_str, "")
+            .with_keycap_groups(&self.layout_generator.keycap_groups())
+            .map_err(|e| js_error("InvalidLayoutError", &e.to_string()))?;
+        let permutation = permutator.perform_n_swaps_with_rng(
+            &permutator.get_permutable_indices(),
+            n_swaps,
+            &mut seeded_rng(seed),
+        );
+        Ok(permutator.generate_string(&permutation))
+    }
 }
 
 #[wasm_bindgen]
 pub struct NgramProvider {
     ngram_provider: OnDemandNgramMapper,
+    ngrams_fingerprint: String,
 }
 
 #[wasm_bindgen]
@@ -115,16 +196,28 @@ impl NgramProvider {
             .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?;
 
         let ngrams_config = eval_params.ngrams;
+        (unigrams, bigrams, trigrams) = ngrams_config
+            .combining_marks
+            .apply(&unigrams, &bigrams, &trigrams);
         if ngrams_config.increase_common_ngrams.enabled {
             unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
             bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
             trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
         }
+        if ngrams_config.prune.enabled() {
+            unigrams = unigrams.prune(&ngrams_config.prune).0;
+            bigrams = bigrams.prune(&ngrams_config.prune).0;
+            trigrams = trigrams.prune(&ngrams_config.prune).0;
+        }
 
+        let ngrams_fingerprint = fingerprint::fingerprint_ngrams(&unigrams, &bigrams, &trigrams);
         let ngram_provider =
             OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
 
-        Ok(NgramProvider { ngram_provider })
+        Ok(NgramProvider {
+            ngram_provider,
+            ngrams_fingerprint,
+        })
     }
 
     pub fn with_text(eval_params_str: &str, text: &str) -> Result<NgramProvider, JsValue> {
@@ -139,16 +232,28 @@ impl NgramProvider {
             .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?;
 
         let ngrams_config = eval_params.ngrams;
+        (unigrams, bigrams, trigrams) = ngrams_config
+            .combining_marks
+            .apply(&unigrams, &bigrams, &trigrams);
         if ngrams_config.increase_common_ngrams.enabled {
             unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
             bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
             trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
         }
+        if ngrams_config.prune.enabled() {
+            unigrams = unigrams.prune(&ngrams_config.prune).0;
+            bigrams = bigrams.prune(&ngrams_config.prune).0;
+            trigrams = trigrams.prune(&ngrams_config.prune).0;
+        }
 
+        let ngrams_fingerprint = fingerprint::fingerprint_ngrams(&unigrams, &bigrams, &trigrams);
         let ngram_provider =
             OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, eval_params.ngram_mapper);
 
-        Ok(NgramProvider { ngram_provider })
+        Ok(NgramProvider {
+            ngram_provider,
+            ngrams_fingerprint,
+        })
     }
 }
 
@@ -156,6 +261,7 @@ impl NgramProvider {
 pub struct LayoutEvaluator {
     layout_generator: NeoLayoutGenerator,
     evaluator: Evaluator,
+    fingerprint: String,
 }
 
 #[wasm_bindgen]
@@ -180,9 +286,18 @@ impl LayoutEvaluator {
         let evaluator = Evaluator::default(Box::new(ngram_provider.ngram_provider.clone()))
             .default_metrics(&eval_params.metrics);
 
+        let fingerprint = fingerprint::combine(
+            &fingerprint::fingerprint_yaml(layout_cfg_str)
+                .map_err(|e| format!("Could not read layout config: {:?}", e))?,
+            &fingerprint::fingerprint_yaml(eval_params_str)
+                .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?,
+            &ngram_provider.ngrams_fingerprint,
+        );
+
         Ok(LayoutEvaluator {
             layout_generator,
             evaluator,
+            fingerprint,
         })
     }
 
@@ -195,15 +310,24 @@ impl LayoutEvaluator {
         let res = self.evaluator.evaluate_layout(&layout);
         let printed = Some(format!("{}", res));
         let plot = Some(layout.plot());
+        let mapping_statistics = self.evaluator.mapping_statistics(&layout);
         let layout_str = Some(layout_str);
 
         let mut res: LayoutEvaluation = res.into();
         res.printed = printed;
         res.plot = plot;
         res.layout = layout_str;
+        res.fingerprint = Some(self.fingerprint.clone());
+        res.mapping_statistics = mapping_statistics;
         Ok(JsValue::from_serde(&res).unwrap())
     }
 
+    /// Fingerprint of the layout config, evaluation parameters, and ngrams used for evaluations
+    /// (for comparing results with those of the server)
+    pub fn fingerprint(&self) -> String {
+        self.fingerprint.clone()
+    }
+
     pub fn plot(&self, layout_str: &str, layer: usize) -> Result<String, JsValue> {
         let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
         let layout = self
@@ -257,6 +381,8 @@ impl LayoutOptimizer {
             fixed_characters,
             start_with_layout,
             true,
+            None,
+            None,
         );
 
         Ok(LayoutOptimizer {
@@ -386,7 +512,9 @@ pub fn sa_optimize(
     let _ = update_callback.call2(&this, &zero, &init_temp);
 
     let observer = SaObserver {
-        permutator: LayoutPermutator::new(layout_str, fixed_characters),
+        permutator: LayoutPermutator::new(layout_str, fixed_characters)
+            .with_keycap_groups(&layout_evaluator.layout_generator.keycap_groups())
+            .expect("Could not apply keycap groups"),
         last_update_call: Instant::now(),
         update_callback: update_callback.clone(),
         new_best_callback,
@@ -406,6 +534,8 @@ pub fn sa_optimize(
         &layout_evaluator.evaluator,
         /* log_everything: */ false,
         Some(Cache::new()),
+        None,
+        None,
         Some(SaCustomObserver(Box::new(observer))),
     );
     let minus_one = JsValue::from(-1);
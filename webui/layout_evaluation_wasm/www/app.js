@@ -139,7 +139,7 @@ Vue.component('evaluator-app', {
 
   <b-row>
     <b-col v-for="detail in details" xl="6">
-      <layout-button :layout="detail.layout" :layout-config="selectedLayoutConfig" @remove="removeLayout"></layout-button>
+      <layout-button :layout="detail.layout" :layout-config="selectedLayoutConfig" :evaluation="detail" @remove="removeLayout"></layout-button>
       <layout-details title="Details" :layout-details="detail"></layout-details>
     </b-col>
 
@@ -579,6 +579,7 @@ Vue.component('layout-button', {
   props: {
     layout: { type: String, default: "", required: true },
     layoutConfig: { type: String, required: true },
+    evaluation: { type: Object, default: null },
   },
   data() {
     return {
@@ -608,13 +609,19 @@ Vue.component('layout-button', {
         bvModalEvt.preventDefault()
         return
       }
+      let payload = { layout: this.layout, published_by: this.publishName, layout_config: this.layoutConfig }
+      // send the local evaluation along, so that the server does not need to repeat it
+      // (it is only accepted if the configuration fingerprints match)
+      if (this.evaluation && this.evaluation.fingerprint) {
+        payload.evaluation = { details: this.evaluation.details, fingerprint: this.evaluation.fingerprint }
+      }
       try {
         let res = await fetch(PUBLISH_URL, {
           method: "POST",
           headers: {
             'Content-Type': 'application/json'
           },
-          body: JSON.stringify({ layout: this.layout, published_by: this.publishName, layout_config: this.layoutConfig })
+          body: JSON.stringify(payload)
         })
         let resData = await res.json()
         if (resData.published_by !== this.publishName) {
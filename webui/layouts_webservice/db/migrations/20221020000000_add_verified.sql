@@ -0,0 +1,3 @@
+-- Results computed by clients are stored unverified until the server has evaluated them
+ALTER TABLE layouts
+ADD verified BOOL NOT NULL DEFAULT TRUE;
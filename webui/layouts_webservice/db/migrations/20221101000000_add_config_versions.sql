@@ -0,0 +1,10 @@
+-- Versions of the configuration served by the webservice (bumped whenever its fingerprint changes)
+CREATE TABLE IF NOT EXISTS config_versions (
+  id SERIAL PRIMARY KEY,
+  fingerprint TEXT NOT NULL,
+  created TIMESTAMP NOT NULL
+);
+
+-- Configuration version each result was evaluated with (unknown for older results)
+ALTER TABLE layouts
+ADD config_version INTEGER REFERENCES config_versions(id);
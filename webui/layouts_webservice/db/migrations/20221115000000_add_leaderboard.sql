@@ -0,0 +1,22 @@
+-- Rank-relevant values of the stored layouts (maintained by the webservice on publish and re-evaluation)
+CREATE TABLE IF NOT EXISTS leaderboard (
+  layout_id INTEGER PRIMARY KEY REFERENCES layouts(id) ON DELETE CASCADE,
+  layout_config VARCHAR NOT NULL,
+  config_version INTEGER REFERENCES config_versions(id),
+  total_cost DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS leaderboard_total_cost_idx
+ON leaderboard (layout_config, total_cost);
+
+-- Weighted costs of the individual metrics for sorting by a metric
+CREATE TABLE IF NOT EXISTS leaderboard_metrics (
+  layout_id INTEGER NOT NULL REFERENCES leaderboard(layout_id) ON DELETE CASCADE,
+  layout_config VARCHAR NOT NULL,
+  metric VARCHAR NOT NULL,
+  cost DOUBLE PRECISION NOT NULL,
+  PRIMARY KEY (layout_id, metric)
+);
+
+CREATE INDEX IF NOT EXISTS leaderboard_metrics_cost_idx
+ON leaderboard_metrics (layout_config, metric, cost);
@@ -1,22 +1,28 @@
+use super::config_api::{ConfigVersion, ServedConfigs};
+use super::leaderboard;
+use super::preview::{self, PreviewCache};
+use super::publish::{self, ClientEvaluation, Verification, Verifier};
 use super::Options;
 
 use keyboard_layout::layout_generator::LayoutGenerator;
 use keyboard_layout::neo_layout_generator::NeoLayoutGenerator;
-use layout_evaluation::{evaluation::Evaluator, results::EvaluationResult};
+use layout_evaluation::{config::WeightProfile, results::EvaluationResult};
 
 use ahash::AHashMap;
 use rocket::{
     fairing::{self, AdHoc},
-    http::Status,
+    fs::NamedFile,
+    http::{ContentType, Status},
     response::status::Created,
     serde::{json::Json, Deserialize, Serialize},
     State, {Build, Rocket},
 };
 use rocket_db_pools::{sqlx, Connection, Database};
+use std::collections::BTreeMap;
 
 #[derive(Database)]
 #[database("sqlx")]
-struct Db(sqlx::PgPool);
+pub struct Db(sqlx::PgPool);
 
 // type Result<T, E = rocket::response::Debug<sqlx::Error>> = std::result::Result<T, E>;
 type Result<T, E = Status> = std::result::Result<T, E>;
@@ -33,6 +39,8 @@ struct LayoutEvaluationDB {
     published_by: Option<String>,
     highlight: bool,
     layout_config: String,
+    verified: bool,
+    config_version: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -45,6 +53,12 @@ struct LayoutEvaluation {
     plot: Option<String>,
     highlight: bool,
     layout_config: String,
+    /// Whether the result was computed by the server (and not only by the publishing client)
+    verified: bool,
+    /// Version of the configuration the result was evaluated with (see `/api/config`)
+    config_version: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
 }
 
 impl From<LayoutEvaluationDB> for LayoutEvaluation {
@@ -58,6 +72,9 @@ impl From<LayoutEvaluationDB> for LayoutEvaluation {
             plot: None,
             highlight: item.highlight,
             layout_config: item.layout_config,
+            verified: item.verified,
+            config_version: item.config_version,
+            profile: None,
         }
     }
 }
@@ -69,6 +86,12 @@ struct PostLayout {
     highlight: Option<bool>,
     secret: Option<String>,
     layout_config: Option<String>,
+    /// Weight profile to apply to the returned evaluation (stored results always use the
+    /// default weights)
+    profile: Option<String>,
+    /// Evaluation result computed by the client (stored without re-evaluation if the
+    /// configuration fingerprints match)
+    evaluation: Option<ClientEvaluation>,
 }
 
 #[options("/")]
@@ -79,7 +102,9 @@ async fn post(
     mut db: Connection<Db>,
     layout: Json<PostLayout>,
     layout_generators: &State<AHashMap<String, NeoLayoutGenerator>>,
-    evaluator: &State<Evaluator>,
+    verifier: &State<Verifier>,
+    profiles: &State<BTreeMap<String, WeightProfile>>,
+    config_version: &State<ConfigVersion>,
     config: &State<Options>,
 ) -> Result<Created<Json<LayoutEvaluation>>> {
     // check if highlight wants to be set without permission
@@ -89,6 +114,11 @@ async fn post(
         return Err(Status::Forbidden);
     };
 
+    let profile = match &layout.profile {
+        Some(name) => Some((name, profiles.get(name).ok_or(Status::BadRequest)?)),
+        None => None,
+    };
+
     // generate layout
     let layout_config = layout
         .layout_config
@@ -119,8 +149,26 @@ async fn post(
 
     let result = match result {
         None => {
-            println!("Evaluating new layout: {}", layout_str);
-            let evaluation_result = evaluator.evaluate_layout(&l);
+            let server_fingerprint = verifier
+                .fingerprint(&layout_config)
+                .ok_or(Status::BadRequest)?;
+            let verification = layout.evaluation.as_ref().map(|client| {
+                let sample = rand::random::<f64>();
+                let verification = Verification::decide(
+                    client,
+                    &layout_str,
+                    server_fingerprint,
+                    &verifier.metric_set,
+                    config.spot_check_fraction,
+                    sample,
+                );
+                (client, verification)
+            });
+
+            let (evaluation_result, verified) =
+                publish::authoritative(verification, &layout_str, || {
+                    verifier.evaluator.evaluate_layout(&l)
+                });
 
             let result = LayoutEvaluationDB {
                 id: None,
@@ -132,9 +180,16 @@ async fn post(
                 printed: format!("{}", evaluation_result),
                 highlight,
                 layout_config,
+                verified,
+                config_version: Some(config_version.0),
             };
 
-            sqlx::query("INSERT INTO layouts (layout, total_cost, published_by, details_json, printed, highlight, layout_config, created) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())")
+            // the layout must not be stored without its leaderboard entry
+            let mut tx = sqlx::Connection::begin(&mut **db)
+                .await
+                .map_err(|_| Status::InternalServerError)?;
+
+            let id: i32 = sqlx::query_scalar("INSERT INTO layouts (layout, total_cost, published_by, details_json, printed, highlight, layout_config, verified, config_version, created) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW()) RETURNING id")
                 .bind(&result.layout)
                 .bind(&result.total_cost)
                 .bind(&result.published_by)
@@ -142,16 +197,40 @@ async fn post(
                 .bind(&result.printed)
                 .bind(&result.highlight)
                 .bind(&result.layout_config)
-                .execute(&mut *db)
+                .bind(&result.verified)
+                .bind(&result.config_version)
+                .fetch_one(&mut *tx)
                 .await
                 .map_err(|_| Status::InternalServerError)?;
 
+            leaderboard::update(&mut tx, id).await.map_err(|e| {
+                eprintln!("Error while updating the leaderboard: {:?}", e);
+                Status::InternalServerError
+            })?;
+
+            tx.commit().await.map_err(|_| Status::InternalServerError)?;
+
             result
         }
         Some(result) => result,
     };
 
-    Ok(Created::new("/").body(Json(result.into())))
+    let response: LayoutEvaluation = match profile {
+        Some((name, profile)) => {
+            let details: EvaluationResult = serde_json::from_str(&result.details_json)
+                .map_err(|_| Status::InternalServerError)?;
+            let details = verifier.evaluator.reweight(&details, name, profile);
+            let mut response: LayoutEvaluation = result.into();
+            response.total_cost = details.total_cost();
+            response.printed = Some(format!("{}", details));
+            response.details = Some(details);
+            response.profile = Some(name.to_owned());
+            response
+        }
+        None => result.into(),
+    };
+
+    Ok(Created::new("/").body(Json(response)))
 }
 
 #[get("/?<layout_config>")]
@@ -162,7 +241,7 @@ async fn list(
 ) -> Result<Json<Vec<LayoutEvaluation>>> {
     let layout_config = layout_config.unwrap_or_else(|| config.default_layout_config.to_owned());
     let layouts = sqlx::query_as::<_, LayoutEvaluationDB>(
-        "SELECT NULL AS id, layout, total_cost, published_by, details_json, printed, highlight, layout_config FROM layouts WHERE layout_config = $1",
+        "SELECT NULL AS id, layout, total_cost, published_by, details_json, printed, highlight, layout_config, verified, config_version FROM layouts WHERE layout_config = $1",
     )
     .bind(&layout_config)
     .fetch_all(&mut *db)
@@ -192,7 +271,7 @@ async fn get(
         .ok_or(Status::BadRequest)?;
 
     sqlx::query_as::<_, LayoutEvaluationDB>(
-        "SELECT NULL AS id, layout, total_cost, published_by, details_json, printed, highlight, layout_config FROM layouts WHERE layout = $1 AND layout_config = $2",
+        "SELECT NULL AS id, layout, total_cost, published_by, details_json, printed, highlight, layout_config, verified, config_version FROM layouts WHERE layout = $1 AND layout_config = $2",
     )
     .bind(layout)
     .bind(&layout_config)
@@ -213,12 +292,53 @@ async fn get(
     })
 }
 
+#[get("/layouts/<id>/preview.png")]
+async fn get_preview(
+    mut db: Connection<Db>,
+    id: i32,
+    layout_generators: &State<AHashMap<String, NeoLayoutGenerator>>,
+    verifier: &State<Verifier>,
+    preview_cache: &State<PreviewCache>,
+) -> Result<(ContentType, NamedFile)> {
+    let result = sqlx::query_as::<_, LayoutEvaluationDB>(
+        "SELECT id, layout, total_cost, published_by, details_json, printed, highlight, layout_config, verified, config_version FROM layouts WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&mut *db)
+    .await
+    .map_err(|_| Status::NotFound)?;
+
+    // layouts of configs that are no longer served can not be rendered
+    let layout_generator = layout_generators
+        .get(&result.layout_config)
+        .ok_or(Status::NotFound)?;
+    let fingerprint = verifier
+        .fingerprint(&result.layout_config)
+        .ok_or(Status::NotFound)?;
+
+    let l = layout_generator
+        .generate(&result.layout)
+        .map_err(|_| Status::InternalServerError)?;
+    let path = preview_cache
+        .get_or_render(id, fingerprint, || {
+            preview::render_svg(&result.layout, result.total_cost, &l.plot())
+        })
+        .map_err(|e| {
+            eprintln!("Error while rendering preview: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    preview::serve(&path).await
+}
+
 #[post("/reeval", data = "<secret>")]
 async fn reeval(
     mut db: Connection<Db>,
     secret: &str,
     layout_generators: &State<AHashMap<String, NeoLayoutGenerator>>,
-    evaluator: &State<Evaluator>,
+    verifier: &State<Verifier>,
+    preview_cache: &State<PreviewCache>,
+    config_version: &State<ConfigVersion>,
     config: &State<Options>,
 ) -> Result<()> {
     let is_admin = config.secret == *secret;
@@ -229,7 +349,7 @@ async fn reeval(
 
     println!("Reevaluating results");
     let results: Vec<LayoutEvaluationDB> = sqlx::query_as::<_, LayoutEvaluationDB>(
-        "SELECT id, layout, total_cost, details_json, printed, published_by, highlight, layout_config FROM layouts",
+        "SELECT id, layout, total_cost, details_json, printed, published_by, highlight, layout_config, verified, config_version FROM layouts",
     )
     .fetch_all(&mut *db)
     .await
@@ -240,7 +360,7 @@ async fn reeval(
             .get(&result.layout_config)
             .ok_or(Status::BadRequest)?;
         let layout = layout_generator.generate(&result.layout).unwrap();
-        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let evaluation_result = verifier.evaluator.evaluate_layout(&layout);
         let total_cost = evaluation_result.total_cost();
         let details_json = Some(serde_json::to_string(&evaluation_result).unwrap());
         let printed = format!("{}", evaluation_result);
@@ -252,18 +372,112 @@ async fn reeval(
             result.total_cost,
             total_cost
         );
+
+        let mut tx = sqlx::Connection::begin(&mut **db)
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
         sqlx::query(
-            "UPDATE layouts SET total_cost = $1, details_json = $2 , printed = $3 WHERE id = $4",
+            "UPDATE layouts SET total_cost = $1, details_json = $2 , printed = $3, verified = TRUE, config_version = $4 WHERE id = $5",
         )
         .bind(&total_cost)
         .bind(&details_json)
         .bind(&printed)
+        .bind(&config_version.0)
         .bind(&result.id)
-        .execute(&mut *db)
+        .execute(&mut *tx)
         .await
         .map_err(|_| Status::InternalServerError)?;
+
+        leaderboard::update(&mut tx, result.id.unwrap())
+            .await
+            .map_err(|_| Status::InternalServerError)?;
+
+        tx.commit().await.map_err(|_| Status::InternalServerError)?;
+    }
+
+    // cached previews show outdated costs now
+    preview_cache
+        .clear()
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(crate = "rocket::serde")]
+struct RankedLayout {
+    rank: i64,
+    layout: String,
+    total_cost: f64,
+    /// The value the ranking is sorted by (the total cost or the weighted cost of a metric)
+    cost: f64,
+    published_by: Option<String>,
+    highlight: bool,
+    verified: bool,
+    config_version: Option<i32>,
+}
+
+/// Maximum number of layouts in a single ranking response
+const MAX_RANKING_LIMIT: i64 = 1000;
+
+/// The ranking of the verified layouts of a layout config by their total cost or the weighted
+/// cost of a metric, optionally restricted to a config version (see [`leaderboard::rank`]). All
+/// ranked layouts are listed individually: the schema knows neither layout families nor hidden
+/// layouts, so there is no best-per-family or hidden-layout filtering.
+#[get("/ranking?<layout_config>&<metric>&<config_version>&<limit>&<offset>")]
+async fn ranking(
+    mut db: Connection<Db>,
+    layout_config: Option<String>,
+    metric: Option<String>,
+    config_version: Option<i32>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    config: &State<Options>,
+) -> Result<Json<Vec<RankedLayout>>> {
+    let layout_config = layout_config.unwrap_or_else(|| config.default_layout_config.to_owned());
+    let limit = limit.unwrap_or(100).clamp(0, MAX_RANKING_LIMIT);
+    let offset = offset.unwrap_or(0).max(0);
+
+    let query = match &metric {
+        None => sqlx::query_as::<_, RankedLayout>(leaderboard::RANKING_QUERY),
+        Some(_) => sqlx::query_as::<_, RankedLayout>(leaderboard::METRIC_RANKING_QUERY),
+    };
+
+    let layouts = query
+        .bind(&layout_config)
+        .bind(config_version)
+        .bind(&metric)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mut *db)
+        .await
+        .map_err(|e| {
+            eprintln!("Error while fetching the ranking from db: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    Ok(Json(layouts))
+}
+
+#[post("/leaderboard/rebuild", data = "<secret>")]
+async fn rebuild_leaderboard(
+    mut db: Connection<Db>,
+    secret: &str,
+    config: &State<Options>,
+) -> Result<()> {
+    let is_admin = config.secret == *secret;
+    if !is_admin {
+        println!("Wrong password provided for rebuilding the leaderboard.");
+        return Err(Status::Unauthorized);
     }
 
+    let n_ranked = leaderboard::rebuild(&mut *db).await.map_err(|e| {
+        eprintln!("Error while rebuilding the leaderboard: {:?}", e);
+        Status::InternalServerError
+    })?;
+    println!("Rebuilt leaderboard with {} layouts", n_ranked);
+
     Ok(())
 }
 
@@ -280,11 +494,95 @@ async fn run_migrations(rocket: Rocket<Build>) -> fairing::Result {
     }
 }
 
+/// Get the version id of the served configuration, adding a new version if its fingerprint
+/// differs from the latest one.
+async fn config_version(db: &sqlx::PgPool, fingerprint: &str) -> sqlx::Result<i32> {
+    let latest: Option<(i32, String)> =
+        sqlx::query_as("SELECT id, fingerprint FROM config_versions ORDER BY id DESC LIMIT 1")
+            .fetch_optional(db)
+            .await?;
+
+    match latest {
+        Some((id, latest_fingerprint)) if latest_fingerprint == fingerprint => Ok(id),
+        _ => sqlx::query_scalar(
+            "INSERT INTO config_versions (fingerprint, created) VALUES ($1, NOW()) RETURNING id",
+        )
+        .bind(fingerprint)
+        .fetch_one(db)
+        .await,
+    }
+}
+
+async fn register_config_version(rocket: Rocket<Build>) -> fairing::Result {
+    let fingerprint = match rocket.state::<ServedConfigs>() {
+        Some(served_configs) => served_configs.fingerprint(),
+        None => return Err(rocket),
+    };
+
+    match Db::fetch(&rocket) {
+        Some(db) => match config_version(&**db, &fingerprint).await {
+            Ok(version) => {
+                println!("Serving configuration version {}", version);
+                Ok(rocket.manage(ConfigVersion(version)))
+            }
+            Err(e) => {
+                error!("Failed to register configuration version: {}", e);
+                Err(rocket)
+            }
+        },
+        None => Err(rocket),
+    }
+}
+
+/// Build the leaderboard if it has not been populated yet (e.g. after the migration that
+/// introduced it).
+async fn init_leaderboard(rocket: Rocket<Build>) -> fairing::Result {
+    let db = match Db::fetch(&rocket) {
+        Some(db) => db,
+        None => return Err(rocket),
+    };
+
+    let result = async {
+        let mut conn = db.acquire().await?;
+        if leaderboard::needs_rebuild(&mut *conn).await? {
+            let n_ranked = leaderboard::rebuild(&mut *conn).await?;
+            println!("Built leaderboard with {} layouts", n_ranked);
+        }
+        Ok::<(), sqlx::Error>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok(rocket),
+        Err(e) => {
+            error!("Failed to initialize the leaderboard: {}", e);
+            Err(rocket)
+        }
+    }
+}
+
 pub fn stage() -> AdHoc {
     AdHoc::on_ignite("SQLx Stage", |rocket| async {
         rocket
             .attach(Db::init())
             .attach(AdHoc::try_on_ignite("SQLx Migrations", run_migrations))
-            .mount("/api", routes![list, post, get, reeval, cors_preflight])
+            .attach(AdHoc::try_on_ignite(
+                "Configuration Version",
+                register_config_version,
+            ))
+            .attach(AdHoc::try_on_ignite("Leaderboard", init_leaderboard))
+            .mount(
+                "/api",
+                routes![
+                    list,
+                    post,
+                    ranking,
+                    get,
+                    get_preview,
+                    reeval,
+                    rebuild_leaderboard,
+                    cors_preflight
+                ],
+            )
     })
 }
@@ -0,0 +1,245 @@
+//! The `config_api` module serves the configuration the server evaluates with (evaluation
+//! parameters, keyboards, and layout configs), so that other tools can evaluate with exactly
+//! the same settings.
+//!
+//! Every served set of configurations has a version id. It is stored in the database and
+//! bumped whenever the fingerprint of the configurations (including the ngram data) changes.
+//! Stored evaluations record the version they were computed with.
+
+use keyboard_layout::config;
+use layout_evaluation::fingerprint;
+
+use anyhow::{anyhow, Result};
+use rocket::{
+    http::{Header, Status},
+    request::{self, FromRequest, Request},
+    response::{self, Responder, Response},
+    serde::{json::Json, Serialize},
+    State,
+};
+use std::{collections::BTreeMap, convert::Infallible, fs};
+
+/// A YAML document along with its fingerprint.
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConfigDocument {
+    pub yaml: String,
+    pub fingerprint: String,
+}
+
+impl ConfigDocument {
+    fn from_yaml(yaml: String) -> Result<Self> {
+        let fingerprint = fingerprint::fingerprint_yaml(&yaml)?;
+        Ok(Self { yaml, fingerprint })
+    }
+}
+
+/// The configurations the server evaluates with.
+#[derive(Clone, Debug)]
+pub struct ServedConfigs {
+    pub evaluation: ConfigDocument,
+    pub keyboards: BTreeMap<String, ConfigDocument>,
+    pub layouts: BTreeMap<String, ConfigDocument>,
+    pub ngrams_fingerprint: String,
+}
+
+impl ServedConfigs {
+    /// Read the evaluation parameters and the layout configs (with all `extends` resolved).
+    /// The keyboard documents are the `keyboard` sections of the layout configs.
+    pub fn load(
+        eval_parameters: &str,
+        layout_configs: &[(String, String)],
+        ngrams_fingerprint: &str,
+    ) -> Result<Self> {
+        let evaluation = ConfigDocument::from_yaml(fs::read_to_string(eval_parameters)?)?;
+
+        let mut keyboards = BTreeMap::new();
+        let mut layouts = BTreeMap::new();
+        for (config_id, filename) in layout_configs {
+            let yaml = config::resolved_yaml(filename)?;
+
+            let value: serde_yaml::Value = serde_yaml::from_str(&yaml)?;
+            let keyboard = value
+                .get("keyboard")
+                .ok_or_else(|| anyhow!("No keyboard section in '{}'", filename))?;
+            keyboards.insert(
+                config_id.to_owned(),
+                ConfigDocument::from_yaml(serde_yaml::to_string(keyboard)?)?,
+            );
+
+            layouts.insert(config_id.to_owned(), ConfigDocument::from_yaml(yaml)?);
+        }
+
+        Ok(Self {
+            evaluation,
+            keyboards,
+            layouts,
+            ngrams_fingerprint: ngrams_fingerprint.to_string(),
+        })
+    }
+
+    /// Fingerprint of all configurations (and the ngram data). A different fingerprint
+    /// results in a new version id.
+    pub fn fingerprint(&self) -> String {
+        let mut parts = vec![
+            self.evaluation.fingerprint.as_str(),
+            self.ngrams_fingerprint.as_str(),
+        ];
+        for (config_id, document) in self.layouts.iter() {
+            parts.push(config_id);
+            parts.push(&document.fingerprint);
+        }
+
+        fingerprint::fingerprint_parts(&parts)
+    }
+}
+
+/// Version id of the served configurations (as stored in the database)
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigVersion(pub i32);
+
+/// The `If-None-Match` header of a request (if any).
+pub struct IfNoneMatch(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(IfNoneMatch(
+            req.headers()
+                .get_one("If-None-Match")
+                .map(|s| s.to_string()),
+        ))
+    }
+}
+
+/// Check whether the value of an `If-None-Match` header matches the given entity tag.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// A config response that can be revalidated by clients with its ETag. The value is omitted
+/// (with status "304 Not Modified") if the client's copy is up to date.
+pub struct Cacheable<T> {
+    value: Option<T>,
+    etag: String,
+}
+
+impl<T> Cacheable<T> {
+    fn new(value: T, etag: String, if_none_match: &IfNoneMatch) -> Self {
+        let value = match &if_none_match.0 {
+            Some(inm) if etag_matches(inm, &etag) => None,
+            _ => Some(value),
+        };
+        Self { value, etag }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Cacheable<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match self.value {
+            Some(value) => Json(value).respond_to(req)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+        response.set_header(Header::new("ETag", self.etag));
+        response.set_header(Header::new("Cache-Control", "no-cache"));
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EvaluationConfigResponse {
+    version: i32,
+    #[serde(flatten)]
+    document: ConfigDocument,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConfigsResponse {
+    version: i32,
+    documents: BTreeMap<String, ConfigDocument>,
+}
+
+fn etag(version: ConfigVersion, fingerprint: &str) -> String {
+    format!("\"{}-{}\"", version.0, fingerprint)
+}
+
+#[get("/evaluation")]
+fn evaluation(
+    configs: &State<ServedConfigs>,
+    version: &State<ConfigVersion>,
+    if_none_match: IfNoneMatch,
+) -> Cacheable<EvaluationConfigResponse> {
+    let document = configs.evaluation.clone();
+    let etag = etag(**version, &document.fingerprint);
+    let response = EvaluationConfigResponse {
+        version: version.0,
+        document,
+    };
+
+    Cacheable::new(response, etag, &if_none_match)
+}
+
+fn documents_response(
+    documents: &BTreeMap<String, ConfigDocument>,
+    version: ConfigVersion,
+    if_none_match: &IfNoneMatch,
+) -> Cacheable<ConfigsResponse> {
+    let parts: Vec<&str> = documents
+        .iter()
+        .flat_map(|(config_id, document)| [config_id.as_str(), document.fingerprint.as_str()])
+        .collect();
+    let etag = etag(version, &fingerprint::fingerprint_parts(&parts));
+    let response = ConfigsResponse {
+        version: version.0,
+        documents: documents.clone(),
+    };
+
+    Cacheable::new(response, etag, if_none_match)
+}
+
+#[get("/keyboards")]
+fn keyboards(
+    configs: &State<ServedConfigs>,
+    version: &State<ConfigVersion>,
+    if_none_match: IfNoneMatch,
+) -> Cacheable<ConfigsResponse> {
+    documents_response(&configs.keyboards, **version, &if_none_match)
+}
+
+#[get("/layouts")]
+fn layouts(
+    configs: &State<ServedConfigs>,
+    version: &State<ConfigVersion>,
+    if_none_match: IfNoneMatch,
+) -> Cacheable<ConfigsResponse> {
+    documents_response(&configs.layouts, **version, &if_none_match)
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    routes![evaluation, keyboards, layouts]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etags_are_matched() {
+        let etag = "\"3-0123456789abcdef\"";
+        assert!(etag_matches(etag, etag));
+        assert!(etag_matches("W/\"3-0123456789abcdef\"", etag));
+        assert!(etag_matches(
+            "\"2-0123456789abcdef\", \"3-0123456789abcdef\"",
+            etag
+        ));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"2-0123456789abcdef\"", etag));
+    }
+}
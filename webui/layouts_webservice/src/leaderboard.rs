@@ -0,0 +1,510 @@
+//! The `leaderboard` module maintains the table the rankings are served from. It holds the
+//! rank-relevant values of the stored layouts (total cost and weighted metric costs), so
+//! that rankings do not require parsing and sorting all stored results on every request.
+//!
+//! The table is updated incrementally whenever a layout is published or re-evaluated and can
+//! be rebuilt from scratch. Both paths derive the entries with [`LeaderboardEntry::from_layout`].
+//! Only results that were computed by the server are ranked.
+
+use layout_evaluation::results::EvaluationResult;
+
+use rocket_db_pools::sqlx::{self, PgConnection};
+use std::collections::BTreeMap;
+
+/// The columns of a stored layout that are relevant for ranking it.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RankingSource {
+    pub id: i32,
+    pub layout_config: String,
+    pub config_version: Option<i32>,
+    pub details_json: String,
+    pub verified: bool,
+}
+
+/// Rank-relevant values of a layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub layout_id: i32,
+    pub layout_config: String,
+    pub config_version: Option<i32>,
+    pub total_cost: f64,
+    /// Weighted costs of the individual metrics (by metric name)
+    pub metric_costs: BTreeMap<String, f64>,
+}
+
+impl LeaderboardEntry {
+    /// The entry of a stored layout. Layouts that were not verified by the server, whose details
+    /// can not be read or whose costs are not finite are not ranked (`None`).
+    pub fn from_layout(layout: &RankingSource) -> Option<Self> {
+        if !layout.verified {
+            return None;
+        }
+
+        let details: EvaluationResult = serde_json::from_str(&layout.details_json).ok()?;
+
+        let all_finite = details
+            .iter()
+            .flat_map(|mr| mr.metric_costs.iter())
+            .all(|mc| mc.core.cost.is_finite() && mc.weighted_cost.is_finite());
+        let total_cost = details.total_cost();
+        if !all_finite || !total_cost.is_finite() {
+            return None;
+        }
+
+        let mut metric_costs: BTreeMap<String, f64> = BTreeMap::new();
+        details
+            .iter()
+            .flat_map(|mr| mr.metric_costs.iter())
+            .for_each(|mc| {
+                *metric_costs.entry(mc.core.name.clone()).or_insert(0.0) += mc.weighted_cost
+            });
+
+        Some(Self {
+            layout_id: layout.id,
+            layout_config: layout.layout_config.clone(),
+            config_version: layout.config_version,
+            total_cost,
+            metric_costs,
+        })
+    }
+}
+
+/// Query of the ranking by total cost. Binds the layout config, the config version (optional),
+/// the metric (must be `NULL`), the limit and the offset. Implements [`rank`].
+pub const RANKING_QUERY: &str =
+    "SELECT $5 + ROW_NUMBER() OVER (ORDER BY b.total_cost, b.layout_id) AS rank, b.layout_id, l.layout, b.total_cost, b.total_cost AS cost, l.published_by, COALESCE(l.highlight, FALSE) AS highlight, l.verified, b.config_version \
+    FROM leaderboard b JOIN layouts l ON l.id = b.layout_id \
+    WHERE b.layout_config = $1 AND ($2::INTEGER IS NULL OR b.config_version = $2) AND $3::VARCHAR IS NULL \
+    ORDER BY b.total_cost, b.layout_id LIMIT $4 OFFSET $5";
+
+/// Query of the ranking by the weighted cost of a metric. Binds the same values as
+/// [`RANKING_QUERY`]. Implements [`rank`].
+pub const METRIC_RANKING_QUERY: &str =
+    "SELECT $5 + ROW_NUMBER() OVER (ORDER BY m.cost, m.layout_id) AS rank, b.layout_id, l.layout, b.total_cost, m.cost, l.published_by, COALESCE(l.highlight, FALSE) AS highlight, l.verified, b.config_version \
+    FROM leaderboard_metrics m JOIN leaderboard b ON b.layout_id = m.layout_id JOIN layouts l ON l.id = m.layout_id \
+    WHERE m.layout_config = $1 AND ($2::INTEGER IS NULL OR b.config_version = $2) AND m.metric = $3 \
+    ORDER BY m.cost, m.layout_id LIMIT $4 OFFSET $5";
+
+/// The ranking of the given entries for a layout config, sorted by the total cost or the
+/// weighted cost of a metric and optionally restricted to a config version. Layouts with
+/// identical costs are ranked by their publication order. Returns the rank (starting at 1), the
+/// entry and the cost it is ranked by.
+///
+/// The ranking queries ([`RANKING_QUERY`], [`METRIC_RANKING_QUERY`]) compute the same ranking
+/// on the leaderboard table.
+pub fn rank<'a>(
+    entries: impl IntoIterator<Item = &'a LeaderboardEntry>,
+    layout_config: &str,
+    metric: Option<&str>,
+    config_version: Option<i32>,
+) -> Vec<(i64, &'a LeaderboardEntry, f64)> {
+    let mut ranked: Vec<(&LeaderboardEntry, f64)> = entries
+        .into_iter()
+        .filter(|entry| entry.layout_config == layout_config)
+        .filter(|entry| config_version.is_none() || entry.config_version == config_version)
+        .filter_map(|entry| match metric {
+            None => Some((entry, entry.total_cost)),
+            Some(metric) => entry.metric_costs.get(metric).map(|cost| (entry, *cost)),
+        })
+        .collect();
+    ranked.sort_by(|(e1, c1), (e2, c2)| c1.total_cmp(c2).then(e1.layout_id.cmp(&e2.layout_id)));
+
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(i, (entry, cost))| (i as i64 + 1, entry, cost))
+        .collect()
+}
+
+/// Write the leaderboard entry of a layout, or remove the layout from the leaderboard if it is
+/// not ranked.
+async fn write(
+    conn: &mut PgConnection,
+    layout_id: i32,
+    entry: Option<&LeaderboardEntry>,
+) -> sqlx::Result<()> {
+    let entry = match entry {
+        Some(entry) => entry,
+        None => {
+            sqlx::query("DELETE FROM leaderboard WHERE layout_id = $1")
+                .bind(layout_id)
+                .execute(&mut *conn)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    sqlx::query(
+        "INSERT INTO leaderboard (layout_id, layout_config, config_version, total_cost) VALUES ($1, $2, $3, $4) \
+        ON CONFLICT (layout_id) DO UPDATE SET layout_config = $2, config_version = $3, total_cost = $4",
+    )
+    .bind(entry.layout_id)
+    .bind(&entry.layout_config)
+    .bind(entry.config_version)
+    .bind(entry.total_cost)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM leaderboard_metrics WHERE layout_id = $1")
+        .bind(entry.layout_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let metrics: Vec<String> = entry.metric_costs.keys().cloned().collect();
+    let costs: Vec<f64> = entry.metric_costs.values().cloned().collect();
+    sqlx::query(
+        "INSERT INTO leaderboard_metrics (layout_id, layout_config, metric, cost) \
+        SELECT $1, $2, * FROM UNNEST($3::VARCHAR[], $4::DOUBLE PRECISION[])",
+    )
+    .bind(entry.layout_id)
+    .bind(&entry.layout_config)
+    .bind(metrics)
+    .bind(costs)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Update the leaderboard entry of a single (newly published or re-evaluated) layout.
+pub async fn update(conn: &mut PgConnection, layout_id: i32) -> sqlx::Result<()> {
+    let layout = sqlx::query_as::<_, RankingSource>(
+        "SELECT id, layout_config, config_version, details_json, verified FROM layouts WHERE id = $1",
+    )
+    .bind(layout_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let entry = layout.as_ref().and_then(LeaderboardEntry::from_layout);
+    write(conn, layout_id, entry.as_ref()).await
+}
+
+/// Rebuild the leaderboard from all stored layouts. Returns the number of ranked layouts.
+pub async fn rebuild(conn: &mut PgConnection) -> sqlx::Result<usize> {
+    let mut tx = sqlx::Connection::begin(conn).await?;
+
+    sqlx::query("DELETE FROM leaderboard")
+        .execute(&mut *tx)
+        .await?;
+
+    let layouts = sqlx::query_as::<_, RankingSource>(
+        "SELECT id, layout_config, config_version, details_json, verified FROM layouts",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut n_ranked = 0;
+    for layout in layouts.iter() {
+        if let Some(entry) = LeaderboardEntry::from_layout(layout) {
+            write(&mut tx, layout.id, Some(&entry)).await?;
+            n_ranked += 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(n_ranked)
+}
+
+/// Whether the leaderboard is empty while there are stored layouts (e.g. after the migration
+/// that introduced it).
+pub async fn needs_rebuild(conn: &mut PgConnection) -> sqlx::Result<bool> {
+    sqlx::query_scalar(
+        "SELECT NOT EXISTS (SELECT 1 FROM leaderboard) AND EXISTS (SELECT 1 FROM layouts)",
+    )
+    .fetch_one(&mut *conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use layout_evaluation::results::{MetricResult, MetricResults, MetricType, NormalizationType};
+
+    fn details(costs: &[(&str, f64)]) -> String {
+        let mut metric_results = MetricResults::new(MetricType::Bigram, 1.0, 0.0);
+        for (name, cost) in costs {
+            metric_results.add_result(MetricResult {
+                name: name.to_string(),
+                cost: *cost,
+                message: None,
+                weight: 1.0,
+                normalization: NormalizationType::Fixed(1.0),
+            });
+        }
+        serde_json::to_string(&EvaluationResult::new(
+            "layout".to_string(),
+            vec![metric_results],
+        ))
+        .unwrap()
+    }
+
+    fn source(id: i32, config_version: Option<i32>, costs: &[(&str, f64)]) -> RankingSource {
+        RankingSource {
+            id,
+            layout_config: "standard".to_string(),
+            config_version,
+            details_json: details(costs),
+            verified: true,
+        }
+    }
+
+    /// A client's result that was stored without being evaluated by the server.
+    fn unverified(id: i32, costs: &[(&str, f64)]) -> RankingSource {
+        RankingSource {
+            verified: false,
+            ..source(id, Some(2), costs)
+        }
+    }
+
+    /// A connection to the database given by `DATABASE_URL`, working in a fresh schema with all
+    /// migrations applied.
+    async fn connect(schema: &str) -> PgConnection {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+        let mut conn: PgConnection = sqlx::Connection::connect(&url).await.unwrap();
+        sqlx::Executor::execute(
+            &mut conn,
+            format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}; SET search_path TO {0}",
+                schema
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+        sqlx::migrate!("db/migrations")
+            .run(&mut conn)
+            .await
+            .unwrap();
+
+        conn
+    }
+
+    /// Store a layout (publishing or re-evaluating it) and update its leaderboard entry the way
+    /// the webservice does.
+    async fn store(conn: &mut PgConnection, layout: &RankingSource) {
+        let mut tx = sqlx::Connection::begin(conn).await.unwrap();
+        sqlx::query(
+            "INSERT INTO layouts (id, layout, total_cost, details_json, printed, layout_config, config_version, verified) \
+            VALUES ($1, 'layout', 0, $2, '', $3, $4, $5) \
+            ON CONFLICT (id) DO UPDATE SET details_json = $2, config_version = $4, verified = $5",
+        )
+        .bind(layout.id)
+        .bind(&layout.details_json)
+        .bind(&layout.layout_config)
+        .bind(layout.config_version)
+        .bind(layout.verified)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        update(&mut tx, layout.id).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    /// The stored leaderboard.
+    async fn entries(conn: &mut PgConnection) -> BTreeMap<i32, LeaderboardEntry> {
+        let rows: Vec<(i32, String, Option<i32>, f64)> = sqlx::query_as(
+            "SELECT layout_id, layout_config, config_version, total_cost FROM leaderboard",
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        let mut entries: BTreeMap<i32, LeaderboardEntry> = rows
+            .into_iter()
+            .map(|(layout_id, layout_config, config_version, total_cost)| {
+                let entry = LeaderboardEntry {
+                    layout_id,
+                    layout_config,
+                    config_version,
+                    total_cost,
+                    metric_costs: BTreeMap::new(),
+                };
+                (layout_id, entry)
+            })
+            .collect();
+
+        let metrics: Vec<(i32, String, f64)> =
+            sqlx::query_as("SELECT layout_id, metric, cost FROM leaderboard_metrics")
+                .fetch_all(&mut *conn)
+                .await
+                .unwrap();
+        for (layout_id, metric, cost) in metrics {
+            entries
+                .get_mut(&layout_id)
+                .unwrap()
+                .metric_costs
+                .insert(metric, cost);
+        }
+
+        entries
+    }
+
+    /// The leaderboard a rebuild would produce (without keeping it).
+    async fn rebuilt_entries(conn: &mut PgConnection) -> BTreeMap<i32, LeaderboardEntry> {
+        let mut tx = sqlx::Connection::begin(conn).await.unwrap();
+        rebuild(&mut tx).await.unwrap();
+        let entries = entries(&mut tx).await;
+        tx.rollback().await.unwrap();
+
+        entries
+    }
+
+    /// The ranking served by the webservice as (rank, layout id, cost).
+    async fn served_ranking(
+        conn: &mut PgConnection,
+        metric: Option<&str>,
+        config_version: Option<i32>,
+        limit: i64,
+        offset: i64,
+    ) -> Vec<(i64, i32, f64)> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            rank: i64,
+            layout_id: i32,
+            cost: f64,
+        }
+
+        let query = match metric {
+            None => RANKING_QUERY,
+            Some(_) => METRIC_RANKING_QUERY,
+        };
+        sqlx::query_as::<_, Row>(query)
+            .bind("standard")
+            .bind(config_version)
+            .bind(metric)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.rank, row.layout_id, row.cost))
+            .collect()
+    }
+
+    #[test]
+    fn entries_contain_weighted_costs() {
+        let entry = LeaderboardEntry::from_layout(&source(
+            1,
+            Some(2),
+            &[("Key Costs", 10.0), ("Finger Repeats", 5.0)],
+        ))
+        .unwrap();
+        assert_eq!(entry.total_cost, 15.0);
+        assert_eq!(entry.config_version, Some(2));
+        assert_eq!(entry.metric_costs.get("Finger Repeats"), Some(&5.0));
+
+        assert!(
+            LeaderboardEntry::from_layout(&source(1, None, &[("Key Costs", f64::NAN)])).is_none()
+        );
+
+        let mut invalid = source(1, None, &[]);
+        invalid.details_json = "{".to_string();
+        assert!(LeaderboardEntry::from_layout(&invalid).is_none());
+
+        assert!(LeaderboardEntry::from_layout(&unverified(1, &[("Key Costs", 0.0)])).is_none());
+    }
+
+    #[test]
+    fn rankings_are_filtered_and_sorted() {
+        let entries: Vec<LeaderboardEntry> = [
+            source(1, Some(1), &[("Key Costs", 10.0), ("Finger Repeats", 5.0)]),
+            source(2, Some(2), &[("Key Costs", 12.0), ("Finger Repeats", 2.0)]),
+            source(3, Some(2), &[("Key Costs", 9.0), ("Finger Repeats", 6.0)]),
+            source(4, None, &[("Key Costs", 8.0)]),
+            RankingSource {
+                layout_config: "ortho".to_string(),
+                ..source(5, Some(2), &[("Key Costs", 1.0)])
+            },
+        ]
+        .iter()
+        .filter_map(LeaderboardEntry::from_layout)
+        .collect();
+        let ranking = |metric, config_version| -> Vec<(i64, i32, f64)> {
+            rank(&entries, "standard", metric, config_version)
+                .into_iter()
+                .map(|(rank, entry, cost)| (rank, entry.layout_id, cost))
+                .collect()
+        };
+
+        // identical costs are ranked by publication order
+        assert_eq!(
+            ranking(None, None),
+            vec![(1, 4, 8.0), (2, 2, 14.0), (3, 1, 15.0), (4, 3, 15.0)]
+        );
+        assert_eq!(ranking(None, Some(2)), vec![(1, 2, 14.0), (2, 3, 15.0)]);
+        // layouts without costs for the metric are not ranked by it
+        assert_eq!(
+            ranking(Some("Finger Repeats"), None),
+            vec![(1, 2, 2.0), (2, 1, 5.0), (3, 3, 6.0)]
+        );
+        assert_eq!(ranking(Some("Key Costs"), Some(1)), vec![(1, 1, 10.0)]);
+        assert!(ranking(Some("Unknown"), None).is_empty());
+    }
+
+    #[rocket::async_test]
+    #[ignore = "requires a PostgreSQL database (DATABASE_URL), run by the `webservice` CI job"]
+    async fn incremental_updates_agree_with_rebuild() {
+        let mut conn = connect("leaderboard_consistency_test").await;
+        sqlx::Executor::execute(
+            &mut conn,
+            "INSERT INTO config_versions (id, fingerprint, created) VALUES (1, 'a', NOW()), (2, 'b', NOW())",
+        )
+        .await
+        .unwrap();
+
+        // publications and re-evaluations in sequence
+        let events = vec![
+            source(1, None, &[("Key Costs", 10.0), ("Finger Repeats", 5.0)]),
+            source(2, Some(1), &[("Key Costs", 12.0), ("Finger Repeats", 2.0)]),
+            source(3, Some(1), &[("Key Costs", f64::INFINITY)]),
+            source(1, Some(2), &[("Key Costs", 9.0), ("Finger Repeats", 6.0)]),
+            source(4, Some(2), &[("Key Costs", 11.0), ("Finger Repeats", 1.0)]),
+            source(3, Some(2), &[("Key Costs", 13.0), ("Finger Repeats", 0.5)]),
+            source(2, Some(2), &[("Key Costs", f64::NAN)]),
+            unverified(5, &[("Key Costs", 0.0)]),
+            unverified(6, &[("Key Costs", 0.0)]),
+            source(6, Some(2), &[("Key Costs", 14.0)]),
+        ];
+
+        for layout in events.iter() {
+            store(&mut conn, layout).await;
+
+            assert_eq!(entries(&mut conn).await, rebuilt_entries(&mut conn).await);
+        }
+
+        let leaderboard = entries(&mut conn).await;
+        assert_eq!(
+            leaderboard.keys().cloned().collect::<Vec<i32>>(),
+            vec![1, 3, 4, 6]
+        );
+        assert_eq!(leaderboard[&1].total_cost, 15.0);
+        assert_eq!(leaderboard[&1].config_version, Some(2));
+
+        for metric in [None, Some("Key Costs"), Some("Finger Repeats")] {
+            for config_version in [None, Some(1), Some(2)] {
+                let expected: Vec<(i64, i32, f64)> =
+                    rank(leaderboard.values(), "standard", metric, config_version)
+                        .into_iter()
+                        .map(|(rank, entry, cost)| (rank, entry.layout_id, cost))
+                        .collect();
+                assert_eq!(
+                    served_ranking(&mut conn, metric, config_version, 100, 0).await,
+                    expected
+                );
+                assert_eq!(
+                    served_ranking(&mut conn, metric, config_version, 2, 1).await,
+                    expected.iter().skip(1).take(2).cloned().collect::<Vec<_>>()
+                );
+            }
+        }
+
+        sqlx::Executor::execute(
+            &mut conn,
+            "DROP SCHEMA leaderboard_consistency_test CASCADE",
+        )
+        .await
+        .unwrap();
+    }
+}
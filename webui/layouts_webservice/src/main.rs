@@ -2,21 +2,29 @@
 extern crate rocket;
 
 use keyboard_layout::{
-    config::LayoutConfig, keyboard::Keyboard, neo_layout_generator::NeoLayoutGenerator,
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
     config::EvaluationParameters,
     evaluation::Evaluator,
+    fingerprint,
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
     ngrams::{Bigrams, Trigrams, Unigrams},
 };
 
+use config_api::ServedConfigs;
+
 use ahash::AHashMap;
 use rocket::{fairing::AdHoc, fs::FileServer};
 use serde::Deserialize;
 use std::{path::Path, sync::Arc};
 
 mod api;
+mod config_api;
+mod leaderboard;
+mod preview;
+mod publish;
 
 #[derive(Clone, Deserialize, Debug)]
 struct Options {
@@ -40,8 +48,31 @@ struct Options {
 
     /// CORS allowed origins
     pub allowed_cors_origins: String,
+
+    /// Public base URL of the service (used for links in OpenGraph tags)
+    #[serde(default)]
+    pub public_url: String,
+
+    /// Directory for caching rendered layout previews
+    #[serde(default = "default_preview_cache_dir")]
+    pub preview_cache_dir: String,
+
+    /// Fraction of published client-side evaluations that are verified by re-evaluating them
+    #[serde(default = "default_spot_check_fraction")]
+    pub spot_check_fraction: f64,
+}
+
+fn default_preview_cache_dir() -> String {
+    "preview_cache".to_string()
+}
+
+fn default_spot_check_fraction() -> f64 {
+    0.1
 }
 
+/// Fingerprints of the evaluation configuration for each layout config identifier
+pub struct Fingerprints(pub AHashMap<String, String>);
+
 use async_trait::async_trait;
 use rocket::{
     fairing::{Fairing, Info, Kind},
@@ -108,6 +139,40 @@ fn rocket() -> _ {
     let p = Path::new(&options.ngrams).join("3-grams.txt");
     let trigrams = Trigrams::from_file(p.to_str().unwrap())
         .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
+
+    // prepare the ngrams the same way as the wasm frontend, so that the fingerprints agree
+    let ngrams_config = &eval_params.ngrams;
+    let (mut unigrams, mut bigrams, mut trigrams) = ngrams_config
+        .combining_marks
+        .apply(&unigrams, &bigrams, &trigrams);
+    if ngrams_config.increase_common_ngrams.enabled {
+        unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
+        bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
+        trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
+    }
+
+    let ngrams_fingerprint = fingerprint::fingerprint_ngrams(&unigrams, &bigrams, &trigrams);
+    let served_configs = ServedConfigs::load(
+        &options.eval_parameters,
+        &options.layout_configs,
+        &ngrams_fingerprint,
+    )
+    .unwrap_or_else(|e| panic!("Could not load config files: {}", e));
+    let fingerprints = Fingerprints(
+        served_configs
+            .layouts
+            .iter()
+            .map(|(config_id, layout_config)| {
+                let fp = fingerprint::combine(
+                    &layout_config.fingerprint,
+                    &served_configs.evaluation.fingerprint,
+                    &ngrams_fingerprint,
+                );
+                (config_id.to_owned(), fp)
+            })
+            .collect(),
+    );
+
     let ngram_mapper_config = eval_params.ngram_mapper.clone();
     let ngram_mapper =
         OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
@@ -115,13 +180,37 @@ fn rocket() -> _ {
     let evaluator =
         Evaluator::default(Box::new(ngram_mapper)).default_metrics(&eval_params.metrics);
 
+    // the metrics do not depend on the evaluated layout
+    let default_layout_generator = layout_generators
+        .get(&options.default_layout_config)
+        .expect("default layout config");
+    let layout: String = default_layout_generator
+        .permutable_keys()
+        .into_iter()
+        .collect();
+    let metric_set = publish::MetricSet::of(
+        &evaluator.evaluate_layout(&default_layout_generator.generate(&layout).unwrap()),
+    );
+    let verifier = publish::Verifier {
+        evaluator,
+        fingerprints,
+        metric_set,
+    };
+
+    let preview_cache = preview::PreviewCache::new(&options.preview_cache_dir);
+
     rocket
-        .manage(evaluator)
+        .manage(verifier)
+        .manage(eval_params.profiles)
         .manage(layout_generators)
+        .manage(preview_cache)
+        .manage(served_configs)
         .attach(AdHoc::config::<Options>())
         .attach(api::stage())
         .attach(Cors {
             options: options.clone(),
         })
+        .mount("/api/config", config_api::routes())
+        .mount("/", routes![preview::share])
         .mount("/", FileServer::from(&options.static_dir))
 }
@@ -0,0 +1,291 @@
+//! Preview images for shared layout links.
+//!
+//! A preview is a PNG image of the layout plot with the layout and its total cost overlaid. It
+//! is rendered as SVG and rasterized with `resvg`. Rendered previews are cached on disk, keyed by
+//! the layout's id and the fingerprint of its evaluation configuration, so changing the
+//! configuration never serves a stale image. Re-evaluations keep the fingerprint, so the whole
+//! cache is cleared after them.
+
+use super::api::Db;
+use super::Options;
+
+use layout_evaluation::atomic_file;
+
+use anyhow::{anyhow, Result};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{fontdb, Options as SvgOptions, Tree},
+};
+use rocket::{
+    fs::NamedFile,
+    http::{ContentType, Status},
+    response::content::RawHtml,
+    State,
+};
+use rocket_db_pools::{sqlx, Connection};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const LINE_HEIGHT: usize = 22;
+const CHAR_WIDTH: usize = 11;
+const PADDING: usize = 20;
+
+/// Escapes characters that are not allowed in XML/HTML text and attribute values.
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders an SVG image of the given layout plot with the layout name and its total cost as header.
+pub fn render_svg(layout: &str, total_cost: f64, plot: &str) -> String {
+    let lines: Vec<&str> = plot.lines().collect();
+    let header = format!("{}  ({:.2})", layout, total_cost);
+
+    let n_columns = lines
+        .iter()
+        .map(|l| l.chars().count())
+        .chain(std::iter::once(header.chars().count()))
+        .max()
+        .unwrap_or(0);
+    let width = 2 * PADDING + n_columns * CHAR_WIDTH;
+    let height = 2 * PADDING + (lines.len() + 2) * LINE_HEIGHT;
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">
+<rect width="100%" height="100%" fill="#ffffff"/>
+<text x="{p}" y="{y}" font-family="DejaVu Sans Mono, monospace" font-size="18" font-weight="bold" fill="#222222" xml:space="preserve">{header}</text>
+"##,
+        w = width,
+        h = height,
+        p = PADDING,
+        y = PADDING + LINE_HEIGHT,
+        header = escape(&header),
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        svg.push_str(&format!(
+            r##"<text x="{}" y="{}" font-family="DejaVu Sans Mono, monospace" font-size="18" fill="#222222" xml:space="preserve">{}</text>
+"##,
+            PADDING,
+            PADDING + (i + 3) * LINE_HEIGHT,
+            escape(line),
+        ));
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// Rasterizes an SVG image to PNG using the fonts of the given database.
+pub fn render_png(svg: &str, fonts: Arc<fontdb::Database>) -> Result<Vec<u8>> {
+    let options = SvgOptions {
+        fontdb: fonts,
+        ..SvgOptions::default()
+    };
+    let tree = Tree::from_str(svg, &options)?;
+
+    let size = tree.size().to_int_size();
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| anyhow!("Invalid preview size {}x{}", size.width(), size.height()))?;
+    resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+
+    Ok(pixmap.encode_png()?)
+}
+
+/// A directory containing rendered preview images.
+#[derive(Clone, Debug)]
+pub struct PreviewCache {
+    dir: PathBuf,
+    /// Fonts for rendering the previews' text (loaded once from the system)
+    fonts: Arc<fontdb::Database>,
+}
+
+impl PreviewCache {
+    pub fn new(dir: &str) -> Self {
+        let dir = PathBuf::from(dir);
+        if let Err(e) = atomic_file::clean_orphaned_temp_files(&dir) {
+            eprintln!("Could not clean up preview cache: {:?}", e);
+        }
+
+        let mut fonts = fontdb::Database::new();
+        fonts.load_system_fonts();
+        if fonts.is_empty() {
+            eprintln!("No system fonts found, previews will not contain any text");
+        }
+
+        Self {
+            dir,
+            fonts: Arc::new(fonts),
+        }
+    }
+
+    fn path(&self, layout_id: i32, fingerprint: &str) -> PathBuf {
+        self.dir.join(format!("{}_{}.png", layout_id, fingerprint))
+    }
+
+    /// Returns the path of the cached preview, rasterizing the SVG image returned by `render` if
+    /// it is not cached yet.
+    pub fn get_or_render<F>(&self, layout_id: i32, fingerprint: &str, render: F) -> Result<PathBuf>
+    where
+        F: FnOnce() -> String,
+    {
+        let path = self.path(layout_id, fingerprint);
+        if !path.exists() {
+            let png = render_png(&render(), self.fonts.clone())?;
+            fs::create_dir_all(&self.dir)?;
+            atomic_file::write_atomic(&path, png)?;
+        }
+
+        Ok(path)
+    }
+
+    /// Removes all cached previews.
+    pub fn clear(&self) -> std::io::Result<()> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serves a cached preview file as PNG.
+pub async fn serve(path: &Path) -> Result<(ContentType, NamedFile), Status> {
+    let file = NamedFile::open(path)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok((ContentType::PNG, file))
+}
+
+/// Serves the frontend with OpenGraph tags pointing to the preview image of the given layout
+/// (if it has been published).
+#[get("/layout/<layout>?<layout_config>")]
+pub async fn share(
+    mut db: Connection<Db>,
+    layout: &str,
+    layout_config: Option<String>,
+    config: &State<Options>,
+) -> Result<RawHtml<String>, Status> {
+    let layout_config = layout_config.unwrap_or_else(|| config.default_layout_config.to_owned());
+    let index = Path::new(&config.static_dir).join("index.html");
+    let html = fs::read_to_string(index).map_err(|_| Status::InternalServerError)?;
+
+    let layout_id: Option<i32> =
+        sqlx::query_scalar("SELECT id FROM layouts WHERE layout = $1 AND layout_config = $2")
+            .bind(layout)
+            .bind(&layout_config)
+            .fetch_optional(&mut *db)
+            .await
+            .map_err(|e| {
+                eprintln!("Error while fetching layout id from db: {:?}", e);
+                Status::InternalServerError
+            })?;
+    let image = match layout_id {
+        Some(id) => format!(
+            r#"<meta property="og:image" content="{base}/api/layouts/{id}/preview.png" />
+  "#,
+            base = escape(config.public_url.trim_end_matches('/')),
+            id = id,
+        ),
+        None => String::new(),
+    };
+
+    let meta = format!(
+        r#"<base href="/" />
+  <meta property="og:title" content="{layout}" />
+  <meta property="og:description" content="Evaluation of the keyboard layout {layout} ({layout_config})" />
+  {image}<meta property="og:type" content="website" />
+  <meta name="twitter:card" content="summary_large_image" />
+"#,
+        layout = escape(layout),
+        layout_config = escape(&layout_config),
+        image = image,
+    );
+
+    Ok(RawHtml(html.replacen(
+        "</head>",
+        &format!("  {}</head>", meta),
+        1,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    /// Width and height from the header of a PNG image
+    fn png_size(png: &[u8]) -> (u32, u32) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let width = u32::from_be_bytes([png[16], png[17], png[18], png[19]]);
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        (width, height)
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "layouts_webservice_preview_{}_{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn rasterizes_the_svg() {
+        let svg = render_svg("abc<>", 12.345, "line 1\nline 2");
+        let png = render_png(&svg, Arc::new(fontdb::Database::new())).unwrap();
+
+        // the header "abc<>  (12.35)" is the longest line
+        assert_eq!(
+            png_size(&png),
+            (
+                (2 * PADDING + 14 * CHAR_WIDTH) as u32,
+                (2 * PADDING + 4 * LINE_HEIGHT) as u32
+            )
+        );
+    }
+
+    #[test]
+    fn previews_are_keyed_by_layout_id_and_fingerprint() {
+        let dir = cache_dir("keys");
+        let cache = PreviewCache::new(dir.to_str().unwrap());
+        let renders = Cell::new(0);
+        let render = || {
+            renders.set(renders.get() + 1);
+            render_svg("abc", 1.0, "plot")
+        };
+
+        let path = cache.get_or_render(1, "0123", render).unwrap();
+        assert_eq!(fs::read(&path).unwrap()[..4], *b"\x89PNG");
+        assert_eq!(cache.get_or_render(1, "0123", render).unwrap(), path);
+        assert_eq!(renders.get(), 1);
+
+        // other layouts and configurations are rendered separately
+        assert_ne!(cache.get_or_render(2, "0123", render).unwrap(), path);
+        assert_ne!(cache.get_or_render(1, "4567", render).unwrap(), path);
+        assert_eq!(renders.get(), 3);
+
+        // re-evaluations clear the cache
+        cache.clear().unwrap();
+        assert!(!path.exists());
+        cache.get_or_render(1, "0123", render).unwrap();
+        assert_eq!(renders.get(), 4);
+
+        cache.clear().unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+//! The `publish` module decides whether an evaluation result that was computed by a client
+//! (e.g. the wasm frontend) can be stored without evaluating the layout on the server.
+//!
+//! A client's result is never accepted unless the fingerprint of the configuration it was
+//! computed with (layout config, evaluation parameters, ngrams) equals the server's and it
+//! consists of the server's metrics. Even then, a random fraction of the accepted results is
+//! re-evaluated on the server as a spot check. Accepted results are stored as not verified and
+//! are not ranked until the server has evaluated them.
+
+use super::Fingerprints;
+
+use layout_evaluation::{
+    evaluation::Evaluator,
+    results::{EvaluationResult, MetricType, NormalizationType},
+};
+
+use rocket::serde::Deserialize;
+
+/// Relative deviation of the total costs up to which a client's result agrees with the server's
+const TOLERANCE: f64 = 1e-6;
+
+/// Name, weight, and normalization of a metric
+type MetricDefinition = (String, f64, NormalizationType);
+
+/// The metrics of an evaluation result (grouped by their types) along with their weights and
+/// normalizations.
+#[derive(Debug, Clone)]
+pub struct MetricSet(Vec<(MetricType, Vec<MetricDefinition>)>);
+
+impl MetricSet {
+    pub fn of(result: &EvaluationResult) -> Self {
+        Self(
+            result
+                .iter()
+                .map(|mr| {
+                    let metrics = mr
+                        .metric_costs
+                        .iter()
+                        .map(|mc| {
+                            (
+                                mc.core.name.clone(),
+                                mc.core.weight,
+                                mc.core.normalization.clone(),
+                            )
+                        })
+                        .collect();
+                    (mr.metric_type.clone(), metrics)
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether both sets consist of the same metrics with (up to the tolerance) equal weights
+    /// and normalizations.
+    pub fn agrees(&self, other: &Self) -> bool {
+        let close = |a: f64, b: f64| (a - b).abs() <= TOLERANCE * a.abs().max(1.0);
+        let same_normalization = |a: &NormalizationType, b: &NormalizationType| match (a, b) {
+            (NormalizationType::Fixed(a), NormalizationType::Fixed(b))
+            | (NormalizationType::WeightFound(a), NormalizationType::WeightFound(b))
+            | (NormalizationType::WeightAll(a), NormalizationType::WeightAll(b)) => close(*a, *b),
+            _ => false,
+        };
+
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|((t1, m1), (t2, m2))| {
+                    t1 == t2
+                        && m1.len() == m2.len()
+                        && m1
+                            .iter()
+                            .zip(m2.iter())
+                            .all(|((n1, w1, nt1), (n2, w2, nt2))| {
+                                n1 == n2 && close(*w1, *w2) && same_normalization(nt1, nt2)
+                            })
+                })
+    }
+}
+
+/// The server's evaluation state that clients' results are verified against.
+pub struct Verifier {
+    pub evaluator: Evaluator,
+    /// Fingerprints of the evaluation configuration for each layout config identifier
+    pub fingerprints: Fingerprints,
+    /// The metrics of the server's evaluation results
+    pub metric_set: MetricSet,
+}
+
+impl Verifier {
+    /// Fingerprint of the evaluation configuration of a layout config (`None` if the layout
+    /// config is not served).
+    pub fn fingerprint(&self, layout_config: &str) -> Option<&str> {
+        self.fingerprints.0.get(layout_config).map(|fp| fp.as_str())
+    }
+}
+
+/// Evaluation result computed by a client along with the fingerprint of its configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ClientEvaluation {
+    pub details: EvaluationResult,
+    pub fingerprint: String,
+}
+
+/// How to handle a client's evaluation result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// Store the client's result without re-evaluation (it is marked as not verified).
+    Accept,
+    /// The client's result is randomly chosen to be compared with the server's evaluation.
+    SpotCheck,
+    /// The client's result can not be used and the server needs to evaluate the layout.
+    Reject(&'static str),
+}
+
+impl Verification {
+    /// Decide how to handle the client's evaluation of `layout`. `sample` is a uniformly
+    /// distributed random number in [0, 1) that selects the result for a spot check if it is
+    /// smaller than `spot_check_fraction`.
+    pub fn decide(
+        client: &ClientEvaluation,
+        layout: &str,
+        server_fingerprint: &str,
+        server_metrics: &MetricSet,
+        spot_check_fraction: f64,
+        sample: f64,
+    ) -> Self {
+        if client.fingerprint != server_fingerprint {
+            Self::Reject("configuration fingerprint mismatch")
+        } else if !server_metrics.agrees(&MetricSet::of(&client.details)) {
+            Self::Reject("metrics differ from the server's")
+        } else if client.details.layout() != layout {
+            Self::Reject("evaluated layout differs from the published one")
+        } else if client.details.profile().is_some() {
+            Self::Reject("evaluation uses a weight profile")
+        } else if !client.details.skipped_metric_types().is_empty() {
+            Self::Reject("evaluation uses a reduced metric set")
+        } else if !client
+            .details
+            .iter()
+            .flat_map(|mr| mr.metric_costs.iter())
+            .all(|mc| mc.core.cost.is_finite() && mc.core.weight.is_finite())
+            || !client.details.total_cost().is_finite()
+        {
+            // NaN costs would silently be normalized to zero
+            Self::Reject("costs are not finite")
+        } else if sample < spot_check_fraction {
+            Self::SpotCheck
+        } else {
+            Self::Accept
+        }
+    }
+}
+
+/// Check whether the total costs of a client's and the server's evaluation agree.
+pub fn agrees(client: &EvaluationResult, server: &EvaluationResult) -> bool {
+    let (c, s) = (client.total_cost(), server.total_cost());
+    (c - s).abs() <= TOLERANCE * s.abs().max(1.0)
+}
+
+/// The result to store for a newly published `layout` and whether it was computed by the server
+/// (`evaluate`). Only accepted client results are stored instead of the server's.
+pub fn authoritative(
+    client: Option<(&ClientEvaluation, Verification)>,
+    layout: &str,
+    evaluate: impl FnOnce() -> EvaluationResult,
+) -> (EvaluationResult, bool) {
+    match client {
+        Some((client, Verification::Accept)) => {
+            println!("Accepting client evaluation of new layout: {}", layout);
+            (client.details.clone(), false)
+        }
+        Some((client, Verification::SpotCheck)) => {
+            println!("Spot-checking client evaluation of new layout: {}", layout);
+            let evaluation_result = evaluate();
+            if !agrees(&client.details, &evaluation_result) {
+                println!(
+                    "Rejecting client evaluation of {} (disagrees: {:>.2} instead of {:>.2})",
+                    layout,
+                    client.details.total_cost(),
+                    evaluation_result.total_cost()
+                );
+            }
+            (evaluation_result, true)
+        }
+        Some((_, Verification::Reject(reason))) => {
+            println!(
+                "Rejecting client evaluation ({}), evaluating new layout: {}",
+                reason, layout
+            );
+            (evaluate(), true)
+        }
+        None => {
+            println!("Evaluating new layout: {}", layout);
+            (evaluate(), true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use layout_evaluation::results::{MetricResult, MetricResults, MetricType, NormalizationType};
+
+    const LAYOUT: &str = "xvlcwkhgfqyßuiaeosnrtdüöäpzbm,.j";
+    const FINGERPRINT: &str = "0123456789abcdef";
+
+    fn result(layout: &str, cost: f64) -> EvaluationResult {
+        let mut metric_results = MetricResults::new(MetricType::Layout, 1.0, 0.0);
+        metric_results.add_result(MetricResult {
+            name: "Test".to_string(),
+            cost,
+            message: None,
+            weight: 1.0,
+            normalization: NormalizationType::Fixed(1.0),
+        });
+        EvaluationResult::new(layout.to_string(), vec![metric_results])
+    }
+
+    /// The server's metrics
+    fn metrics() -> MetricSet {
+        MetricSet::of(&result(LAYOUT, 0.0))
+    }
+
+    fn client(layout: &str, cost: f64, fingerprint: &str) -> ClientEvaluation {
+        ClientEvaluation {
+            details: result(layout, cost),
+            fingerprint: fingerprint.to_string(),
+        }
+    }
+
+    #[test]
+    fn accept_matching_fingerprint() {
+        let c = client(LAYOUT, 300.0, FINGERPRINT);
+        assert_eq!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Accept
+        );
+        assert_eq!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.0, 0.0),
+            Verification::Accept
+        );
+    }
+
+    #[test]
+    fn reject_untrusted_results() {
+        let c = client(LAYOUT, 300.0, "fedcba9876543210");
+        assert!(matches!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Reject(_)
+        ));
+
+        let c = client("qwertzuiopüasdfghjklöäyxcvbnm,.-", 300.0, FINGERPRINT);
+        assert!(matches!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Reject(_)
+        ));
+
+        for cost in [f64::NAN, f64::INFINITY] {
+            let c = client(LAYOUT, cost, FINGERPRINT);
+            assert!(matches!(
+                Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+                Verification::Reject(_)
+            ));
+        }
+
+        // a single zero-cost metric instead of the server's
+        let mut metric_results = MetricResults::new(MetricType::Layout, 1.0, 0.0);
+        metric_results.add_result(MetricResult {
+            name: "Other".to_string(),
+            cost: 0.0,
+            message: None,
+            weight: 1.0,
+            normalization: NormalizationType::Fixed(1.0),
+        });
+        let mut c = client(LAYOUT, 300.0, FINGERPRINT);
+        c.details = EvaluationResult::new(LAYOUT.to_string(), vec![metric_results]);
+        assert!(matches!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Reject(_)
+        ));
+
+        let mut c = client(LAYOUT, 300.0, FINGERPRINT);
+        c.details = c.details.with_profile(Some("custom".to_string()));
+        assert!(matches!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Reject(_)
+        ));
+
+        let mut c = client(LAYOUT, 300.0, FINGERPRINT);
+        c.details = c
+            .details
+            .with_skipped_metric_types(vec![MetricType::Trigram]);
+        assert!(matches!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.5),
+            Verification::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn spot_check_random_subset() {
+        let c = client(LAYOUT, 300.0, FINGERPRINT);
+        assert_eq!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 0.1, 0.05),
+            Verification::SpotCheck
+        );
+        assert_eq!(
+            Verification::decide(&c, LAYOUT, FINGERPRINT, &metrics(), 1.0, 0.99),
+            Verification::SpotCheck
+        );
+
+        assert!(agrees(&result(LAYOUT, 300.0), &result(LAYOUT, 300.0)));
+        assert!(!agrees(&result(LAYOUT, 290.0), &result(LAYOUT, 300.0)));
+    }
+
+    #[test]
+    fn store_server_result_unless_accepted() {
+        let c = client(LAYOUT, 290.0, FINGERPRINT);
+        let stored = |verification| {
+            let (result, verified) =
+                authoritative(Some((&c, verification)), LAYOUT, || result(LAYOUT, 300.0));
+            (result.total_cost(), verified)
+        };
+
+        assert_eq!(stored(Verification::Accept), (290.0, false));
+        assert_eq!(stored(Verification::SpotCheck), (300.0, true));
+        assert_eq!(stored(Verification::Reject("mismatch")), (300.0, true));
+        assert!(authoritative(None, LAYOUT, || result(LAYOUT, 300.0)).1);
+    }
+}